@@ -21,18 +21,28 @@ fn main() {
     //   - the core count ('single_core' or 'multi_core')
     //
     // Additionally, the following symbols MAY be defined if present:
+    //   - 'bt'
     //   - 'dac'
+    //   - 'dedicated_gpio'
+    //   - 'ds'
+    //   - 'emac'
     //   - 'gdma'
+    //   - 'hmac'
     //   - 'i2c1'
     //   - 'i2s'
     //   - 'mcpwm'
+    //   - 'pcnt'
     //   - 'pdma'
     //   - 'rmt'
+    //   - 'sdio_slave'
     //   - 'spi3'
     //   - 'systimer'
     //   - 'timg0'
     //   - 'timg1'
+    //   - 'twai'
     //   - 'uart2'
+    //   - 'ulp'
+    //   - 'ulp_riscv'
     //   - 'usb_otg'
     //   - 'usb_serial_jtag'
     //
@@ -44,30 +54,47 @@ fn main() {
             "xtensa",
             "mcpwm",
             "multi_core",
+            "bt",
             "dac",
+            "emac",
             "i2c1",
             "i2s",
+            "pcnt",
             "pdma",
             "rmt",
+            "sdio_slave",
             "spi3",
             "timg0",
             "timg1",
             "uart2",
+            "ulp",
         ]
     } else if esp32c2 {
-        vec!["esp32c2", "riscv", "single_core", "gdma", "systimer", "timg0"]
+        vec![
+            "esp32c2",
+            "riscv",
+            "single_core",
+            "bt",
+            "gdma",
+            "systimer",
+            "timg0",
+        ]
     } else if esp32c3 {
         vec![
             "esp32c3",
             "riscv",
             "single_core",
+            "bt",
+            "dedicated_gpio",
+            "ds",
             "gdma",
+            "hmac",
             "i2s",
             "rmt",
-            "spi3",
             "systimer",
             "timg0",
             "timg1",
+            "twai",
             "usb_serial_jtag",
         ]
     } else if esp32s2 {
@@ -78,12 +105,15 @@ fn main() {
             "dac",
             "i2c1",
             "i2s",
+            "pcnt",
             "pdma",
             "rmt",
             "spi3",
             "systimer",
             "timg0",
             "timg1",
+            "ulp",
+            "ulp_riscv",
             "usb_otg",
         ]
     } else if esp32s3 {
@@ -91,16 +121,22 @@ fn main() {
             "esp32s3",
             "xtensa",
             "multi_core",
+            "bt",
+            "ds",
             "gdma",
+            "hmac",
             "i2c1",
             "i2s",
             "mcpwm",
+            "pcnt",
             "rmt",
             "spi3",
             "systimer",
             "timg0",
             "timg1",
             "uart2",
+            "ulp",
+            "ulp_riscv",
             "usb_otg",
             "usb_serial_jtag",
         ]
@@ -108,7 +144,179 @@ fn main() {
         unreachable!(); // We've already confirmed exactly one chip was selected
     };
 
+    let chip = symbols[0];
     for symbol in symbols {
         println!("cargo:rustc-cfg={symbol}");
     }
+
+    generate_pins(chip);
+}
+
+// The `gpio!` and `analog!` tables of a chip can be generated from
+// `pins/<chip>.csv`, the chip's module then includes `$OUT_DIR/pins.rs`
+// instead of invoking the macros itself. The CSV has a header line and one
+// line per pad, lines starting with '#' are comments:
+//
+//   gpio,bank,type,strapping,analog,input_af,output_af
+//   4,0,InputOutputAnalog,,analog,2=FSPIHD,0=USB_JTAG_TMS 2=FSPIHD
+//
+//   - `type` is the pin type of `gpio!` (`InputOutput`, `InputOutputAnalog`,
+//     ...)
+//   - `strapping` and `analog` are either empty or the column name
+//   - `input_af` and `output_af` list the IO MUX functions of the pad as
+//     `<function>=<signal>` separated by spaces, the signals have to exist in
+//     the chip's `InputSignal` and `OutputSignal`
+//
+// Only the `analog!` syntax of the RISC-V chips (a list of pins) can be
+// generated so far.
+fn generate_pins(chip: &str) {
+    use std::{env, fs, path::PathBuf};
+
+    let path = format!("pins/{chip}.csv");
+    let csv = match fs::read_to_string(&path) {
+        Ok(csv) => csv,
+        Err(_) => return,
+    };
+
+    let signals_path = format!("src/gpio/{chip}.rs");
+    let signals = fs::read_to_string(&signals_path).unwrap();
+    let input_signals = enum_variants(&signals, "InputSignal");
+    let output_signals = enum_variants(&signals, "OutputSignal");
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={path}");
+    println!("cargo:rerun-if-changed={signals_path}");
+
+    let mut strapping = Vec::new();
+    let mut analog = Vec::new();
+    let mut pins = Vec::new();
+    let mut last_gpio = None;
+
+    let lines = csv
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .skip(1);
+
+    for (index, line) in lines {
+        let location = format!("{path}:{}", index + 1);
+        let columns: Vec<&str> = line.split(',').map(str::trim).collect();
+        let [gpio, bank, pin_type, strapping_column, analog_column, input_af, output_af] =
+            match columns[..] {
+                [a, b, c, d, e, f, g] => [a, b, c, d, e, f, g],
+                _ => panic!("{location}: expected 7 columns"),
+            };
+
+        let gpio: u8 = gpio
+            .parse()
+            .unwrap_or_else(|_| panic!("{location}: invalid GPIO number `{gpio}`"));
+        if last_gpio.map_or(false, |last| gpio <= last) {
+            panic!("{location}: the GPIO numbers have to rise");
+        }
+        last_gpio = Some(gpio);
+
+        if bank != (gpio / 32).to_string() {
+            panic!("{location}: GPIO{gpio} is in bank {}", gpio / 32);
+        }
+
+        let analog_type = match pin_type {
+            "InputOutput" | "InputOnly" => false,
+            "InputOutputAnalog" | "InputOnlyAnalog" => true,
+            _ => panic!("{location}: unknown pin type `{pin_type}`"),
+        };
+
+        match strapping_column {
+            "" => {}
+            "strapping" => strapping.push(gpio),
+            _ => panic!("{location}: `strapping` has to be empty or `strapping`"),
+        }
+
+        match analog_column {
+            "" if analog_type => panic!("{location}: analog pin type without `analog`"),
+            "" => {}
+            "analog" if !analog_type => panic!("{location}: `analog` without analog pin type"),
+            "analog" => analog.push(gpio),
+            _ => panic!("{location}: `analog` has to be empty or `analog`"),
+        }
+
+        let input_af = alternate_functions(&location, input_af, "InputSignal", &input_signals);
+        let output_af = alternate_functions(&location, output_af, "OutputSignal", &output_signals);
+
+        if input_af.is_empty() && output_af.is_empty() {
+            pins.push(format!("    ({gpio}, {bank}, {pin_type})\n"));
+        } else {
+            pins.push(format!(
+                "    ({gpio}, {bank}, {pin_type} ({input_af}) ({output_af}))\n"
+            ));
+        }
+    }
+
+    let strapping: Vec<String> = strapping.iter().map(u8::to_string).collect();
+
+    let mut out = String::new();
+    out += "crate::gpio::gpio! {\n";
+    out += &format!("    strapping: {};\n\n", strapping.join(" "));
+    out += &pins.concat();
+    out += "}\n";
+
+    if !analog.is_empty() {
+        if !matches!(chip, "esp32c2" | "esp32c3") {
+            panic!("{path}: the `analog!` table of the {chip} can't be generated yet");
+        }
+
+        out += "\ncrate::gpio::analog! {\n";
+        for gpio in analog {
+            out += &format!("    {gpio}\n");
+        }
+        out += "}\n";
+    }
+
+    let out_dir = PathBuf::from(env::var_os("OUT_DIR").unwrap());
+    fs::write(out_dir.join("pins.rs"), out).unwrap();
+}
+
+/// The variants of `pub enum <name>` in `source`
+fn enum_variants(source: &str, name: &str) -> Vec<String> {
+    let start = format!("pub enum {name} {{");
+
+    source
+        .lines()
+        .skip_while(|line| line.trim() != start)
+        .skip(1)
+        .take_while(|line| line.trim() != "}")
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with("//") && !line.starts_with('#'))
+        .map(|line| {
+            line.split(|c: char| c == '=' || c == ',')
+                .next()
+                .unwrap()
+                .trim()
+                .to_string()
+        })
+        .collect()
+}
+
+/// The `gpio!` syntax of the alternate functions in `column`, checked against
+/// the `signals` of the enum `kind`
+fn alternate_functions(location: &str, column: &str, kind: &str, signals: &[String]) -> String {
+    let mut functions = Vec::new();
+
+    for entry in column.split_whitespace() {
+        let (function, signal) = entry
+            .split_once('=')
+            .unwrap_or_else(|| panic!("{location}: expected `<function>=<signal>`, got `{entry}`"));
+
+        match function.parse::<u8>() {
+            Ok(0..=5) => {}
+            _ => panic!("{location}: invalid IO MUX function `{function}`"),
+        }
+
+        if !signals.iter().any(|variant| variant == signal) {
+            panic!("{location}: {kind}::{signal} doesn't exist");
+        }
+
+        functions.push(format!("{function} => {signal}"));
+    }
+
+    functions.join(" ")
 }
@@ -0,0 +1,327 @@
+//! Streaming ADC samples asynchronously
+//!
+//! [AdcStream] has a GDMA channel write the samples of an [AdcContinuous]
+//! into a ring buffer, [AdcStream::next_samples] returns a future which
+//! completes as soon as there are samples and otherwise waits for the next
+//! chunk of `samples_per_chunk`. The futures are woken from the interrupt of
+//! the DMA channel, which has to be enabled and call
+//! [dma::asynch::handle_interrupt](crate::dma::asynch::handle_interrupt):
+//!
+//! ```no_run
+//! let adc = AdcContinuous::new(adc1, 8u32.kHz(), &clocks);
+//! let mut stream = AdcStream::new(adc, channel, buffer, 256).unwrap();
+//!
+//! interrupt::enable(pac::Interrupt::DMA_CH0, interrupt::Priority::Priority1).unwrap();
+//!
+//! #[interrupt]
+//! fn DMA_CH0() {
+//!     dma::asynch::handle_interrupt(0);
+//! }
+//!
+//! let mut samples = [0u16; 64];
+//! let count = stream.next_samples(&mut samples).await?;
+//! ```
+//!
+//! The samples are raw 12-bit conversion results, one per enabled channel in
+//! turn, in the order of their channel numbers.
+//!
+//! ## Overruns
+//!
+//! When the samples aren't read fast enough, the DMA reaches the chunk read
+//! last and stops instead of overwriting it. The next call of
+//! [AdcStream::next_samples] returns [StreamError::Overrun] and restarts the
+//! stream with an empty buffer, the samples in between are lost. The chunk
+//! which is being read is already handed back to the DMA, the buffer has to
+//! hold at least one chunk more than the reader falls behind.
+//!
+//! ## Cancellation
+//!
+//! Dropping a future only stops waiting, samples which arrived in the
+//! meantime are returned by the next call. A future never returns samples
+//! it didn't also consume.
+//!
+//! ADC2 isn't reliable in DMA mode on the ESP32-C3, only ADC1 should be
+//! streamed.
+
+use core::{
+    future::Future,
+    mem,
+    pin::Pin,
+    ptr,
+    task::{Context, Poll},
+};
+
+use embedded_dma::WriteBuffer;
+
+use super::{AdcContinuous, RegisterAccess};
+use crate::{
+    dma::{
+        asynch::{listen_rx, register_rx, unlisten_rx},
+        private::{AdcPeripheral, Rx, Tx},
+        Channel,
+        DmaError,
+        DmaPeripheral,
+    },
+    pac::APB_SARADC,
+};
+
+/// Bytes per sample written by the DMA
+const SAMPLE_SIZE: usize = 4;
+/// Largest chunk a descriptor covers, in samples
+const MAX_SAMPLES_PER_CHUNK: usize = 4092 / SAMPLE_SIZE;
+
+// APB_SARADC_DMA_CONF_REG
+const EOF_NUM_MASK: u32 = 0xffff;
+const RESET_FSM: u32 = 1 << 30;
+const ADC_TRANS: u32 = 1 << 31;
+
+/// Error returned by [AdcStream::next_samples]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamError {
+    /// The buffer ran full and samples were lost, the stream was restarted
+    Overrun,
+}
+
+/// Samples of an [AdcContinuous] written into a ring buffer by a GDMA
+/// channel, see the [module documentation](self)
+pub struct AdcStream<ADCI, TX, RX, P, BUFFER>
+where
+    ADCI: RegisterAccess,
+    TX: Tx,
+    RX: Rx,
+    P: AdcPeripheral,
+{
+    adc: AdcContinuous<ADCI>,
+    channel: Channel<TX, RX, P>,
+    buffer: BUFFER,
+    samples_per_chunk: usize,
+}
+
+impl<ADCI, TX, RX, P, BUFFER> AdcStream<ADCI, TX, RX, P, BUFFER>
+where
+    ADCI: RegisterAccess,
+    TX: Tx,
+    RX: Rx,
+    P: AdcPeripheral,
+    BUFFER: WriteBuffer<Word = u8>,
+{
+    /// Start sampling into `buffer`, the DMA interrupt is raised after every
+    /// `samples_per_chunk` samples
+    ///
+    /// A sample takes 4 bytes, the buffer has to hold at least two chunks
+    /// and the RX side of the channel needs a descriptor per chunk. A chunk
+    /// holds 1 to 1023 samples, other counts return
+    /// [DmaError::BufferTooSmall] or [DmaError::BufferTooLarge].
+    pub fn new(
+        adc: AdcContinuous<ADCI>,
+        channel: Channel<TX, RX, P>,
+        buffer: BUFFER,
+        samples_per_chunk: usize,
+    ) -> Result<Self, DmaError> {
+        if samples_per_chunk == 0 {
+            return Err(DmaError::BufferTooSmall);
+        }
+        if samples_per_chunk > MAX_SAMPLES_PER_CHUNK {
+            return Err(DmaError::BufferTooLarge);
+        }
+
+        let mut stream = Self {
+            adc,
+            channel,
+            buffer,
+            samples_per_chunk,
+        };
+
+        stream
+            .channel
+            .rx
+            .set_chunk_size(samples_per_chunk * SAMPLE_SIZE);
+        stream.channel.rx.set_check_owner(true);
+        stream.start()?;
+
+        Ok(stream)
+    }
+
+    /// Wait for samples and copy up to `samples.len()` of them
+    ///
+    /// Returns the number of samples copied, at least one.
+    pub fn next_samples<'a>(
+        &'a mut self,
+        samples: &'a mut [u16],
+    ) -> NextSamples<'a, ADCI, TX, RX, P, BUFFER> {
+        NextSamples {
+            stream: self,
+            samples,
+            waiting: false,
+        }
+    }
+
+    /// Stop sampling and return the parts
+    pub fn free(mut self) -> (AdcContinuous<ADCI>, Channel<TX, RX, P>, BUFFER) {
+        self.stop();
+        self.channel.rx.set_check_owner(false);
+
+        // NOTE(unsafe) `self` is forgotten, the parts are moved out once
+        unsafe {
+            let adc = ptr::read(&self.adc);
+            let channel = ptr::read(&self.channel);
+            let buffer = ptr::read(&self.buffer);
+            mem::forget(self);
+
+            (adc, channel, buffer)
+        }
+    }
+
+    fn start(&mut self) -> Result<(), DmaError> {
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+        sar_adc.dma_conf.modify(|r, w| unsafe {
+            w.bits(r.bits() & !EOF_NUM_MASK | RESET_FSM | self.samples_per_chunk as u32)
+        });
+        sar_adc
+            .dma_conf
+            .modify(|r, w| unsafe { w.bits(r.bits() & !RESET_FSM | ADC_TRANS) });
+
+        let (ptr, len) = unsafe { self.buffer.write_buffer() };
+        self.channel
+            .rx
+            .prepare_transfer(true, DmaPeripheral::Adc, ptr, len)?;
+
+        self.adc.start();
+        Ok(())
+    }
+
+    /// Copy the available samples, restart after an overrun
+    fn read(&mut self, samples: &mut [u16]) -> Result<usize, StreamError> {
+        if self.channel.rx.has_error() {
+            self.stop();
+            // the buffer and descriptors were accepted before
+            self.start().ok();
+
+            return Err(StreamError::Overrun);
+        }
+
+        // an end of chunk after this raises the interrupt once listened
+        self.channel.rx.clear_done();
+
+        let mut count = 0;
+        let mut raw = [0u8; 16 * SAMPLE_SIZE];
+        while count < samples.len() {
+            let available = self.channel.rx.available();
+            if available == 0 {
+                break;
+            }
+
+            let len = usize::min(available, (samples.len() - count) * SAMPLE_SIZE);
+            let len = self
+                .channel
+                .rx
+                .pop_partial(&mut raw[..usize::min(len, raw.len())]);
+            for word in raw[..len].chunks_exact(SAMPLE_SIZE) {
+                // data in bits 0..12, followed by the channel and the unit
+                samples[count] = u16::from_le_bytes([word[0], word[1]]) & 0xfff;
+                count += 1;
+            }
+        }
+
+        Ok(count)
+    }
+}
+
+impl<ADCI, TX, RX, P, BUFFER> AdcStream<ADCI, TX, RX, P, BUFFER>
+where
+    ADCI: RegisterAccess,
+    TX: Tx,
+    RX: Rx,
+    P: AdcPeripheral,
+{
+    fn stop(&mut self) {
+        self.adc.stop();
+        self.channel.rx.stop();
+
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+        sar_adc
+            .dma_conf
+            .modify(|r, w| unsafe { w.bits(r.bits() & !ADC_TRANS) });
+    }
+}
+
+impl<ADCI, TX, RX, P, BUFFER> Drop for AdcStream<ADCI, TX, RX, P, BUFFER>
+where
+    ADCI: RegisterAccess,
+    TX: Tx,
+    RX: Rx,
+    P: AdcPeripheral,
+{
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Future returned by [AdcStream::next_samples]
+pub struct NextSamples<'a, ADCI, TX, RX, P, BUFFER>
+where
+    ADCI: RegisterAccess,
+    TX: Tx,
+    RX: Rx,
+    P: AdcPeripheral,
+    BUFFER: WriteBuffer<Word = u8>,
+{
+    stream: &'a mut AdcStream<ADCI, TX, RX, P, BUFFER>,
+    samples: &'a mut [u16],
+    waiting: bool,
+}
+
+impl<'a, ADCI, TX, RX, P, BUFFER> Future for NextSamples<'a, ADCI, TX, RX, P, BUFFER>
+where
+    ADCI: RegisterAccess,
+    TX: Tx,
+    RX: Rx,
+    P: AdcPeripheral,
+    BUFFER: WriteBuffer<Word = u8>,
+{
+    type Output = Result<usize, StreamError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = &mut *self;
+        if this.samples.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        register_rx(&this.stream.channel.rx, cx.waker());
+
+        match this.stream.read(this.samples) {
+            Ok(0) => {
+                this.waiting = true;
+                listen_rx(&mut this.stream.channel.rx);
+                Poll::Pending
+            }
+            result => {
+                if this.waiting {
+                    this.waiting = false;
+                    unlisten_rx(&mut this.stream.channel.rx);
+                }
+                Poll::Ready(result)
+            }
+        }
+    }
+}
+
+impl<'a, ADCI, TX, RX, P, BUFFER> Drop for NextSamples<'a, ADCI, TX, RX, P, BUFFER>
+where
+    ADCI: RegisterAccess,
+    TX: Tx,
+    RX: Rx,
+    P: AdcPeripheral,
+    BUFFER: WriteBuffer<Word = u8>,
+{
+    fn drop(&mut self) {
+        if self.waiting {
+            unlisten_rx(&mut self.stream.channel.rx);
+        }
+    }
+}
+
+const _: () = {
+    assert!(MAX_SAMPLES_PER_CHUNK == 1023);
+    assert!(MAX_SAMPLES_PER_CHUNK as u32 <= EOF_NUM_MASK);
+};
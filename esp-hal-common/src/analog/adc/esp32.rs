@@ -2,8 +2,9 @@ use core::marker::PhantomData;
 
 use embedded_hal::adc::{Channel, OneShot};
 
+pub use crate::analog::oversample::Oversample;
 use crate::{
-    analog::{ADC1, ADC2},
+    analog::{oversample::Oversampling, ADC1, ADC2},
     pac::{RTCIO, SENS},
 };
 
@@ -24,6 +25,15 @@ pub enum Attenuation {
     Attenuation11dB  = 0b11,
 }
 
+/// ADC error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdcError {
+    /// ADC2 is used by WiFi, see [Adc2Arbiter]
+    Adc2InUse,
+}
+
+pub use crate::analog::adc2_arbiter::{set_adc2_wifi_priority, Adc2Arbiter};
+
 pub struct AdcPin<PIN, ADCI> {
     pub pin: PIN,
     _phantom: PhantomData<ADCI>,
@@ -40,6 +50,7 @@ impl<PIN: Channel<ADCI, ID = u8>, ADCI> Channel<ADCI> for AdcPin<PIN, ADCI> {
 pub struct AdcConfig<ADCI> {
     pub resolution: Resolution,
     pub attenuations: [Option<Attenuation>; 10],
+    oversampling: [Oversampling; 10],
     _phantom: PhantomData<ADCI>,
 }
 
@@ -57,12 +68,42 @@ where
         attenuation: Attenuation,
     ) -> AdcPin<PIN, ADCI> {
         self.attenuations[PIN::channel() as usize] = Some(attenuation);
+        self.oversampling[PIN::channel() as usize] = Oversampling::NONE;
 
         AdcPin {
             pin,
             _phantom: PhantomData::default(),
         }
     }
+
+    /// Like [AdcConfig::enable_pin], each read of the pin returns the
+    /// average of `oversample` conversions
+    ///
+    /// See the [Oversample] documentation for the conversion time and
+    /// saturation.
+    pub fn enable_pin_with_oversampling<PIN: Channel<ADCI, ID = u8>>(
+        &mut self,
+        pin: PIN,
+        attenuation: Attenuation,
+        oversample: Oversample,
+    ) -> AdcPin<PIN, ADCI> {
+        let pin = self.enable_pin(pin, attenuation);
+        self.oversampling[PIN::channel() as usize] = Oversampling::average(oversample);
+        pin
+    }
+
+    /// Like [AdcConfig::enable_pin_with_oversampling], but the reads return
+    /// [Oversample::extra_bits] more bits than the resolution of the ADC
+    pub fn enable_pin_with_decimation<PIN: Channel<ADCI, ID = u8>>(
+        &mut self,
+        pin: PIN,
+        attenuation: Attenuation,
+        oversample: Oversample,
+    ) -> AdcPin<PIN, ADCI> {
+        let pin = self.enable_pin(pin, attenuation);
+        self.oversampling[PIN::channel() as usize] = Oversampling::decimate(oversample);
+        pin
+    }
 }
 
 impl<ADCI> Default for AdcConfig<ADCI> {
@@ -70,6 +111,7 @@ impl<ADCI> Default for AdcConfig<ADCI> {
         AdcConfig {
             resolution: Resolution::Resolution12Bit,
             attenuations: [None; 10],
+            oversampling: [Oversampling::NONE; 10],
             _phantom: PhantomData::default(),
         }
     }
@@ -97,6 +139,16 @@ pub trait RegisterAccess {
     fn read_done_sar() -> bool;
 
     fn read_data_sar() -> u16;
+
+    /// Take the ADC2 lock for a conversion, `None` if not needed
+    fn arbitrate() -> Result<Option<Adc2Arbiter>, AdcError> {
+        Ok(None)
+    }
+
+    /// Whether a conversion result is valid
+    fn is_data_valid(_data: u16) -> bool {
+        true
+    }
 }
 
 #[doc(hidden)]
@@ -254,12 +306,21 @@ impl RegisterAccess for ADC2 {
         let sensors = unsafe { &*SENS::ptr() };
         sensors.sar_meas_start2.read().meas2_data_sar().bits() as u16
     }
+
+    fn arbitrate() -> Result<Option<Adc2Arbiter>, AdcError> {
+        Adc2Arbiter::try_lock().map(Some)
+    }
 }
 
 pub struct ADC<ADC> {
     adc: PhantomData<ADC>,
     attenuations: [Option<Attenuation>; 10],
+    oversampling: [Oversampling; 10],
     active_channel: Option<u8>,
+    /// Sum and number of the samples of the read in progress
+    sum: u32,
+    samples: u16,
+    adc2_lock: Option<Adc2Arbiter>,
 }
 
 impl<ADCI> ADC<ADCI>
@@ -326,7 +387,11 @@ where
         let adc = ADC {
             adc: PhantomData,
             attenuations: config.attenuations,
+            oversampling: config.oversampling,
             active_channel: None,
+            sum: 0,
+            samples: 0,
+            adc2_lock: None,
         };
 
         Ok(adc)
@@ -353,7 +418,7 @@ where
     PIN: Channel<ADCI, ID = u8>,
     ADCI: RegisterAccess,
 {
-    type Error = ();
+    type Error = AdcError;
 
     fn read(&mut self, _pin: &mut AdcPin<PIN, ADCI>) -> nb::Result<WORD, Self::Error> {
         if self.attenuations[AdcPin::<PIN, ADCI>::channel() as usize] == None {
@@ -372,6 +437,7 @@ where
             }
         } else {
             // If no conversions are in progress, start a new one for given channel
+            self.adc2_lock = ADCI::arbitrate()?;
             self.active_channel = Some(AdcPin::<PIN, ADCI>::channel());
 
             ADCI::set_en_pad(AdcPin::<PIN, ADCI>::channel() as u8);
@@ -389,10 +455,25 @@ where
         // Get converted value
         let converted_value = ADCI::read_data_sar();
 
+        // Take the next sample of an oversampled read
+        let oversampling = self.oversampling[AdcPin::<PIN, ADCI>::channel() as usize];
+        self.sum += converted_value as u32;
+        self.samples += 1;
+        if self.samples < oversampling.samples() {
+            ADCI::clear_start_sar();
+            ADCI::set_start_sar();
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let value = oversampling.result(self.sum);
+        self.sum = 0;
+        self.samples = 0;
+
         // Mark that no conversions are currently in progress
         self.active_channel = None;
+        self.adc2_lock = None;
 
-        Ok(converted_value.into())
+        Ok(value.into())
     }
 }
 
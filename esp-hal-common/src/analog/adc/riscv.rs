@@ -1,15 +1,33 @@
+#[cfg(all(esp32c3, feature = "async"))]
+pub mod asynch;
+
 use core::marker::PhantomData;
 
 use embedded_hal::adc::{Channel, OneShot};
+use fugit::HertzU32;
 
+pub use crate::analog::oversample::Oversample;
 #[cfg(esp32c3)]
 use crate::analog::ADC2;
 use crate::{
-    analog::ADC1,
+    analog::{oversample::Oversampling, ADC1},
+    clock::Clocks,
     pac::APB_SARADC,
     system::{Peripheral, PeripheralClockControl},
 };
 
+/// ADC error
+///
+/// The same error type as on the Xtensa chips, so code reading the ADC
+/// through [OneShot] is portable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdcError {
+    /// ADC2 is used by WiFi
+    ///
+    /// Not returned on the ESP32-C2 and ESP32-C3, which don't arbitrate ADC2.
+    Adc2InUse,
+}
+
 /// The sampling/readout resolution of the ADC
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum Resolution {
@@ -41,6 +59,7 @@ impl<PIN: Channel<ADCI, ID = u8>, ADCI> Channel<ADCI> for AdcPin<PIN, ADCI> {
 pub struct AdcConfig<ADCI> {
     pub resolution: Resolution,
     pub attenuations: [Option<Attenuation>; 5],
+    oversampling: [Oversampling; 5],
     _phantom: PhantomData<ADCI>,
 }
 
@@ -58,12 +77,42 @@ where
         attenuation: Attenuation,
     ) -> AdcPin<PIN, ADCI> {
         self.attenuations[PIN::channel() as usize] = Some(attenuation);
+        self.oversampling[PIN::channel() as usize] = Oversampling::NONE;
 
         AdcPin {
             pin,
             _phantom: PhantomData::default(),
         }
     }
+
+    /// Like [AdcConfig::enable_pin], each read of the pin returns the
+    /// average of `oversample` conversions
+    ///
+    /// See the [Oversample] documentation for the conversion time and
+    /// saturation.
+    pub fn enable_pin_with_oversampling<PIN: Channel<ADCI, ID = u8>>(
+        &mut self,
+        pin: PIN,
+        attenuation: Attenuation,
+        oversample: Oversample,
+    ) -> AdcPin<PIN, ADCI> {
+        let pin = self.enable_pin(pin, attenuation);
+        self.oversampling[PIN::channel() as usize] = Oversampling::average(oversample);
+        pin
+    }
+
+    /// Like [AdcConfig::enable_pin_with_oversampling], but the reads return
+    /// [Oversample::extra_bits] more bits than the resolution of the ADC
+    pub fn enable_pin_with_decimation<PIN: Channel<ADCI, ID = u8>>(
+        &mut self,
+        pin: PIN,
+        attenuation: Attenuation,
+        oversample: Oversample,
+    ) -> AdcPin<PIN, ADCI> {
+        let pin = self.enable_pin(pin, attenuation);
+        self.oversampling[PIN::channel() as usize] = Oversampling::decimate(oversample);
+        pin
+    }
 }
 
 impl<ADCI> Default for AdcConfig<ADCI> {
@@ -71,6 +120,7 @@ impl<ADCI> Default for AdcConfig<ADCI> {
         AdcConfig {
             resolution: Resolution::Resolution12Bit,
             attenuations: [None; 5],
+            oversampling: [Oversampling::NONE; 5],
             _phantom: PhantomData::default(),
         }
     }
@@ -78,6 +128,9 @@ impl<ADCI> Default for AdcConfig<ADCI> {
 
 #[doc(hidden)]
 pub trait RegisterAccess {
+    /// Index of the ADC unit as used by the digital controller
+    const UNIT: u8;
+
     fn start_onetime_sample(channel: u8, attenuation: u8);
 
     fn is_done() -> bool;
@@ -88,6 +141,8 @@ pub trait RegisterAccess {
 }
 
 impl RegisterAccess for ADC1 {
+    const UNIT: u8 = 0;
+
     fn start_onetime_sample(channel: u8, attenuation: u8) {
         let sar_adc = unsafe { &*APB_SARADC::PTR };
 
@@ -130,6 +185,8 @@ impl RegisterAccess for ADC1 {
 
 #[cfg(esp32c3)]
 impl RegisterAccess for ADC2 {
+    const UNIT: u8 = 1;
+
     fn start_onetime_sample(channel: u8, attenuation: u8) {
         let sar_adc = unsafe { &*APB_SARADC::PTR };
 
@@ -173,7 +230,11 @@ impl RegisterAccess for ADC2 {
 pub struct ADC<ADC> {
     adc: PhantomData<ADC>,
     attenuations: [Option<Attenuation>; 5],
+    oversampling: [Oversampling; 5],
     active_channel: Option<u8>,
+    /// Sum and number of the samples of the read in progress
+    sum: u32,
+    samples: u16,
 }
 
 impl<ADCI> ADC<ADCI>
@@ -201,7 +262,10 @@ where
         let adc = ADC {
             adc: PhantomData,
             attenuations: config.attenuations,
+            oversampling: config.oversampling,
             active_channel: None,
+            sum: 0,
+            samples: 0,
         };
 
         Ok(adc)
@@ -214,7 +278,7 @@ where
     PIN: Channel<ADCI, ID = u8>,
     ADCI: RegisterAccess,
 {
-    type Error = ();
+    type Error = AdcError;
 
     fn read(&mut self, _pin: &mut AdcPin<PIN, ADCI>) -> nb::Result<WORD, Self::Error> {
         if self.attenuations[AdcPin::<PIN, ADCI>::channel() as usize] == None {
@@ -260,10 +324,342 @@ where
         // We reset ``onetime_start`` in `reset` and assume enough time has passed until
         // the next sample is requested.
 
+        // Take the next sample of an oversampled read
+        let channel = AdcPin::<PIN, ADCI>::channel();
+        let oversampling = self.oversampling[channel as usize];
+        self.sum += converted_value as u32;
+        self.samples += 1;
+        if self.samples < oversampling.samples() {
+            let attenuation = self.attenuations[channel as usize].unwrap() as u8;
+            ADCI::start_onetime_sample(channel, attenuation);
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let value = oversampling.result(self.sum);
+        self.sum = 0;
+        self.samples = 0;
+
         // Mark that no conversions are currently in progress
         self.active_channel = None;
 
-        Ok(converted_value.into())
+        Ok(value.into())
+    }
+}
+
+// Digital controller clock: APB / (CLKM_DIV_NUM + 1), 5 MHz at 80 MHz APB
+const CLKM_DIV_NUM: u8 = 15;
+// The SAR ADC is clocked with the digital controller clock divided by this
+const SAR_CLK_DIV: u8 = 2;
+// Clock source of the digital controller, CLK_SEL
+const CLK_SEL_APB: u8 = 2;
+
+/// ADC sampling continuously, paced by the timer of the digital controller
+///
+/// All channels enabled in the [`AdcConfig`] the ADC was created with are
+/// sampled in turn. The samples are consumed by the digital controller, e.g.
+/// by an [`AdcMonitor`], or streamed to memory by an `asynch::AdcStream` on
+/// the ESP32-C3.
+pub struct AdcContinuous<ADCI> {
+    adc: ADC<ADCI>,
+}
+
+impl<ADCI> AdcContinuous<ADCI>
+where
+    ADCI: RegisterAccess,
+{
+    /// Configure continuous sampling with `sample_rate` conversions per
+    /// second, shared by all enabled channels
+    ///
+    /// The supported sample rates range from about 1.2 kHz to 83 kHz with an
+    /// 80 MHz APB clock.
+    pub fn new(adc: ADC<ADCI>, sample_rate: HertzU32, clocks: &Clocks) -> Self {
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+
+        // Digital controller clock from APB
+        sar_adc.clkm_conf.write(|w| unsafe {
+            w.clkm_div_num()
+                .bits(CLKM_DIV_NUM)
+                .clk_en()
+                .set_bit()
+                .clk_sel()
+                .bits(CLK_SEL_APB)
+        });
+
+        // Build the pattern table from the enabled channels, 4 entries of 6 bits
+        // per register, starting at the most significant bits
+        let mut pattern = [0u32; 2];
+        let mut len = 0;
+        for (channel, attenuation) in adc.attenuations.iter().enumerate() {
+            if let Some(attenuation) = attenuation {
+                let entry = (ADCI::UNIT as u32) << 5 | (channel as u32) << 2 | *attenuation as u32;
+                pattern[len / 4] |= entry << (18 - (len % 4) * 6);
+                len += 1;
+            }
+        }
+        assert!(len > 0, "No channel is enabled");
+
+        sar_adc
+            .sar_patt_tab1
+            .write(|w| unsafe { w.saradc_sar_patt_tab1().bits(pattern[0]) });
+        sar_adc
+            .sar_patt_tab2
+            .write(|w| unsafe { w.saradc_sar_patt_tab2().bits(pattern[1]) });
+
+        sar_adc.ctrl.modify(|_, w| unsafe {
+            w.saradc_start_force()
+                .clear_bit()
+                .saradc_sar_clk_div()
+                .bits(SAR_CLK_DIV)
+                .saradc_sar_patt_len()
+                .bits(len as u8 - 1)
+                .saradc_sar_patt_p_clear()
+                .set_bit()
+        });
+        sar_adc
+            .ctrl
+            .modify(|_, w| w.saradc_sar_patt_p_clear().clear_bit());
+
+        let clkm = clocks.apb_clock.to_Hz() / (CLKM_DIV_NUM as u32 + 1);
+        let target = (clkm / sample_rate.to_Hz()).clamp(60, 0xfff);
+        sar_adc.ctrl2.modify(|_, w| unsafe {
+            w.saradc_meas_num_limit()
+                .clear_bit()
+                .saradc_timer_target()
+                .bits(target as u16)
+        });
+
+        Self { adc }
+    }
+
+    /// Start sampling
+    pub fn start(&mut self) {
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+        sar_adc.ctrl2.modify(|_, w| w.saradc_timer_en().set_bit());
+    }
+
+    /// Stop sampling
+    pub fn stop(&mut self) {
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+        sar_adc.ctrl2.modify(|_, w| w.saradc_timer_en().clear_bit());
+    }
+
+    /// Stop sampling and return the ADC for one-shot conversions
+    pub fn free(mut self) -> ADC<ADCI> {
+        self.stop();
+
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+        sar_adc.ctrl.modify(|_, w| w.saradc_start_force().set_bit());
+
+        self.adc
+    }
+}
+
+/// Condition an [`AdcMonitor`] reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThresholdMode {
+    /// A sample is above the value
+    Above(u16),
+    /// A sample is below the value
+    Below(u16),
+}
+
+/// Threshold crossing reported by an [`AdcMonitor`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Crossing {
+    /// The condition of the [`ThresholdMode`] became true
+    Triggered,
+    /// The signal went back past the hysteresis threshold, the monitor is
+    /// armed again
+    Rearmed,
+}
+
+/// Threshold monitor of the ADC digital controller
+///
+/// Compares every sample of one channel taken by [`AdcContinuous`] against a
+/// threshold in hardware and raises the `APB_ADC` interrupt when it is
+/// crossed, without involving the CPU in the sampling. Thresholds are raw
+/// 12-bit conversion results.
+///
+/// The hardware has no hysteresis: without it, the interrupt keeps firing for
+/// every sample beyond the threshold. With [`AdcMonitor::with_hysteresis`],
+/// the monitor disarms after triggering and only triggers again after the
+/// signal went back past the second threshold.
+pub struct AdcMonitor<ADCI> {
+    adc: AdcContinuous<ADCI>,
+    mode: ThresholdMode,
+    rearm: Option<u16>,
+    armed: bool,
+    listening: bool,
+}
+
+impl<ADCI> AdcMonitor<ADCI>
+where
+    ADCI: RegisterAccess,
+{
+    /// Monitor the channel of `pin`, which must be enabled in the ADC
+    /// configuration
+    pub fn new<PIN>(adc: AdcContinuous<ADCI>, _pin: &AdcPin<PIN, ADCI>, mode: ThresholdMode) -> Self
+    where
+        PIN: Channel<ADCI, ID = u8>,
+    {
+        let channel = AdcPin::<PIN, ADCI>::channel();
+        if adc.adc.attenuations[channel as usize].is_none() {
+            panic!("Channel {} is not configured for sampling!", channel);
+        }
+
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+        // the unit is the upper bit of the channel field
+        sar_adc
+            .thres0_ctrl
+            .modify(|_, w| unsafe { w.saradc_thres0_channel().bits(ADCI::UNIT << 3 | channel) });
+        sar_adc
+            .thres_ctrl
+            .modify(|_, w| w.saradc_thres0_en().set_bit());
+
+        let mut monitor = Self {
+            adc,
+            mode,
+            rearm: None,
+            armed: true,
+            listening: false,
+        };
+        monitor.arm();
+
+        monitor
+    }
+
+    /// Only trigger again after the signal went back past `rearm`
+    ///
+    /// For [`ThresholdMode::Above`] `rearm` has to be below the threshold,
+    /// for [`ThresholdMode::Below`] above it.
+    pub fn with_hysteresis(mut self, rearm: u16) -> Self {
+        self.rearm = Some(rearm);
+        self
+    }
+
+    /// Enable the interrupt
+    pub fn listen(&mut self) {
+        self.listening = true;
+        self.update_interrupt();
+    }
+
+    /// Disable the interrupt
+    pub fn unlisten(&mut self) {
+        self.listening = false;
+        self.update_interrupt();
+    }
+
+    /// Returns `true` if the monitor's interrupt is pending
+    pub fn is_interrupt_set(&self) -> bool {
+        let int_st = unsafe { &*APB_SARADC::PTR }.int_st.read();
+        int_st.apb_saradc_thres0_high_int_st().bit_is_set()
+            || int_st.apb_saradc_thres0_low_int_st().bit_is_set()
+    }
+
+    /// Clear the pending interrupt and report what caused it
+    ///
+    /// With hysteresis, this also switches between waiting for the trigger
+    /// and the re-arm threshold, so it has to be called from the interrupt
+    /// handler.
+    pub fn clear_interrupt(&mut self) -> Option<Crossing> {
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+        let int_st = sar_adc.int_st.read();
+        let high = int_st.apb_saradc_thres0_high_int_st().bit_is_set();
+        let low = int_st.apb_saradc_thres0_low_int_st().bit_is_set();
+        sar_adc.int_clr.write(|w| {
+            w.apb_saradc_thres0_high_int_clr()
+                .bit(high)
+                .apb_saradc_thres0_low_int_clr()
+                .bit(low)
+        });
+
+        if !high && !low {
+            return None;
+        }
+
+        let crossing = if self.armed {
+            Crossing::Triggered
+        } else {
+            Crossing::Rearmed
+        };
+
+        if self.rearm.is_some() {
+            self.armed = !self.armed;
+            self.arm();
+        }
+
+        Some(crossing)
+    }
+
+    /// Start sampling
+    pub fn start(&mut self) {
+        self.adc.start();
+    }
+
+    /// Stop sampling
+    pub fn stop(&mut self) {
+        self.adc.stop();
+    }
+
+    /// Disable the monitor and return the continuously sampling ADC
+    pub fn free(mut self) -> AdcContinuous<ADCI> {
+        self.unlisten();
+
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+        sar_adc
+            .thres_ctrl
+            .modify(|_, w| w.saradc_thres0_en().clear_bit());
+
+        self.adc
+    }
+
+    // The monitor compares against a high and a low threshold at the same
+    // time, only the interrupt of the one currently waited for is enabled
+    fn arm(&mut self) {
+        let (high, low) = match (self.mode, self.armed, self.rearm) {
+            (ThresholdMode::Above(value), true, _) => (value, 0),
+            (ThresholdMode::Above(_), false, Some(rearm)) => (0xfff, rearm),
+            (ThresholdMode::Below(value), true, _) => (0xfff, value),
+            (ThresholdMode::Below(_), false, Some(rearm)) => (rearm, 0),
+            (_, false, None) => unreachable!(),
+        };
+
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+        sar_adc.thres0_ctrl.modify(|_, w| unsafe {
+            w.saradc_thres0_high()
+                .bits(high & 0xfff)
+                .saradc_thres0_low()
+                .bits(low & 0xfff)
+        });
+
+        self.update_interrupt();
+    }
+
+    /// Whether the high threshold is waited for, otherwise the low one
+    fn awaits_high(&self) -> bool {
+        matches!(
+            (self.mode, self.armed),
+            (ThresholdMode::Above(_), true) | (ThresholdMode::Below(_), false)
+        )
+    }
+
+    fn update_interrupt(&mut self) {
+        let high = self.listening && self.awaits_high();
+        let low = self.listening && !self.awaits_high();
+
+        let sar_adc = unsafe { &*APB_SARADC::PTR };
+        sar_adc.int_clr.write(|w| {
+            w.apb_saradc_thres0_high_int_clr()
+                .set_bit()
+                .apb_saradc_thres0_low_int_clr()
+                .set_bit()
+        });
+        sar_adc.int_ena.modify(|_, w| {
+            w.apb_saradc_thres0_high_int_ena()
+                .bit(high)
+                .apb_saradc_thres0_low_int_ena()
+                .bit(low)
+        });
     }
 }
 
@@ -2,8 +2,9 @@ use core::marker::PhantomData;
 
 use embedded_hal::adc::{Channel, OneShot};
 
+pub use crate::analog::oversample::Oversample;
 use crate::{
-    analog::{ADC1, ADC2},
+    analog::{oversample::Oversampling, ADC1, ADC2},
     pac::{APB_SARADC, SENS},
 };
 
@@ -22,6 +23,18 @@ pub enum Attenuation {
     Attenuation11dB  = 0b11,
 }
 
+/// ADC error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdcError {
+    /// ADC2 is used by WiFi
+    ///
+    /// Only returned on the ESP32-S2, see `Adc2Arbiter`.
+    Adc2InUse,
+}
+
+#[cfg(esp32s2)]
+pub use crate::analog::adc2_arbiter::{set_adc2_wifi_priority, Adc2Arbiter};
+
 pub struct AdcPin<PIN, ADCI> {
     pub pin: PIN,
     _phantom: PhantomData<ADCI>,
@@ -38,6 +51,7 @@ impl<PIN: Channel<ADCI, ID = u8>, ADCI> Channel<ADCI> for AdcPin<PIN, ADCI> {
 pub struct AdcConfig<ADCI> {
     pub resolution: Resolution,
     pub attenuations: [Option<Attenuation>; 10],
+    oversampling: [Oversampling; 10],
     _phantom: PhantomData<ADCI>,
 }
 
@@ -55,12 +69,42 @@ where
         attenuation: Attenuation,
     ) -> AdcPin<PIN, ADCI> {
         self.attenuations[PIN::channel() as usize] = Some(attenuation);
+        self.oversampling[PIN::channel() as usize] = Oversampling::NONE;
 
         AdcPin {
             pin,
             _phantom: PhantomData::default(),
         }
     }
+
+    /// Like [AdcConfig::enable_pin], each read of the pin returns the
+    /// average of `oversample` conversions
+    ///
+    /// See the [Oversample] documentation for the conversion time and
+    /// saturation.
+    pub fn enable_pin_with_oversampling<PIN: Channel<ADCI, ID = u8>>(
+        &mut self,
+        pin: PIN,
+        attenuation: Attenuation,
+        oversample: Oversample,
+    ) -> AdcPin<PIN, ADCI> {
+        let pin = self.enable_pin(pin, attenuation);
+        self.oversampling[PIN::channel() as usize] = Oversampling::average(oversample);
+        pin
+    }
+
+    /// Like [AdcConfig::enable_pin_with_oversampling], but the reads return
+    /// [Oversample::extra_bits] more bits than the resolution of the ADC
+    pub fn enable_pin_with_decimation<PIN: Channel<ADCI, ID = u8>>(
+        &mut self,
+        pin: PIN,
+        attenuation: Attenuation,
+        oversample: Oversample,
+    ) -> AdcPin<PIN, ADCI> {
+        let pin = self.enable_pin(pin, attenuation);
+        self.oversampling[PIN::channel() as usize] = Oversampling::decimate(oversample);
+        pin
+    }
 }
 
 impl<ADCI> Default for AdcConfig<ADCI> {
@@ -68,6 +112,7 @@ impl<ADCI> Default for AdcConfig<ADCI> {
         AdcConfig {
             resolution: Resolution::Resolution13Bit,
             attenuations: [None; 10],
+            oversampling: [Oversampling::NONE; 10],
             _phantom: PhantomData::default(),
         }
     }
@@ -96,6 +141,18 @@ pub trait RegisterAccess {
     fn read_done_sar() -> bool;
 
     fn read_data_sar() -> u16;
+
+    /// Take the ADC2 lock for a conversion, `None` if not needed
+    #[cfg(esp32s2)]
+    fn arbitrate() -> Result<Option<Adc2Arbiter>, AdcError> {
+        Ok(None)
+    }
+
+    /// Whether a conversion result is valid
+    #[cfg(esp32s2)]
+    fn is_data_valid(_data: u16) -> bool {
+        true
+    }
 }
 
 impl RegisterAccess for ADC1 {
@@ -195,10 +252,16 @@ impl RegisterAccess for ADC2 {
             .sar_meas2_mux
             .modify(|_, w| w.sar2_rtc_force().set_bit());
 
-        let sar_apb = unsafe { &*APB_SARADC::ptr() };
-        sar_apb
-            .arb_ctrl
-            .modify(|_, w| w.adc_arb_rtc_force().set_bit());
+        #[cfg(esp32s2)]
+        crate::analog::adc2_arbiter::configure_hardware_arbiter();
+
+        #[cfg(esp32s3)]
+        {
+            let sar_apb = unsafe { &*APB_SARADC::ptr() };
+            sar_apb
+                .arb_ctrl
+                .modify(|_, w| w.adc_arb_rtc_force().set_bit());
+        }
     }
 
     fn set_start_force() {
@@ -245,12 +308,30 @@ impl RegisterAccess for ADC2 {
         let sensors = unsafe { &*SENS::ptr() };
         sensors.sar_meas2_ctrl2.read().meas2_data_sar().bits() as u16
     }
+
+    #[cfg(esp32s2)]
+    fn arbitrate() -> Result<Option<Adc2Arbiter>, AdcError> {
+        Adc2Arbiter::try_lock().map(Some)
+    }
+
+    #[cfg(esp32s2)]
+    fn is_data_valid(data: u16) -> bool {
+        // The arbiter sets the upper two bits if the conversion was interrupted
+        // or the RTC controller wasn't granted ADC2
+        !crate::analog::adc2_arbiter::wifi_priority() || data >> 14 == 0
+    }
 }
 
 pub struct ADC<ADC> {
     adc: PhantomData<ADC>,
     attenuations: [Option<Attenuation>; 10],
+    oversampling: [Oversampling; 10],
     active_channel: Option<u8>,
+    /// Sum and number of the samples of the read in progress
+    sum: u32,
+    samples: u16,
+    #[cfg(esp32s2)]
+    adc2_lock: Option<Adc2Arbiter>,
 }
 
 impl<ADCI> ADC<ADCI>
@@ -331,7 +412,12 @@ where
         let adc = ADC {
             adc: PhantomData,
             attenuations: config.attenuations,
+            oversampling: config.oversampling,
             active_channel: None,
+            sum: 0,
+            samples: 0,
+            #[cfg(esp32s2)]
+            adc2_lock: None,
         };
 
         Ok(adc)
@@ -344,7 +430,7 @@ where
     PIN: Channel<ADCI, ID = u8>,
     ADCI: RegisterAccess,
 {
-    type Error = ();
+    type Error = AdcError;
 
     fn read(&mut self, _pin: &mut AdcPin<PIN, ADCI>) -> nb::Result<WORD, Self::Error> {
         if self.attenuations[AdcPin::<PIN, ADCI>::channel() as usize] == None {
@@ -363,6 +449,11 @@ where
             }
         } else {
             // If no conversions are in progress, start a new one for given channel
+            #[cfg(esp32s2)]
+            {
+                self.adc2_lock = ADCI::arbitrate()?;
+            }
+
             self.active_channel = Some(AdcPin::<PIN, ADCI>::channel());
 
             ADCI::set_en_pad(AdcPin::<PIN, ADCI>::channel() as u8);
@@ -380,10 +471,39 @@ where
         // Get converted value
         let converted_value = ADCI::read_data_sar();
 
+        #[cfg(esp32s2)]
+        if !ADCI::is_data_valid(converted_value) {
+            // the samples taken so far are dropped as well
+            self.sum = 0;
+            self.samples = 0;
+            self.active_channel = None;
+            self.adc2_lock = None;
+            return Err(nb::Error::Other(AdcError::Adc2InUse));
+        }
+
+        // Take the next sample of an oversampled read
+        let oversampling = self.oversampling[AdcPin::<PIN, ADCI>::channel() as usize];
+        self.sum += converted_value as u32;
+        self.samples += 1;
+        if self.samples < oversampling.samples() {
+            ADCI::clear_start_sar();
+            ADCI::set_start_sar();
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let value = oversampling.result(self.sum);
+        self.sum = 0;
+        self.samples = 0;
+
         // Mark that no conversions are currently in progress
         self.active_channel = None;
 
-        Ok(converted_value.into())
+        #[cfg(esp32s2)]
+        {
+            self.adc2_lock = None;
+        }
+
+        Ok(value.into())
     }
 }
 
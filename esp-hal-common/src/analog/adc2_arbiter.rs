@@ -0,0 +1,114 @@
+//! Arbitration of ADC2 between the application and the WiFi driver
+//!
+//! On the ESP32 and ESP32-S2 the RF frontend uses ADC2 for power detection
+//! while WiFi is active. A conversion started in that time either fails or
+//! returns a wrong value, so ADC2 reads take the [Adc2Arbiter] lock and
+//! return [AdcError::Adc2InUse] if it is held elsewhere.
+//!
+//! - ESP32: there is no hardware arbiter. The HAL can't see the RF frontend,
+//!   the WiFi driver has to claim ADC2 with [crate::coex::claim] while WiFi is
+//!   active.
+//! - ESP32-S2: additionally the arbiter in `APB_SARADC` grants ADC2 to WiFi
+//!   first, a conversion it interrupted is flagged in the result and reported
+//!   as [AdcError::Adc2InUse] as well.
+//! - ESP32-C3 / ESP32-S3: ADC2 is read through a different controller path, the
+//!   arbiter isn't used.
+//!
+//! [set_adc2_wifi_priority] turns the arbitration off, ADC2 reads always
+//! succeed then at the cost of disturbing WiFi.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use super::adc::AdcError;
+
+static LOCKED: AtomicBool = AtomicBool::new(false);
+static WIFI_PRIORITY: AtomicBool = AtomicBool::new(true);
+
+/// Lock on ADC2, see the [module documentation](self)
+pub struct Adc2Arbiter {
+    owned: bool,
+}
+
+impl Adc2Arbiter {
+    /// Take the ADC2 lock
+    ///
+    /// Returns [AdcError::Adc2InUse] if it is already held. The lock is
+    /// released when the returned guard is dropped.
+    pub fn try_lock() -> Result<Self, AdcError> {
+        if !wifi_priority() {
+            return Ok(Self { owned: false });
+        }
+
+        LOCKED
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .map(|_| Self { owned: true })
+            .map_err(|_| AdcError::Adc2InUse)
+    }
+
+    /// Whether the lock is currently held
+    pub fn is_locked() -> bool {
+        LOCKED.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Adc2Arbiter {
+    fn drop(&mut self) {
+        if self.owned {
+            LOCKED.store(false, Ordering::Release);
+        }
+    }
+}
+
+/// Take the lock on behalf of the radio driver until [unlock], see
+/// [crate::coex]
+pub(crate) fn lock() -> bool {
+    LOCKED
+        .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+        .is_ok()
+}
+
+pub(crate) fn unlock() {
+    LOCKED.store(false, Ordering::Release);
+}
+
+/// Let WiFi take precedence over ADC2 reads (the default) or not
+///
+/// With `false` ADC2 reads ignore the lock and, on the ESP32-S2, the hardware
+/// arbiter grants ADC2 to the application unconditionally. This is an escape
+/// hatch for applications which don't use WiFi or accept disturbing it.
+pub fn set_adc2_wifi_priority(enabled: bool) {
+    WIFI_PRIORITY.store(enabled, Ordering::Relaxed);
+
+    #[cfg(esp32s2)]
+    configure_hardware_arbiter();
+}
+
+pub(crate) fn wifi_priority() -> bool {
+    WIFI_PRIORITY.load(Ordering::Relaxed)
+}
+
+/// Grant ADC2 to WiFi first, then the RTC controller, or force it to the RTC
+/// controller if the WiFi priority is disabled
+#[cfg(esp32s2)]
+pub(crate) fn configure_hardware_arbiter() {
+    let sar_apb = unsafe { &*crate::pac::APB_SARADC::ptr() };
+
+    if wifi_priority() {
+        sar_apb.arb_ctrl.modify(|_, w| unsafe {
+            w.adc_arb_rtc_force()
+                .clear_bit()
+                .adc_arb_fix_priority()
+                .set_bit()
+                .adc_arb_wifi_priority()
+                .bits(2)
+                .adc_arb_rtc_priority()
+                .bits(1)
+                .adc_arb_apb_priority()
+                .bits(0)
+        });
+    } else {
+        sar_apb
+            .arb_ctrl
+            .modify(|_, w| w.adc_arb_rtc_force().set_bit());
+    }
+}
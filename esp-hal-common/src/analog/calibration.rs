@@ -0,0 +1,311 @@
+//! Calibration of the ADC and a DAC channel against each other
+//!
+//! With a DAC output connected to an ADC input (a jumper), [self_calibrate]
+//! sweeps the DAC across its range and measures every step with the ADC.
+//! The resulting [CalibrationCurve] corrects both directions with a
+//! piecewise-linear interpolation between the measured points:
+//!
+//! ```no_run
+//! let curve = analog::self_calibrate(&mut dac1, &mut adc2, &mut pin, &mut delay)?;
+//!
+//! let mv = nb::block!(adc1.read_corrected(&mut sensor, &curve))?;
+//! dac2.write_corrected_mv(1500, &curve);
+//! ```
+//!
+//! Without an external reference only the combined error of both converters
+//! is measured, each correction attributes it to one of them:
+//! - [ADC::read_corrected] assumes the DAC outputs its nominal voltage and
+//!   returns millivolts on the `vdd * code / 256` scale
+//! - `write_corrected_mv` of a DAC channel assumes the ADC is linear between
+//!   the lowest and the highest measured point
+//!
+//! The ESP32 DACs are much more linear than the ADCs, the ADC correction
+//! removes most of the nonlinearity of the ADC, in particular towards the
+//! ends of its range. The curve is only valid for the attenuation and
+//! resolution the ADC pin was configured with during the sweep.
+//!
+//! A [CalibrationCurve] is plain data, [CalibrationCurve::to_bytes] and
+//! [CalibrationCurve::from_bytes] store and load it e.g. in flash.
+
+use embedded_hal::{
+    adc::{Channel, OneShot},
+    blocking::delay::DelayUs,
+};
+
+use super::{
+    adc::{AdcError, AdcPin, RegisterAccess, ADC},
+    dac::{mv_for_code, DAC},
+};
+use crate::Delay;
+
+/// Number of DAC codes measured by [self_calibrate]
+pub const POINTS: usize = 17;
+
+/// Time the output is given to settle after every step of the sweep
+const SETTLE_US: u32 = 2_000;
+/// Conversions per step, the lowest and highest quarter are dropped and the
+/// rest is averaged
+const SAMPLES: usize = 16;
+
+/// Error of [self_calibrate]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalibrationError {
+    /// The ADC failed
+    Adc(AdcError),
+    /// The ADC reading doesn't follow the DAC, fewer than two points rise
+    /// with the code. Is the DAC connected to the ADC pin?
+    NotConnected,
+}
+
+impl From<AdcError> for CalibrationError {
+    fn from(error: AdcError) -> Self {
+        Self::Adc(error)
+    }
+}
+
+/// Measured points of a DAC channel and the ADC, see the [module
+/// documentation](self)
+///
+/// Only `u16` fields without padding, the layout is fixed.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalibrationCurve {
+    /// The supply voltage the DAC assumed during the sweep, in millivolts
+    pub vdd_mv: u16,
+    /// Number of valid points
+    pub len: u16,
+    /// DAC code of every point, rising
+    pub dac_codes: [u16; POINTS],
+    /// ADC reading of every point, rising
+    pub adc_raw: [u16; POINTS],
+}
+
+impl CalibrationCurve {
+    /// Size of [CalibrationCurve::to_bytes]
+    pub const SIZE: usize = core::mem::size_of::<Self>();
+
+    /// The curve as little endian `u16` fields in declaration order
+    pub fn to_bytes(&self) -> [u8; Self::SIZE] {
+        let mut bytes = [0; Self::SIZE];
+        let words = [self.vdd_mv, self.len]
+            .into_iter()
+            .chain(self.dac_codes)
+            .chain(self.adc_raw);
+
+        for (chunk, word) in bytes.chunks_exact_mut(2).zip(words) {
+            chunk.copy_from_slice(&word.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Load a curve stored with [CalibrationCurve::to_bytes]
+    ///
+    /// Returns `None` if the bytes don't hold a valid curve, e.g. erased
+    /// flash.
+    pub fn from_bytes(bytes: &[u8; Self::SIZE]) -> Option<Self> {
+        let mut words = bytes
+            .chunks_exact(2)
+            .map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]));
+
+        let mut curve = Self {
+            vdd_mv: words.next()?,
+            len: words.next()?,
+            dac_codes: [0; POINTS],
+            adc_raw: [0; POINTS],
+        };
+        for code in curve.dac_codes.iter_mut() {
+            *code = words.next()?;
+        }
+        for raw in curve.adc_raw.iter_mut() {
+            *raw = words.next()?;
+        }
+
+        if curve.is_valid() {
+            Some(curve)
+        } else {
+            None
+        }
+    }
+
+    /// Whether the curve has at least two points and both the codes and the
+    /// readings rise
+    pub fn is_valid(&self) -> bool {
+        let len = self.len as usize;
+        if !(2..=POINTS).contains(&len) || self.vdd_mv == 0 {
+            return false;
+        }
+
+        let rising = |values: &[u16]| values.windows(2).all(|pair| pair[0] < pair[1]);
+        self.dac_codes[..len]
+            .iter()
+            .all(|&code| code <= u8::MAX as u16)
+            && rising(&self.dac_codes[..len])
+            && rising(&self.adc_raw[..len])
+    }
+
+    /// The voltage at the ADC for the reading `raw`, in millivolts on the
+    /// scale of the DAC's nominal output
+    pub fn adc_mv(&self, raw: u16) -> u16 {
+        let len = self.len as usize;
+        let index = segment(&self.adc_raw[..len], raw);
+
+        let mv = interpolate(
+            raw as i32,
+            (self.adc_raw[index] as i32, self.nominal_mv(index)),
+            (self.adc_raw[index + 1] as i32, self.nominal_mv(index + 1)),
+        );
+        mv.clamp(0, u16::MAX as i32) as u16
+    }
+
+    /// The DAC code which outputs `mv`, rounded to the nearest code and
+    /// saturated at 0 and 255
+    pub fn dac_code_for_mv(&self, mv: u16) -> u8 {
+        let len = self.len as usize;
+
+        // the output of the DAC at each point, measured with the ADC as a
+        // straight line between the first and the last point
+        let actual_mv = |index: usize| {
+            interpolate(
+                self.adc_raw[index] as i32,
+                (self.adc_raw[0] as i32, self.nominal_mv(0)),
+                (self.adc_raw[len - 1] as i32, self.nominal_mv(len - 1)),
+            )
+        };
+
+        let mut index = 0;
+        while index + 2 < len && actual_mv(index + 1) <= mv as i32 {
+            index += 1;
+        }
+
+        let code = interpolate(
+            mv as i32,
+            (actual_mv(index), self.dac_codes[index] as i32),
+            (actual_mv(index + 1), self.dac_codes[index + 1] as i32),
+        );
+        code.clamp(0, u8::MAX as i32) as u8
+    }
+
+    fn nominal_mv(&self, index: usize) -> i32 {
+        mv_for_code(self.dac_codes[index] as u8, self.vdd_mv) as i32
+    }
+}
+
+/// Index of the segment of the rising `values` which contains `value`, the
+/// first or last segment beyond the ends
+fn segment(values: &[u16], value: u16) -> usize {
+    let mut index = 0;
+    while index + 2 < values.len() && values[index + 1] <= value {
+        index += 1;
+    }
+    index
+}
+
+/// The `y` of the line through `a` and `b` at `x`, rounded
+const fn interpolate(x: i32, a: (i32, i32), b: (i32, i32)) -> i32 {
+    let (x0, y0) = a;
+    let (x1, y1) = b;
+    if x1 == x0 {
+        return y0;
+    }
+
+    let n = (x - x0) * (y1 - y0);
+    let d = x1 - x0;
+    let rounded = if (n >= 0) == (d > 0) {
+        (n + d / 2) / d
+    } else {
+        (n - d / 2) / d
+    };
+    y0 + rounded
+}
+
+const _: () = {
+    assert!(interpolate(5, (0, 0), (10, 100)) == 50);
+    assert!(interpolate(15, (0, 0), (10, 100)) == 150);
+    assert!(interpolate(-5, (0, 0), (10, 100)) == -50);
+    assert!(interpolate(1, (0, 0), (3, 1)) == 0);
+    assert!(interpolate(2, (0, 0), (3, 1)) == 1);
+    assert!(interpolate(2, (0, 10), (3, 7)) == 8);
+};
+
+/// Sweep `dac` across its range, measure it with `adc` on `pin` and return
+/// the curve
+///
+/// The DAC has to be connected to the ADC pin. Every step is given 2 ms to
+/// settle and converted 16 times, the average of the middle half of the
+/// readings rejects outliers. Points where the ADC reading doesn't rise,
+/// because it clipped at either end, are left out. The DAC is left at 0.
+pub fn self_calibrate<D, ADCI, PIN>(
+    dac: &mut D,
+    adc: &mut ADC<ADCI>,
+    pin: &mut AdcPin<PIN, ADCI>,
+    delay: &mut Delay,
+) -> Result<CalibrationCurve, CalibrationError>
+where
+    D: DAC,
+    ADCI: RegisterAccess,
+    PIN: Channel<ADCI, ID = u8>,
+{
+    let mut curve = CalibrationCurve {
+        vdd_mv: dac.vdd_mv(),
+        len: 0,
+        dac_codes: [0; POINTS],
+        adc_raw: [0; POINTS],
+    };
+
+    for point in 0..POINTS {
+        let code = (point * 256 / (POINTS - 1)).min(u8::MAX as usize) as u8;
+        dac.write(code);
+        delay.delay_us(SETTLE_US);
+
+        let mut samples = [0u16; SAMPLES];
+        for sample in samples.iter_mut() {
+            *sample = nb::block!(adc.read(pin))?;
+        }
+        samples.sort_unstable();
+
+        let middle = &samples[SAMPLES / 4..SAMPLES * 3 / 4];
+        let sum: u32 = middle.iter().map(|&sample| sample as u32).sum();
+        let raw = ((sum + middle.len() as u32 / 2) / middle.len() as u32) as u16;
+
+        let len = curve.len as usize;
+        let rises = match len {
+            0 => raw > 0,
+            _ => raw > curve.adc_raw[len - 1],
+        };
+        if rises {
+            curve.dac_codes[len] = code as u16;
+            curve.adc_raw[len] = raw;
+            curve.len += 1;
+        }
+    }
+
+    dac.write(0);
+
+    if curve.is_valid() {
+        Ok(curve)
+    } else {
+        Err(CalibrationError::NotConnected)
+    }
+}
+
+impl<ADCI> ADC<ADCI>
+where
+    ADCI: RegisterAccess,
+{
+    /// Read `pin` and correct the result with `curve`, in millivolts
+    ///
+    /// Works like `read` of [OneShot], the pin must be configured with the
+    /// attenuation and resolution the curve was measured with.
+    pub fn read_corrected<PIN>(
+        &mut self,
+        pin: &mut AdcPin<PIN, ADCI>,
+        curve: &CalibrationCurve,
+    ) -> nb::Result<u16, AdcError>
+    where
+        PIN: Channel<ADCI, ID = u8>,
+    {
+        let raw: u16 = self.read(pin)?;
+        Ok(curve.adc_mv(raw))
+    }
+}
@@ -1,7 +1,145 @@
 use crate::pac::{RTCIO, SENS};
 
+/// Supply voltage the millivolt API of a DAC channel assumes until
+/// `set_vdd_mv` is called
+pub const DEFAULT_VDD_MV: u16 = 3300;
+
+/// The code which outputs `mv` at a supply voltage of `vdd_mv`, rounded to
+/// the nearest code and saturated at 255
+///
+/// The output voltage is `VDD3P3_RTC * code / 256`.
+pub const fn code_for_mv(mv: u16, vdd_mv: u16) -> u8 {
+    let code = div_round(mv as i32 * 256, vdd_mv as i32);
+    saturate_code(code)
+}
+
+/// The voltage `code` outputs at a supply voltage of `vdd_mv`, in millivolts
+pub const fn mv_for_code(code: u8, vdd_mv: u16) -> u16 {
+    div_round(code as i32 * vdd_mv as i32, 256) as u16
+}
+
+/// Two measured points of a DAC channel, the codes in between and beyond are
+/// interpolated linearly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Calibration {
+    low: (u8, u16),
+    high: (u8, u16),
+}
+
+impl Calibration {
+    /// Calibration from two `(code, measured_mv)` points
+    ///
+    /// Returns `None` unless the voltage rises with the code between the two
+    /// points.
+    pub const fn new(a: (u8, u16), b: (u8, u16)) -> Option<Self> {
+        let (low, high) = if a.0 < b.0 { (a, b) } else { (b, a) };
+        if low.0 == high.0 || low.1 >= high.1 {
+            return None;
+        }
+
+        Some(Self { low, high })
+    }
+
+    /// The code which outputs `mv`, rounded to the nearest code and
+    /// saturated at 0 and 255
+    pub const fn code_for_mv(&self, mv: u16) -> u8 {
+        let (code0, mv0) = (self.low.0 as i32, self.low.1 as i32);
+        let (code1, mv1) = (self.high.0 as i32, self.high.1 as i32);
+
+        let code = code0 + div_round((mv as i32 - mv0) * (code1 - code0), mv1 - mv0);
+        saturate_code(code)
+    }
+
+    /// The voltage `code` outputs, in millivolts
+    pub const fn mv_for_code(&self, code: u8) -> u16 {
+        let (code0, mv0) = (self.low.0 as i32, self.low.1 as i32);
+        let (code1, mv1) = (self.high.0 as i32, self.high.1 as i32);
+
+        let mv = mv0 + div_round((code as i32 - code0) * (mv1 - mv0), code1 - code0);
+        if mv < 0 {
+            0
+        } else if mv > u16::MAX as i32 {
+            u16::MAX
+        } else {
+            mv as u16
+        }
+    }
+}
+
+/// `n / d` rounded half away from zero, `d` must be positive
+const fn div_round(n: i32, d: i32) -> i32 {
+    if n >= 0 {
+        (n + d / 2) / d
+    } else {
+        -((-n + d / 2) / d)
+    }
+}
+
+const fn saturate_code(code: i32) -> u8 {
+    if code < 0 {
+        0
+    } else if code > u8::MAX as i32 {
+        u8::MAX
+    } else {
+        code as u8
+    }
+}
+
+const _: () = {
+    assert!(code_for_mv(0, 3300) == 0);
+    assert!(code_for_mv(100, 3300) == 8);
+    assert!(code_for_mv(1650, 3300) == 128);
+    assert!(code_for_mv(3300, 3300) == 255);
+    assert!(code_for_mv(u16::MAX, 3300) == 255);
+    assert!(mv_for_code(0, 3300) == 0);
+    assert!(mv_for_code(255, 3300) == 3287);
+    assert!(mv_for_code(128, 3300) == 1650);
+
+    let mut code = 0;
+    while code <= u8::MAX as u16 {
+        assert!(code_for_mv(mv_for_code(code as u8, 3300), 3300) == code as u8);
+        code += 1;
+    }
+
+    let calibration = match Calibration::new((255, 3180), (0, 85)) {
+        Some(calibration) => calibration,
+        None => panic!(),
+    };
+    assert!(calibration.code_for_mv(85) == 0);
+    assert!(calibration.code_for_mv(0) == 0);
+    assert!(calibration.code_for_mv(1650) == 129);
+    assert!(calibration.code_for_mv(3180) == 255);
+    assert!(calibration.code_for_mv(3300) == 255);
+    assert!(calibration.mv_for_code(255) == 3180);
+    assert!(calibration.mv_for_code(128) == 1639);
+
+    // Measured between codes 64 and 192, the codes beyond are extrapolated
+    let calibration = match Calibration::new((64, 800), (192, 2400)) {
+        Some(calibration) => calibration,
+        None => panic!(),
+    };
+    assert!(calibration.mv_for_code(0) == 0);
+    assert!(calibration.mv_for_code(255) == 3188);
+    assert!(calibration.code_for_mv(0) == 0);
+    assert!(calibration.code_for_mv(3188) == 255);
+
+    assert!(Calibration::new((10, 100), (10, 200)).is_none());
+    assert!(Calibration::new((10, 200), (20, 100)).is_none());
+    assert!(Calibration::new((10, 100), (20, 100)).is_none());
+
+    assert!(div_round(5, 2) == 3);
+    assert!(div_round(-5, 2) == -3);
+    assert!(div_round(4, 3) == 1);
+    assert!(div_round(-4, 3) == -1);
+};
+
 pub trait DAC {
     fn write(&mut self, value: u8);
+
+    /// The supply voltage the millivolt API assumes
+    fn vdd_mv(&self) -> u16 {
+        DEFAULT_VDD_MV
+    }
 }
 
 #[doc(hidden)]
@@ -94,10 +232,22 @@ macro_rules! impl_dac {
                 /// DAC channel
                 pub struct [<DAC $number>] {
                     _private: PhantomData<()>,
+                    vdd_mv: u16,
+                    calibration: Option<$crate::analog::dac::Calibration>,
                 }
 
                 impl [<DAC $number Impl>] for [<DAC $number>] {}
 
+                impl $crate::analog::dac::DAC for [<DAC $number>] {
+                    fn write(&mut self, value: u8) {
+                        [<DAC $number Impl>]::write(self, value)
+                    }
+
+                    fn vdd_mv(&self) -> u16 {
+                        self.vdd_mv
+                    }
+                }
+
                 impl [<DAC $number>] {
                     /// Constructs a new DAC instance
                     pub fn dac(
@@ -106,6 +256,8 @@ macro_rules! impl_dac {
                     ) -> Result<Self, ()> {
                         let dac = Self {
                             _private: PhantomData,
+                            vdd_mv: $crate::analog::dac::DEFAULT_VDD_MV,
+                            calibration: None,
                         }
                         .set_power();
                         Ok(dac)
@@ -118,6 +270,57 @@ macro_rules! impl_dac {
                     pub fn write(&mut self, value: u8) {
                         [<DAC $number Impl>]::write(self, value)
                     }
+
+                    /// Set the voltage of VDD3P3_RTC the millivolt API assumes,
+                    /// [DEFAULT_VDD_MV] by default
+                    pub fn set_vdd_mv(&mut self, vdd_mv: u16) {
+                        assert!(vdd_mv != 0, "the supply voltage must not be 0");
+                        self.vdd_mv = vdd_mv;
+                    }
+
+                    /// The supply voltage the millivolt API assumes
+                    pub fn vdd_mv(&self) -> u16 {
+                        self.vdd_mv
+                    }
+
+                    /// Interpolate the millivolt API between two measured points
+                    /// instead of using the supply voltage, `None` goes back to
+                    /// the supply voltage
+                    pub fn set_calibration(
+                        &mut self,
+                        calibration: Option<$crate::analog::dac::Calibration>,
+                    ) {
+                        self.calibration = calibration;
+                    }
+
+                    /// The highest voltage the channel outputs, in millivolts
+                    pub fn max_mv(&self) -> u16 {
+                        match &self.calibration {
+                            Some(calibration) => calibration.mv_for_code(u8::MAX),
+                            None => $crate::analog::dac::mv_for_code(u8::MAX, self.vdd_mv),
+                        }
+                    }
+
+                    /// Output the voltage closest to `mv` millivolts
+                    ///
+                    /// Voltages above [max_mv](Self::max_mv) saturate.
+                    pub fn write_mv(&mut self, mv: u16) {
+                        let code = match &self.calibration {
+                            Some(calibration) => calibration.code_for_mv(mv),
+                            None => $crate::analog::dac::code_for_mv(mv, self.vdd_mv),
+                        };
+                        self.write(code);
+                    }
+
+                    /// Output `mv` millivolts corrected with a curve measured by
+                    /// [self_calibrate]($crate::analog::calibration::self_calibrate)
+                    pub fn write_corrected_mv(
+                        &mut self,
+                        mv: u16,
+                        curve: &$crate::analog::calibration::CalibrationCurve,
+                    ) {
+                        self.write(curve.dac_code_for_mv(mv));
+                    }
                 }
             }
         )+
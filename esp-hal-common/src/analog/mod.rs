@@ -4,8 +4,15 @@
 #[cfg_attr(esp32s2, path = "adc/xtensa.rs")]
 #[cfg_attr(esp32s3, path = "adc/xtensa.rs")]
 pub mod adc;
+#[cfg(any(esp32, esp32s2))]
+pub(crate) mod adc2_arbiter;
+#[cfg(dac)]
+pub mod calibration;
 #[cfg(dac)]
 pub mod dac;
+pub(crate) mod oversample;
+#[cfg(any(esp32s2, esp32s3))]
+pub mod touch;
 
 cfg_if::cfg_if! {
     if #[cfg(any(esp32, esp32s2, esp32s3))] {
@@ -28,11 +35,19 @@ cfg_if::cfg_if! {
             _private: PhantomData<()>,
         }
 
+        /// The touch controller, see [touch::Touch]
+        #[cfg(any(esp32s2, esp32s3))]
+        pub struct TOUCH {
+            _private: PhantomData<()>,
+        }
+
         pub struct AvailableAnalog {
             pub adc1: ADC1,
             pub adc2: ADC2,
             pub dac1: DAC1,
             pub dac2: DAC2,
+            #[cfg(any(esp32s2, esp32s3))]
+            pub touch: TOUCH,
         }
 
         /// Extension trait to split a SENS peripheral in independent parts
@@ -55,6 +70,10 @@ cfg_if::cfg_if! {
                     dac2: DAC2 {
                         _private: PhantomData,
                     },
+                    #[cfg(any(esp32s2, esp32s3))]
+                    touch: TOUCH {
+                        _private: PhantomData,
+                    },
                 }
             }
         }
@@ -0,0 +1,113 @@
+//! Oversampling of one-shot ADC reads, see [Oversample]
+
+/// Number of conversions averaged per read
+///
+/// A pin enabled with `enable_pin_with_oversampling` is converted
+/// [Oversample::samples] times per `read`, which returns the rounded average
+/// in the resolution of the ADC. `enable_pin_with_decimation` returns the
+/// sum shifted right by fewer bits instead: every factor of 4 adds one bit,
+/// e.g. a 12-bit ADC returns 15-bit values with [Oversample::X64]. The extra
+/// bits only carry information if the input has at least about 1 LSB of
+/// noise.
+///
+/// None of the controllers the driver uses accumulates in hardware, the
+/// samples are taken one after another by `read`. A read takes `samples`
+/// times as long as a single conversion, `nb::block!` on it blocks for that
+/// long. Conversions of other channels wait until the oversampled read
+/// finished.
+///
+/// Samples are summed in 32 bits, which can't overflow: 256 samples of
+/// 13 bits need 21 bits. A sample at the end of the range stays there, so
+/// an input which clips for part of the samples averages below its actual
+/// level. Decimated results which don't fit into 16 bits, only possible
+/// with 13-bit samples and [Oversample::X256], saturate at `u16::MAX`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Oversample {
+    X1   = 0,
+    X2   = 1,
+    X4   = 2,
+    X8   = 3,
+    X16  = 4,
+    X32  = 5,
+    X64  = 6,
+    X128 = 7,
+    X256 = 8,
+}
+
+impl Oversample {
+    /// Number of conversions per read
+    pub const fn samples(self) -> u16 {
+        1 << self as u16
+    }
+
+    /// Bits a decimated result has in addition to the resolution of the
+    /// ADC
+    pub const fn extra_bits(self) -> u8 {
+        self as u8 / 2
+    }
+}
+
+/// How the samples of a channel are combined
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Oversampling {
+    oversample: Oversample,
+    decimate: bool,
+}
+
+impl Oversampling {
+    /// A single conversion per read
+    pub(crate) const NONE: Self = Self::average(Oversample::X1);
+
+    pub(crate) const fn average(oversample: Oversample) -> Self {
+        Self {
+            oversample,
+            decimate: false,
+        }
+    }
+
+    pub(crate) const fn decimate(oversample: Oversample) -> Self {
+        Self {
+            oversample,
+            decimate: true,
+        }
+    }
+
+    pub(crate) const fn samples(self) -> u16 {
+        self.oversample.samples()
+    }
+
+    /// The result of a read from the sum of its samples
+    pub(crate) const fn result(self, sum: u32) -> u16 {
+        let mut shift = self.oversample as u32;
+        if self.decimate {
+            shift -= self.oversample.extra_bits() as u32;
+        }
+
+        let value = if shift == 0 {
+            sum
+        } else {
+            (sum + (1 << (shift - 1))) >> shift
+        };
+
+        if value > u16::MAX as u32 {
+            u16::MAX
+        } else {
+            value as u16
+        }
+    }
+}
+
+const _: () = {
+    assert!(Oversampling::NONE.result(4095) == 4095);
+    assert!(Oversampling::average(Oversample::X4).result(1 + 2 + 2 + 2) == 2);
+    assert!(Oversampling::average(Oversample::X4).result(1 + 1 + 2 + 2) == 2);
+    assert!(Oversampling::average(Oversample::X4).result(1 + 1 + 1 + 2) == 1);
+    assert!(Oversampling::average(Oversample::X256).result(4095 * 256) == 4095);
+
+    // decimation adds a bit for every factor of 4
+    assert!(Oversampling::decimate(Oversample::X2).result(2 * 4095) == 4095);
+    assert!(Oversampling::decimate(Oversample::X4).result(4 * 4095) == 8190);
+    assert!(Oversampling::decimate(Oversample::X64).result(64 * 4095) == 32760);
+    assert!(Oversampling::decimate(Oversample::X256).result(256 * 4095) == 65520);
+    assert!(Oversampling::decimate(Oversample::X256).result(256 * 8191) == u16::MAX);
+};
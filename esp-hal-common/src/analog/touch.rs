@@ -0,0 +1,590 @@
+//! Touch sensor
+//!
+//! The touch controller of the ESP32-S2 and ESP32-S3 scans the enabled pads
+//! `TOUCH1` to `TOUCH14` (GPIO1 to GPIO14) on its own, filters the readings
+//! into a benchmark and flags a pad as touched when its reading rises by
+//! more than the pad's threshold above the benchmark:
+//!
+//! ```no_run
+//! let analog = peripherals.SENS.split();
+//! let mut touch = Touch::new(analog.touch, TouchConfig::default()).unwrap();
+//!
+//! let button = io.pins.gpio2.into_analog();
+//! touch.enable_pad(&button, 1500).unwrap();
+//!
+//! if touch.is_touched(&button) {
+//!     // ...
+//! }
+//! ```
+//!
+//! [TouchConfig] selects the optional parts of the controller:
+//! - [Denoise]: the internal pad `TOUCH0` measures the noise common to all
+//!   pads, which is subtracted from their readings.
+//! - [GuardRing]: a ring around the pads detects water flowing over the panel
+//!   and locks all other pads while it's covered. `TOUCH14` drives the shield
+//!   electrode between the pads and the ring, it can't be used as a pad then.
+//!   The guard ring pad is enabled with [Touch::enable_pad] like any other pad.
+//! - The sleep channel: a pad with its own threshold, which keeps being scanned
+//!   in deep sleep and wakes the chip after [Touch::enable_wakeup], see
+//!   `Rtc::sleep_deep`. The triggering pad is reported as `WakeReason::Touch`.
+//! - [DriftCompensation]: the benchmark follows slow changes, e.g. of humidity
+//!   or temperature, through a filter. Additionally [Touch::poll] resets the
+//!   benchmark of the idle pads periodically.
+//!
+//! Thresholds are in counts of the raw reading, [Touch::benchmark] helps to
+//! pick one relative to the untouched reading of a pad.
+
+use crate::{
+    analog::TOUCH,
+    pac::{RTC_CNTL, SENS},
+    rtc_cntl,
+};
+
+/// Charge and discharge cycles of a measurement
+const MEAS_CYCLES: u16 = 500;
+/// RTC_SLOW_CLK cycles between two scans
+const SLEEP_CYCLES: u16 = 0xf;
+/// RTC_FAST_CLK cycles between powering a pad and measuring it
+const XPD_WAIT: u8 = 0xff;
+
+/// `TOUCH_DATA_SEL` for the raw, benchmark and smoothed readings
+const DATA_RAW: u8 = 0;
+const DATA_BENCHMARK: u8 = 2;
+const DATA_SMOOTH: u8 = 3;
+
+/// The data fields of SAR_TOUCH_THRESn and SAR_TOUCH_STATUSn
+const DATA_MASK: u32 = 0x3f_ffff;
+
+/// Pad driving the shield electrode of a [GuardRing]
+pub const SHIELD_PAD: u8 = 14;
+
+/// A pin usable as a touch pad
+pub trait TouchPin {
+    /// Number of the pad, 1 to 14
+    const PAD: u8;
+}
+
+macro_rules! impl_touch_pin {
+    ($( ($pin:ident, $pad:literal) ),+) => {
+        $(
+            impl TouchPin for crate::gpio::$pin<crate::gpio::Analog> {
+                const PAD: u8 = $pad;
+            }
+        )+
+    };
+}
+
+impl_touch_pin!(
+    (Gpio1, 1),
+    (Gpio2, 2),
+    (Gpio3, 3),
+    (Gpio4, 4),
+    (Gpio5, 5),
+    (Gpio6, 6),
+    (Gpio7, 7),
+    (Gpio8, 8),
+    (Gpio9, 9),
+    (Gpio10, 10),
+    (Gpio11, 11),
+    (Gpio12, 12),
+    (Gpio13, 13),
+    (Gpio14, 14)
+);
+
+/// Touch sensor errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TouchError {
+    /// Not a pad from 1 to 14, or the shield pad while the guard ring is used
+    InvalidPad,
+    /// A capacitance or drive level above 7
+    InvalidLevel,
+    /// The pad hasn't been enabled with [Touch::enable_pad]
+    PadNotEnabled,
+    /// The configuration has no sleep channel
+    NoSleepChannel,
+}
+
+/// Resolution of the noise measured on `TOUCH0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DenoiseResolution {
+    Bit12 = 0,
+    Bit10 = 1,
+    Bit8  = 2,
+    Bit4  = 3,
+}
+
+/// Noise cancellation with the internal pad `TOUCH0`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Denoise {
+    pub resolution: DenoiseResolution,
+    /// Internal capacitance of `TOUCH0`, from 0 (5 pF) to 7 (14.8 pF), to
+    /// be matched to the capacitance of the pads
+    pub capacitance: u8,
+}
+
+/// Water rejection with a guard ring and a shield electrode on `TOUCH14`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GuardRing {
+    /// Pad of the guard ring
+    pub pad: u8,
+    /// Drive of the shield electrode, from 0 to 7, higher for a larger
+    /// shield
+    pub shield_drive: u8,
+}
+
+/// Filter of the benchmark
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Iir4   = 0,
+    Iir8   = 1,
+    Iir16  = 2,
+    Iir32  = 3,
+    Iir64  = 4,
+    Iir128 = 5,
+    Iir256 = 6,
+    /// Steps of a fixed size towards the reading
+    Jitter = 7,
+}
+
+/// Tracking of the benchmark
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DriftCompensation {
+    pub filter: FilterMode,
+    /// Reset the benchmark of the idle pads every this many calls of
+    /// [Touch::poll], 0 never does
+    pub rebenchmark_interval: u32,
+}
+
+impl Default for DriftCompensation {
+    fn default() -> Self {
+        DriftCompensation {
+            filter: FilterMode::Iir16,
+            rebenchmark_interval: 0,
+        }
+    }
+}
+
+/// Configuration of the touch controller, see the [module
+/// documentation](self)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TouchConfig {
+    pub denoise: Option<Denoise>,
+    pub waterproof: Option<GuardRing>,
+    /// Pad and threshold scanned in deep sleep
+    pub sleep_channel: Option<(u8, u32)>,
+    pub drift: Option<DriftCompensation>,
+}
+
+impl TouchConfig {
+    const fn check(&self) -> Result<(), TouchError> {
+        if let Some(denoise) = self.denoise {
+            if denoise.capacitance > 7 {
+                return Err(TouchError::InvalidLevel);
+            }
+        }
+
+        if let Some(guard) = self.waterproof {
+            if !is_pad(guard.pad) || guard.pad == SHIELD_PAD {
+                return Err(TouchError::InvalidPad);
+            }
+            if guard.shield_drive > 7 {
+                return Err(TouchError::InvalidLevel);
+            }
+        }
+
+        if let Some((pad, _)) = self.sleep_channel {
+            if !is_pad(pad) || (self.waterproof.is_some() && pad == SHIELD_PAD) {
+                return Err(TouchError::InvalidPad);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+const fn is_pad(pad: u8) -> bool {
+    matches!(pad, 1..=14)
+}
+
+// Guard ring and sleep channel pads, capacitance and drive levels
+const _: () = {
+    const GUARD_RING: GuardRing = GuardRing {
+        pad: 1,
+        shield_drive: 2,
+    };
+
+    assert!(matches!(
+        TouchConfig {
+            denoise: Some(Denoise {
+                resolution: DenoiseResolution::Bit12,
+                capacitance: 4,
+            }),
+            waterproof: Some(GUARD_RING),
+            sleep_channel: Some((2, 1000)),
+            drift: Some(DriftCompensation {
+                filter: FilterMode::Iir16,
+                rebenchmark_interval: 100,
+            }),
+        }
+        .check(),
+        Ok(())
+    ));
+
+    // the shield pad is taken by the guard ring
+    assert!(matches!(
+        TouchConfig {
+            denoise: None,
+            waterproof: Some(GuardRing {
+                pad: SHIELD_PAD,
+                shield_drive: 2,
+            }),
+            sleep_channel: None,
+            drift: None,
+        }
+        .check(),
+        Err(TouchError::InvalidPad)
+    ));
+    assert!(matches!(
+        TouchConfig {
+            denoise: None,
+            waterproof: Some(GUARD_RING),
+            sleep_channel: Some((SHIELD_PAD, 1000)),
+            drift: None,
+        }
+        .check(),
+        Err(TouchError::InvalidPad)
+    ));
+    // and a regular pad without one
+    assert!(matches!(
+        TouchConfig {
+            denoise: None,
+            waterproof: None,
+            sleep_channel: Some((SHIELD_PAD, 1000)),
+            drift: None,
+        }
+        .check(),
+        Ok(())
+    ));
+
+    let mut pad = 0;
+    while pad <= 15 {
+        let config = TouchConfig {
+            denoise: None,
+            waterproof: None,
+            sleep_channel: Some((pad, 1000)),
+            drift: None,
+        };
+        assert!(config.check().is_ok() == matches!(pad, 1..=14));
+        pad += 1;
+    }
+
+    assert!(matches!(
+        TouchConfig {
+            denoise: Some(Denoise {
+                resolution: DenoiseResolution::Bit8,
+                capacitance: 8,
+            }),
+            waterproof: None,
+            sleep_channel: None,
+            drift: None,
+        }
+        .check(),
+        Err(TouchError::InvalidLevel)
+    ));
+    assert!(matches!(
+        TouchConfig {
+            denoise: None,
+            waterproof: Some(GuardRing {
+                pad: 1,
+                shield_drive: 8,
+            }),
+            sleep_channel: None,
+            drift: None,
+        }
+        .check(),
+        Err(TouchError::InvalidLevel)
+    ));
+};
+
+/// Touch sensor driver
+pub struct Touch {
+    config: TouchConfig,
+    /// Bit mask of the enabled pads
+    enabled: u16,
+    polls: u32,
+}
+
+impl Touch {
+    /// Configure the touch controller and start scanning, no pad is enabled
+    pub fn new(_touch: TOUCH, config: TouchConfig) -> Result<Self, TouchError> {
+        config.check()?;
+
+        let rtc_cntl = unsafe { &*RTC_CNTL::PTR };
+        let sens = unsafe { &*SENS::PTR };
+
+        stop_fsm();
+        set_scan_mask(0);
+        sens.sar_touch_conf
+            .modify(|_, w| w.touch_status_clr().set_bit());
+
+        rtc_cntl.touch_ctrl1.modify(|_, w| unsafe {
+            w.touch_meas_num()
+                .bits(MEAS_CYCLES)
+                .touch_sleep_cycles()
+                .bits(SLEEP_CYCLES)
+        });
+        rtc_cntl
+            .touch_ctrl2
+            .modify(|_, w| unsafe { w.touch_xpd_wait().bits(XPD_WAIT) });
+        // pads which aren't measured are tied to GND
+        rtc_cntl
+            .touch_scan_ctrl
+            .modify(|_, w| w.touch_inactive_connection().set_bit());
+
+        match config.denoise {
+            Some(denoise) => {
+                rtc_cntl
+                    .touch_ctrl2
+                    .modify(|_, w| unsafe { w.touch_refc().bits(denoise.capacitance) });
+                rtc_cntl.touch_scan_ctrl.modify(|_, w| unsafe {
+                    w.touch_denoise_res()
+                        .bits(denoise.resolution as u8)
+                        .touch_denoise_en()
+                        .set_bit()
+                });
+            }
+            None => rtc_cntl
+                .touch_scan_ctrl
+                .modify(|_, w| w.touch_denoise_en().clear_bit()),
+        }
+
+        match config.waterproof {
+            Some(guard) => rtc_cntl.touch_scan_ctrl.modify(|_, w| unsafe {
+                w.touch_out_ring()
+                    .bits(guard.pad)
+                    .touch_bufdrv()
+                    .bits(guard.shield_drive)
+                    .touch_shield_pad_en()
+                    .set_bit()
+            }),
+            None => rtc_cntl
+                .touch_scan_ctrl
+                .modify(|_, w| w.touch_shield_pad_en().clear_bit()),
+        }
+
+        match config.drift {
+            Some(drift) => rtc_cntl.touch_filter_ctrl.modify(|_, w| unsafe {
+                w.touch_filter_mode()
+                    .bits(drift.filter as u8)
+                    .touch_debounce()
+                    .bits(1)
+                    .touch_noise_thres()
+                    .bits(0)
+                    .touch_jitter_step()
+                    .bits(4)
+                    .touch_smooth_lvl()
+                    .bits(1)
+                    .touch_filter_en()
+                    .set_bit()
+            }),
+            None => rtc_cntl
+                .touch_filter_ctrl
+                .modify(|_, w| w.touch_filter_en().clear_bit()),
+        }
+
+        if let Some((pad, threshold)) = config.sleep_channel {
+            rtc_cntl.touch_slp_thres.modify(|_, w| unsafe {
+                w.touch_slp_pad()
+                    .bits(pad)
+                    .touch_slp_th()
+                    .bits(threshold & DATA_MASK)
+            });
+        }
+
+        start_fsm();
+
+        Ok(Self {
+            config,
+            enabled: 0,
+            polls: 0,
+        })
+    }
+
+    /// Scan `pin` and flag it as touched when its reading rises by more
+    /// than `threshold` above its benchmark
+    pub fn enable_pad<P: TouchPin>(&mut self, _pin: &P, threshold: u32) -> Result<(), TouchError> {
+        if self.config.waterproof.is_some() && P::PAD == SHIELD_PAD {
+            return Err(TouchError::InvalidPad);
+        }
+
+        set_threshold(P::PAD, threshold);
+        self.enabled |= 1 << P::PAD;
+        set_scan_mask(self.enabled);
+        reset_benchmark(1 << P::PAD);
+
+        Ok(())
+    }
+
+    /// Change the threshold of `pin`
+    pub fn set_threshold<P: TouchPin>(&mut self, _pin: &P, threshold: u32) {
+        set_threshold(P::PAD, threshold);
+    }
+
+    /// Whether `pin` is touched
+    pub fn is_touched<P: TouchPin>(&self, _pin: &P) -> bool {
+        active_mask() & 1 << P::PAD != 0
+    }
+
+    /// Latest unfiltered reading of `pin`
+    pub fn raw<P: TouchPin>(&mut self, _pin: &P) -> u32 {
+        read(P::PAD, DATA_RAW)
+    }
+
+    /// Latest smoothed reading of `pin`
+    pub fn smooth<P: TouchPin>(&mut self, _pin: &P) -> u32 {
+        read(P::PAD, DATA_SMOOTH)
+    }
+
+    /// Untouched reading of `pin`, which the threshold is relative to
+    pub fn benchmark<P: TouchPin>(&mut self, _pin: &P) -> u32 {
+        read(P::PAD, DATA_BENCHMARK)
+    }
+
+    /// Latest noise measured on `TOUCH0`, see [Denoise]
+    pub fn denoise_data(&self) -> u32 {
+        let sens = unsafe { &*SENS::PTR };
+        sens.sar_touch_denoise.read().touch_denoise_data().bits()
+    }
+
+    /// Bit mask of the touched pads, bit `n` is `TOUCHn`
+    ///
+    /// To be called at a fixed rate with [DriftCompensation], every
+    /// `rebenchmark_interval` calls it resets the benchmark of the enabled
+    /// pads which aren't touched.
+    pub fn poll(&mut self) -> u16 {
+        let active = active_mask();
+
+        if let Some(drift) = self.config.drift {
+            if drift.rebenchmark_interval != 0 {
+                self.polls += 1;
+                if self.polls >= drift.rebenchmark_interval {
+                    self.polls = 0;
+                    reset_benchmark(self.enabled & !active);
+                }
+            }
+        }
+
+        active
+    }
+
+    /// Reset the benchmark of the enabled pads which aren't touched to their
+    /// current reading
+    pub fn rebenchmark(&mut self) {
+        reset_benchmark(self.enabled & !active_mask());
+    }
+
+    /// Enable or disable the sleep channel as a wake source for
+    /// `Rtc::sleep_light` and `Rtc::sleep_deep`
+    pub fn enable_wakeup(&mut self, enable: bool) -> Result<(), TouchError> {
+        let (pad, _) = self
+            .config
+            .sleep_channel
+            .ok_or(TouchError::NoSleepChannel)?;
+        if self.enabled & 1 << pad == 0 {
+            return Err(TouchError::PadNotEnabled);
+        }
+
+        rtc_cntl::set_touch_wake_source(enable);
+        Ok(())
+    }
+}
+
+/// Pad which woke the chip, the sleep channel
+pub(crate) fn wakeup_pad() -> u8 {
+    let rtc_cntl = unsafe { &*RTC_CNTL::PTR };
+    rtc_cntl.touch_slp_thres.read().touch_slp_pad().bits()
+}
+
+/// Scan only the sleep channel, to be called right before deep sleep
+pub(crate) fn prepare_deep_sleep() {
+    stop_fsm();
+    set_scan_mask(1 << wakeup_pad());
+    start_fsm();
+}
+
+fn stop_fsm() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::PTR };
+    rtc_cntl
+        .touch_ctrl2
+        .modify(|_, w| w.touch_slp_timer_en().clear_bit());
+}
+
+/// Start scanning, triggered by the touch timer
+fn start_fsm() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::PTR };
+
+    rtc_cntl.touch_ctrl2.modify(|_, w| {
+        w.touch_start_en()
+            .clear_bit()
+            .touch_start_force()
+            .clear_bit()
+            .touch_clkgate_en()
+            .set_bit()
+    });
+    // the timer always gets a measurement done after this
+    rtc_cntl
+        .touch_ctrl2
+        .modify(|_, w| unsafe { w.touch_timer_force_done().bits(3) });
+    rtc_cntl
+        .touch_ctrl2
+        .modify(|_, w| unsafe { w.touch_timer_force_done().bits(0) });
+    rtc_cntl
+        .touch_ctrl2
+        .modify(|_, w| w.touch_slp_timer_en().set_bit());
+}
+
+fn set_scan_mask(mask: u16) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::PTR };
+    let sens = unsafe { &*SENS::PTR };
+
+    rtc_cntl
+        .touch_scan_ctrl
+        .modify(|_, w| unsafe { w.touch_scan_pad_map().bits(mask) });
+    sens.sar_touch_conf
+        .modify(|_, w| unsafe { w.touch_outen().bits(mask) });
+}
+
+fn active_mask() -> u16 {
+    let sens = unsafe { &*SENS::PTR };
+    sens.sar_touch_chn_st.read().touch_pad_active().bits()
+}
+
+/// Reset the benchmark of the pads in `mask` to their current reading
+fn reset_benchmark(mask: u16) {
+    let sens = unsafe { &*SENS::PTR };
+    sens.sar_touch_chn_st
+        .write(|w| unsafe { w.touch_channel_clr().bits(mask) });
+}
+
+// SAR_TOUCH_THRES1 to SAR_TOUCH_THRES14 and SAR_TOUCH_STATUS1 to
+// SAR_TOUCH_STATUS14 are consecutive registers
+
+fn set_threshold(pad: u8, threshold: u32) {
+    let sens = unsafe { &*SENS::PTR };
+    let first = &sens.sar_touch_thres1 as *const _ as *mut u32;
+
+    unsafe {
+        first
+            .add(pad as usize - 1)
+            .write_volatile(threshold & DATA_MASK)
+    };
+}
+
+fn read(pad: u8, data: u8) -> u32 {
+    let sens = unsafe { &*SENS::PTR };
+    let first = &sens.sar_touch_status1 as *const _ as *const u32;
+
+    sens.sar_touch_conf
+        .modify(|_, w| unsafe { w.touch_data_sel().bits(data) });
+    unsafe { first.add(pad as usize - 1).read_volatile() & DATA_MASK }
+}
@@ -0,0 +1,208 @@
+//! Chip identification
+//!
+//! Information about the chip the application is running on, assembled from
+//! the eFuses and system registers. Useful for binaries which have to handle
+//! different revisions or variants of the same chip at runtime.
+//!
+//! ```no_run
+//! // prints e.g. "esp32c3 (revision v0.3)"
+//! println!("{}", chip::info());
+//! ```
+
+use core::fmt;
+
+use fugit::HertzU32;
+
+use crate::{efuse::Efuse, pac::RTC_CNTL};
+
+/// Chip model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChipModel {
+    Esp32,
+    Esp32c2,
+    Esp32c3,
+    Esp32s2,
+    Esp32s3,
+}
+
+impl fmt::Display for ChipModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ChipModel::Esp32 => "esp32",
+            ChipModel::Esp32c2 => "esp32c2",
+            ChipModel::Esp32c3 => "esp32c3",
+            ChipModel::Esp32s2 => "esp32s2",
+            ChipModel::Esp32s3 => "esp32s3",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Chip revision
+///
+/// Ordered, so it can be compared against the first revision that fixed a
+/// silicon issue, e.g. `revision() >= Revision { major: 0, minor: 3 }`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Revision {
+    pub major: u8,
+    pub minor: u8,
+}
+
+impl fmt::Display for Revision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}.{}", self.major, self.minor)
+    }
+}
+
+/// Features available on this particular chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChipFeatures {
+    /// Number of usable CPU cores
+    pub cores: u8,
+    /// Wi-Fi
+    pub wifi: bool,
+    /// Bluetooth Classic
+    pub bluetooth_classic: bool,
+    /// Bluetooth Low Energy. On the ESP32 the Bluetooth controller can be
+    /// disabled in the eFuses, which is checked. The ESP32-C2, ESP32-C3 and
+    /// ESP32-S3 have no eFuse to disable it, so every package has BLE, the
+    /// ESP32-S2 has no Bluetooth controller.
+    pub ble: bool,
+    /// Size of the flash embedded in the chip package in MB, if any
+    pub embedded_flash: Option<u32>,
+    /// Size of the PSRAM embedded in the chip package in MB, if any
+    pub embedded_psram: Option<u32>,
+    /// Frequency of the external crystal
+    pub xtal_frequency: HertzU32,
+}
+
+/// Model, revision and features of the chip
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChipInfo {
+    pub model: ChipModel,
+    pub revision: Revision,
+    pub features: ChipFeatures,
+}
+
+impl fmt::Display for ChipInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (revision {})", self.model, self.revision)
+    }
+}
+
+/// Returns the model of the chip.
+pub fn model() -> ChipModel {
+    #[cfg(esp32)]
+    let model = ChipModel::Esp32;
+    #[cfg(esp32c2)]
+    let model = ChipModel::Esp32c2;
+    #[cfg(esp32c3)]
+    let model = ChipModel::Esp32c3;
+    #[cfg(esp32s2)]
+    let model = ChipModel::Esp32s2;
+    #[cfg(esp32s3)]
+    let model = ChipModel::Esp32s3;
+
+    model
+}
+
+/// Returns the revision of the chip.
+pub fn revision() -> Revision {
+    Revision {
+        major: Efuse::get_major_chip_version(),
+        minor: Efuse::get_minor_chip_version(),
+    }
+}
+
+/// Returns the features available on the chip.
+pub fn features() -> ChipFeatures {
+    #[cfg(esp32)]
+    let features = {
+        use crate::efuse::ChipType;
+
+        let bluetooth = Efuse::is_bluetooth_enabled();
+        // the D2WD and PICO packages come with the flash in the package
+        let embedded_flash = match Efuse::get_chip_type() {
+            ChipType::Esp32D2wdq5 | ChipType::Esp32Picod2 => Some(2),
+            ChipType::Esp32Picod4 => Some(4),
+            _ => None,
+        };
+
+        ChipFeatures {
+            cores: Efuse::get_core_count() as u8,
+            wifi: true,
+            bluetooth_classic: bluetooth,
+            ble: bluetooth,
+            embedded_flash,
+            embedded_psram: None,
+            xtal_frequency: xtal_frequency(),
+        }
+    };
+    #[cfg(esp32c2)]
+    let features = ChipFeatures {
+        cores: 1,
+        wifi: true,
+        bluetooth_classic: false,
+        ble: true,
+        // the size of the embedded flash isn't recorded in the eFuses
+        embedded_flash: None,
+        embedded_psram: None,
+        xtal_frequency: xtal_frequency(),
+    };
+    #[cfg(esp32c3)]
+    let features = ChipFeatures {
+        cores: 1,
+        wifi: true,
+        bluetooth_classic: false,
+        ble: true,
+        embedded_flash: Efuse::get_embedded_flash_size(),
+        embedded_psram: None,
+        xtal_frequency: xtal_frequency(),
+    };
+    #[cfg(esp32s2)]
+    let features = ChipFeatures {
+        cores: 1,
+        wifi: true,
+        bluetooth_classic: false,
+        ble: false,
+        embedded_flash: Efuse::get_embedded_flash_size(),
+        embedded_psram: Efuse::get_embedded_psram_size(),
+        xtal_frequency: xtal_frequency(),
+    };
+    #[cfg(esp32s3)]
+    let features = ChipFeatures {
+        cores: Efuse::get_core_count() as u8,
+        wifi: true,
+        bluetooth_classic: false,
+        ble: true,
+        embedded_flash: Efuse::get_embedded_flash_size(),
+        embedded_psram: Efuse::get_embedded_psram_size(),
+        xtal_frequency: xtal_frequency(),
+    };
+
+    features
+}
+
+/// Returns model, revision and features of the chip.
+pub fn info() -> ChipInfo {
+    ChipInfo {
+        model: model(),
+        revision: revision(),
+        features: features(),
+    }
+}
+
+// The bootloader stores the crystal frequency (in MHz) in the lower and upper
+// half of RTC_CNTL_STORE4. Fall back to 40 MHz if it didn't.
+fn xtal_frequency() -> HertzU32 {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let value = rtc_cntl.store4.read().bits();
+
+    let low = value & 0xffff;
+    let high = value >> 16;
+    if low == high && low != 0 && low != 0xffff {
+        HertzU32::MHz(low)
+    } else {
+        HertzU32::MHz(40)
+    }
+}
@@ -1,7 +1,40 @@
 //! # Clock Control
+//!
+//! ## Running the CPU from the RC oscillator
+//!
+//! [CpuClock::RcFast] clocks the CPU and the APB from the internal fast RC
+//! oscillator (`RC_FAST_CLK`, 8.5 MHz on the ESP32 and ESP32-S2, 17.5 MHz on
+//! the other chips) instead of the PLL, for a device which mostly sleeps and
+//! only does little work while awake.
+//!
+//! On the ESP32, ESP32-C2 and ESP32-C3 the PLL is powered down meanwhile,
+//! which also stops everything else clocked from it, like the radio, the
+//! MCPWM of the ESP32 and the USB-Serial-JTAG of the ESP32-C3. On the
+//! ESP32-S2 and ESP32-S3 the PLL keeps running.
+//!
+//! The frequency of the oscillator differs by several percent from chip to
+//! chip and changes with temperature and supply voltage. It is measured
+//! against the XTAL every time [CpuClock::RcFast] is configured, [Clocks]
+//! holds the measured frequency and drivers derive their dividers from it.
+//! The oscillator keeps drifting afterwards and the integer baud rate
+//! dividers get coarse at high baud rates (8.5 MHz / 115200 baud is 73.8,
+//! rounded to 73, 1.1 % off), so a UART is only reliable at low baud rates
+//! like 9600 baud. Timers clocked from the APB are off by the same drift.
+//!
+//! [Clocks::set_cpu_clock] switches between the oscillator and the PLL at
+//! runtime:
+//!
+//! ```no_run
+//! clocks.set_cpu_clock(CpuClock::RcFast, &mut [&mut serial, &mut delay]);
+//! // little work
+//! clocks.set_cpu_clock(CpuClock::Clock160MHz, &mut [&mut serial, &mut delay]);
+//! ```
 use fugit::HertzU32;
 
-use crate::system::SystemClockControl;
+use crate::{
+    rtc_cntl::{RtcClock, RtcFastClock},
+    system::SystemClockControl,
+};
 
 #[cfg_attr(esp32, path = "clocks_ll/esp32.rs")]
 #[cfg_attr(esp32c2, path = "clocks_ll/esp32c2.rs")]
@@ -32,6 +65,8 @@ pub enum CpuClock {
     Clock160MHz,
     #[cfg(not(any(esp32c2, esp32c3)))]
     Clock240MHz,
+    /// The internal fast RC oscillator, see the [module documentation](self)
+    RcFast,
 }
 
 #[allow(dead_code)]
@@ -45,6 +80,7 @@ impl Clock for CpuClock {
             CpuClock::Clock160MHz => HertzU32::MHz(160),
             #[cfg(not(any(esp32c2, esp32c3)))]
             CpuClock::Clock240MHz => HertzU32::MHz(240),
+            CpuClock::RcFast => RtcFastClock::RtcFastClock8m.frequency(),
         }
     }
 }
@@ -59,7 +95,7 @@ pub(crate) enum XtalClock {
     #[cfg(any(esp32c3, esp32s3))]
     RtcXtalFreq32M,
     RtcXtalFreq40M,
-    RtcXtalFreqOther(u32),
+    RtcXtalFreqOther(HertzU32),
 }
 
 impl Clock for XtalClock {
@@ -72,7 +108,7 @@ impl Clock for XtalClock {
             #[cfg(any(esp32c3, esp32s3))]
             XtalClock::RtcXtalFreq32M => HertzU32::MHz(32),
             XtalClock::RtcXtalFreq40M => HertzU32::MHz(40),
-            XtalClock::RtcXtalFreqOther(mhz) => HertzU32::MHz(*mhz),
+            XtalClock::RtcXtalFreqOther(frequency) => *frequency,
         }
     }
 }
@@ -90,7 +126,7 @@ pub(crate) enum PllClock {
 pub(crate) enum ApbClock {
     ApbFreq40MHz,
     ApbFreq80MHz,
-    ApbFreqOther(u32),
+    ApbFreqOther(HertzU32),
 }
 
 impl Clock for ApbClock {
@@ -98,15 +134,16 @@ impl Clock for ApbClock {
         match self {
             ApbClock::ApbFreq40MHz => HertzU32::MHz(40),
             ApbClock::ApbFreq80MHz => HertzU32::MHz(80),
-            ApbClock::ApbFreqOther(mhz) => HertzU32::MHz(*mhz),
+            ApbClock::ApbFreqOther(frequency) => *frequency,
         }
     }
 }
 
-/// Frozen clock frequencies
+/// Clock frequencies
 ///
-/// The existence of this value indicates that the clock configuration can no
-/// longer be changed
+/// The frequencies the clocks were configured with. They only change with
+/// [Clocks::set_cpu_clock], which needs exclusive access, so drivers holding
+/// a reference to this value see the frequencies they were created with.
 pub struct Clocks {
     _private: (),
     pub cpu_clock: HertzU32,
@@ -141,6 +178,37 @@ impl Clocks {
     }
 }
 
+impl Clocks {
+    /// Changes the CPU clock speed at runtime.
+    ///
+    /// Drivers derive their dividers from the clock frequencies when they are
+    /// created. Every driver still in use has to be passed in `listeners` to
+    /// be notified of the new frequencies so it can re-derive them, e.g. to
+    /// keep a UART at the same baud rate. Drivers which keep a reference to
+    /// `Clocks` (like [crate::ledc::LEDC]) prevent the clocks from being
+    /// changed while they exist.
+    pub fn set_cpu_clock(
+        &mut self,
+        cpu_clock_speed: CpuClock,
+        listeners: &mut [&mut dyn ClockListener],
+    ) {
+        let raw_clocks = ClockControl::configure_clocks(cpu_clock_speed);
+        *self = Clocks::from_raw_clocks(raw_clocks);
+
+        for listener in listeners.iter_mut() {
+            listener.clocks_changed(self);
+        }
+    }
+}
+
+/// Implemented by drivers which derive dividers from the clock frequencies
+///
+/// See [Clocks::set_cpu_clock].
+pub trait ClockListener {
+    /// Called after the clock frequencies changed
+    fn clocks_changed(&mut self, clocks: &Clocks);
+}
+
 #[doc(hidden)]
 pub struct RawClocks {
     pub cpu_clock: HertzU32,
@@ -192,6 +260,13 @@ impl ClockControl {
     /// Configure the CPU clock speed.
     #[allow(unused)]
     pub fn configure(clock_control: SystemClockControl, cpu_clock_speed: CpuClock) -> ClockControl {
+        ClockControl {
+            _private: (),
+            desired_rates: Self::configure_clocks(cpu_clock_speed),
+        }
+    }
+
+    fn configure_clocks(cpu_clock_speed: CpuClock) -> RawClocks {
         // like NuttX use 40M hardcoded - if it turns out to be a problem
         // we will take care then
         let xtal_freq = XtalClock::RtcXtalFreq40M;
@@ -199,6 +274,20 @@ impl ClockControl {
             CpuClock::Clock80MHz => PllClock::Pll320MHz,
             CpuClock::Clock160MHz => PllClock::Pll320MHz,
             CpuClock::Clock240MHz => PllClock::Pll480MHz,
+            CpuClock::RcFast => {
+                let rc_fast_freq = RtcClock::enable_8m_for_cpu();
+                clocks_ll::esp32_rtc_update_to_rc_fast(rc_fast_freq);
+                clocks_ll::esp32_rtc_bbpll_disable();
+
+                return RawClocks {
+                    cpu_clock: rc_fast_freq,
+                    apb_clock: rc_fast_freq,
+                    xtal_clock: xtal_freq.frequency(),
+                    i2c_clock: rc_fast_freq,
+                    // clocked from the PLL, which is powered down
+                    pwm_clock: HertzU32::MHz(160),
+                };
+            }
         };
 
         clocks_ll::esp32_rtc_update_to_xtal(xtal_freq, 1);
@@ -206,18 +295,15 @@ impl ClockControl {
         clocks_ll::esp32_rtc_bbpll_configure(xtal_freq, pll_freq);
         clocks_ll::set_cpu_freq(cpu_clock_speed);
 
-        ClockControl {
-            _private: (),
-            desired_rates: RawClocks {
-                cpu_clock: cpu_clock_speed.frequency(),
-                apb_clock: HertzU32::MHz(80),
-                xtal_clock: HertzU32::MHz(40),
-                i2c_clock: HertzU32::MHz(40),
-                // The docs are unclear here. pwm_clock seems to be tied to clocks.apb_clock
-                // while simultaneously being fixed at 160 MHz.
-                // Testing showed 160 MHz to be correct for current clock configurations.
-                pwm_clock: HertzU32::MHz(160),
-            },
+        RawClocks {
+            cpu_clock: cpu_clock_speed.frequency(),
+            apb_clock: HertzU32::MHz(80),
+            xtal_clock: HertzU32::MHz(40),
+            i2c_clock: HertzU32::MHz(40),
+            // The docs are unclear here. pwm_clock seems to be tied to clocks.apb_clock
+            // while simultaneously being fixed at 160 MHz.
+            // Testing showed 160 MHz to be correct for current clock configurations.
+            pwm_clock: HertzU32::MHz(160),
         }
     }
 }
@@ -241,12 +327,32 @@ impl ClockControl {
     /// Configure the CPU clock speed.
     #[allow(unused)]
     pub fn configure(clock_control: SystemClockControl, cpu_clock_speed: CpuClock) -> ClockControl {
+        ClockControl {
+            _private: (),
+            desired_rates: Self::configure_clocks(cpu_clock_speed),
+        }
+    }
+
+    fn configure_clocks(cpu_clock_speed: CpuClock) -> RawClocks {
         let apb_freq;
         let xtal_freq = XtalClock::RtcXtalFreq40M;
         let pll_freq = PllClock::Pll480MHz;
 
+        if matches!(cpu_clock_speed, CpuClock::RcFast) {
+            let rc_fast_freq = RtcClock::enable_8m_for_cpu();
+            clocks_ll::esp32c2_rtc_update_to_rc_fast(rc_fast_freq);
+            clocks_ll::esp32c2_rtc_bbpll_disable();
+
+            return RawClocks {
+                cpu_clock: rc_fast_freq,
+                apb_clock: rc_fast_freq,
+                xtal_clock: xtal_freq.frequency(),
+                i2c_clock: HertzU32::MHz(40),
+            };
+        }
+
         if cpu_clock_speed.mhz() <= xtal_freq.mhz() {
-            apb_freq = ApbClock::ApbFreqOther(cpu_clock_speed.mhz());
+            apb_freq = ApbClock::ApbFreqOther(cpu_clock_speed.frequency());
             clocks_ll::esp32c2_rtc_update_to_xtal(xtal_freq, 1);
             clocks_ll::esp32c2_rtc_apb_freq_update(apb_freq);
         } else {
@@ -257,14 +363,11 @@ impl ClockControl {
             clocks_ll::esp32c2_rtc_apb_freq_update(apb_freq);
         }
 
-        ClockControl {
-            _private: (),
-            desired_rates: RawClocks {
-                cpu_clock: cpu_clock_speed.frequency(),
-                apb_clock: apb_freq.frequency(),
-                xtal_clock: xtal_freq.frequency(),
-                i2c_clock: HertzU32::MHz(40),
-            },
+        RawClocks {
+            cpu_clock: cpu_clock_speed.frequency(),
+            apb_clock: apb_freq.frequency(),
+            xtal_clock: xtal_freq.frequency(),
+            i2c_clock: HertzU32::MHz(40),
         }
     }
 }
@@ -288,12 +391,32 @@ impl ClockControl {
     /// Configure the CPU clock speed.
     #[allow(unused)]
     pub fn configure(clock_control: SystemClockControl, cpu_clock_speed: CpuClock) -> ClockControl {
+        ClockControl {
+            _private: (),
+            desired_rates: Self::configure_clocks(cpu_clock_speed),
+        }
+    }
+
+    fn configure_clocks(cpu_clock_speed: CpuClock) -> RawClocks {
         let apb_freq;
         let xtal_freq = XtalClock::RtcXtalFreq40M;
         let pll_freq = PllClock::Pll480MHz;
 
+        if matches!(cpu_clock_speed, CpuClock::RcFast) {
+            let rc_fast_freq = RtcClock::enable_8m_for_cpu();
+            clocks_ll::esp32c3_rtc_update_to_rc_fast(rc_fast_freq);
+            clocks_ll::esp32c3_rtc_bbpll_disable();
+
+            return RawClocks {
+                cpu_clock: rc_fast_freq,
+                apb_clock: rc_fast_freq,
+                xtal_clock: xtal_freq.frequency(),
+                i2c_clock: HertzU32::MHz(40),
+            };
+        }
+
         if cpu_clock_speed.mhz() <= xtal_freq.mhz() {
-            apb_freq = ApbClock::ApbFreqOther(cpu_clock_speed.mhz());
+            apb_freq = ApbClock::ApbFreqOther(cpu_clock_speed.frequency());
             clocks_ll::esp32c3_rtc_update_to_xtal(xtal_freq, 1);
             clocks_ll::esp32c3_rtc_apb_freq_update(apb_freq);
         } else {
@@ -304,14 +427,11 @@ impl ClockControl {
             clocks_ll::esp32c3_rtc_apb_freq_update(apb_freq);
         }
 
-        ClockControl {
-            _private: (),
-            desired_rates: RawClocks {
-                cpu_clock: cpu_clock_speed.frequency(),
-                apb_clock: apb_freq.frequency(),
-                xtal_clock: xtal_freq.frequency(),
-                i2c_clock: HertzU32::MHz(40),
-            },
+        RawClocks {
+            cpu_clock: cpu_clock_speed.frequency(),
+            apb_clock: apb_freq.frequency(),
+            xtal_clock: xtal_freq.frequency(),
+            i2c_clock: HertzU32::MHz(40),
         }
     }
 }
@@ -335,16 +455,32 @@ impl ClockControl {
     /// Configure the CPU clock speed.
     #[allow(unused)]
     pub fn configure(clock_control: SystemClockControl, cpu_clock_speed: CpuClock) -> ClockControl {
-        clocks_ll::set_cpu_clock(cpu_clock_speed);
-
         ClockControl {
             _private: (),
-            desired_rates: RawClocks {
-                cpu_clock: cpu_clock_speed.frequency(),
-                apb_clock: HertzU32::MHz(80),
+            desired_rates: Self::configure_clocks(cpu_clock_speed),
+        }
+    }
+
+    fn configure_clocks(cpu_clock_speed: CpuClock) -> RawClocks {
+        if matches!(cpu_clock_speed, CpuClock::RcFast) {
+            let rc_fast_freq = RtcClock::enable_8m_for_cpu();
+            clocks_ll::set_cpu_clock_rc_fast(rc_fast_freq);
+
+            return RawClocks {
+                cpu_clock: rc_fast_freq,
+                apb_clock: rc_fast_freq,
                 xtal_clock: HertzU32::MHz(40),
-                i2c_clock: HertzU32::MHz(40),
-            },
+                i2c_clock: rc_fast_freq,
+            };
+        }
+
+        clocks_ll::set_cpu_clock(cpu_clock_speed);
+
+        RawClocks {
+            cpu_clock: cpu_clock_speed.frequency(),
+            apb_clock: HertzU32::MHz(80),
+            xtal_clock: HertzU32::MHz(40),
+            i2c_clock: HertzU32::MHz(40),
         }
     }
 }
@@ -369,17 +505,34 @@ impl ClockControl {
     /// Configure the CPU clock speed.
     #[allow(unused)]
     pub fn configure(clock_control: SystemClockControl, cpu_clock_speed: CpuClock) -> ClockControl {
-        clocks_ll::set_cpu_clock(cpu_clock_speed);
-
         ClockControl {
             _private: (),
-            desired_rates: RawClocks {
-                cpu_clock: cpu_clock_speed.frequency(),
-                apb_clock: HertzU32::MHz(80),
+            desired_rates: Self::configure_clocks(cpu_clock_speed),
+        }
+    }
+
+    fn configure_clocks(cpu_clock_speed: CpuClock) -> RawClocks {
+        if matches!(cpu_clock_speed, CpuClock::RcFast) {
+            let rc_fast_freq = RtcClock::enable_8m_for_cpu();
+            clocks_ll::set_cpu_clock_rc_fast(rc_fast_freq);
+
+            return RawClocks {
+                cpu_clock: rc_fast_freq,
+                apb_clock: rc_fast_freq,
                 xtal_clock: HertzU32::MHz(40),
                 i2c_clock: HertzU32::MHz(40),
                 crypto_pwm_clock: HertzU32::MHz(160),
-            },
+            };
+        }
+
+        clocks_ll::set_cpu_clock(cpu_clock_speed);
+
+        RawClocks {
+            cpu_clock: cpu_clock_speed.frequency(),
+            apb_clock: HertzU32::MHz(80),
+            xtal_clock: HertzU32::MHz(40),
+            i2c_clock: HertzU32::MHz(40),
+            crypto_pwm_clock: HertzU32::MHz(160),
         }
     }
 }
@@ -1,3 +1,5 @@
+use fugit::HertzU32;
+
 use crate::clock::{Clock, PllClock, XtalClock};
 
 const REF_CLK_FREQ: u32 = 1000000;
@@ -260,6 +262,43 @@ unsafe fn i2c_writereg_rtc(block: u32, block_hostid: u32, reg_add: u32, indata:
     rom_i2c_writereg(block, block_hostid, reg_add, indata);
 }
 
+pub(crate) fn esp32_rtc_bbpll_disable() {
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+
+    rtc_cntl.options0.modify(|_, w| {
+        w.bb_i2c_force_pd()
+            .set_bit()
+            .bbpll_force_pd()
+            .set_bit()
+            .bbpll_i2c_force_pd()
+            .set_bit()
+    });
+}
+
+pub(crate) fn esp32_rtc_update_to_rc_fast(freq: HertzU32) {
+    let apb_cntl = unsafe { &*crate::pac::APB_CTRL::ptr() };
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+
+    unsafe {
+        let value = ((freq.raw() >> 12) & UINT16_MAX) | (((freq.raw() >> 12) & UINT16_MAX) << 16);
+        // rounded up, ROM delays rather wait too long than too short
+        esp32_update_cpu_freq((freq.raw() + MHZ - 1) / MHZ);
+        // no divider from RC_FAST_CLK to APB clock
+        apb_cntl.sysclk_conf.modify(|_, w| w.pre_div_cnt().bits(0));
+
+        // switch clock source
+        rtc_cntl.clk_conf.modify(|_, w| w.soc_clk_sel().ck8m());
+        rtc_cntl
+            .store5
+            .modify(|_, w| w.scratch5().bits(value as u32));
+
+        // lower the voltage
+        rtc_cntl
+            .reg
+            .modify(|_, w| w.dig_dbias_wak().variant(DIG_DBIAS_XTAL as u8));
+    }
+}
+
 pub(crate) fn esp32_rtc_update_to_xtal(freq: XtalClock, _div: u32) {
     let apb_cntl = unsafe { &*crate::pac::APB_CTRL::ptr() };
     let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
@@ -321,6 +360,7 @@ pub(crate) fn set_cpu_freq(cpu_freq_mhz: crate::clock::CpuClock) {
             crate::clock::CpuClock::Clock80MHz => {
                 per_conf = CPU_80M;
             }
+            crate::clock::CpuClock::RcFast => unreachable!(),
         }
 
         let value = (((80 * MHZ) >> 12) & UINT16_MAX) | ((((80 * MHZ) >> 12) & UINT16_MAX) << 16);
@@ -1,3 +1,4 @@
+use fugit::HertzU32;
 use paste::paste;
 
 use crate::{
@@ -185,6 +186,36 @@ pub(crate) fn esp32c3_rtc_bbpll_enable() {
     });
 }
 
+pub(crate) fn esp32c3_rtc_bbpll_disable() {
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+
+    rtc_cntl.options0.modify(|_, w| {
+        w.bb_i2c_force_pd()
+            .set_bit()
+            .bbpll_force_pd()
+            .set_bit()
+            .bbpll_i2c_force_pd()
+            .set_bit()
+    });
+}
+
+pub(crate) fn esp32c3_rtc_update_to_rc_fast(freq: HertzU32) {
+    let system_control = unsafe { &*crate::pac::SYSTEM::ptr() };
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+    let value =
+        ((freq.raw() >> 12) & u16::MAX as u32) | (((freq.raw() >> 12) & u16::MAX as u32) << 16);
+
+    unsafe {
+        // rounded up, ROM delays rather wait too long than too short
+        ets_update_cpu_frequency((freq.raw() + 999_999) / 1_000_000);
+        // RC_FAST_CLK drives the CPU and the APB without a divider
+        system_control
+            .sysclk_conf
+            .modify(|_, w| w.pre_div_cnt().bits(0).soc_clk_sel().bits(2));
+        rtc_cntl.store5.modify(|_, w| w.scratch5().bits(value));
+    }
+}
+
 pub(crate) fn esp32c3_rtc_update_to_xtal(freq: XtalClock, _div: u32) {
     let system_control = unsafe { &*crate::pac::SYSTEM::ptr() };
 
@@ -219,6 +250,7 @@ pub(crate) fn esp32c3_rtc_freq_to_pll_mhz(cpu_clock_speed: CpuClock) {
             w.cpuperiod_sel().bits(match cpu_clock_speed {
                 CpuClock::Clock80MHz => 0,
                 CpuClock::Clock160MHz => 1,
+                CpuClock::RcFast => unreachable!(),
             })
         });
         ets_update_cpu_frequency(cpu_clock_speed.mhz());
@@ -1,4 +1,9 @@
-use crate::clock::CpuClock;
+use fugit::HertzU32;
+
+use crate::{
+    clock::{Clock, CpuClock},
+    rom::ets_update_cpu_frequency,
+};
 
 const MHZ: u32 = 1000000;
 const UINT16_MAX: u32 = 0xffff;
@@ -26,6 +31,7 @@ pub(crate) fn set_cpu_clock(cpu_clock_speed: CpuClock) {
                     CpuClock::Clock80MHz => 0,
                     CpuClock::Clock160MHz => 1,
                     CpuClock::Clock240MHz => 2,
+                    CpuClock::RcFast => unreachable!(),
                 })
         });
 
@@ -34,6 +40,7 @@ pub(crate) fn set_cpu_clock(cpu_clock_speed: CpuClock) {
                 CpuClock::Clock80MHz => DIG_DBIAS_80M_160M,
                 CpuClock::Clock160MHz => DIG_DBIAS_80M_160M,
                 CpuClock::Clock240MHz => DIG_DBIAS_240M,
+                CpuClock::RcFast => unreachable!(),
             } as u8)
         });
 
@@ -41,5 +48,26 @@ pub(crate) fn set_cpu_clock(cpu_clock_speed: CpuClock) {
         rtc_cntl
             .store5
             .modify(|_, w| w.scratch5().bits(value as u32));
+
+        ets_update_cpu_frequency(cpu_clock_speed.mhz());
+    }
+}
+
+pub(crate) fn set_cpu_clock_rc_fast(freq: HertzU32) {
+    let system_control = unsafe { &*crate::pac::SYSTEM::PTR };
+    let rtc_cntl = unsafe { &*crate::pac::RTC_CNTL::ptr() };
+
+    unsafe {
+        // rounded up, ROM delays rather wait too long than too short
+        ets_update_cpu_frequency((freq.raw() + MHZ - 1) / MHZ);
+        // RC_FAST_CLK drives the CPU and the APB without a divider
+        system_control
+            .sysclk_conf
+            .modify(|_, w| w.pre_div_cnt().bits(0).soc_clk_sel().bits(2));
+
+        let value = ((freq.raw() >> 12) & UINT16_MAX) | (((freq.raw() >> 12) & UINT16_MAX) << 16);
+        rtc_cntl
+            .store5
+            .modify(|_, w| w.scratch5().bits(value as u32));
     }
 }
@@ -1,4 +1,9 @@
-use crate::clock::CpuClock;
+use fugit::HertzU32;
+
+use crate::{
+    clock::{Clock, CpuClock},
+    rom::ets_update_cpu_frequency,
+};
 
 pub(crate) fn set_cpu_clock(cpu_clock_speed: CpuClock) {
     let system_control = unsafe { &*crate::pac::SYSTEM::PTR };
@@ -15,7 +20,23 @@ pub(crate) fn set_cpu_clock(cpu_clock_speed: CpuClock) {
                     CpuClock::Clock80MHz => 0,
                     CpuClock::Clock160MHz => 1,
                     CpuClock::Clock240MHz => 2,
+                    CpuClock::RcFast => unreachable!(),
                 })
         });
+
+        ets_update_cpu_frequency(cpu_clock_speed.mhz());
+    }
+}
+
+pub(crate) fn set_cpu_clock_rc_fast(freq: HertzU32) {
+    let system_control = unsafe { &*crate::pac::SYSTEM::PTR };
+
+    unsafe {
+        // rounded up, ROM delays rather wait too long than too short
+        ets_update_cpu_frequency((freq.raw() + 999_999) / 1_000_000);
+        // RC_FAST_CLK drives the CPU and the APB without a divider
+        system_control
+            .sysclk_conf
+            .modify(|_, w| w.pre_div_cnt().bits(0).soc_clk_sel().bits(2));
     }
 }
@@ -0,0 +1,191 @@
+//! Coordination with the radio drivers
+//!
+//! The WiFi and Bluetooth drivers live outside of the HAL (`esp-wifi`), but
+//! they share resources with the HAL drivers. Instead of writing the SENS and
+//! RTC_CNTL registers behind the HAL's back, a radio driver claims the
+//! resources it needs while the radio is active and the HAL drivers respect
+//! these claims:
+//!
+//! - [Resource::Adc2]: the RF frontend uses ADC2 for power detection. On the
+//!   ESP32 and ESP32-S2 a claim holds the [ADC2 lock](crate::analog::adc), ADC2
+//!   reads of the application return `AdcError::Adc2InUse` until it's released.
+//!   Claiming fails while a conversion of the application is in progress.
+//! - [Resource::Rc8m]: the 8 MHz RC oscillator and its divided by 256 output,
+//!   e.g. used as the Bluetooth low power clock. While claimed the clock code
+//!   doesn't turn them off and keeps them powered in sleep.
+//!
+//! Claims are counted, the WiFi and the Bluetooth driver can both claim the
+//! same resource and it's released after the last [release].
+//!
+//! ```no_run
+//! coex::claim(Resource::Adc2)?;
+//! coex::claim(Resource::Rc8m)?;
+//! coex::register(&RADIO);
+//!
+//! struct Radio;
+//!
+//! impl RadioCoexistence for Radio {
+//!     fn before_sleep(&self, mode: SleepMode) {
+//!         // stop the radio
+//!     }
+//! }
+//!
+//! static RADIO: Radio = Radio;
+//! ```
+//!
+//! The HAL notifies the registered [RadioCoexistence] before it puts the chip
+//! to sleep. Light sleep isn't entered by the HAL yet, code which enters it
+//! on its own has to call [notify_sleep] first.
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+/// A resource shared between the radio drivers and the HAL
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    /// ADC2, used by the RF frontend
+    Adc2,
+    /// The 8 MHz RC oscillator (`RTC8M_CLK`) and `RTC8M_D256_CLK`
+    Rc8m,
+}
+
+const RESOURCES: usize = 2;
+
+/// Errors of [claim] and [release]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The application currently uses the resource
+    InUse,
+    /// The resource was claimed more often than supported
+    TooManyClaims,
+    /// The resource isn't claimed
+    NotClaimed,
+}
+
+/// The sleep mode the chip is about to enter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepMode {
+    /// The CPU is stopped and resumes after wake up
+    Light,
+    /// The chip is powered down and resets on wake up
+    Deep,
+}
+
+/// Hooks of a radio driver, see [register]
+pub trait RadioCoexistence: Sync {
+    /// Called before the chip enters `mode`
+    ///
+    /// The radio has to be stopped when this returns, it can't receive or
+    /// transmit in sleep.
+    fn before_sleep(&self, mode: SleepMode);
+
+    /// Called after the chip woke up from light sleep
+    fn after_light_sleep(&self) {}
+}
+
+static CLAIMS: Mutex<Cell<[u8; RESOURCES]>> = Mutex::new(Cell::new([0; RESOURCES]));
+static RADIO: Mutex<Cell<Option<&'static dyn RadioCoexistence>>> = Mutex::new(Cell::new(None));
+
+/// Claim `resource` for the radio
+///
+/// Returns [Error::InUse] if the application currently uses it, the caller
+/// may retry later.
+pub fn claim(resource: Resource) -> Result<(), Error> {
+    critical_section::with(|cs| {
+        let mut claims = CLAIMS.borrow(cs).get();
+        let count = &mut claims[resource as usize];
+
+        if *count == u8::MAX {
+            return Err(Error::TooManyClaims);
+        }
+        if *count == 0 {
+            acquire(resource)?;
+        }
+
+        *count += 1;
+        CLAIMS.borrow(cs).set(claims);
+        Ok(())
+    })
+}
+
+/// Release a claim of `resource` taken with [claim]
+pub fn release(resource: Resource) -> Result<(), Error> {
+    critical_section::with(|cs| {
+        let mut claims = CLAIMS.borrow(cs).get();
+        let count = &mut claims[resource as usize];
+
+        if *count == 0 {
+            return Err(Error::NotClaimed);
+        }
+
+        *count -= 1;
+        let released = *count == 0;
+        // store the count first, relinquishing checks `is_claimed`
+        CLAIMS.borrow(cs).set(claims);
+
+        if released {
+            relinquish(resource);
+        }
+
+        Ok(())
+    })
+}
+
+/// Whether `resource` is claimed by a radio driver
+pub fn is_claimed(resource: Resource) -> bool {
+    critical_section::with(|cs| CLAIMS.borrow(cs).get()[resource as usize] != 0)
+}
+
+/// Register the hooks of the radio driver, replacing the ones registered
+/// before
+pub fn register(radio: &'static dyn RadioCoexistence) {
+    critical_section::with(|cs| RADIO.borrow(cs).set(Some(radio)));
+}
+
+/// Remove the hooks registered with [register]
+pub fn unregister() {
+    critical_section::with(|cs| RADIO.borrow(cs).set(None));
+}
+
+/// Notify the radio driver that the chip is about to enter `mode`
+///
+/// Called by the HAL before it puts the chip to sleep.
+pub fn notify_sleep(mode: SleepMode) {
+    if let Some(radio) = critical_section::with(|cs| RADIO.borrow(cs).get()) {
+        radio.before_sleep(mode);
+    }
+}
+
+/// Notify the radio driver that the chip woke up from light sleep
+pub fn notify_wake_up() {
+    if let Some(radio) = critical_section::with(|cs| RADIO.borrow(cs).get()) {
+        radio.after_light_sleep();
+    }
+}
+
+fn acquire(resource: Resource) -> Result<(), Error> {
+    match resource {
+        #[cfg(any(esp32, esp32s2))]
+        Resource::Adc2 => {
+            if !crate::analog::adc2_arbiter::lock() {
+                return Err(Error::InUse);
+            }
+        }
+        #[cfg(not(any(esp32, esp32s2)))]
+        Resource::Adc2 => (),
+        Resource::Rc8m => crate::rtc_cntl::RtcClock::keep_8m_alive(true),
+    }
+
+    Ok(())
+}
+
+fn relinquish(resource: Resource) {
+    match resource {
+        #[cfg(any(esp32, esp32s2))]
+        Resource::Adc2 => crate::analog::adc2_arbiter::unlock(),
+        #[cfg(not(any(esp32, esp32s2)))]
+        Resource::Adc2 => (),
+        Resource::Rc8m => crate::rtc_cntl::RtcClock::keep_8m_alive(false),
+    }
+}
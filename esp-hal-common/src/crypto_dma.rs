@@ -0,0 +1,36 @@
+//! Arbitration of the crypto accelerators
+//!
+//! The HMAC and Digital Signature peripherals use the SHA (and AES)
+//! accelerator internally, and the DMA interface of the accelerators is shared
+//! as well. A SHA operation interleaved with one of them, e.g. started from an
+//! interrupt of a different priority, corrupts the state of both.
+//!
+//! Every driver takes a [CryptoDma] guard for the duration of an operation,
+//! from the first block to reading the result. Drivers with an `nb` API return
+//! `WouldBlock` while it is held elsewhere, the others a busy error.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+static LOCKED: AtomicBool = AtomicBool::new(false);
+
+/// Exclusive access to the crypto accelerators, released on drop
+#[derive(Debug)]
+pub(crate) struct CryptoDma {
+    _private: (),
+}
+
+impl CryptoDma {
+    /// Take the accelerators if they aren't in use
+    pub(crate) fn try_acquire() -> Option<Self> {
+        LOCKED
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| Self { _private: () })
+    }
+}
+
+impl Drop for CryptoDma {
+    fn drop(&mut self) {
+        LOCKED.store(false, Ordering::Release);
+    }
+}
@@ -0,0 +1,248 @@
+//! Stack overflow detection with a hardware watchpoint
+//!
+//! A stack overflow silently overwrites whatever lies below the stack, the
+//! heap or static data, and shows up as a random crash much later.
+//! [enable_stack_guard] points a watchpoint of the current core at the
+//! bottom of its stack: the first write to the guarded bytes raises a debug
+//! exception, which panics with
+//!
+//! ```text
+//! stack overflow detected at 0x42000a3c, sp 0x3fcdf1f0 (guard 0x3fcdf1e0)
+//! ```
+//!
+//! through the regular panic handler, e.g. the one of `esp-backtrace`:
+//!
+//! ```no_run
+//! // keep 1 kB below the guard for the exception and the panic handler
+//! debug::enable_stack_guard(1024);
+//! ```
+//!
+//! The guard watches [GUARD_SIZE] bytes `margin_bytes` above the lowest
+//! address of the stack, the margin is what's left to the exception and the
+//! panic handler. A function which reserves a frame larger than that and
+//! writes below the guarded bytes before touching them isn't caught.
+//!
+//! Each core has its own guard, [enable_stack_guard] has to be called on
+//! both cores of the ESP32 and ESP32-S3, for the second one from the function
+//! passed to `CpuControl::start_app_core`. Schedulers running tasks on their
+//! own stacks move the guard with [set_stack_guard] when they switch to
+//! another task.
+//!
+//! The Xtensa cores use data breakpoint 0 (`DBREAK0`), the RISC-V cores
+//! trigger 0 of their debug module, neither is available to a debugger while
+//! the guard is enabled. The feature `stack-guard` enables this module.
+
+/// Number of bytes watched by the guard
+pub const GUARD_SIZE: usize = 32;
+
+/// Watch the stack of the current core, `margin_bytes` above its lowest
+/// address
+///
+/// The stack of the core is the one the linker script reserves for it, see
+/// [set_stack_guard] for other stacks.
+pub fn enable_stack_guard(margin_bytes: usize) {
+    set_stack_guard(stack_bottom(), margin_bytes);
+}
+
+/// Watch the stack starting at `stack_bottom` (its lowest address) on the
+/// current core, `margin_bytes` above it
+///
+/// Replaces the guard the core had, e.g. in the context switch of a
+/// scheduler before it switches to the stack of the next task.
+#[inline]
+pub fn set_stack_guard(stack_bottom: usize, margin_bytes: usize) {
+    let address = (stack_bottom + margin_bytes + GUARD_SIZE - 1) & !(GUARD_SIZE - 1);
+
+    arch::set_watchpoint(address);
+}
+
+/// Remove the guard of the current core
+#[inline]
+pub fn disable_stack_guard() {
+    arch::clear_watchpoint();
+}
+
+/// Lowest address of the stack the linker script reserves for the current
+/// core
+#[cfg(riscv)]
+fn stack_bottom() -> usize {
+    extern "C" {
+        // the stack grows down from `_stack_start` to the end of the heap
+        static _estack: u32;
+    }
+
+    unsafe { &_estack as *const u32 as usize }
+}
+
+/// Lowest address of the stack the linker script reserves for the current
+/// core
+#[cfg(xtensa)]
+fn stack_bottom() -> usize {
+    extern "C" {
+        static _stack_start_cpu0: u32;
+        #[cfg(multi_core)]
+        static _stack_start_cpu1: u32;
+    }
+
+    match crate::get_core() {
+        crate::Cpu::ProCpu => unsafe { &_stack_start_cpu0 as *const u32 as usize },
+        #[cfg(multi_core)]
+        crate::Cpu::AppCpu => unsafe { &_stack_start_cpu1 as *const u32 as usize },
+        #[cfg(not(multi_core))]
+        crate::Cpu::AppCpu => unreachable!(),
+    }
+}
+
+/// Panic if the debug exception taken at `pc` was raised by the guard
+///
+/// Called by the exception handlers with the stack pointer at the time of
+/// the exception.
+pub(crate) fn check_stack_guard(pc: usize, sp: usize) {
+    if let Some(guard) = arch::guard_hit(pc) {
+        arch::clear_watchpoint();
+        panic!(
+            "stack overflow detected at {:#010x}, sp {:#010x} (guard {:#010x})",
+            pc, sp, guard
+        );
+    }
+}
+
+#[cfg(riscv)]
+mod arch {
+    use super::GUARD_SIZE;
+
+    /// `mcontrol`, breakpoint exception on stores in machine mode to a
+    /// naturally aligned power of two sized range (NAPOT)
+    const MCONTROL_STORE: u32 = 2 << 28 | 1 << 7 | 1 << 6 | 1 << 1;
+    /// `tcontrol.mte`, enables the triggers in machine mode
+    const TCONTROL_MTE: u32 = 1 << 3;
+    const MCAUSE_BREAKPOINT: usize = 3;
+    const EBREAK: u16 = 0x0073;
+    const C_EBREAK: u16 = 0x9002;
+
+    #[inline(always)]
+    pub(super) fn set_watchpoint(address: usize) {
+        // NAPOT encodes the size in the low bits of the address
+        let tdata2 = address | (GUARD_SIZE - 1) >> 1;
+
+        unsafe {
+            core::arch::asm!(
+                "csrw 0x7a0, zero",
+                "csrw 0x7a1, zero",
+                "csrw 0x7a2, {tdata2}",
+                "csrw 0x7a1, {tdata1}",
+                "csrs 0x7a5, {mte}",
+                tdata2 = in(reg) tdata2,
+                tdata1 = in(reg) MCONTROL_STORE,
+                mte = in(reg) TCONTROL_MTE,
+                options(nostack),
+            );
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn clear_watchpoint() {
+        unsafe { core::arch::asm!("csrw 0x7a0, zero", "csrw 0x7a1, zero", options(nostack)) };
+    }
+
+    /// The guarded address if the breakpoint exception at `pc` was raised by
+    /// the trigger and not by an `ebreak`
+    pub(super) fn guard_hit(pc: usize) -> Option<usize> {
+        if riscv::register::mcause::read().code() != MCAUSE_BREAKPOINT {
+            return None;
+        }
+
+        let tdata1: u32;
+        let tdata2: usize;
+        unsafe {
+            core::arch::asm!(
+                "csrw 0x7a0, zero",
+                "csrr {0}, 0x7a1",
+                "csrr {1}, 0x7a2",
+                out(reg) tdata1,
+                out(reg) tdata2,
+                options(nostack),
+            );
+        }
+        if tdata1 & MCONTROL_STORE != MCONTROL_STORE {
+            return None;
+        }
+
+        // NOTE(unsafe) `pc` points to the instruction which trapped, the
+        // halfwords are read one by one for compressed instructions
+        let insn = unsafe { *(pc as *const u16) };
+        if insn == C_EBREAK || (insn == EBREAK && unsafe { *((pc + 2) as *const u16) } == 0x0010) {
+            return None;
+        }
+
+        Some(tdata2 & !(GUARD_SIZE - 1))
+    }
+}
+
+#[cfg(xtensa)]
+mod arch {
+    use super::GUARD_SIZE;
+
+    /// `DBREAKC` break on stores, the mask ignores the low address bits
+    /// within the guarded bytes
+    const DBREAKC_STORE: u32 = 1 << 31 | (!(GUARD_SIZE as u32 - 1) & 0x3f);
+    /// `DEBUGCAUSE.DBREAK`, raised by a data breakpoint
+    const DEBUGCAUSE_DBREAK: u32 = 1 << 2;
+    /// `DEBUGCAUSE.DBNUM`, the data breakpoint which was hit
+    const DEBUGCAUSE_DBNUM: u32 = 0xf << 8;
+
+    #[inline(always)]
+    pub(super) fn set_watchpoint(address: usize) {
+        unsafe {
+            core::arch::asm!(
+                "wsr.dbreakc0 {zero}",
+                "wsr.dbreaka0 {address}",
+                "wsr.dbreakc0 {control}",
+                "dsync",
+                zero = in(reg) 0,
+                address = in(reg) address,
+                control = in(reg) DBREAKC_STORE,
+                options(nostack),
+            );
+        }
+    }
+
+    #[inline(always)]
+    pub(super) fn clear_watchpoint() {
+        unsafe { core::arch::asm!("wsr.dbreakc0 {0}", "dsync", in(reg) 0, options(nostack)) };
+    }
+
+    /// The guarded address if the debug exception was raised by data
+    /// breakpoint 0
+    pub(super) fn guard_hit(_pc: usize) -> Option<usize> {
+        let cause: u32;
+        let control: u32;
+        let address: usize;
+        unsafe {
+            core::arch::asm!(
+                "rsr.debugcause {0}",
+                "rsr.dbreakc0 {1}",
+                "rsr.dbreaka0 {2}",
+                out(reg) cause,
+                out(reg) control,
+                out(reg) address,
+                options(nostack),
+            );
+        }
+
+        if cause & DEBUGCAUSE_DBREAK != 0
+            && cause & DEBUGCAUSE_DBNUM == 0
+            && control == DBREAKC_STORE
+        {
+            Some(address)
+        } else {
+            None
+        }
+    }
+}
+
+const _: () = {
+    assert!(GUARD_SIZE.is_power_of_two());
+    // the largest range a data breakpoint of the Xtensa cores covers
+    assert!(GUARD_SIZE <= 64);
+};
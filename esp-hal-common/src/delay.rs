@@ -1,18 +1,45 @@
 //! Delay driver
 //!
-//! Implement the `DelayMs` and `DelayUs` traits from [embedded-hal].
+//! Blocks for a [fugit] duration with [Delay::delay_for], and implements the
+//! `DelayMs` and `DelayUs` traits from [embedded-hal].
 //!
 //! [embedded-hal]: https://docs.rs/embedded-hal/latest/embedded_hal/
 
+use fugit::MicrosDurationU64;
+
 pub use self::delay::Delay;
 
+impl Delay {
+    /// Delay for `duration`, e.g. `delay.delay_for(10u32.millis())`
+    pub fn delay_for<T>(&self, duration: T)
+    where
+        T: Into<MicrosDurationU64>,
+    {
+        // in steps of a second, which fit into the cycle counts of all chips
+        const STEP_US: u64 = 1_000_000;
+
+        let mut us = duration.into().ticks();
+        while us > STEP_US {
+            self.delay_micros(STEP_US as u32);
+            us -= STEP_US;
+        }
+        self.delay_micros(us as u32);
+    }
+
+    /// Delay for the specified number of microseconds
+    #[deprecated(note = "use `Delay::delay_for`, e.g. `delay.delay_for(10u32.micros())`")]
+    pub fn delay(&self, us: u32) {
+        self.delay_micros(us);
+    }
+}
+
 impl<T> embedded_hal::blocking::delay::DelayMs<T> for Delay
 where
     T: Into<u32>,
 {
     fn delay_ms(&mut self, ms: T) {
         for _ in 0..ms.into() {
-            self.delay(1000u32);
+            self.delay_micros(1000u32);
         }
     }
 }
@@ -22,7 +49,7 @@ where
     T: Into<u32>,
 {
     fn delay_us(&mut self, us: T) {
-        self.delay(us.into());
+        self.delay_micros(us.into());
     }
 }
 
@@ -31,7 +58,7 @@ impl embedded_hal_1::delay::DelayUs for Delay {
     type Error = core::convert::Infallible;
 
     fn delay_us(&mut self, us: u32) -> Result<(), Self::Error> {
-        self.delay(us);
+        self.delay_micros(us);
 
         Ok(())
     }
@@ -41,7 +68,10 @@ impl embedded_hal_1::delay::DelayUs for Delay {
 mod delay {
     use fugit::HertzU64;
 
-    use crate::{clock::Clocks, systimer::SystemTimer};
+    use crate::{
+        clock::{ClockListener, Clocks},
+        systimer::SystemTimer,
+    };
 
     /// Uses the `SYSTIMER` peripheral for counting clock cycles, as
     /// unfortunately the ESP32-C3 does NOT implement the `mcycle` CSR, which is
@@ -63,20 +93,28 @@ mod delay {
         }
 
         /// Delay for the specified number of microseconds
-        pub fn delay(&self, us: u32) {
+        pub(super) fn delay_micros(&self, us: u32) {
             let t0 = SystemTimer::now();
             let clocks = (us as u64 * self.freq.raw()) / HertzU64::MHz(1).raw();
 
             while SystemTimer::now().wrapping_sub(t0) & SystemTimer::BIT_MASK <= clocks {}
         }
     }
+
+    impl ClockListener for Delay {
+        /// `SYSTIMER` is driven by `XTAL_CLK`, so nothing changes here, but
+        /// the frequency is re-derived anyway to keep this in one place.
+        fn clocks_changed(&mut self, clocks: &Clocks) {
+            self.freq = HertzU64::MHz((clocks.xtal_clock.to_MHz() * 10 / 25) as u64);
+        }
+    }
 }
 
 #[cfg(xtensa)]
 mod delay {
     use fugit::HertzU64;
 
-    use crate::clock::Clocks;
+    use crate::clock::{ClockListener, Clocks};
 
     /// Delay driver
     ///
@@ -89,14 +127,22 @@ mod delay {
         /// Instantiate the `Delay` driver
         pub fn new(clocks: &Clocks) -> Self {
             Self {
-                freq: HertzU64::MHz(clocks.cpu_clock.to_MHz() as u64),
+                freq: HertzU64::Hz(clocks.cpu_clock.to_Hz() as u64),
             }
         }
 
         /// Delay for the specified number of microseconds
-        pub fn delay(&self, us: u32) {
+        pub(super) fn delay_micros(&self, us: u32) {
             let clocks = (us as u64 * self.freq.raw()) / HertzU64::MHz(1).raw();
             xtensa_lx::timer::delay(clocks as u32);
         }
     }
+
+    impl ClockListener for Delay {
+        /// The CPU cycle counter runs at the CPU clock, so the cycles per
+        /// microsecond have to be updated.
+        fn clocks_changed(&mut self, clocks: &Clocks) {
+            self.freq = HertzU64::Hz(clocks.cpu_clock.to_Hz() as u64);
+        }
+    }
 }
@@ -0,0 +1,76 @@
+//! Configuration done by the second stage bootloader
+//!
+//! A direct boot image is started by the ROM without the ESP-IDF bootloader.
+//! The ROM maps the flash 1:1 into the address space, which is what the
+//! direct boot linker scripts rely on, but it leaves the CPU and the flash at
+//! their slow reset clocks. [init()](crate::init::init) takes over the rest:
+//! the CPU clock defaults to the maximum and the flash clock is raised
+//! according to [FlashConfig].
+//!
+//! The read mode of the flash is left as the ROM configured it. The quad
+//! modes would need the QE bit of the flash chip to be set, which differs
+//! between vendors.
+
+use crate::pac::{SPI0, SPI1};
+
+/// Offset of the `SPI_MEM_CLOCK_REG` in the SPI0 and SPI1 register blocks
+const CLOCK_REG: usize = 0x14;
+/// The flash clock is the 80 MHz source clock
+const CLK_EQU_SYSCLK: u32 = 1 << 31;
+
+/// Clock of the flash
+///
+/// 80 MHz is not offered, it needs the input timing to be tuned like the
+/// bootloader does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashClock {
+    Clock20MHz = 4,
+    Clock26MHz = 3,
+    Clock40MHz = 2,
+}
+
+/// Flash configuration applied by [init()](crate::init::init)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FlashConfig {
+    /// Clock of the flash, `None` keeps the clock of the ROM
+    pub clock: Option<FlashClock>,
+}
+
+impl Default for FlashConfig {
+    fn default() -> Self {
+        Self {
+            clock: Some(FlashClock::Clock40MHz),
+        }
+    }
+}
+
+/// Value of `SPI_MEM_CLOCK_REG` for dividing the 80 MHz source clock by
+/// `divider`, the same value the ROM writes
+const fn clock_register(divider: u32) -> u32 {
+    if divider == 1 {
+        CLK_EQU_SYSCLK
+    } else {
+        (divider - 1) << 16 | (divider / 2 - 1) << 8 | (divider - 1)
+    }
+}
+
+const _: () = {
+    assert!(clock_register(1) == CLK_EQU_SYSCLK);
+    assert!(clock_register(2) == 0x0001_0001);
+    assert!(clock_register(3) == 0x0002_0002);
+    assert!(clock_register(4) == 0x0003_0103);
+};
+
+/// Apply `config` to SPI0, which serves the cache, and SPI1
+///
+/// Runs from RAM, the flash can't be read while its clock changes.
+#[procmacros::ram]
+pub(crate) fn configure_flash(config: FlashConfig) {
+    if let Some(clock) = config.clock {
+        let value = clock_register(clock as u32);
+        critical_section::with(|_| unsafe {
+            ((SPI0::PTR as usize + CLOCK_REG) as *mut u32).write_volatile(value);
+            ((SPI1::PTR as usize + CLOCK_REG) as *mut u32).write_volatile(value);
+        });
+    }
+}
@@ -0,0 +1,109 @@
+//! Waking futures from the GDMA channel interrupts
+//!
+//! Async drivers receiving through a GDMA channel, like the ADC stream, wait
+//! for the end of a chunk or a descriptor error of their channel. Each
+//! channel has its own waker, the handler of the channel's interrupt
+//! (`DMA_CHn`, `DMA_IN_CHn` on the ESP32-S3) has to call [handle_interrupt]
+//! with the number of the channel:
+//!
+//! ```no_run
+//! interrupt::enable(pac::Interrupt::DMA_CH0, interrupt::Priority::Priority1).unwrap();
+//!
+//! #[interrupt]
+//! fn DMA_CH0() {
+//!     dma::asynch::handle_interrupt(0);
+//! }
+//! ```
+
+use core::{
+    sync::atomic::{AtomicU8, Ordering},
+    task::Waker,
+};
+
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::private::{RegisterAccess, Rx};
+use crate::dma::gdma::private::*;
+
+/// Number of GDMA channels
+#[cfg(esp32c2)]
+const CHANNELS: usize = 1;
+#[cfg(esp32c3)]
+const CHANNELS: usize = 3;
+#[cfg(esp32s3)]
+const CHANNELS: usize = 5;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEW_WAKER: AtomicWaker = AtomicWaker::new();
+
+static RX_WAKERS: [AtomicWaker; CHANNELS] = [NEW_WAKER; CHANNELS];
+/// Channels with a waiting future, cleared by the interrupt handler
+static WAITING: AtomicU8 = AtomicU8::new(0);
+
+/// Wake `waker` at the next end of a chunk or error of the channel of `rx`
+///
+/// Registers the waker, the caller then checks the state of the channel and
+/// calls [listen_rx] if it has to wait.
+pub(crate) fn register_rx<RX: Rx>(rx: &RX, waker: &Waker) {
+    RX_WAKERS[rx.channel_number() as usize].register(waker);
+}
+
+/// Enable the interrupts the future waiting on `rx` is woken by
+pub(crate) fn listen_rx<RX: Rx>(rx: &mut RX) {
+    WAITING.fetch_or(1 << rx.channel_number(), Ordering::AcqRel);
+    critical_section::with(|_| {
+        rx.listen_done();
+        rx.listen_error();
+    });
+}
+
+/// Disable the interrupts of a future which stopped waiting on `rx`
+pub(crate) fn unlisten_rx<RX: Rx>(rx: &mut RX) {
+    critical_section::with(|_| {
+        rx.unlisten_done();
+        rx.unlisten_error();
+    });
+    WAITING.fetch_and(!(1 << rx.channel_number()), Ordering::AcqRel);
+}
+
+fn take_rx<R: RegisterAccess>() -> bool {
+    if !R::is_in_done() && !R::has_in_descriptor_error() {
+        return false;
+    }
+
+    critical_section::with(|_| {
+        R::unlisten_in_done();
+        R::unlisten_in_error();
+    });
+    true
+}
+
+/// Wake the future waiting on GDMA channel `channel`
+///
+/// To be called from the interrupt handler of the channel. The interrupts
+/// stay disabled until the future was polled, the flags are left for the
+/// driver to clear.
+#[procmacros::ram]
+pub fn handle_interrupt(channel: u8) {
+    if WAITING.load(Ordering::Acquire) & (1 << channel) == 0 {
+        return;
+    }
+
+    let pending = match channel {
+        0 => take_rx::<Channel0>(),
+        #[cfg(not(esp32c2))]
+        1 => take_rx::<Channel1>(),
+        #[cfg(not(esp32c2))]
+        2 => take_rx::<Channel2>(),
+        #[cfg(esp32s3)]
+        3 => take_rx::<Channel3>(),
+        #[cfg(esp32s3)]
+        4 => take_rx::<Channel4>(),
+        _ => false,
+    };
+
+    if pending {
+        WAITING.fetch_and(!(1 << channel), Ordering::AcqRel);
+        RX_WAKERS[channel as usize].wake();
+    }
+}
@@ -11,6 +11,8 @@ macro_rules! impl_channel {
             pub struct [<Channel $num>] {}
 
             impl RegisterAccess for [<Channel $num>] {
+                const CHANNEL: u8 = $num;
+
                 fn init_channel() {
                     // nothing special to be done here
                 }
@@ -108,6 +110,12 @@ macro_rules! impl_channel {
                     dma.[<out_link_ch $num>].modify(|_, w| w.outlink_start().set_bit());
                 }
 
+                fn stop_out() {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    dma.[<out_link_ch $num>].modify(|_, w| w.outlink_stop().set_bit());
+                }
+
                 fn is_out_done() -> bool {
                     let dma = unsafe { &*crate::pac::DMA::PTR };
 
@@ -151,6 +159,26 @@ macro_rules! impl_channel {
                     });
                 }
 
+                fn listen_out_eof() {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    #[cfg(not(esp32s3))]
+                    dma.[<int_ena_ch $num>].modify(|_, w| w.out_eof().set_bit());
+
+                    #[cfg(esp32s3)]
+                    dma.[<out_int_ena_ch $num>].modify(|_, w| w.out_eof().set_bit());
+                }
+
+                fn unlisten_out_eof() {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    #[cfg(not(esp32s3))]
+                    dma.[<int_ena_ch $num>].modify(|_, w| w.out_eof().clear_bit());
+
+                    #[cfg(esp32s3)]
+                    dma.[<out_int_ena_ch $num>].modify(|_, w| w.out_eof().clear_bit());
+                }
+
                 fn set_in_burstmode(burst_mode: bool) {
                     let dma = unsafe { &*crate::pac::DMA::PTR };
 
@@ -247,6 +275,12 @@ macro_rules! impl_channel {
                     dma.[<in_link_ch $num>].modify(|_, w| w.inlink_start().set_bit());
                 }
 
+                fn stop_in() {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    dma.[<in_link_ch $num>].modify(|_, w| w.inlink_stop().set_bit());
+                }
+
                 fn is_in_done() -> bool {
                     let dma = unsafe { &*crate::pac::DMA::PTR };
 
@@ -262,6 +296,82 @@ macro_rules! impl_channel {
                     let dma = unsafe { &*crate::pac::DMA::PTR };
                     dma.[<in_dscr_bf0_ch $num>].read().inlink_dscr_bf0().bits() as usize
                 }
+
+                fn set_mem_trans(enable: bool) {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    dma.[<in_conf0_ch $num>].modify(|_, w| w.mem_trans_en().bit(enable));
+                }
+
+                fn listen_in_done() {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    #[cfg(not(esp32s3))]
+                    dma.[<int_ena_ch $num>].modify(|_, w| w.in_suc_eof().set_bit());
+
+                    #[cfg(esp32s3)]
+                    dma.[<in_int_ena_ch $num>].modify(|_, w| w.in_suc_eof().set_bit());
+                }
+
+                fn unlisten_in_done() {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    #[cfg(not(esp32s3))]
+                    dma.[<int_ena_ch $num>].modify(|_, w| w.in_suc_eof().clear_bit());
+
+                    #[cfg(esp32s3)]
+                    dma.[<in_int_ena_ch $num>].modify(|_, w| w.in_suc_eof().clear_bit());
+                }
+
+                fn clear_in_done() {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    #[cfg(not(esp32s3))]
+                    dma.[<int_clr_ch $num>].write(|w| w.in_suc_eof().set_bit());
+
+                    #[cfg(esp32s3)]
+                    dma.[<in_int_clr_ch $num>].write(|w| w.in_suc_eof().set_bit());
+                }
+
+                fn listen_in_error() {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    #[cfg(not(esp32s3))]
+                    dma.[<int_ena_ch $num>].modify(|_, w| w.in_dscr_err().set_bit());
+
+                    #[cfg(esp32s3)]
+                    dma.[<in_int_ena_ch $num>].modify(|_, w| w.in_dscr_err().set_bit());
+                }
+
+                fn unlisten_in_error() {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    #[cfg(not(esp32s3))]
+                    dma.[<int_ena_ch $num>].modify(|_, w| w.in_dscr_err().clear_bit());
+
+                    #[cfg(esp32s3)]
+                    dma.[<in_int_ena_ch $num>].modify(|_, w| w.in_dscr_err().clear_bit());
+                }
+
+                fn set_in_check_owner(enable: bool) {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    dma.[<in_conf1_ch $num>].modify(|_, w| w.in_check_owner().bit(enable));
+                }
+
+                #[cfg(esp32s3)]
+                fn set_out_ext_mem_block_size(size: u8) {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    dma.[<out_conf1_ch $num>].modify(|_, w| unsafe { w.out_ext_mem_bk_size().bits(size) });
+                }
+
+                #[cfg(esp32s3)]
+                fn set_in_ext_mem_block_size(size: u8) {
+                    let dma = unsafe { &*crate::pac::DMA::PTR };
+
+                    dma.[<in_conf1_ch $num>].modify(|_, w| unsafe { w.in_ext_mem_bk_size().bits(size) });
+                }
             }
 
             pub struct [<Channel $num TxImpl>] {}
@@ -288,6 +398,7 @@ macro_rules! impl_channel {
                     let tx_channel = ChannelTx {
                         descriptors: tx_descriptors,
                         burst_mode,
+                        chunk_size: CHUNK_SIZE,
                         tx_impl: tx_impl,
                         write_offset: 0,
                         write_descr_ptr: core::ptr::null(),
@@ -295,6 +406,8 @@ macro_rules! impl_channel {
                         last_seen_handled_descriptor_ptr: core::ptr::null(),
                         buffer_start: core::ptr::null(),
                         buffer_len: 0,
+                        #[cfg(all(esp32s3, feature = "psram"))]
+                        ext_mem_alignment: EXT_MEM_DEFAULT_ALIGNMENT,
                         _phantom: PhantomData::default(),
                     };
 
@@ -304,11 +417,14 @@ macro_rules! impl_channel {
                     let rx_channel = ChannelRx {
                         descriptors: rx_descriptors,
                         burst_mode,
+                        chunk_size: CHUNK_SIZE,
                         rx_impl: rx_impl,
                         read_descr_ptr: core::ptr::null(),
                         available: 0,
                         last_seen_handled_descriptor_ptr: core::ptr::null(),
                         read_buffer_start: core::ptr::null(),
+                        #[cfg(all(esp32s3, feature = "psram"))]
+                        ext_mem_alignment: EXT_MEM_DEFAULT_ALIGNMENT,
                         _phantom: PhantomData::default(),
                     };
 
@@ -326,9 +442,13 @@ macro_rules! impl_channel {
             // with GDMA every channel can be used for any peripheral
             impl SpiPeripheral for [<SuitablePeripheral $num>] {}
             impl Spi2Peripheral for [<SuitablePeripheral $num>] {}
+            #[cfg(spi3)]
+            impl Spi3Peripheral for [<SuitablePeripheral $num>] {}
             impl I2sPeripheral for [<SuitablePeripheral $num>] {}
             impl I2s0Peripheral for [<SuitablePeripheral $num>] {}
             impl I2s1Peripheral for [<SuitablePeripheral $num>] {}
+            #[cfg(any(esp32c3, esp32s3))]
+            impl AdcPeripheral for [<SuitablePeripheral $num>] {}
         }
     };
 }
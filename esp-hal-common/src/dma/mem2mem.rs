@@ -0,0 +1,353 @@
+//! Memory-to-memory copies
+//!
+//! A GDMA channel in memory-to-memory mode copies a buffer while the CPU
+//! does other work, e.g. a frame from PSRAM into internal RAM for a
+//! peripheral which can't read PSRAM:
+//!
+//! ```no_run
+//! let dma = Gdma::new(peripherals.DMA, &mut system.peripheral_clock_control);
+//! let channel = dma.channel0.configure(
+//!     false,
+//!     &mut tx_descriptors,
+//!     &mut rx_descriptors,
+//!     DmaPriority::Priority0,
+//! );
+//!
+//! let transfer = Mem2Mem::new(channel).copy(dst, src).unwrap();
+//! // ... other work, `dst` and `src` are owned by the transfer
+//! let (dst, src, mem2mem) = transfer.wait();
+//! ```
+//!
+//! Like the DMA transfers of the peripherals, a copy takes its buffers by
+//! value, e.g. as `&'static mut [u8; N]`, and only [DmaTransferRxTx::wait]
+//! hands them back. Neither buffer can be touched while the DMA works on
+//! it. Dropping the transfer aborts the copy, the destination is left
+//! partially written.
+//!
+//! ## Buffers
+//!
+//! Buffers in internal RAM have to start at a 4 byte boundary and their
+//! length has to be a multiple of 4 bytes. On the ESP32-S3 with the `psram`
+//! feature buffers can also be in PSRAM, they have to start at a 64 byte
+//! boundary and be a multiple of 64 bytes long. The data cache is written
+//! back for both buffers before the copy and invalidated for the destination
+//! when it's done. Buffers anywhere
+//! else, e.g. in flash, are rejected with
+//! [DmaError::UnsupportedMemoryRegion](super::DmaError::UnsupportedMemoryRegion).
+//!
+//! Each descriptor covers up to 4092 bytes (4032 bytes if a buffer is in
+//! PSRAM), both directions of the channel need enough descriptors of 3
+//! words for the length of the copy.
+//!
+//! ## Completion
+//!
+//! Poll [Mem2MemTransfer::is_done] or block in [DmaTransferRxTx::wait]. After
+//! [Mem2Mem::listen] the end of each copy also raises the interrupt of the
+//! channel (`DMA_CHn`, `DMA_IN_CHn` on the ESP32-S3), whose handler has to
+//! call [Mem2MemTransfer::on_interrupt].
+//!
+//! ## Throughput
+//!
+//! The DMA shares the bus with the CPU, within internal RAM it isn't
+//! necessarily faster than `copy_from_slice`. It pays off when the CPU has
+//! other work to do in the meantime, or for PSRAM, where the CPU stalls on
+//! every cache miss. The `dma_mem2mem` example prints the rates of both.
+//!
+//! All memory-to-memory channels select the same unused peripheral ID, only
+//! one [Mem2Mem] may exist at a time.
+
+use core::mem;
+
+use embedded_dma::{ReadBuffer, WriteBuffer};
+
+#[cfg(esp32s3)]
+use super::EXT_MEM;
+use super::{
+    memory_region,
+    private::{PeripheralMarker, Rx, Tx},
+    Channel,
+    DmaError,
+    DmaPeripheral,
+    DmaTransferRxTx,
+    MemoryRegion,
+    CHUNK_SIZE,
+};
+#[cfg(esp32s3)]
+use crate::pac::EXTMEM;
+
+const ALIGNMENT: usize = 4;
+#[cfg(all(esp32s3, feature = "psram"))]
+const EXT_MEM_ALIGNMENT: usize = 64;
+/// `*_EXT_MEM_BK_SIZE` of 64 bytes
+#[cfg(esp32s3)]
+const EXT_MEM_BLOCK_SIZE_64: u8 = 2;
+/// Largest multiple of [EXT_MEM_ALIGNMENT] a descriptor covers
+#[cfg(esp32s3)]
+const EXT_MEM_CHUNK_SIZE: usize = 4032;
+
+/// A GDMA channel copying memory, see the [module documentation](self)
+pub struct Mem2Mem<TX, RX, P>
+where
+    TX: Tx,
+    RX: Rx,
+    P: PeripheralMarker,
+{
+    channel: Channel<TX, RX, P>,
+    listening: bool,
+}
+
+impl<TX, RX, P> Mem2Mem<TX, RX, P>
+where
+    TX: Tx,
+    RX: Rx,
+    P: PeripheralMarker,
+{
+    /// Switch `channel` to memory-to-memory mode
+    pub fn new(mut channel: Channel<TX, RX, P>) -> Self {
+        channel.tx.init_channel();
+        channel.rx.set_mem2mem(true);
+        channel.rx.unlisten_done();
+
+        Self {
+            channel,
+            listening: false,
+        }
+    }
+
+    /// Raise the interrupt of the channel at the end of the following copies
+    pub fn listen(&mut self) {
+        self.listening = true;
+    }
+
+    /// Don't raise the interrupt of the channel
+    pub fn unlisten(&mut self) {
+        self.listening = false;
+        self.channel.rx.unlisten_done();
+    }
+
+    /// Start copying `src` to the beginning of `dst`
+    ///
+    /// `dst` has to be at least as long as `src`.
+    pub fn copy<DST, SRC>(
+        mut self,
+        mut dst: DST,
+        src: SRC,
+    ) -> Result<Mem2MemTransfer<TX, RX, P, DST, SRC>, DmaError>
+    where
+        DST: WriteBuffer<Word = u8>,
+        SRC: ReadBuffer<Word = u8>,
+    {
+        let (src_ptr, len) = unsafe { src.read_buffer() };
+        let (dst_ptr, dst_len) = unsafe { dst.write_buffer() };
+
+        if dst_len < len {
+            return Err(DmaError::BufferTooSmall);
+        }
+
+        if len > 0 {
+            let src_ext = check_buffer(src_ptr as usize, len)?;
+            let dst_ext = check_buffer(dst_ptr as usize, len)?;
+            self.prepare_ext_mem(src_ptr as usize, src_ext, dst_ptr as usize, dst_ext, len);
+
+            if self.listening {
+                self.channel.rx.clear_done();
+                self.channel.rx.listen_done();
+            }
+
+            self.channel
+                .rx
+                .prepare_transfer(false, DmaPeripheral::Mem2Mem, dst_ptr, len)?;
+            self.channel
+                .tx
+                .prepare_transfer(DmaPeripheral::Mem2Mem, false, src_ptr, len)?;
+        }
+
+        Ok(Mem2MemTransfer {
+            mem2mem: self,
+            dst,
+            src,
+            dst_ptr: dst_ptr as usize,
+            len,
+            done: len == 0,
+        })
+    }
+
+    /// Leave memory-to-memory mode and return the channel
+    pub fn free(mut self) -> Channel<TX, RX, P> {
+        self.channel.rx.unlisten_done();
+        self.channel.rx.set_mem2mem(false);
+        self.channel.rx.set_chunk_size(CHUNK_SIZE);
+        self.channel.tx.set_chunk_size(CHUNK_SIZE);
+        self.channel
+    }
+
+    /// Configure the channel for buffers in PSRAM and write them back from
+    /// the data cache
+    #[cfg(esp32s3)]
+    fn prepare_ext_mem(
+        &mut self,
+        src: usize,
+        src_ext: bool,
+        dst: usize,
+        dst_ext: bool,
+        len: usize,
+    ) {
+        let chunk_size = if src_ext || dst_ext {
+            EXT_MEM_CHUNK_SIZE
+        } else {
+            CHUNK_SIZE
+        };
+        self.channel.tx.set_chunk_size(chunk_size);
+        self.channel.rx.set_chunk_size(chunk_size);
+        self.channel
+            .tx
+            .set_ext_mem_block_size(EXT_MEM_BLOCK_SIZE_64);
+        self.channel
+            .rx
+            .set_ext_mem_block_size(EXT_MEM_BLOCK_SIZE_64);
+
+        // dirty lines of the destination would overwrite the copy when
+        // they're evicted
+        if src_ext {
+            sync_dcache(src, len, false);
+        }
+        if dst_ext {
+            sync_dcache(dst, len, false);
+        }
+    }
+
+    #[cfg(not(esp32s3))]
+    fn prepare_ext_mem(&mut self, _: usize, _: bool, _: usize, _: bool, _: usize) {}
+}
+
+/// An in-progress copy of a [Mem2Mem]
+pub struct Mem2MemTransfer<TX, RX, P, DST, SRC>
+where
+    TX: Tx,
+    RX: Rx,
+    P: PeripheralMarker,
+{
+    mem2mem: Mem2Mem<TX, RX, P>,
+    dst: DST,
+    src: SRC,
+    dst_ptr: usize,
+    len: usize,
+    done: bool,
+}
+
+impl<TX, RX, P, DST, SRC> Mem2MemTransfer<TX, RX, P, DST, SRC>
+where
+    TX: Tx,
+    RX: Rx,
+    P: PeripheralMarker,
+{
+    /// Whether the copy is done
+    pub fn is_done(&mut self) -> bool {
+        self.done || self.mem2mem.channel.rx.is_done()
+    }
+
+    /// Clear the interrupt of the channel
+    ///
+    /// To be called from the interrupt handler after [Mem2Mem::listen].
+    pub fn on_interrupt(&mut self) {
+        if self.mem2mem.channel.rx.is_done() {
+            self.done = true;
+            self.mem2mem.channel.rx.clear_done();
+        }
+    }
+
+    fn finish(&mut self) {
+        while !self.is_done() {}
+
+        #[cfg(esp32s3)]
+        if EXT_MEM.contains(&self.dst_ptr) {
+            sync_dcache(self.dst_ptr, self.len, true);
+        }
+    }
+}
+
+impl<TX, RX, P, DST, SRC> DmaTransferRxTx<DST, SRC, Mem2Mem<TX, RX, P>>
+    for Mem2MemTransfer<TX, RX, P, DST, SRC>
+where
+    TX: Tx,
+    RX: Rx,
+    P: PeripheralMarker,
+{
+    /// Wait for the copy to finish and return the destination, the source
+    /// and the [Mem2Mem]
+    fn wait(mut self) -> (DST, SRC, Mem2Mem<TX, RX, P>) {
+        self.finish();
+
+        // see `SpiDmaTransfer::wait`, the fields can't be moved out of a type
+        // implementing `Drop`
+        //
+        // NOTE(unsafe) There is no panic branch between getting the resources
+        // and forgetting `self`.
+        unsafe {
+            let dst = core::ptr::read(&self.dst);
+            let src = core::ptr::read(&self.src);
+            let mem2mem = core::ptr::read(&self.mem2mem);
+            mem::forget(self);
+            (dst, src, mem2mem)
+        }
+    }
+}
+
+impl<TX, RX, P, DST, SRC> Drop for Mem2MemTransfer<TX, RX, P, DST, SRC>
+where
+    TX: Tx,
+    RX: Rx,
+    P: PeripheralMarker,
+{
+    fn drop(&mut self) {
+        self.mem2mem.channel.tx.stop();
+        self.mem2mem.channel.rx.stop();
+    }
+}
+
+/// Check that the DMA can copy the `len` bytes at `address`, returns whether
+/// they're in external memory
+fn check_buffer(address: usize, len: usize) -> Result<bool, DmaError> {
+    match memory_region(address, len)? {
+        MemoryRegion::Internal if address % ALIGNMENT != 0 || len % ALIGNMENT != 0 => {
+            Err(DmaError::Unaligned)
+        }
+        MemoryRegion::Internal => Ok(false),
+        #[cfg(all(esp32s3, feature = "psram"))]
+        MemoryRegion::External
+            if address % EXT_MEM_ALIGNMENT != 0 || len % EXT_MEM_ALIGNMENT != 0 =>
+        {
+            Err(DmaError::Unaligned)
+        }
+        #[cfg(all(esp32s3, feature = "psram"))]
+        MemoryRegion::External => Ok(true),
+    }
+}
+
+/// Write back (or invalidate) the data cache lines of the `len` bytes at
+/// `address`
+#[cfg(esp32s3)]
+fn sync_dcache(address: usize, len: usize, invalidate: bool) {
+    let extmem = unsafe { &*EXTMEM::PTR };
+
+    extmem
+        .dcache_sync_addr
+        .write(|w| unsafe { w.dcache_sync_addr().bits(address as u32) });
+    extmem
+        .dcache_sync_size
+        .write(|w| unsafe { w.dcache_sync_size().bits(len as u32) });
+    extmem.dcache_sync_ctrl.write(|w| {
+        if invalidate {
+            w.dcache_invalidate_ena().set_bit()
+        } else {
+            w.dcache_writeback_ena().set_bit()
+        }
+    });
+
+    while extmem
+        .dcache_sync_ctrl
+        .read()
+        .dcache_sync_done()
+        .bit_is_clear()
+    {}
+}
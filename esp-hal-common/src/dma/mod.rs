@@ -1,26 +1,129 @@
 //! Direct Memory Access Commons
 
-use core::{marker::PhantomData, sync::atomic::compiler_fence};
+use core::{marker::PhantomData, ops::Range, sync::atomic::compiler_fence};
 
 use private::*;
 
+#[cfg(gdma)]
+pub use self::mem2mem::Mem2Mem;
+
+#[cfg(all(gdma, feature = "async"))]
+pub mod asynch;
 #[cfg(gdma)]
 pub mod gdma;
+#[cfg(gdma)]
+pub mod mem2mem;
 #[cfg(pdma)]
 pub mod pdma;
 
 const CHUNK_SIZE: usize = 4092;
 
+/// Data bus address ranges of the internal RAM the DMA can access, of all
+/// chips to test them together
+#[allow(unused)]
+mod dma_dram {
+    use core::ops::Range;
+
+    pub const ESP32: Range<usize> = 0x3ffa_e000..0x4000_0000;
+    pub const ESP32S2: Range<usize> = 0x3ffb_0000..0x4000_0000;
+    pub const ESP32C2: Range<usize> = 0x3fca_0000..0x3fce_0000;
+    pub const ESP32C3: Range<usize> = 0x3fc8_0000..0x3fce_0000;
+    pub const ESP32S3: Range<usize> = 0x3fc8_8000..0x3fd0_0000;
+}
+
+/// Data bus address range of the internal RAM the DMA can access
+#[cfg(esp32)]
+const DMA_DRAM: Range<usize> = dma_dram::ESP32;
+#[cfg(esp32s2)]
+const DMA_DRAM: Range<usize> = dma_dram::ESP32S2;
+#[cfg(esp32c2)]
+const DMA_DRAM: Range<usize> = dma_dram::ESP32C2;
+#[cfg(esp32c3)]
+const DMA_DRAM: Range<usize> = dma_dram::ESP32C3;
+#[cfg(esp32s3)]
+const DMA_DRAM: Range<usize> = dma_dram::ESP32S3;
+
+/// Data bus address range of the external memory, shared by the flash and
+/// the PSRAM
+#[cfg(esp32s3)]
+pub(crate) const EXT_MEM: Range<usize> = 0x3c00_0000..0x3e00_0000;
+/// Alignment of PSRAM buffers for the default `*_EXT_MEM_BK_SIZE` of 16
+/// bytes
+#[cfg(all(esp32s3, feature = "psram"))]
+const EXT_MEM_DEFAULT_ALIGNMENT: usize = 16;
+
 /// DMA Errors
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DmaError {
-    InvalidAlignment,
-    OutOfDescriptors,
+    /// The buffer doesn't meet the alignment the transfer needs: a multiple
+    /// of 4 bytes in burst mode, of the external memory block size for
+    /// PSRAM
+    Unaligned,
+    /// The descriptors of the channel don't cover the buffer, each one
+    /// covers up to 4092 bytes
+    DescriptorsExhausted,
+    /// The descriptor list isn't a multiple of 3 words
     InvalidDescriptorSize,
+    /// The DMA reported an error in a descriptor
     DescriptorError,
+    /// More data was pushed than the buffer has room for
     Overflow,
+    /// No more data to pop
     Exhausted,
+    /// The buffer is too small for the transfer, e.g. a circular buffer
+    /// holding less than two chunks
     BufferTooSmall,
+    /// The buffer is larger than the peripheral transfers at once
+    BufferTooLarge,
+    /// The DMA can't access the memory of the buffer, e.g. flash: only the
+    /// internal RAM and, on the ESP32-S3 with the `psram` feature, PSRAM are
+    /// supported
+    UnsupportedMemoryRegion,
+}
+
+/// Memory a DMA buffer is in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum MemoryRegion {
+    /// Internal RAM
+    Internal,
+    /// PSRAM, through the data cache
+    #[cfg(all(esp32s3, feature = "psram"))]
+    External,
+}
+
+/// Whether the `len` bytes at `address` lie within `range`
+const fn range_contains(range: &Range<usize>, address: usize, len: usize) -> bool {
+    address >= range.start && address < range.end && len <= range.end - address
+}
+
+/// The memory of the `len` bytes at `address`, if the DMA can access it
+///
+/// On the ESP32-S3 the flash and the PSRAM share the address space of the
+/// external memory, the MMU tells them apart.
+pub(crate) fn memory_region(address: usize, len: usize) -> Result<MemoryRegion, DmaError> {
+    let region = classify(address, len)?;
+
+    #[cfg(all(esp32s3, feature = "psram"))]
+    if matches!(region, MemoryRegion::External) && !crate::psram::is_psram(address, len) {
+        return Err(DmaError::UnsupportedMemoryRegion);
+    }
+
+    Ok(region)
+}
+
+/// [memory_region] by the address alone, external memory may still be
+/// mapped to the flash
+const fn classify(address: usize, len: usize) -> Result<MemoryRegion, DmaError> {
+    if range_contains(&DMA_DRAM, address, len) {
+        return Ok(MemoryRegion::Internal);
+    }
+
+    #[cfg(all(esp32s3, feature = "psram"))]
+    if range_contains(&EXT_MEM, address, len) {
+        return Ok(MemoryRegion::External);
+    }
+
+    Err(DmaError::UnsupportedMemoryRegion)
 }
 
 /// DMA Priorities
@@ -51,27 +154,67 @@ pub enum DmaPriority {
 /// The values need to match the TRM
 #[derive(Clone, Copy)]
 pub enum DmaPeripheral {
-    Spi2   = 0,
-    #[cfg(any(pdma, esp32s3))]
-    Spi3   = 1,
+    Spi2    = 0,
+    #[cfg(spi3)]
+    Spi3    = 1,
     #[cfg(any(esp32c3, esp32s3))]
-    Uhci0  = 2,
+    Uhci0   = 2,
     #[cfg(any(esp32, esp32s2, esp32c3, esp32s3))]
-    I2s0   = 3,
+    I2s0    = 3,
     #[cfg(any(esp32, esp32s3))]
-    I2s1   = 4,
+    I2s1    = 4,
     #[cfg(esp32s3)]
-    LcdCam = 5,
+    LcdCam  = 5,
     #[cfg(any(esp32c3, esp32s3))]
-    Aes    = 6,
+    Aes     = 6,
     #[cfg(gdma)]
-    Sha    = 7,
+    Sha     = 7,
     #[cfg(any(esp32c3, esp32s3))]
-    Adc    = 8,
+    Adc     = 8,
+    #[cfg(esp32s3)]
+    Rmt     = 9,
+    // an ID no peripheral uses, selected by both directions of a
+    // memory-to-memory channel: the one of SPI3, which the ESP32-C2 and
+    // ESP32-C3 don't have
+    #[cfg(any(esp32c2, esp32c3))]
+    Mem2Mem = 1,
     #[cfg(esp32s3)]
-    Rmt    = 9,
+    Mem2Mem = 10,
 }
 
+/// Mark the ID of `peripheral` as taken in `ids`, fails if it already is
+const fn claim_peripheral_id(ids: u32, peripheral: DmaPeripheral) -> u32 {
+    let id = 1 << peripheral as u32;
+    assert!(ids & id == 0, "Two DMA peripherals share an ID");
+    ids | id
+}
+
+// every peripheral has to select a distinct ID
+const _: () = {
+    let ids = claim_peripheral_id(0, DmaPeripheral::Spi2);
+    #[cfg(spi3)]
+    let ids = claim_peripheral_id(ids, DmaPeripheral::Spi3);
+    #[cfg(any(esp32c3, esp32s3))]
+    let ids = claim_peripheral_id(ids, DmaPeripheral::Uhci0);
+    #[cfg(any(esp32, esp32s2, esp32c3, esp32s3))]
+    let ids = claim_peripheral_id(ids, DmaPeripheral::I2s0);
+    #[cfg(any(esp32, esp32s3))]
+    let ids = claim_peripheral_id(ids, DmaPeripheral::I2s1);
+    #[cfg(esp32s3)]
+    let ids = claim_peripheral_id(ids, DmaPeripheral::LcdCam);
+    #[cfg(any(esp32c3, esp32s3))]
+    let ids = claim_peripheral_id(ids, DmaPeripheral::Aes);
+    #[cfg(gdma)]
+    let ids = claim_peripheral_id(ids, DmaPeripheral::Sha);
+    #[cfg(any(esp32c3, esp32s3))]
+    let ids = claim_peripheral_id(ids, DmaPeripheral::Adc);
+    #[cfg(esp32s3)]
+    let ids = claim_peripheral_id(ids, DmaPeripheral::Rmt);
+    #[cfg(any(esp32c2, esp32c3, esp32s3))]
+    let ids = claim_peripheral_id(ids, DmaPeripheral::Mem2Mem);
+    let _ = ids;
+};
+
 #[derive(PartialEq, PartialOrd)]
 enum Owner {
     Cpu = 0,
@@ -164,6 +307,69 @@ impl DmaLinkedListDw0 for &mut u32 {
     }
 }
 
+/// Descriptors of 3 words [build_descriptor_chain] needs for `len` bytes
+const fn descriptors_needed(len: usize, chunk_size: usize) -> usize {
+    if len == 0 {
+        1
+    } else {
+        (len + chunk_size - 1) / chunk_size
+    }
+}
+
+/// Build the descriptor chain for a transfer of `len` bytes at `data`
+///
+/// Each descriptor covers up to `chunk_size` bytes. Outbound descriptors
+/// carry the length of their data and the EOF flag, inbound ones get the
+/// length written by the DMA. The last descriptor links back to the first
+/// one for `circular` transfers.
+fn build_descriptor_chain(
+    descriptors: &mut [u32],
+    data: u32,
+    len: usize,
+    chunk_size: usize,
+    circular: bool,
+    outbound: bool,
+) {
+    for descr in descriptors.iter_mut() {
+        *descr = 0;
+    }
+
+    compiler_fence(core::sync::atomic::Ordering::SeqCst);
+
+    let mut processed = 0;
+    let mut descr = 0;
+    loop {
+        let chunk_size = usize::min(chunk_size, len - processed);
+        let last = processed + chunk_size >= len;
+
+        descriptors[descr + 1] = data + processed as u32;
+
+        let mut dw0 = &mut descriptors[descr];
+
+        dw0.set_suc_eof(outbound);
+        dw0.set_owner(Owner::Dma);
+        dw0.set_size(chunk_size as u16); // align to 32 bits?
+        dw0.set_length(if outbound { chunk_size as u16 } else { 0 });
+
+        if !last {
+            descriptors[descr + 2] = (&descriptors[descr + 3]) as *const _ as *const () as u32;
+        } else {
+            descriptors[descr + 2] = if circular {
+                descriptors.as_ptr() as *const () as u32
+            } else {
+                0
+            };
+        }
+
+        processed += chunk_size;
+        descr += 3;
+
+        if processed >= len {
+            break;
+        }
+    }
+}
+
 /// Crate private implementatin details
 pub(crate) mod private {
     use super::*;
@@ -177,7 +383,7 @@ pub(crate) mod private {
     pub trait Spi2Peripheral: SpiPeripheral + PeripheralMarker {}
 
     /// Marks channels as useable for SPI3
-    #[cfg(any(esp32, esp32s2, esp32s3))]
+    #[cfg(spi3)]
     pub trait Spi3Peripheral: SpiPeripheral + PeripheralMarker {}
 
     /// Marks channels as useable for I2S
@@ -189,6 +395,10 @@ pub(crate) mod private {
     /// Marks channels as useable for I2S1
     pub trait I2s1Peripheral: I2sPeripheral + PeripheralMarker {}
 
+    /// Marks channels as useable for the ADC
+    #[cfg(any(esp32c3, esp32s3))]
+    pub trait AdcPeripheral: PeripheralMarker {}
+
     /// DMA Rx
     ///
     /// The functions here are not meant to be used outside the HAL and will be
@@ -208,11 +418,52 @@ pub(crate) mod private {
 
         fn is_done(&mut self) -> bool;
 
+        /// Abort the transfer, the DMA doesn't touch the buffer afterwards
+        fn stop(&mut self);
+
         fn available(&mut self) -> usize;
 
         fn pop(&mut self, data: &mut [u8]) -> Result<usize, DmaError>;
 
+        /// Copy up to `data.len()` of the [Rx::available] bytes, the rest
+        /// stays available
+        fn pop_partial(&mut self, data: &mut [u8]) -> usize;
+
+        /// The channel ran into a descriptor it doesn't own or an invalid one
+        fn has_error(&mut self) -> bool;
+
         fn drain_buffer(&mut self, dst: &mut [u8]) -> Result<usize, DmaError>;
+
+        fn set_chunk_size(&mut self, chunk_size: usize);
+
+        #[cfg(gdma)]
+        fn set_mem2mem(&mut self, enable: bool);
+
+        #[cfg(gdma)]
+        fn listen_done(&mut self);
+
+        #[cfg(gdma)]
+        fn unlisten_done(&mut self);
+
+        #[cfg(gdma)]
+        fn clear_done(&mut self);
+
+        #[cfg(gdma)]
+        fn listen_error(&mut self);
+
+        #[cfg(gdma)]
+        fn unlisten_error(&mut self);
+
+        /// Stop with an error at a descriptor owned by the CPU instead of
+        /// overwriting it
+        #[cfg(gdma)]
+        fn set_check_owner(&mut self, enable: bool);
+
+        #[cfg(gdma)]
+        fn channel_number(&self) -> u8;
+
+        #[cfg(esp32s3)]
+        fn set_ext_mem_block_size(&mut self, size: u8);
     }
 
     pub trait RxChannel<R>
@@ -231,46 +482,9 @@ pub(crate) mod private {
             peri: DmaPeripheral,
             data: *mut u8,
             len: usize,
+            chunk_size: usize,
         ) -> Result<(), DmaError> {
-            for descr in descriptors.iter_mut() {
-                *descr = 0;
-            }
-
-            compiler_fence(core::sync::atomic::Ordering::SeqCst);
-
-            let mut processed = 0;
-            let mut descr = 0;
-            loop {
-                let chunk_size = usize::min(CHUNK_SIZE, len - processed);
-                let last = processed + chunk_size >= len;
-
-                descriptors[descr + 1] = data as u32 + processed as u32;
-
-                let mut dw0 = &mut descriptors[descr];
-
-                dw0.set_suc_eof(false);
-                dw0.set_owner(Owner::Dma);
-                dw0.set_size(chunk_size as u16); // align to 32 bits?
-                dw0.set_length(0); // actual size of the data!?
-
-                if !last {
-                    descriptors[descr + 2] =
-                        (&descriptors[descr + 3]) as *const _ as *const () as u32;
-                } else {
-                    descriptors[descr + 2] = if circular {
-                        descriptors.as_ptr() as *const () as u32
-                    } else {
-                        0
-                    };
-                }
-
-                processed += chunk_size;
-                descr += 3;
-
-                if processed >= len {
-                    break;
-                }
-            }
+            build_descriptor_chain(descriptors, data as u32, len, chunk_size, circular, false);
 
             R::clear_in_interrupts();
             R::reset_in();
@@ -301,14 +515,42 @@ pub(crate) mod private {
     {
         pub descriptors: &'a mut [u32],
         pub burst_mode: bool,
+        pub chunk_size: usize,
         pub rx_impl: T,
         pub read_descr_ptr: *const u32,
         pub available: usize,
         pub last_seen_handled_descriptor_ptr: *const u32,
         pub read_buffer_start: *const u8,
+        /// Alignment of PSRAM buffers for the external memory block size
+        #[cfg(all(esp32s3, feature = "psram"))]
+        pub ext_mem_alignment: usize,
         pub _phantom: PhantomData<R>,
     }
 
+    impl<'a, T, R> ChannelRx<'a, T, R>
+    where
+        T: RxChannel<R>,
+        R: RegisterAccess,
+    {
+        /// Check that the DMA can access the `len` bytes at `address`
+        fn check_buffer(&self, address: usize, len: usize) -> Result<(), DmaError> {
+            if len == 0 {
+                return Ok(());
+            }
+
+            match memory_region(address, len)? {
+                MemoryRegion::Internal => Ok(()),
+                #[cfg(all(esp32s3, feature = "psram"))]
+                MemoryRegion::External => {
+                    if address % self.ext_mem_alignment != 0 || len % self.ext_mem_alignment != 0 {
+                        return Err(DmaError::Unaligned);
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+
     impl<'a, T, R> Rx for ChannelRx<'a, T, R>
     where
         T: RxChannel<R>,
@@ -329,15 +571,17 @@ pub(crate) mod private {
                 return Err(DmaError::InvalidDescriptorSize);
             }
 
-            if self.descriptors.len() / 3 < len / CHUNK_SIZE {
-                return Err(DmaError::OutOfDescriptors);
+            if self.descriptors.len() / 3 < descriptors_needed(len, self.chunk_size) {
+                return Err(DmaError::DescriptorsExhausted);
             }
 
             if self.burst_mode && (len % 4 != 0 || data as u32 % 4 != 0) {
-                return Err(DmaError::InvalidAlignment);
+                return Err(DmaError::Unaligned);
             }
 
-            if circular && len < CHUNK_SIZE * 2 {
+            self.check_buffer(data as usize, len)?;
+
+            if circular && len < self.chunk_size * 2 {
                 return Err(DmaError::BufferTooSmall);
             }
 
@@ -346,8 +590,14 @@ pub(crate) mod private {
             self.last_seen_handled_descriptor_ptr = core::ptr::null();
             self.read_buffer_start = data;
 
-            self.rx_impl
-                .prepare_transfer(self.descriptors, circular, peri, data, len)?;
+            self.rx_impl.prepare_transfer(
+                self.descriptors,
+                circular,
+                peri,
+                data,
+                len,
+                self.chunk_size,
+            )?;
             Ok(())
         }
 
@@ -355,6 +605,12 @@ pub(crate) mod private {
             self.rx_impl.is_done()
         }
 
+        fn stop(&mut self) {
+            R::stop_in();
+            R::reset_in();
+            R::clear_in_interrupts();
+        }
+
         fn init_channel(&mut self) {
             R::init_channel();
         }
@@ -417,6 +673,22 @@ pub(crate) mod private {
             Ok(data.len())
         }
 
+        fn pop_partial(&mut self, data: &mut [u8]) -> usize {
+            let count = usize::min(self.available, data.len());
+
+            unsafe {
+                core::ptr::copy_nonoverlapping(self.read_buffer_start, data.as_mut_ptr(), count);
+                self.read_buffer_start = self.read_buffer_start.add(count);
+            }
+
+            self.available -= count;
+            count
+        }
+
+        fn has_error(&mut self) -> bool {
+            R::has_in_descriptor_error()
+        }
+
         fn drain_buffer(&mut self, dst: &mut [u8]) -> Result<usize, DmaError> {
             let mut len: usize = 0;
             let mut dscr = self.descriptors.as_ptr() as *mut u32;
@@ -444,6 +716,59 @@ pub(crate) mod private {
 
             Ok(len)
         }
+
+        fn set_chunk_size(&mut self, chunk_size: usize) {
+            self.chunk_size = chunk_size;
+        }
+
+        #[cfg(gdma)]
+        fn set_mem2mem(&mut self, enable: bool) {
+            R::set_mem_trans(enable);
+        }
+
+        #[cfg(gdma)]
+        fn listen_done(&mut self) {
+            R::listen_in_done();
+        }
+
+        #[cfg(gdma)]
+        fn unlisten_done(&mut self) {
+            R::unlisten_in_done();
+        }
+
+        #[cfg(gdma)]
+        fn clear_done(&mut self) {
+            R::clear_in_done();
+        }
+
+        #[cfg(gdma)]
+        fn listen_error(&mut self) {
+            R::listen_in_error();
+        }
+
+        #[cfg(gdma)]
+        fn unlisten_error(&mut self) {
+            R::unlisten_in_error();
+        }
+
+        #[cfg(gdma)]
+        fn set_check_owner(&mut self, enable: bool) {
+            R::set_in_check_owner(enable);
+        }
+
+        #[cfg(gdma)]
+        fn channel_number(&self) -> u8 {
+            R::CHANNEL
+        }
+
+        #[cfg(esp32s3)]
+        fn set_ext_mem_block_size(&mut self, size: u8) {
+            R::set_in_ext_mem_block_size(size);
+            #[cfg(feature = "psram")]
+            {
+                self.ext_mem_alignment = EXT_MEM_DEFAULT_ALIGNMENT << size;
+            }
+        }
     }
 
     /// DMA Tx
@@ -465,9 +790,25 @@ pub(crate) mod private {
 
         fn is_done(&mut self) -> bool;
 
+        /// Abort the transfer, the DMA doesn't touch the buffer afterwards
+        fn stop(&mut self);
+
         fn available(&mut self) -> usize;
 
         fn push(&mut self, data: &[u8]) -> Result<usize, super::DmaError>;
+
+        fn listen_eof(&mut self);
+
+        fn unlisten_eof(&mut self);
+
+        fn is_eof_set(&mut self) -> bool;
+
+        fn clear_eof(&mut self);
+
+        fn set_chunk_size(&mut self, chunk_size: usize);
+
+        #[cfg(esp32s3)]
+        fn set_ext_mem_block_size(&mut self, size: u8);
     }
 
     pub trait TxChannel<R>
@@ -486,46 +827,9 @@ pub(crate) mod private {
             peri: DmaPeripheral,
             data: *const u8,
             len: usize,
+            chunk_size: usize,
         ) -> Result<(), DmaError> {
-            for descr in descriptors.iter_mut() {
-                *descr = 0;
-            }
-
-            compiler_fence(core::sync::atomic::Ordering::SeqCst);
-
-            let mut processed = 0;
-            let mut descr = 0;
-            loop {
-                let chunk_size = usize::min(CHUNK_SIZE, len - processed);
-                let last = processed + chunk_size >= len;
-
-                descriptors[descr + 1] = data as u32 + processed as u32;
-
-                let mut dw0 = &mut descriptors[descr];
-
-                dw0.set_suc_eof(true);
-                dw0.set_owner(Owner::Dma);
-                dw0.set_size(chunk_size as u16); // align to 32 bits?
-                dw0.set_length(chunk_size as u16); // actual size of the data!?
-
-                if !last {
-                    descriptors[descr + 2] =
-                        (&descriptors[descr + 3]) as *const _ as *const () as u32;
-                } else {
-                    if !circular {
-                        descriptors[descr + 2] = 0;
-                    } else {
-                        descriptors[descr + 2] = descriptors.as_ptr() as u32;
-                    }
-                }
-
-                processed += chunk_size;
-                descr += 3;
-
-                if processed >= len {
-                    break;
-                }
-            }
+            build_descriptor_chain(descriptors, data as u32, len, chunk_size, circular, true);
 
             R::clear_out_interrupts();
             R::reset_out();
@@ -552,6 +856,14 @@ pub(crate) mod private {
             R::reset_out_eof_interrupt();
         }
 
+        fn listen_eof(&self) {
+            R::listen_out_eof();
+        }
+
+        fn unlisten_eof(&self) {
+            R::unlisten_out_eof();
+        }
+
         fn last_out_dscr_address(&self) -> usize {
             R::last_out_dscr_address()
         }
@@ -565,6 +877,7 @@ pub(crate) mod private {
         pub descriptors: &'a mut [u32],
         #[allow(unused)]
         pub burst_mode: bool,
+        pub chunk_size: usize,
         pub tx_impl: T,
         pub write_offset: usize,
         pub write_descr_ptr: *const u32,
@@ -572,9 +885,36 @@ pub(crate) mod private {
         pub last_seen_handled_descriptor_ptr: *const u32,
         pub buffer_start: *const u8,
         pub buffer_len: usize,
+        /// Alignment of PSRAM buffers for the external memory block size
+        #[cfg(all(esp32s3, feature = "psram"))]
+        pub ext_mem_alignment: usize,
         pub _phantom: PhantomData<R>,
     }
 
+    impl<'a, T, R> ChannelTx<'a, T, R>
+    where
+        T: TxChannel<R>,
+        R: RegisterAccess,
+    {
+        /// Check that the DMA can access the `len` bytes at `address`
+        fn check_buffer(&self, address: usize, len: usize) -> Result<(), DmaError> {
+            if len == 0 {
+                return Ok(());
+            }
+
+            match memory_region(address, len)? {
+                MemoryRegion::Internal => Ok(()),
+                #[cfg(all(esp32s3, feature = "psram"))]
+                MemoryRegion::External => {
+                    if address % self.ext_mem_alignment != 0 || len % self.ext_mem_alignment != 0 {
+                        return Err(DmaError::Unaligned);
+                    }
+                    Ok(())
+                }
+            }
+        }
+    }
+
     impl<'a, T, R> Tx for ChannelTx<'a, T, R>
     where
         T: TxChannel<R>,
@@ -599,11 +939,13 @@ pub(crate) mod private {
                 return Err(DmaError::InvalidDescriptorSize);
             }
 
-            if self.descriptors.len() / 3 < len / CHUNK_SIZE {
-                return Err(DmaError::OutOfDescriptors);
+            if self.descriptors.len() / 3 < descriptors_needed(len, self.chunk_size) {
+                return Err(DmaError::DescriptorsExhausted);
             }
 
-            if circular && len < CHUNK_SIZE * 2 {
+            self.check_buffer(data as usize, len)?;
+
+            if circular && len < self.chunk_size * 2 {
                 return Err(DmaError::BufferTooSmall);
             }
 
@@ -614,8 +956,14 @@ pub(crate) mod private {
             self.buffer_start = data;
             self.buffer_len = len;
 
-            self.tx_impl
-                .prepare_transfer(self.descriptors, circular, peri, data, len)?;
+            self.tx_impl.prepare_transfer(
+                self.descriptors,
+                circular,
+                peri,
+                data,
+                len,
+                self.chunk_size,
+            )?;
 
             Ok(())
         }
@@ -624,6 +972,12 @@ pub(crate) mod private {
             self.tx_impl.is_done()
         }
 
+        fn stop(&mut self) {
+            R::stop_out();
+            R::reset_out();
+            R::clear_out_interrupts();
+        }
+
         fn available(&mut self) -> usize {
             if self.tx_impl.descriptors_handled() {
                 self.tx_impl.reset_descriptors_handled();
@@ -730,9 +1084,43 @@ pub(crate) mod private {
 
             Ok(data.len())
         }
+
+        fn listen_eof(&mut self) {
+            self.tx_impl.listen_eof();
+        }
+
+        fn unlisten_eof(&mut self) {
+            self.tx_impl.unlisten_eof();
+        }
+
+        fn is_eof_set(&mut self) -> bool {
+            self.tx_impl.descriptors_handled()
+        }
+
+        fn clear_eof(&mut self) {
+            // `available` accounts for the handled descriptors before the flag is
+            // cleared, otherwise the freed space would never show up
+            self.available();
+        }
+
+        fn set_chunk_size(&mut self, chunk_size: usize) {
+            self.chunk_size = chunk_size;
+        }
+
+        #[cfg(esp32s3)]
+        fn set_ext_mem_block_size(&mut self, size: u8) {
+            R::set_out_ext_mem_block_size(size);
+            #[cfg(feature = "psram")]
+            {
+                self.ext_mem_alignment = EXT_MEM_DEFAULT_ALIGNMENT << size;
+            }
+        }
     }
 
     pub trait RegisterAccess {
+        #[cfg(gdma)]
+        const CHANNEL: u8;
+
         fn init_channel();
         fn set_out_burstmode(burst_mode: bool);
         fn set_out_priority(priority: DmaPriority);
@@ -742,9 +1130,12 @@ pub(crate) mod private {
         fn has_out_descriptor_error() -> bool;
         fn set_out_peripheral(peripheral: u8);
         fn start_out();
+        fn stop_out();
         fn is_out_done() -> bool;
         fn is_out_eof_interrupt_set() -> bool;
         fn reset_out_eof_interrupt();
+        fn listen_out_eof();
+        fn unlisten_out_eof();
         fn last_out_dscr_address() -> usize;
 
         fn set_in_burstmode(burst_mode: bool);
@@ -755,8 +1146,28 @@ pub(crate) mod private {
         fn has_in_descriptor_error() -> bool;
         fn set_in_peripheral(peripheral: u8);
         fn start_in();
+        fn stop_in();
         fn is_in_done() -> bool;
         fn last_in_dscr_address() -> usize;
+
+        #[cfg(gdma)]
+        fn set_mem_trans(enable: bool);
+        #[cfg(gdma)]
+        fn listen_in_done();
+        #[cfg(gdma)]
+        fn unlisten_in_done();
+        #[cfg(gdma)]
+        fn clear_in_done();
+        #[cfg(gdma)]
+        fn listen_in_error();
+        #[cfg(gdma)]
+        fn unlisten_in_error();
+        #[cfg(gdma)]
+        fn set_in_check_owner(enable: bool);
+        #[cfg(esp32s3)]
+        fn set_out_ext_mem_block_size(size: u8);
+        #[cfg(esp32s3)]
+        fn set_in_ext_mem_block_size(size: u8);
     }
 }
 
@@ -785,3 +1196,90 @@ pub trait DmaTransferRxTx<BR, BT, T>: Drop {
     /// Wait for the transfer to finish.
     fn wait(self) -> (BR, BT, T);
 }
+
+const _: () = {
+    assert!(descriptors_needed(0, CHUNK_SIZE) == 1);
+    assert!(descriptors_needed(CHUNK_SIZE, CHUNK_SIZE) == 1);
+    assert!(descriptors_needed(CHUNK_SIZE + 1, CHUNK_SIZE) == 2);
+
+    assert!(range_contains(&DMA_DRAM, DMA_DRAM.start, 4));
+    assert!(range_contains(&DMA_DRAM, DMA_DRAM.end - 4, 4));
+    assert!(range_contains(&DMA_DRAM, DMA_DRAM.end - 4, 0));
+    assert!(range_contains(
+        &DMA_DRAM,
+        DMA_DRAM.start,
+        DMA_DRAM.end - DMA_DRAM.start
+    ));
+    assert!(!range_contains(&DMA_DRAM, DMA_DRAM.end - 4, 8));
+    assert!(!range_contains(&DMA_DRAM, DMA_DRAM.end, 0));
+    assert!(!range_contains(&DMA_DRAM, DMA_DRAM.start - 4, 8));
+    assert!(!range_contains(&DMA_DRAM, DMA_DRAM.start, usize::MAX));
+    assert!(!range_contains(&DMA_DRAM, usize::MAX, 1));
+
+    assert!(matches!(
+        classify(DMA_DRAM.start + 0x100, 64),
+        Ok(MemoryRegion::Internal)
+    ));
+    assert!(matches!(
+        classify(DMA_DRAM.end - 32, 64),
+        Err(DmaError::UnsupportedMemoryRegion)
+    ));
+    // constants in flash: ESP32 and ESP32-S2 DROM, then the external memory of
+    // the other chips
+    assert!(matches!(
+        classify(0x3f40_0000, 64),
+        Err(DmaError::UnsupportedMemoryRegion)
+    ));
+    #[cfg(not(all(esp32s3, feature = "psram")))]
+    assert!(matches!(
+        classify(0x3c00_0000, 64),
+        Err(DmaError::UnsupportedMemoryRegion)
+    ));
+    // the MMU decides between flash and PSRAM
+    #[cfg(all(esp32s3, feature = "psram"))]
+    assert!(matches!(
+        classify(0x3d00_0000, 64),
+        Ok(MemoryRegion::External)
+    ));
+    #[cfg(all(esp32s3, feature = "psram"))]
+    assert!(matches!(
+        classify(0x3e00_0000, 64),
+        Err(DmaError::UnsupportedMemoryRegion)
+    ));
+};
+
+// The data RAM of every chip, whichever is being compiled
+const _: () = {
+    let all = [
+        dma_dram::ESP32,
+        dma_dram::ESP32S2,
+        dma_dram::ESP32C2,
+        dma_dram::ESP32C3,
+        dma_dram::ESP32S3,
+    ];
+    let mut i = 0;
+    while i < all.len() {
+        let dram = &all[i];
+        assert!(dram.start < dram.end);
+        // constants in flash
+        assert!(!range_contains(dram, 0x3f40_0000, 4));
+        assert!(!range_contains(dram, 0x3f00_0000, 4));
+        assert!(!range_contains(dram, 0x3c00_0000, 4));
+        // instruction bus of the internal RAM and code in flash
+        assert!(!range_contains(dram, 0x4037_c000, 4));
+        assert!(!range_contains(dram, 0x4008_0000, 4));
+        assert!(!range_contains(dram, 0x4200_0000, 4));
+        i += 1;
+    }
+
+    // the start of the DRAM of the 2nd stage bootloader and the heap
+    assert!(range_contains(&dma_dram::ESP32, 0x3ffb_0000, 4));
+    assert!(range_contains(&dma_dram::ESP32S2, 0x3ffb_0000, 4));
+    assert!(range_contains(&dma_dram::ESP32C2, 0x3fca_0000, 4));
+    assert!(range_contains(&dma_dram::ESP32C3, 0x3fc8_0000, 4));
+    assert!(range_contains(&dma_dram::ESP32S3, 0x3fc8_8000, 4));
+    // the ROM reserves the end of the internal RAM of the ESP32-S3
+    assert!(!range_contains(&dma_dram::ESP32S3, 0x3fd0_0000, 4));
+    // the ESP32 data RAM below 0x3ffa_e000 is the ROM's
+    assert!(!range_contains(&dma_dram::ESP32, 0x3ffa_0000, 4));
+};
@@ -81,6 +81,11 @@ macro_rules! ImplSpiChannel {
                     spi.dma_out_link.modify(|_, w| w.outlink_start().set_bit());
                 }
 
+                fn stop_out() {
+                    let spi = unsafe { &*crate::pac::[<SPI $num>]::PTR };
+                    spi.dma_out_link.modify(|_, w| w.outlink_stop().set_bit());
+                }
+
                 fn is_out_done() -> bool {
                     let spi = unsafe { &*crate::pac::[<SPI $num>]::PTR };
                     spi.dma_int_raw.read().out_done_int_raw().bit()
@@ -104,6 +109,16 @@ macro_rules! ImplSpiChannel {
                     });
                 }
 
+                fn listen_out_eof() {
+                    let spi = unsafe { &*crate::pac::[<SPI $num>]::PTR };
+                    spi.dma_int_ena.modify(|_, w| w.out_eof_int_ena().set_bit());
+                }
+
+                fn unlisten_out_eof() {
+                    let spi = unsafe { &*crate::pac::[<SPI $num>]::PTR };
+                    spi.dma_int_ena.modify(|_, w| w.out_eof_int_ena().clear_bit());
+                }
+
                 fn set_in_burstmode(burst_mode: bool) {
                     let spi = unsafe { &*crate::pac::[<SPI $num>]::PTR };
                     spi.dma_conf
@@ -152,6 +167,11 @@ macro_rules! ImplSpiChannel {
                     spi.dma_in_link.modify(|_, w| w.inlink_start().set_bit());
                 }
 
+                fn stop_in() {
+                    let spi = unsafe { &*crate::pac::[<SPI $num>]::PTR };
+                    spi.dma_in_link.modify(|_, w| w.inlink_stop().set_bit());
+                }
+
                 fn is_in_done() -> bool {
                     let spi = unsafe { &*crate::pac::[<SPI $num>]::PTR };
                     spi.dma_int_raw.read().in_done_int_raw().bit()
@@ -191,6 +211,7 @@ macro_rules! ImplSpiChannel {
                     let tx_channel = ChannelTx {
                         descriptors: tx_descriptors,
                         burst_mode,
+                        chunk_size: CHUNK_SIZE,
                         tx_impl: tx_impl,
                         write_offset: 0,
                         write_descr_ptr: core::ptr::null(),
@@ -207,6 +228,7 @@ macro_rules! ImplSpiChannel {
                     let rx_channel = ChannelRx {
                         descriptors: rx_descriptors,
                         burst_mode,
+                        chunk_size: CHUNK_SIZE,
                         rx_impl: rx_impl,
                         read_descr_ptr: core::ptr::null(),
                         available: 0,
@@ -284,6 +306,11 @@ macro_rules! ImplI2sChannel {
                     reg_block.out_link.modify(|_, w| w.outlink_start().set_bit());
                 }
 
+                fn stop_out() {
+                    let reg_block = unsafe { &*crate::pac::[<$peripheral>]::PTR };
+                    reg_block.out_link.modify(|_, w| w.outlink_stop().set_bit());
+                }
+
                 fn is_out_done() -> bool {
                     let reg_block = unsafe { &*crate::pac::[<$peripheral>]::PTR };
                     reg_block.int_raw.read().out_done_int_raw().bit()
@@ -307,6 +334,16 @@ macro_rules! ImplI2sChannel {
                     });
                 }
 
+                fn listen_out_eof() {
+                    let reg_block = unsafe { &*crate::pac::[<$peripheral>]::PTR };
+                    reg_block.int_ena.modify(|_, w| w.out_eof_int_ena().set_bit());
+                }
+
+                fn unlisten_out_eof() {
+                    let reg_block = unsafe { &*crate::pac::[<$peripheral>]::PTR };
+                    reg_block.int_ena.modify(|_, w| w.out_eof_int_ena().clear_bit());
+                }
+
                 fn set_in_burstmode(burst_mode: bool) {
                     let reg_block = unsafe { &*crate::pac::[<$peripheral>]::PTR };
                     reg_block.lc_conf
@@ -355,6 +392,11 @@ macro_rules! ImplI2sChannel {
                     reg_block.in_link.modify(|_, w| w.inlink_start().set_bit());
                 }
 
+                fn stop_in() {
+                    let reg_block = unsafe { &*crate::pac::[<$peripheral>]::PTR };
+                    reg_block.in_link.modify(|_, w| w.inlink_stop().set_bit());
+                }
+
                 fn is_in_done() -> bool {
                     let reg_block = unsafe { &*crate::pac::[<$peripheral>]::PTR };
                     reg_block.int_raw.read().in_done_int_raw().bit()
@@ -394,6 +436,7 @@ macro_rules! ImplI2sChannel {
                     let tx_channel = ChannelTx {
                         descriptors: tx_descriptors,
                         burst_mode,
+                        chunk_size: CHUNK_SIZE,
                         tx_impl: tx_impl,
                         write_offset: 0,
                         write_descr_ptr: core::ptr::null(),
@@ -410,6 +453,7 @@ macro_rules! ImplI2sChannel {
                     let rx_channel = ChannelRx {
                         descriptors: rx_descriptors,
                         burst_mode,
+                        chunk_size: CHUNK_SIZE,
                         rx_impl: rx_impl,
                         read_descr_ptr: core::ptr::null(),
                         available: 0,
@@ -0,0 +1,237 @@
+//! Digital Signature Peripheral
+//!
+//! The Digital Signature (DS) peripheral produces RSA signatures with a private
+//! key that is only ever available to software in encrypted form. The
+//! encrypted key parameters are decrypted inside the peripheral with an AES key
+//! derived by the HMAC peripheral from an eFuse key block, which has to be
+//! provisioned with the `HMAC_DOWN_DIGITAL_SIGNATURE` (or `HMAC_DOWN_ALL`)
+//! purpose.
+//!
+//! The encrypted parameters are usually generated on the host, e.g. with
+//! ESP-IDF's `esp_efuse_helper`/`configure_ds.py` tooling.
+
+use crate::{
+    crypto_dma::CryptoDma,
+    hmac::{self, Hmac, HmacPurpose, KeyId},
+    pac::{DS, HMAC},
+    system::{Peripheral, PeripheralClockControl},
+};
+
+/// Maximum RSA key length supported by the peripheral, in bits
+#[cfg(esp32c3)]
+pub const MAX_KEY_BITS: usize = 3072;
+/// Maximum RSA key length supported by the peripheral, in bits
+#[cfg(esp32s3)]
+pub const MAX_KEY_BITS: usize = 4096;
+
+const MAX_KEY_BYTES: usize = MAX_KEY_BITS / 8;
+
+/// Length of the encrypted key parameters
+pub const C_LENGTH: usize = MAX_KEY_BYTES * 3 + BOX_LENGTH;
+
+/// Length of the initialization vector used to encrypt the key parameters
+pub const IV_LENGTH: usize = 16;
+
+const BOX_LENGTH: usize = 48;
+
+const Y_MEM: usize = 0x000;
+const M_MEM: usize = 0x200;
+const RB_MEM: usize = 0x400;
+const BOX_MEM: usize = 0x600;
+const IV_MEM: usize = 0x630;
+const X_MEM: usize = 0x800;
+const Z_MEM: usize = 0xA00;
+
+const PADDING_BAD: u32 = 1 << 0;
+const MD_ERROR: u32 = 1 << 1;
+
+/// Digital Signature errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Deriving the decryption key with the HMAC peripheral failed
+    Hmac(hmac::Error),
+    /// The peripheral didn't get a valid decryption key
+    InvalidKey,
+    /// The decrypted parameters don't match their digest, i.e. the parameters
+    /// were encrypted with a different key or have been tampered with
+    InvalidDigest,
+    /// The padding of the decrypted parameters is invalid
+    InvalidPadding,
+    /// The RSA length of the parameters isn't supported or the message /
+    /// signature buffers don't match it
+    InvalidLength,
+    /// The crypto accelerators used by the peripheral are in use by another
+    /// driver
+    Busy,
+}
+
+impl From<hmac::Error> for Error {
+    fn from(error: hmac::Error) -> Self {
+        Error::Hmac(error)
+    }
+}
+
+/// Encrypted private key parameters
+pub struct EncryptedParams {
+    /// IV used to encrypt the parameters
+    pub iv: [u8; IV_LENGTH],
+    /// Encrypted key parameters
+    pub c: [u8; C_LENGTH],
+    /// Length of the RSA key, in 32 bit words minus one (e.g. 63 for RSA-2048)
+    pub rsa_length: usize,
+}
+
+impl EncryptedParams {
+    /// Length of the message and signature, in bytes
+    pub fn signature_length(&self) -> usize {
+        (self.rsa_length + 1) * 4
+    }
+}
+
+/// Digital Signature peripheral driver
+pub struct Ds {
+    ds: DS,
+    hmac: Hmac,
+    signature_length: usize,
+    lock: Option<CryptoDma>,
+}
+
+impl Ds {
+    pub fn new(ds: DS, hmac: HMAC, peripheral_clock_control: &mut PeripheralClockControl) -> Self {
+        peripheral_clock_control.enable(Peripheral::Ds);
+
+        Self {
+            ds,
+            hmac: Hmac::new(hmac, peripheral_clock_control),
+            signature_length: 0,
+            lock: None,
+        }
+    }
+
+    /// Starts signing `message` with the given encrypted parameters and the
+    /// eFuse key block they have been encrypted for.
+    ///
+    /// `message` is the little-endian integer to sign and must be exactly
+    /// [EncryptedParams::signature_length] bytes long. It's the caller's
+    /// responsibility to hash and pad the message (e.g. PKCS#1 v1.5).
+    ///
+    /// Returns [Error::Busy] while the SHA or AES accelerator is used by
+    /// another driver, they stay taken until [Ds::finish_sign] returned.
+    pub fn start_sign(
+        &mut self,
+        key: KeyId,
+        params: &EncryptedParams,
+        message: &[u8],
+    ) -> Result<(), Error> {
+        let length = params.signature_length();
+        if length > MAX_KEY_BYTES || message.len() != length {
+            return Err(Error::InvalidLength);
+        }
+
+        let lock = CryptoDma::try_acquire().ok_or(Error::Busy)?;
+        self.hmac.start(HmacPurpose::ToDs, key)?;
+        self.lock = Some(lock);
+
+        self.ds.set_start.write(|w| unsafe { w.bits(1) });
+        while self.is_busy() {}
+
+        if self.ds.query_key_wrong.read().bits() != 0 {
+            self.finish();
+            return Err(Error::InvalidKey);
+        }
+
+        unsafe {
+            self.write_mem(IV_MEM, &params.iv);
+
+            let (y, rest) = params.c.split_at(MAX_KEY_BYTES);
+            let (m, rest) = rest.split_at(MAX_KEY_BYTES);
+            let (rb, key_box) = rest.split_at(MAX_KEY_BYTES);
+            self.write_mem(Y_MEM, y);
+            self.write_mem(M_MEM, m);
+            self.write_mem(RB_MEM, rb);
+            self.write_mem(BOX_MEM, key_box);
+
+            self.write_mem(X_MEM, message);
+        }
+
+        self.ds.set_continue.write(|w| unsafe { w.bits(1) });
+        self.signature_length = length;
+
+        Ok(())
+    }
+
+    /// Reads the signature once the peripheral is done.
+    ///
+    /// `signature` must be exactly [EncryptedParams::signature_length] bytes
+    /// long and receives the signature as a little-endian integer.
+    pub fn finish_sign(&mut self, signature: &mut [u8]) -> nb::Result<(), Error> {
+        if self.is_busy() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        if signature.len() != self.signature_length {
+            self.finish();
+            return Err(nb::Error::Other(Error::InvalidLength));
+        }
+
+        let check = self.ds.query_check.read().bits();
+        let result = if check & MD_ERROR != 0 {
+            Err(nb::Error::Other(Error::InvalidDigest))
+        } else if check & PADDING_BAD != 0 {
+            Err(nb::Error::Other(Error::InvalidPadding))
+        } else {
+            unsafe { self.read_mem(Z_MEM, signature) };
+            Ok(())
+        };
+
+        self.finish();
+
+        result
+    }
+
+    /// Signs `message`, blocking until the signature is available.
+    ///
+    /// See [Ds::start_sign] and [Ds::finish_sign].
+    pub fn sign(
+        &mut self,
+        key: KeyId,
+        params: &EncryptedParams,
+        message: &[u8],
+        signature: &mut [u8],
+    ) -> Result<(), Error> {
+        self.start_sign(key, params, message)?;
+        nb::block!(self.finish_sign(signature))
+    }
+
+    pub fn free(self) -> (DS, HMAC) {
+        (self.ds, self.hmac.free())
+    }
+
+    fn is_busy(&self) -> bool {
+        self.ds.query_busy.read().bits() != 0
+    }
+
+    fn finish(&mut self) {
+        self.ds.set_finish.write(|w| unsafe { w.bits(1) });
+        self.hmac.invalidate_ds();
+        self.signature_length = 0;
+        self.lock = None;
+    }
+
+    unsafe fn write_mem(&mut self, offset: usize, data: &[u8]) {
+        let dst = (DS::PTR as *mut u8).add(offset) as *mut u32;
+        for (i, chunk) in data.chunks(4).enumerate() {
+            let mut word = [0u8; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            dst.add(i).write_volatile(u32::from_le_bytes(word));
+        }
+    }
+
+    unsafe fn read_mem(&self, offset: usize, data: &mut [u8]) {
+        let src = (DS::PTR as *const u8).add(offset) as *const u32;
+        for (i, chunk) in data.chunks_mut(4).enumerate() {
+            let word = src.add(i).read_volatile().to_le_bytes();
+            chunk.copy_from_slice(&word[..chunk.len()]);
+        }
+    }
+}
@@ -119,4 +119,27 @@ impl Efuse {
             % 2)
             != 0
     }
+
+    /// Returns the major chip revision.
+    pub fn get_major_chip_version() -> u8 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        let apb_ctrl = unsafe { &*crate::pac::APB_CTRL::ptr() };
+
+        let eco_bit0 = (efuse.blk0_rdata3.read().bits() >> 15) & 0x1;
+        let eco_bit1 = (efuse.blk0_rdata5.read().bits() >> 20) & 0x1;
+        let eco_bit2 = apb_ctrl.date.read().bits() >> 31;
+
+        match (eco_bit2 << 2) | (eco_bit1 << 1) | eco_bit0 {
+            1 => 1,
+            3 => 2,
+            7 => 3,
+            _ => 0,
+        }
+    }
+
+    /// Returns the minor chip revision.
+    pub fn get_minor_chip_version() -> u8 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        ((efuse.blk0_rdata5.read().bits() >> 24) & 0x3) as u8
+    }
 }
@@ -59,4 +59,16 @@ impl Efuse {
         let efuse = unsafe { &*EFUSE::ptr() };
         efuse.rd_repeat_data0.read().wdt_delay_sel().bits()
     }
+
+    /// Returns the major chip revision.
+    pub fn get_major_chip_version() -> u8 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        ((efuse.rd_blk2_data1.read().bits() >> 20) & 0x3) as u8
+    }
+
+    /// Returns the minor chip revision.
+    pub fn get_minor_chip_version() -> u8 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        ((efuse.rd_blk2_data1.read().bits() >> 16) & 0xf) as u8
+    }
 }
@@ -59,4 +59,42 @@ impl Efuse {
         let efuse = unsafe { &*EFUSE::ptr() };
         efuse.rd_repeat_data1.read().wdt_delay_sel().bits()
     }
+
+    /// Returns the major chip revision.
+    pub fn get_major_chip_version() -> u8 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        ((efuse.rd_mac_spi_sys_3.read().bits() >> 18) & 0x3) as u8
+    }
+
+    /// Returns the minor chip revision.
+    pub fn get_minor_chip_version() -> u8 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        let hi = (efuse.rd_mac_spi_sys_3.read().bits() >> 20) & 0x1;
+        let lo = (efuse.rd_mac_spi_sys_4.read().bits() >> 4) & 0x7;
+        ((hi << 3) | lo) as u8
+    }
+
+    /// Returns the size of the flash embedded in the chip package, in MB.
+    ///
+    /// Returns `None` if there is no embedded flash.
+    pub fn get_embedded_flash_size() -> Option<u32> {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        match (efuse.rd_mac_spi_sys_3.read().bits() >> 21) & 0xf {
+            1 => Some(2),
+            2 => Some(4),
+            _ => None,
+        }
+    }
+
+    /// Returns the size of the PSRAM embedded in the chip package, in MB.
+    ///
+    /// Returns `None` if there is no embedded PSRAM.
+    pub fn get_embedded_psram_size() -> Option<u32> {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        match (efuse.rd_mac_spi_sys_3.read().bits() >> 28) & 0xf {
+            1 => Some(2),
+            2 => Some(4),
+            _ => None,
+        }
+    }
 }
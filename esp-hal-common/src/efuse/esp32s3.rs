@@ -1,9 +1,52 @@
-//! Reading of eFuses
+//! Reading and writing of eFuses
 
+#[cfg(feature = "efuse-writing")]
+pub use self::write::*;
 use crate::pac::EFUSE;
 
+#[cfg(feature = "efuse-writing")]
+mod write;
+
 pub struct Efuse;
 
+/// Purpose of an eFuse key block (`BLOCK_KEY0` to `BLOCK_KEY5`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyPurpose {
+    User,
+    Reserved,
+    XtsAes256Key1,
+    XtsAes256Key2,
+    XtsAes128Key,
+    HmacDownAll,
+    HmacDownJtag,
+    HmacDownDigitalSignature,
+    HmacUp,
+    SecureBootDigest0,
+    SecureBootDigest1,
+    SecureBootDigest2,
+    Unknown(u8),
+}
+
+impl KeyPurpose {
+    fn from_bits(bits: u8) -> Self {
+        match bits {
+            0 => KeyPurpose::User,
+            1 => KeyPurpose::Reserved,
+            2 => KeyPurpose::XtsAes256Key1,
+            3 => KeyPurpose::XtsAes256Key2,
+            4 => KeyPurpose::XtsAes128Key,
+            5 => KeyPurpose::HmacDownAll,
+            6 => KeyPurpose::HmacDownJtag,
+            7 => KeyPurpose::HmacDownDigitalSignature,
+            8 => KeyPurpose::HmacUp,
+            9 => KeyPurpose::SecureBootDigest0,
+            10 => KeyPurpose::SecureBootDigest1,
+            11 => KeyPurpose::SecureBootDigest2,
+            other => KeyPurpose::Unknown(other),
+        }
+    }
+}
+
 impl Efuse {
     /// Reads chip's MAC address from the eFuse storage.
     ///
@@ -59,4 +102,75 @@ impl Efuse {
         let efuse = unsafe { &*EFUSE::ptr() };
         efuse.rd_repeat_data1.read().wdt_delay_sel().bits()
     }
+
+    /// Get the configured purpose of the given eFuse key block.
+    ///
+    /// `key` is the number of the key block, from 0 (`BLOCK_KEY0`) to 5
+    /// (`BLOCK_KEY5`). Returns `None` for any other number.
+    pub fn get_key_purpose(key: u8) -> Option<KeyPurpose> {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        let bits = match key {
+            0 => efuse.rd_repeat_data1.read().key_purpose_0().bits(),
+            1 => efuse.rd_repeat_data1.read().key_purpose_1().bits(),
+            2 => efuse.rd_repeat_data2.read().key_purpose_2().bits(),
+            3 => efuse.rd_repeat_data2.read().key_purpose_3().bits(),
+            4 => efuse.rd_repeat_data2.read().key_purpose_4().bits(),
+            5 => efuse.rd_repeat_data2.read().key_purpose_5().bits(),
+            _ => return None,
+        };
+
+        Some(KeyPurpose::from_bits(bits))
+    }
+
+    /// Returns the major chip revision.
+    pub fn get_major_chip_version() -> u8 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        ((efuse.rd_mac_spi_sys_5.read().bits() >> 24) & 0x3) as u8
+    }
+
+    /// Returns the minor chip revision.
+    pub fn get_minor_chip_version() -> u8 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        let hi = (efuse.rd_mac_spi_sys_5.read().bits() >> 23) & 0x1;
+        let lo = (efuse.rd_mac_spi_sys_3.read().bits() >> 18) & 0x7;
+        ((hi << 3) | lo) as u8
+    }
+
+    /// Returns the size of the flash embedded in the chip package, in MB.
+    ///
+    /// Returns `None` if there is no embedded flash.
+    pub fn get_embedded_flash_size() -> Option<u32> {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        match (efuse.rd_mac_spi_sys_3.read().bits() >> 27) & 0x7 {
+            1 => Some(4),
+            2 => Some(2),
+            3 => Some(1),
+            4 => Some(8),
+            _ => None,
+        }
+    }
+
+    /// Returns the size of the PSRAM embedded in the chip package, in MB.
+    ///
+    /// Returns `None` if there is no embedded PSRAM.
+    pub fn get_embedded_psram_size() -> Option<u32> {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        match (efuse.rd_mac_spi_sys_4.read().bits() >> 3) & 0x3 {
+            1 => Some(8),
+            2 => Some(2),
+            _ => None,
+        }
+    }
+
+    /// Returns the number of CPUs available on the chip.
+    pub fn get_core_count() -> u32 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+
+        let cpu_disabled = (efuse.rd_repeat_data0.read().bits() >> 15) & 0x1 != 0;
+        if cpu_disabled {
+            1
+        } else {
+            2
+        }
+    }
 }
@@ -0,0 +1,452 @@
+//! Writing of the user eFuse blocks
+//!
+//! Burning an eFuse is irreversible, a bit once set can never be cleared. To
+//! make accidental burns unlikely this is only available with the
+//! `efuse-writing` feature and every write takes a [WriteToken], which can
+//! only be created in `unsafe` code.
+//!
+//! Only the user data block (`BLOCK3`) and key blocks with the purpose
+//! [KeyPurpose::User] can be written, the system blocks are refused. Key
+//! blocks whose reading was disabled are refused as well, the written data
+//! couldn't be verified.
+//!
+//! The blocks are protected by a Reed-Solomon code which is burned together
+//! with the data, calculated by the ROM function `ets_efuse_rs_calculate`.
+//! The code can't be updated later, so a block can be written only once:
+//! writing data to a block which already holds other data fails with
+//! [Error::AlreadyProgrammed] or [Error::ConflictingBits].
+//!
+//! [Efuse::write_block_dry_run] runs all checks and reports the bits a write
+//! would burn without touching the hardware.
+
+use super::{Efuse, KeyPurpose};
+use crate::{pac::EFUSE, rom::ets_efuse_rs_calculate};
+
+/// Number of 32 bit words in a block
+const BLOCK_WORDS: usize = 8;
+
+/// Number of Reed-Solomon check bytes of a block
+const RS_CHECK_BYTES: usize = 12;
+
+/// Number of the last key block (`BLOCK_KEY5`)
+const MAX_KEY: u8 = 5;
+
+const CONF_WRITE_OP_CODE: u32 = 0x5a5a;
+const CONF_READ_OP_CODE: u32 = 0x5aa5;
+
+const CMD_READ: u32 = 1 << 0;
+const CMD_PGM: u32 = 1 << 1;
+
+/// eFuse write error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The key block doesn't exist, valid keys are 0 to 5
+    InvalidBlock,
+    /// The key block's purpose isn't [KeyPurpose::User]
+    NotUserBlock,
+    /// Writing to the block was disabled
+    WriteProtected,
+    /// Reading the block was disabled
+    ReadProtected,
+    /// The block already holds different data, new bits can't be added
+    AlreadyProgrammed,
+    /// Bits which are set in the block are cleared in the new data
+    ConflictingBits,
+    /// Reading back the burned block returned different data or reported a
+    /// coding error
+    VerifyFailed,
+}
+
+/// A block which can be written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UserBlock {
+    /// `BLOCK_USR_DATA` (`BLOCK3`)
+    UserData,
+    /// `BLOCK_KEY0` (`BLOCK4`) to `BLOCK_KEY5` (`BLOCK9`)
+    Key(u8),
+}
+
+impl UserBlock {
+    /// Number of the block in the eFuse controller
+    const fn number(self) -> Result<u32, Error> {
+        match self {
+            UserBlock::UserData => Ok(3),
+            UserBlock::Key(key) if key <= MAX_KEY => Ok(4 + key as u32),
+            UserBlock::Key(_) => Err(Error::InvalidBlock),
+        }
+    }
+
+    /// Error status of the block from `RD_RS_ERR0` / `RD_RS_ERR1`
+    fn rs_error(number: u32) -> u32 {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        rs_error_bits(
+            number,
+            efuse.rd_rs_err0.read().bits(),
+            efuse.rd_rs_err1.read().bits(),
+        )
+    }
+
+    fn read(number: u32) -> [u32; BLOCK_WORDS] {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        // The read registers of the user data and key blocks are contiguous
+        let base = &efuse.rd_usr_data0 as *const _ as *const u32;
+        let offset = (number as usize - 3) * BLOCK_WORDS;
+
+        let mut words = [0u32; BLOCK_WORDS];
+        for (i, word) in words.iter_mut().enumerate() {
+            *word = unsafe { base.add(offset + i).read_volatile() };
+        }
+        words
+    }
+}
+
+/// Confirmation that an eFuse block should really be burned
+pub struct WriteToken {
+    _private: (),
+}
+
+impl WriteToken {
+    /// Create a token
+    ///
+    /// # Safety
+    ///
+    /// Burning eFuses is permanent. Writing wrong data, e.g. a wrong MAC
+    /// address, can't be undone and may render the chip unusable for its
+    /// purpose.
+    pub unsafe fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+/// Comparison of the contents of a block with the data requested to write
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockDiff {
+    /// The words currently burned into the block
+    pub current: [u32; BLOCK_WORDS],
+    /// The requested words
+    pub requested: [u32; BLOCK_WORDS],
+}
+
+impl BlockDiff {
+    /// Bits which would be burned
+    pub const fn bits_to_set(&self) -> [u32; BLOCK_WORDS] {
+        let mut bits = [0; BLOCK_WORDS];
+        let mut i = 0;
+        while i < BLOCK_WORDS {
+            bits[i] = self.requested[i] & !self.current[i];
+            i += 1;
+        }
+        bits
+    }
+
+    /// Bits which are set in the block but not in the requested data
+    pub const fn conflicting_bits(&self) -> [u32; BLOCK_WORDS] {
+        let mut bits = [0; BLOCK_WORDS];
+        let mut i = 0;
+        while i < BLOCK_WORDS {
+            bits[i] = self.current[i] & !self.requested[i];
+            i += 1;
+        }
+        bits
+    }
+
+    /// Number of bits which would be burned
+    pub const fn bits_to_set_count(&self) -> u32 {
+        let bits = self.bits_to_set();
+        let mut count = 0;
+        let mut i = 0;
+        while i < BLOCK_WORDS {
+            count += bits[i].count_ones();
+            i += 1;
+        }
+        count
+    }
+
+    /// Whether the block already holds the requested data
+    pub const fn is_unchanged(&self) -> bool {
+        let mut i = 0;
+        while i < BLOCK_WORDS {
+            if self.current[i] != self.requested[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    /// Check whether the requested data can be burned
+    const fn check(&self) -> Result<(), Error> {
+        if !is_blank(&self.conflicting_bits()) {
+            Err(Error::ConflictingBits)
+        } else if !self.is_unchanged() && !is_blank(&self.current) {
+            Err(Error::AlreadyProgrammed)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Efuse {
+    /// Check if `data` can be written to `block` and report the bits which
+    /// would be burned, without burning anything.
+    ///
+    /// Returns the same errors as [Efuse::write_block] except
+    /// [Error::VerifyFailed].
+    pub fn write_block_dry_run(block: UserBlock, data: &[u8; 32]) -> Result<BlockDiff, Error> {
+        let efuse = unsafe { &*EFUSE::ptr() };
+        let number = block.number()?;
+
+        if let UserBlock::Key(key) = block {
+            if Efuse::get_key_purpose(key) != Some(KeyPurpose::User) {
+                return Err(Error::NotUserBlock);
+            }
+        }
+
+        if is_write_disabled(number, efuse.rd_wr_dis.read().bits()) {
+            return Err(Error::WriteProtected);
+        }
+        if is_read_disabled(number, efuse.rd_repeat_data0.read().bits()) {
+            return Err(Error::ReadProtected);
+        }
+
+        let diff = BlockDiff {
+            current: UserBlock::read(number),
+            requested: to_words(data),
+        };
+        diff.check()?;
+
+        Ok(diff)
+    }
+
+    /// Burn `data` into `block` and verify it.
+    ///
+    /// Does nothing if the block already holds `data`. See the [module
+    /// documentation](self) for the restrictions.
+    pub fn write_block(
+        block: UserBlock,
+        data: &[u8; 32],
+        _token: &WriteToken,
+    ) -> Result<(), Error> {
+        let diff = Efuse::write_block_dry_run(block, data)?;
+        if diff.is_unchanged() {
+            return Ok(());
+        }
+        let number = block.number()?;
+
+        let mut check = [0u8; RS_CHECK_BYTES];
+        unsafe { ets_efuse_rs_calculate(data.as_ptr(), check.as_mut_ptr()) };
+
+        critical_section::with(|_| {
+            set_timing();
+            clear_program_registers();
+            program(number, &diff.requested, &check);
+            clear_program_registers();
+            reload();
+        });
+
+        if UserBlock::read(number) != diff.requested || UserBlock::rs_error(number) != 0 {
+            return Err(Error::VerifyFailed);
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether block `number` is write protected, its bit in `WR_DIS` goes from
+/// 22 (`BLOCK_USR_DATA`) to 28 (`BLOCK_KEY5`)
+const fn is_write_disabled(number: u32, wr_dis: u32) -> bool {
+    wr_dis & (1 << (22 + number - 3)) != 0
+}
+
+/// Whether block `number` is read protected, only the key blocks have a bit
+/// in the `RD_DIS` field of `RD_REPEAT_DATA0`, from 0 (`BLOCK_KEY0`) to 5
+/// (`BLOCK_KEY5`)
+const fn is_read_disabled(number: u32, rd_repeat_data0: u32) -> bool {
+    number >= 4 && rd_repeat_data0 & (1 << (number - 4)) != 0
+}
+
+/// Error status of block `number`, four bits per block: blocks 1 to 8 in
+/// `RD_RS_ERR0`, from bit 0, block 9 (`BLOCK_KEY5`) in bits 0 to 3 of
+/// `RD_RS_ERR1`
+const fn rs_error_bits(number: u32, rd_rs_err0: u32, rd_rs_err1: u32) -> u32 {
+    if number <= 8 {
+        (rd_rs_err0 >> ((number - 1) * 4)) & 0xf
+    } else {
+        (rd_rs_err1 >> ((number - 9) * 4)) & 0xf
+    }
+}
+
+const fn to_words(data: &[u8; 32]) -> [u32; BLOCK_WORDS] {
+    let mut words = [0u32; BLOCK_WORDS];
+    let mut i = 0;
+    while i < BLOCK_WORDS {
+        words[i] = u32::from_le_bytes([
+            data[4 * i],
+            data[4 * i + 1],
+            data[4 * i + 2],
+            data[4 * i + 3],
+        ]);
+        i += 1;
+    }
+    words
+}
+
+/// Whether all bits of `words` are cleared
+const fn is_blank(words: &[u32; BLOCK_WORDS]) -> bool {
+    let mut i = 0;
+    while i < BLOCK_WORDS {
+        if words[i] != 0 {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const _: () = {
+    const fn diff(current: [u32; BLOCK_WORDS], requested: [u32; BLOCK_WORDS]) -> BlockDiff {
+        BlockDiff { current, requested }
+    }
+
+    assert!(matches!(UserBlock::UserData.number(), Ok(3)));
+    assert!(matches!(UserBlock::Key(0).number(), Ok(4)));
+    assert!(matches!(UserBlock::Key(5).number(), Ok(9)));
+    assert!(matches!(
+        UserBlock::Key(6).number(),
+        Err(Error::InvalidBlock)
+    ));
+    assert!(matches!(
+        UserBlock::Key(255).number(),
+        Err(Error::InvalidBlock)
+    ));
+
+    assert!(is_write_disabled(3, 1 << 22));
+    assert!(is_write_disabled(4, 1 << 23));
+    assert!(is_write_disabled(9, 1 << 28));
+    assert!(!is_write_disabled(3, !(1 << 22)));
+    assert!(!is_write_disabled(9, 1 << 29));
+
+    // the user data block can't be read protected
+    assert!(!is_read_disabled(3, 0x7f));
+    assert!(is_read_disabled(4, 1 << 0));
+    assert!(is_read_disabled(9, 1 << 5));
+    assert!(!is_read_disabled(9, 1 << 6));
+    assert!(!is_read_disabled(8, !(1 << 4)));
+
+    // RD_RS_ERR0: block 3 in bits 8..=11, KEY0 in 12..=15 and KEY4 in 28..=31
+    assert!(rs_error_bits(3, 0x0000_0f00, 0) == 0xf);
+    assert!(rs_error_bits(4, 0x0000_9000, 0) == 0x9);
+    assert!(rs_error_bits(8, 0xa000_0000, 0) == 0xa);
+    assert!(rs_error_bits(8, 0x0fff_ffff, 0xffff_ffff) == 0);
+    // RD_RS_ERR1: KEY5 in bits 0..=3, SYS_DATA_PART2 next to it
+    assert!(rs_error_bits(9, 0xffff_ffff, 0x0000_0005) == 0x5);
+    assert!(rs_error_bits(9, 0, 0x0000_00f0) == 0);
+
+    // little endian words
+    let mut data = [0u8; 32];
+    data[0] = 0x01;
+    data[3] = 0x04;
+    data[31] = 0x80;
+    let words = to_words(&data);
+    assert!(words[0] == 0x0400_0001);
+    assert!(words[1] == 0 && words[6] == 0);
+    assert!(words[7] == 0x8000_0000);
+
+    // a blank block takes any data
+    let mut requested = [0u32; BLOCK_WORDS];
+    requested[0] = 0x0000_00ff;
+    requested[7] = 0x8000_0001;
+    let blank = diff([0; BLOCK_WORDS], requested);
+    assert!(blank.check().is_ok());
+    assert!(blank.bits_to_set()[0] == 0x0000_00ff);
+    assert!(blank.bits_to_set()[7] == 0x8000_0001);
+    assert!(blank.bits_to_set_count() == 10);
+    assert!(is_blank(&blank.conflicting_bits()));
+    assert!(!blank.is_unchanged());
+
+    // identical data
+    let unchanged = diff([0x1234_5678; BLOCK_WORDS], [0x1234_5678; BLOCK_WORDS]);
+    assert!(unchanged.check().is_ok());
+    assert!(unchanged.is_unchanged());
+    assert!(unchanged.bits_to_set_count() == 0);
+
+    // cleared bits conflict
+    let mut current = [0u32; BLOCK_WORDS];
+    current[2] = 0b1010;
+    let mut requested = [0u32; BLOCK_WORDS];
+    requested[2] = 0b0011;
+    let conflicting = diff(current, requested);
+    assert!(conflicting.conflicting_bits()[2] == 0b1000);
+    assert!(conflicting.bits_to_set()[2] == 0b0001);
+    assert!(matches!(conflicting.check(), Err(Error::ConflictingBits)));
+
+    // adding bits to a programmed block would need a new Reed-Solomon code,
+    // which can't be burned over the old one
+    let mut current = [0u32; BLOCK_WORDS];
+    current[0] = 0b0001;
+    let mut requested = current;
+    requested[5] = 0b0100;
+    let programmed = diff(current, requested);
+    assert!(is_blank(&programmed.conflicting_bits()));
+    assert!(programmed.bits_to_set_count() == 1);
+    assert!(matches!(programmed.check(), Err(Error::AlreadyProgrammed)));
+};
+
+/// Program timing for the 20 MHz eFuse clock
+fn set_timing() {
+    let efuse = unsafe { &*EFUSE::ptr() };
+
+    // DAC_CLK_DIV = 0x28, DAC_NUM = 0xff
+    efuse
+        .dac_conf
+        .modify(|r, w| unsafe { w.bits(r.bits() & !0x1_ffff | 0xff << 9 | 0x28) });
+    // PWR_ON_NUM = 0x3000
+    efuse
+        .wr_tim_conf1
+        .modify(|r, w| unsafe { w.bits(r.bits() & !(0xffff << 8) | 0x3000 << 8) });
+    // PWR_OFF_NUM = 0x190
+    efuse
+        .wr_tim_conf2
+        .modify(|r, w| unsafe { w.bits(r.bits() & !0xffff | 0x190) });
+}
+
+fn program_registers() -> *mut u32 {
+    let efuse = unsafe { &*EFUSE::ptr() };
+    // PGM_DATA0..7 are followed by PGM_CHECK_VALUE0..2
+    &efuse.pgm_data0 as *const _ as *mut u32
+}
+
+fn clear_program_registers() {
+    let registers = program_registers();
+    for i in 0..BLOCK_WORDS + RS_CHECK_BYTES / 4 {
+        unsafe { registers.add(i).write_volatile(0) };
+    }
+}
+
+fn program(number: u32, words: &[u32; BLOCK_WORDS], check: &[u8; RS_CHECK_BYTES]) {
+    let efuse = unsafe { &*EFUSE::ptr() };
+    let registers = program_registers();
+
+    for (i, word) in words.iter().enumerate() {
+        unsafe { registers.add(i).write_volatile(*word) };
+    }
+    for (i, bytes) in check.chunks_exact(4).enumerate() {
+        let word = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        unsafe { registers.add(BLOCK_WORDS + i).write_volatile(word) };
+    }
+
+    efuse.conf.write(|w| unsafe { w.bits(CONF_WRITE_OP_CODE) });
+    efuse
+        .cmd
+        .write(|w| unsafe { w.bits(number << 2 | CMD_PGM) });
+    while efuse.cmd.read().bits() & CMD_PGM != 0 {}
+}
+
+/// Update the read registers from the eFuses
+fn reload() {
+    let efuse = unsafe { &*EFUSE::ptr() };
+
+    efuse.conf.write(|w| unsafe { w.bits(CONF_READ_OP_CODE) });
+    efuse.cmd.write(|w| unsafe { w.bits(CMD_READ) });
+    while efuse.cmd.read().bits() & CMD_READ != 0 {}
+}
@@ -0,0 +1,829 @@
+//! Ethernet MAC (ESP32 only)
+//!
+//! The EMAC connects to an external PHY through RMII. The RMII data pins are
+//! fixed IO MUX functions (function 5 of the pads), only the SMI management
+//! pins (MDC and MDIO) are routed through the GPIO matrix:
+//!
+//! | RMII signal | GPIO |
+//! |-------------|------|
+//! | TX_EN       | 21   |
+//! | TXD0        | 19   |
+//! | TXD1        | 22   |
+//! | RXD0        | 25   |
+//! | RXD1        | 26   |
+//! | CRS_DV      | 27   |
+//!
+//! The 50 MHz reference clock is either an input on GPIO0, driven by the PHY
+//! or an oscillator, or generated by the APLL and output on GPIO16 (or
+//! inverted on GPIO17) to clock the PHY, see [RmiiClock].
+//!
+//! The DMA moves frames between the MAC and the descriptor rings in
+//! [DmaBuffers], one frame per buffer. Frames are sent with
+//! [Emac::transmit] and received with [Emac::receive], or through the
+//! `smoltcp::phy::Device` implementation with the `smoltcp` feature:
+//!
+//! ```no_run
+//! static mut BUFFERS: DmaBuffers<8, 4> = DmaBuffers::new();
+//!
+//! let pins = RmiiPins {
+//!     tx_en: io.pins.gpio21.into_alternate_5(),
+//!     txd0: io.pins.gpio19.into_alternate_5(),
+//!     txd1: io.pins.gpio22.into_alternate_5(),
+//!     rxd0: io.pins.gpio25.into_alternate_5(),
+//!     rxd1: io.pins.gpio26.into_alternate_5(),
+//!     crs_dv: io.pins.gpio27.into_alternate_5(),
+//! };
+//! let mut emac = Emac::new(
+//!     pins,
+//!     RmiiClock::ExternalInput(io.pins.gpio0.into_alternate_5()),
+//!     io.pins.gpio23,
+//!     io.pins.gpio18,
+//!     mac_address,
+//!     unsafe { &mut BUFFERS },
+//!     &mut peripheral_clock_control,
+//!     &clocks,
+//! )?;
+//!
+//! let phy = Phy::lan8720(1);
+//! phy.init(&mut emac)?;
+//! ```
+//!
+//! The link speed and duplex mode negotiated by the PHY have to be passed to
+//! [Emac::set_link], the [phy] helpers poll them.
+
+use core::sync::atomic::{compiler_fence, Ordering};
+
+use fugit::MicrosDurationU64;
+
+use crate::{
+    clock::Clocks,
+    efuse::Efuse,
+    gpio::{
+        Alternate,
+        Gpio0,
+        Gpio16,
+        Gpio17,
+        Gpio19,
+        Gpio21,
+        Gpio22,
+        Gpio25,
+        Gpio26,
+        Gpio27,
+        InputPin,
+        OutputPin,
+        AF5,
+    },
+    pac::RTC_CNTL,
+    rom::{esp_rom_delay_us, regi2c_ctrl_write_reg, regi2c_ctrl_write_reg_mask},
+    system::{Peripheral, PeripheralClockControl},
+    time::Deadline,
+    types::{InputSignal, OutputSignal},
+};
+
+pub mod phy;
+
+/// Size of each DMA buffer, one buffer holds a whole frame
+pub const BUFFER_SIZE: usize = 1536;
+
+/// Largest frame which can be sent, without the frame check sequence
+pub const MAX_FRAME_SIZE: usize = 1514;
+
+/// Time the MAC and the SMI get to finish an operation
+const TIMEOUT: MicrosDurationU64 = MicrosDurationU64::millis(100);
+
+const EMAC_DMA_BASE: u32 = 0x3ff6_9000;
+const EMAC_EXT_BASE: u32 = 0x3ff6_9800;
+const EMAC_MAC_BASE: u32 = 0x3ff6_a000;
+
+const DMA_BUS_MODE: u32 = EMAC_DMA_BASE;
+const DMA_TX_POLL_DEMAND: u32 = EMAC_DMA_BASE + 0x04;
+const DMA_RX_POLL_DEMAND: u32 = EMAC_DMA_BASE + 0x08;
+const DMA_RX_BASE_ADDR: u32 = EMAC_DMA_BASE + 0x0c;
+const DMA_TX_BASE_ADDR: u32 = EMAC_DMA_BASE + 0x10;
+const DMA_STATUS: u32 = EMAC_DMA_BASE + 0x14;
+const DMA_OPERATION_MODE: u32 = EMAC_DMA_BASE + 0x18;
+const DMA_INTERRUPT_ENABLE: u32 = EMAC_DMA_BASE + 0x1c;
+
+const BUS_MODE_SOFTWARE_RESET: u32 = 1 << 0;
+const BUS_MODE_ALTERNATE_DESCRIPTOR_SIZE: u32 = 1 << 7;
+const BUS_MODE_BURST_LENGTH_SHIFT: u32 = 8;
+const BUS_MODE_FIXED_BURST: u32 = 1 << 16;
+const BUS_MODE_ADDRESS_ALIGNED_BEATS: u32 = 1 << 25;
+
+const OPERATION_MODE_START_RECEIVE: u32 = 1 << 1;
+const OPERATION_MODE_START_TRANSMIT: u32 = 1 << 13;
+const OPERATION_MODE_FLUSH_TX_FIFO: u32 = 1 << 20;
+const OPERATION_MODE_TX_STORE_FORWARD: u32 = 1 << 21;
+const OPERATION_MODE_RX_STORE_FORWARD: u32 = 1 << 25;
+
+const MAC_CONFIG: u32 = EMAC_MAC_BASE;
+const MAC_FRAME_FILTER: u32 = EMAC_MAC_BASE + 0x04;
+const MAC_MII_ADDRESS: u32 = EMAC_MAC_BASE + 0x10;
+const MAC_MII_DATA: u32 = EMAC_MAC_BASE + 0x14;
+const MAC_ADDRESS0_HIGH: u32 = EMAC_MAC_BASE + 0x40;
+const MAC_ADDRESS0_LOW: u32 = EMAC_MAC_BASE + 0x44;
+
+const CONFIG_RECEIVER_ENABLE: u32 = 1 << 2;
+const CONFIG_TRANSMITTER_ENABLE: u32 = 1 << 3;
+const CONFIG_AUTO_PAD_CRC_STRIP: u32 = 1 << 7;
+const CONFIG_DUPLEX_MODE: u32 = 1 << 11;
+const CONFIG_SPEED_100M: u32 = 1 << 14;
+const CONFIG_PORT_SELECT_MII: u32 = 1 << 15;
+
+const FRAME_FILTER_PASS_ALL_MULTICAST: u32 = 1 << 4;
+
+const MII_BUSY: u32 = 1 << 0;
+const MII_WRITE: u32 = 1 << 1;
+// clock range field of 0, MDC is the 80 MHz APB clock divided by 42
+const MII_CLOCK_RANGE_60M_100M: u32 = 0;
+const MII_REGISTER_SHIFT: u32 = 6;
+const MII_PHY_SHIFT: u32 = 11;
+
+const EXT_CLKOUT_CONF: u32 = EMAC_EXT_BASE;
+const EXT_OSCCLK_CONF: u32 = EMAC_EXT_BASE + 0x04;
+const EXT_CLK_CTRL: u32 = EMAC_EXT_BASE + 0x08;
+const EXT_PHYINF_CONF: u32 = EMAC_EXT_BASE + 0x0c;
+const EXT_PD_SEL: u32 = EMAC_EXT_BASE + 0x10;
+
+const OSCCLK_CONF_CLK_SEL: u32 = 1 << 24;
+const CLK_CTRL_EXT_EN: u32 = 1 << 0;
+const CLK_CTRL_INT_EN: u32 = 1 << 1;
+const PHYINF_CONF_INTF_SEL_MASK: u32 = 0b111 << 13;
+const PHYINF_CONF_INTF_SEL_RMII: u32 = 4 << 13;
+
+const TDES0_OWN: u32 = 1 << 31;
+const TDES0_INTERRUPT_ON_COMPLETION: u32 = 1 << 30;
+const TDES0_LAST_SEGMENT: u32 = 1 << 29;
+const TDES0_FIRST_SEGMENT: u32 = 1 << 28;
+const TDES0_SECOND_ADDRESS_CHAINED: u32 = 1 << 20;
+
+const RDES0_OWN: u32 = 1 << 31;
+const RDES0_FRAME_LENGTH_SHIFT: u32 = 16;
+const RDES0_FRAME_LENGTH_MASK: u32 = 0x3fff;
+const RDES0_ERROR_SUMMARY: u32 = 1 << 15;
+const RDES0_FIRST_DESCRIPTOR: u32 = 1 << 9;
+const RDES0_LAST_DESCRIPTOR: u32 = 1 << 8;
+const RDES1_SECOND_ADDRESS_CHAINED: u32 = 1 << 14;
+
+const FRAME_CHECK_SEQUENCE_SIZE: usize = 4;
+
+const I2C_APLL: u32 = 0x6d;
+const I2C_APLL_HOSTID: u32 = 3;
+const I2C_APLL_IR_CAL_DELAY: u32 = 0;
+const I2C_APLL_OR_OUTPUT_DIV: u32 = 4;
+const I2C_APLL_SDM_STOP: u32 = 5;
+const I2C_APLL_DSDM2: u32 = 7;
+const I2C_APLL_DSDM1: u32 = 8;
+const I2C_APLL_DSDM0: u32 = 9;
+
+const APLL_SDM_STOP_VAL_1: u32 = 0x09;
+const APLL_SDM_STOP_VAL_2_REV0: u32 = 0x69;
+const APLL_SDM_STOP_VAL_2_REV1: u32 = 0x49;
+const APLL_CAL_DELAY_1: u32 = 0x0f;
+const APLL_CAL_DELAY_2: u32 = 0x3f;
+const APLL_CAL_DELAY_3: u32 = 0x1f;
+
+/// The APLL runs at `xtal * (4 + sdm) / (2 * (APLL_OUTPUT_DIV + 2))`
+const APLL_OUTPUT_DIV: u32 = 2;
+
+/// EMAC errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The MAC didn't leave the reset, usually the RMII reference clock is
+    /// missing
+    ResetTimeout,
+    /// The PHY didn't answer on the SMI
+    SmiTimeout,
+    /// The frame is larger than [MAX_FRAME_SIZE]
+    FrameTooLarge,
+    /// The received frame is larger than the buffer passed to
+    /// [Emac::receive], it was dropped
+    BufferTooSmall,
+    /// The PHY didn't finish its software reset
+    PhyResetTimeout,
+    /// The PHY identifier doesn't match the expected model
+    UnexpectedPhyId(u32),
+}
+
+/// Speed of the Ethernet link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    Speed10M,
+    Speed100M,
+}
+
+/// Duplex mode of the Ethernet link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Duplex {
+    Half,
+    Full,
+}
+
+/// Speed and duplex mode of an established link
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Link {
+    pub speed: Speed,
+    pub duplex: Duplex,
+}
+
+/// The fixed RMII data pins
+pub struct RmiiPins {
+    pub tx_en: Gpio21<Alternate<AF5>>,
+    pub txd0: Gpio19<Alternate<AF5>>,
+    pub txd1: Gpio22<Alternate<AF5>>,
+    pub rxd0: Gpio25<Alternate<AF5>>,
+    pub rxd1: Gpio26<Alternate<AF5>>,
+    pub crs_dv: Gpio27<Alternate<AF5>>,
+}
+
+/// Source of the 50 MHz RMII reference clock
+pub enum RmiiClock {
+    /// Input on GPIO0, provided by the PHY or an external oscillator
+    ExternalInput(Gpio0<Alternate<AF5>>),
+    /// Generated by the APLL and output on GPIO16
+    InternalOutput(Gpio16<Alternate<AF5>>),
+    /// Generated by the APLL and output inverted on GPIO17
+    InternalOutputInverted(Gpio17<Alternate<AF5>>),
+}
+
+/// A DMA descriptor in the extended (8 word) format
+#[derive(Clone, Copy)]
+#[repr(C, align(4))]
+struct Descriptor {
+    words: [u32; 8],
+}
+
+impl Descriptor {
+    const fn new() -> Self {
+        Self { words: [0; 8] }
+    }
+
+    fn read(&self, word: usize) -> u32 {
+        unsafe { core::ptr::read_volatile(&self.words[word]) }
+    }
+
+    fn write(&mut self, word: usize, value: u32) {
+        unsafe { core::ptr::write_volatile(&mut self.words[word], value) }
+    }
+}
+
+/// Descriptors and buffers of the `RX` receive and `TX` transmit slots
+///
+/// Has to be in internal RAM, e.g. a `static`, the DMA can't access PSRAM
+/// or flash.
+pub struct DmaBuffers<const RX: usize, const TX: usize> {
+    rx_descriptors: [Descriptor; RX],
+    tx_descriptors: [Descriptor; TX],
+    rx_buffers: [[u8; BUFFER_SIZE]; RX],
+    tx_buffers: [[u8; BUFFER_SIZE]; TX],
+}
+
+impl<const RX: usize, const TX: usize> DmaBuffers<RX, TX> {
+    const VALID: () = assert!(
+        RX >= 2 && TX >= 2,
+        "the EMAC needs at least 2 receive and 2 transmit buffers"
+    );
+
+    pub const fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::VALID;
+
+        Self {
+            rx_descriptors: [Descriptor::new(); RX],
+            tx_descriptors: [Descriptor::new(); TX],
+            rx_buffers: [[0; BUFFER_SIZE]; RX],
+            tx_buffers: [[0; BUFFER_SIZE]; TX],
+        }
+    }
+}
+
+/// A ring of chained descriptors, each with one buffer
+struct Ring {
+    descriptors: &'static mut [Descriptor],
+    buffers: &'static mut [[u8; BUFFER_SIZE]],
+    index: usize,
+}
+
+impl Ring {
+    fn new(
+        descriptors: &'static mut [Descriptor],
+        buffers: &'static mut [[u8; BUFFER_SIZE]],
+    ) -> Self {
+        let len = descriptors.len();
+        for i in 0..len {
+            let next = &descriptors[(i + 1) % len] as *const _ as u32;
+            let buffer = buffers[i].as_ptr() as u32;
+            descriptors[i] = Descriptor::new();
+            descriptors[i].write(2, buffer);
+            descriptors[i].write(3, next);
+        }
+
+        Self {
+            descriptors,
+            buffers,
+            index: 0,
+        }
+    }
+
+    fn address(&self) -> u32 {
+        self.descriptors.as_ptr() as u32
+    }
+
+    fn advance(&mut self) {
+        self.index = (self.index + 1) % self.descriptors.len();
+    }
+
+    /// Give all receive descriptors to the DMA
+    fn init_rx(&mut self) {
+        for descriptor in self.descriptors.iter_mut() {
+            descriptor.write(1, RDES1_SECOND_ADDRESS_CHAINED | BUFFER_SIZE as u32);
+            descriptor.write(0, RDES0_OWN);
+        }
+    }
+
+    /// Keep all transmit descriptors for the CPU
+    fn init_tx(&mut self) {
+        for descriptor in self.descriptors.iter_mut() {
+            descriptor.write(1, 0);
+            descriptor.write(0, TDES0_SECOND_ADDRESS_CHAINED);
+        }
+    }
+
+    /// Length of the next received frame, frames with errors are dropped
+    fn rx_frame_len(&mut self) -> Option<usize> {
+        loop {
+            let status = self.descriptors[self.index].read(0);
+            if status & RDES0_OWN != 0 {
+                return None;
+            }
+
+            let whole_frame = RDES0_FIRST_DESCRIPTOR | RDES0_LAST_DESCRIPTOR;
+            let len = ((status >> RDES0_FRAME_LENGTH_SHIFT) & RDES0_FRAME_LENGTH_MASK) as usize;
+            if status & RDES0_ERROR_SUMMARY == 0
+                && status & whole_frame == whole_frame
+                && len > FRAME_CHECK_SEQUENCE_SIZE
+            {
+                return Some(len - FRAME_CHECK_SEQUENCE_SIZE);
+            }
+
+            self.release_rx();
+        }
+    }
+
+    /// Give the current receive descriptor back to the DMA
+    fn release_rx(&mut self) {
+        compiler_fence(Ordering::SeqCst);
+        self.descriptors[self.index].write(0, RDES0_OWN);
+        self.advance();
+        write_register(DMA_RX_POLL_DEMAND, 0);
+    }
+
+    fn tx_ready(&self) -> bool {
+        self.descriptors[self.index].read(0) & TDES0_OWN == 0
+    }
+
+    /// Give the current transmit descriptor with `len` bytes to the DMA
+    fn send_tx(&mut self, len: usize) {
+        let descriptor = &mut self.descriptors[self.index];
+        descriptor.write(1, len as u32);
+        compiler_fence(Ordering::SeqCst);
+        descriptor.write(
+            0,
+            TDES0_OWN
+                | TDES0_INTERRUPT_ON_COMPLETION
+                | TDES0_LAST_SEGMENT
+                | TDES0_FIRST_SEGMENT
+                | TDES0_SECOND_ADDRESS_CHAINED,
+        );
+        self.advance();
+        write_register(DMA_TX_POLL_DEMAND, 0);
+    }
+}
+
+/// Ethernet MAC driver
+pub struct Emac<MDC, MDIO> {
+    pins: RmiiPins,
+    clock: RmiiClock,
+    mdc: MDC,
+    mdio: MDIO,
+    rx: Ring,
+    tx: Ring,
+}
+
+impl<MDC, MDIO> Emac<MDC, MDIO>
+where
+    MDC: OutputPin,
+    MDIO: OutputPin + InputPin,
+{
+    /// Configure the pins and the clock, reset the MAC and start it with
+    /// `mac_address`, 100 Mbit/s and full duplex
+    ///
+    /// Fails with [Error::ResetTimeout] if the reference clock is missing,
+    /// e.g. because the PHY or the oscillator providing it isn't powered
+    /// yet.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new<const RX: usize, const TX: usize>(
+        mut pins: RmiiPins,
+        mut clock: RmiiClock,
+        mut mdc: MDC,
+        mut mdio: MDIO,
+        mac_address: [u8; 6],
+        buffers: &'static mut DmaBuffers<RX, TX>,
+        peripheral_clock_control: &mut PeripheralClockControl,
+        clocks: &Clocks,
+    ) -> Result<Self, Error> {
+        peripheral_clock_control.enable(Peripheral::Emac);
+
+        // the data pads are inputs or driven by the MAC through the IO MUX
+        pins.rxd0.enable_output(false).enable_input(true);
+        pins.rxd1.enable_output(false).enable_input(true);
+        pins.crs_dv.enable_output(false).enable_input(true);
+
+        mdc.set_to_push_pull_output()
+            .connect_peripheral_to_output(OutputSignal::EMAC_MDC);
+        mdio.set_to_push_pull_output()
+            .enable_input(true)
+            .connect_peripheral_to_output(OutputSignal::EMAC_MDO)
+            .connect_input_to_peripheral(InputSignal::EMAC_MDI);
+
+        // power up the MAC RAM
+        write_register(EXT_PD_SEL, 0);
+        modify_register(EXT_PHYINF_CONF, |r| {
+            (r & !PHYINF_CONF_INTF_SEL_MASK) | PHYINF_CONF_INTF_SEL_RMII
+        });
+
+        match &mut clock {
+            RmiiClock::ExternalInput(pin) => {
+                pin.enable_output(false).enable_input(true);
+                write_register(EXT_CLK_CTRL, CLK_CTRL_EXT_EN);
+                modify_register(EXT_OSCCLK_CONF, |r| r | OSCCLK_CONF_CLK_SEL);
+            }
+            RmiiClock::InternalOutput(_) | RmiiClock::InternalOutputInverted(_) => {
+                enable_apll_50mhz(clocks.xtal_clock.to_Hz());
+                write_register(EXT_CLKOUT_CONF, 0);
+                modify_register(EXT_OSCCLK_CONF, |r| r & !OSCCLK_CONF_CLK_SEL);
+                write_register(EXT_CLK_CTRL, CLK_CTRL_INT_EN);
+            }
+        }
+
+        modify_register(DMA_BUS_MODE, |r| r | BUS_MODE_SOFTWARE_RESET);
+        let deadline = Deadline::after(TIMEOUT);
+        while read_register(DMA_BUS_MODE) & BUS_MODE_SOFTWARE_RESET != 0 {
+            if deadline.is_expired() {
+                return Err(Error::ResetTimeout);
+            }
+        }
+
+        let DmaBuffers {
+            rx_descriptors,
+            tx_descriptors,
+            rx_buffers,
+            tx_buffers,
+        } = buffers;
+        let mut rx = Ring::new(rx_descriptors, rx_buffers);
+        let mut tx = Ring::new(tx_descriptors, tx_buffers);
+        rx.init_rx();
+        tx.init_tx();
+
+        write_register(
+            DMA_BUS_MODE,
+            BUS_MODE_ALTERNATE_DESCRIPTOR_SIZE
+                | (32 << BUS_MODE_BURST_LENGTH_SHIFT)
+                | BUS_MODE_FIXED_BURST
+                | BUS_MODE_ADDRESS_ALIGNED_BEATS,
+        );
+        write_register(DMA_RX_BASE_ADDR, rx.address());
+        write_register(DMA_TX_BASE_ADDR, tx.address());
+        write_register(DMA_INTERRUPT_ENABLE, 0);
+        write_register(DMA_STATUS, u32::MAX);
+
+        write_register(
+            MAC_CONFIG,
+            CONFIG_PORT_SELECT_MII
+                | CONFIG_SPEED_100M
+                | CONFIG_DUPLEX_MODE
+                | CONFIG_AUTO_PAD_CRC_STRIP,
+        );
+        write_register(MAC_FRAME_FILTER, FRAME_FILTER_PASS_ALL_MULTICAST);
+
+        let mut emac = Self {
+            pins,
+            clock,
+            mdc,
+            mdio,
+            rx,
+            tx,
+        };
+        emac.set_mac_address(mac_address);
+
+        write_register(
+            DMA_OPERATION_MODE,
+            OPERATION_MODE_TX_STORE_FORWARD
+                | OPERATION_MODE_RX_STORE_FORWARD
+                | OPERATION_MODE_FLUSH_TX_FIFO,
+        );
+        modify_register(DMA_OPERATION_MODE, |r| {
+            r | OPERATION_MODE_START_TRANSMIT | OPERATION_MODE_START_RECEIVE
+        });
+        modify_register(MAC_CONFIG, |r| {
+            r | CONFIG_TRANSMITTER_ENABLE | CONFIG_RECEIVER_ENABLE
+        });
+
+        Ok(emac)
+    }
+
+    /// Set the address frames are received for, besides broadcast and
+    /// multicast frames
+    pub fn set_mac_address(&mut self, mac_address: [u8; 6]) {
+        let [a0, a1, a2, a3, a4, a5] = mac_address.map(u32::from);
+        write_register(MAC_ADDRESS0_HIGH, a5 << 8 | a4);
+        write_register(MAC_ADDRESS0_LOW, a3 << 24 | a2 << 16 | a1 << 8 | a0);
+    }
+
+    /// Match the MAC to the link negotiated by the PHY
+    pub fn set_link(&mut self, link: Link) {
+        modify_register(MAC_CONFIG, |r| {
+            let mut r = r & !(CONFIG_SPEED_100M | CONFIG_DUPLEX_MODE);
+            if link.speed == Speed::Speed100M {
+                r |= CONFIG_SPEED_100M;
+            }
+            if link.duplex == Duplex::Full {
+                r |= CONFIG_DUPLEX_MODE;
+            }
+            r
+        });
+    }
+
+    /// Read `register` of the PHY at `phy_address` through the SMI
+    pub fn read_phy(&mut self, phy_address: u8, register: u8) -> Result<u16, Error> {
+        self.smi(phy_address, register, 0)?;
+        self.wait_smi()?;
+        Ok(read_register(MAC_MII_DATA) as u16)
+    }
+
+    /// Write `register` of the PHY at `phy_address` through the SMI
+    pub fn write_phy(&mut self, phy_address: u8, register: u8, value: u16) -> Result<(), Error> {
+        write_register(MAC_MII_DATA, value as u32);
+        self.smi(phy_address, register, MII_WRITE)?;
+        self.wait_smi()
+    }
+
+    fn smi(&mut self, phy_address: u8, register: u8, write: u32) -> Result<(), Error> {
+        self.wait_smi()?;
+        write_register(
+            MAC_MII_ADDRESS,
+            (phy_address as u32 & 0x1f) << MII_PHY_SHIFT
+                | (register as u32 & 0x1f) << MII_REGISTER_SHIFT
+                | MII_CLOCK_RANGE_60M_100M
+                | write
+                | MII_BUSY,
+        );
+        Ok(())
+    }
+
+    fn wait_smi(&mut self) -> Result<(), Error> {
+        let deadline = Deadline::after(TIMEOUT);
+        while read_register(MAC_MII_ADDRESS) & MII_BUSY != 0 {
+            if deadline.is_expired() {
+                return Err(Error::SmiTimeout);
+            }
+        }
+        Ok(())
+    }
+
+    /// Queue `frame` for transmission, without the frame check sequence
+    /// which is appended by the MAC
+    ///
+    /// Returns `WouldBlock` while all transmit buffers are in use.
+    pub fn transmit(&mut self, frame: &[u8]) -> nb::Result<(), Error> {
+        if frame.len() > MAX_FRAME_SIZE {
+            return Err(nb::Error::Other(Error::FrameTooLarge));
+        }
+        if !self.tx.tx_ready() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.tx.buffers[self.tx.index][..frame.len()].copy_from_slice(frame);
+        self.tx.send_tx(frame.len());
+        Ok(())
+    }
+
+    /// Copy the next received frame into `buffer`, without the frame check
+    /// sequence, and return its length
+    ///
+    /// Returns `WouldBlock` until a frame was received. Frames with errors
+    /// are dropped.
+    pub fn receive(&mut self, buffer: &mut [u8]) -> nb::Result<usize, Error> {
+        let len = self.rx.rx_frame_len().ok_or(nb::Error::WouldBlock)?;
+
+        let result = if len <= buffer.len() {
+            buffer[..len].copy_from_slice(&self.rx.buffers[self.rx.index][..len]);
+            Ok(len)
+        } else {
+            Err(nb::Error::Other(Error::BufferTooSmall))
+        };
+        self.rx.release_rx();
+
+        result
+    }
+
+    /// Stop the MAC and return the pins
+    pub fn free(self) -> (RmiiPins, RmiiClock, MDC, MDIO) {
+        modify_register(MAC_CONFIG, |r| {
+            r & !(CONFIG_TRANSMITTER_ENABLE | CONFIG_RECEIVER_ENABLE)
+        });
+        modify_register(DMA_OPERATION_MODE, |r| {
+            r & !(OPERATION_MODE_START_TRANSMIT | OPERATION_MODE_START_RECEIVE)
+        });
+
+        (self.pins, self.clock, self.mdc, self.mdio)
+    }
+}
+
+/// The MAC address assigned to the Ethernet interface, the base MAC address
+/// of the chip plus 3
+pub fn default_mac_address() -> [u8; 6] {
+    let mut mac_address = Efuse::get_mac_address();
+    mac_address[5] = mac_address[5].wrapping_add(3);
+    mac_address
+}
+
+/// Run the APLL at 50 MHz from the crystal running at `xtal_hz`
+fn enable_apll_50mhz(xtal_hz: u32) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::PTR };
+    rtc_cntl
+        .ana_conf
+        .modify(|_, w| w.plla_force_pd().clear_bit().plla_force_pu().set_bit());
+
+    let (sdm2, sdm1, sdm0) = apll_sdm(xtal_hz, 50_000_000);
+    let sdm_stop_val_2 = if Efuse::get_major_chip_version() == 0 {
+        APLL_SDM_STOP_VAL_2_REV0
+    } else {
+        APLL_SDM_STOP_VAL_2_REV1
+    };
+
+    unsafe {
+        regi2c_ctrl_write_reg_mask(I2C_APLL, I2C_APLL_HOSTID, I2C_APLL_DSDM2, 5, 0, sdm2);
+        regi2c_ctrl_write_reg_mask(I2C_APLL, I2C_APLL_HOSTID, I2C_APLL_DSDM0, 7, 0, sdm0);
+        regi2c_ctrl_write_reg_mask(I2C_APLL, I2C_APLL_HOSTID, I2C_APLL_DSDM1, 7, 0, sdm1);
+        regi2c_ctrl_write_reg(
+            I2C_APLL,
+            I2C_APLL_HOSTID,
+            I2C_APLL_SDM_STOP,
+            APLL_SDM_STOP_VAL_1,
+        );
+        regi2c_ctrl_write_reg(I2C_APLL, I2C_APLL_HOSTID, I2C_APLL_SDM_STOP, sdm_stop_val_2);
+        regi2c_ctrl_write_reg_mask(
+            I2C_APLL,
+            I2C_APLL_HOSTID,
+            I2C_APLL_OR_OUTPUT_DIV,
+            4,
+            0,
+            APLL_OUTPUT_DIV,
+        );
+
+        regi2c_ctrl_write_reg(
+            I2C_APLL,
+            I2C_APLL_HOSTID,
+            I2C_APLL_IR_CAL_DELAY,
+            APLL_CAL_DELAY_1,
+        );
+        regi2c_ctrl_write_reg(
+            I2C_APLL,
+            I2C_APLL_HOSTID,
+            I2C_APLL_IR_CAL_DELAY,
+            APLL_CAL_DELAY_2,
+        );
+        regi2c_ctrl_write_reg(
+            I2C_APLL,
+            I2C_APLL_HOSTID,
+            I2C_APLL_IR_CAL_DELAY,
+            APLL_CAL_DELAY_3,
+        );
+
+        // there is no ROM function to read the calibration status, the
+        // calibration takes less than this
+        esp_rom_delay_us(1000);
+    }
+}
+
+/// The sigma delta modulator settings `(sdm2, sdm1, sdm0)` for the APLL to
+/// run at `target_hz`
+///
+/// The ESP32 revision 0 ignores `sdm1` and `sdm0`, it runs at exactly 50 MHz
+/// from a 40 MHz crystal only.
+const fn apll_sdm(xtal_hz: u32, target_hz: u32) -> (u32, u32, u32) {
+    let vco_hz = target_hz as u64 * 2 * (APLL_OUTPUT_DIV as u64 + 2);
+    let sdm = (vco_hz << 16) / xtal_hz as u64 - (4 << 16);
+
+    (
+        (sdm >> 16) as u32,
+        (sdm >> 8) as u32 & 0xff,
+        sdm as u32 & 0xff,
+    )
+}
+
+const _: () = {
+    assert!(matches!(apll_sdm(40_000_000, 50_000_000), (6, 0, 0)));
+    assert!(matches!(apll_sdm(26_000_000, 50_000_000), (11, 98, 118)));
+};
+
+fn read_register(address: u32) -> u32 {
+    unsafe { (address as *const u32).read_volatile() }
+}
+
+fn write_register(address: u32, value: u32) {
+    unsafe { (address as *mut u32).write_volatile(value) }
+}
+
+fn modify_register(address: u32, f: impl FnOnce(u32) -> u32) {
+    write_register(address, f(read_register(address)));
+}
+
+#[cfg(feature = "smoltcp")]
+mod smoltcp_device {
+    use smoltcp::{
+        phy::{Device, DeviceCapabilities, Medium, RxToken, TxToken},
+        time::Instant,
+    };
+
+    use super::*;
+
+    /// A received frame, given back to the DMA once consumed
+    pub struct EmacRxToken<'a> {
+        ring: &'a mut Ring,
+        len: usize,
+    }
+
+    /// A free transmit buffer
+    pub struct EmacTxToken<'a> {
+        ring: &'a mut Ring,
+    }
+
+    impl<'a, MDC: 'a, MDIO: 'a> Device<'a> for Emac<MDC, MDIO> {
+        type RxToken = EmacRxToken<'a>;
+        type TxToken = EmacTxToken<'a>;
+
+        fn receive(&'a mut self) -> Option<(Self::RxToken, Self::TxToken)> {
+            if !self.tx.tx_ready() {
+                return None;
+            }
+            let len = self.rx.rx_frame_len()?;
+
+            Some((
+                EmacRxToken {
+                    ring: &mut self.rx,
+                    len,
+                },
+                EmacTxToken { ring: &mut self.tx },
+            ))
+        }
+
+        fn transmit(&'a mut self) -> Option<Self::TxToken> {
+            if !self.tx.tx_ready() {
+                return None;
+            }
+
+            Some(EmacTxToken { ring: &mut self.tx })
+        }
+
+        fn capabilities(&self) -> DeviceCapabilities {
+            let mut capabilities = DeviceCapabilities::default();
+            capabilities.medium = Medium::Ethernet;
+            capabilities.max_transmission_unit = MAX_FRAME_SIZE;
+            capabilities.max_burst_size = Some(self.tx.descriptors.len());
+            capabilities
+        }
+    }
+
+    impl RxToken for EmacRxToken<'_> {
+        fn consume<R, F>(self, _timestamp: Instant, f: F) -> smoltcp::Result<R>
+        where
+            F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+        {
+            let result = f(&mut self.ring.buffers[self.ring.index][..self.len]);
+            self.ring.release_rx();
+            result
+        }
+    }
+
+    impl TxToken for EmacTxToken<'_> {
+        fn consume<R, F>(self, _timestamp: Instant, len: usize, f: F) -> smoltcp::Result<R>
+        where
+            F: FnOnce(&mut [u8]) -> smoltcp::Result<R>,
+        {
+            if len > MAX_FRAME_SIZE {
+                return Err(smoltcp::Error::Truncated);
+            }
+
+            let result = f(&mut self.ring.buffers[self.ring.index][..len]);
+            if result.is_ok() {
+                self.ring.send_tx(len);
+            }
+            result
+        }
+    }
+}
+
+#[cfg(feature = "smoltcp")]
+pub use smoltcp_device::{EmacRxToken, EmacTxToken};
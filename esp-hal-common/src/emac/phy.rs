@@ -0,0 +1,229 @@
+//! Bring-up of the LAN8720 and IP101 PHYs
+//!
+//! Both PHYs implement the IEEE 802.3 clause 22 registers, the helpers only
+//! use those: a software reset, auto-negotiation of all 10/100 Mbit/s modes
+//! and the link state. The link has to be polled, e.g. every second:
+//!
+//! ```no_run
+//! let phy = Phy::lan8720(1);
+//! phy.init(&mut emac)?;
+//!
+//! let mut current = None;
+//! loop {
+//!     let link = phy.link(&mut emac)?;
+//!     if link != current {
+//!         if let Some(link) = link {
+//!             emac.set_link(link);
+//!         }
+//!         current = link;
+//!     }
+//! }
+//! ```
+
+use embedded_hal::blocking::delay::DelayUs;
+use fugit::MicrosDurationU64;
+
+use super::{Duplex, Emac, Error, Link, Speed};
+use crate::{
+    gpio::{InputPin, OutputPin},
+    time::Deadline,
+};
+
+const BASIC_CONTROL: u8 = 0;
+const BASIC_STATUS: u8 = 1;
+const PHY_ID1: u8 = 2;
+const PHY_ID2: u8 = 3;
+const AUTONEG_ADVERTISEMENT: u8 = 4;
+const AUTONEG_LINK_PARTNER: u8 = 5;
+
+const CONTROL_RESET: u16 = 1 << 15;
+const CONTROL_AUTONEG_ENABLE: u16 = 1 << 12;
+const CONTROL_AUTONEG_RESTART: u16 = 1 << 9;
+
+const STATUS_AUTONEG_COMPLETE: u16 = 1 << 5;
+const STATUS_LINK_UP: u16 = 1 << 2;
+
+const ABILITY_100M_FULL: u16 = 1 << 8;
+const ABILITY_100M_HALF: u16 = 1 << 7;
+const ABILITY_10M_FULL: u16 = 1 << 6;
+const ABILITY_10M_HALF: u16 = 1 << 5;
+const SELECTOR_IEEE_802_3: u16 = 0x01;
+
+/// Time the PHY gets to finish its software reset
+const RESET_TIMEOUT: MicrosDurationU64 = MicrosDurationU64::millis(500);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Model {
+    Lan8720,
+    Ip101,
+}
+
+impl Model {
+    /// The OUI and model number in the identifier registers, without the
+    /// revision
+    const fn id(self) -> u32 {
+        match self {
+            Model::Lan8720 => 0x0007_c0f0,
+            Model::Ip101 => 0x0243_0c50,
+        }
+    }
+
+    /// Time the reset pin is held low, and the PHY needs after it
+    const fn reset_us(self) -> (u32, u32) {
+        match self {
+            Model::Lan8720 => (100, 25_000),
+            Model::Ip101 => (10_000, 10_000),
+        }
+    }
+}
+
+/// A PHY on the SMI of the [Emac]
+#[derive(Debug, Clone, Copy)]
+pub struct Phy {
+    address: u8,
+    model: Model,
+}
+
+impl Phy {
+    /// A Microchip LAN8720 at SMI address `address` (0 or 1 depending on
+    /// its strapping)
+    pub const fn lan8720(address: u8) -> Self {
+        Self {
+            address,
+            model: Model::Lan8720,
+        }
+    }
+
+    /// An IC Plus IP101 at SMI address `address`
+    pub const fn ip101(address: u8) -> Self {
+        Self {
+            address,
+            model: Model::Ip101,
+        }
+    }
+
+    /// SMI address of the PHY
+    pub fn address(&self) -> u8 {
+        self.address
+    }
+
+    /// Reset the PHY with its reset pin
+    ///
+    /// The reset pin of most boards is pulled up, this is only needed if it
+    /// is connected to a GPIO.
+    pub fn hardware_reset<P, D>(&self, reset: &mut P, delay: &mut D)
+    where
+        P: embedded_hal::digital::v2::OutputPin,
+        D: DelayUs<u32>,
+    {
+        let (low_us, settle_us) = self.model.reset_us();
+
+        reset.set_low().ok();
+        delay.delay_us(low_us);
+        reset.set_high().ok();
+        delay.delay_us(settle_us);
+    }
+
+    /// Reset the PHY, check its identifier and start the auto-negotiation
+    /// of all 10 and 100 Mbit/s modes
+    pub fn init<MDC, MDIO>(&self, emac: &mut Emac<MDC, MDIO>) -> Result<(), Error>
+    where
+        MDC: OutputPin,
+        MDIO: OutputPin + InputPin,
+    {
+        emac.write_phy(self.address, BASIC_CONTROL, CONTROL_RESET)?;
+        let deadline = Deadline::after(RESET_TIMEOUT);
+        while emac.read_phy(self.address, BASIC_CONTROL)? & CONTROL_RESET != 0 {
+            if deadline.is_expired() {
+                return Err(Error::PhyResetTimeout);
+            }
+        }
+
+        let id = self.id(emac)?;
+        if id & !0xf != self.model.id() {
+            return Err(Error::UnexpectedPhyId(id));
+        }
+
+        emac.write_phy(
+            self.address,
+            AUTONEG_ADVERTISEMENT,
+            ABILITY_100M_FULL
+                | ABILITY_100M_HALF
+                | ABILITY_10M_FULL
+                | ABILITY_10M_HALF
+                | SELECTOR_IEEE_802_3,
+        )?;
+        emac.write_phy(
+            self.address,
+            BASIC_CONTROL,
+            CONTROL_AUTONEG_ENABLE | CONTROL_AUTONEG_RESTART,
+        )
+    }
+
+    /// The identifier registers of the PHY, OUI, model number and revision
+    pub fn id<MDC, MDIO>(&self, emac: &mut Emac<MDC, MDIO>) -> Result<u32, Error>
+    where
+        MDC: OutputPin,
+        MDIO: OutputPin + InputPin,
+    {
+        let id1 = emac.read_phy(self.address, PHY_ID1)?;
+        let id2 = emac.read_phy(self.address, PHY_ID2)?;
+        Ok((id1 as u32) << 16 | id2 as u32)
+    }
+
+    /// The negotiated link, `None` while the link is down or the
+    /// auto-negotiation didn't finish
+    pub fn link<MDC, MDIO>(&self, emac: &mut Emac<MDC, MDIO>) -> Result<Option<Link>, Error>
+    where
+        MDC: OutputPin,
+        MDIO: OutputPin + InputPin,
+    {
+        // the link status latches low, the first read returns whether the
+        // link was lost since the last read
+        emac.read_phy(self.address, BASIC_STATUS)?;
+        let status = emac.read_phy(self.address, BASIC_STATUS)?;
+        if status & STATUS_LINK_UP == 0 || status & STATUS_AUTONEG_COMPLETE == 0 {
+            return Ok(None);
+        }
+
+        let common = emac.read_phy(self.address, AUTONEG_ADVERTISEMENT)?
+            & emac.read_phy(self.address, AUTONEG_LINK_PARTNER)?;
+
+        Ok(resolve(common))
+    }
+}
+
+/// The best mode both link partners are capable of
+const fn resolve(common: u16) -> Option<Link> {
+    let (speed, duplex) = if common & ABILITY_100M_FULL != 0 {
+        (Speed::Speed100M, Duplex::Full)
+    } else if common & ABILITY_100M_HALF != 0 {
+        (Speed::Speed100M, Duplex::Half)
+    } else if common & ABILITY_10M_FULL != 0 {
+        (Speed::Speed10M, Duplex::Full)
+    } else if common & ABILITY_10M_HALF != 0 {
+        (Speed::Speed10M, Duplex::Half)
+    } else {
+        return None;
+    };
+
+    Some(Link { speed, duplex })
+}
+
+const _: () = {
+    assert!(matches!(
+        resolve(ABILITY_100M_FULL | ABILITY_10M_FULL),
+        Some(Link {
+            speed: Speed::Speed100M,
+            duplex: Duplex::Full
+        })
+    ));
+    assert!(matches!(
+        resolve(ABILITY_100M_HALF | ABILITY_10M_FULL),
+        Some(Link {
+            speed: Speed::Speed100M,
+            duplex: Duplex::Half
+        })
+    ));
+    assert!(resolve(SELECTOR_IEEE_802_3).is_none());
+};
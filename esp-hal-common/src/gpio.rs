@@ -18,14 +18,33 @@ use core::marker::PhantomData;
 #[cfg_attr(esp32s3, path = "gpio/esp32s3.rs")]
 pub mod types;
 
+pub mod activity_led;
+#[cfg(feature = "async")]
+pub mod asynch;
+pub mod board;
+pub mod debounce;
+#[cfg(dedicated_gpio)]
+pub mod dedicated;
+pub mod dispatch;
+pub mod edge_counter;
+pub mod frequency_counter;
+pub mod handler;
+pub mod pad;
+#[cfg(dedicated_gpio)]
+pub mod parallel_bus;
+pub mod self_test;
+pub mod soft_pwm;
+
 use core::convert::Infallible;
 
+pub use self::{handler::handle_interrupts, pad::borrow_pad};
 pub use crate::types::*;
 use crate::{
     pac::{GPIO, IO_MUX},
     types::{
         get_io_mux_reg,
         gpio_intr_enable,
+        gpio_intr_target,
         OutputSignalType,
         GPIO_FUNCTION,
         INPUT_SIGNAL_MAX,
@@ -33,7 +52,7 @@ use crate::{
     },
 };
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Event {
     RisingEdge  = 1,
     FallingEdge = 2,
@@ -42,6 +61,100 @@ pub enum Event {
     HighLevel   = 5,
 }
 
+impl Event {
+    /// The event selected by the `int_type` field of the pin register, `None`
+    /// if the interrupt is disabled
+    pub(crate) const fn from_int_type(int_type: u8) -> Option<Event> {
+        match int_type {
+            1 => Some(Event::RisingEdge),
+            2 => Some(Event::FallingEdge),
+            3 => Some(Event::AnyEdge),
+            4 => Some(Event::LowLevel),
+            5 => Some(Event::HighLevel),
+            _ => None,
+        }
+    }
+}
+
+/// Where the interrupt of a pin is delivered to
+///
+/// Selects the `int_ena` bits of the pin register. On the ESP32 and ESP32-S2
+/// the interrupt is delivered to both cores, see [Pin::listen_on_core] to
+/// select one core of the ESP32.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InterruptTarget {
+    /// Not delivered, the event still sets the status bit of the pin and
+    /// can wake the chip up from light sleep
+    None,
+    /// The `GPIO` interrupt
+    Cpu,
+    /// The `GPIO_NMI` interrupt, see `enable_nmi` on the Xtensa chips
+    Nmi,
+    /// Both the `GPIO` and the `GPIO_NMI` interrupt
+    CpuAndNmi,
+}
+
+impl InterruptTarget {
+    pub(crate) const fn from_flags(int_enable: bool, nmi_enable: bool) -> Self {
+        match (int_enable, nmi_enable) {
+            (false, false) => InterruptTarget::None,
+            (true, false) => InterruptTarget::Cpu,
+            (false, true) => InterruptTarget::Nmi,
+            (true, true) => InterruptTarget::CpuAndNmi,
+        }
+    }
+
+    /// Whether the `GPIO` interrupt is raised
+    pub const fn interrupt(self) -> bool {
+        matches!(self, InterruptTarget::Cpu | InterruptTarget::CpuAndNmi)
+    }
+
+    /// Whether the `GPIO_NMI` interrupt is raised
+    pub const fn nmi(self) -> bool {
+        matches!(self, InterruptTarget::Nmi | InterruptTarget::CpuAndNmi)
+    }
+}
+
+/// GPIO error
+///
+/// The typed pins can't be misconfigured and keep using `Infallible`, this is
+/// returned by the fallible functions which check a configuration at runtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The pin doesn't exist on this chip
+    InvalidPin,
+    /// The pin has no internal pull resistor of the requested kind
+    UnsupportedPull,
+    /// Only level events can wake the chip up from light sleep
+    EdgeWakeUnsupported,
+    /// The peripheral signal number is out of range
+    SignalOutOfRange,
+    /// The pin can't be used as an input
+    NotAnInput,
+    /// The pin can't be used as an output
+    NotAnOutput,
+}
+
+#[cfg(feature = "eh1")]
+impl embedded_hal_1::digital::Error for Error {
+    fn kind(&self) -> embedded_hal_1::digital::ErrorKind {
+        embedded_hal_1::digital::ErrorKind::Other
+    }
+}
+
+/// Path a peripheral signal takes between the peripheral and a pad
+///
+/// The IO MUX connects a few signals of each pad directly, its alternate
+/// functions. All other signals go through the GPIO matrix, which adds a
+/// delay and on the ESP32 limits e.g. SPI to 40 MHz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoutedVia {
+    /// Directly through an IO MUX function of the pad
+    IoMux,
+    /// Through the GPIO matrix
+    Matrix,
+}
+
 pub struct Unknown {}
 
 pub struct Input<MODE> {
@@ -85,6 +198,16 @@ pub struct AF1;
 #[doc(hidden)]
 pub struct AF2;
 
+#[doc(hidden)]
+pub struct AF3;
+
+#[doc(hidden)]
+pub struct AF4;
+
+#[doc(hidden)]
+pub struct AF5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DriveStrength {
     I5mA  = 0,
     I10mA = 1,
@@ -92,7 +215,180 @@ pub enum DriveStrength {
     I40mA = 3,
 }
 
-#[derive(PartialEq)]
+impl DriveStrength {
+    const fn from_bits(bits: u8) -> Self {
+        match bits & 0b11 {
+            0 => DriveStrength::I5mA,
+            1 => DriveStrength::I10mA,
+            2 => DriveStrength::I20mA,
+            _ => DriveStrength::I40mA,
+        }
+    }
+}
+
+/// Internal pull resistor of a pin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pull {
+    None,
+    Up,
+    Down,
+}
+
+impl Pull {
+    fn from_bits(pull_up: bool, pull_down: bool) -> Self {
+        match (pull_up, pull_down) {
+            (true, _) => Pull::Up,
+            (false, true) => Pull::Down,
+            (false, false) => Pull::None,
+        }
+    }
+
+    fn up(self) -> bool {
+        self == Pull::Up
+    }
+
+    fn down(self) -> bool {
+        self == Pull::Down
+    }
+}
+
+/// Register holding the pull resistors of a pad
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) enum PullRegister {
+    /// `fun_wpu` and `fun_wpd` of the pad's IO_MUX register
+    IoMux,
+    /// `rue` and `rde` of the pad's RTC_IO register
+    RtcIo,
+    /// The pad has no pull resistors
+    None,
+}
+
+/// Select the register holding the pull resistors of GPIO `pin` while it's
+/// routed to the IO_MUX or, if `rtc_routed` is set, to the RTC mux
+pub(crate) const fn pull_register(pin: u8, rtc_routed: bool) -> PullRegister {
+    if !has_pull_resistors(pin) {
+        PullRegister::None
+    } else if rtc_routed {
+        PullRegister::RtcIo
+    } else {
+        PullRegister::IoMux
+    }
+}
+
+/// GPIO34 to GPIO39 of the ESP32 are input only pads without pull resistors
+const fn has_pull_resistors(pin: u8) -> bool {
+    !cfg!(esp32) || !matches!(pin, 34..=39)
+}
+
+const _: () = {
+    assert!(matches!(pull_register(0, false), PullRegister::IoMux));
+    assert!(matches!(pull_register(0, true), PullRegister::RtcIo));
+    assert!(matches!(pull_register(21, false), PullRegister::IoMux));
+};
+
+/// Configuration of a push-pull output
+///
+/// Defaults to no pull resistor, 20 mA drive strength and the input disabled.
+///
+/// Configuring a strapping pin as output panics in debug builds unless it is
+/// explicitly allowed with [OutputConfig::allow_strapping_pin_use].
+#[derive(Clone, Copy)]
+pub struct OutputConfig {
+    pub pull: Pull,
+    pub drive_strength: DriveStrength,
+    pub input_enabled: bool,
+    /// Allow using a strapping pin, see [STRAPPING_PINS]
+    pub allow_strapping_pin: bool,
+}
+
+impl OutputConfig {
+    pub fn pull(mut self, pull: Pull) -> Self {
+        self.pull = pull;
+        self
+    }
+
+    pub fn drive_strength(mut self, drive_strength: DriveStrength) -> Self {
+        self.drive_strength = drive_strength;
+        self
+    }
+
+    pub fn input_enabled(mut self, input_enabled: bool) -> Self {
+        self.input_enabled = input_enabled;
+        self
+    }
+
+    /// Don't complain if the pin is a strapping pin
+    ///
+    /// Only use it if the board is designed for the pin to be driven, i.e.
+    /// nothing else drives it while the chip is in reset.
+    pub fn allow_strapping_pin_use(mut self) -> Self {
+        self.allow_strapping_pin = true;
+        self
+    }
+}
+
+impl Default for OutputConfig {
+    fn default() -> OutputConfig {
+        OutputConfig {
+            pull: Pull::None,
+            drive_strength: DriveStrength::I20mA,
+            input_enabled: false,
+            allow_strapping_pin: false,
+        }
+    }
+}
+
+/// Configuration of an open drain output
+///
+/// Defaults to no pull resistor, 20 mA drive strength and the input enabled,
+/// so the level of the line can be read back.
+#[derive(Clone, Copy)]
+pub struct OutputOpenDrainConfig {
+    pub pull: Pull,
+    pub drive_strength: DriveStrength,
+    pub input_enabled: bool,
+    /// Allow using a strapping pin, see [STRAPPING_PINS]
+    pub allow_strapping_pin: bool,
+}
+
+impl OutputOpenDrainConfig {
+    pub fn pull(mut self, pull: Pull) -> Self {
+        self.pull = pull;
+        self
+    }
+
+    pub fn drive_strength(mut self, drive_strength: DriveStrength) -> Self {
+        self.drive_strength = drive_strength;
+        self
+    }
+
+    pub fn input_enabled(mut self, input_enabled: bool) -> Self {
+        self.input_enabled = input_enabled;
+        self
+    }
+
+    /// Don't complain if the pin is a strapping pin
+    ///
+    /// Only use it if the board is designed for the pin to be driven, i.e.
+    /// nothing else drives it while the chip is in reset.
+    pub fn allow_strapping_pin_use(mut self) -> Self {
+        self.allow_strapping_pin = true;
+        self
+    }
+}
+
+impl Default for OutputOpenDrainConfig {
+    fn default() -> OutputOpenDrainConfig {
+        OutputOpenDrainConfig {
+            pull: Pull::None,
+            drive_strength: DriveStrength::I20mA,
+            input_enabled: true,
+            allow_strapping_pin: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
 pub enum AlternateFunction {
     Function0 = 0,
     Function1 = 1,
@@ -114,17 +410,122 @@ pub trait Pin {
     fn set_alternate_function(&mut self, alternate: AlternateFunction) -> &mut Self;
 
     fn listen(&mut self, event: Event) {
-        self.listen_with_options(event, true, false, false)
+        self.listen_with_target(event, InterruptTarget::Cpu, false)
+    }
+
+    /// Listen for `event`, raising the interrupts of `target`
+    ///
+    /// With `wake_up_from_light_sleep` a level event also wakes the chip up
+    /// from light sleep, edge events can't (see
+    /// [`Pin::try_listen_with_target`]).
+    fn listen_with_target(
+        &mut self,
+        event: Event,
+        target: InterruptTarget,
+        wake_up_from_light_sleep: bool,
+    );
+
+    /// Like [`Pin::listen_with_target`], but returns
+    /// [`Error::EdgeWakeUnsupported`] instead of panicking when an edge event
+    /// should wake the chip up from light sleep
+    fn try_listen_with_target(
+        &mut self,
+        event: Event,
+        target: InterruptTarget,
+        wake_up_from_light_sleep: bool,
+    ) -> Result<(), Error> {
+        if wake_up_from_light_sleep
+            && matches!(
+                event,
+                Event::AnyEdge | Event::RisingEdge | Event::FallingEdge
+            )
+        {
+            return Err(Error::EdgeWakeUnsupported);
+        }
+
+        self.listen_with_target(event, target, wake_up_from_light_sleep);
+        Ok(())
     }
 
+    #[deprecated(note = "use `Pin::listen_with_target` with an `InterruptTarget`")]
     fn listen_with_options(
         &mut self,
         event: Event,
         int_enable: bool,
         nmi_enable: bool,
         wake_up_from_light_sleep: bool,
+    ) {
+        self.listen_with_target(
+            event,
+            InterruptTarget::from_flags(int_enable, nmi_enable),
+            wake_up_from_light_sleep,
+        )
+    }
+
+    #[deprecated(note = "use `Pin::try_listen_with_target` with an `InterruptTarget`")]
+    fn try_listen_with_options(
+        &mut self,
+        event: Event,
+        int_enable: bool,
+        nmi_enable: bool,
+        wake_up_from_light_sleep: bool,
+    ) -> Result<(), Error> {
+        self.try_listen_with_target(
+            event,
+            InterruptTarget::from_flags(int_enable, nmi_enable),
+            wake_up_from_light_sleep,
+        )
+    }
+
+    /// Listen for interrupts, delivered to `core` only
+    ///
+    /// The ESP32 can deliver the interrupt of each pin to either core, the
+    /// `GPIO` interrupt has to be enabled on that core to handle it there.
+    #[cfg(esp32)]
+    fn listen_on_core(&mut self, event: Event, core: crate::Cpu) {
+        self.listen_on_core_with_target(event, core, InterruptTarget::Cpu, false)
+    }
+
+    /// Like [`Pin::listen_with_target`], but delivers the interrupt to `core`
+    /// only
+    #[cfg(esp32)]
+    fn listen_on_core_with_target(
+        &mut self,
+        event: Event,
+        core: crate::Cpu,
+        target: InterruptTarget,
+        wake_up_from_light_sleep: bool,
     );
 
+    #[cfg(esp32)]
+    #[deprecated(note = "use `Pin::listen_on_core_with_target` with an `InterruptTarget`")]
+    fn listen_on_core_with_options(
+        &mut self,
+        event: Event,
+        core: crate::Cpu,
+        int_enable: bool,
+        nmi_enable: bool,
+        wake_up_from_light_sleep: bool,
+    ) {
+        self.listen_on_core_with_target(
+            event,
+            core,
+            InterruptTarget::from_flags(int_enable, nmi_enable),
+            wake_up_from_light_sleep,
+        )
+    }
+
+    /// The event and target the pin is listening for, `None` if it isn't
+    ///
+    /// Decoded from the pin register. On the ESP32 the target doesn't tell
+    /// which core the interrupt is delivered to.
+    fn is_listening(&self) -> Option<(Event, InterruptTarget)>;
+
+    /// Stop listening
+    ///
+    /// Clears the event, the interrupt target and the light sleep wake-up of
+    /// the pin and then its pending status, so a later listen doesn't start
+    /// with a stale interrupt.
     fn unlisten(&mut self);
 
     fn clear_interrupt(&mut self);
@@ -135,6 +536,17 @@ pub trait Pin {
 
     fn is_acore_interrupt_set(&self) -> bool;
 
+    /// Whether the interrupt of this pin is pending for the core calling this
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+    fn is_interrupt_set(&self) -> bool {
+        #[cfg(esp32)]
+        if matches!(crate::get_core(), crate::Cpu::AppCpu) {
+            return self.is_acore_interrupt_set();
+        }
+
+        self.is_pcore_interrupt_set()
+    }
+
     fn is_acore_non_maskable_interrupt_set(&self) -> bool;
 
     fn enable_hold(&mut self, on: bool);
@@ -149,6 +561,13 @@ pub trait InputPin: Pin {
 
     fn is_input_high(&self) -> bool;
 
+    /// Connect `signal` to this pin
+    ///
+    /// Selects the GPIO function of the pad, or the IO MUX function which
+    /// carries `signal`. Use it on pins which are only inputs; an output
+    /// connected through the IO MUX is detached. To feed a pin's own output
+    /// back into a peripheral use
+    /// [`InputPin::connect_input_to_peripheral_keeping_function`].
     fn connect_input_to_peripheral(&mut self, signal: InputSignal) -> &mut Self {
         self.connect_input_to_peripheral_with_options(signal, false, false)
     }
@@ -158,7 +577,38 @@ pub trait InputPin: Pin {
         signal: InputSignal,
         invert: bool,
         force_via_gpio_mux: bool,
-    ) -> &mut Self;
+    ) -> &mut Self {
+        if self
+            .try_connect_input_to_peripheral_with_options(signal, invert, force_via_gpio_mux)
+            .is_err()
+        {
+            panic!("Cannot connect GPIO to this peripheral");
+        }
+        self
+    }
+
+    /// Like [`InputPin::connect_input_to_peripheral_with_options`], but
+    /// returns [`Error::SignalOutOfRange`] instead of panicking when `signal`
+    /// can only be reached through the IO_MUX of another pin
+    ///
+    /// Unless `force_via_gpio_mux` is set, the signal bypasses the GPIO
+    /// matrix if it is an IO MUX function of this pin and isn't inverted.
+    /// The returned [RoutedVia] tells which path was taken.
+    fn try_connect_input_to_peripheral_with_options(
+        &mut self,
+        signal: InputSignal,
+        invert: bool,
+        force_via_gpio_mux: bool,
+    ) -> Result<RoutedVia, Error>;
+
+    /// Connect `signal` to this pin through the GPIO matrix, leaving the
+    /// function of the pad and its output untouched
+    ///
+    /// Only enables the input of the pad and programs the matrix, so an
+    /// output pin or a pin in an alternate function keeps driving the pad
+    /// while a peripheral monitors it, e.g. to count the pulses the pin
+    /// outputs. Panics if `signal` can only be reached through the IO MUX.
+    fn connect_input_to_peripheral_keeping_function(&mut self, signal: InputSignal) -> &mut Self;
 
     /// Remove a connected `signal` from this input pin.
     ///
@@ -198,7 +648,37 @@ pub trait OutputPin: Pin {
         invert_enable: bool,
         enable_from_gpio: bool,
         force_via_gpio_mux: bool,
-    ) -> &mut Self;
+    ) -> &mut Self {
+        if self
+            .try_connect_peripheral_to_output_with_options(
+                signal,
+                invert,
+                invert_enable,
+                enable_from_gpio,
+                force_via_gpio_mux,
+            )
+            .is_err()
+        {
+            panic!("Cannot connect this peripheral to GPIO");
+        }
+        self
+    }
+
+    /// Like [`OutputPin::connect_peripheral_to_output_with_options`], but
+    /// returns [`Error::SignalOutOfRange`] instead of panicking when `signal`
+    /// can only be reached through the IO_MUX of another pin
+    ///
+    /// Unless `force_via_gpio_mux` is set, the signal bypasses the GPIO
+    /// matrix if it is an IO MUX function of this pin. The returned
+    /// [RoutedVia] tells which path was taken.
+    fn try_connect_peripheral_to_output_with_options(
+        &mut self,
+        signal: OutputSignal,
+        invert: bool,
+        invert_enable: bool,
+        enable_from_gpio: bool,
+        force_via_gpio_mux: bool,
+    ) -> Result<RoutedVia, Error>;
 
     /// Remove this output pin from a connected [signal](`InputSignal`).
     ///
@@ -212,6 +692,26 @@ pub trait OutputPin: Pin {
     fn internal_pull_down(&mut self, on: bool) -> &mut Self;
 }
 
+/// A driver which drives a pin with a peripheral output signal
+///
+/// The GPIO matrix can connect one output signal to any number of pads, so
+/// the signal can be mirrored to further pins (e.g. a spare header pin for a
+/// scope) without giving up the driver's own pin. Input signals can't be
+/// mirrored this way, a peripheral input is always read from a single pad.
+pub trait PeripheralOutput {
+    /// The output signal the driver connects to its pin
+    fn output_signal(&self) -> OutputSignal;
+
+    /// Drive `pin` with the same signal as the driver's own pin
+    ///
+    /// The mirror stays connected when the driver is dropped, use
+    /// [OutputPin::disconnect_peripheral_from_output] to release it.
+    fn mirror_to<P: OutputPin>(&self, pin: &mut P) {
+        pin.set_to_push_pull_output()
+            .connect_peripheral_to_output(self.output_signal());
+    }
+}
+
 #[doc(hidden)]
 pub struct SingleCoreInteruptStatusRegisterAccess {}
 #[doc(hidden)]
@@ -229,18 +729,22 @@ pub trait InteruptStatusRegisterAccess {
 }
 
 impl InteruptStatusRegisterAccess for SingleCoreInteruptStatusRegisterAccess {
+    #[inline(always)]
     fn pro_cpu_interrupt_status_read() -> u32 {
         unsafe { &*GPIO::PTR }.pcpu_int.read().bits()
     }
 
+    #[inline(always)]
     fn pro_cpu_nmi_status_read() -> u32 {
         unsafe { &*GPIO::PTR }.pcpu_nmi_int.read().bits()
     }
 
+    #[inline(always)]
     fn app_cpu_interrupt_status_read() -> u32 {
         unsafe { &*GPIO::PTR }.pcpu_int.read().bits()
     }
 
+    #[inline(always)]
     fn app_cpu_nmi_status_read() -> u32 {
         unsafe { &*GPIO::PTR }.pcpu_nmi_int.read().bits()
     }
@@ -252,18 +756,22 @@ impl InteruptStatusRegisterAccess for SingleCoreInteruptStatusRegisterAccess {
 // Treating it as SingleCore in the gpio macro makes this work.
 #[cfg(not(any(esp32c2, esp32c3, esp32s2, esp32s3)))]
 impl InteruptStatusRegisterAccess for DualCoreInteruptStatusRegisterAccess {
+    #[inline(always)]
     fn pro_cpu_interrupt_status_read() -> u32 {
         unsafe { &*GPIO::PTR }.pcpu_int.read().bits()
     }
 
+    #[inline(always)]
     fn pro_cpu_nmi_status_read() -> u32 {
         unsafe { &*GPIO::PTR }.pcpu_nmi_int.read().bits()
     }
 
+    #[inline(always)]
     fn app_cpu_interrupt_status_read() -> u32 {
         unsafe { &*GPIO::PTR }.acpu_int.read().bits()
     }
 
+    #[inline(always)]
     fn app_cpu_nmi_status_read() -> u32 {
         unsafe { &*GPIO::PTR }.acpu_nmi_int.read().bits()
     }
@@ -274,27 +782,33 @@ pub trait InterruptStatusRegisters<RegisterAccess>
 where
     RegisterAccess: InteruptStatusRegisterAccess,
 {
+    #[inline(always)]
     fn pro_cpu_interrupt_status_read(&self) -> u32 {
         RegisterAccess::pro_cpu_interrupt_status_read()
     }
 
+    #[inline(always)]
     fn pro_cpu_nmi_status_read(&self) -> u32 {
         RegisterAccess::pro_cpu_nmi_status_read()
     }
 
+    #[inline(always)]
     fn app_cpu_interrupt_status_read(&self) -> u32 {
         RegisterAccess::app_cpu_interrupt_status_read()
     }
 
+    #[inline(always)]
     fn app_cpu_nmi_status_read(&self) -> u32 {
         RegisterAccess::app_cpu_nmi_status_read()
     }
 }
 
 #[doc(hidden)]
+#[derive(Clone, Copy)]
 pub struct Bank0GpioRegisterAccess;
 
 #[doc(hidden)]
+#[derive(Clone, Copy)]
 pub struct Bank1GpioRegisterAccess;
 
 #[doc(hidden)]
@@ -387,26 +901,31 @@ impl BankGpioRegisterAccess for Bank0GpioRegisterAccess {
             .write(|w| unsafe { w.bits(word) });
     }
 
+    #[inline(always)]
     fn read_input(&self) -> u32 {
         unsafe { &*GPIO::PTR }.in_.read().bits()
     }
 
+    #[inline(always)]
     fn read_output(&self) -> u32 {
         unsafe { &*GPIO::PTR }.out.read().bits()
     }
 
+    #[inline(always)]
     fn write_interrupt_status_clear(&self, word: u32) {
         unsafe { &*GPIO::PTR }
             .status_w1tc
             .write(|w| unsafe { w.bits(word) });
     }
 
+    #[inline(always)]
     fn write_output_set(&self, word: u32) {
         unsafe { &*GPIO::PTR }
             .out_w1ts
             .write(|w| unsafe { w.bits(word) });
     }
 
+    #[inline(always)]
     fn write_output_clear(&self, word: u32) {
         unsafe { &*GPIO::PTR }
             .out_w1tc
@@ -428,26 +947,31 @@ impl BankGpioRegisterAccess for Bank1GpioRegisterAccess {
             .write(|w| unsafe { w.bits(word) });
     }
 
+    #[inline(always)]
     fn read_input(&self) -> u32 {
         unsafe { &*GPIO::PTR }.in1.read().bits()
     }
 
+    #[inline(always)]
     fn read_output(&self) -> u32 {
         unsafe { &*GPIO::PTR }.out1.read().bits()
     }
 
+    #[inline(always)]
     fn write_interrupt_status_clear(&self, word: u32) {
         unsafe { &*GPIO::PTR }
             .status1_w1tc
             .write(|w| unsafe { w.bits(word) });
     }
 
+    #[inline(always)]
     fn write_output_set(&self, word: u32) {
         unsafe { &*GPIO::PTR }
             .out1_w1ts
             .write(|w| unsafe { w.bits(word) });
     }
 
+    #[inline(always)]
     fn write_output_clear(&self, word: u32) {
         unsafe { &*GPIO::PTR }
             .out1_w1tc
@@ -477,8 +1001,267 @@ pub fn connect_high_to_peripheral(signal: InputSignal) {
     });
 }
 
+/// Connect a peripheral output signal to a peripheral input signal
+///
+/// The GPIO matrix is not able to connect two signals directly, so the
+/// connection is made through `pin`: the output signal drives the pad and the
+/// input signal is taken from the same pad with its input buffer enabled.
+/// The looped back signal is therefore visible on the pad, which must not be
+/// driven externally.
+///
+/// This is useful to self-test peripherals without any external wiring.
+pub fn loopback<P>(pin: &mut P, output: OutputSignal, input: InputSignal)
+where
+    P: InputPin + OutputPin,
+{
+    pin.set_to_push_pull_output()
+        .connect_peripheral_to_output_with_options(output, false, false, false, true)
+        .enable_input(true)
+        .connect_input_to_peripheral_with_options(input, false, true);
+}
+
+/// Undo a connection made by [`loopback`]
+///
+/// `pin` is left as a GPIO output.
+pub fn unloopback<P>(pin: &mut P, input: InputSignal)
+where
+    P: InputPin + OutputPin,
+{
+    pin.disconnect_input_from_peripheral(input)
+        .disconnect_peripheral_from_output()
+        .enable_input(false);
+}
+
+/// Find the pad a peripheral output signal is routed to via the GPIO matrix
+pub(crate) fn find_output_pad(signal: OutputSignal) -> Option<u8> {
+    if signal as usize > OUTPUT_SIGNAL_MAX as usize {
+        return None;
+    }
+
+    unsafe { &*GPIO::PTR }
+        .func_out_sel_cfg
+        .iter()
+        .position(|cfg| cfg.read().out_sel().bits() == signal as OutputSignalType)
+        .map(|gpio_num| gpio_num as u8)
+}
+
+/// Find the pad a peripheral input signal is taken from via the GPIO matrix
+pub(crate) fn find_input_pad(signal: InputSignal) -> Option<u8> {
+    let cfg = unsafe { &*GPIO::PTR }.func_in_sel_cfg[signal as usize].read();
+
+    // the constant levels are selected via pads which don't exist
+    if cfg.sel().bit_is_set() && types::pin_exists(cfg.in_sel().bits()) {
+        Some(cfg.in_sel().bits())
+    } else {
+        None
+    }
+}
+
+/// Input selection and pad input enable replaced by
+/// [`loopback_from_output_pad`]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Loopback {
+    input_selection: u32,
+    gpio_num: u8,
+    input_enable: bool,
+}
+
+/// Take a peripheral input signal from the pad the given output signal is
+/// routed to
+///
+/// Returns the previous input selection of the signal and input enable of the
+/// pad so they can be restored with [`restore_input_selection`], or `None` if
+/// the output signal is not routed to any pad.
+pub(crate) fn loopback_from_output_pad(
+    output: OutputSignal,
+    input: InputSignal,
+) -> Option<Loopback> {
+    let gpio_num = find_output_pad(output)?;
+    let cfg = &unsafe { &*GPIO::PTR }.func_in_sel_cfg[input as usize];
+    let io_mux = get_io_mux_reg(gpio_num);
+    let previous = Loopback {
+        input_selection: cfg.read().bits(),
+        gpio_num,
+        input_enable: io_mux.read().fun_ie().bit_is_set(),
+    };
+
+    io_mux.modify(|_, w| w.fun_ie().set_bit());
+    cfg.modify(|_, w| unsafe {
+        w.sel()
+            .set_bit()
+            .in_inv_sel()
+            .bit(false)
+            .in_sel()
+            .bits(gpio_num)
+    });
+
+    Some(previous)
+}
+
+/// Restore the input selection of a signal and the input enable of the pad
+/// changed by [`loopback_from_output_pad`]
+pub(crate) fn restore_input_selection(input: InputSignal, previous: Loopback) {
+    unsafe { &*GPIO::PTR }.func_in_sel_cfg[input as usize]
+        .write(|w| unsafe { w.bits(previous.input_selection) });
+    get_io_mux_reg(previous.gpio_num).modify(|_, w| w.fun_ie().bit(previous.input_enable));
+}
+
+/// Drive a pad directly from the output of its IO_MUX function 0, bypassing
+/// the GPIO matrix. The output enable is controlled by the peripheral.
+pub(crate) fn connect_iomux_output(gpio_num: u8) {
+    unsafe { &*GPIO::PTR }.func_out_sel_cfg[gpio_num as usize]
+        .modify(|_, w| w.oen_sel().clear_bit().oen_inv_sel().clear_bit());
+    get_io_mux_reg(gpio_num).modify(|_, w| unsafe { w.mcu_sel().bits(0) });
+}
+
+/// Feed an input signal directly from the pad of its IO_MUX function 0,
+/// bypassing the GPIO matrix. The pad's pull-up is enabled.
+pub(crate) fn connect_iomux_input(gpio_num: u8, input: InputSignal) {
+    unsafe { &*GPIO::PTR }.func_in_sel_cfg[input as usize].modify(|_, w| w.sel().clear_bit());
+    get_io_mux_reg(gpio_num).modify(|_, w| unsafe {
+        w.mcu_sel()
+            .bits(0)
+            .fun_ie()
+            .set_bit()
+            .fun_wpd()
+            .clear_bit()
+            .fun_wpu()
+            .set_bit()
+    });
+}
+
+#[cfg(xtensa)]
+static mut NMI_HANDLER: Option<unsafe fn()> = None;
+
+/// Route the GPIO NMI to the non-maskable (level 7) interrupt of the current
+/// core and call `handler` from it.
+///
+/// Pins trigger the NMI when they are listened to with [InterruptTarget::Nmi]
+/// or [InterruptTarget::CpuAndNmi], see [`Pin::listen_with_target`]. The
+/// pending NMI status of all pins is cleared after `handler` returned. Level 7
+/// interrupts without a pending GPIO NMI still go to the `level7_interrupt`
+/// of the application.
+///
+/// The NMI preempts everything, including critical sections. `handler` must
+/// therefore not access any state shared with the rest of the program unless
+/// it is done with lock-free structures (e.g. atomics); taking a
+/// `critical_section::Mutex` or `RefCell` in the handler can corrupt the state
+/// or deadlock. It should be short and placed in RAM.
+#[cfg(xtensa)]
+pub fn enable_nmi(handler: unsafe fn()) {
+    use crate::interrupt::{self, CpuInterrupt};
+
+    unsafe {
+        NMI_HANDLER = Some(handler);
+
+        interrupt::map(
+            crate::get_core(),
+            crate::pac::Interrupt::GPIO_NMI,
+            CpuInterrupt::Interrupt14NmiPriority7,
+        );
+        xtensa_lx::interrupt::enable_mask(
+            xtensa_lx::interrupt::get_mask() | 1 << CpuInterrupt::Interrupt14NmiPriority7 as u32,
+        );
+    }
+}
+
+/// Stop routing the GPIO NMI to the current core.
+#[cfg(xtensa)]
+pub fn disable_nmi() {
+    crate::interrupt::disable(crate::get_core(), crate::pac::Interrupt::GPIO_NMI);
+}
+
+/// Called from the level 7 interrupt, returns `false` if no handler has been
+/// registered with [`enable_nmi`] or no GPIO NMI is pending. The interrupt is
+/// then passed on to the `level7_interrupt` of the application.
+#[cfg(xtensa)]
+#[procmacros::ram]
+pub(crate) unsafe fn handle_nmi() -> bool {
+    let handler = match NMI_HANDLER {
+        Some(handler) => handler,
+        None => return false,
+    };
+
+    let gpio = &*GPIO::PTR;
+    #[cfg(esp32)]
+    let (status, status1) = match crate::get_core() {
+        crate::Cpu::ProCpu => (
+            gpio.pcpu_nmi_int.read().bits(),
+            gpio.pcpu_nmi_int1.read().bits(),
+        ),
+        crate::Cpu::AppCpu => (
+            gpio.acpu_nmi_int.read().bits(),
+            gpio.acpu_nmi_int1.read().bits(),
+        ),
+    };
+    #[cfg(not(esp32))]
+    let (status, status1) = (
+        gpio.pcpu_nmi_int.read().bits(),
+        gpio.pcpu_nmi_int1.read().bits(),
+    );
+
+    // another level 7 source, e.g. the NMI of a peripheral
+    if status == 0 && status1 == 0 {
+        return false;
+    }
+
+    handler();
+
+    gpio.status_w1tc.write(|w| w.bits(status));
+    gpio.status1_w1tc.write(|w| w.bits(status1));
+
+    true
+}
+
+/// Capabilities of a GPIO, see [`pin_capabilities`]
+///
+/// A set of flags, combined with `|`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PinCapabilities(u8);
+
+impl PinCapabilities {
+    /// The pin can't be used as an output
+    pub const INPUT_ONLY: Self = Self(1 << 0);
+    /// The pin is an RTC GPIO, usable while the digital domain sleeps
+    pub const RTC: Self = Self(1 << 1);
+    /// The pin can be used as an ADC input
+    pub const ANALOG: Self = Self(1 << 2);
+    /// The pin can be used as a touch sensor
+    pub const TOUCH: Self = Self(1 << 3);
+
+    /// No capabilities
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    /// The raw flags
+    pub const fn bits(&self) -> u8 {
+        self.0
+    }
+
+    /// Returns `true` if all flags of `other` are set
+    pub const fn contains(&self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Flags set in either `self` or `other`
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl core::ops::BitOr for PinCapabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
 #[doc(hidden)]
-pub trait PinType {}
+pub trait PinType {
+    const CAPABILITIES: PinCapabilities;
+}
 
 #[doc(hidden)]
 pub trait IsOutputPin: PinType {}
@@ -501,19 +1284,28 @@ pub struct InputOutputAnalogPinType;
 #[doc(hidden)]
 pub struct InputOnlyAnalogPinType;
 
-impl PinType for InputOutputPinType {}
+impl PinType for InputOutputPinType {
+    const CAPABILITIES: PinCapabilities = PinCapabilities::empty();
+}
 impl IsOutputPin for InputOutputPinType {}
 impl IsInputPin for InputOutputPinType {}
 
-impl PinType for InputOnlyPinType {}
+impl PinType for InputOnlyPinType {
+    const CAPABILITIES: PinCapabilities = PinCapabilities::INPUT_ONLY;
+}
 impl IsInputPin for InputOnlyPinType {}
 
-impl PinType for InputOutputAnalogPinType {}
+impl PinType for InputOutputAnalogPinType {
+    const CAPABILITIES: PinCapabilities = PinCapabilities::ANALOG;
+}
 impl IsOutputPin for InputOutputAnalogPinType {}
 impl IsInputPin for InputOutputAnalogPinType {}
 impl IsAnalogPin for InputOutputAnalogPinType {}
 
-impl PinType for InputOnlyAnalogPinType {}
+impl PinType for InputOnlyAnalogPinType {
+    const CAPABILITIES: PinCapabilities =
+        PinCapabilities::INPUT_ONLY.union(PinCapabilities::ANALOG);
+}
 impl IsInputPin for InputOnlyAnalogPinType {}
 impl IsAnalogPin for InputOnlyAnalogPinType {}
 
@@ -589,32 +1381,165 @@ where
     RA: BankGpioRegisterAccess,
     PINTYPE: PinType,
 {
-    fn init_input(&self, pull_down: bool, pull_up: bool) {
-        let gpio = unsafe { &*GPIO::PTR };
-
-        self.reg_access.write_out_en_clear(1 << (GPIONUM % 32));
-        gpio.func_out_sel_cfg[GPIONUM as usize]
-            .modify(|_, w| unsafe { w.out_sel().bits(OutputSignal::GPIO as OutputSignalType) });
-
-        #[cfg(esp32)]
-        types::errata36(GPIONUM, pull_up, pull_down);
-
-        get_io_mux_reg(GPIONUM).modify(|_, w| unsafe {
-            w.mcu_sel()
-                .bits(GPIO_FUNCTION as u8)
+    fn listen_raw(&mut self, event: Event, int_ena: u8, wake_up_from_light_sleep: bool) {
+        if wake_up_from_light_sleep {
+            match event {
+                Event::AnyEdge | Event::RisingEdge | Event::FallingEdge => {
+                    panic!("Edge triggering is not supported for wake-up from light sleep");
+                }
+                _ => {}
+            }
+        }
+        unsafe {
+            (&*GPIO::PTR).pin[GPIONUM as usize].modify(|_, w| {
+                w.int_ena()
+                    .bits(int_ena)
+                    .int_type()
+                    .bits(event as u8)
+                    .wakeup_enable()
+                    .bit(wake_up_from_light_sleep)
+            });
+        }
+    }
+
+    fn init_input(&self, pull: Pull) {
+        let gpio = unsafe { &*GPIO::PTR };
+
+        if PINTYPE::CAPABILITIES.contains(PinCapabilities::ANALOG) {
+            types::internal_into_digital(GPIONUM);
+        }
+
+        self.reg_access.write_out_en_clear(1 << (GPIONUM % 32));
+        gpio.func_out_sel_cfg[GPIONUM as usize]
+            .modify(|_, w| unsafe { w.out_sel().bits(OutputSignal::GPIO as OutputSignalType) });
+
+        get_io_mux_reg(GPIONUM).modify(|_, w| unsafe {
+            w.mcu_sel()
+                .bits(GPIO_FUNCTION as u8)
                 .fun_ie()
                 .set_bit()
-                .fun_wpd()
-                .bit(pull_down)
-                .fun_wpu()
-                .bit(pull_up)
                 .slp_sel()
                 .clear_bit()
         });
+
+        // the pad is routed to the IO_MUX now, pads without pull resistors can
+        // only be turned into a floating input
+        if has_pull_resistors(GPIONUM) {
+            self.write_io_mux_pull(pull);
+        }
+    }
+
+    fn write_io_mux_pull(&self, pull: Pull) {
+        #[cfg(esp32)]
+        types::errata36(GPIONUM, pull.up(), pull.down());
+
+        get_io_mux_reg(GPIONUM)
+            .modify(|_, w| w.fun_wpu().bit(pull.up()).fun_wpd().bit(pull.down()));
+    }
+
+    /// Write the pull resistors of the register selected by
+    /// [pull_register] for the current routing of the pad
+    fn write_pull(&self, pull: Pull) -> Result<(), Error> {
+        match pull_register(GPIONUM, types::internal_is_rtc_routed(GPIONUM)) {
+            PullRegister::IoMux => {
+                self.write_io_mux_pull(pull);
+                Ok(())
+            }
+            PullRegister::RtcIo => {
+                if types::internal_set_rtc_pull(GPIONUM, pull.up(), pull.down()) {
+                    Ok(())
+                } else {
+                    Err(Error::UnsupportedPull)
+                }
+            }
+            PullRegister::None if pull == Pull::None => Ok(()),
+            PullRegister::None => Err(Error::UnsupportedPull),
+        }
+    }
+
+    /// Enable the internal pull resistor of the pad in its current routing
+    ///
+    /// While the pad is routed to the RTC mux (e.g. by `into_analog`) this
+    /// writes the pull of the RTC_IO pad register, otherwise the one of the
+    /// IO_MUX. Fails with [Error::UnsupportedPull] on GPIO34 to GPIO39 of the
+    /// ESP32, which have no pull resistors.
+    pub fn set_pull(&mut self, pull: Pull) -> Result<(), Error> {
+        self.write_pull(pull)
+    }
+
+    /// The internal pull resistor of the pad in its current routing
+    ///
+    /// Reports [Pull::Up] if both resistors are enabled.
+    pub fn pull(&self) -> Pull {
+        match pull_register(GPIONUM, types::internal_is_rtc_routed(GPIONUM)) {
+            PullRegister::IoMux => {
+                let r = get_io_mux_reg(GPIONUM).read();
+                Pull::from_bits(r.fun_wpu().bit_is_set(), r.fun_wpd().bit_is_set())
+            }
+            PullRegister::RtcIo => match types::internal_rtc_pull(GPIONUM) {
+                Some((pull_up, pull_down)) => Pull::from_bits(pull_up, pull_down),
+                None => Pull::None,
+            },
+            PullRegister::None => Pull::None,
+        }
+    }
+
+    /// Enable the internal pull resistor used while the chip is in light
+    /// sleep and the pad's sleep configuration is selected
+    ///
+    /// Fails with [Error::UnsupportedPull] on GPIO34 to GPIO39 of the ESP32.
+    pub fn set_sleep_pull(&mut self, pull: Pull) -> Result<(), Error> {
+        if !has_pull_resistors(GPIONUM) {
+            return match pull {
+                Pull::None => Ok(()),
+                _ => Err(Error::UnsupportedPull),
+            };
+        }
+
+        get_io_mux_reg(GPIONUM)
+            .modify(|_, w| w.mcu_wpu().bit(pull.up()).mcu_wpd().bit(pull.down()));
+        Ok(())
+    }
+
+    /// The internal pull resistor used in light sleep
+    pub fn sleep_pull(&self) -> Pull {
+        let r = get_io_mux_reg(GPIONUM).read();
+        Pull::from_bits(r.mcu_wpu().bit_is_set(), r.mcu_wpd().bit_is_set())
+    }
+
+    /// Enable the internal pull resistor of the RTC domain, independently of
+    /// the current routing
+    ///
+    /// This is the pull which stays active in deep sleep, when the RTC domain
+    /// controls the pad. On the ESP32-C2 and ESP32-C3 the RTC GPIOs share the
+    /// pull resistors of the IO_MUX. Fails with [Error::UnsupportedPull] for
+    /// pads which aren't RTC GPIOs or have no pull resistors.
+    pub fn set_rtc_pull(&mut self, pull: Pull) -> Result<(), Error> {
+        if !has_pull_resistors(GPIONUM) {
+            return match pull {
+                Pull::None => Ok(()),
+                _ => Err(Error::UnsupportedPull),
+            };
+        }
+
+        if types::internal_set_rtc_pull(GPIONUM, pull.up(), pull.down()) {
+            Ok(())
+        } else {
+            Err(Error::UnsupportedPull)
+        }
+    }
+
+    /// The internal pull resistor of the RTC domain, [Pull::None] for pads
+    /// without one
+    pub fn rtc_pull(&self) -> Pull {
+        match types::internal_rtc_pull(GPIONUM) {
+            Some((pull_up, pull_down)) => Pull::from_bits(pull_up, pull_down),
+            None => Pull::None,
+        }
     }
 
     pub fn into_floating_input(self) -> GpioPin<Input<Floating>, RA, PINTYPE, GPIONUM> {
-        self.init_input(false, false);
+        self.init_input(Pull::None);
         GpioPin {
             _mode: PhantomData,
             _pintype: PhantomData,
@@ -623,9 +1548,17 @@ where
             af_output_signals: self.af_output_signals,
         }
     }
+}
 
+// Only pads which can drive an output have pull resistors, GPIO34 to GPIO39
+// of the ESP32 can only be turned into a floating input
+impl<MODE, RA, PINTYPE, const GPIONUM: u8> GpioPin<MODE, RA, PINTYPE, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+    PINTYPE: IsOutputPin,
+{
     pub fn into_pull_up_input(self) -> GpioPin<Input<PullUp>, RA, PINTYPE, GPIONUM> {
-        self.init_input(false, true);
+        self.init_input(Pull::Up);
         GpioPin {
             _mode: PhantomData,
             _pintype: PhantomData,
@@ -636,7 +1569,7 @@ where
     }
 
     pub fn into_pull_down_input(self) -> GpioPin<Input<PullDown>, RA, PINTYPE, GPIONUM> {
-        self.init_input(true, false);
+        self.init_input(Pull::Down);
         GpioPin {
             _mode: PhantomData,
             _pintype: PhantomData,
@@ -647,13 +1580,62 @@ where
     }
 }
 
+impl<MODE, RA, PINTYPE, const GPIONUM: u8> GpioPin<Input<MODE>, RA, PINTYPE, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+    PINTYPE: IsInputPin,
+{
+    /// Count the given edges in software, wrapping around on overflow
+    ///
+    /// The edges are counted by [edge_counter::handle_interrupt], which has to
+    /// be called from the `GPIO` interrupt handler. Returns
+    /// [edge_counter::Error::LevelEvent] for a level event.
+    pub fn enable_edge_counter(&mut self, event: Event) -> Result<(), edge_counter::Error> {
+        self.enable_edge_counter_with_overflow(event, edge_counter::Overflow::Wrap)
+    }
+
+    /// Count the given edges in software, with the given behavior on overflow
+    pub fn enable_edge_counter_with_overflow(
+        &mut self,
+        event: Event,
+        overflow: edge_counter::Overflow,
+    ) -> Result<(), edge_counter::Error> {
+        edge_counter::check_event(event)?;
+
+        edge_counter::enable(GPIONUM, overflow);
+        self.clear_interrupt();
+        self.listen(event);
+        Ok(())
+    }
+
+    /// Stop counting edges, the count is kept
+    pub fn disable_edge_counter(&mut self) {
+        self.unlisten();
+        edge_counter::disable(GPIONUM);
+    }
+
+    /// Number of edges counted since the counter was enabled or reset
+    pub fn edge_count(&self) -> u32 {
+        edge_counter::count(GPIONUM)
+    }
+
+    /// Set the count to 0, the counter stays enabled
+    ///
+    /// Runs in a critical section, an edge counted by
+    /// [edge_counter::handle_interrupt] at the same time ends up either
+    /// before or after the reset.
+    pub fn reset_edge_count(&mut self) {
+        edge_counter::reset(GPIONUM);
+    }
+}
+
 impl<MODE, RA, PINTYPE, const GPIONUM: u8> InputPin for GpioPin<MODE, RA, PINTYPE, GPIONUM>
 where
     RA: BankGpioRegisterAccess,
     PINTYPE: PinType,
 {
     fn set_to_input(&mut self) -> &mut Self {
-        self.init_input(false, false);
+        self.init_input(Pull::None);
         self
     }
     fn enable_input(&mut self, on: bool) -> &mut Self {
@@ -667,12 +1649,12 @@ where
     fn is_input_high(&self) -> bool {
         self.reg_access.read_input() & (1 << (GPIONUM % 32)) != 0
     }
-    fn connect_input_to_peripheral_with_options(
+    fn try_connect_input_to_peripheral_with_options(
         &mut self,
         signal: InputSignal,
         invert: bool,
         force_via_gpio_mux: bool,
-    ) -> &mut Self {
+    ) -> Result<RoutedVia, Error> {
         let af = if force_via_gpio_mux {
             GPIO_FUNCTION
         } else {
@@ -696,19 +1678,49 @@ where
             res
         };
         if af == GPIO_FUNCTION && signal as usize > INPUT_SIGNAL_MAX as usize {
-            panic!("Cannot connect GPIO to this peripheral");
+            return Err(Error::SignalOutOfRange);
         }
         self.set_alternate_function(af);
+
+        // only the matrix can invert an input
+        let route = if af != GPIO_FUNCTION && !invert {
+            RoutedVia::IoMux
+        } else {
+            RoutedVia::Matrix
+        };
+
         if (signal as usize) <= INPUT_SIGNAL_MAX as usize {
+            // `sel` set takes the signal from the matrix, clear from the IO MUX
             unsafe { &*GPIO::PTR }.func_in_sel_cfg[signal as usize].modify(|_, w| unsafe {
-                w.sel()
-                    .set_bit()
-                    .in_inv_sel()
-                    .bit(invert)
-                    .in_sel()
-                    .bits(GPIONUM)
+                match route {
+                    RoutedVia::IoMux => w.sel().clear_bit(),
+                    RoutedVia::Matrix => w
+                        .sel()
+                        .set_bit()
+                        .in_inv_sel()
+                        .bit(invert)
+                        .in_sel()
+                        .bits(GPIONUM),
+                }
             });
         }
+        Ok(route)
+    }
+
+    fn connect_input_to_peripheral_keeping_function(&mut self, signal: InputSignal) -> &mut Self {
+        if signal as usize > INPUT_SIGNAL_MAX as usize {
+            panic!("Cannot connect GPIO to this peripheral");
+        }
+
+        self.enable_input(true);
+        unsafe { &*GPIO::PTR }.func_in_sel_cfg[signal as usize].modify(|_, w| unsafe {
+            w.sel()
+                .set_bit()
+                .in_inv_sel()
+                .clear_bit()
+                .in_sel()
+                .bits(GPIONUM)
+        });
         self
     }
 
@@ -740,45 +1752,60 @@ where
         self
     }
 
-    fn listen_with_options(
+    fn listen_with_target(
         &mut self,
         event: Event,
-        int_enable: bool,
-        nmi_enable: bool,
+        target: InterruptTarget,
         wake_up_from_light_sleep: bool,
     ) {
-        if wake_up_from_light_sleep {
-            match event {
-                Event::AnyEdge | Event::RisingEdge | Event::FallingEdge => {
-                    panic!("Edge triggering is not supported for wake-up from light sleep");
-                }
-                _ => {}
-            }
-        }
-        unsafe {
-            (&*GPIO::PTR).pin[GPIONUM as usize].modify(|_, w| {
-                w.int_ena()
-                    .bits(gpio_intr_enable(int_enable, nmi_enable))
-                    .int_type()
-                    .bits(event as u8)
-                    .wakeup_enable()
-                    .bit(wake_up_from_light_sleep)
-            });
-        }
+        self.listen_raw(event, gpio_intr_enable(target), wake_up_from_light_sleep);
+    }
+
+    #[cfg(esp32)]
+    fn listen_on_core_with_target(
+        &mut self,
+        event: Event,
+        core: crate::Cpu,
+        target: InterruptTarget,
+        wake_up_from_light_sleep: bool,
+    ) {
+        self.listen_raw(
+            event,
+            types::gpio_intr_enable_on_core(core, target),
+            wake_up_from_light_sleep,
+        );
+    }
+
+    fn is_listening(&self) -> Option<(Event, InterruptTarget)> {
+        let pin = unsafe { &*GPIO::PTR }.pin[GPIONUM as usize].read();
+        Event::from_int_type(pin.int_type().bits())
+            .map(|event| (event, gpio_intr_target(pin.int_ena().bits())))
     }
 
     fn unlisten(&mut self) {
-        unsafe {
-            (&*GPIO::PTR).pin[GPIONUM as usize]
-                .modify(|_, w| w.int_ena().bits(0).int_type().bits(0).int_ena().bits(0));
-        }
+        critical_section::with(|_| {
+            unsafe {
+                (&*GPIO::PTR).pin[GPIONUM as usize].modify(|_, w| {
+                    w.int_ena()
+                        .bits(0)
+                        .int_type()
+                        .bits(0)
+                        .wakeup_enable()
+                        .clear_bit()
+                });
+            }
+            self.reg_access
+                .write_interrupt_status_clear(1 << (GPIONUM % 32));
+        });
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn clear_interrupt(&mut self) {
         self.reg_access
             .write_interrupt_status_clear(1 << (GPIONUM % 32));
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn is_pcore_interrupt_set(&self) -> bool {
         (self.pro_cpu_interrupt_status_read() & (1 << (GPIONUM % 32))) != 0
     }
@@ -787,6 +1814,7 @@ where
         (self.pro_cpu_nmi_status_read() & (1 << (GPIONUM % 32))) != 0
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn is_acore_interrupt_set(&self) -> bool {
         (self.app_cpu_interrupt_status_read() & (1 << (GPIONUM % 32))) != 0
     }
@@ -807,10 +1835,12 @@ where
     PINTYPE: IsOutputPin,
 {
     type Error = Infallible;
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn set_high(&mut self) -> Result<(), Self::Error> {
         self.reg_access.write_output_set(1 << (GPIONUM % 32));
         Ok(())
     }
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn set_low(&mut self) -> Result<(), Self::Error> {
         self.reg_access.write_output_clear(1 << (GPIONUM % 32));
         Ok(())
@@ -823,6 +1853,7 @@ where
     RA: BankGpioRegisterAccess,
     PINTYPE: IsOutputPin,
 {
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn is_set_high(&self) -> Result<bool, Self::Error> {
         Ok(self.reg_access.read_output() & (1 << (GPIONUM % 32)) != 0)
     }
@@ -838,6 +1869,7 @@ where
     PINTYPE: IsOutputPin,
 {
     type Error = Infallible;
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn toggle(&mut self) -> Result<(), Self::Error> {
         use embedded_hal::digital::v2::{OutputPin as _, StatefulOutputPin as _};
         if self.is_set_high()? {
@@ -865,10 +1897,12 @@ where
     RA: BankGpioRegisterAccess,
     PINTYPE: IsOutputPin,
 {
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn set_low(&mut self) -> Result<(), Self::Error> {
         self.reg_access.write_output_clear(1 << (GPIONUM % 32));
         Ok(())
     }
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn set_high(&mut self) -> Result<(), Self::Error> {
         self.reg_access.write_output_set(1 << (GPIONUM % 32));
         Ok(())
@@ -882,6 +1916,7 @@ where
     RA: BankGpioRegisterAccess,
     PINTYPE: IsOutputPin,
 {
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn is_set_high(&self) -> Result<bool, Self::Error> {
         Ok(self.reg_access.read_output() & (1 << (GPIONUM % 32)) != 0)
     }
@@ -897,6 +1932,7 @@ where
     RA: BankGpioRegisterAccess,
     PINTYPE: IsOutputPin,
 {
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn toggle(&mut self) -> Result<(), Self::Error> {
         use embedded_hal_1::digital::{OutputPin as _, StatefulOutputPin as _};
         if self.is_set_high()? {
@@ -907,6 +1943,141 @@ where
     }
 }
 
+impl<MODE, RA, PINTYPE, const GPIONUM: u8> GpioPin<Output<MODE>, RA, PINTYPE, GPIONUM>
+where
+    RA: BankGpioRegisterAccess + Copy,
+    PINTYPE: IsOutputPin,
+{
+    /// Splits the output into `N` handles which can be used independently of
+    /// each other, e.g. one from the main loop and one from an interrupt
+    /// handler.
+    ///
+    /// See [SharedOutput].
+    pub fn into_shared<const N: usize>(self) -> [SharedOutput<MODE, RA, GPIONUM>; N] {
+        // `core::array::from_fn` needs Rust 1.63, the MSRV is 1.60
+        [(); N].map(|_| SharedOutput {
+            _mode: PhantomData,
+            reg_access: self.reg_access,
+        })
+    }
+}
+
+/// Handle to an output pin which can be used concurrently with other handles
+/// to the same pin
+///
+/// Created by [GpioPin::into_shared]. Setting and clearing the output only
+/// writes the pin's bit to the write-1-to-set / write-1-to-clear registers.
+/// That is a single store which doesn't affect any other pin, so handles can
+/// be used from different contexts at the same time without locking.
+///
+/// Toggling isn't atomic: the current level has to be read before the new one
+/// is written, and another handle may change the level in between. That's why
+/// `ToggleableOutputPin` isn't implemented; use [SharedOutput::try_toggle]
+/// instead.
+pub struct SharedOutput<MODE, RA, const GPIONUM: u8>
+where
+    RA: BankGpioRegisterAccess,
+{
+    _mode: PhantomData<MODE>,
+    reg_access: RA,
+}
+
+impl<MODE, RA, const GPIONUM: u8> SharedOutput<MODE, RA, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+{
+    /// Toggles the output inside a critical section.
+    ///
+    /// This can't race with handles used from interrupt handlers or from other
+    /// critical sections. On multi-core chips, a handle used on the other core
+    /// outside of a critical section can still change the level between
+    /// reading and writing it.
+    pub fn try_toggle(&mut self) -> Result<(), Infallible> {
+        critical_section::with(|_| {
+            if self.reg_access.read_output() & (1 << (GPIONUM % 32)) != 0 {
+                self.reg_access.write_output_clear(1 << (GPIONUM % 32));
+            } else {
+                self.reg_access.write_output_set(1 << (GPIONUM % 32));
+            }
+        });
+        Ok(())
+    }
+}
+
+impl<MODE, RA, const GPIONUM: u8> embedded_hal::digital::v2::OutputPin
+    for SharedOutput<MODE, RA, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+{
+    type Error = Infallible;
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.reg_access.write_output_set(1 << (GPIONUM % 32));
+        Ok(())
+    }
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.reg_access.write_output_clear(1 << (GPIONUM % 32));
+        Ok(())
+    }
+}
+
+impl<MODE, RA, const GPIONUM: u8> embedded_hal::digital::v2::StatefulOutputPin
+    for SharedOutput<MODE, RA, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+{
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.reg_access.read_output() & (1 << (GPIONUM % 32)) != 0)
+    }
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.is_set_high()?)
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<MODE, RA, const GPIONUM: u8> embedded_hal_1::digital::ErrorType
+    for SharedOutput<MODE, RA, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+{
+    type Error = Infallible;
+}
+
+#[cfg(feature = "eh1")]
+impl<MODE, RA, const GPIONUM: u8> embedded_hal_1::digital::OutputPin
+    for SharedOutput<MODE, RA, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+{
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.reg_access.write_output_clear(1 << (GPIONUM % 32));
+        Ok(())
+    }
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.reg_access.write_output_set(1 << (GPIONUM % 32));
+        Ok(())
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<MODE, RA, const GPIONUM: u8> embedded_hal_1::digital::StatefulOutputPin
+    for SharedOutput<MODE, RA, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+{
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.reg_access.read_output() & (1 << (GPIONUM % 32)) != 0)
+    }
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.is_set_high()?)
+    }
+}
+
 impl<RA, PINTYPE, const GPIONUM: u8> From<GpioPin<Unknown, RA, PINTYPE, GPIONUM>>
     for GpioPin<Input<Floating>, RA, PINTYPE, GPIONUM>
 where
@@ -937,7 +2108,7 @@ impl<RA, PINTYPE, const GPIONUM: u8> From<GpioPin<Unknown, RA, PINTYPE, GPIONUM>
     for GpioPin<Input<PullDown>, RA, PINTYPE, GPIONUM>
 where
     RA: BankGpioRegisterAccess,
-    PINTYPE: IsInputPin,
+    PINTYPE: IsOutputPin,
 {
     fn from(
         pin: GpioPin<Unknown, RA, PINTYPE, GPIONUM>,
@@ -972,6 +2143,19 @@ where
     }
 }
 
+impl<RA, PINTYPE, const GPIONUM: u8> From<GpioPin<Unknown, RA, PINTYPE, GPIONUM>>
+    for GpioPin<Alternate<AF0>, RA, PINTYPE, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+    PINTYPE: IsOutputPin,
+{
+    fn from(
+        pin: GpioPin<Unknown, RA, PINTYPE, GPIONUM>,
+    ) -> GpioPin<Alternate<AF0>, RA, PINTYPE, GPIONUM> {
+        pin.into_alternate_0()
+    }
+}
+
 impl<RA, PINTYPE, const GPIONUM: u8> From<GpioPin<Unknown, RA, PINTYPE, GPIONUM>>
     for GpioPin<Alternate<AF1>, RA, PINTYPE, GPIONUM>
 where
@@ -998,38 +2182,128 @@ where
     }
 }
 
+impl<RA, PINTYPE, const GPIONUM: u8> From<GpioPin<Unknown, RA, PINTYPE, GPIONUM>>
+    for GpioPin<Alternate<AF3>, RA, PINTYPE, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+    PINTYPE: IsOutputPin,
+{
+    fn from(
+        pin: GpioPin<Unknown, RA, PINTYPE, GPIONUM>,
+    ) -> GpioPin<Alternate<AF3>, RA, PINTYPE, GPIONUM> {
+        pin.into_alternate_3()
+    }
+}
+
+impl<RA, PINTYPE, const GPIONUM: u8> From<GpioPin<Unknown, RA, PINTYPE, GPIONUM>>
+    for GpioPin<Alternate<AF4>, RA, PINTYPE, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+    PINTYPE: IsOutputPin,
+{
+    fn from(
+        pin: GpioPin<Unknown, RA, PINTYPE, GPIONUM>,
+    ) -> GpioPin<Alternate<AF4>, RA, PINTYPE, GPIONUM> {
+        pin.into_alternate_4()
+    }
+}
+
+impl<RA, PINTYPE, const GPIONUM: u8> From<GpioPin<Unknown, RA, PINTYPE, GPIONUM>>
+    for GpioPin<Alternate<AF5>, RA, PINTYPE, GPIONUM>
+where
+    RA: BankGpioRegisterAccess,
+    PINTYPE: IsOutputPin,
+{
+    fn from(
+        pin: GpioPin<Unknown, RA, PINTYPE, GPIONUM>,
+    ) -> GpioPin<Alternate<AF5>, RA, PINTYPE, GPIONUM> {
+        pin.into_alternate_5()
+    }
+}
+
 impl<MODE, RA, PINTYPE, const GPIONUM: u8> GpioPin<MODE, RA, PINTYPE, GPIONUM>
 where
     RA: BankGpioRegisterAccess,
     PINTYPE: IsOutputPin,
 {
     fn init_output(&self, alternate: AlternateFunction, open_drain: bool) {
+        self.init_output_with_config(
+            alternate,
+            open_drain,
+            Pull::None,
+            DriveStrength::I20mA,
+            open_drain,
+        );
+    }
+
+    fn init_output_with_config(
+        &self,
+        alternate: AlternateFunction,
+        open_drain: bool,
+        pull: Pull,
+        drive_strength: DriveStrength,
+        input_enabled: bool,
+    ) {
         let gpio = unsafe { &*GPIO::PTR };
 
-        self.reg_access.write_out_en_set(1 << (GPIONUM % 32));
-        gpio.pin[GPIONUM as usize].modify(|_, w| w.pad_driver().bit(open_drain));
-
-        gpio.func_out_sel_cfg[GPIONUM as usize]
-            .modify(|_, w| unsafe { w.out_sel().bits(OutputSignal::GPIO as OutputSignalType) });
+        if PINTYPE::CAPABILITIES.contains(PinCapabilities::ANALOG) {
+            types::internal_into_digital(GPIONUM);
+        }
 
+        // configure the pad completely before enabling the output, so the pin doesn't
+        // glitch in between
         get_io_mux_reg(GPIONUM).modify(|_, w| unsafe {
             w.mcu_sel()
                 .bits(alternate as u8)
                 .fun_ie()
-                .bit(open_drain)
-                .fun_wpd()
-                .clear_bit()
-                .fun_wpu()
-                .clear_bit()
+                .bit(input_enabled)
                 .fun_drv()
-                .bits(DriveStrength::I20mA as u8)
+                .bits(drive_strength as u8)
                 .slp_sel()
                 .clear_bit()
         });
+        // the pad is routed to the IO_MUX now and output capable pads always
+        // have pull resistors
+        self.write_io_mux_pull(pull);
+
+        gpio.pin[GPIONUM as usize].modify(|_, w| w.pad_driver().bit(open_drain));
+
+        gpio.func_out_sel_cfg[GPIONUM as usize]
+            .modify(|_, w| unsafe { w.out_sel().bits(OutputSignal::GPIO as OutputSignalType) });
+
+        self.reg_access.write_out_en_set(1 << (GPIONUM % 32));
     }
 
+    /// Configure the pin as push-pull output
+    ///
+    /// Panics in debug builds if the pin is a strapping pin, see
+    /// [STRAPPING_PINS]. Use [GpioPin::into_push_pull_output_unchecked] if
+    /// driving it is intended.
     pub fn into_push_pull_output(self) -> GpioPin<Output<PushPull>, RA, PINTYPE, GPIONUM> {
-        self.init_output(GPIO_FUNCTION, false);
+        self.into_push_pull_output_with_config(OutputConfig::default())
+    }
+
+    /// Configure the pin as push-pull output, even if it is a strapping pin
+    pub fn into_push_pull_output_unchecked(
+        self,
+    ) -> GpioPin<Output<PushPull>, RA, PINTYPE, GPIONUM> {
+        self.into_push_pull_output_with_config(OutputConfig::default().allow_strapping_pin_use())
+    }
+
+    /// Configure the pin as push-pull output with the given pull, drive
+    /// strength and input settings in one step.
+    pub fn into_push_pull_output_with_config(
+        self,
+        config: OutputConfig,
+    ) -> GpioPin<Output<PushPull>, RA, PINTYPE, GPIONUM> {
+        check_strapping_pin(GPIONUM, config.allow_strapping_pin);
+        self.init_output_with_config(
+            GPIO_FUNCTION,
+            false,
+            config.pull,
+            config.drive_strength,
+            config.input_enabled,
+        );
         GpioPin {
             _mode: PhantomData,
             _pintype: PhantomData,
@@ -1039,8 +2313,38 @@ where
         }
     }
 
+    /// Configure the pin as open drain output
+    ///
+    /// Panics in debug builds if the pin is a strapping pin, see
+    /// [STRAPPING_PINS]. Use [GpioPin::into_open_drain_output_unchecked] if
+    /// driving it is intended.
     pub fn into_open_drain_output(self) -> GpioPin<Output<OpenDrain>, RA, PINTYPE, GPIONUM> {
-        self.init_output(GPIO_FUNCTION, true);
+        self.into_open_drain_output_with_config(OutputOpenDrainConfig::default())
+    }
+
+    /// Configure the pin as open drain output, even if it is a strapping pin
+    pub fn into_open_drain_output_unchecked(
+        self,
+    ) -> GpioPin<Output<OpenDrain>, RA, PINTYPE, GPIONUM> {
+        self.into_open_drain_output_with_config(
+            OutputOpenDrainConfig::default().allow_strapping_pin_use(),
+        )
+    }
+
+    /// Configure the pin as open drain output with the given pull, drive
+    /// strength and input settings in one step.
+    pub fn into_open_drain_output_with_config(
+        self,
+        config: OutputOpenDrainConfig,
+    ) -> GpioPin<Output<OpenDrain>, RA, PINTYPE, GPIONUM> {
+        check_strapping_pin(GPIONUM, config.allow_strapping_pin);
+        self.init_output_with_config(
+            GPIO_FUNCTION,
+            true,
+            config.pull,
+            config.drive_strength,
+            config.input_enabled,
+        );
         GpioPin {
             _mode: PhantomData,
             _pintype: PhantomData,
@@ -1050,19 +2354,73 @@ where
         }
     }
 
+    /// Connect the pad to IO MUX function 0
+    ///
+    /// Panics in debug builds if the pad has no signal on this function,
+    /// see [GpioPin::into_alternate_1].
+    pub fn into_alternate_0(self) -> GpioPin<Alternate<AF0>, RA, PINTYPE, GPIONUM> {
+        self.into_alternate(AlternateFunction::Function0)
+    }
+
+    /// Connect the pad to IO MUX function 1
+    ///
+    /// The signals a pad has on each function are listed in the IO MUX
+    /// table of the technical reference manual. Functions are counted from
+    /// 0 here, function 1 of the ESP32 manual is `into_alternate_0`.
+    ///
+    /// Panics in debug builds if the pad has no signal on this function.
     pub fn into_alternate_1(self) -> GpioPin<Alternate<AF1>, RA, PINTYPE, GPIONUM> {
-        self.init_output(AlternateFunction::Function1, false);
-        GpioPin {
-            _mode: PhantomData,
-            _pintype: PhantomData,
-            reg_access: self.reg_access,
-            af_input_signals: self.af_input_signals,
-            af_output_signals: self.af_output_signals,
-        }
+        self.into_alternate(AlternateFunction::Function1)
     }
 
+    /// Connect the pad to IO MUX function 2
+    ///
+    /// Panics in debug builds if the pad has no signal on this function,
+    /// see [GpioPin::into_alternate_1].
     pub fn into_alternate_2(self) -> GpioPin<Alternate<AF2>, RA, PINTYPE, GPIONUM> {
-        self.init_output(AlternateFunction::Function2, false);
+        self.into_alternate(AlternateFunction::Function2)
+    }
+
+    /// Connect the pad to IO MUX function 3
+    ///
+    /// Panics in debug builds if the pad has no signal on this function,
+    /// see [GpioPin::into_alternate_1].
+    pub fn into_alternate_3(self) -> GpioPin<Alternate<AF3>, RA, PINTYPE, GPIONUM> {
+        self.into_alternate(AlternateFunction::Function3)
+    }
+
+    /// Connect the pad to IO MUX function 4
+    ///
+    /// Panics in debug builds if the pad has no signal on this function,
+    /// see [GpioPin::into_alternate_1].
+    pub fn into_alternate_4(self) -> GpioPin<Alternate<AF4>, RA, PINTYPE, GPIONUM> {
+        self.into_alternate(AlternateFunction::Function4)
+    }
+
+    /// Connect the pad to IO MUX function 5, e.g. the EMAC RMII signals on
+    /// the ESP32
+    ///
+    /// Panics in debug builds if the pad has no signal on this function,
+    /// see [GpioPin::into_alternate_1].
+    pub fn into_alternate_5(self) -> GpioPin<Alternate<AF5>, RA, PINTYPE, GPIONUM> {
+        self.into_alternate(AlternateFunction::Function5)
+    }
+
+    fn into_alternate<AF>(
+        self,
+        alternate: AlternateFunction,
+    ) -> GpioPin<Alternate<AF>, RA, PINTYPE, GPIONUM> {
+        let function = alternate as usize;
+        debug_assert!(
+            function == GPIO_FUNCTION as usize
+                || self.af_input_signals[function].is_some()
+                || self.af_output_signals[function].is_some(),
+            "GPIO{} has no signal on IO MUX function {}",
+            GPIONUM,
+            function
+        );
+
+        self.init_output(alternate, false);
         GpioPin {
             _mode: PhantomData,
             _pintype: PhantomData,
@@ -1130,14 +2488,14 @@ where
         self
     }
 
-    fn connect_peripheral_to_output_with_options(
+    fn try_connect_peripheral_to_output_with_options(
         &mut self,
         signal: OutputSignal,
         invert: bool,
         invert_enable: bool,
         enable_from_gpio: bool,
         force_via_gpio_mux: bool,
-    ) -> &mut Self {
+    ) -> Result<RoutedVia, Error> {
         let af = if force_via_gpio_mux {
             GPIO_FUNCTION
         } else {
@@ -1161,7 +2519,7 @@ where
             res
         };
         if af == GPIO_FUNCTION && signal as usize > OUTPUT_SIGNAL_MAX as usize {
-            panic!("Cannot connect this peripheral to GPIO");
+            return Err(Error::SignalOutOfRange);
         }
         self.set_alternate_function(af);
         let clipped_signal = if signal as usize <= OUTPUT_SIGNAL_MAX as usize {
@@ -1179,7 +2537,13 @@ where
                 .oen_inv_sel()
                 .bit(invert_enable)
         });
-        self
+
+        // the matrix output is ignored while an IO MUX function is selected
+        Ok(if af == GPIO_FUNCTION {
+            RoutedVia::Matrix
+        } else {
+            RoutedVia::IoMux
+        })
     }
 
     fn disconnect_peripheral_from_output(&mut self) -> &mut Self {
@@ -1202,8 +2566,12 @@ where
 impl<MODE, RA, PINTYPE, const GPIONUM: u8> GpioPin<MODE, RA, PINTYPE, GPIONUM>
 where
     RA: BankGpioRegisterAccess,
-    PINTYPE: IsOutputPin,
+    PINTYPE: IsAnalogPin,
 {
+    /// Configure the pin for use by the ADC, DAC or touch sensor
+    ///
+    /// Any of the input and output conversions turn the pin back into a
+    /// digital pin.
     pub fn into_analog(self) -> GpioPin<Analog, RA, PINTYPE, GPIONUM> {
         types::internal_into_analog(GPIONUM);
 
@@ -1220,6 +2588,13 @@ where
 pub struct IO {
     _io_mux: IO_MUX,
     pub pins: types::Pins,
+    /// Electrical settings of the flash and PSRAM pads
+    pub flash_pads: FlashPads,
+    /// Levels of GPIO0 to GPIO31
+    pub bank0: GpioBank0,
+    /// Levels of GPIO32 and above
+    #[cfg(not(any(esp32c2, esp32c3)))]
+    pub bank1: GpioBank1,
 }
 impl IO {
     pub fn new(gpio: GPIO, io_mux: IO_MUX) -> Self {
@@ -1227,11 +2602,117 @@ impl IO {
         let io = IO {
             _io_mux: io_mux,
             pins,
+            flash_pads: FlashPads { _private: () },
+            bank0: GpioBank {
+                reg_access: Bank0GpioRegisterAccess,
+            },
+            #[cfg(not(any(esp32c2, esp32c3)))]
+            bank1: GpioBank {
+                reg_access: Bank1GpioRegisterAccess,
+            },
         };
         io
     }
 }
 
+/// Electrical settings of the flash and PSRAM pads
+///
+/// The pads stay connected to the SPI memory controller, only their drive
+/// strength and input enable can be changed. E.g. a stronger `SPICLK` driver
+/// helps flash at 80 MHz with long traces:
+///
+/// ```no_run
+/// io.flash_pads
+///     .set_drive_strength(FlashPad::Clk, DriveStrength::I40mA);
+/// ```
+///
+/// Disabling the input of a pad the memory controller reads from (any data
+/// pad) makes the next flash or PSRAM access fail.
+pub struct FlashPads {
+    _private: (),
+}
+
+impl FlashPads {
+    /// Set the drive strength of `pad`
+    pub fn set_drive_strength(&mut self, pad: FlashPad, strength: DriveStrength) -> &mut Self {
+        get_io_mux_reg(pad.gpio_num()).modify(|_, w| unsafe { w.fun_drv().bits(strength as u8) });
+        self
+    }
+
+    /// The drive strength of `pad`
+    pub fn drive_strength(&self, pad: FlashPad) -> DriveStrength {
+        DriveStrength::from_bits(get_io_mux_reg(pad.gpio_num()).read().fun_drv().bits())
+    }
+
+    /// Enable or disable the input of `pad`
+    pub fn enable_input(&mut self, pad: FlashPad, on: bool) -> &mut Self {
+        get_io_mux_reg(pad.gpio_num()).modify(|_, w| w.fun_ie().bit(on));
+        self
+    }
+
+    /// Check if the input of `pad` is enabled
+    pub fn is_input_enabled(&self, pad: FlashPad) -> bool {
+        get_io_mux_reg(pad.gpio_num()).read().fun_ie().bit_is_set()
+    }
+}
+
+/// Access to the levels of a bank of 32 GPIOs at once
+///
+/// Bit `n` of a mask stands for GPIO `n` of bank 0, or GPIO `32 + n` of
+/// bank 1. All bits of a mask are read or written with a single register
+/// access, e.g. to wait for a pattern on several inputs:
+///
+/// ```no_run
+/// let pattern = 0b0101;
+/// while io.bank0.read_input() & 0b1111 != pattern {}
+/// ```
+///
+/// The pins are configured with the typed pins (e.g.
+/// [GpioPin::into_push_pull_output]), the bank only reads and sets levels:
+/// - reading the input and output levels is always fine
+/// - setting and clearing outputs only touches the pins in the mask and can be
+///   mixed with typed pins, as long as the mask only contains output pins the
+///   caller is responsible for
+///
+/// Output enables and the pin configuration can't be changed through the bank,
+/// so it can't break the configuration of pins owned elsewhere.
+pub struct GpioBank<RA> {
+    reg_access: RA,
+}
+
+pub type GpioBank0 = GpioBank<Bank0GpioRegisterAccess>;
+#[cfg(not(any(esp32c2, esp32c3)))]
+pub type GpioBank1 = GpioBank<Bank1GpioRegisterAccess>;
+
+impl<RA> GpioBank<RA>
+where
+    RA: BankGpioRegisterAccess,
+{
+    /// Input levels of all pins of the bank
+    #[inline(always)]
+    pub fn read_input(&self) -> u32 {
+        self.reg_access.read_input()
+    }
+
+    /// Output levels set for all pins of the bank
+    #[inline(always)]
+    pub fn read_output(&self) -> u32 {
+        self.reg_access.read_output()
+    }
+
+    /// Drive the pins in `mask` high, other pins are not affected
+    #[inline(always)]
+    pub fn write_output_set(&mut self, mask: u32) {
+        self.reg_access.write_output_set(mask);
+    }
+
+    /// Drive the pins in `mask` low, other pins are not affected
+    #[inline(always)]
+    pub fn write_output_clear(&mut self, mask: u32) {
+        self.reg_access.write_output_clear(mask);
+    }
+}
+
 // while ESP32-S3 is multicore it is more like single core in terms of GPIO
 // interrupts
 #[cfg(esp32s3)]
@@ -1284,10 +2765,20 @@ where
 {
 }
 
+fn check_strapping_pin(gpionum: u8, allowed: bool) {
+    debug_assert!(
+        allowed || !is_strapping_pin(gpionum),
+        "GPIO{} is a strapping pin, driving it may keep the chip from booting. \
+         Allow it explicitly if the board is designed for that.",
+        gpionum
+    );
+}
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! gpio {
     (
+        strapping: $($strapping:literal)+;
         $(
             ($gpionum:literal, $bank:literal, $type:ident
                 $(
@@ -1345,9 +2836,95 @@ macro_rules! gpio {
                 )+
             }
 
+            /// The pins a board definition of [board_pins!](crate::board_pins)
+            /// doesn't use, `None` for the used ones
+            pub struct UnusedPins {
+                $(
+                    pub [< gpio $gpionum >] : Option<GpioPin<Unknown, [< Bank $bank GpioRegisterAccess >], [< $type PinType >], $gpionum>>,
+                )+
+            }
+
+            impl From<Pins> for UnusedPins {
+                fn from(pins: Pins) -> Self {
+                    UnusedPins {
+                        $(
+                            [< gpio $gpionum >]: Some(pins.[< gpio $gpionum >]),
+                        )+
+                    }
+                }
+            }
+
             $(
                 pub type [<Gpio $gpionum >]<MODE> = GpioPin<MODE, [< Bank $bank GpioRegisterAccess >], [< $type PinType >], $gpionum>;
             )+
+
+            /// Number of GPIOs of the chip
+            ///
+            /// GPIO numbers aren't necessarily contiguous, use [`pin_exists`]
+            /// to check for a specific one.
+            pub const NUM_PINS: usize = [$($gpionum),+].len();
+
+            /// Returns `true` if the chip has GPIO `n`
+            pub const fn pin_exists(n: u8) -> bool {
+                match n {
+                    $(
+                        $gpionum => true,
+                    )+
+                    _ => false,
+                }
+            }
+
+            /// Strapping pins of the chip
+            ///
+            /// Their levels are sampled at reset to select the boot mode (and
+            /// e.g. the flash voltage on the ESP32), driving them from the
+            /// board can keep the chip from booting or from entering the
+            /// download mode.
+            pub const STRAPPING_PINS: &[u8] = &[$($strapping),+];
+
+            /// Returns `true` if GPIO `n` is a strapping pin
+            pub const fn is_strapping_pin(n: u8) -> bool {
+                match n {
+                    $(
+                        $strapping => true,
+                    )+
+                    _ => false,
+                }
+            }
+
+            const _: () = {
+                let mut i = 0;
+                while i < STRAPPING_PINS.len() {
+                    assert!(pin_exists(STRAPPING_PINS[i]));
+                    i += 1;
+                }
+            };
+
+            /// Returns the capabilities of GPIO `n`, or `None` if the chip
+            /// doesn't have it
+            pub const fn pin_capabilities(n: u8) -> Option<PinCapabilities> {
+                let rtc = if is_rtc_pin(n) {
+                    PinCapabilities::RTC
+                } else {
+                    PinCapabilities::empty()
+                };
+                let touch = if is_touch_pin(n) {
+                    PinCapabilities::TOUCH
+                } else {
+                    PinCapabilities::empty()
+                };
+
+                match n {
+                    $(
+                        $gpionum => Some(
+                            <[< $type PinType >] as PinType>::CAPABILITIES
+                                .union(rtc)
+                                .union(touch)
+                        ),
+                    )+
+                    _ => None,
+                }
+            }
         }
     };
 }
@@ -1378,6 +2955,15 @@ macro_rules! analog {
             )
         )+
     ) => {
+        pub(crate) const fn is_rtc_pin(pin: u8) -> bool {
+            match pin {
+                $(
+                    $pin_num => true,
+                )+
+                _ => false,
+            }
+        }
+
         pub(crate) fn internal_into_analog(pin: u8) {
             use crate::pac::RTCIO;
             let rtcio = unsafe{ &*RTCIO::ptr() };
@@ -1420,6 +3006,76 @@ macro_rules! analog {
                     _ => unreachable!(),
             }
         }
+
+        pub(crate) fn internal_into_digital(pin: u8) {
+            use crate::pac::RTCIO;
+            let rtcio = unsafe{ &*RTCIO::ptr() };
+            $crate::gpio::enable_iomux_clk_gate();
+
+            match pin {
+                $(
+                    $pin_num => {
+                        // Connect pin to the IO_MUX again, the caller restores the
+                        // digital configuration
+                        paste! {
+                            rtcio.$pin_reg.modify(|_,w| w.$mux_sel().clear_bit());
+                        }
+                    }
+                )+
+                    _ => unreachable!(),
+            }
+        }
+        pub(crate) fn internal_is_rtc_routed(pin: u8) -> bool {
+            use crate::pac::RTCIO;
+            let rtcio = unsafe{ &*RTCIO::ptr() };
+
+            match pin {
+                $(
+                    $pin_num => rtcio.$pin_reg.read().$mux_sel().bit_is_set(),
+                )+
+                _ => false,
+            }
+        }
+
+        /// Returns `false` if the pad has no RTC pull resistors
+        pub(crate) fn internal_set_rtc_pull(pin: u8, pull_up: bool, pull_down: bool) -> bool {
+            use crate::pac::RTCIO;
+            let rtcio = unsafe{ &*RTCIO::ptr() };
+
+            match pin {
+                $(
+                    $pin_num => {
+                        $(
+                            rtcio.$pin_reg.modify(|_,w| w.$rue().bit(pull_up).$rde().bit(pull_down));
+                            return true;
+                        )?
+                    }
+                )+
+                _ => {}
+            }
+
+            false
+        }
+
+        /// The RTC pull-up and pull-down of the pad, `None` if it has none
+        pub(crate) fn internal_rtc_pull(pin: u8) -> Option<(bool, bool)> {
+            use crate::pac::RTCIO;
+            let rtcio = unsafe{ &*RTCIO::ptr() };
+
+            match pin {
+                $(
+                    $pin_num => {
+                        $(
+                            let r = rtcio.$pin_reg.read();
+                            return Some((r.$rue().bit_is_set(), r.$rde().bit_is_set()));
+                        )?
+                    }
+                )+
+                _ => {}
+            }
+
+            None
+        }
     }
 }
 
@@ -1435,6 +3091,15 @@ macro_rules! analog {
             )
         )+
     ) => {
+        pub(crate) const fn is_rtc_pin(pin: u8) -> bool {
+            match pin {
+                $(
+                    $pin_num => true,
+                )+
+                _ => false,
+            }
+        }
+
         pub(crate) fn internal_into_analog(pin: u8) {
             use crate::pac::RTCIO;
             let rtcio = unsafe{ &*RTCIO::ptr() };
@@ -1481,6 +3146,85 @@ macro_rules! analog {
                     _ => unreachable!(),
             }
         }
+
+        pub(crate) fn internal_into_digital(pin: u8) {
+            $crate::gpio::enable_iomux_clk_gate();
+
+            match pin {
+                $(
+                    $pin_num => {
+                        paste!{
+                            use $crate::gpio::types::[< esp32s2_get_rtc_pad_ $pin_reg>];
+                            let rtc_pad = [< esp32s2_get_rtc_pad_ $pin_reg>]();
+                        }
+
+                        // Connect pin to the IO_MUX again, the caller restores the
+                        // digital configuration
+                        rtc_pad.modify(|_,w| w.$mux_sel().clear_bit());
+                    }
+                )+
+                    _ => unreachable!(),
+            }
+        }
+        pub(crate) fn internal_is_rtc_routed(pin: u8) -> bool {
+            match pin {
+                $(
+                    $pin_num => {
+                        paste!{
+                            use $crate::gpio::types::[< esp32s2_get_rtc_pad_ $pin_reg>];
+                            let rtc_pad = [< esp32s2_get_rtc_pad_ $pin_reg>]();
+                        }
+
+                        rtc_pad.read().$mux_sel().bit_is_set()
+                    }
+                )+
+                _ => false,
+            }
+        }
+
+        /// Returns `false` if the pad has no RTC pull resistors
+        pub(crate) fn internal_set_rtc_pull(pin: u8, pull_up: bool, pull_down: bool) -> bool {
+            match pin {
+                $(
+                    $pin_num => {
+                        $(
+                            paste!{
+                                use $crate::gpio::types::[< esp32s2_get_rtc_pad_ $pin_reg>];
+                                let rtc_pad = [< esp32s2_get_rtc_pad_ $pin_reg>]();
+                            }
+
+                            rtc_pad.modify(|_,w| w.$rue().bit(pull_up).$rde().bit(pull_down));
+                            return true;
+                        )?
+                    }
+                )+
+                _ => {}
+            }
+
+            false
+        }
+
+        /// The RTC pull-up and pull-down of the pad, `None` if it has none
+        pub(crate) fn internal_rtc_pull(pin: u8) -> Option<(bool, bool)> {
+            match pin {
+                $(
+                    $pin_num => {
+                        $(
+                            paste!{
+                                use $crate::gpio::types::[< esp32s2_get_rtc_pad_ $pin_reg>];
+                                let rtc_pad = [< esp32s2_get_rtc_pad_ $pin_reg>]();
+                            }
+
+                            let r = rtc_pad.read();
+                            return Some((r.$rue().bit_is_set(), r.$rde().bit_is_set()));
+                        )?
+                    }
+                )+
+                _ => {}
+            }
+
+            None
+        }
     }
 }
 
@@ -1515,6 +3259,47 @@ macro_rules! analog {
             }
 
         }
+
+        pub(crate) fn internal_into_digital(pin: u8) {
+            // The analog function doesn't change the IO_MUX routing, the caller
+            // restores the digital configuration
+            match pin {
+                $(
+                    $pin_num => {}
+                )+
+                _ => unreachable!()
+            }
+        }
+        pub(crate) fn internal_is_rtc_routed(_pin: u8) -> bool {
+            // the RTC GPIOs are always controlled through the IO_MUX
+            false
+        }
+
+        /// Returns `false` if the pad isn't an RTC GPIO
+        pub(crate) fn internal_set_rtc_pull(pin: u8, pull_up: bool, pull_down: bool) -> bool {
+            use crate::pac::IO_MUX;
+            let io_mux = unsafe{ &*IO_MUX::PTR };
+
+            if !is_rtc_pin(pin) {
+                return false;
+            }
+
+            io_mux.gpio[pin as usize].modify(|_,w| w.fun_wpu().bit(pull_up).fun_wpd().bit(pull_down));
+            true
+        }
+
+        /// The pull-up and pull-down of the RTC GPIO, `None` for other pads
+        pub(crate) fn internal_rtc_pull(pin: u8) -> Option<(bool, bool)> {
+            use crate::pac::IO_MUX;
+            let io_mux = unsafe{ &*IO_MUX::PTR };
+
+            if !is_rtc_pin(pin) {
+                return None;
+            }
+
+            let r = io_mux.gpio[pin as usize].read();
+            Some((r.fun_wpu().bit_is_set(), r.fun_wpd().bit_is_set()))
+        }
     }
 }
 
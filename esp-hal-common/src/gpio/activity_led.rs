@@ -0,0 +1,203 @@
+//! Activity LEDs with a stretched blink
+//!
+//! A UART transmitting a few bytes toggles its TX line for less than a
+//! millisecond, too short to see on an LED connected to it. The
+//! [ActivityLed] works like a monostable: it watches a signal and keeps an
+//! LED on for at least the stretch time after the last edge of the signal.
+//!
+//! ```no_run
+//! let source = ActivitySource::signal(OutputSignal::U0TXD, io.pins.gpio3);
+//! let mut tx_led = ActivityLed::new(source, io.pins.gpio4, timer0, 50u64.millis());
+//!
+//! #[interrupt]
+//! fn GPIO() {
+//!     edge_counter::handle_interrupt();
+//! }
+//!
+//! #[interrupt]
+//! fn TG0_T0_LEVEL() {
+//!     critical_section::with(|cs| {
+//!         TX_LED.borrow_ref_mut(cs).as_mut().unwrap().on_interrupt();
+//!     });
+//! }
+//! ```
+//!
+//! The signal is watched on a pad, either
+//! - [ActivitySource::pin]: an input pin, e.g. the RX line of a UART, or
+//! - [ActivitySource::signal]: a spare pin which the GPIO matrix connects to a
+//!   peripheral output signal, in addition to the pin the peripheral drives.
+//!   The spare pin outputs a copy of the signal, leave it unconnected.
+//!
+//! The edges of the pad are counted by the software [edge
+//! counter](super::edge_counter) in the `GPIO` interrupt, which has to call
+//! [edge_counter::handle_interrupt]. A timer interrupt, which has to call
+//! [ActivityLed::on_interrupt], checks the count every quarter of the
+//! stretch time (at least every millisecond) and switches the LED. The LED
+//! lights up one check after the first edge at the latest and stays on for
+//! the stretch time (rounded up to whole checks) after the last one.
+//!
+//! Every edge costs an interrupt, see the [edge
+//! counter](super::edge_counter#maximum-pulse-rate) for the rates this
+//! allows. A UART at 115200 baud transmitting continuously is within them on
+//! the ESP32-C3.
+
+use embedded_hal::timer::CountDown;
+use fugit::MicrosDurationU64;
+
+use super::{edge_counter, Event, InputPin, OutputPin, OutputSignal};
+use crate::timer::{Instance, Timer};
+
+/// Shortest period of the checks of the edge count, in µs
+const MIN_CHECK_PERIOD_US: u64 = 1_000;
+
+/// The pad watched by an [ActivityLed], see the [module
+/// documentation](self)
+pub struct ActivitySource<P> {
+    pin: P,
+}
+
+impl<P> ActivitySource<P>
+where
+    P: InputPin,
+{
+    /// Watch the level of `pin`
+    pub fn pin(mut pin: P) -> Self {
+        pin.enable_input(true);
+        Self { pin }
+    }
+}
+
+impl<P> ActivitySource<P>
+where
+    P: InputPin + OutputPin,
+{
+    /// Connect `signal` to `spare_pin` and watch it there
+    ///
+    /// The pin the peripheral drives keeps driving the signal, `spare_pin`
+    /// outputs a copy of it.
+    pub fn signal(signal: OutputSignal, mut spare_pin: P) -> Self {
+        spare_pin
+            .set_to_push_pull_output()
+            .connect_peripheral_to_output(signal)
+            .enable_input(true);
+        Self { pin: spare_pin }
+    }
+}
+
+/// Keeps an LED on while a signal is active, see the [module
+/// documentation](self)
+pub struct ActivityLed<P, L, T> {
+    source: ActivitySource<P>,
+    led: L,
+    timer: Timer<T>,
+    /// Checks the LED stays on after the last edge
+    hold_checks: u32,
+    /// Checks left until the LED goes off
+    remaining: u32,
+    last_count: u32,
+}
+
+impl<P, L, T> ActivityLed<P, L, T>
+where
+    P: InputPin,
+    L: OutputPin,
+    T: Instance,
+{
+    /// Light `led` for at least `stretch` after every edge of `source`
+    ///
+    /// Listens for both edges of the source pad, the `GPIO` interrupt has to
+    /// call [edge_counter::handle_interrupt]. Enables the interrupt of
+    /// `timer`, which has to call [ActivityLed::on_interrupt].
+    pub fn new<Time>(
+        mut source: ActivitySource<P>,
+        mut led: L,
+        mut timer: Timer<T>,
+        stretch: Time,
+    ) -> Self
+    where
+        Time: Into<MicrosDurationU64>,
+    {
+        let (period, hold_checks) = check_period(stretch.into().to_micros());
+
+        let gpio_num = source.pin.number();
+        edge_counter::enable(gpio_num, edge_counter::Overflow::Wrap);
+        source.pin.clear_interrupt();
+        source.pin.listen(Event::AnyEdge);
+
+        led.set_to_push_pull_output().set_output_high(false);
+
+        timer.start(MicrosDurationU64::micros(period));
+        timer.listen();
+
+        Self {
+            source,
+            led,
+            timer,
+            hold_checks,
+            remaining: 0,
+            last_count: edge_counter::count(gpio_num),
+        }
+    }
+
+    /// Whether the LED is on
+    pub fn is_active(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// Check for edges since the last call and switch the LED
+    ///
+    /// To be called from the interrupt handler of the timer.
+    pub fn on_interrupt(&mut self) {
+        self.timer.clear_interrupt();
+        self.timer.set_alarm_active(true);
+
+        let count = edge_counter::count(self.source.pin.number());
+        if count != self.last_count {
+            self.last_count = count;
+            self.remaining = self.hold_checks;
+        } else if self.remaining > 0 {
+            self.remaining -= 1;
+        }
+
+        self.led.set_output_high(self.remaining > 0);
+    }
+
+    /// Stop watching, switch the LED off and return the source pin, the LED
+    /// pin and the timer
+    pub fn free(mut self) -> (P, L, Timer<T>) {
+        self.timer.unlisten();
+        self.timer.set_counter_active(false);
+
+        self.source.pin.unlisten();
+        edge_counter::disable(self.source.pin.number());
+
+        self.led.set_output_high(false);
+
+        (self.source.pin, self.led, self.timer)
+    }
+}
+
+/// The period of the checks in µs and the number of checks covering
+/// `stretch_us`
+const fn check_period(stretch_us: u64) -> (u64, u32) {
+    let period = if stretch_us / 4 > MIN_CHECK_PERIOD_US {
+        stretch_us / 4
+    } else {
+        MIN_CHECK_PERIOD_US
+    };
+    let checks = (stretch_us + period - 1) / period;
+    let checks = if checks == 0 { 1 } else { checks };
+
+    (period, checks as u32)
+}
+
+const _: () = {
+    // 50 ms: checked every 12.5 ms, on for 4 checks
+    assert!(check_period(50_000).0 == 12_500);
+    assert!(check_period(50_000).1 == 4);
+    // short stretch times are checked every millisecond
+    assert!(check_period(2_500).0 == 1_000);
+    assert!(check_period(2_500).1 == 3);
+    // no stretch still shows the activity for one check
+    assert!(check_period(0).1 == 1);
+};
@@ -0,0 +1,204 @@
+//! Waiting for pin events asynchronously
+//!
+//! Input pins and open drain outputs implement
+//! [embedded_hal_async::digital::Wait], e.g. to wait for a button or for
+//! another device to release an open drain line. The futures are woken from
+//! the `GPIO` interrupt, which has to be enabled and call
+//! [handle_interrupt]:
+//!
+//! ```no_run
+//! interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority1).unwrap();
+//!
+//! #[interrupt]
+//! fn GPIO() {
+//!     gpio::asynch::handle_interrupt();
+//! }
+//!
+//! button.wait_for_falling_edge().await.unwrap();
+//! ```
+//!
+//! [handle_interrupt] only handles the pins with a waiting future and leaves
+//! the interrupts of all other pins pending, so it can share the `GPIO`
+//! interrupt with other handlers (e.g. the [edge
+//! counter](super::edge_counter)).
+//!
+//! Waiting borrows the pin mutably, so there is at most one future per pin
+//! and a second registration can't replace the waker of the first one.
+//! Dropping a future before it completed stops listening on the pin.
+
+use core::{
+    future::Future,
+    pin::Pin as FuturePin,
+    sync::atomic::{AtomicU32, Ordering},
+    task::{Context, Poll},
+};
+
+use embassy_sync::waitqueue::AtomicWaker;
+use embedded_hal_async::digital::Wait;
+
+use super::{
+    BankGpioRegisterAccess,
+    Event,
+    GpioPin,
+    Input,
+    IsOutputPin,
+    OpenDrain,
+    Output,
+    Pin,
+    PinType,
+};
+use crate::pac::GPIO;
+
+#[cfg(any(esp32c2, esp32c3))]
+const BANKS: usize = 1;
+#[cfg(not(any(esp32c2, esp32c3)))]
+const BANKS: usize = 2;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEW_WAKER: AtomicWaker = AtomicWaker::new();
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU32 = AtomicU32::new(0);
+
+static WAKERS: [AtomicWaker; 32 * BANKS] = [NEW_WAKER; 32 * BANKS];
+/// Pins with a waiting future, cleared by the interrupt handler when the
+/// event occurred
+static WAITING: [AtomicU32; BANKS] = [ZERO; BANKS];
+
+/// Future returned by the [Wait] methods of the pins
+pub struct PinFuture<'a, P>
+where
+    P: Pin,
+{
+    pin: &'a mut P,
+}
+
+impl<'a, P> PinFuture<'a, P>
+where
+    P: Pin,
+{
+    fn new(pin: &'a mut P, event: Event) -> Self {
+        let gpio_num = pin.number();
+        WAITING[gpio_num as usize / 32].fetch_or(1 << (gpio_num % 32), Ordering::AcqRel);
+
+        pin.clear_interrupt();
+        pin.listen(event);
+
+        Self { pin }
+    }
+
+    fn is_waiting(&self) -> bool {
+        let gpio_num = self.pin.number();
+        WAITING[gpio_num as usize / 32].load(Ordering::Acquire) & (1 << (gpio_num % 32)) != 0
+    }
+}
+
+impl<'a, P> Future for PinFuture<'a, P>
+where
+    P: Pin,
+{
+    type Output = Result<(), core::convert::Infallible>;
+
+    fn poll(self: FuturePin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        WAKERS[self.pin.number() as usize].register(cx.waker());
+
+        if self.is_waiting() {
+            Poll::Pending
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+impl<'a, P> Drop for PinFuture<'a, P>
+where
+    P: Pin,
+{
+    fn drop(&mut self) {
+        if self.is_waiting() {
+            self.pin.unlisten();
+
+            let gpio_num = self.pin.number();
+            WAITING[gpio_num as usize / 32].fetch_and(!(1 << (gpio_num % 32)), Ordering::AcqRel);
+        }
+    }
+}
+
+/// Wake the futures waiting for the pending events
+///
+/// To be called from the `GPIO` interrupt handler. Only the interrupts of
+/// pins with a waiting future are handled, the pins stop listening.
+#[procmacros::ram]
+pub fn handle_interrupt() {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    let status = gpio.status.read().bits() & WAITING[0].load(Ordering::Acquire);
+    if status != 0 {
+        wake_bank(0, status);
+        gpio.status_w1tc.write(|w| unsafe { w.bits(status) });
+    }
+
+    #[cfg(not(any(esp32c2, esp32c3)))]
+    {
+        let status = gpio.status1.read().bits() & WAITING[1].load(Ordering::Acquire);
+        if status != 0 {
+            wake_bank(1, status);
+            gpio.status1_w1tc.write(|w| unsafe { w.bits(status) });
+        }
+    }
+}
+
+#[procmacros::ram]
+fn wake_bank(bank: usize, mut status: u32) {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    WAITING[bank].fetch_and(!status, Ordering::AcqRel);
+
+    while status != 0 {
+        let bit = status.trailing_zeros();
+        status &= !(1 << bit);
+
+        let gpio_num = bank * 32 + bit as usize;
+        gpio.pin[gpio_num].modify(|_, w| unsafe { w.int_ena().bits(0).int_type().bits(0) });
+        WAKERS[gpio_num].wake();
+    }
+}
+
+macro_rules! impl_wait {
+    ([$($generic:ident)?] $mode:ty, $pintype_bound:path) => {
+        impl<$($generic,)? RA, PINTYPE, const GPIONUM: u8> Wait
+            for GpioPin<$mode, RA, PINTYPE, GPIONUM>
+        where
+            RA: BankGpioRegisterAccess,
+            PINTYPE: $pintype_bound,
+        {
+            type WaitForHighFuture<'a> = PinFuture<'a, Self> where Self: 'a;
+            type WaitForLowFuture<'a> = PinFuture<'a, Self> where Self: 'a;
+            type WaitForRisingEdgeFuture<'a> = PinFuture<'a, Self> where Self: 'a;
+            type WaitForFallingEdgeFuture<'a> = PinFuture<'a, Self> where Self: 'a;
+            type WaitForAnyEdgeFuture<'a> = PinFuture<'a, Self> where Self: 'a;
+
+            fn wait_for_high<'a>(&'a mut self) -> Self::WaitForHighFuture<'a> {
+                PinFuture::new(self, Event::HighLevel)
+            }
+
+            fn wait_for_low<'a>(&'a mut self) -> Self::WaitForLowFuture<'a> {
+                PinFuture::new(self, Event::LowLevel)
+            }
+
+            fn wait_for_rising_edge<'a>(&'a mut self) -> Self::WaitForRisingEdgeFuture<'a> {
+                PinFuture::new(self, Event::RisingEdge)
+            }
+
+            fn wait_for_falling_edge<'a>(&'a mut self) -> Self::WaitForFallingEdgeFuture<'a> {
+                PinFuture::new(self, Event::FallingEdge)
+            }
+
+            fn wait_for_any_edge<'a>(&'a mut self) -> Self::WaitForAnyEdgeFuture<'a> {
+                PinFuture::new(self, Event::AnyEdge)
+            }
+        }
+    };
+}
+
+impl_wait!([MODE] Input<MODE>, PinType);
+impl_wait!([] Output<OpenDrain>, IsOutputPin);
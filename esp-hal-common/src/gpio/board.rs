@@ -0,0 +1,136 @@
+//! Board definitions
+//!
+//! A board support crate maps the pins of the chip to their function on the
+//! board. [board_pins!](crate::board_pins) declares a struct with a field of
+//! the right pin type for each function, and a constructor taking the
+//! [Pins](super::Pins) of the chip:
+//!
+//! ```no_run
+//! board_pins! {
+//!     /// The pins of my board
+//!     pub struct Board {
+//!         led: gpio5 => Output<PushPull>,
+//!         button: gpio9 => Input<PullUp>,
+//!         i2c_sda: gpio1 => OpenDrain,
+//!         i2c_scl: gpio2 => OpenDrain,
+//!         battery: gpio3 => Analog,
+//!     }
+//! }
+//!
+//! let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+//! let (mut board, unused) = Board::new(io.pins);
+//! board.led.set_high().unwrap();
+//! let spare = unused.gpio4.unwrap();
+//! ```
+//!
+//! The constructor converts the pins in the order of the fields and returns
+//! the pins the board doesn't use as [UnusedPins](super::UnusedPins). Using
+//! a pin twice is a compile error ("field `gpio5` bound multiple times in
+//! the pattern").
+//!
+//! The modes are
+//! - `Output<PushPull>` and `Output<OpenDrain>`, or just `OpenDrain`
+//! - `Input<Floating>`, `Input<PullUp>` and `Input<PullDown>`
+//! - `Analog`
+//! - `Unknown`, the pin is handed over unconverted
+
+/// Declare a board definition, see the [module
+/// documentation](crate::gpio::board)
+#[macro_export]
+macro_rules! board_pins {
+    (@type Output<PushPull>) => {
+        $crate::gpio::Output<$crate::gpio::PushPull>
+    };
+    (@type Output<OpenDrain>) => {
+        $crate::gpio::Output<$crate::gpio::OpenDrain>
+    };
+    (@type OpenDrain) => {
+        $crate::gpio::Output<$crate::gpio::OpenDrain>
+    };
+    (@type Input<Floating>) => {
+        $crate::gpio::Input<$crate::gpio::Floating>
+    };
+    (@type Input<PullUp>) => {
+        $crate::gpio::Input<$crate::gpio::PullUp>
+    };
+    (@type Input<PullDown>) => {
+        $crate::gpio::Input<$crate::gpio::PullDown>
+    };
+    (@type Analog) => {
+        $crate::gpio::Analog
+    };
+    (@type Unknown) => {
+        $crate::gpio::Unknown
+    };
+
+    (@convert $pin:expr, Output<PushPull>) => {
+        $pin.into_push_pull_output()
+    };
+    (@convert $pin:expr, Output<OpenDrain>) => {
+        $pin.into_open_drain_output()
+    };
+    (@convert $pin:expr, OpenDrain) => {
+        $pin.into_open_drain_output()
+    };
+    (@convert $pin:expr, Input<Floating>) => {
+        $pin.into_floating_input()
+    };
+    (@convert $pin:expr, Input<PullUp>) => {
+        $pin.into_pull_up_input()
+    };
+    (@convert $pin:expr, Input<PullDown>) => {
+        $pin.into_pull_down_input()
+    };
+    (@convert $pin:expr, Analog) => {
+        $pin.into_analog()
+    };
+    (@convert $pin:expr, Unknown) => {
+        $pin
+    };
+
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_attr:meta])*
+                $field:ident : $pin:ident => $mode:ident $(<$mode_arg:ident>)?
+            ),+ $(,)?
+        }
+    ) => {
+        $crate::paste::paste! {
+            $(#[$attr])*
+            $vis struct $name {
+                $(
+                    $(#[$field_attr])*
+                    pub $field: $crate::gpio::[< $pin:camel >]<
+                        $crate::board_pins!(@type $mode $(<$mode_arg>)?)
+                    >,
+                )+
+            }
+        }
+
+        impl $name {
+            /// Convert the pins of the board, returns the board and the pins
+            /// it doesn't use
+            $vis fn new(pins: $crate::gpio::Pins) -> (Self, $crate::gpio::UnusedPins) {
+                // rejects a pin used twice at compile time
+                #[allow(dead_code)]
+                fn unique(pins: &$crate::gpio::Pins) {
+                    let $crate::gpio::Pins { $($pin: _,)+ .. } = pins;
+                }
+
+                let mut unused = $crate::gpio::UnusedPins::from(pins);
+                let board = $name {
+                    $(
+                        $field: $crate::board_pins!(
+                            @convert unused.$pin.take().unwrap(),
+                            $mode $(<$mode_arg>)?
+                        ),
+                    )+
+                };
+
+                (board, unused)
+            }
+        }
+    };
+}
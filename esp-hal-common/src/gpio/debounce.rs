@@ -0,0 +1,232 @@
+//! Debouncing of input pins in a periodic timer interrupt
+//!
+//! Mechanical switches bounce for a few milliseconds when they are pressed
+//! or released. The [Debouncer] samples up to `N` pins with the period of a
+//! timer, filters the samples and reports stable states and edges:
+//!
+//! ```no_run
+//! let mut debouncer = Debouncer::<_, 4>::new(timer0, 1u64.millis(), Filter::Majority3of5);
+//! let start = debouncer
+//!     .add(io.pins.gpio4.into_pull_up_input(), true)
+//!     .unwrap();
+//! debouncer.on_change(start, |pressed| {
+//!     // called from the timer interrupt
+//! });
+//!
+//! #[interrupt]
+//! fn TG0_T0_LEVEL() {
+//!     critical_section::with(|cs| {
+//!         DEBOUNCER
+//!             .borrow_ref_mut(cs)
+//!             .as_mut()
+//!             .unwrap()
+//!             .on_interrupt();
+//!     });
+//! }
+//!
+//! if debouncer.rising(start) {
+//!     // pressed since the last call
+//! }
+//! ```
+//!
+//! [Debouncer::on_interrupt] reads the input levels of all pins with one
+//! register read per bank, the filter costs a shift and a mask operation per
+//! pin. A pin is reported as pressed once the filter saw it active for long
+//! enough, i.e. the state lags the switch by a few sample periods:
+//! - [Filter::Majority3of5]: three of the last five samples, a sample period of
+//!   1 ms to 2 ms suits most switches
+//! - [Filter::Integrator]: `samples` more active than inactive samples, which
+//!   also rides out longer bursts of bounces
+
+use embedded_hal::timer::CountDown;
+use fugit::MicrosDurationU64;
+
+use super::InputPin;
+use crate::{
+    pac::GPIO,
+    timer::{Instance, Timer},
+};
+
+/// How the samples of a pin are filtered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// The state follows the majority of the last five samples
+    Majority3of5,
+    /// A counter goes up with every active and down with every inactive
+    /// sample, between 0 and `samples`. The pin is pressed when it reaches
+    /// `samples` and released when it reaches 0.
+    Integrator { samples: u8 },
+}
+
+/// Called from the timer interrupt with the new state (`true` for pressed)
+/// when the stable state of a pin changes
+pub type Callback = fn(bool);
+
+/// Debouncer for up to `N` (at most 32) pins, see the [module
+/// documentation](self)
+pub struct Debouncer<T, const N: usize> {
+    timer: Timer<T>,
+    filter: Filter,
+    len: usize,
+    gpio_nums: [u8; N],
+    active_low: u32,
+    filters: [u8; N],
+    pressed: u32,
+    rose: u32,
+    fell: u32,
+    callbacks: [Option<Callback>; N],
+}
+
+impl<T, const N: usize> Debouncer<T, N>
+where
+    T: Instance,
+{
+    const VALID: () = assert!(N <= 32, "a Debouncer handles at most 32 pins");
+
+    /// Sample every `period` with `timer` and filter the samples with
+    /// `filter`
+    ///
+    /// Enables the interrupt of the timer, which has to call
+    /// [Debouncer::on_interrupt].
+    pub fn new<Time>(mut timer: Timer<T>, period: Time, filter: Filter) -> Self
+    where
+        Time: Into<MicrosDurationU64>,
+    {
+        #[allow(clippy::let_unit_value)]
+        let _ = Self::VALID;
+
+        timer.start(period);
+        timer.listen();
+
+        Self {
+            timer,
+            filter,
+            len: 0,
+            gpio_nums: [0; N],
+            active_low: 0,
+            filters: [0; N],
+            pressed: 0,
+            rose: 0,
+            fell: 0,
+            callbacks: [None; N],
+        }
+    }
+
+    /// Debounce `pin` and return its index
+    ///
+    /// With `active_low` the pin is pressed when it reads low, e.g. a switch
+    /// to ground with a pull-up. Returns `None` if all `N` slots are in use.
+    pub fn add<P: InputPin>(&mut self, pin: P, active_low: bool) -> Option<usize> {
+        if self.len == N {
+            return None;
+        }
+
+        let index = self.len;
+        self.gpio_nums[index] = pin.number();
+        if active_low {
+            self.active_low |= 1 << index;
+        }
+        self.len += 1;
+
+        Some(index)
+    }
+
+    /// Call `callback` from the timer interrupt when the state of pin `index`
+    /// changes
+    pub fn on_change(&mut self, index: usize, callback: Callback) {
+        self.callbacks[index] = Some(callback);
+    }
+
+    /// Whether pin `index` is pressed
+    pub fn is_pressed(&self, index: usize) -> bool {
+        self.pressed & (1 << index) != 0
+    }
+
+    /// Whether pin `index` was pressed since the last call
+    pub fn rising(&mut self, index: usize) -> bool {
+        let rose = self.rose & (1 << index) != 0;
+        self.rose &= !(1 << index);
+        rose
+    }
+
+    /// Whether pin `index` was released since the last call
+    pub fn falling(&mut self, index: usize) -> bool {
+        let fell = self.fell & (1 << index) != 0;
+        self.fell &= !(1 << index);
+        fell
+    }
+
+    /// Stop sampling and return the timer
+    pub fn free(mut self) -> Timer<T> {
+        self.timer.unlisten();
+        self.timer.set_counter_active(false);
+        self.timer
+    }
+
+    /// Sample and filter the pins
+    ///
+    /// To be called from the interrupt handler of the timer.
+    pub fn on_interrupt(&mut self) {
+        self.timer.clear_interrupt();
+        self.timer.set_alarm_active(true);
+
+        let gpio = unsafe { &*GPIO::PTR };
+        let bank0 = gpio.in_.read().bits();
+        #[cfg(not(any(esp32c2, esp32c3)))]
+        let bank1 = gpio.in1.read().bits();
+
+        let mut pressed = self.pressed;
+        for index in 0..self.len {
+            let gpio_num = self.gpio_nums[index];
+            #[cfg(not(any(esp32c2, esp32c3)))]
+            let levels = if gpio_num < 32 { bank0 } else { bank1 };
+            #[cfg(any(esp32c2, esp32c3))]
+            let levels = bank0;
+
+            let high = levels >> (gpio_num % 32) & 1;
+            let active = high ^ (self.active_low >> index & 1) != 0;
+
+            let was_pressed = pressed & (1 << index) != 0;
+            let is_pressed = self.sample(index, active, was_pressed);
+
+            if is_pressed != was_pressed {
+                pressed ^= 1 << index;
+                if is_pressed {
+                    self.rose |= 1 << index;
+                } else {
+                    self.fell |= 1 << index;
+                }
+
+                if let Some(callback) = self.callbacks[index] {
+                    callback(is_pressed);
+                }
+            }
+        }
+        self.pressed = pressed;
+    }
+
+    #[inline(always)]
+    fn sample(&mut self, index: usize, active: bool, pressed: bool) -> bool {
+        let state = &mut self.filters[index];
+
+        match self.filter {
+            Filter::Majority3of5 => {
+                *state = (*state << 1 | active as u8) & 0b1_1111;
+                state.count_ones() >= 3
+            }
+            Filter::Integrator { samples } => {
+                if active {
+                    *state = state.saturating_add(1).min(samples);
+                } else {
+                    *state = state.saturating_sub(1);
+                }
+
+                match *state {
+                    0 => false,
+                    s if s == samples => true,
+                    _ => pressed,
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//! Dedicated GPIO
+//!
+//! The ESP32-C3 core drives eight GPIO matrix signals, `CPU_GPIO_0` to
+//! `CPU_GPIO_7`, and reads eight more through custom CSRs. A CSR access takes
+//! a single CPU cycle, unlike the `GPIO` registers behind the APB, which
+//! makes bit-banged buses with tight timing possible:
+//!
+//! ```no_run
+//! let mut dedicated = DedicatedGpio::new(&mut peripheral_clock_control);
+//! dedicated.connect_output(0, &mut io.pins.gpio4);
+//! dedicated.connect_input(1, &mut io.pins.gpio5);
+//!
+//! dedicated::set(0b01);
+//! let high = dedicated::read() & 0b10 != 0;
+//! ```
+//!
+//! The channels are written and read by the free functions of this module,
+//! they only work after [DedicatedGpio::new] enabled the peripheral.
+
+use super::{InputPin, InputSignal, OutputPin, OutputSignal};
+use crate::system::{Peripheral, PeripheralClockControl};
+
+/// Number of channels in each direction
+pub const CHANNELS: u8 = 8;
+
+const OUTPUT_SIGNALS: [OutputSignal; CHANNELS as usize] = [
+    OutputSignal::CPU_GPIO_0,
+    OutputSignal::CPU_GPIO_1,
+    OutputSignal::CPU_GPIO_2,
+    OutputSignal::CPU_GPIO_3,
+    OutputSignal::CPU_GPIO_4,
+    OutputSignal::CPU_GPIO_5,
+    OutputSignal::CPU_GPIO_6,
+    OutputSignal::CPU_GPIO_7,
+];
+
+const INPUT_SIGNALS: [InputSignal; CHANNELS as usize] = [
+    InputSignal::CPU_GPIO_0,
+    InputSignal::CPU_GPIO_1,
+    InputSignal::CPU_GPIO_2,
+    InputSignal::CPU_GPIO_3,
+    InputSignal::CPU_GPIO_4,
+    InputSignal::CPU_GPIO_5,
+    InputSignal::CPU_GPIO_6,
+    InputSignal::CPU_GPIO_7,
+];
+
+/// The dedicated GPIO peripheral, connects channels to pins
+pub struct DedicatedGpio {
+    _private: (),
+}
+
+impl DedicatedGpio {
+    /// Enable the peripheral, all channels are low inputs
+    pub fn new(peripheral_clock_control: &mut PeripheralClockControl) -> Self {
+        peripheral_clock_control.enable(Peripheral::DedicatedGpio);
+        set_output_enable(0);
+        write(0);
+
+        Self { _private: () }
+    }
+
+    /// Drive `pin` from output channel `channel`
+    ///
+    /// The channel is enabled as an output.
+    pub fn connect_output<P: OutputPin>(&mut self, channel: u8, pin: &mut P) {
+        assert!(channel < CHANNELS);
+
+        // the output enable of the pad follows the channel's output enable
+        pin.connect_peripheral_to_output_with_options(
+            OUTPUT_SIGNALS[channel as usize],
+            false,
+            false,
+            false,
+            true,
+        );
+        set_output_enable(output_enable() | 1 << channel);
+    }
+
+    /// Read `pin` on input channel `channel`
+    pub fn connect_input<P: InputPin>(&mut self, channel: u8, pin: &mut P) {
+        assert!(channel < CHANNELS);
+
+        pin.connect_input_to_peripheral_keeping_function(INPUT_SIGNALS[channel as usize]);
+    }
+
+    /// Drive and read `pin` on channel `channel`, e.g. a bus line
+    ///
+    /// The channel starts as an input, [set_output_enable] switches the
+    /// direction.
+    pub fn connect_bidirectional<P>(&mut self, channel: u8, pin: &mut P)
+    where
+        P: InputPin + OutputPin,
+    {
+        self.connect_output(channel, pin);
+        self.connect_input(channel, pin);
+        set_output_enable(output_enable() & !(1 << channel));
+    }
+}
+
+/// Set the output channels to the bits of `value`
+#[inline(always)]
+pub fn write(value: u8) {
+    unsafe { core::arch::asm!("csrw 0x805, {0}", in(reg) value as u32, options(nostack)) };
+}
+
+/// Set the output channels whose bits in `mask` are set
+#[inline(always)]
+pub fn set(mask: u8) {
+    unsafe { core::arch::asm!("csrs 0x805, {0}", in(reg) mask as u32, options(nostack)) };
+}
+
+/// Clear the output channels whose bits in `mask` are set
+#[inline(always)]
+pub fn clear(mask: u8) {
+    unsafe { core::arch::asm!("csrc 0x805, {0}", in(reg) mask as u32, options(nostack)) };
+}
+
+/// The levels of the input channels
+#[inline(always)]
+pub fn read() -> u8 {
+    let value: u32;
+    unsafe { core::arch::asm!("csrr {0}, 0x804", out(reg) value, options(nostack)) };
+    value as u8
+}
+
+/// Enable the output of the channels whose bits in `mask` are set and
+/// disable all others
+#[inline(always)]
+pub fn set_output_enable(mask: u8) {
+    unsafe { core::arch::asm!("csrw 0x803, {0}", in(reg) mask as u32, options(nostack)) };
+}
+
+/// The channels with enabled output
+#[inline(always)]
+pub fn output_enable() -> u8 {
+    let mask: u32;
+    unsafe { core::arch::asm!("csrr {0}, 0x803", out(reg) mask, options(nostack)) };
+    mask as u8
+}
@@ -0,0 +1,546 @@
+//! Per-pin interrupt handlers in two priority tiers
+//!
+//! Every pin can have one handler which is called when the pin's event
+//! occurs. Handlers are registered in one of two tiers which use different
+//! interrupt sources of the GPIO peripheral:
+//!
+//! - [register_handler] listens with the normal interrupt enable of the pin.
+//!   The handlers are dispatched by [handle_interrupt] from the `GPIO`
+//!   interrupt. Dispatcher and handlers can be placed in flash.
+//! - [register_iram_handler] listens with the NMI enable of the pin. The
+//!   handlers are dispatched by [handle_iram_interrupt] from the `GPIO_NMI`
+//!   interrupt, which should be enabled with a higher priority. The dispatcher
+//!   is placed in RAM and the handlers have to be placed in RAM with `#[ram]`
+//!   as well, which is checked in debug builds. These handlers keep running
+//!   while the flash cache is disabled (e.g. during a flash write) as long as
+//!   they don't call any code or read any constants from flash.
+//!
+//! ```no_run
+//! interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority1).unwrap();
+//! interrupt::enable(pac::Interrupt::GPIO_NMI, interrupt::Priority::Priority3).unwrap();
+//!
+//! dispatch::register_handler(&mut button, Event::FallingEdge, on_button);
+//! dispatch::register_iram_handler(&mut encoder, Event::AnyEdge, on_encoder);
+//!
+//! #[interrupt]
+//! fn GPIO() {
+//!     gpio::dispatch::handle_interrupt();
+//! }
+//!
+//! #[ram]
+//! #[interrupt]
+//! fn GPIO_NMI() {
+//!     gpio::dispatch::handle_iram_interrupt();
+//! }
+//!
+//! #[ram]
+//! fn on_encoder() {
+//!     // ...
+//! }
+//! ```
+//!
+//! Both dispatchers only handle and clear the interrupts of the pins
+//! registered in their tier and leave all other pins pending, so they can
+//! share the interrupts with other handlers (e.g. the [edge
+//! counter](super::edge_counter)). On Xtensa chips the IRAM tier can't be
+//! used together with [enable_nmi](super::enable_nmi), which routes the same
+//! interrupt source to the non-maskable interrupt.
+//!
+//! Handlers can be registered and removed at any time. Registration runs in
+//! a critical section, which spans both cores, so concurrent registrations
+//! can't mix up the tables of a pin. The dispatchers only use atomics, as the
+//! IRAM tier can't be held off by a critical section: a handler and its
+//! signature are stored in one word, a dispatcher racing with a registration
+//! calls either the old or the new handler, never one with the wrong
+//! signature.
+//!
+//! The pending interrupt of a pin is cleared before its handler is called:
+//! an edge occurring while the handler runs stays pending and calls it
+//! again.
+//!
+//! ## Level snapshots
+//!
+//! A handler which reads other pins with `is_high()` sees their levels some
+//! time after the event, which is too late e.g. for the second channel of a
+//! rotary encoder turned quickly. [register_snapshot_handler] and
+//! [register_iram_snapshot_handler] register a handler which is called with a
+//! [Snapshot] of the levels of its own pin and up to four [SnapshotPins],
+//! taken from the input registers read once when the dispatcher is entered:
+//!
+//! ```no_run
+//! dispatch::register_iram_snapshot_handler(
+//!     &mut encoder_a,
+//!     Event::AnyEdge,
+//!     SnapshotPins::new().with(&encoder_b),
+//!     on_encoder_a,
+//! );
+//!
+//! #[ram]
+//! fn on_encoder_a(snapshot: Snapshot) {
+//!     let a = snapshot.is_high();
+//!     let b = snapshot.is_high_at(0);
+//!     // ...
+//! }
+//! ```
+//!
+//! The snapshot is taken before the pending interrupts are read, every level
+//! is at least as old as the event it's reported with.
+//!
+//! ## Level events
+//!
+//! A level interrupt stays pending as long as the level persists, so the
+//! dispatchers mask the pin before they call the handler of a
+//! [LowLevel](Event::LowLevel) or [HighLevel](Event::HighLevel) event. The
+//! [Rearm] policy of the registration decides when the pin listens again:
+//! with [Rearm::OnRelease] (the default of [register_handler]) once the level
+//! cleared, so the handler of a held-down button is called once per press;
+//! with [Rearm::Manual] when [re_arm] is called, e.g. after the condition
+//! signalled by the level was dealt with. The `listen*` functions of the
+//! pins don't mask level events.
+
+use core::sync::atomic::{AtomicU32, AtomicU8, AtomicUsize, Ordering};
+
+use super::{Event, InterruptTarget, Pin};
+use crate::pac::GPIO;
+
+#[cfg(any(esp32c2, esp32c3))]
+const BANKS: usize = 1;
+#[cfg(not(any(esp32c2, esp32c3)))]
+const BANKS: usize = 2;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_HANDLER: AtomicUsize = AtomicUsize::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU32 = AtomicU32::new(0);
+#[allow(clippy::declare_interior_mutable_const)]
+const NO_LEVEL: AtomicU8 = AtomicU8::new(0);
+
+/// `int_type` of a pin which doesn't trigger any interrupt
+const INT_TYPE_DISABLED: u8 = 0;
+
+/// The handler of each pin, tagged with [SNAPSHOT_HANDLER]
+static HANDLERS: [AtomicUsize; 32 * BANKS] = [NO_HANDLER; 32 * BANKS];
+static REGISTERED: [AtomicU32; BANKS] = [ZERO; BANKS];
+static REGISTERED_IRAM: [AtomicU32; BANKS] = [ZERO; BANKS];
+
+/// The [SnapshotPins] of each pin with a snapshot handler
+static SNAPSHOT_PINS: [AtomicU32; 32 * BANKS] = [ZERO; 32 * BANKS];
+
+/// The level event of each pin registered with one, 0 for edge events
+static LEVELS: [AtomicU8; 32 * BANKS] = [NO_LEVEL; 32 * BANKS];
+/// Pins registered with [Rearm::OnRelease]
+static REARM_ON_RELEASE: [AtomicU32; BANKS] = [ZERO; BANKS];
+/// Pins which listen for the release of their level
+static RELEASING: [AtomicU32; BANKS] = [ZERO; BANKS];
+
+/// Tag of a [HANDLERS] entry which is a `fn(Snapshot)`, functions are at
+/// least 2-byte aligned on all chips
+const SNAPSHOT_HANDLER: usize = 1;
+
+/// Unused entry of [SnapshotPins]
+const NO_PIN: u8 = 0xff;
+
+/// The pins whose levels are passed to a snapshot handler, at most four
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SnapshotPins {
+    gpio_nums: [u8; 4],
+}
+
+impl SnapshotPins {
+    /// No pins, the handler only gets the level of its own pin
+    pub fn new() -> Self {
+        Self {
+            gpio_nums: [NO_PIN; 4],
+        }
+    }
+
+    /// Add `pin`, its level is [Snapshot::is_high_at] the index it was added
+    /// at
+    ///
+    /// Panics if there are four pins already.
+    pub fn with(mut self, pin: &impl Pin) -> Self {
+        let index = self
+            .gpio_nums
+            .iter()
+            .position(|&gpio_num| gpio_num == NO_PIN)
+            .expect("at most four pins can be snapshot");
+        self.gpio_nums[index] = pin.number();
+        self
+    }
+
+    fn to_bits(self) -> u32 {
+        u32::from_le_bytes(self.gpio_nums)
+    }
+}
+
+impl Default for SnapshotPins {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Levels of a pin and its [SnapshotPins] when the dispatcher was entered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    /// Bit 0 is the pin of the handler, bit `n + 1` the snapshot pin `n`
+    levels: u8,
+}
+
+impl Snapshot {
+    /// Level of the pin the handler is registered for
+    pub fn is_high(&self) -> bool {
+        self.levels & 1 != 0
+    }
+
+    /// Level of the snapshot pin `index`, in the order they were added to the
+    /// [SnapshotPins]
+    pub fn is_high_at(&self, index: usize) -> bool {
+        self.levels & (2 << index) != 0
+    }
+
+    /// Levels of all pins, bit 0 is the pin of the handler and bit `n + 1`
+    /// the snapshot pin `n`
+    pub fn bits(&self) -> u8 {
+        self.levels
+    }
+
+    #[inline(always)]
+    fn capture(gpio_num: usize, snapshot_pins: u32, input: &[u32; BANKS]) -> Self {
+        let mut levels = level(gpio_num, input);
+        for (index, gpio_num) in snapshot_pins.to_le_bytes().into_iter().enumerate() {
+            if gpio_num != NO_PIN {
+                levels |= level(gpio_num as usize, input) << (index + 1);
+            }
+        }
+
+        Self { levels }
+    }
+}
+
+#[inline(always)]
+fn level(gpio_num: usize, input: &[u32; BANKS]) -> u8 {
+    (input[gpio_num / 32] >> (gpio_num % 32) & 1) as u8
+}
+
+/// When a pin listens for its level event again after the handler was called
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rearm {
+    /// Once the level cleared
+    OnRelease,
+    /// When [re_arm] is called
+    Manual,
+}
+
+/// Call `handler` from [handle_interrupt] when `event` occurs on `pin`
+///
+/// Level events are re-armed with [Rearm::OnRelease]. Replaces a handler
+/// registered before for the same pin, in either tier.
+pub fn register_handler(pin: &mut impl Pin, event: Event, handler: fn()) {
+    register_handler_with_rearm(pin, event, handler, Rearm::OnRelease);
+}
+
+/// Like [register_handler], with the [Rearm] policy of level events
+pub fn register_handler_with_rearm(pin: &mut impl Pin, event: Event, handler: fn(), rearm: Rearm) {
+    register(
+        pin.number(),
+        event,
+        handler as usize,
+        None,
+        rearm,
+        &REGISTERED,
+        &REGISTERED_IRAM,
+    );
+    pin.listen_with_target(event, InterruptTarget::Cpu, false);
+}
+
+/// Like [register_handler], `handler` is called with a [Snapshot] of the
+/// levels of `pin` and `snapshot`
+pub fn register_snapshot_handler(
+    pin: &mut impl Pin,
+    event: Event,
+    snapshot: SnapshotPins,
+    handler: fn(Snapshot),
+) {
+    register(
+        pin.number(),
+        event,
+        handler as usize,
+        Some(snapshot),
+        Rearm::OnRelease,
+        &REGISTERED,
+        &REGISTERED_IRAM,
+    );
+    pin.listen_with_target(event, InterruptTarget::Cpu, false);
+}
+
+/// Call `handler` from [handle_iram_interrupt] when `event` occurs on `pin`
+///
+/// `handler` must be placed in RAM with `#[ram]`, debug builds panic if it
+/// isn't. Level events are re-armed with [Rearm::OnRelease]. Replaces a
+/// handler registered before for the same pin, in either tier.
+pub fn register_iram_handler(pin: &mut impl Pin, event: Event, handler: fn()) {
+    register_iram_handler_with_rearm(pin, event, handler, Rearm::OnRelease);
+}
+
+/// Like [register_iram_handler], with the [Rearm] policy of level events
+pub fn register_iram_handler_with_rearm(
+    pin: &mut impl Pin,
+    event: Event,
+    handler: fn(),
+    rearm: Rearm,
+) {
+    debug_assert!(
+        crate::IRAM.contains(&(handler as usize)),
+        "IRAM handler of GPIO{} is not placed in RAM",
+        pin.number()
+    );
+
+    register(
+        pin.number(),
+        event,
+        handler as usize,
+        None,
+        rearm,
+        &REGISTERED_IRAM,
+        &REGISTERED,
+    );
+    pin.listen_with_target(event, InterruptTarget::Nmi, false);
+}
+
+/// Like [register_iram_handler], `handler` is called with a [Snapshot] of
+/// the levels of `pin` and `snapshot`
+pub fn register_iram_snapshot_handler(
+    pin: &mut impl Pin,
+    event: Event,
+    snapshot: SnapshotPins,
+    handler: fn(Snapshot),
+) {
+    debug_assert!(
+        crate::IRAM.contains(&(handler as usize)),
+        "IRAM handler of GPIO{} is not placed in RAM",
+        pin.number()
+    );
+
+    register(
+        pin.number(),
+        event,
+        handler as usize,
+        Some(snapshot),
+        Rearm::OnRelease,
+        &REGISTERED_IRAM,
+        &REGISTERED,
+    );
+    pin.listen_with_target(event, InterruptTarget::Nmi, false);
+}
+
+/// Listen for the level event of `pin` again
+///
+/// Needed after each call of a handler registered with [Rearm::Manual], the
+/// handler is called again right away if the level persists. Does nothing
+/// for edge events and while a [Rearm::OnRelease] pin waits for the release.
+pub fn re_arm(pin: &mut impl Pin) {
+    let gpio_num = pin.number() as usize;
+    let bank = gpio_num / 32;
+    let mask = 1 << (gpio_num % 32);
+
+    let level = LEVELS[gpio_num].load(Ordering::Acquire);
+    if level != 0 && RELEASING[bank].load(Ordering::Acquire) & mask == 0 {
+        set_int_type(gpio_num, level);
+    }
+}
+
+/// Stop listening on `pin` and remove its handler
+pub fn unregister_handler(pin: &mut impl Pin) {
+    let gpio_num = pin.number();
+    let bank = gpio_num as usize / 32;
+    let mask = 1 << (gpio_num % 32);
+
+    pin.unlisten();
+    critical_section::with(|_| {
+        REGISTERED[bank].fetch_and(!mask, Ordering::AcqRel);
+        REGISTERED_IRAM[bank].fetch_and(!mask, Ordering::AcqRel);
+        HANDLERS[gpio_num as usize].store(0, Ordering::Release);
+        LEVELS[gpio_num as usize].store(0, Ordering::Release);
+        RELEASING[bank].fetch_and(!mask, Ordering::AcqRel);
+    });
+}
+
+/// `handler` is a `fn(Snapshot)` if `snapshot` is set, a `fn()` otherwise
+fn register(
+    gpio_num: u8,
+    event: Event,
+    handler: usize,
+    snapshot: Option<SnapshotPins>,
+    rearm: Rearm,
+    tier: &[AtomicU32],
+    other: &[AtomicU32],
+) {
+    let bank = gpio_num as usize / 32;
+    let mask = 1 << (gpio_num % 32);
+
+    debug_assert_eq!(handler & SNAPSHOT_HANDLER, 0);
+
+    critical_section::with(|_| {
+        other[bank].fetch_and(!mask, Ordering::AcqRel);
+
+        // the snapshot pins are stored before the handler which uses them
+        let handler = match snapshot {
+            Some(snapshot) => {
+                HANDLERS[gpio_num as usize].store(0, Ordering::Release);
+                SNAPSHOT_PINS[gpio_num as usize].store(snapshot.to_bits(), Ordering::Release);
+                handler | SNAPSHOT_HANDLER
+            }
+            None => handler,
+        };
+        HANDLERS[gpio_num as usize].store(handler, Ordering::Release);
+
+        let level = match event {
+            Event::LowLevel | Event::HighLevel => event as u8,
+            _ => 0,
+        };
+        LEVELS[gpio_num as usize].store(level, Ordering::Release);
+        RELEASING[bank].fetch_and(!mask, Ordering::AcqRel);
+        match rearm {
+            Rearm::OnRelease => REARM_ON_RELEASE[bank].fetch_or(mask, Ordering::AcqRel),
+            Rearm::Manual => REARM_ON_RELEASE[bank].fetch_and(!mask, Ordering::AcqRel),
+        };
+
+        tier[bank].fetch_or(mask, Ordering::AcqRel);
+    });
+}
+
+/// Call the handlers registered with [register_handler] for the pending
+/// events
+///
+/// To be called from the `GPIO` interrupt handler. Placed in RAM with the
+/// `place-isr-in-ram` feature, except for the handling of level events on
+/// the ESP32-C2/C3: it uses atomic read-modify-write operations, which are
+/// emulated from flash.
+#[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+pub fn handle_interrupt() {
+    dispatch(&REGISTERED);
+}
+
+/// Call the handlers registered with [register_iram_handler] for the pending
+/// events
+///
+/// To be called from the `GPIO_NMI` interrupt handler, which should be
+/// placed in RAM with `#[ram]` as well. On the ESP32-C2/C3 level events
+/// use atomic read-modify-write operations, which are emulated from flash,
+/// so only register edge events here on those chips.
+#[procmacros::ram]
+pub fn handle_iram_interrupt() {
+    dispatch(&REGISTERED_IRAM);
+}
+
+#[inline(always)]
+fn dispatch(registered: &[AtomicU32; BANKS]) {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    // the snapshot for the snapshot handlers, read first to keep it close to
+    // the events
+    let input = [
+        gpio.in_.read().bits(),
+        #[cfg(not(any(esp32c2, esp32c3)))]
+        gpio.in1.read().bits(),
+    ];
+
+    // level events are masked before their interrupt is cleared, it would be
+    // raised again right away otherwise
+    let status = gpio.status.read().bits() & registered[0].load(Ordering::Acquire);
+    if status != 0 {
+        let calls = mask_levels(0, status);
+        gpio.status_w1tc.write(|w| unsafe { w.bits(status) });
+        call_bank(0, calls, &input);
+    }
+
+    #[cfg(not(any(esp32c2, esp32c3)))]
+    {
+        let status = gpio.status1.read().bits() & registered[1].load(Ordering::Acquire);
+        if status != 0 {
+            let calls = mask_levels(1, status);
+            gpio.status1_w1tc.write(|w| unsafe { w.bits(status) });
+            call_bank(1, calls, &input);
+        }
+    }
+}
+
+/// Mask the level events of the pending pins in `status` of `bank`, returns
+/// the pins whose handler has to be called
+#[inline(always)]
+fn mask_levels(bank: usize, status: u32) -> u32 {
+    let mut pending = status;
+    let mut calls = status;
+    while pending != 0 {
+        let bit = pending.trailing_zeros();
+        pending &= !(1 << bit);
+
+        if !mask_level(bank * 32 + bit as usize) {
+            calls &= !(1 << bit);
+        }
+    }
+
+    calls
+}
+
+#[inline(always)]
+fn call_bank(bank: usize, mut calls: u32, input: &[u32; BANKS]) {
+    while calls != 0 {
+        let bit = calls.trailing_zeros();
+        calls &= !(1 << bit);
+
+        let gpio_num = bank * 32 + bit as usize;
+        let handler = HANDLERS[gpio_num].load(Ordering::Acquire);
+        if handler == 0 {
+            continue;
+        }
+
+        if handler & SNAPSHOT_HANDLER != 0 {
+            let snapshot_pins = SNAPSHOT_PINS[gpio_num].load(Ordering::Acquire);
+            let handler: fn(Snapshot) =
+                unsafe { core::mem::transmute(handler & !SNAPSHOT_HANDLER) };
+            handler(Snapshot::capture(gpio_num, snapshot_pins, input));
+        } else {
+            let handler: fn() = unsafe { core::mem::transmute(handler) };
+            handler();
+        }
+    }
+}
+
+/// Mask the level event of `gpio_num` before its handler is called
+///
+/// Returns `false` if the interrupt was the release of the level, which only
+/// re-arms the pin.
+#[inline(always)]
+fn mask_level(gpio_num: usize) -> bool {
+    let level = LEVELS[gpio_num].load(Ordering::Acquire);
+    if level == 0 {
+        return true;
+    }
+
+    let bank = gpio_num / 32;
+    let mask = 1 << (gpio_num % 32);
+
+    if RELEASING[bank].load(Ordering::Acquire) & mask != 0 {
+        RELEASING[bank].fetch_and(!mask, Ordering::AcqRel);
+        set_int_type(gpio_num, level);
+        return false;
+    }
+
+    if REARM_ON_RELEASE[bank].load(Ordering::Acquire) & mask != 0 {
+        // listen for the opposite level, `LowLevel` and `HighLevel` only
+        // differ in bit 0
+        RELEASING[bank].fetch_or(mask, Ordering::AcqRel);
+        set_int_type(gpio_num, level ^ 1);
+    } else {
+        set_int_type(gpio_num, INT_TYPE_DISABLED);
+    }
+
+    true
+}
+
+#[inline(always)]
+fn set_int_type(gpio_num: usize, int_type: u8) {
+    let gpio = unsafe { &*GPIO::PTR };
+    gpio.pin[gpio_num].modify(|_, w| unsafe { w.int_type().bits(int_type) });
+}
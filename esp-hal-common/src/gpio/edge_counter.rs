@@ -0,0 +1,241 @@
+//! Software edge counter
+//!
+//! The ESP32-C2 and ESP32-C3 have no pulse counter peripheral. As a fallback
+//! edges on any input pin can be counted in the `GPIO` interrupt:
+//!
+//! ```no_run
+//! let mut sensor = io.pins.gpio4.into_pull_up_input();
+//! sensor.enable_edge_counter(Event::RisingEdge).unwrap();
+//!
+//! #[interrupt]
+//! fn GPIO() {
+//!     edge_counter::handle_interrupt();
+//! }
+//!
+//! let pulses = sensor.edge_count();
+//! ```
+//!
+//! [handle_interrupt] counts and acknowledges the edges of all pins with an
+//! enabled counter and leaves the interrupts of all other pins pending, so it
+//! can share the `GPIO` interrupt with other handlers. It is placed in RAM.
+//!
+//! The tables are only ever loaded and stored, every read-modify-write
+//! (including the counting in [handle_interrupt]) runs in a critical
+//! section. The ESP32-C2 and ESP32-C3 don't implement the atomic extension,
+//! read-modify-write atomics would depend on trap emulation which is far
+//! too slow for an interrupt handler. Reading a count doesn't need a
+//! critical section.
+//!
+//! ## Maximum pulse rate
+//!
+//! Every edge costs one interrupt. Entering and leaving the interrupt and
+//! counting takes in the order of 1-2 µs on the ESP32-C3 at 160 MHz (more on
+//! the ESP32-C2 and when the interrupt is shared with other handlers). Edges
+//! which occur while the interrupt of the pin is still pending are merged
+//! into one and get lost, so keep the pulse rate well below ~100 kHz (in
+//! total for all counted pins) and prefer the PCNT peripheral where the chip
+//! has one.
+//!
+//! The [FrequencyCounter](super::frequency_counter::FrequencyCounter) builds
+//! on the edge counter and additionally records the time of the edges.
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use super::Event;
+use crate::pac::GPIO;
+
+#[cfg(any(esp32c2, esp32c3))]
+const BANKS: usize = 1;
+#[cfg(not(any(esp32c2, esp32c3)))]
+const BANKS: usize = 2;
+
+/// Edge counter errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// Only [Event::RisingEdge], [Event::FallingEdge] and
+    /// [Event::AnyEdge] can be counted
+    LevelEvent,
+}
+
+/// Behavior of a counter when it reaches `u32::MAX`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Continue counting from 0
+    Wrap,
+    /// Stop counting at `u32::MAX`
+    Saturate,
+}
+
+#[allow(clippy::declare_interior_mutable_const)]
+const ZERO: AtomicU32 = AtomicU32::new(0);
+
+static COUNTS: [AtomicU32; 32 * BANKS] = [ZERO; 32 * BANKS];
+static ENABLED: [AtomicU32; BANKS] = [ZERO; BANKS];
+static SATURATE: [AtomicU32; BANKS] = [ZERO; BANKS];
+static TIMESTAMPED: [AtomicU32; BANKS] = [ZERO; BANKS];
+static FIRST_EDGE: [AtomicU32; 32 * BANKS] = [ZERO; 32 * BANKS];
+static LAST_EDGE: [AtomicU32; 32 * BANKS] = [ZERO; 32 * BANKS];
+
+/// Check that the event is an edge
+pub(crate) const fn check_event(event: Event) -> Result<(), Error> {
+    match event {
+        Event::LowLevel | Event::HighLevel => Err(Error::LevelEvent),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn enable(gpio_num: u8, overflow: Overflow) {
+    let bank = gpio_num as usize / 32;
+    let mask = 1 << (gpio_num % 32);
+
+    critical_section::with(|_| {
+        COUNTS[gpio_num as usize].store(0, Ordering::Relaxed);
+        let saturate = SATURATE[bank].load(Ordering::Relaxed);
+        SATURATE[bank].store(
+            match overflow {
+                Overflow::Wrap => saturate & !mask,
+                Overflow::Saturate => saturate | mask,
+            },
+            Ordering::Relaxed,
+        );
+        let enabled = ENABLED[bank].load(Ordering::Relaxed);
+        ENABLED[bank].store(enabled | mask, Ordering::Release);
+    });
+}
+
+pub(crate) fn disable(gpio_num: u8) {
+    let bank = gpio_num as usize / 32;
+    let mask = 1 << (gpio_num % 32);
+
+    critical_section::with(|_| {
+        let enabled = ENABLED[bank].load(Ordering::Relaxed);
+        ENABLED[bank].store(enabled & !mask, Ordering::Release);
+    });
+}
+
+pub(crate) fn count(gpio_num: u8) -> u32 {
+    COUNTS[gpio_num as usize].load(Ordering::Relaxed)
+}
+
+/// Set the count to 0, in a critical section so an edge counted at the same
+/// time isn't lost or counted after the reset
+pub(crate) fn reset(gpio_num: u8) {
+    critical_section::with(|_| COUNTS[gpio_num as usize].store(0, Ordering::Relaxed));
+}
+
+/// Record the time of the first edge after a [reset] and of the last edge,
+/// in the low 32 bits of [crate::time::now]
+pub(crate) fn enable_timestamps(gpio_num: u8, enable: bool) {
+    let bank = gpio_num as usize / 32;
+    let mask = 1 << (gpio_num % 32);
+
+    critical_section::with(|_| {
+        let timestamped = TIMESTAMPED[bank].load(Ordering::Relaxed);
+        TIMESTAMPED[bank].store(
+            if enable {
+                timestamped | mask
+            } else {
+                timestamped & !mask
+            },
+            Ordering::Relaxed,
+        );
+    });
+}
+
+/// The times of the first and the last edge, only valid once the count is
+/// at least 1
+pub(crate) fn timestamps(gpio_num: u8) -> (u32, u32) {
+    (
+        FIRST_EDGE[gpio_num as usize].load(Ordering::Relaxed),
+        LAST_EDGE[gpio_num as usize].load(Ordering::Relaxed),
+    )
+}
+
+/// Count the pending edges of all pins with an enabled edge counter
+///
+/// To be called from the `GPIO` interrupt handler. Only the interrupts of
+/// the counted pins are cleared.
+#[procmacros::ram]
+pub fn handle_interrupt() {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    let status = gpio.status.read().bits() & ENABLED[0].load(Ordering::Acquire);
+    if status != 0 {
+        gpio.status_w1tc.write(|w| unsafe { w.bits(status) });
+        count_bank(0, status);
+    }
+
+    #[cfg(not(any(esp32c2, esp32c3)))]
+    {
+        let status = gpio.status1.read().bits() & ENABLED[1].load(Ordering::Acquire);
+        if status != 0 {
+            gpio.status1_w1tc.write(|w| unsafe { w.bits(status) });
+            count_bank(1, status);
+        }
+    }
+}
+
+#[procmacros::ram]
+fn count_bank(bank: usize, mut status: u32) {
+    critical_section::with(|_| {
+        let saturate = SATURATE[bank].load(Ordering::Relaxed);
+        let timestamped = TIMESTAMPED[bank].load(Ordering::Relaxed);
+
+        // all edges handled by this call share one timestamp
+        let now = if status & timestamped != 0 {
+            crate::time::now() as u32
+        } else {
+            0
+        };
+
+        while status != 0 {
+            let bit = status.trailing_zeros();
+            status &= !(1 << bit);
+
+            let index = bank * 32 + bit as usize;
+            let previous = COUNTS[index].load(Ordering::Relaxed);
+            COUNTS[index].store(
+                increment(previous, saturate & (1 << bit) != 0),
+                Ordering::Relaxed,
+            );
+
+            if timestamped & (1 << bit) != 0 {
+                if previous == 0 {
+                    FIRST_EDGE[index].store(now, Ordering::Relaxed);
+                }
+                LAST_EDGE[index].store(now, Ordering::Relaxed);
+            }
+        }
+    });
+}
+
+#[inline(always)]
+const fn increment(count: u32, saturate: bool) -> u32 {
+    if saturate {
+        count.saturating_add(1)
+    } else {
+        count.wrapping_add(1)
+    }
+}
+
+const _: () = {
+    assert!(increment(0, false) == 1);
+    assert!(increment(u32::MAX - 1, false) == u32::MAX);
+    assert!(increment(u32::MAX, false) == 0);
+
+    assert!(increment(0, true) == 1);
+    assert!(increment(u32::MAX - 1, true) == u32::MAX);
+    assert!(increment(u32::MAX, true) == u32::MAX);
+
+    assert!(check_event(Event::RisingEdge).is_ok());
+    assert!(check_event(Event::FallingEdge).is_ok());
+    assert!(check_event(Event::AnyEdge).is_ok());
+    assert!(matches!(
+        check_event(Event::LowLevel),
+        Err(Error::LevelEvent)
+    ));
+    assert!(matches!(
+        check_event(Event::HighLevel),
+        Err(Error::LevelEvent)
+    ));
+};
@@ -6,10 +6,14 @@ use crate::{
     AlternateFunction,
     Bank0GpioRegisterAccess,
     Bank1GpioRegisterAccess,
+    Cpu,
     GpioPin,
     InputOnlyAnalogPinType,
     InputOutputAnalogPinType,
     InputOutputPinType,
+    InterruptTarget,
+    PinCapabilities,
+    PinType,
     Unknown,
 };
 
@@ -22,6 +26,36 @@ pub const ZERO_INPUT: u8 = 0x30;
 
 pub(crate) const GPIO_FUNCTION: AlternateFunction = AlternateFunction::Function2;
 
+/// The flash pads of the ESP32, the PSRAM pads depend on the module
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashPad {
+    /// SPICLK (SD_CLK), GPIO6
+    Clk,
+    /// SPIQ (SD_DATA0), GPIO7
+    Q,
+    /// SPID (SD_DATA1), GPIO8
+    D,
+    /// SPIHD (SD_DATA2), GPIO9
+    Hd,
+    /// SPIWP (SD_DATA3), GPIO10
+    Wp,
+    /// SPICS0 (SD_CMD), flash chip select, GPIO11
+    Cs0,
+}
+
+impl FlashPad {
+    pub(crate) const fn gpio_num(self) -> u8 {
+        match self {
+            FlashPad::Clk => 6,
+            FlashPad::Q => 7,
+            FlashPad::D => 8,
+            FlashPad::Hd => 9,
+            FlashPad::Wp => 10,
+            FlashPad::Cs0 => 11,
+        }
+    }
+}
+
 pub(crate) fn get_io_mux_reg(gpio_num: u8) -> &'static crate::pac::io_mux::GPIO0 {
     unsafe {
         let iomux = &*crate::pac::IO_MUX::PTR;
@@ -68,13 +102,56 @@ pub(crate) fn get_io_mux_reg(gpio_num: u8) -> &'static crate::pac::io_mux::GPIO0
     }
 }
 
-pub(crate) fn gpio_intr_enable(int_enable: bool, nmi_enable: bool) -> u8 {
-    int_enable as u8
-        | ((nmi_enable as u8) << 1)
-        | (int_enable as u8) << 2
-        | ((nmi_enable as u8) << 3)
+/// The `int_ena` bits of the pin register for `target`, the same target in
+/// bits 0 and 1 and in bits 2 and 3
+pub(crate) const fn gpio_intr_enable(target: InterruptTarget) -> u8 {
+    let bits = target.interrupt() as u8 | ((target.nmi() as u8) << 1);
+    bits | bits << 2
+}
+
+/// The target selected by the `int_ena` bits of the pin register, in either
+/// pair of bits
+pub(crate) const fn gpio_intr_target(int_ena: u8) -> InterruptTarget {
+    InterruptTarget::from_flags(int_ena & 0b0101 != 0, int_ena & 0b1010 != 0)
+}
+
+// bits 0 and 1 enable the interrupt and NMI of the APP core, bits 2 and 3 the
+// ones of the PRO core
+pub(crate) const fn gpio_intr_enable_on_core(core: Cpu, target: InterruptTarget) -> u8 {
+    let bits = target.interrupt() as u8 | ((target.nmi() as u8) << 1);
+    match core {
+        Cpu::AppCpu => bits,
+        Cpu::ProCpu => bits << 2,
+    }
 }
 
+const _: () = {
+    assert!(gpio_intr_enable(InterruptTarget::None) == 0b0000);
+    assert!(gpio_intr_enable(InterruptTarget::Cpu) == 0b0101);
+    assert!(gpio_intr_enable(InterruptTarget::Nmi) == 0b1010);
+    assert!(gpio_intr_enable(InterruptTarget::CpuAndNmi) == 0b1111);
+
+    assert!(gpio_intr_enable_on_core(Cpu::AppCpu, InterruptTarget::Cpu) == 0b0001);
+    assert!(gpio_intr_enable_on_core(Cpu::AppCpu, InterruptTarget::CpuAndNmi) == 0b0011);
+    assert!(gpio_intr_enable_on_core(Cpu::ProCpu, InterruptTarget::Cpu) == 0b0100);
+    assert!(gpio_intr_enable_on_core(Cpu::ProCpu, InterruptTarget::Nmi) == 0b1000);
+
+    assert!(matches!(gpio_intr_target(0b0000), InterruptTarget::None));
+    assert!(matches!(gpio_intr_target(0b0101), InterruptTarget::Cpu));
+    assert!(matches!(gpio_intr_target(0b1010), InterruptTarget::Nmi));
+    assert!(matches!(
+        gpio_intr_target(0b1111),
+        InterruptTarget::CpuAndNmi
+    ));
+    // delivered to one core only
+    assert!(matches!(gpio_intr_target(0b0001), InterruptTarget::Cpu));
+    assert!(matches!(gpio_intr_target(0b0100), InterruptTarget::Cpu));
+    assert!(matches!(
+        gpio_intr_target(0b1100),
+        InterruptTarget::CpuAndNmi
+    ));
+};
+
 /// Peripheral input signals for the GPIO mux
 #[allow(non_camel_case_types)]
 #[derive(PartialEq, Copy, Clone)]
@@ -654,11 +731,13 @@ pub(crate) fn errata36(pin_num: u8, pull_up: bool, pull_down: bool) {
 }
 
 crate::gpio::gpio! {
+    strapping: 0 2 5 12 15;
+
     (0, 0, InputOutputAnalog (5 => EMAC_TX_CLK) (1 => CLK_OUT1))
     (1, 0, InputOutput (5 => EMAC_RXD2) (0 => U0TXD 1 => CLK_OUT3))
     (2, 0, InputOutputAnalog (1 => HSPIWP 3 => HS2_DATA0 4 => SD_DATA0) (3 => HS2_DATA0 4 => SD_DATA0))
     (3, 0, InputOutput (0 => U0RXD) (1 => CLK_OUT2))
-    (4, 0, InputOutput (1 => HSPIHD 3 => HS2_DATA1 4 => SD_DATA1 5 => EMAC_TX_ER) (3 => HS2_DATA1 4 => SD_DATA1))
+    (4, 0, InputOutputAnalog (1 => HSPIHD 3 => HS2_DATA1 4 => SD_DATA1 5 => EMAC_TX_ER) (3 => HS2_DATA1 4 => SD_DATA1))
     (5, 0, InputOutput (1 => VSPICS0 3 => HS1_DATA6 5 => EMAC_RX_CLK) (3 => HS1_DATA6))
     (6, 0, InputOutput (4 => U1CTS) (0 => SD_CLK 1 => SPICLK 3 => HS1_CLK))
     (7, 0, InputOutput (0 => SD_DATA0 1 => SPIQ 3 => HS1_DATA0) (0 => SD_DATA0 1 => SPIQ 3 => HS1_DATA0 4 => U2RTS))
@@ -712,3 +791,39 @@ crate::gpio::analog! {
      (14, 16, touch_pad6,           mux_sel,        fun_sel,        fun_ie, rue,       rde      )
      (27, 17, touch_pad7,           mux_sel,        fun_sel,        fun_ie, rue,       rde      )
 }
+
+// T0 to T9
+pub(crate) const fn is_touch_pin(pin: u8) -> bool {
+    matches!(pin, 0 | 2 | 4 | 12..=15 | 27 | 32 | 33)
+}
+
+// Sanity checks of the capability table
+const _: () = {
+    assert!(NUM_PINS == 36);
+    assert!(!pin_exists(28));
+    assert!(pin_capabilities(31).is_none());
+    assert!(match pin_capabilities(34) {
+        Some(caps) => caps.contains(PinCapabilities::INPUT_ONLY.union(PinCapabilities::ANALOG)),
+        None => false,
+    });
+    assert!(match pin_capabilities(4) {
+        Some(caps) => caps.contains(PinCapabilities::RTC.union(PinCapabilities::TOUCH)),
+        None => false,
+    });
+    assert!(match pin_capabilities(5) {
+        Some(caps) => caps.bits() == 0,
+        None => false,
+    });
+    assert!(matches!(
+        crate::gpio::pull_register(34, false),
+        crate::gpio::PullRegister::None
+    ));
+    assert!(matches!(
+        crate::gpio::pull_register(39, true),
+        crate::gpio::PullRegister::None
+    ));
+    assert!(matches!(
+        crate::gpio::pull_register(33, true),
+        crate::gpio::PullRegister::RtcIo
+    ));
+};
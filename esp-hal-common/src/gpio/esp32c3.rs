@@ -8,6 +8,9 @@ use crate::{
     GpioPin,
     InputOutputAnalogPinType,
     InputOutputPinType,
+    InterruptTarget,
+    PinCapabilities,
+    PinType,
     Unknown,
 };
 
@@ -20,14 +23,63 @@ pub const ZERO_INPUT: u8 = 0x1f;
 
 pub(crate) const GPIO_FUNCTION: AlternateFunction = AlternateFunction::Function1;
 
+/// The flash pads of the ESP32-C3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashPad {
+    /// SPIHD, GPIO12
+    Hd,
+    /// SPIWP, GPIO13
+    Wp,
+    /// SPICS0, flash chip select, GPIO14
+    Cs0,
+    /// SPICLK, GPIO15
+    Clk,
+    /// SPID, GPIO16
+    D,
+    /// SPIQ, GPIO17
+    Q,
+}
+
+impl FlashPad {
+    pub(crate) const fn gpio_num(self) -> u8 {
+        match self {
+            FlashPad::Hd => 12,
+            FlashPad::Wp => 13,
+            FlashPad::Cs0 => 14,
+            FlashPad::Clk => 15,
+            FlashPad::D => 16,
+            FlashPad::Q => 17,
+        }
+    }
+}
+
 pub(crate) const fn get_io_mux_reg(gpio_num: u8) -> &'static crate::pac::io_mux::GPIO {
     unsafe { &(&*crate::pac::IO_MUX::PTR).gpio[gpio_num as usize] }
 }
 
-pub(crate) fn gpio_intr_enable(int_enable: bool, nmi_enable: bool) -> u8 {
-    int_enable as u8 | ((nmi_enable as u8) << 1)
+/// The `int_ena` bits of the pin register for `target`: bit 0 enables the
+/// interrupt, bit 1 the NMI
+pub(crate) const fn gpio_intr_enable(target: InterruptTarget) -> u8 {
+    target.interrupt() as u8 | ((target.nmi() as u8) << 1)
+}
+
+/// The target selected by the `int_ena` bits of the pin register
+pub(crate) const fn gpio_intr_target(int_ena: u8) -> InterruptTarget {
+    InterruptTarget::from_flags(int_ena & 0b01 != 0, int_ena & 0b10 != 0)
 }
 
+const _: () = {
+    assert!(gpio_intr_enable(InterruptTarget::None) == 0b00);
+    assert!(gpio_intr_enable(InterruptTarget::Cpu) == 0b01);
+    assert!(gpio_intr_enable(InterruptTarget::Nmi) == 0b10);
+    assert!(gpio_intr_enable(InterruptTarget::CpuAndNmi) == 0b11);
+
+    assert!(matches!(gpio_intr_target(0b00), InterruptTarget::None));
+    assert!(matches!(gpio_intr_target(0b01), InterruptTarget::Cpu));
+    assert!(matches!(gpio_intr_target(0b10), InterruptTarget::Nmi));
+    assert!(matches!(gpio_intr_target(0b11), InterruptTarget::CpuAndNmi));
+};
+
 /// Peripheral input signals for the GPIO mux
 #[allow(non_camel_case_types)]
 #[derive(Clone, Copy, PartialEq)]
@@ -161,36 +213,28 @@ pub enum OutputSignal {
     GPIO             = 128,
 }
 
-crate::gpio::gpio! {
-    (0, 0, InputOutputAnalog)
-    (1, 0, InputOutputAnalog)
-    (2, 0, InputOutputAnalog (2 => FSPIQ) (2 => FSPIQ))
-    (3, 0, InputOutputAnalog)
-    (4, 0, InputOutputAnalog (2 => FSPIHD) (0 => USB_JTAG_TMS 2 => FSPIHD))
-    (5, 0, InputOutputAnalog (2 => FSPIWP) (0 => USB_JTAG_TDI 2 => FSPIWP))
-    (6, 0, InputOutput (2 => FSPICLK) (0 => USB_JTAG_TCK 2 => FSPICLK_MUX))
-    (7, 0, InputOutput (2 => FSPID) (0 => USB_JTAG_TDO 2 => FSPID))
-    (8, 0, InputOutput)
-    (9, 0, InputOutput)
-    (10, 0, InputOutput (2 => FSPICS0) (2 => FSPICS0))
-    (11, 0, InputOutput)
-    (12, 0, InputOutput (0 => SPIHD) (0 => SPIHD))
-    (13, 0, InputOutput (0 => SPIWP) (0 => SPIWP))
-    (14, 0, InputOutput () (0 => SPICS0))
-    (15, 0, InputOutput () (0 => SPICLK_MUX))
-    (16, 0, InputOutput (0 => SPID) (0 => SPID))
-    (17, 0, InputOutput (0 => SPIQ) (0 => SPIQ))
-    (18, 0, InputOutput)
-    (19, 0, InputOutput)
-    (20, 0, InputOutput (0 => U0RXD) ())
-    (21, 0, InputOutput () (0 => U0TXD))
+// `gpio!` and `analog!` tables generated from `pins/esp32c3.csv` by build.rs
+include!(concat!(env!("OUT_DIR"), "/pins.rs"));
+
+pub(crate) const fn is_touch_pin(_pin: u8) -> bool {
+    false
 }
 
-crate::gpio::analog! {
-    0
-    1
-    2
-    3
-    4
-    5
+// RTC_GPIO0 to RTC_GPIO5
+pub(crate) const fn is_rtc_pin(pin: u8) -> bool {
+    matches!(pin, 0..=5)
 }
+
+// Sanity checks of the capability table
+const _: () = {
+    assert!(NUM_PINS == 22);
+    assert!(!pin_exists(22));
+    assert!(match pin_capabilities(0) {
+        Some(caps) => caps.contains(PinCapabilities::ANALOG.union(PinCapabilities::RTC)),
+        None => false,
+    });
+    assert!(match pin_capabilities(6) {
+        Some(caps) => caps.bits() == 0,
+        None => false,
+    });
+};
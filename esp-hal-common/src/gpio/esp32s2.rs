@@ -9,6 +9,9 @@ use crate::{
     GpioPin,
     InputOutputAnalogPinType,
     InputOutputPinType,
+    InterruptTarget,
+    PinCapabilities,
+    PinType,
     Unknown,
 };
 
@@ -21,6 +24,39 @@ pub const ZERO_INPUT: u8 = 0x3c;
 
 pub(crate) const GPIO_FUNCTION: AlternateFunction = AlternateFunction::Function1;
 
+/// The flash and PSRAM pads of the ESP32-S2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashPad {
+    /// SPICS1, PSRAM chip select, GPIO26
+    Cs1,
+    /// SPIHD, GPIO27
+    Hd,
+    /// SPIWP, GPIO28
+    Wp,
+    /// SPICS0, flash chip select, GPIO29
+    Cs0,
+    /// SPICLK, GPIO30
+    Clk,
+    /// SPIQ, GPIO31
+    Q,
+    /// SPID, GPIO32
+    D,
+}
+
+impl FlashPad {
+    pub(crate) const fn gpio_num(self) -> u8 {
+        match self {
+            FlashPad::Cs1 => 26,
+            FlashPad::Hd => 27,
+            FlashPad::Wp => 28,
+            FlashPad::Cs0 => 29,
+            FlashPad::Clk => 30,
+            FlashPad::Q => 31,
+            FlashPad::D => 32,
+        }
+    }
+}
+
 pub(crate) const fn get_io_mux_reg(gpio_num: u8) -> &'static crate::pac::io_mux::GPIO0 {
     unsafe {
         let iomux = &*crate::pac::IO_MUX::PTR;
@@ -70,13 +106,34 @@ pub(crate) const fn get_io_mux_reg(gpio_num: u8) -> &'static crate::pac::io_mux:
     }
 }
 
-pub(crate) fn gpio_intr_enable(int_enable: bool, nmi_enable: bool) -> u8 {
-    int_enable as u8
-        | ((nmi_enable as u8) << 1)
-        | (int_enable as u8) << 2
-        | ((nmi_enable as u8) << 3)
+/// The `int_ena` bits of the pin register for `target`, the same target in
+/// bits 0 and 1 and in bits 2 and 3
+pub(crate) const fn gpio_intr_enable(target: InterruptTarget) -> u8 {
+    let bits = target.interrupt() as u8 | ((target.nmi() as u8) << 1);
+    bits | bits << 2
+}
+
+/// The target selected by the `int_ena` bits of the pin register, in either
+/// pair of bits
+pub(crate) const fn gpio_intr_target(int_ena: u8) -> InterruptTarget {
+    InterruptTarget::from_flags(int_ena & 0b0101 != 0, int_ena & 0b1010 != 0)
 }
 
+const _: () = {
+    assert!(gpio_intr_enable(InterruptTarget::None) == 0b0000);
+    assert!(gpio_intr_enable(InterruptTarget::Cpu) == 0b0101);
+    assert!(gpio_intr_enable(InterruptTarget::Nmi) == 0b1010);
+    assert!(gpio_intr_enable(InterruptTarget::CpuAndNmi) == 0b1111);
+
+    assert!(matches!(gpio_intr_target(0b0000), InterruptTarget::None));
+    assert!(matches!(gpio_intr_target(0b0101), InterruptTarget::Cpu));
+    assert!(matches!(gpio_intr_target(0b1010), InterruptTarget::Nmi));
+    assert!(matches!(
+        gpio_intr_target(0b1111),
+        InterruptTarget::CpuAndNmi
+    ));
+};
+
 /// Peripheral input signals for the GPIO mux
 #[allow(non_camel_case_types)]
 #[derive(PartialEq, Copy, Clone)]
@@ -242,6 +299,8 @@ pub enum OutputSignal {
 }
 
 crate::gpio::gpio! {
+    strapping: 0 45 46;
+
     (0, 0, InputOutputAnalog)
     (1, 0, InputOutputAnalog)
     (2, 0, InputOutputAnalog)
@@ -362,6 +421,29 @@ crate::gpio::analog! {
     (21, 21,  rtc_pad21,      mux_sel,             fun_sel,             fun_ie,             rue,             rde)
 }
 
+// TOUCH1 to TOUCH14, TOUCH0 is internal
+pub(crate) const fn is_touch_pin(pin: u8) -> bool {
+    matches!(pin, 1..=14)
+}
+
+// Sanity checks of the capability table
+const _: () = {
+    assert!(NUM_PINS == 43);
+    assert!(!pin_exists(22));
+    assert!(match pin_capabilities(1) {
+        Some(caps) => caps.contains(
+            PinCapabilities::ANALOG
+                .union(PinCapabilities::RTC)
+                .union(PinCapabilities::TOUCH)
+        ),
+        None => false,
+    });
+    assert!(match pin_capabilities(0) {
+        Some(caps) => !caps.contains(PinCapabilities::TOUCH),
+        None => false,
+    });
+};
+
 // implement marker traits on USB pins
 impl<T> crate::otg_fs::UsbSel for Gpio18<T> {}
 impl<T> crate::otg_fs::UsbDp for Gpio19<T> {}
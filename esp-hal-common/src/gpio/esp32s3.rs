@@ -9,6 +9,9 @@ use crate::{
     GpioPin,
     InputOutputAnalogPinType,
     InputOutputPinType,
+    InterruptTarget,
+    PinCapabilities,
+    PinType,
     Unknown,
 };
 
@@ -21,14 +24,67 @@ pub const ZERO_INPUT: u8 = 0x3c;
 
 pub(crate) const GPIO_FUNCTION: AlternateFunction = AlternateFunction::Function1;
 
+/// The flash and PSRAM pads of the ESP32-S3, without the additional
+/// pads of octal flash and PSRAM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlashPad {
+    /// SPICS1, PSRAM chip select, GPIO26
+    Cs1,
+    /// SPIHD, GPIO27
+    Hd,
+    /// SPIWP, GPIO28
+    Wp,
+    /// SPICS0, flash chip select, GPIO29
+    Cs0,
+    /// SPICLK, GPIO30
+    Clk,
+    /// SPIQ, GPIO31
+    Q,
+    /// SPID, GPIO32
+    D,
+}
+
+impl FlashPad {
+    pub(crate) const fn gpio_num(self) -> u8 {
+        match self {
+            FlashPad::Cs1 => 26,
+            FlashPad::Hd => 27,
+            FlashPad::Wp => 28,
+            FlashPad::Cs0 => 29,
+            FlashPad::Clk => 30,
+            FlashPad::Q => 31,
+            FlashPad::D => 32,
+        }
+    }
+}
+
 pub(crate) const fn get_io_mux_reg(gpio_num: u8) -> &'static crate::pac::io_mux::GPIO {
     unsafe { &(&*crate::pac::IO_MUX::PTR).gpio[gpio_num as usize] }
 }
 
-pub(crate) fn gpio_intr_enable(int_enable: bool, nmi_enable: bool) -> u8 {
-    int_enable as u8 | ((nmi_enable as u8) << 1)
+/// The `int_ena` bits of the pin register for `target`: bit 0 enables the
+/// interrupt, bit 1 the NMI
+pub(crate) const fn gpio_intr_enable(target: InterruptTarget) -> u8 {
+    target.interrupt() as u8 | ((target.nmi() as u8) << 1)
 }
 
+/// The target selected by the `int_ena` bits of the pin register
+pub(crate) const fn gpio_intr_target(int_ena: u8) -> InterruptTarget {
+    InterruptTarget::from_flags(int_ena & 0b01 != 0, int_ena & 0b10 != 0)
+}
+
+const _: () = {
+    assert!(gpio_intr_enable(InterruptTarget::None) == 0b00);
+    assert!(gpio_intr_enable(InterruptTarget::Cpu) == 0b01);
+    assert!(gpio_intr_enable(InterruptTarget::Nmi) == 0b10);
+    assert!(gpio_intr_enable(InterruptTarget::CpuAndNmi) == 0b11);
+
+    assert!(matches!(gpio_intr_target(0b00), InterruptTarget::None));
+    assert!(matches!(gpio_intr_target(0b01), InterruptTarget::Cpu));
+    assert!(matches!(gpio_intr_target(0b10), InterruptTarget::Nmi));
+    assert!(matches!(gpio_intr_target(0b11), InterruptTarget::CpuAndNmi));
+};
+
 /// Peripheral input signals for the GPIO mux
 #[allow(non_camel_case_types)]
 #[derive(PartialEq, Copy, Clone)]
@@ -63,6 +119,22 @@ pub enum InputSignal {
     I2S1I_SD          = 30,
     I2S1I_BCK         = 31,
     I2S1I_WS          = 32,
+    PCNT_SIG_CH0_0    = 33,
+    PCNT_SIG_CH1_0    = 34,
+    PCNT_CTRL_CH0_0   = 35,
+    PCNT_CTRL_CH1_0   = 36,
+    PCNT_SIG_CH0_1    = 37,
+    PCNT_SIG_CH1_1    = 38,
+    PCNT_CTRL_CH0_1   = 39,
+    PCNT_CTRL_CH1_1   = 40,
+    PCNT_SIG_CH0_2    = 41,
+    PCNT_SIG_CH1_2    = 42,
+    PCNT_CTRL_CH0_2   = 43,
+    PCNT_CTRL_CH1_2   = 44,
+    PCNT_SIG_CH0_3    = 45,
+    PCNT_SIG_CH1_3    = 46,
+    PCNT_CTRL_CH0_3   = 47,
+    PCNT_CTRL_CH1_3   = 48,
     I2S0I_SD1         = 51,
     I2S0I_SD2         = 52,
     I2S0I_SD3         = 53,
@@ -243,6 +315,8 @@ pub enum OutputSignal {
 }
 
 crate::gpio::gpio! {
+    strapping: 0 3 45 46;
+
     (0, 0, InputOutputAnalog)
     (1, 0, InputOutputAnalog)
     (2, 0, InputOutputAnalog)
@@ -315,6 +389,29 @@ crate::gpio::analog! {
      (21, 21,  rtc_pad21,      mux_sel,      fun_sel,      fun_ie,              rue,       rde)
 }
 
+// TOUCH1 to TOUCH14, TOUCH0 is internal
+pub(crate) const fn is_touch_pin(pin: u8) -> bool {
+    matches!(pin, 1..=14)
+}
+
+// Sanity checks of the capability table
+const _: () = {
+    assert!(NUM_PINS == 45);
+    assert!(!pin_exists(22));
+    assert!(match pin_capabilities(1) {
+        Some(caps) => caps.contains(
+            PinCapabilities::ANALOG
+                .union(PinCapabilities::RTC)
+                .union(PinCapabilities::TOUCH)
+        ),
+        None => false,
+    });
+    assert!(match pin_capabilities(0) {
+        Some(caps) => !caps.contains(PinCapabilities::TOUCH),
+        None => false,
+    });
+};
+
 // implement marker traits on USB pins
 impl<T> crate::otg_fs::UsbSel for Gpio18<T> {}
 impl<T> crate::otg_fs::UsbDp for Gpio19<T> {}
@@ -0,0 +1,149 @@
+//! Frequency measurement on an input pin
+//!
+//! The [FrequencyCounter] measures the frequency of a signal like a fan
+//! tachometer or a flow sensor:
+//!
+//! ```no_run
+//! let mut tach = FrequencyCounter::new(io.pins.gpio4.into_pull_up_input(), 1u64.secs());
+//!
+//! #[interrupt]
+//! fn GPIO() {
+//!     edge_counter::handle_interrupt();
+//! }
+//!
+//! let rpm = tach.measure().to_Hz() * 60 / 2;
+//! ```
+//!
+//! None of the chips' PCNT or RMT receive channels have a driver yet, the
+//! rising edges are counted by the software [edge counter](super::edge_counter)
+//! on all chips, which also records the time of the first and the last edge
+//! of the gate time.
+//!
+//! The frequency is derived from the number of periods between these edges
+//! and the time they took (reciprocal counting). At high frequencies this
+//! equals counting the edges during the gate time, at low frequencies with
+//! only a few edges per gate it turns into measuring the period. If fewer
+//! than two edges arrive during the gate time, the measurement continues
+//! until the second edge or [FrequencyCounter::set_max_period] passed, so
+//! a frequency below `1 / gate_time` is measured without configuration.
+//!
+//! ## Range and resolution
+//!
+//! - upper limit: every edge costs an interrupt, see the [edge
+//!   counter](super::edge_counter#maximum-pulse-rate), in the order of 100 kHz
+//!   on the ESP32-C3
+//! - lower limit: one period has to fit into the maximum period, i.e. 0.1 Hz
+//!   with the default of 10 s
+//! - resolution: the edges are timestamped with the tick counter of
+//!   [crate::time], the interrupt latency adds a jitter of 1-2 µs. The relative
+//!   error is that jitter divided by the measured time span, e.g. 2 ppm for a
+//!   gate time of 1 s. The result is rounded to whole Hz.
+
+use fugit::{HertzU32, MicrosDurationU64};
+
+use super::{edge_counter, Event, InputPin};
+use crate::time::{Deadline, TICKS_PER_SECOND};
+
+/// Longest supported measurement, the edge timestamps wrap around after
+/// `u32::MAX` ticks
+const MAX_SPAN_TICKS: u64 = u32::MAX as u64 / 2;
+
+/// Measures the frequency of rising edges on a pin, see the [module
+/// documentation](self)
+pub struct FrequencyCounter<P> {
+    pin: P,
+    gate_time: MicrosDurationU64,
+    max_period: MicrosDurationU64,
+}
+
+impl<P> FrequencyCounter<P>
+where
+    P: InputPin,
+{
+    /// Count the rising edges of `pin` for `gate_time` per measurement
+    ///
+    /// Listens for the edges of the pin, the `GPIO` interrupt has to call
+    /// [edge_counter::handle_interrupt].
+    pub fn new<T>(mut pin: P, gate_time: T) -> Self
+    where
+        T: Into<MicrosDurationU64>,
+    {
+        let gpio_num = pin.number();
+        edge_counter::enable(gpio_num, edge_counter::Overflow::Saturate);
+        edge_counter::enable_timestamps(gpio_num, true);
+        pin.clear_interrupt();
+        pin.listen(Event::RisingEdge);
+
+        Self {
+            pin,
+            gate_time: gate_time.into(),
+            max_period: MicrosDurationU64::secs(10),
+        }
+    }
+
+    /// Longest period measured before [FrequencyCounter::measure] gives up
+    /// and returns 0 Hz
+    ///
+    /// Limited to about `u32::MAX / 2` ticks of [crate::time], i.e. 134 s
+    /// with 16 MHz ticks.
+    pub fn set_max_period<T>(&mut self, max_period: T)
+    where
+        T: Into<MicrosDurationU64>,
+    {
+        self.max_period = max_period.into();
+    }
+
+    /// Measure the frequency
+    ///
+    /// Blocks for the gate time, or up to the maximum period if fewer than
+    /// two edges arrived by then. Returns 0 Hz if there was no full period.
+    pub fn measure(&mut self) -> HertzU32 {
+        let gpio_num = self.pin.number();
+
+        edge_counter::reset(gpio_num);
+        let gate = Deadline::after(self.gate_time);
+        let limit = Deadline::after(self.max_period.max(self.gate_time));
+
+        while !(gate.is_expired() && edge_counter::count(gpio_num) >= 2) && !limit.is_expired() {}
+
+        let (edges, (first, last)) = critical_section::with(|_| {
+            (
+                edge_counter::count(gpio_num),
+                edge_counter::timestamps(gpio_num),
+            )
+        });
+
+        HertzU32::Hz(frequency(edges, last.wrapping_sub(first)))
+    }
+
+    /// Stop counting and return the pin
+    pub fn free(mut self) -> P {
+        let gpio_num = self.pin.number();
+        self.pin.unlisten();
+        edge_counter::disable(gpio_num);
+        edge_counter::enable_timestamps(gpio_num, false);
+        self.pin
+    }
+}
+
+/// The frequency in Hz of `edges` rising edges with `span` ticks between the
+/// first and the last, rounded to the nearest Hz
+const fn frequency(edges: u32, span: u32) -> u32 {
+    if edges < 2 || span == 0 || span as u64 > MAX_SPAN_TICKS {
+        return 0;
+    }
+
+    let periods = (edges - 1) as u64;
+    let span = span as u64;
+    ((periods * TICKS_PER_SECOND + span / 2) / span) as u32
+}
+
+const _: () = {
+    // counting: 1001 edges in one second
+    assert!(frequency(1001, TICKS_PER_SECOND as u32) == 1000);
+    // period measurement: two edges 400 ms apart
+    assert!(frequency(2, (TICKS_PER_SECOND * 2 / 5) as u32) == 3);
+    // no full period
+    assert!(frequency(1, 0) == 0);
+    assert!(frequency(0, 12345) == 0);
+};
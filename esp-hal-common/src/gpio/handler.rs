@@ -0,0 +1,130 @@
+//! Decoding the GPIO interrupt in a handler installed by the application
+//!
+//! Frameworks like RTIC bind the `GPIO` interrupt themselves, the HAL
+//! doesn't install any handler. [handle_interrupts] does the decode for
+//! them: it calls a closure for every pin with a pending interrupt and
+//! clears it.
+//!
+//! ```no_run
+//! #[task(binds = GPIO, shared = [presses])]
+//! fn on_gpio(mut cx: on_gpio::Context) {
+//!     gpio::handle_interrupts(|pin, status| {
+//!         if pin == 9 {
+//!             cx.shared.presses.lock(|presses| *presses += 1);
+//!         }
+//!     });
+//! }
+//! ```
+//!
+//! Only the pins which listen with the normal interrupt enable of the
+//! current core are handled, pins listening for the NMI are left pending.
+//! Don't use it on the same interrupt as the [dispatch](super::dispatch)
+//! handlers, it would take their pending pins.
+
+use super::Event;
+use crate::pac::GPIO;
+
+/// State of a pin with a pending interrupt
+#[derive(Clone, Copy)]
+pub struct EventStatus {
+    event: Option<Event>,
+    high: bool,
+}
+
+impl EventStatus {
+    /// The event the pin listens for, `None` if it stopped listening since
+    /// the interrupt became pending
+    pub fn event(&self) -> Option<Event> {
+        self.event
+    }
+
+    /// The level of the pin when the interrupt was decoded, tells a rising
+    /// from a falling edge of an [Event::AnyEdge] pin, unless the pin changed
+    /// again in the meantime
+    pub fn is_high(&self) -> bool {
+        self.high
+    }
+}
+
+/// Call `handler` with the number and [EventStatus] of every pin which has a
+/// pending interrupt on the current core, then clear it
+///
+/// The pending interrupts are cleared before `handler` is called: an event
+/// occurring while `handler` runs stays pending, and a nested call from a
+/// higher priority interrupt doesn't report the same pins again. Only pins
+/// which were pending are cleared, so concurrent calls don't lose events.
+pub fn handle_interrupts<F>(mut handler: F)
+where
+    F: FnMut(u8, EventStatus),
+{
+    let gpio = unsafe { &*GPIO::PTR };
+
+    let status = interrupt_status(0);
+    if status != 0 {
+        gpio.status_w1tc.write(|w| unsafe { w.bits(status) });
+        call(0, status, gpio.in_.read().bits(), &mut handler);
+    }
+
+    #[cfg(not(any(esp32c2, esp32c3)))]
+    {
+        let status = interrupt_status(1);
+        if status != 0 {
+            gpio.status1_w1tc.write(|w| unsafe { w.bits(status) });
+            call(1, status, gpio.in1.read().bits(), &mut handler);
+        }
+    }
+}
+
+/// The pending interrupts of `bank` routed to the normal interrupt of the
+/// current core
+fn interrupt_status(bank: usize) -> u32 {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    // the ESP32-S3 has a shared enable for both cores, see
+    // `InteruptStatusRegisterAccess`
+    #[cfg(esp32)]
+    if matches!(crate::get_core(), crate::Cpu::AppCpu) {
+        return match bank {
+            0 => gpio.acpu_int.read().bits(),
+            _ => gpio.acpu_int1.read().bits(),
+        };
+    }
+
+    match bank {
+        0 => gpio.pcpu_int.read().bits(),
+        #[cfg(not(any(esp32c2, esp32c3)))]
+        _ => gpio.pcpu_int1.read().bits(),
+        #[cfg(any(esp32c2, esp32c3))]
+        _ => unreachable!(),
+    }
+}
+
+fn call<F>(bank: u8, mut status: u32, input: u32, handler: &mut F)
+where
+    F: FnMut(u8, EventStatus),
+{
+    let gpio = unsafe { &*GPIO::PTR };
+
+    while status != 0 {
+        let bit = status.trailing_zeros();
+        status &= !(1 << bit);
+
+        let gpio_num = bank * 32 + bit as u8;
+        let event = match gpio.pin[gpio_num as usize].read().int_type().bits() {
+            1 => Some(Event::RisingEdge),
+            2 => Some(Event::FallingEdge),
+            3 => Some(Event::AnyEdge),
+            4 => Some(Event::LowLevel),
+            5 => Some(Event::HighLevel),
+            _ => None,
+        };
+
+        handler(
+            gpio_num,
+            EventStatus {
+                event,
+                high: input & (1 << bit) != 0,
+            },
+        );
+    }
+}
@@ -0,0 +1,529 @@
+//! Temporarily bit-bang a pad owned by a peripheral
+//!
+//! I2C bus recovery (clocking SCL until a stuck slave releases SDA), the
+//! reset pulse of a 1-Wire bus or any other "toggle this pin by hand for a
+//! moment" needs the same thing: detach the pad from the signal it is routed
+//! to, drive it as a GPIO and attach it again exactly as it was.
+//!
+//! [borrow_pad] takes a [PadSnapshot] of the pad, hands a [Flex] to the
+//! closure and restores the snapshot afterwards, however the closure returns:
+//!
+//! ```no_run
+//! // nine clocks on SCL while an I2C driver owns the pad
+//! gpio::borrow_pad(scl, |scl| {
+//!     scl.set_as_open_drain(Pull::Up);
+//!     for _ in 0..9 {
+//!         scl.set_low();
+//!         delay.delay_us(5u32);
+//!         scl.set_high();
+//!         delay.delay_us(5u32);
+//!     }
+//! })
+//! .unwrap();
+//! ```
+//!
+//! A pad can only be borrowed once at a time: borrowing it again from within
+//! the closure (or from an interrupt handler meanwhile) returns
+//! [PadError::AlreadyBorrowed], the inner restore would otherwise undo the
+//! configuration of the outer closure.
+//!
+//! The snapshot covers the IO_MUX register of the pad (function, input
+//! enable, pulls, drive strength), its GPIO pin register (open drain,
+//! interrupt configuration), its output matrix selection, its bits in the
+//! output and output enable registers and its pending interrupt. Peripheral
+//! inputs routed from the pad through the input matrix are left connected,
+//! they see the levels the closure drives. Pads routed to the RTC mux are
+//! not supported.
+
+use core::cell::Cell;
+
+use critical_section::Mutex;
+
+#[cfg(not(any(esp32c2, esp32c3)))]
+use super::Bank1GpioRegisterAccess;
+use super::{
+    get_io_mux_reg,
+    pin_exists,
+    Bank0GpioRegisterAccess,
+    BankGpioRegisterAccess,
+    OutputSignal,
+    OutputSignalType,
+    Pull,
+    GPIO_FUNCTION,
+};
+use crate::pac::GPIO;
+
+/// Bit `n` is set while GPIO `n` is borrowed
+static BORROWED: Mutex<Cell<u64>> = Mutex::new(Cell::new(0));
+
+/// Errors of [borrow_pad] and [PadSnapshot::capture]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadError {
+    /// The chip has no GPIO with this number
+    InvalidPin,
+    /// The pad is already borrowed, by an enclosing [borrow_pad] or one
+    /// running in an interrupted context
+    AlreadyBorrowed,
+}
+
+/// Configuration of a pad, see the [module documentation](self)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PadSnapshot {
+    gpio_num: u8,
+    /// The IO_MUX register of the pad
+    pub io_mux: u32,
+    /// The GPIO pin register of the pad
+    pub pin: u32,
+    /// The output matrix selection of the pad
+    pub out_sel: u32,
+    /// The output enable bit of the pad
+    pub output_enable: bool,
+    /// The output bit of the pad
+    pub output: bool,
+    /// Whether the interrupt of the pad was pending
+    pub interrupt_pending: bool,
+}
+
+impl PadSnapshot {
+    /// Read the configuration of GPIO `gpio_num`
+    pub fn capture(gpio_num: u8) -> Result<Self, PadError> {
+        if !pin_exists(gpio_num) {
+            return Err(PadError::InvalidPin);
+        }
+
+        Ok(Self::read(gpio_num))
+    }
+
+    /// Write the configuration back
+    ///
+    /// An interrupt raised since the snapshot was taken is cleared unless it
+    /// was already pending then.
+    pub fn restore(&self) {
+        let (writes, len) = self.writes();
+        for &write in &writes[..len] {
+            write_pad(self.gpio_num, write);
+        }
+    }
+
+    /// The GPIO the snapshot was taken of
+    pub fn gpio_num(&self) -> u8 {
+        self.gpio_num
+    }
+
+    fn read(gpio_num: u8) -> Self {
+        let gpio = unsafe { &*GPIO::PTR };
+        let n = gpio_num as usize;
+        let bit = 1 << (gpio_num % 32);
+
+        Self {
+            gpio_num,
+            io_mux: get_io_mux_reg(gpio_num).read().bits(),
+            pin: gpio.pin[n].read().bits(),
+            out_sel: gpio.func_out_sel_cfg[n].read().bits(),
+            output_enable: read_enable(gpio_num) & bit != 0,
+            output: bank(gpio_num).read_output() & bit != 0,
+            interrupt_pending: read_status(gpio_num) & bit != 0,
+        }
+    }
+
+    /// The register writes of [PadSnapshot::restore], in order, and their
+    /// number
+    const fn writes(&self) -> ([PadWrite; 6], usize) {
+        // set the level before the pad is attached or driven again
+        let mut writes = [PadWrite::Output(self.output); 6];
+        let mut len = 1;
+
+        if !self.interrupt_pending {
+            writes[len] = PadWrite::ClearInterrupt;
+            len += 1;
+        }
+
+        writes[len] = PadWrite::OutSel(self.out_sel);
+        writes[len + 1] = PadWrite::Pin(self.pin);
+        writes[len + 2] = PadWrite::IoMux(self.io_mux);
+        writes[len + 3] = PadWrite::OutputEnable(self.output_enable);
+
+        (writes, len + 4)
+    }
+}
+
+/// One register write restoring a [PadSnapshot]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PadWrite {
+    Output(bool),
+    ClearInterrupt,
+    OutSel(u32),
+    Pin(u32),
+    IoMux(u32),
+    OutputEnable(bool),
+}
+
+/// Carry out `write` on the registers of GPIO `gpio_num`
+fn write_pad(gpio_num: u8, write: PadWrite) {
+    let gpio = unsafe { &*GPIO::PTR };
+    let n = gpio_num as usize;
+    let bit = 1 << (gpio_num % 32);
+    let bank = bank(gpio_num);
+
+    match write {
+        PadWrite::Output(true) => bank.write_output_set(bit),
+        PadWrite::Output(false) => bank.write_output_clear(bit),
+        PadWrite::ClearInterrupt => bank.write_interrupt_status_clear(bit),
+        PadWrite::OutSel(bits) => gpio.func_out_sel_cfg[n].write(|w| unsafe { w.bits(bits) }),
+        PadWrite::Pin(bits) => gpio.pin[n].write(|w| unsafe { w.bits(bits) }),
+        PadWrite::IoMux(bits) => get_io_mux_reg(gpio_num).write(|w| unsafe { w.bits(bits) }),
+        PadWrite::OutputEnable(true) => bank.write_out_en_set(bit),
+        PadWrite::OutputEnable(false) => bank.write_out_en_clear(bit),
+    }
+}
+
+/// Mark GPIO `gpio_num` as borrowed in the set `borrowed`
+const fn take(borrowed: u64, gpio_num: u8) -> Result<u64, PadError> {
+    let bit = match 1u64.checked_shl(gpio_num as u32) {
+        Some(bit) => bit,
+        None => return Err(PadError::InvalidPin),
+    };
+    if borrowed & bit != 0 {
+        return Err(PadError::AlreadyBorrowed);
+    }
+
+    Ok(borrowed | bit)
+}
+
+/// Remove GPIO `gpio_num` from the set `borrowed`
+const fn release(borrowed: u64, gpio_num: u8) -> u64 {
+    match 1u64.checked_shl(gpio_num as u32) {
+        Some(bit) => borrowed & !bit,
+        None => borrowed,
+    }
+}
+
+/// Restores the snapshot and ends the borrow when dropped
+struct RestoreOnDrop(PadSnapshot);
+
+impl Drop for RestoreOnDrop {
+    fn drop(&mut self) {
+        self.0.restore();
+
+        critical_section::with(|cs| {
+            let borrowed = BORROWED.borrow(cs);
+            borrowed.set(release(borrowed.get(), self.0.gpio_num));
+        });
+    }
+}
+
+/// Drive GPIO `gpio_num` by hand in `f`, whatever it is routed to
+///
+/// The pad starts as a GPIO input, its pull resistors and its output level
+/// unchanged, and the interrupt of the pad disabled. Afterwards the pad is
+/// restored, see the [module documentation](self).
+///
+/// The pad isn't owned while it's borrowed: only borrow pads of pins owned by
+/// the caller or by a driver the caller owns. Returns
+/// [PadError::AlreadyBorrowed] without calling `f` if the pad is borrowed
+/// already.
+pub fn borrow_pad<R>(gpio_num: u8, f: impl FnOnce(&mut Flex) -> R) -> Result<R, PadError> {
+    if !pin_exists(gpio_num) {
+        return Err(PadError::InvalidPin);
+    }
+
+    critical_section::with(|cs| {
+        let borrowed = BORROWED.borrow(cs);
+        borrowed.set(take(borrowed.get(), gpio_num)?);
+        Ok(())
+    })?;
+
+    let _restore = RestoreOnDrop(PadSnapshot::read(gpio_num));
+
+    let mut flex = Flex { gpio_num };
+    flex.attach();
+
+    Ok(f(&mut flex))
+}
+
+/// Leave GPIO `gpio_num` as a floating GPIO input
+///
+/// Drivers put the pads they own into this state when they are dropped: the
+/// pad is detached from peripheral outputs, its output driver, pull resistors
+/// and interrupt are disabled.
+pub(crate) fn float_pad(gpio_num: u8) {
+    let mut flex = Flex { gpio_num };
+    flex.attach();
+    flex.set_as_input(Pull::None);
+}
+
+/// A pad borrowed by [borrow_pad], usable as input, push-pull or open-drain
+/// output
+pub struct Flex {
+    gpio_num: u8,
+}
+
+impl Flex {
+    /// Route the pad to the GPIO input and output registers, with the output
+    /// driver disabled
+    fn attach(&mut self) {
+        let gpio = unsafe { &*GPIO::PTR };
+        let n = self.gpio_num as usize;
+
+        bank(self.gpio_num).write_out_en_clear(self.bit());
+        gpio.func_out_sel_cfg[n].modify(|_, w| unsafe {
+            w.out_sel()
+                .bits(OutputSignal::GPIO as OutputSignalType)
+                .inv_sel()
+                .clear_bit()
+                .oen_sel()
+                .clear_bit()
+                .oen_inv_sel()
+                .clear_bit()
+        });
+        gpio.pin[n].modify(|_, w| unsafe { w.int_ena().bits(0) });
+        get_io_mux_reg(self.gpio_num)
+            .modify(|_, w| unsafe { w.mcu_sel().bits(GPIO_FUNCTION as u8).fun_ie().set_bit() });
+    }
+
+    /// The number of the GPIO
+    pub fn gpio_num(&self) -> u8 {
+        self.gpio_num
+    }
+
+    /// Disable the output driver
+    pub fn set_as_input(&mut self, pull: Pull) {
+        self.set_pull(pull);
+        bank(self.gpio_num).write_out_en_clear(self.bit());
+    }
+
+    /// Drive the pad in both directions
+    pub fn set_as_output(&mut self) {
+        self.set_open_drain(false);
+        bank(self.gpio_num).write_out_en_set(self.bit());
+    }
+
+    /// Only drive the pad low, it floats (or is pulled) when set high
+    pub fn set_as_open_drain(&mut self, pull: Pull) {
+        self.set_pull(pull);
+        self.set_open_drain(true);
+        bank(self.gpio_num).write_out_en_set(self.bit());
+    }
+
+    /// Set the internal pull resistor
+    pub fn set_pull(&mut self, pull: Pull) {
+        get_io_mux_reg(self.gpio_num)
+            .modify(|_, w| w.fun_wpu().bit(pull.up()).fun_wpd().bit(pull.down()));
+    }
+
+    /// Drive the pad high (release it in open-drain mode)
+    pub fn set_high(&mut self) {
+        bank(self.gpio_num).write_output_set(self.bit());
+    }
+
+    /// Drive the pad low
+    pub fn set_low(&mut self) {
+        bank(self.gpio_num).write_output_clear(self.bit());
+    }
+
+    /// Whether the pad reads high
+    pub fn is_high(&self) -> bool {
+        bank(self.gpio_num).read_input() & self.bit() != 0
+    }
+
+    /// Whether the pad reads low
+    pub fn is_low(&self) -> bool {
+        !self.is_high()
+    }
+
+    fn set_open_drain(&mut self, open_drain: bool) {
+        bank(self.gpio_num).set_open_drain(self.gpio_num, open_drain);
+    }
+
+    fn bit(&self) -> u32 {
+        1 << (self.gpio_num % 32)
+    }
+}
+
+impl embedded_hal::digital::v2::OutputPin for Flex {
+    type Error = core::convert::Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        Flex::set_high(self);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        Flex::set_low(self);
+        Ok(())
+    }
+}
+
+impl embedded_hal::digital::v2::InputPin for Flex {
+    type Error = core::convert::Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(Flex::is_high(self))
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(Flex::is_low(self))
+    }
+}
+
+/// The register bank of GPIO `gpio_num`
+#[cfg(any(esp32c2, esp32c3))]
+fn bank(_gpio_num: u8) -> Bank0GpioRegisterAccess {
+    Bank0GpioRegisterAccess
+}
+
+/// The register bank of GPIO `gpio_num`
+#[cfg(not(any(esp32c2, esp32c3)))]
+fn bank(gpio_num: u8) -> &'static dyn BankGpioRegisterAccess {
+    if gpio_num < 32 {
+        &Bank0GpioRegisterAccess
+    } else {
+        &Bank1GpioRegisterAccess
+    }
+}
+
+/// The output enable register of the bank of GPIO `gpio_num`
+fn read_enable(gpio_num: u8) -> u32 {
+    let gpio = unsafe { &*GPIO::PTR };
+    match gpio_num {
+        #[cfg(not(any(esp32c2, esp32c3)))]
+        32..=63 => gpio.enable1.read().bits(),
+        _ => gpio.enable.read().bits(),
+    }
+}
+
+/// The raw interrupt status register of the bank of GPIO `gpio_num`
+fn read_status(gpio_num: u8) -> u32 {
+    let gpio = unsafe { &*GPIO::PTR };
+    match gpio_num {
+        #[cfg(not(any(esp32c2, esp32c3)))]
+        32..=63 => gpio.status1.read().bits(),
+        _ => gpio.status.read().bits(),
+    }
+}
+
+const _: () = {
+    /// A pad in memory after `writes`
+    const fn apply(mut pad: PadSnapshot, writes: ([PadWrite; 6], usize)) -> PadSnapshot {
+        let mut i = 0;
+        while i < writes.1 {
+            match writes.0[i] {
+                PadWrite::Output(high) => pad.output = high,
+                PadWrite::ClearInterrupt => pad.interrupt_pending = false,
+                PadWrite::OutSel(bits) => pad.out_sel = bits,
+                PadWrite::Pin(bits) => pad.pin = bits,
+                PadWrite::IoMux(bits) => pad.io_mux = bits,
+                PadWrite::OutputEnable(enable) => pad.output_enable = enable,
+            }
+            i += 1;
+        }
+        pad
+    }
+
+    const fn same(a: PadSnapshot, b: PadSnapshot) -> bool {
+        a.gpio_num == b.gpio_num
+            && a.io_mux == b.io_mux
+            && a.pin == b.pin
+            && a.out_sel == b.out_sel
+            && a.output_enable == b.output_enable
+            && a.output == b.output
+            && a.interrupt_pending == b.interrupt_pending
+    }
+
+    // A pad routed to a peripheral output, driving high
+    let peripheral = PadSnapshot {
+        gpio_num: 7,
+        io_mux: 0x0000_1a00,
+        pin: 0x0000_2004,
+        out_sel: 0x0000_0043,
+        output_enable: true,
+        output: true,
+        interrupt_pending: false,
+    };
+    // The same pad changed in every field, as a borrow does
+    let borrowed = PadSnapshot {
+        gpio_num: 7,
+        io_mux: 0x0000_1300,
+        pin: 0,
+        out_sel: 0x0000_0080,
+        output_enable: false,
+        output: false,
+        interrupt_pending: true,
+    };
+
+    // The level is set first and the output enabled last, an interrupt
+    // raised meanwhile is cleared
+    let writes = peripheral.writes();
+    assert!(writes.1 == 6);
+    assert!(matches!(writes.0[0], PadWrite::Output(true)));
+    assert!(matches!(writes.0[1], PadWrite::ClearInterrupt));
+    assert!(matches!(writes.0[2], PadWrite::OutSel(0x0000_0043)));
+    assert!(matches!(writes.0[3], PadWrite::Pin(0x0000_2004)));
+    assert!(matches!(writes.0[4], PadWrite::IoMux(0x0000_1a00)));
+    assert!(matches!(writes.0[5], PadWrite::OutputEnable(true)));
+    assert!(!same(borrowed, peripheral));
+    assert!(same(apply(borrowed, writes), peripheral));
+
+    // An interrupt already pending at the snapshot is kept
+    let pending = PadSnapshot {
+        interrupt_pending: true,
+        ..peripheral
+    };
+    let writes = pending.writes();
+    assert!(writes.1 == 5);
+    assert!(matches!(writes.0[1], PadWrite::OutSel(_)));
+    assert!(matches!(writes.0[4], PadWrite::OutputEnable(true)));
+    assert!(same(apply(borrowed, writes), pending));
+
+    // A GPIO input with a pending interrupt, output latch low
+    let input = PadSnapshot {
+        gpio_num: 33,
+        io_mux: 0x0000_1280,
+        pin: 0x0001_2000,
+        out_sel: 0x0000_0480,
+        output_enable: false,
+        output: false,
+        interrupt_pending: true,
+    };
+    let driven = PadSnapshot {
+        io_mux: 0,
+        pin: 0,
+        out_sel: 0x80,
+        output_enable: true,
+        output: true,
+        ..input
+    };
+    assert!(same(apply(driven, input.writes()), input));
+
+    // Borrowing once
+    assert!(matches!(take(0, 5), Ok(0x20)));
+    assert!(matches!(take(1 << 5, 5), Err(PadError::AlreadyBorrowed)));
+    assert!(matches!(take(1 << 5, 6), Ok(0x60)));
+    assert!(matches!(take(release(1 << 5, 5), 5), Ok(0x20)));
+    assert!(matches!(take(0, 64), Err(PadError::InvalidPin)));
+    assert!(matches!(take(0, u8::MAX), Err(PadError::InvalidPin)));
+
+    // Every GPIO number
+    let mut borrowed = 0;
+    let mut gpio_num = 0;
+    while gpio_num < 64 {
+        borrowed = match take(borrowed, gpio_num) {
+            Ok(borrowed) => borrowed,
+            Err(_) => panic!(),
+        };
+        gpio_num += 1;
+    }
+    assert!(borrowed == u64::MAX);
+    while gpio_num > 0 {
+        gpio_num -= 1;
+        borrowed = release(borrowed, gpio_num);
+    }
+    assert!(borrowed == 0);
+
+    // Releasing keeps the other pads
+    let borrowed = 1 << 1 | 1 << 2 | 1 << 63;
+    assert!(release(borrowed, 2) == 1 << 1 | 1 << 63);
+    assert!(release(borrowed, 63) == 1 << 1 | 1 << 2);
+    assert!(release(borrowed, 5) == borrowed);
+    assert!(release(borrowed, u8::MAX) == borrowed);
+};
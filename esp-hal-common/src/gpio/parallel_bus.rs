@@ -0,0 +1,397 @@
+//! 8-bit parallel bus on dedicated GPIO
+//!
+//! Old peripherals like 8080-style ADCs, latches or SRAMs have a multiplexed
+//! 8-bit bus with `ALE`, `RD`, `WR` and `CS` strobes of around 100 ns. That's
+//! too short for `GPIO` register accesses from ordinary code and too little
+//! data to set up the LCD_CAM. [ParallelBus8] bit-bangs such a bus: the data
+//! lines are [dedicated GPIO](super::dedicated) channels, the strobes are
+//! `GPIO` pins, and every bus cycle runs from RAM with interrupts disabled.
+//!
+//! ```no_run
+//! let timing = BusTiming {
+//!     strobe: 100u32.nanos(),
+//!     ..BusTiming::default()
+//! };
+//! let mut bus = ParallelBus8::new(DedicatedGpio::new(&mut pcc), timing, &clocks).unwrap();
+//! bus.connect_data(0, &mut io.pins.gpio0);
+//! // ... data bits 1 to 7
+//! bus.connect_control(Control::Wr, &mut io.pins.gpio8);
+//! bus.connect_control(Control::Rd, &mut io.pins.gpio9);
+//! bus.connect_control(Control::Ale, &mut io.pins.gpio10);
+//!
+//! bus.write_cycle(0x12, 0x34).unwrap();
+//! let value = bus.read_cycle(0x12).unwrap();
+//! ```
+//!
+//! ## Bus cycle
+//!
+//! `CS` goes low, the address is put on the data lines and `ALE` goes high
+//! for [BusTiming::address_setup], then `ALE` falls and the address stays
+//! for [BusTiming::address_hold]. A write puts the data on the lines for
+//! [BusTiming::setup] before `WR` goes low for [BusTiming::strobe], a read
+//! releases the lines for [BusTiming::setup] before `RD` goes low for
+//! [BusTiming::strobe] and samples them just before `RD` rises. Both wait
+//! [BusTiming::hold] before `CS` goes high. `CS` and `ALE` are optional.
+//!
+//! ## Timing
+//!
+//! The widths are converted to CPU cycles, rounded up. Each phase is a
+//! `GPIO` register write followed by a delay into a sled of single-cycle
+//! instructions, the cycles the write and the jump into the sled take are
+//! measured with the [cycle counter](crate::profiling) when the bus is
+//! created and subtracted. The latency of a `GPIO` write until the pad
+//! changes is the same for every edge, so the widths between edges are
+//! accurate to a cycle or two. A width shorter than a phase takes at the
+//! current CPU clock is rejected with [Error::TimingTooShort], one longer
+//! than the sled with [Error::TimingTooLong]; a width of 0 selects the
+//! shortest possible phase. The bus is a [ClockListener], the widths are
+//! converted again when the CPU clock changes.
+
+use fugit::NanosDurationU32;
+
+use super::{
+    dedicated::{self, DedicatedGpio},
+    InputPin,
+    OutputPin,
+};
+use crate::{
+    clock::{ClockListener, Clocks},
+    pac::GPIO,
+    profiling::{cycle_count, CycleCounter},
+};
+
+/// Longest delay of a phase in addition to the time the phase takes itself,
+/// in CPU cycles
+pub const MAX_DELAY_CYCLES: u32 = 256;
+
+/// Widths of the phases of a bus cycle, see the [module
+/// documentation](self#bus-cycle)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BusTiming {
+    /// Address on the data lines before `ALE` falls
+    pub address_setup: NanosDurationU32,
+    /// Address on the data lines after `ALE` fell
+    pub address_hold: NanosDurationU32,
+    /// Data (write) or released lines (read) before the strobe
+    pub setup: NanosDurationU32,
+    /// Low time of `WR` or `RD`
+    pub strobe: NanosDurationU32,
+    /// Time after the strobe before `CS` rises
+    pub hold: NanosDurationU32,
+}
+
+impl Default for BusTiming {
+    /// 50 ns for all phases but the strobe, which is 100 ns
+    fn default() -> Self {
+        BusTiming {
+            address_setup: NanosDurationU32::from_ticks(50),
+            address_hold: NanosDurationU32::from_ticks(50),
+            setup: NanosDurationU32::from_ticks(50),
+            strobe: NanosDurationU32::from_ticks(100),
+            hold: NanosDurationU32::from_ticks(50),
+        }
+    }
+}
+
+/// Bus errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// A width is shorter than its phase takes at the CPU clock
+    TimingTooShort,
+    /// A width exceeds the phase by more than [MAX_DELAY_CYCLES]
+    TimingTooLong,
+}
+
+/// The control lines of the bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Control {
+    /// Read strobe, active low
+    Rd,
+    /// Write strobe, active low
+    Wr,
+    /// Chip select, active low
+    Cs,
+    /// Address latch enable, active high
+    Ale,
+}
+
+/// Delay cycles of the phases
+#[derive(Debug, Clone, Copy)]
+struct Cycles {
+    address_setup: u32,
+    address_hold: u32,
+    setup: u32,
+    strobe: u32,
+    hold: u32,
+}
+
+/// `GPIO` masks of the control lines, 0 if not connected
+#[derive(Debug, Clone, Copy, Default)]
+struct ControlMasks {
+    rd: u32,
+    wr: u32,
+    cs: u32,
+    ale: u32,
+}
+
+/// 8-bit parallel bus, see the [module documentation](self)
+pub struct ParallelBus8 {
+    dedicated: DedicatedGpio,
+    timing: BusTiming,
+    /// CPU cycles a phase takes without delay
+    overhead: u32,
+    cpu_hz: u32,
+    cycles: Result<Cycles, Error>,
+    controls: ControlMasks,
+}
+
+impl ParallelBus8 {
+    /// Create a bus with `timing` at the current CPU clock
+    ///
+    /// Measures the time a phase takes, connect the pins with
+    /// [ParallelBus8::connect_data] and [ParallelBus8::connect_control]
+    /// afterwards.
+    pub fn new(
+        dedicated: DedicatedGpio,
+        timing: BusTiming,
+        clocks: &Clocks,
+    ) -> Result<Self, Error> {
+        let overhead = phase_overhead();
+        let cpu_hz = clocks.cpu_clock.to_Hz();
+        let cycles = to_cycles(&timing, cpu_hz, overhead)?;
+
+        Ok(Self {
+            dedicated,
+            timing,
+            overhead,
+            cpu_hz,
+            cycles: Ok(cycles),
+            controls: ControlMasks::default(),
+        })
+    }
+
+    /// Connect data line `bit` (0 to 7) to `pin`
+    ///
+    /// The line is released between bus cycles.
+    pub fn connect_data<P>(&mut self, bit: u8, pin: &mut P)
+    where
+        P: InputPin + OutputPin,
+    {
+        self.dedicated.connect_bidirectional(bit, pin);
+    }
+
+    /// Drive `control` on `pin`, which starts out inactive
+    pub fn connect_control<P: OutputPin>(&mut self, control: Control, pin: &mut P) {
+        let mask = 1 << pin.number();
+
+        pin.set_output_high(control != Control::Ale)
+            .set_to_push_pull_output();
+
+        match control {
+            Control::Rd => self.controls.rd = mask,
+            Control::Wr => self.controls.wr = mask,
+            Control::Cs => self.controls.cs = mask,
+            Control::Ale => self.controls.ale = mask,
+        }
+    }
+
+    /// Use `timing` for the following bus cycles
+    ///
+    /// Keeps the previous timing if `timing` can't be represented at the
+    /// current CPU clock.
+    pub fn set_timing(&mut self, timing: BusTiming) -> Result<(), Error> {
+        self.cycles = Ok(to_cycles(&timing, self.cpu_hz, self.overhead)?);
+        self.timing = timing;
+        Ok(())
+    }
+
+    /// The timing of the bus cycles
+    pub fn timing(&self) -> BusTiming {
+        self.timing
+    }
+
+    /// CPU cycles a phase takes at least
+    pub fn phase_overhead_cycles(&self) -> u32 {
+        self.overhead
+    }
+
+    /// Write `data` to `address`
+    pub fn write_cycle(&mut self, address: u8, data: u8) -> Result<(), Error> {
+        let cycles = self.cycles?;
+        let controls = self.controls;
+        critical_section::with(|_| write_cycle_raw(&controls, &cycles, address, data));
+        Ok(())
+    }
+
+    /// Read from `address`
+    pub fn read_cycle(&mut self, address: u8) -> Result<u8, Error> {
+        let cycles = self.cycles?;
+        let controls = self.controls;
+        Ok(critical_section::with(|_| {
+            read_cycle_raw(&controls, &cycles, address)
+        }))
+    }
+}
+
+impl ClockListener for ParallelBus8 {
+    /// The widths are converted again, bus cycles fail with the error of the
+    /// conversion until the timing fits the CPU clock again.
+    fn clocks_changed(&mut self, clocks: &Clocks) {
+        self.cpu_hz = clocks.cpu_clock.to_Hz();
+        self.cycles = to_cycles(&self.timing, self.cpu_hz, self.overhead);
+    }
+}
+
+fn to_cycles(timing: &BusTiming, cpu_hz: u32, overhead: u32) -> Result<Cycles, Error> {
+    Ok(Cycles {
+        address_setup: delay_cycles_for(timing.address_setup.ticks(), cpu_hz, overhead)?,
+        address_hold: delay_cycles_for(timing.address_hold.ticks(), cpu_hz, overhead)?,
+        setup: delay_cycles_for(timing.setup.ticks(), cpu_hz, overhead)?,
+        strobe: delay_cycles_for(timing.strobe.ticks(), cpu_hz, overhead)?,
+        hold: delay_cycles_for(timing.hold.ticks(), cpu_hz, overhead)?,
+    })
+}
+
+/// The delay for a phase of `ns` at `cpu_hz`, of which the phase takes
+/// `overhead` CPU cycles itself
+const fn delay_cycles_for(ns: u32, cpu_hz: u32, overhead: u32) -> Result<u32, Error> {
+    if ns == 0 {
+        return Ok(0);
+    }
+
+    let cycles = ((ns as u64 * cpu_hz as u64 + 999_999_999) / 1_000_000_000) as u32;
+    if cycles < overhead {
+        Err(Error::TimingTooShort)
+    } else if cycles - overhead > MAX_DELAY_CYCLES {
+        Err(Error::TimingTooLong)
+    } else {
+        Ok(cycles - overhead)
+    }
+}
+
+const _: () = {
+    // 160 MHz, 6.25 ns per cycle
+    assert!(matches!(delay_cycles_for(100, 160_000_000, 6), Ok(10)));
+    assert!(matches!(delay_cycles_for(50, 160_000_000, 6), Ok(2)));
+    assert!(matches!(
+        delay_cycles_for(30, 160_000_000, 6),
+        Err(Error::TimingTooShort)
+    ));
+    assert!(matches!(
+        delay_cycles_for(1_637, 160_000_000, 6),
+        Ok(MAX_DELAY_CYCLES)
+    ));
+    assert!(matches!(
+        delay_cycles_for(1_700, 160_000_000, 6),
+        Err(Error::TimingTooLong)
+    ));
+    // 80 MHz, 12.5 ns per cycle, rounded up
+    assert!(matches!(delay_cycles_for(100, 80_000_000, 6), Ok(2)));
+    assert!(matches!(delay_cycles_for(80, 80_000_000, 6), Ok(1)));
+    assert!(matches!(
+        delay_cycles_for(50, 80_000_000, 6),
+        Err(Error::TimingTooShort)
+    ));
+    // the shortest phase
+    assert!(matches!(delay_cycles_for(0, 80_000_000, 6), Ok(0)));
+};
+
+/// Wait `cycles` (at most [MAX_DELAY_CYCLES]) plus the constant cost of the
+/// call, by jumping into a sled of single-cycle `c.nop`s
+///
+/// The length of the sled has to match [MAX_DELAY_CYCLES].
+#[procmacros::ram]
+#[inline(never)]
+fn delay(cycles: u32) {
+    unsafe {
+        core::arch::asm!(
+            "la {target}, 2f",
+            "slli {offset}, {cycles}, 1",
+            "sub {target}, {target}, {offset}",
+            "jr {target}",
+            ".rept 256",
+            "c.nop",
+            ".endr",
+            "2:",
+            cycles = in(reg) cycles,
+            target = out(reg) _,
+            offset = out(reg) _,
+            options(nostack),
+        );
+    }
+}
+
+/// CPU cycles of a `GPIO` write followed by a delay of 0, the minimum
+#[procmacros::ram]
+fn phase_overhead() -> u32 {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    // enables the counter
+    let _ = CycleCounter::start();
+
+    critical_section::with(|_| {
+        let mut overhead = u32::MAX;
+        for _ in 0..4 {
+            let start = cycle_count();
+            let baseline = cycle_count().wrapping_sub(start);
+
+            let start = cycle_count();
+            gpio.out_w1ts.write(|w| unsafe { w.bits(0) });
+            delay(0);
+            let elapsed = cycle_count().wrapping_sub(start);
+
+            overhead = overhead.min(elapsed.saturating_sub(baseline));
+        }
+        overhead
+    })
+}
+
+#[inline(always)]
+fn address_phase(controls: &ControlMasks, cycles: &Cycles, address: u8) {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    gpio.out_w1tc.write(|w| unsafe { w.bits(controls.cs) });
+    dedicated::set_output_enable(0xff);
+    dedicated::write(address);
+    gpio.out_w1ts.write(|w| unsafe { w.bits(controls.ale) });
+    delay(cycles.address_setup);
+    gpio.out_w1tc.write(|w| unsafe { w.bits(controls.ale) });
+    delay(cycles.address_hold);
+}
+
+#[procmacros::ram]
+#[inline(never)]
+fn write_cycle_raw(controls: &ControlMasks, cycles: &Cycles, address: u8, data: u8) {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    address_phase(controls, cycles, address);
+
+    dedicated::write(data);
+    delay(cycles.setup);
+    gpio.out_w1tc.write(|w| unsafe { w.bits(controls.wr) });
+    delay(cycles.strobe);
+    gpio.out_w1ts.write(|w| unsafe { w.bits(controls.wr) });
+    delay(cycles.hold);
+    gpio.out_w1ts.write(|w| unsafe { w.bits(controls.cs) });
+
+    dedicated::set_output_enable(0);
+}
+
+#[procmacros::ram]
+#[inline(never)]
+fn read_cycle_raw(controls: &ControlMasks, cycles: &Cycles, address: u8) -> u8 {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    address_phase(controls, cycles, address);
+
+    dedicated::set_output_enable(0);
+    delay(cycles.setup);
+    gpio.out_w1tc.write(|w| unsafe { w.bits(controls.rd) });
+    delay(cycles.strobe);
+    let data = dedicated::read();
+    gpio.out_w1ts.write(|w| unsafe { w.bits(controls.rd) });
+    delay(cycles.hold);
+    gpio.out_w1ts.write(|w| unsafe { w.bits(controls.cs) });
+
+    data
+}
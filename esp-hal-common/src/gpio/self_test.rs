@@ -0,0 +1,318 @@
+//! GPIO short and solder bridge detection
+//!
+//! [IO::self_test] is meant for production test firmware. It checks every
+//! pin for a connection to a supply rail and to the other pins:
+//!
+//! - all pins are pulled down, a pin reading high is stuck high (and the same
+//!   the other way round)
+//! - then every pin in turn drives high while all others are pulled down, and
+//!   low while all others are pulled up; pins which follow it both times are
+//!   shorted to it
+//!
+//! Pins connected to the flash and input-only pins (which have no pull
+//! resistors on the ESP32) are never tested, the strapping pins are skipped
+//! unless [SelfTestConfig::include_strapping] is set. Exclude further pins
+//! which are driven externally or would be disturbed by toggling (e.g. the
+//! PSRAM or a console UART) with [SelfTestConfig::exclude].
+//!
+//! The configuration of all tested pins is saved before and restored after
+//! the test.
+//!
+//! ```no_run
+//! let mut io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+//! let report = io.self_test(SelfTestConfig::default());
+//! for (a, b) in report.shorts() {
+//!     println!("GPIO{} is shorted to GPIO{}", a, b);
+//! }
+//! ```
+
+#[cfg(not(any(esp32c2, esp32c3)))]
+use super::Bank1GpioRegisterAccess;
+use super::{
+    get_io_mux_reg,
+    Bank0GpioRegisterAccess,
+    BankGpioRegisterAccess,
+    OutputSignal,
+    OutputSignalType,
+    GPIO_FUNCTION,
+    IO,
+    STRAPPING_PINS,
+};
+use crate::pac::GPIO;
+
+/// Maximum number of shorted pairs in a [ShortReport]
+pub const MAX_SHORTS: usize = 16;
+
+/// Time for the levels to settle after changing the pulls or outputs, in µs
+const SETTLE_US: u32 = 10;
+
+/// Mask of the GPIOs `from` to `to` (inclusive)
+const fn pins(from: u8, to: u8) -> u64 {
+    (u64::MAX >> (63 - to)) & (u64::MAX << from)
+}
+
+#[cfg(esp32)]
+const EXISTING: u64 = pins(0, 19) | pins(21, 23) | pins(25, 27) | pins(32, 39);
+#[cfg(esp32)]
+const RESERVED: u64 = pins(6, 11) | pins(34, 39);
+
+#[cfg(esp32c2)]
+const EXISTING: u64 = pins(0, 20);
+#[cfg(esp32c2)]
+const RESERVED: u64 = pins(11, 17);
+
+#[cfg(esp32c3)]
+const EXISTING: u64 = pins(0, 21);
+#[cfg(esp32c3)]
+const RESERVED: u64 = pins(11, 17);
+
+#[cfg(esp32s2)]
+const EXISTING: u64 = pins(0, 21) | pins(26, 46);
+#[cfg(esp32s2)]
+const RESERVED: u64 = pins(26, 32) | 1 << 46;
+
+#[cfg(esp32s3)]
+const EXISTING: u64 = pins(0, 21) | pins(26, 48);
+#[cfg(esp32s3)]
+const RESERVED: u64 = pins(26, 32);
+
+const STRAPPING: u64 = {
+    let mut mask = 0;
+    let mut i = 0;
+    while i < STRAPPING_PINS.len() {
+        mask |= 1 << STRAPPING_PINS[i];
+        i += 1;
+    }
+    mask
+};
+
+const GPIO_COUNT: usize = 64 - EXISTING.leading_zeros() as usize;
+
+/// Configuration of [IO::self_test]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SelfTestConfig {
+    /// Pins not to test, bit `n` stands for GPIO `n`
+    pub exclude: u64,
+    /// Test the strapping pins as well
+    pub include_strapping: bool,
+}
+
+/// Result of [IO::self_test]
+///
+/// All masks have bit `n` set for GPIO `n`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShortReport {
+    /// The tested pins
+    pub tested: u64,
+    /// Pins reading high while pulled down
+    pub stuck_high: u64,
+    /// Pins reading low while pulled up
+    pub stuck_low: u64,
+    /// More than [MAX_SHORTS] shorted pairs were found
+    pub truncated: bool,
+    shorts: [(u8, u8); MAX_SHORTS],
+    short_count: u8,
+}
+
+impl ShortReport {
+    /// Pairs of shorted pins, the lower GPIO number first
+    pub fn shorts(&self) -> &[(u8, u8)] {
+        &self.shorts[..self.short_count as usize]
+    }
+
+    /// Whether no short and no stuck pin was found
+    pub fn passed(&self) -> bool {
+        self.stuck_high == 0 && self.stuck_low == 0 && self.short_count == 0
+    }
+
+    fn add_short(&mut self, a: u8, b: u8) {
+        if (self.short_count as usize) < MAX_SHORTS {
+            self.shorts[self.short_count as usize] = (a, b);
+            self.short_count += 1;
+        } else {
+            self.truncated = true;
+        }
+    }
+}
+
+/// Pin configuration before the test
+struct SavedConfig {
+    io_mux: [u32; GPIO_COUNT],
+    pin: [u32; GPIO_COUNT],
+    out_sel: [u32; GPIO_COUNT],
+    output_enable: u64,
+    output: u64,
+}
+
+impl IO {
+    /// Check the pins for shorts to each other and to the supply rails
+    ///
+    /// Can only be called while `self` still owns all pins, see the [module
+    /// documentation](self).
+    pub fn self_test(&mut self, config: SelfTestConfig) -> ShortReport {
+        let mut tested = EXISTING & !RESERVED & !config.exclude;
+        if !config.include_strapping {
+            tested &= !STRAPPING;
+        }
+
+        let mut report = ShortReport {
+            tested,
+            stuck_high: 0,
+            stuck_low: 0,
+            truncated: false,
+            shorts: [(0, 0); MAX_SHORTS],
+            short_count: 0,
+        };
+
+        let saved = save(tested);
+        configure_inputs(tested);
+
+        set_pulls(tested, false);
+        report.stuck_high = read_input() & tested;
+        set_pulls(tested, true);
+        report.stuck_low = !read_input() & tested;
+
+        let candidates = tested & !report.stuck_high & !report.stuck_low;
+        for a in 0..GPIO_COUNT as u8 {
+            let mask = 1u64 << a;
+            if candidates & mask == 0 {
+                continue;
+            }
+
+            let others = candidates & !mask;
+            set_output_enable(mask, true);
+
+            set_pulls(others, false);
+            set_output(mask, true);
+            let follow_high = read_input() & others;
+
+            set_pulls(others, true);
+            set_output(mask, false);
+            let follow_low = !read_input() & others;
+
+            set_output_enable(mask, false);
+
+            let shorted = follow_high & follow_low;
+            for b in a + 1..GPIO_COUNT as u8 {
+                if shorted & (1 << b) != 0 {
+                    report.add_short(a, b);
+                }
+            }
+        }
+
+        restore(tested, &saved);
+
+        report
+    }
+}
+
+fn gpio_numbers(mask: u64) -> impl Iterator<Item = usize> {
+    (0..GPIO_COUNT).filter(move |n| mask & (1 << n) != 0)
+}
+
+fn save(mask: u64) -> SavedConfig {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    let mut saved = SavedConfig {
+        io_mux: [0; GPIO_COUNT],
+        pin: [0; GPIO_COUNT],
+        out_sel: [0; GPIO_COUNT],
+        output_enable: gpio.enable.read().bits() as u64,
+        output: Bank0GpioRegisterAccess.read_output() as u64,
+    };
+
+    #[cfg(not(any(esp32c2, esp32c3)))]
+    {
+        saved.output_enable |= (gpio.enable1.read().bits() as u64) << 32;
+        saved.output |= (Bank1GpioRegisterAccess.read_output() as u64) << 32;
+    }
+
+    for n in gpio_numbers(mask) {
+        saved.io_mux[n] = get_io_mux_reg(n as u8).read().bits();
+        saved.pin[n] = gpio.pin[n].read().bits();
+        saved.out_sel[n] = gpio.func_out_sel_cfg[n].read().bits();
+    }
+
+    saved
+}
+
+fn restore(mask: u64, saved: &SavedConfig) {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    set_output(mask & saved.output, true);
+    set_output(mask & !saved.output, false);
+
+    for n in gpio_numbers(mask) {
+        gpio.pin[n].write(|w| unsafe { w.bits(saved.pin[n]) });
+        gpio.func_out_sel_cfg[n].write(|w| unsafe { w.bits(saved.out_sel[n]) });
+        get_io_mux_reg(n as u8).write(|w| unsafe { w.bits(saved.io_mux[n]) });
+    }
+
+    set_output_enable(mask & saved.output_enable, true);
+}
+
+/// Make the pins GPIO inputs, with the output driver disabled but configured
+/// as push-pull driven from the GPIO output registers
+fn configure_inputs(mask: u64) {
+    let gpio = unsafe { &*GPIO::PTR };
+
+    set_output_enable(mask, false);
+
+    for n in gpio_numbers(mask) {
+        get_io_mux_reg(n as u8)
+            .modify(|_, w| unsafe { w.mcu_sel().bits(GPIO_FUNCTION as u8).fun_ie().set_bit() });
+        gpio.pin[n].modify(|_, w| w.pad_driver().clear_bit());
+        gpio.func_out_sel_cfg[n].modify(|_, w| unsafe {
+            w.out_sel()
+                .bits(OutputSignal::GPIO as OutputSignalType)
+                .oen_sel()
+                .clear_bit()
+        });
+    }
+}
+
+/// Pull the pins up or down and wait for the levels to settle
+fn set_pulls(mask: u64, up: bool) {
+    for n in gpio_numbers(mask) {
+        get_io_mux_reg(n as u8).modify(|_, w| w.fun_wpu().bit(up).fun_wpd().bit(!up));
+    }
+
+    unsafe { crate::rom::esp_rom_delay_us(SETTLE_US) };
+}
+
+fn read_input() -> u64 {
+    #[allow(unused_mut)]
+    let mut levels = Bank0GpioRegisterAccess.read_input() as u64;
+    #[cfg(not(any(esp32c2, esp32c3)))]
+    {
+        levels |= (Bank1GpioRegisterAccess.read_input() as u64) << 32;
+    }
+    levels
+}
+
+/// Drive the pins high or low and wait for the levels to settle
+fn set_output(mask: u64, high: bool) {
+    if high {
+        Bank0GpioRegisterAccess.write_output_set(mask as u32);
+        #[cfg(not(any(esp32c2, esp32c3)))]
+        Bank1GpioRegisterAccess.write_output_set((mask >> 32) as u32);
+    } else {
+        Bank0GpioRegisterAccess.write_output_clear(mask as u32);
+        #[cfg(not(any(esp32c2, esp32c3)))]
+        Bank1GpioRegisterAccess.write_output_clear((mask >> 32) as u32);
+    }
+
+    unsafe { crate::rom::esp_rom_delay_us(SETTLE_US) };
+}
+
+fn set_output_enable(mask: u64, enable: bool) {
+    if enable {
+        Bank0GpioRegisterAccess.write_out_en_set(mask as u32);
+        #[cfg(not(any(esp32c2, esp32c3)))]
+        Bank1GpioRegisterAccess.write_out_en_set((mask >> 32) as u32);
+    } else {
+        Bank0GpioRegisterAccess.write_out_en_clear(mask as u32);
+        #[cfg(not(any(esp32c2, esp32c3)))]
+        Bank1GpioRegisterAccess.write_out_en_clear((mask >> 32) as u32);
+    }
+}
@@ -0,0 +1,219 @@
+//! PWM generated in software on any output pin
+//!
+//! For slow PWM outputs (heaters switched through an SSR, blinking LEDs) when
+//! no LEDC channel is left. The [SoftPwm] toggles up to `N` pins from the
+//! interrupt of a periodic timer, every pin with its own period and duty in
+//! units of the timer period (the tick):
+//!
+//! ```no_run
+//! let mut pwm = SoftPwm::<_, 8>::new(timer0, 100u64.micros());
+//! let heater = pwm.add(io.pins.gpio4, 1u64.secs()).unwrap();
+//! pwm.set_duty_pct(heater, 25);
+//!
+//! #[interrupt]
+//! fn TG0_T0_LEVEL() {
+//!     critical_section::with(|cs| {
+//!         SOFT_PWM.borrow_ref_mut(cs).as_mut().unwrap().on_interrupt();
+//!     });
+//! }
+//! ```
+//!
+//! [SoftPwm::on_interrupt] collects the new levels of all pins and applies
+//! them with one set and one clear write per bank, the pins switching in the
+//! same tick switch together.
+//!
+//! Timing:
+//! - periods and duties are whole ticks, the duty resolution of a pin is its
+//!   period in ticks
+//! - the timer reloads itself, so the edges don't drift, but every edge is late
+//!   by the interrupt latency, which jitters by up to one tick when the
+//!   interrupt is held off by other interrupts or critical sections
+//! - if the interrupt is held off for longer than a tick, ticks are lost and
+//!   the current periods are stretched
+//!
+//! Changes of the duty or period take effect at the start of the next period
+//! of the pin, a period is never cut short or stretched by a change.
+
+use embedded_hal::timer::CountDown;
+use fugit::MicrosDurationU64;
+
+#[cfg(not(any(esp32c2, esp32c3)))]
+use super::Bank1GpioRegisterAccess;
+use super::{Bank0GpioRegisterAccess, BankGpioRegisterAccess, OutputPin};
+use crate::timer::{Instance, Timer};
+
+#[cfg(any(esp32c2, esp32c3))]
+const BANKS: usize = 1;
+#[cfg(not(any(esp32c2, esp32c3)))]
+const BANKS: usize = 2;
+
+/// Software PWM for up to `N` pins, see the [module documentation](self)
+pub struct SoftPwm<T, const N: usize> {
+    timer: Timer<T>,
+    tick: MicrosDurationU64,
+    len: usize,
+    gpio_nums: [u8; N],
+    /// Position of every pin in its period, in ticks
+    counters: [u32; N],
+    periods: [u32; N],
+    duties: [u32; N],
+    /// Applied at the start of the next period
+    next_periods: [u32; N],
+    next_duties: [u32; N],
+}
+
+impl<T, const N: usize> SoftPwm<T, N>
+where
+    T: Instance,
+{
+    /// Tick every `tick` with `timer`
+    ///
+    /// Enables the interrupt of the timer, which has to call
+    /// [SoftPwm::on_interrupt].
+    pub fn new<Time>(mut timer: Timer<T>, tick: Time) -> Self
+    where
+        Time: Into<MicrosDurationU64>,
+    {
+        let tick = tick.into();
+
+        timer.start(tick);
+        timer.listen();
+
+        Self {
+            timer,
+            tick,
+            len: 0,
+            gpio_nums: [0; N],
+            counters: [0; N],
+            periods: [1; N],
+            duties: [0; N],
+            next_periods: [1; N],
+            next_duties: [0; N],
+        }
+    }
+
+    /// Drive `pin` with a PWM of `period` and return its index
+    ///
+    /// The pin is configured as push-pull output and starts low, with a duty
+    /// of 0. The period is rounded to whole ticks, at least one. Returns
+    /// `None` if all `N` slots are in use.
+    pub fn add<P, Time>(&mut self, mut pin: P, period: Time) -> Option<usize>
+    where
+        P: OutputPin,
+        Time: Into<MicrosDurationU64>,
+    {
+        if self.len == N {
+            return None;
+        }
+
+        pin.set_output_high(false);
+        pin.set_to_push_pull_output();
+
+        let index = self.len;
+        let period = self.ticks(period.into());
+        self.gpio_nums[index] = pin.number();
+        self.counters[index] = 0;
+        self.periods[index] = period;
+        self.duties[index] = 0;
+        self.next_periods[index] = period;
+        self.next_duties[index] = 0;
+        self.len += 1;
+
+        Some(index)
+    }
+
+    /// Change the period of pin `index` at the start of its next period
+    ///
+    /// The duty in ticks is kept, it is limited to the new period.
+    pub fn set_period<Time>(&mut self, index: usize, period: Time)
+    where
+        Time: Into<MicrosDurationU64>,
+    {
+        self.next_periods[index] = self.ticks(period.into());
+    }
+
+    /// Period of pin `index` in ticks, including a pending change
+    pub fn period_ticks(&self, index: usize) -> u32 {
+        self.next_periods[index]
+    }
+
+    /// Drive pin `index` high for `ticks` of every period, starting with its
+    /// next period
+    ///
+    /// Values above the period keep the pin high.
+    pub fn set_duty(&mut self, index: usize, ticks: u32) {
+        self.next_duties[index] = ticks;
+    }
+
+    /// Like [SoftPwm::set_duty], in percent of the period
+    pub fn set_duty_pct(&mut self, index: usize, duty_pct: u8) {
+        let ticks = self.next_periods[index] as u64 * duty_pct.min(100) as u64 / 100;
+        self.next_duties[index] = ticks as u32;
+    }
+
+    /// Stop the PWM, drive all pins low and return the timer
+    pub fn free(mut self) -> Timer<T> {
+        self.timer.unlisten();
+        self.timer.set_counter_active(false);
+
+        let mut low = [0u32; BANKS];
+        for &gpio_num in &self.gpio_nums[..self.len] {
+            low[gpio_num as usize / 32] |= 1 << (gpio_num % 32);
+        }
+        write_levels(&[0; BANKS], &low);
+
+        self.timer
+    }
+
+    /// Advance all pins by one tick
+    ///
+    /// To be called from the interrupt handler of the timer.
+    pub fn on_interrupt(&mut self) {
+        self.timer.clear_interrupt();
+        self.timer.set_alarm_active(true);
+
+        let mut high = [0u32; BANKS];
+        let mut low = [0u32; BANKS];
+
+        for index in 0..self.len {
+            let mut counter = self.counters[index];
+            if counter == 0 {
+                self.periods[index] = self.next_periods[index];
+                self.duties[index] = self.next_duties[index];
+            }
+
+            let gpio_num = self.gpio_nums[index];
+            let bit = 1 << (gpio_num % 32);
+            if counter < self.duties[index] {
+                high[gpio_num as usize / 32] |= bit;
+            } else {
+                low[gpio_num as usize / 32] |= bit;
+            }
+
+            counter += 1;
+            if counter >= self.periods[index] {
+                counter = 0;
+            }
+            self.counters[index] = counter;
+        }
+
+        write_levels(&high, &low);
+    }
+
+    fn ticks(&self, duration: MicrosDurationU64) -> u32 {
+        let tick = self.tick.ticks().max(1);
+        let ticks = (duration.ticks() + tick / 2) / tick;
+        ticks.clamp(1, u32::MAX as u64) as u32
+    }
+}
+
+#[inline(always)]
+fn write_levels(high: &[u32; BANKS], low: &[u32; BANKS]) {
+    Bank0GpioRegisterAccess.write_output_set(high[0]);
+    Bank0GpioRegisterAccess.write_output_clear(low[0]);
+    #[cfg(not(any(esp32c2, esp32c3)))]
+    {
+        Bank1GpioRegisterAccess.write_output_set(high[1]);
+        Bank1GpioRegisterAccess.write_output_clear(low[1]);
+    }
+}
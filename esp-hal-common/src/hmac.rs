@@ -0,0 +1,297 @@
+//! HMAC Accelerator
+//!
+//! The HMAC peripheral computes HMAC-SHA256 message authentication codes using
+//! a key burned into one of the eFuse key blocks. The key itself is never
+//! readable by software.
+//!
+//! The result is either returned to software (upstream mode, which requires the
+//! key block purpose to be `HMAC_UP`) or handed directly to another peripheral
+//! (downstream mode), e.g. to derive the decryption key of the Digital
+//! Signature peripheral (see [crate::ds]).
+//!
+//! Message padding is done by the driver.
+
+use crate::{
+    crypto_dma::CryptoDma,
+    efuse::{Efuse, KeyPurpose},
+    pac::HMAC,
+    system::{Peripheral, PeripheralClockControl},
+};
+
+const BLOCK_SIZE: usize = 64;
+const RESULT_SIZE: usize = 32;
+
+/// Where the HMAC result is delivered to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HmacPurpose {
+    /// Used as the key of the Digital Signature peripheral or to re-enable JTAG
+    ToDsOrJtag = 5,
+    /// Used to re-enable a soft-disabled JTAG
+    ToJtag     = 6,
+    /// Used as the key of the Digital Signature peripheral
+    ToDs       = 7,
+    /// The result is returned to software
+    ToUser     = 8,
+}
+
+impl HmacPurpose {
+    fn accepts(&self, purpose: KeyPurpose) -> bool {
+        match self {
+            HmacPurpose::ToDsOrJtag => purpose == KeyPurpose::HmacDownAll,
+            HmacPurpose::ToJtag => {
+                purpose == KeyPurpose::HmacDownAll || purpose == KeyPurpose::HmacDownJtag
+            }
+            HmacPurpose::ToDs => {
+                purpose == KeyPurpose::HmacDownAll
+                    || purpose == KeyPurpose::HmacDownDigitalSignature
+            }
+            HmacPurpose::ToUser => purpose == KeyPurpose::HmacUp,
+        }
+    }
+}
+
+/// eFuse key block used by the HMAC peripheral
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyId {
+    Key0 = 0,
+    Key1 = 1,
+    Key2 = 2,
+    Key3 = 3,
+    Key4 = 4,
+    Key5 = 5,
+}
+
+/// HMAC errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The eFuse key block has not been provisioned for the requested purpose
+    KeyPurposeMismatch { key: KeyId, configured: KeyPurpose },
+    /// `update`/`finalize` was called without configuring the peripheral for
+    /// [HmacPurpose::ToUser] first
+    NotConfigured,
+    /// The SHA accelerator used by the peripheral is in use by another driver
+    Busy,
+}
+
+/// HMAC peripheral driver
+pub struct Hmac {
+    hmac: HMAC,
+    buffer: [u8; BLOCK_SIZE],
+    buffered: usize,
+    blocks: usize,
+    length: usize,
+    configured: bool,
+    lock: Option<CryptoDma>,
+}
+
+impl Hmac {
+    pub fn new(hmac: HMAC, peripheral_clock_control: &mut PeripheralClockControl) -> Self {
+        peripheral_clock_control.enable(Peripheral::Hmac);
+
+        Self {
+            hmac,
+            buffer: [0u8; BLOCK_SIZE],
+            buffered: 0,
+            blocks: 0,
+            length: 0,
+            configured: false,
+            lock: None,
+        }
+    }
+
+    /// Starts a new HMAC operation using the given eFuse key block.
+    ///
+    /// The purpose of the key block is checked before the peripheral is
+    /// started. With [HmacPurpose::ToUser] the message can then be passed in
+    /// via [Hmac::update] and the result read with [Hmac::finalize]. With any
+    /// other purpose the result is handed directly to the downstream
+    /// peripheral and nothing else needs to be done.
+    ///
+    /// Returns [Error::Busy] while the SHA accelerator is used elsewhere, e.g.
+    /// by [crate::sha::Sha]. With [HmacPurpose::ToUser] it stays taken until
+    /// [Hmac::finalize].
+    pub fn configure(&mut self, purpose: HmacPurpose, key: KeyId) -> Result<(), Error> {
+        if self.lock.is_none() {
+            self.lock = Some(CryptoDma::try_acquire().ok_or(Error::Busy)?);
+        }
+
+        let result = self.start(purpose, key);
+        if result.is_err() || purpose != HmacPurpose::ToUser {
+            self.lock = None;
+        }
+
+        result
+    }
+
+    /// Starts the peripheral, the caller has to hold the [CryptoDma] lock
+    pub(crate) fn start(&mut self, purpose: HmacPurpose, key: KeyId) -> Result<(), Error> {
+        // every `KeyId` is a key block, `Reserved` is never accepted anyway
+        let configured = Efuse::get_key_purpose(key as u8).unwrap_or(KeyPurpose::Reserved);
+        if !purpose.accepts(configured) {
+            return Err(Error::KeyPurposeMismatch { key, configured });
+        }
+
+        while self.is_busy() {}
+
+        self.hmac.set_start.write(|w| w.set_start().set_bit());
+        self.hmac
+            .set_para_purpose
+            .write(|w| unsafe { w.purpose_set().bits(purpose as u8) });
+        self.hmac
+            .set_para_key
+            .write(|w| unsafe { w.key_set().bits(key as u8) });
+        self.hmac
+            .set_para_finish
+            .write(|w| w.set_para_end().set_bit());
+
+        while self.is_busy() {}
+
+        // the hardware does the same check, this only catches eFuses changing between
+        // our check and starting the peripheral
+        if self.hmac.query_error.read().query_check().bit_is_set() {
+            self.hmac
+                .set_result_finish
+                .write(|w| w.set_result_end().set_bit());
+            return Err(Error::KeyPurposeMismatch { key, configured });
+        }
+
+        self.buffered = 0;
+        self.blocks = 0;
+        self.length = 0;
+        self.configured = purpose == HmacPurpose::ToUser;
+
+        Ok(())
+    }
+
+    /// Adds data to the message being authenticated.
+    ///
+    /// Can be called any number of times between [Hmac::configure] and
+    /// [Hmac::finalize].
+    pub fn update(&mut self, data: &[u8]) -> Result<(), Error> {
+        if !self.configured {
+            return Err(Error::NotConfigured);
+        }
+
+        for &byte in data {
+            // only write out a full block once we know it isn't the last one, the
+            // last block is handled by `finalize`
+            if self.buffered == BLOCK_SIZE {
+                self.write_block(false);
+                self.buffered = 0;
+            }
+
+            self.buffer[self.buffered] = byte;
+            self.buffered += 1;
+        }
+        self.length += data.len();
+
+        Ok(())
+    }
+
+    /// Pads the message and reads the 32 byte HMAC-SHA256 result.
+    ///
+    /// Ends the operation; a new one has to be started with
+    /// [Hmac::configure].
+    pub fn finalize(&mut self, output: &mut [u8; RESULT_SIZE]) -> Result<(), Error> {
+        if !self.configured {
+            return Err(Error::NotConfigured);
+        }
+
+        // the hardware already processed the key block, which counts towards the
+        // message length
+        let bit_length = ((BLOCK_SIZE + self.length) as u64 * 8).to_be_bytes();
+
+        if self.buffered == BLOCK_SIZE {
+            self.write_block(false);
+            self.buffered = 0;
+        }
+
+        self.buffer[self.buffered] = 0x80;
+        self.buffer[self.buffered + 1..].fill(0);
+
+        if self.buffered + 1 + bit_length.len() > BLOCK_SIZE {
+            // the length doesn't fit anymore and goes into an additional block
+            self.write_block(false);
+            self.buffer.fill(0);
+        }
+
+        self.buffer[BLOCK_SIZE - bit_length.len()..].copy_from_slice(&bit_length);
+
+        if self.blocks == 0 {
+            self.write_block(true);
+            self.hmac.one_block.write(|w| w.set_one_block().set_bit());
+            while self.is_busy() {}
+        } else {
+            self.write_block(true);
+        }
+
+        for (i, chunk) in output.chunks_exact_mut(4).enumerate() {
+            let word = self.hmac.rd_result_mem[i].read().bits();
+            chunk.copy_from_slice(&word.to_ne_bytes());
+        }
+
+        self.hmac
+            .set_result_finish
+            .write(|w| w.set_result_end().set_bit());
+
+        self.buffered = 0;
+        self.configured = false;
+        self.lock = None;
+
+        Ok(())
+    }
+
+    /// Invalidates the HMAC result handed to the Digital Signature peripheral.
+    pub fn invalidate_ds(&mut self) {
+        self.hmac
+            .set_invalidate_ds
+            .write(|w| w.set_invalidate_ds().set_bit());
+    }
+
+    /// Invalidates the HMAC result used to re-enable JTAG.
+    pub fn invalidate_jtag(&mut self) {
+        self.hmac
+            .set_invalidate_jtag
+            .write(|w| w.set_invalidate_jtag().set_bit());
+    }
+
+    pub fn free(self) -> HMAC {
+        self.hmac
+    }
+
+    fn is_busy(&self) -> bool {
+        self.hmac.query_busy.read().busy_state().bit_is_set()
+    }
+
+    // Writes the buffer to the message memory and processes it. Every block but
+    // the first has to be announced as either a normal or the final (padded)
+    // block.
+    fn write_block(&mut self, last: bool) {
+        while self.is_busy() {}
+
+        if self.blocks > 0 {
+            if last {
+                self.hmac
+                    .set_message_pad
+                    .write(|w| w.set_text_pad().set_bit());
+            } else {
+                self.hmac
+                    .set_message_ing
+                    .write(|w| w.set_text_ing().set_bit());
+            }
+        }
+
+        for (i, chunk) in self.buffer.chunks_exact(4).enumerate() {
+            let word = u32::from_ne_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+            self.hmac.wr_message_mem[i].write(|w| unsafe { w.bits(word) });
+        }
+
+        self.hmac
+            .set_message_one
+            .write(|w| w.set_text_one().set_bit());
+
+        while self.is_busy() {}
+
+        self.blocks += 1;
+    }
+}
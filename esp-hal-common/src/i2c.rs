@@ -2,13 +2,14 @@
 //!
 //! Supports multiple I2C peripheral instances
 
-use fugit::HertzU32;
+use fugit::{HertzU32, MicrosDurationU64};
 
 use crate::{
-    clock::Clocks,
-    gpio::{InputPin, OutputPin},
+    clock::{ClockListener, Clocks},
+    gpio::{borrow_pad, pad::PadError, InputPin, OutputPin, Pull},
     pac::i2c0::{RegisterBlock, COMD},
     system::PeripheralClockControl,
+    time::Deadline,
     types::{InputSignal, OutputSignal},
 };
 
@@ -29,6 +30,16 @@ pub enum Error {
     ArbitrationLost,
     ExecIncomplete,
     CommandNrExceeded,
+    /// The [Deadline] of a `*_timeout` method passed, unlike [Error::TimeOut]
+    /// which is the bus timeout of the peripheral
+    DeadlineExpired,
+    /// The length of an SMBus block is 0 or doesn't fit into the buffer
+    InvalidBlockLength,
+    /// The SMBus packet error code doesn't match the received bytes
+    PecMismatch,
+    /// The buffer passed to [I2C::poll_transaction] is shorter than the
+    /// read, only the bytes which fit were copied
+    BufferTooSmall,
 }
 
 #[cfg(feature = "eh1")]
@@ -45,6 +56,7 @@ impl embedded_hal_1::i2c::Error for Error {
 }
 
 /// A generic I2C Command
+#[derive(Clone, Copy, Debug, PartialEq)]
 enum Command {
     Start,
     Stop,
@@ -124,12 +136,13 @@ impl From<Command> for u16 {
     }
 }
 
+#[derive(Clone, Copy, PartialEq)]
 enum OperationType {
     Write = 0,
     Read  = 1,
 }
 
-#[derive(Eq, PartialEq, Copy, Clone)]
+#[derive(Eq, PartialEq, Copy, Clone, Debug)]
 enum Ack {
     Ack,
     Nack,
@@ -151,9 +164,62 @@ enum Opcode {
     Stop   = 3,
 }
 
+/// Maximum number of data bytes written by a non-blocking transaction, the
+/// address byte takes the last FIFO entry
+pub const NB_MAX_WRITE_LEN: usize = 31;
+
+/// Maximum number of bytes read by a non-blocking transaction
+pub const NB_MAX_READ_LEN: usize = 32;
+
+/// Maximum number of data bytes written by a blocking transaction
+pub const MAX_WRITE_LEN: usize = 254;
+
+/// Maximum number of operations of an `embedded-hal` 1.0 transaction
+pub const MAX_TRANSACTION_OPERATIONS: usize = 16;
+
+/// Size of the TX and RX FIFOs
+const FIFO_LEN: usize = 32;
+
+/// Maximum number of data bytes in an SMBus block
+pub const SMBUS_MAX_BLOCK_LEN: usize = 32;
+
+/// Time after which [I2C::scan] gives up on an address
+pub const SCAN_TIMEOUT: MicrosDurationU64 = MicrosDurationU64::millis(10);
+
+/// Pending step of a non-blocking transaction
+#[derive(Clone, Copy)]
+enum Transaction {
+    Idle,
+    Write,
+    WriteRead { address: u8, len: u8 },
+    Read { len: u8 },
+}
+
 /// I2C peripheral container (I2C)
 pub struct I2C<T> {
     peripheral: T,
+    frequency: HertzU32,
+    transaction: Transaction,
+    pec: bool,
+    sda: u8,
+    scl: u8,
+}
+
+impl<T> ClockListener for I2C<T>
+where
+    T: Instance,
+{
+    /// Re-derives the SCL timings for the configured bus frequency.
+    fn clocks_changed(&mut self, clocks: &Clocks) {
+        self.peripheral
+            .set_frequency(clocks.i2c_clock.convert(), self.frequency);
+
+        #[cfg(any(esp32c2, esp32c3, esp32s3))]
+        self.peripheral
+            .register_block()
+            .ctr
+            .modify(|_, w| w.conf_upgate().set_bit());
+    }
 }
 
 impl<T> embedded_hal::blocking::i2c::Read for I2C<T>
@@ -199,6 +265,22 @@ impl<T> embedded_hal_1::i2c::ErrorType for I2C<T> {
     type Error = Error;
 }
 
+/// The operations of [embedded_hal_1::i2c::I2c::transaction] and
+/// [embedded_hal_1::i2c::I2c::transaction_iter] run as one transfer: adjacent
+/// operations of the same kind are merged, a (repeated) start condition and
+/// the address precede each run of reads or writes, one stop condition ends
+/// the transfer. Reads of zero bytes are skipped.
+///
+/// The whole transfer is loaded into the peripheral up front, so:
+/// - the address bytes and all written bytes have to fit into the 32 byte FIFO,
+///   on the ESP32 and ESP32-S2 the read bytes as well
+/// - a run of writes takes 2 command registers, a run of reads 3 or 4 and the
+///   stop condition 1, out of 16 on the ESP32 and ESP32-S2 and 8 on the other
+///   chips. A write followed by a read fits everywhere.
+/// - at most [MAX_TRANSACTION_OPERATIONS] operations are supported
+///
+/// [embedded_hal_1::i2c::I2c::write_read] still separates the write and the
+/// read with a stop condition, like the `embedded-hal` 0.2 implementation.
 #[cfg(feature = "eh1")]
 impl<T> embedded_hal_1::i2c::I2c for I2C<T>
 where
@@ -212,11 +294,12 @@ where
         self.peripheral.master_write(address, bytes)
     }
 
-    fn write_iter<B>(&mut self, _address: u8, _bytes: B) -> Result<(), Self::Error>
+    fn write_iter<B>(&mut self, address: u8, bytes: B) -> Result<(), Self::Error>
     where
         B: IntoIterator<Item = u8>,
     {
-        todo!()
+        let bytes = collect_write(bytes)?;
+        self.peripheral.master_write(address, &bytes)
     }
 
     fn write_read(
@@ -230,30 +313,51 @@ where
 
     fn write_iter_read<B>(
         &mut self,
-        _address: u8,
-        _bytes: B,
-        _buffer: &mut [u8],
+        address: u8,
+        bytes: B,
+        buffer: &mut [u8],
     ) -> Result<(), Self::Error>
     where
         B: IntoIterator<Item = u8>,
     {
-        todo!()
+        let bytes = collect_write(bytes)?;
+        self.peripheral.master_write_read(address, &bytes, buffer)
     }
 
     fn transaction<'a>(
         &mut self,
-        _address: u8,
-        _operations: &mut [embedded_hal_1::i2c::Operation<'a>],
+        address: u8,
+        operations: &mut [embedded_hal_1::i2c::Operation<'a>],
     ) -> Result<(), Self::Error> {
-        todo!()
+        self.peripheral.master_transaction(address, operations)
     }
 
-    fn transaction_iter<'a, O>(&mut self, _address: u8, _operations: O) -> Result<(), Self::Error>
+    fn transaction_iter<'a, O>(&mut self, address: u8, operations: O) -> Result<(), Self::Error>
     where
         O: IntoIterator<Item = embedded_hal_1::i2c::Operation<'a>>,
     {
-        todo!()
+        let mut collected: heapless::Vec<_, MAX_TRANSACTION_OPERATIONS> = heapless::Vec::new();
+        for operation in operations {
+            collected
+                .push(operation)
+                .map_err(|_| Error::CommandNrExceeded)?;
+        }
+
+        self.peripheral.master_transaction(address, &mut collected)
+    }
+}
+
+/// Collect the bytes of an iterator based write
+#[cfg(feature = "eh1")]
+fn collect_write<B>(bytes: B) -> Result<heapless::Vec<u8, MAX_WRITE_LEN>, Error>
+where
+    B: IntoIterator<Item = u8>,
+{
+    let mut collected = heapless::Vec::new();
+    for byte in bytes {
+        collected.push(byte).map_err(|_| Error::ExceedingFifo)?;
     }
+    Ok(collected)
 }
 
 impl<T> I2C<T>
@@ -273,7 +377,14 @@ where
     ) -> Self {
         enable_peripheral(&i2c, peripheral_clock_control);
 
-        let mut i2c = I2C { peripheral: i2c };
+        let mut i2c = I2C {
+            peripheral: i2c,
+            frequency,
+            transaction: Transaction::Idle,
+            pec: false,
+            sda: sda.number(),
+            scl: scl.number(),
+        };
 
         // initialize SCL first to not confuse some devices like MPU6050
         scl.set_to_open_drain_output()
@@ -293,6 +404,335 @@ where
         i2c
     }
 
+    /// Recover the bus and reset the controller
+    ///
+    /// A device interrupted in the middle of a read (e.g. by a reset of the
+    /// master) may keep holding SDA low, waiting for the rest of its byte.
+    /// SCL is clocked up to nine times until SDA is released, followed by a
+    /// STOP condition. Both pads are bit-banged with [borrow_pad] and routed
+    /// back to the controller afterwards, whose FIFO, command list and state
+    /// machine are reset then.
+    ///
+    /// This is done automatically when a transaction with a deadline expires.
+    ///
+    /// The controller is reset in any case. Returns [PadError::AlreadyBorrowed]
+    /// without recovering the bus if SCL or SDA is borrowed already.
+    pub fn reset(&mut self) -> Result<(), PadError> {
+        let half_period_us = (500_000 / self.frequency.to_Hz()).max(1);
+        let delay = || unsafe { crate::rom::esp_rom_delay_us(half_period_us) };
+
+        let recovered = borrow_pad(self.scl, |scl| {
+            borrow_pad(self.sda, |sda| {
+                // both lines released, as the controller left them
+                sda.set_high();
+                sda.set_as_open_drain(Pull::Up);
+                scl.set_high();
+                scl.set_as_open_drain(Pull::Up);
+
+                for _ in 0..9 {
+                    if sda.is_high() {
+                        break;
+                    }
+                    scl.set_low();
+                    delay();
+                    scl.set_high();
+                    delay();
+                }
+
+                // STOP: SDA rises while SCL is high
+                scl.set_low();
+                delay();
+                sda.set_low();
+                delay();
+                scl.set_high();
+                delay();
+                sda.set_high();
+                delay();
+            })
+        })
+        .and_then(|recovered| recovered);
+
+        self.peripheral.reset();
+        self.transaction = Transaction::Idle;
+
+        recovered
+    }
+
+    /// Start a non-blocking write of `bytes` to `address`
+    ///
+    /// Drive it with [I2C::poll_transaction]. The whole transaction is loaded
+    /// into the FIFO at once, so at most [NB_MAX_WRITE_LEN] bytes can be
+    /// written. Returns `WouldBlock` while another transaction is pending.
+    pub fn start_write(&mut self, address: u8, bytes: &[u8]) -> nb::Result<(), Error> {
+        self.start_transaction(address, bytes, 0)
+    }
+
+    /// Start a non-blocking read of `len` bytes from `address`
+    ///
+    /// The bytes are copied into the buffer passed to
+    /// [I2C::poll_transaction] when it completes. At most [NB_MAX_READ_LEN]
+    /// bytes can be read.
+    pub fn start_read(&mut self, address: u8, len: usize) -> nb::Result<(), Error> {
+        if let Transaction::Idle = self.transaction {
+            self.check_nb_read_len(len)?;
+            self.peripheral.start_read_nb(address, len as u8)?;
+            self.transaction = Transaction::Read { len: len as u8 };
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Start a non-blocking write of `bytes` followed by a read of `len` bytes
+    ///
+    /// Like [I2C::start_write] and [I2C::start_read] in sequence, the read is
+    /// started by [I2C::poll_transaction] once the write completed.
+    pub fn start_write_read(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        len: usize,
+    ) -> nb::Result<(), Error> {
+        self.check_nb_read_len(len)?;
+        self.start_transaction(address, bytes, len)
+    }
+
+    /// Advance the pending non-blocking transaction by one step
+    ///
+    /// Never waits for the bus. Returns `WouldBlock` while the transaction is
+    /// in progress, and `Ok(())` when it completed (or if there is none).
+    /// For reads the received bytes are copied into the start of `buffer`,
+    /// it is ignored otherwise. A `buffer` shorter than the read gets the
+    /// bytes which fit and [Error::BufferTooSmall] is returned, the
+    /// transaction is complete in either case.
+    pub fn poll_transaction(&mut self, buffer: &mut [u8]) -> nb::Result<(), Error> {
+        if let Transaction::Idle = self.transaction {
+            return Ok(());
+        }
+
+        if let Err(error) = self.peripheral.poll_completion() {
+            if let nb::Error::Other(_) = error {
+                self.transaction = Transaction::Idle;
+            }
+            return Err(error);
+        }
+
+        match self.transaction {
+            Transaction::WriteRead { address, len } => {
+                if let Err(error) = self.peripheral.start_read_nb(address, len) {
+                    self.transaction = Transaction::Idle;
+                    return Err(nb::Error::Other(error));
+                }
+                self.transaction = Transaction::Read { len };
+                Err(nb::Error::WouldBlock)
+            }
+            Transaction::Read { len } => {
+                self.transaction = Transaction::Idle;
+
+                // the rest stays in the FIFO, the next transaction resets it
+                let copied = (len as usize).min(buffer.len());
+                self.peripheral.read_fifo_nb(&mut buffer[..copied]);
+                if copied < len as usize {
+                    return Err(nb::Error::Other(Error::BufferTooSmall));
+                }
+
+                Ok(())
+            }
+            _ => {
+                self.transaction = Transaction::Idle;
+                Ok(())
+            }
+        }
+    }
+
+    fn start_transaction(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        len: usize,
+    ) -> nb::Result<(), Error> {
+        if let Transaction::Idle = self.transaction {
+            if bytes.len() > NB_MAX_WRITE_LEN {
+                return Err(nb::Error::Other(Error::ExceedingFifo));
+            }
+
+            self.peripheral.start_write_nb(address, bytes)?;
+            self.transaction = if len > 0 {
+                Transaction::WriteRead {
+                    address,
+                    len: len as u8,
+                }
+            } else {
+                Transaction::Write
+            };
+            Ok(())
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Write `bytes` to `address`, giving up once `deadline` passed
+    ///
+    /// The deadline covers the whole transaction including waiting for a
+    /// pending non-blocking one. At most [NB_MAX_WRITE_LEN] bytes can be
+    /// written. On [Error::DeadlineExpired] the bus is recovered and the
+    /// controller reset (see [I2C::reset]), it's unknown how many bytes the
+    /// device received.
+    pub fn write_timeout(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        deadline: Deadline,
+    ) -> Result<(), Error> {
+        self.with_deadline(deadline, |i2c| i2c.start_write(address, bytes))?;
+        self.with_deadline(deadline, |i2c| i2c.poll_transaction(&mut []))
+    }
+
+    /// Fill `buffer` from `address`, giving up once `deadline` passed
+    ///
+    /// At most [NB_MAX_READ_LEN] bytes can be read, see
+    /// [I2C::write_timeout].
+    pub fn read_timeout(
+        &mut self,
+        address: u8,
+        buffer: &mut [u8],
+        deadline: Deadline,
+    ) -> Result<(), Error> {
+        self.with_deadline(deadline, |i2c| i2c.start_read(address, buffer.len()))?;
+        self.with_deadline(deadline, |i2c| i2c.poll_transaction(buffer))
+    }
+
+    /// Write `bytes` to `address` and fill `buffer` from it, giving up once
+    /// `deadline` passed
+    ///
+    /// The deadline covers both parts, see [I2C::write_timeout].
+    pub fn write_read_timeout(
+        &mut self,
+        address: u8,
+        bytes: &[u8],
+        buffer: &mut [u8],
+        deadline: Deadline,
+    ) -> Result<(), Error> {
+        self.with_deadline(deadline, |i2c| {
+            i2c.start_write_read(address, bytes, buffer.len())
+        })?;
+        self.with_deadline(deadline, |i2c| i2c.poll_transaction(buffer))
+    }
+
+    /// Find the devices on the bus
+    ///
+    /// Probes the addresses 0x08 to 0x77 with a write of no data bytes, and
+    /// returns those which acknowledged. Each probe is a [I2C::write_timeout]
+    /// with [SCAN_TIMEOUT], so a stuck bus (e.g. a device holding SCL low)
+    /// returns [Error::DeadlineExpired] or [Error::TimeOut] instead of
+    /// hanging.
+    pub fn scan(&mut self) -> Result<heapless::Vec<u8, 112>, Error> {
+        let mut found = heapless::Vec::new();
+
+        for address in 0x08..=0x77 {
+            match self.write_timeout(address, &[], Deadline::after(SCAN_TIMEOUT)) {
+                Ok(()) => found.push(address).unwrap(),
+                Err(Error::AckCheckFailed) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        Ok(found)
+    }
+
+    /// Append and verify the SMBus packet error code (PEC) in
+    /// [I2C::read_block] and [I2C::write_block]
+    pub fn set_pec(&mut self, enabled: bool) {
+        self.pec = enabled;
+    }
+
+    /// SMBus block read of `command` from `address`
+    ///
+    /// Returns the length of the block copied into the start of `buffer`, or
+    /// [Error::InvalidBlockLength] if it's 0 or longer than `buffer`. Like
+    /// [embedded_hal::blocking::i2c::WriteRead] the read follows a stop
+    /// condition instead of a repeated start.
+    pub fn read_block(
+        &mut self,
+        address: u8,
+        command: u8,
+        buffer: &mut [u8],
+    ) -> Result<usize, Error> {
+        let max_len = buffer.len().min(SMBUS_MAX_BLOCK_LEN);
+
+        // the count byte, the data and the PEC
+        let mut received = [0u8; SMBUS_MAX_BLOCK_LEN + 2];
+        let received = &mut received[..1 + max_len + self.pec as usize];
+        self.peripheral
+            .master_write_read(address, &[command], received)?;
+
+        let len = received[0] as usize;
+        if len == 0 || len > max_len {
+            return Err(Error::InvalidBlockLength);
+        }
+
+        if self.pec {
+            let crc = pec(0, &[address << 1, command, address << 1 | 1]);
+            if pec(crc, &received[..1 + len]) != received[1 + len] {
+                return Err(Error::PecMismatch);
+            }
+        }
+
+        buffer[..len].copy_from_slice(&received[1..][..len]);
+        Ok(len)
+    }
+
+    /// SMBus block write of `data` (1 to 32 bytes) to `command` of `address`
+    pub fn write_block(&mut self, address: u8, command: u8, data: &[u8]) -> Result<(), Error> {
+        if data.is_empty() || data.len() > SMBUS_MAX_BLOCK_LEN {
+            return Err(Error::InvalidBlockLength);
+        }
+
+        // the command, the count byte, the data and the PEC
+        let mut bytes = [0u8; SMBUS_MAX_BLOCK_LEN + 3];
+        bytes[0] = command;
+        bytes[1] = data.len() as u8;
+        bytes[2..][..data.len()].copy_from_slice(data);
+        let mut len = 2 + data.len();
+
+        if self.pec {
+            bytes[len] = pec(pec(0, &[address << 1]), &bytes[..len]);
+            len += 1;
+        }
+
+        self.peripheral.master_write(address, &bytes[..len])
+    }
+
+    /// Repeat `op` while it returns `WouldBlock`, abort the transaction once
+    /// `deadline` passed
+    fn with_deadline(
+        &mut self,
+        deadline: Deadline,
+        mut op: impl FnMut(&mut Self) -> nb::Result<(), Error>,
+    ) -> Result<(), Error> {
+        loop {
+            match op(self) {
+                Ok(()) => return Ok(()),
+                Err(nb::Error::Other(error)) => return Err(error),
+                Err(nb::Error::WouldBlock) if deadline.is_expired() => {
+                    // the deadline is reported either way, the recovery only
+                    // fails while the caller itself borrows SCL or SDA
+                    let _ = self.reset();
+                    return Err(Error::DeadlineExpired);
+                }
+                Err(nb::Error::WouldBlock) => {}
+            }
+        }
+    }
+
+    fn check_nb_read_len(&self, len: usize) -> Result<(), Error> {
+        if len == 0 || len > NB_MAX_READ_LEN {
+            Err(Error::ExceedingFifo)
+        } else {
+            Ok(())
+        }
+    }
+
     /// Return the raw interface to the underlying peripheral
     pub fn free(self) -> T {
         self.peripheral
@@ -309,6 +749,340 @@ fn enable_peripheral<T: Instance>(i2c: &T, peripheral_clock_control: &mut Periph
     }
 }
 
+/// Register values of the bus timing, in cycles of the I2C core clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct BusTiming {
+    sclk_div: u32,
+    scl_low_period: u32,
+    scl_high_period: u32,
+    scl_wait_high_period: u32,
+    sda_hold_time: u32,
+    sda_sample_time: u32,
+    scl_rstart_setup_time: u32,
+    scl_stop_setup_time: u32,
+    scl_start_hold_time: u32,
+    scl_stop_hold_time: u32,
+    time_out_value: u32,
+    time_out_en: bool,
+}
+
+/// Field by field `==` of two timings, for the checks of `bus_timing`
+const fn same_timing(a: BusTiming, b: BusTiming) -> bool {
+    a.sclk_div == b.sclk_div
+        && a.scl_low_period == b.scl_low_period
+        && a.scl_high_period == b.scl_high_period
+        && a.scl_wait_high_period == b.scl_wait_high_period
+        && a.sda_hold_time == b.sda_hold_time
+        && a.sda_sample_time == b.sda_sample_time
+        && a.scl_rstart_setup_time == b.scl_rstart_setup_time
+        && a.scl_stop_setup_time == b.scl_stop_setup_time
+        && a.scl_start_hold_time == b.scl_start_hold_time
+        && a.scl_stop_hold_time == b.scl_stop_hold_time
+        && a.time_out_value == b.time_out_value
+        && a.time_out_en == b.time_out_en
+}
+
+#[cfg(esp32)]
+/// Calculates the timings of `bus_freq` - corresponds to i2c_ll_cal_bus_clk
+/// and i2c_ll_set_bus_timing in ESP-IDF
+const fn bus_timing(source_clk: u32, bus_freq: u32) -> BusTiming {
+    let half_cycle: u32 = source_clk / bus_freq / 2;
+    let scl_low = half_cycle;
+    let scl_high = half_cycle;
+    let sda_hold = half_cycle / 2;
+    let sda_sample = scl_high / 2;
+    let setup = half_cycle;
+    let hold = half_cycle;
+    let tout = half_cycle * 20; // default we set the timeout value to 10 bus cycles.
+
+    // SCL period. According to the TRM, we should always subtract 1 to SCL low
+    // period
+    let scl_low = scl_low - 1;
+    // Still according to the TRM, if filter is not enbled, we have to subtract 7,
+    // if SCL filter is enabled, we have to subtract:
+    //   8 if SCL filter is between 0 and 2 (included)
+    //   6 + SCL threshold if SCL filter is between 3 and 7 (included)
+    // to SCL high period
+    let mut scl_high = scl_high;
+    // In the "worst" case, we will subtract 13, make sure the result will still be
+    // correct
+
+    // FIXME since we always set the filter threshold to 7 we don't need conditional
+    // code here once that changes we need the conditional code here
+    scl_high -= 7 + 6;
+
+    // if (filter_cfg_en) {
+    //     if (thres <= 2) {
+    //         scl_high -= 8;
+    //     } else {
+    //         assert(hw->scl_filter_cfg.thres <= 7);
+    //         scl_high -= thres + 6;
+    //     }
+    // } else {
+    //    scl_high -= 7;
+    //}
+
+    BusTiming {
+        sclk_div: 0,
+        scl_low_period: scl_low,
+        scl_high_period: scl_high,
+        scl_wait_high_period: 0,
+        // sda sample
+        sda_hold_time: sda_hold,
+        sda_sample_time: sda_sample,
+        // setup
+        scl_rstart_setup_time: setup,
+        scl_stop_setup_time: setup,
+        // hold
+        scl_start_hold_time: hold,
+        scl_stop_hold_time: hold,
+        time_out_value: tout,
+        time_out_en: true,
+    }
+}
+
+// 100 kHz and 400 kHz from the 80 MHz APB clock, the filter takes 13 cycles
+// of the high period
+#[cfg(esp32)]
+const _: () = {
+    assert!(same_timing(
+        bus_timing(80_000_000, 100_000),
+        BusTiming {
+            sclk_div: 0,
+            scl_low_period: 399,
+            scl_high_period: 387,
+            scl_wait_high_period: 0,
+            sda_hold_time: 200,
+            sda_sample_time: 200,
+            scl_rstart_setup_time: 400,
+            scl_stop_setup_time: 400,
+            scl_start_hold_time: 400,
+            scl_stop_hold_time: 400,
+            time_out_value: 8000,
+            time_out_en: true,
+        }
+    ));
+
+    assert!(same_timing(
+        bus_timing(80_000_000, 400_000),
+        BusTiming {
+            sclk_div: 0,
+            scl_low_period: 99,
+            scl_high_period: 87,
+            scl_wait_high_period: 0,
+            sda_hold_time: 50,
+            sda_sample_time: 50,
+            scl_rstart_setup_time: 100,
+            scl_stop_setup_time: 100,
+            scl_start_hold_time: 100,
+            scl_stop_hold_time: 100,
+            time_out_value: 2000,
+            time_out_en: true,
+        }
+    ));
+};
+
+#[cfg(esp32s2)]
+/// Calculates the timings of `bus_freq` - corresponds to i2c_ll_cal_bus_clk
+/// and i2c_ll_set_bus_timing in ESP-IDF
+const fn bus_timing(source_clk: u32, bus_freq: u32) -> BusTiming {
+    let half_cycle: u32 = source_clk / bus_freq / 2;
+    // SCL
+    let scl_low = half_cycle;
+    // default, scl_wait_high < scl_high
+    let scl_high = half_cycle / 2 + 2;
+    let scl_wait_high = half_cycle - scl_high;
+    let sda_hold = half_cycle / 2;
+    // scl_wait_high < sda_sample <= scl_high
+    let sda_sample = half_cycle / 2 - 1;
+    let setup = half_cycle;
+    let hold = half_cycle;
+    // default we set the timeout value to 10 bus cycles
+    let tout = half_cycle * 20;
+
+    BusTiming {
+        sclk_div: 0,
+        // scl period
+        scl_low_period: scl_low - 1,
+        scl_high_period: scl_high,
+        scl_wait_high_period: scl_wait_high,
+        // sda sample
+        sda_hold_time: sda_hold,
+        sda_sample_time: sda_sample,
+        // setup
+        scl_rstart_setup_time: setup,
+        scl_stop_setup_time: setup,
+        // hold
+        scl_start_hold_time: hold - 1,
+        scl_stop_hold_time: hold,
+        time_out_value: tout,
+        time_out_en: true,
+    }
+}
+
+// 100 kHz and 400 kHz from the 80 MHz APB clock
+#[cfg(esp32s2)]
+const _: () = {
+    assert!(same_timing(
+        bus_timing(80_000_000, 100_000),
+        BusTiming {
+            sclk_div: 0,
+            scl_low_period: 399,
+            scl_high_period: 202,
+            scl_wait_high_period: 198,
+            sda_hold_time: 200,
+            sda_sample_time: 199,
+            scl_rstart_setup_time: 400,
+            scl_stop_setup_time: 400,
+            scl_start_hold_time: 399,
+            scl_stop_hold_time: 400,
+            time_out_value: 8000,
+            time_out_en: true,
+        }
+    ));
+
+    assert!(same_timing(
+        bus_timing(80_000_000, 400_000),
+        BusTiming {
+            sclk_div: 0,
+            scl_low_period: 99,
+            scl_high_period: 52,
+            scl_wait_high_period: 48,
+            sda_hold_time: 50,
+            sda_sample_time: 49,
+            scl_rstart_setup_time: 100,
+            scl_stop_setup_time: 100,
+            scl_start_hold_time: 99,
+            scl_stop_hold_time: 100,
+            time_out_value: 2000,
+            time_out_en: true,
+        }
+    ));
+};
+
+#[cfg(any(esp32c2, esp32c3, esp32s3))]
+/// Calculates the timings of `bus_freq` - corresponds to i2c_ll_cal_bus_clk
+/// and i2c_ll_set_bus_timing in ESP-IDF
+const fn bus_timing(source_clk: u32, bus_freq: u32) -> BusTiming {
+    let clkm_div: u32 = source_clk / (bus_freq * 1024) + 1;
+    let sclk_freq: u32 = source_clk / clkm_div;
+    let half_cycle: u32 = sclk_freq / bus_freq / 2;
+    // SCL
+    let scl_low = half_cycle;
+    // default, scl_wait_high < scl_high
+    // Make 80KHz as a boundary here, because when working at lower frequency, too
+    // much scl_wait_high will faster the frequency according to some
+    // hardware behaviors.
+    let scl_wait_high = if bus_freq >= 80 * 1000 {
+        half_cycle / 2 - 2
+    } else {
+        half_cycle / 4
+    };
+    let scl_high = half_cycle - scl_wait_high;
+    let sda_hold = half_cycle / 4;
+    let sda_sample = half_cycle / 2 + scl_wait_high;
+    let setup = half_cycle;
+    let hold = half_cycle;
+    // default we set the timeout value to about 10 bus cycles
+    // log(20*half_cycle)/log(2) = log(half_cycle)/log(2) +  log(20)/log(2)
+    let tout = (4 * 8 - (5 * half_cycle).leading_zeros()) + 2;
+
+    // According to the Technical Reference Manual, the following timings must be
+    // subtracted by 1. However, according to the practical measurement and
+    // some hardware behaviour, if wait_high_period and scl_high minus one.
+    // The SCL frequency would be a little higher than expected. Therefore, the
+    // solution here is not to minus scl_high as well as scl_wait high, and
+    // the frequency will be absolutely accurate to all frequency
+    // to some extent.
+    BusTiming {
+        sclk_div: clkm_div,
+        scl_low_period: scl_low - 1,
+        scl_high_period: scl_high,
+        scl_wait_high_period: scl_wait_high,
+        // sda sample
+        sda_hold_time: sda_hold - 1,
+        sda_sample_time: sda_sample - 1,
+        // setup
+        scl_rstart_setup_time: setup - 1,
+        scl_stop_setup_time: setup - 1,
+        // hold
+        scl_start_hold_time: hold - 1,
+        scl_stop_hold_time: hold - 1,
+        time_out_value: tout,
+        time_out_en: true,
+    }
+}
+
+// 100 kHz and 400 kHz from a 40 MHz crystal
+#[cfg(any(esp32c2, esp32c3, esp32s3))]
+const _: () = {
+    assert!(same_timing(
+        bus_timing(40_000_000, 100_000),
+        BusTiming {
+            sclk_div: 1,
+            scl_low_period: 199,
+            scl_high_period: 102,
+            scl_wait_high_period: 98,
+            sda_hold_time: 49,
+            sda_sample_time: 197,
+            scl_rstart_setup_time: 199,
+            scl_stop_setup_time: 199,
+            scl_start_hold_time: 199,
+            scl_stop_hold_time: 199,
+            time_out_value: 12,
+            time_out_en: true,
+        }
+    ));
+
+    assert!(same_timing(
+        bus_timing(40_000_000, 400_000),
+        BusTiming {
+            sclk_div: 1,
+            scl_low_period: 49,
+            scl_high_period: 27,
+            scl_wait_high_period: 23,
+            sda_hold_time: 11,
+            sda_sample_time: 47,
+            scl_rstart_setup_time: 49,
+            scl_stop_setup_time: 49,
+            scl_start_hold_time: 49,
+            scl_stop_hold_time: 49,
+            time_out_value: 10,
+            time_out_en: true,
+        }
+    ));
+
+    // below 80 kHz the core clock is divided and the wait high period is a
+    // quarter of the half cycle
+    assert!(same_timing(
+        bus_timing(40_000_000, 10_000),
+        BusTiming {
+            sclk_div: 4,
+            scl_low_period: 499,
+            scl_high_period: 375,
+            scl_wait_high_period: 125,
+            sda_hold_time: 124,
+            sda_sample_time: 374,
+            scl_rstart_setup_time: 499,
+            scl_stop_setup_time: 499,
+            scl_start_hold_time: 499,
+            scl_stop_hold_time: 499,
+            time_out_value: 14,
+            time_out_en: true,
+        }
+    ));
+
+    // SCLK_DIV_NUM is 8 bits wide, the periods 9 bits
+    let bus_freqs = [1_000, 5_000, 50_000, 100_000, 400_000, 1_000_000];
+    let mut i = 0;
+    while i < bus_freqs.len() {
+        let timing = bus_timing(40_000_000, bus_freqs[i]);
+        assert!(timing.sclk_div >= 1 && timing.sclk_div <= 256);
+        assert!(timing.scl_low_period < 512 && timing.scl_high_period < 512);
+        i += 1;
+    }
+};
+
 /// I2C Peripheral Instance
 pub trait Instance {
     fn register_block(&self) -> &RegisterBlock;
@@ -424,200 +1198,24 @@ pub trait Instance {
         }
     }
 
-    #[cfg(esp32)]
-    /// Sets the frequency of the I2C interface by calculating and applying the
-    /// associated timings - corresponds to i2c_ll_cal_bus_clk and
-    /// i2c_ll_set_bus_timing in ESP-IDF
-    fn set_frequency(&mut self, source_clk: HertzU32, bus_freq: HertzU32) {
-        let source_clk = source_clk.raw();
-        let bus_freq = bus_freq.raw();
-
-        let half_cycle: u32 = source_clk / bus_freq / 2;
-        let scl_low = half_cycle;
-        let scl_high = half_cycle;
-        let sda_hold = half_cycle / 2;
-        let sda_sample = scl_high / 2;
-        let setup = half_cycle;
-        let hold = half_cycle;
-        let tout = half_cycle * 20; // default we set the timeout value to 10 bus cycles.
-
-        // SCL period. According to the TRM, we should always subtract 1 to SCL low
-        // period
-        let scl_low = scl_low - 1;
-        // Still according to the TRM, if filter is not enbled, we have to subtract 7,
-        // if SCL filter is enabled, we have to subtract:
-        //   8 if SCL filter is between 0 and 2 (included)
-        //   6 + SCL threshold if SCL filter is between 3 and 7 (included)
-        // to SCL high period
-        let mut scl_high = scl_high;
-        // In the "worst" case, we will subtract 13, make sure the result will still be
-        // correct
-
-        // FIXME since we always set the filter threshold to 7 we don't need conditional
-        // code here once that changes we need the conditional code here
-        scl_high -= 7 + 6;
-
-        // if (filter_cfg_en) {
-        //     if (thres <= 2) {
-        //         scl_high -= 8;
-        //     } else {
-        //         assert(hw->scl_filter_cfg.thres <= 7);
-        //         scl_high -= thres + 6;
-        //     }
-        // } else {
-        //    scl_high -= 7;
-        //}
-
-        let scl_high_period = scl_high;
-        let scl_low_period = scl_low;
-        // sda sample
-        let sda_hold_time = sda_hold;
-        let sda_sample_time = sda_sample;
-        // setup
-        let scl_rstart_setup_time = setup;
-        let scl_stop_setup_time = setup;
-        // hold
-        let scl_start_hold_time = hold;
-        let scl_stop_hold_time = hold;
-        let time_out_value = tout;
-
-        self.configure_clock(
-            0,
-            scl_low_period,
-            scl_high_period,
-            0,
-            sda_hold_time,
-            sda_sample_time,
-            scl_rstart_setup_time,
-            scl_stop_setup_time,
-            scl_start_hold_time,
-            scl_stop_hold_time,
-            time_out_value,
-            true,
-        );
-    }
-
-    #[cfg(esp32s2)]
-    /// Sets the frequency of the I2C interface by calculating and applying the
-    /// associated timings - corresponds to i2c_ll_cal_bus_clk and
-    /// i2c_ll_set_bus_timing in ESP-IDF
-    fn set_frequency(&mut self, source_clk: HertzU32, bus_freq: HertzU32) {
-        let source_clk = source_clk.raw();
-        let bus_freq = bus_freq.raw();
-
-        let half_cycle: u32 = source_clk / bus_freq / 2;
-        // SCL
-        let scl_low = half_cycle;
-        // default, scl_wait_high < scl_high
-        let scl_high = half_cycle / 2 + 2;
-        let scl_wait_high = half_cycle - scl_high;
-        let sda_hold = half_cycle / 2;
-        // scl_wait_high < sda_sample <= scl_high
-        let sda_sample = half_cycle / 2 - 1;
-        let setup = half_cycle;
-        let hold = half_cycle;
-        // default we set the timeout value to 10 bus cycles
-        let tout = half_cycle * 20;
-
-        // scl period
-        let scl_low_period = scl_low - 1;
-        let scl_high_period = scl_high;
-        let scl_wait_high_period = scl_wait_high;
-        // sda sample
-        let sda_hold_time = sda_hold;
-        let sda_sample_time = sda_sample;
-        // setup
-        let scl_rstart_setup_time = setup;
-        let scl_stop_setup_time = setup;
-        // hold
-        let scl_start_hold_time = hold - 1;
-        let scl_stop_hold_time = hold;
-        let time_out_value = tout;
-        let time_out_en = true;
-
-        self.configure_clock(
-            0,
-            scl_low_period,
-            scl_high_period,
-            scl_wait_high_period,
-            sda_hold_time,
-            sda_sample_time,
-            scl_rstart_setup_time,
-            scl_stop_setup_time,
-            scl_start_hold_time,
-            scl_stop_hold_time,
-            time_out_value,
-            time_out_en,
-        );
-    }
-
-    #[cfg(any(esp32c2, esp32c3, esp32s3))]
     /// Sets the frequency of the I2C interface by calculating and applying the
-    /// associated timings - corresponds to i2c_ll_cal_bus_clk and
-    /// i2c_ll_set_bus_timing in ESP-IDF
+    /// associated timings
     fn set_frequency(&mut self, source_clk: HertzU32, bus_freq: HertzU32) {
-        let source_clk = source_clk.raw();
-        let bus_freq = bus_freq.raw();
-
-        let clkm_div: u32 = source_clk / (bus_freq * 1024) + 1;
-        let sclk_freq: u32 = source_clk / clkm_div;
-        let half_cycle: u32 = sclk_freq / bus_freq / 2;
-        // SCL
-        let clkm_div = clkm_div;
-        let scl_low = half_cycle;
-        // default, scl_wait_high < scl_high
-        // Make 80KHz as a boundary here, because when working at lower frequency, too
-        // much scl_wait_high will faster the frequency according to some
-        // hardware behaviors.
-        let scl_wait_high = if bus_freq >= 80 * 1000 {
-            half_cycle / 2 - 2
-        } else {
-            half_cycle / 4
-        };
-        let scl_high = half_cycle - scl_wait_high;
-        let sda_hold = half_cycle / 4;
-        let sda_sample = half_cycle / 2 + scl_wait_high;
-        let setup = half_cycle;
-        let hold = half_cycle;
-        // default we set the timeout value to about 10 bus cycles
-        // log(20*half_cycle)/log(2) = log(half_cycle)/log(2) +  log(20)/log(2)
-        let tout = (4 * 8 - (5 * half_cycle).leading_zeros()) + 2;
-
-        // According to the Technical Reference Manual, the following timings must be
-        // subtracted by 1. However, according to the practical measurement and
-        // some hardware behaviour, if wait_high_period and scl_high minus one.
-        // The SCL frequency would be a little higher than expected. Therefore, the
-        // solution here is not to minus scl_high as well as scl_wait high, and
-        // the frequency will be absolutely accurate to all frequency
-        // to some extent.
-        let scl_low_period = scl_low - 1;
-        let scl_high_period = scl_high;
-        let scl_wait_high_period = scl_wait_high;
-        // sda sample
-        let sda_hold_time = sda_hold - 1;
-        let sda_sample_time = sda_sample - 1;
-        // setup
-        let scl_rstart_setup_time = setup - 1;
-        let scl_stop_setup_time = setup - 1;
-        // hold
-        let scl_start_hold_time = hold - 1;
-        let scl_stop_hold_time = hold - 1;
-        let time_out_value = tout;
-        let time_out_en = true;
+        let timing = bus_timing(source_clk.raw(), bus_freq.raw());
 
         self.configure_clock(
-            clkm_div,
-            scl_low_period,
-            scl_high_period,
-            scl_wait_high_period,
-            sda_hold_time,
-            sda_sample_time,
-            scl_rstart_setup_time,
-            scl_stop_setup_time,
-            scl_start_hold_time,
-            scl_stop_hold_time,
-            time_out_value,
-            time_out_en,
+            timing.sclk_div,
+            timing.scl_low_period,
+            timing.scl_high_period,
+            timing.scl_wait_high_period,
+            timing.sda_hold_time,
+            timing.sda_sample_time,
+            timing.scl_rstart_setup_time,
+            timing.scl_stop_setup_time,
+            timing.scl_start_hold_time,
+            timing.scl_stop_hold_time,
+            timing.time_out_value,
+            timing.time_out_en,
         );
     }
 
@@ -736,7 +1334,7 @@ pub trait Instance {
     where
         I: Iterator<Item = &'a COMD>,
     {
-        if bytes.len() > 254 {
+        if bytes.len() > MAX_WRITE_LEN {
             // we could support more by adding multiple write operations
             return Err(Error::ExceedingFifo);
         }
@@ -1133,6 +1731,109 @@ pub trait Instance {
             .write(|w| w.rxfifo_full_int_clr().set_bit());
     }
 
+    /// Load a write transaction fitting into the FIFO and start it
+    fn start_write_nb(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.reset_fifo();
+        self.reset_command_list();
+        self.clear_all_interrupts();
+
+        let cmd_iterator = &mut self.register_block().comd.iter();
+        add_cmd(cmd_iterator, Command::Start)?;
+        add_cmd(
+            cmd_iterator,
+            Command::Write {
+                ack_exp: Ack::Ack,
+                ack_check_en: true,
+                length: 1 + bytes.len() as u8,
+            },
+        )?;
+        add_cmd(cmd_iterator, Command::Stop)?;
+
+        self.update_config();
+
+        write_fifo(
+            self.register_block(),
+            addr << 1 | OperationType::Write as u8,
+        );
+        for byte in bytes {
+            write_fifo(self.register_block(), *byte);
+        }
+
+        self.start_transmission();
+
+        Ok(())
+    }
+
+    /// Start a read transaction fitting into the FIFO
+    fn start_read_nb(&mut self, addr: u8, len: u8) -> Result<(), Error> {
+        self.reset_fifo();
+        self.reset_command_list();
+        self.clear_all_interrupts();
+
+        let cmd_iterator = &mut self.register_block().comd.iter();
+        add_cmd(cmd_iterator, Command::Start)?;
+        add_cmd(
+            cmd_iterator,
+            Command::Write {
+                ack_exp: Ack::Ack,
+                ack_check_en: true,
+                length: 1,
+            },
+        )?;
+        if len > 1 {
+            add_cmd(
+                cmd_iterator,
+                Command::Read {
+                    ack_value: Ack::Ack,
+                    length: len - 1,
+                },
+            )?;
+        }
+        add_cmd(
+            cmd_iterator,
+            Command::Read {
+                ack_value: Ack::Nack,
+                length: 1,
+            },
+        )?;
+        add_cmd(cmd_iterator, Command::Stop)?;
+
+        self.update_config();
+
+        write_fifo(self.register_block(), addr << 1 | OperationType::Read as u8);
+
+        self.start_transmission();
+
+        Ok(())
+    }
+
+    /// Check once if the started transaction completed, without waiting
+    fn poll_completion(&self) -> nb::Result<(), Error> {
+        self.check_errors()?;
+
+        let interrupts = self.register_block().int_raw.read();
+        if interrupts.trans_complete_int_raw().bit_is_clear()
+            && interrupts.end_detect_int_raw().bit_is_clear()
+        {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        for cmd in self.register_block().comd.iter() {
+            if cmd.read().command().bits() != 0x0 && cmd.read().command_done().bit_is_clear() {
+                return Err(nb::Error::Other(Error::ExecIncomplete));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copy the bytes of a completed read out of the FIFO
+    fn read_fifo_nb(&self, buffer: &mut [u8]) {
+        for byte in buffer.iter_mut() {
+            *byte = read_fifo(self.register_block());
+        }
+    }
+
     /// Send data bytes from the `bytes` array to a target slave with the
     /// address `addr`
     fn master_write(&mut self, addr: u8, bytes: &[u8]) -> Result<(), Error> {
@@ -1170,8 +1871,380 @@ pub trait Instance {
         self.master_read(addr, buffer)?;
         Ok(())
     }
+
+    /// Run the operations of an `embedded-hal` 1.0 transaction as one
+    /// transfer, see `transaction_commands`
+    #[cfg(feature = "eh1")]
+    fn master_transaction(
+        &mut self,
+        addr: u8,
+        operations: &mut [embedded_hal_1::i2c::Operation<'_>],
+    ) -> Result<(), Error> {
+        use embedded_hal_1::i2c::Operation;
+
+        let mut operation_runs: heapless::Vec<_, MAX_TRANSACTION_OPERATIONS> = heapless::Vec::new();
+        for run in operations.iter().filter_map(operation_run) {
+            operation_runs
+                .push(run)
+                .map_err(|_| Error::CommandNrExceeded)?;
+        }
+        let commands = transaction_commands(&operation_runs)?;
+        if commands.len == 0 {
+            return Ok(());
+        }
+
+        // these chips only read the FIFO once the transfer completed
+        #[cfg(any(esp32, esp32s2))]
+        if operations
+            .iter()
+            .filter_map(operation_run)
+            .filter(|(kind, _)| *kind == OperationType::Read)
+            .map(|(_, len)| len)
+            .sum::<usize>()
+            > FIFO_LEN
+        {
+            return Err(Error::ExceedingFifo);
+        }
+
+        self.reset_fifo();
+        self.reset_command_list();
+        self.clear_all_interrupts();
+
+        let cmd_iterator = &mut self.register_block().comd.iter();
+        for &command in commands.as_slice() {
+            add_cmd(cmd_iterator, command)?;
+        }
+
+        self.update_config();
+
+        // the address at the start of each run, followed by the written bytes
+        let mut previous = None;
+        for operation in operations.iter() {
+            let (kind, _) = match operation_run(operation) {
+                Some(run) => run,
+                None => continue,
+            };
+            if previous != Some(kind) {
+                write_fifo(self.register_block(), addr << 1 | kind as u8);
+                previous = Some(kind);
+            }
+            if let Operation::Write(bytes) = operation {
+                for byte in bytes.iter() {
+                    write_fifo(self.register_block(), *byte);
+                }
+            }
+        }
+
+        self.start_transmission();
+
+        for operation in operations.iter_mut() {
+            if let Operation::Read(buffer) = operation {
+                self.read_all_from_fifo(buffer)?;
+            }
+        }
+
+        self.wait_for_completion()
+    }
 }
 
+/// SMBus packet error code, CRC-8 with the polynomial x^8 + x^2 + x + 1,
+/// continued from `crc`
+const fn pec(mut crc: u8, bytes: &[u8]) -> u8 {
+    let mut i = 0;
+    while i < bytes.len() {
+        crc ^= bytes[i];
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 0x80 != 0 {
+                crc << 1 ^ 0x07
+            } else {
+                crc << 1
+            };
+            bit += 1;
+        }
+        i += 1;
+    }
+    crc
+}
+
+// the check value of CRC-8/SMBUS
+const _: () = assert!(pec(0, b"123456789") == 0xf4);
+
+/// Kind and length of an `embedded-hal` 1.0 operation, `None` for a read of
+/// zero bytes which transfers nothing
+#[cfg(feature = "eh1")]
+fn operation_run(operation: &embedded_hal_1::i2c::Operation<'_>) -> Option<(OperationType, usize)> {
+    use embedded_hal_1::i2c::Operation;
+
+    match operation {
+        Operation::Write(bytes) => Some((OperationType::Write, bytes.len())),
+        Operation::Read(buffer) if buffer.is_empty() => None,
+        Operation::Read(buffer) => Some((OperationType::Read, buffer.len())),
+    }
+}
+
+/// Commands of a transfer, see `transaction_commands`
+struct CommandList {
+    commands: [Command; 16],
+    len: usize,
+}
+
+impl CommandList {
+    const fn push(mut self, command: Command) -> Self {
+        self.commands[self.len] = command;
+        self.len += 1;
+        self
+    }
+
+    #[cfg(feature = "eh1")]
+    fn as_slice(&self) -> &[Command] {
+        &self.commands[..self.len]
+    }
+}
+
+/// Commands of a transfer made of operations of the given kinds and lengths
+///
+/// Adjacent operations of the same kind are merged into one run. Each run
+/// starts with a (repeated) start condition and the address byte, the last
+/// byte of a run of reads is not acknowledged. One stop condition ends the
+/// transfer. No commands are returned for no operations.
+const fn transaction_commands(operations: &[(OperationType, usize)]) -> Result<CommandList, Error> {
+    let mut runs = [(OperationType::Write, 0); 8];
+    let mut run_count = 0;
+    let mut i = 0;
+    while i < operations.len() {
+        let (kind, len) = operations[i];
+        if run_count > 0 && runs[run_count - 1].0 as u8 == kind as u8 {
+            runs[run_count - 1].1 += len;
+        } else if run_count == runs.len() {
+            return Err(Error::CommandNrExceeded);
+        } else {
+            runs[run_count] = (kind, len);
+            run_count += 1;
+        }
+        i += 1;
+    }
+
+    let mut commands = CommandList {
+        commands: [Command::Stop; 16],
+        len: 0,
+    };
+    if run_count == 0 {
+        return Ok(commands);
+    }
+
+    // the address byte of each run and the written bytes are loaded up front
+    let mut tx_len = 0;
+    let mut i = 0;
+    while i < run_count {
+        tx_len += match runs[i] {
+            (OperationType::Write, len) => 1 + len,
+            (OperationType::Read, _) => 1,
+        };
+        i += 1;
+    }
+    if tx_len > FIFO_LEN {
+        return Err(Error::ExceedingFifo);
+    }
+
+    let mut i = 0;
+    while i < run_count {
+        let (kind, len) = runs[i];
+        if matches!(kind, OperationType::Read) && len > 255 {
+            return Err(Error::ExceedingFifo);
+        }
+
+        // the start, the address byte and the reads of the run, followed by
+        // the stop condition
+        let needed = match kind {
+            OperationType::Write => 2,
+            OperationType::Read if len > 1 => 4,
+            OperationType::Read => 3,
+        };
+        if commands.len + needed + 1 > commands.commands.len() {
+            return Err(Error::CommandNrExceeded);
+        }
+
+        commands = commands.push(Command::Start);
+        match kind {
+            OperationType::Write => {
+                commands = commands.push(Command::Write {
+                    ack_exp: Ack::Ack,
+                    ack_check_en: true,
+                    length: 1 + len as u8,
+                });
+            }
+            OperationType::Read => {
+                commands = commands.push(Command::Write {
+                    ack_exp: Ack::Ack,
+                    ack_check_en: true,
+                    length: 1,
+                });
+                if len > 1 {
+                    commands = commands.push(Command::Read {
+                        ack_value: Ack::Ack,
+                        length: len as u8 - 1,
+                    });
+                }
+                commands = commands.push(Command::Read {
+                    ack_value: Ack::Nack,
+                    length: 1,
+                });
+            }
+        }
+        i += 1;
+    }
+
+    Ok(commands.push(Command::Stop))
+}
+
+const _: () = {
+    const fn write(length: u8) -> Command {
+        Command::Write {
+            ack_exp: Ack::Ack,
+            ack_check_en: true,
+            length,
+        }
+    }
+
+    const fn read(ack_value: Ack, length: u8) -> Command {
+        Command::Read { ack_value, length }
+    }
+
+    /// Whether `operations` result in exactly the `expected` commands
+    const fn commands(operations: &[(OperationType, usize)], expected: &[Command]) -> bool {
+        let commands = match transaction_commands(operations) {
+            Ok(commands) => commands,
+            Err(_) => return false,
+        };
+        if commands.len != expected.len() {
+            return false;
+        }
+
+        let mut i = 0;
+        while i < expected.len() {
+            let same = match (commands.commands[i], expected[i]) {
+                (Command::Start, Command::Start) | (Command::Stop, Command::Stop) => true,
+                (
+                    Command::Write {
+                        ack_exp,
+                        ack_check_en,
+                        length,
+                    },
+                    Command::Write {
+                        ack_exp: expected_ack_exp,
+                        ack_check_en: expected_ack_check_en,
+                        length: expected_length,
+                    },
+                ) => {
+                    ack_exp as u8 == expected_ack_exp as u8
+                        && ack_check_en == expected_ack_check_en
+                        && length == expected_length
+                }
+                (
+                    Command::Read { ack_value, length },
+                    Command::Read {
+                        ack_value: expected_ack_value,
+                        length: expected_length,
+                    },
+                ) => ack_value as u8 == expected_ack_value as u8 && length == expected_length,
+                _ => false,
+            };
+            if !same {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    use OperationType::{Read, Write};
+
+    assert!(commands(
+        &[(Write, 2), (Read, 3)],
+        &[
+            Command::Start,
+            write(3),
+            Command::Start,
+            write(1),
+            read(Ack::Ack, 2),
+            read(Ack::Nack, 1),
+            Command::Stop,
+        ]
+    ));
+    // adjacent operations of the same kind are merged
+    assert!(commands(
+        &[(Read, 2), (Read, 2)],
+        &[
+            Command::Start,
+            write(1),
+            read(Ack::Ack, 3),
+            read(Ack::Nack, 1),
+            Command::Stop,
+        ]
+    ));
+    assert!(commands(
+        &[(Write, 1), (Write, 2)],
+        &[Command::Start, write(4), Command::Stop]
+    ));
+    assert!(commands(
+        &[(Read, 1), (Write, 1), (Read, 1)],
+        &[
+            Command::Start,
+            write(1),
+            read(Ack::Nack, 1),
+            Command::Start,
+            write(2),
+            Command::Start,
+            write(1),
+            read(Ack::Nack, 1),
+            Command::Stop,
+        ]
+    ));
+    assert!(commands(&[], &[]));
+
+    // the written bytes and the address of every run fit into the FIFO
+    assert!(transaction_commands(&[(Write, FIFO_LEN - 1)]).is_ok());
+    assert!(matches!(
+        transaction_commands(&[(Write, FIFO_LEN)]),
+        Err(Error::ExceedingFifo)
+    ));
+    assert!(matches!(
+        transaction_commands(&[(Write, FIFO_LEN - 1), (Read, 1)]),
+        Err(Error::ExceedingFifo)
+    ));
+
+    // a read command reads up to 255 bytes
+    assert!(transaction_commands(&[(Read, 255)]).is_ok());
+    assert!(matches!(
+        transaction_commands(&[(Read, 200), (Read, 56)]),
+        Err(Error::ExceedingFifo)
+    ));
+
+    // 20 commands
+    assert!(matches!(
+        transaction_commands(&[
+            (Write, 1),
+            (Read, 1),
+            (Write, 1),
+            (Read, 1),
+            (Write, 1),
+            (Read, 1),
+            (Write, 1),
+            (Read, 1),
+        ]),
+        Err(Error::CommandNrExceeded)
+    ));
+    // 16 commands, the maximum
+    let operations = [(Read, 2), (Write, 1), (Read, 2), (Write, 1), (Read, 1)];
+    assert!(transaction_commands(&operations).is_ok());
+    let operations = [(Read, 2), (Write, 1), (Read, 2), (Write, 1), (Read, 2)];
+    assert!(matches!(
+        transaction_commands(&operations),
+        Err(Error::CommandNrExceeded)
+    ));
+};
+
 fn add_cmd<'a, I>(cmd_iterator: &mut I, command: Command) -> Result<(), Error>
 where
     I: Iterator<Item = &'a COMD>,
@@ -180,6 +180,64 @@ where
     }
 }
 
+/// Pins to use for full-duplex I2S, see [I2s::split_full_duplex]
+pub struct PinsBclkWsDoutDin<B: OutputPin, W: OutputPin, DO: OutputPin, DI: InputPin> {
+    pub bclk: B,
+    pub ws: W,
+    pub dout: DO,
+    pub din: DI,
+}
+
+/// Pins of the TX half of a full-duplex I2S, it generates the shared clocks
+pub struct DuplexTxPins<B: OutputPin, W: OutputPin, DO: OutputPin> {
+    bclk: B,
+    ws: W,
+    dout: DO,
+}
+
+impl<B, W, DO> I2sTxPins for DuplexTxPins<B, W, DO>
+where
+    B: OutputPin,
+    W: OutputPin,
+    DO: OutputPin,
+{
+    fn configure<I>(&mut self, instance: &mut I)
+    where
+        I: RegisterAccess,
+    {
+        self.bclk
+            .set_to_push_pull_output()
+            .connect_peripheral_to_output(instance.bclk_signal());
+
+        self.ws
+            .set_to_push_pull_output()
+            .connect_peripheral_to_output(instance.ws_signal());
+
+        self.dout
+            .set_to_push_pull_output()
+            .connect_peripheral_to_output(instance.dout_signal());
+    }
+}
+
+/// Pin of the RX half of a full-duplex I2S, it samples with the clocks of TX
+pub struct DuplexRxPins<DI: InputPin> {
+    din: DI,
+}
+
+impl<DI> I2sRxPins for DuplexRxPins<DI>
+where
+    DI: InputPin,
+{
+    fn configure<I>(&mut self, instance: &mut I)
+    where
+        I: RegisterAccess,
+    {
+        self.din
+            .set_to_input()
+            .connect_input_to_peripheral(instance.din_signal());
+    }
+}
+
 /// MCLK pin to use
 #[cfg(not(esp32))]
 pub struct MclkPin<M: OutputPin> {
@@ -214,6 +272,8 @@ impl I2sMclkPin for NoMclk {
 }
 
 /// An in-progress DMA write transfer.
+///
+/// Dropping it aborts the transfer, unlike [DmaTransfer::wait].
 pub struct I2sWriteDmaTransfer<T, P, TX, BUFFER>
 where
     T: RegisterAccess,
@@ -241,6 +301,33 @@ where
     pub fn push(&mut self, data: &[u8]) -> Result<usize, Error> {
         Ok(self.i2s_tx.tx_channel.push(data)?)
     }
+
+    /// Enable the interrupt for a sent DMA descriptor, see
+    /// [I2sTx::listen_tx_done]
+    pub fn listen_tx_done(&mut self) {
+        self.i2s_tx.listen_tx_done();
+    }
+
+    /// Disable the interrupt for a sent DMA descriptor
+    pub fn unlisten_tx_done(&mut self) {
+        self.i2s_tx.unlisten_tx_done();
+    }
+
+    /// Check if a DMA descriptor was sent since the last call to
+    /// [Self::clear_tx_done]
+    ///
+    /// In a circular transfer this means a part of the buffer can be refilled.
+    pub fn is_tx_done(&mut self) -> bool {
+        self.i2s_tx.tx_channel.is_eof_set()
+    }
+
+    /// Acknowledge a sent DMA descriptor
+    ///
+    /// The freed space is accounted for in [Self::available], clearing the
+    /// flag doesn't lose it.
+    pub fn clear_tx_done(&mut self) {
+        self.i2s_tx.tx_channel.clear_eof();
+    }
 }
 
 impl<T, P, TX, BUFFER> DmaTransfer<BUFFER, I2sTx<T, P, TX>>
@@ -252,7 +339,7 @@ where
 {
     /// Wait for the DMA transfer to complete and return the buffers and the
     /// I2sTx instance.
-    fn wait(self) -> (BUFFER, I2sTx<T, P, TX>) {
+    fn wait(mut self) -> (BUFFER, I2sTx<T, P, TX>) {
         self.i2s_tx.wait_tx_dma_done().ok(); // waiting for the DMA transfer is not enough
 
         // `DmaTransfer` needs to have a `Drop` implementation, because we accept
@@ -278,7 +365,16 @@ where
     TX: Tx,
 {
     fn drop(&mut self) {
-        self.i2s_tx.wait_tx_dma_done().ok();
+        self.i2s_tx.tx_channel.stop();
+        // in full duplex mode TX generates the clocks RX samples with, and on
+        // the ESP32 and ESP32-S2 both share `conf`: stopping or resetting the
+        // unit would stop a read still running, only stop the DMA
+        if self.i2s_tx.register_access.is_full_duplex() {
+            return;
+        }
+
+        self.i2s_tx.register_access.tx_stop();
+        self.i2s_tx.register_access.reset_tx();
     }
 }
 
@@ -318,6 +414,8 @@ where
 }
 
 /// An in-progress DMA read transfer.
+///
+/// Dropping it aborts the transfer, unlike [DmaTransfer::wait].
 pub struct I2sReadDmaTransfer<T, P, RX, BUFFER>
 where
     T: RegisterAccess,
@@ -402,7 +500,16 @@ where
     RX: Rx,
 {
     fn drop(&mut self) {
-        self.i2s_rx.wait_rx_dma_done().ok();
+        self.i2s_rx.rx_channel.stop();
+        // in full duplex mode both units share the clocks, and on the ESP32
+        // and ESP32-S2 the `conf` register: resetting RX would disturb a write
+        // still running, only stop the DMA
+        if self.i2s_rx.register_access.is_full_duplex() {
+            return;
+        }
+
+        self.i2s_rx.register_access.rx_stop();
+        self.i2s_rx.register_access.reset_rx();
     }
 }
 
@@ -509,6 +616,40 @@ where
             },
         }
     }
+
+    /// Split the driver into a TX and an RX half running on the same clocks
+    ///
+    /// TX generates BCLK and WS, RX samples DIN with them, as needed by audio
+    /// codecs with a single clock domain. Start both halves with
+    /// [I2sTx::transfer_dma] or [I2sTx::transfer_dma_circular], running
+    /// out of data in one direction doesn't stop the other one.
+    pub fn split_full_duplex<B, W, DO, DI>(
+        self,
+        pins: PinsBclkWsDoutDin<B, W, DO, DI>,
+    ) -> (
+        I2sTx<T, DuplexTxPins<B, W, DO>, TX>,
+        I2sRx<T, DuplexRxPins<DI>, RX>,
+    )
+    where
+        B: OutputPin,
+        W: OutputPin,
+        DO: OutputPin,
+        DI: InputPin,
+    {
+        let PinsBclkWsDoutDin {
+            bclk,
+            ws,
+            dout,
+            din,
+        } = pins;
+
+        self.i2s_tx.register_access.set_full_duplex();
+
+        let tx = self.i2s_tx.with_pins(DuplexTxPins { bclk, ws, dout });
+        let rx = self.i2s_rx.with_pins(DuplexRxPins { din });
+
+        (tx, rx)
+    }
 }
 
 /// Construct a new I2S peripheral driver instance for the first I2S peripheral
@@ -651,6 +792,22 @@ where
         }
     }
 
+    /// Enable the interrupt for a sent DMA descriptor
+    ///
+    /// Every DMA descriptor covers up to 4092 bytes of the buffer, the
+    /// interrupt fires each time one of them was sent. It is raised on the
+    /// interrupt of the DMA channel (`DMA_CHn`) on chips with GDMA and on the
+    /// I2S interrupt on the ESP32 and ESP32-S2. Acknowledge it with
+    /// [I2sWriteDmaTransfer::clear_tx_done].
+    pub fn listen_tx_done(&mut self) {
+        self.tx_channel.listen_eof();
+    }
+
+    /// Disable the interrupt for a sent DMA descriptor
+    pub fn unlisten_tx_done(&mut self) {
+        self.tx_channel.unlisten_eof();
+    }
+
     fn write_bytes(&mut self, data: &[u8]) -> Result<(), Error> {
         let ptr = data as *const _ as *const u8;
 
@@ -672,10 +829,7 @@ where
         // start: set I2S_TX_START
         self.register_access.tx_start();
 
-        // wait until I2S_TX_IDLE is 1
-        self.register_access.wait_for_tx_done();
-
-        Ok(())
+        self.wait_tx_dma_done()
     }
 
     fn start_tx_transfer<TXBUF>(
@@ -683,6 +837,21 @@ where
         words: TXBUF,
         circular: bool,
     ) -> Result<I2sWriteDmaTransfer<T, P, TX, TXBUF>, Error>
+    where
+        TXBUF: ReadBuffer<Word = u8>,
+    {
+        self.prepare_tx_transfer(&words, circular)?;
+
+        // start: set I2S_TX_START
+        self.register_access.tx_start();
+
+        Ok(I2sWriteDmaTransfer {
+            i2s_tx: self,
+            buffer: words,
+        })
+    }
+
+    fn prepare_tx_transfer<TXBUF>(&mut self, words: &TXBUF, circular: bool) -> Result<(), Error>
     where
         TXBUF: ReadBuffer<Word = u8>,
     {
@@ -703,16 +872,20 @@ where
 
         // set I2S_TX_STOP_EN if needed
 
-        // start: set I2S_TX_START
-        self.register_access.tx_start();
-
-        Ok(I2sWriteDmaTransfer {
-            i2s_tx: self,
-            buffer: words,
-        })
+        Ok(())
     }
 
-    fn wait_tx_dma_done(&self) -> Result<(), Error> {
+    fn wait_tx_dma_done(&mut self) -> Result<(), Error> {
+        // in full-duplex TX keeps the shared clocks running and never becomes
+        // idle, the data is out once the DMA is done
+        if self.register_access.is_full_duplex() {
+            while !self.tx_channel.is_done() {
+                // wait
+            }
+
+            return Ok(());
+        }
+
         // wait until I2S_TX_IDLE is 1
         self.register_access.wait_for_tx_done();
 
@@ -758,6 +931,101 @@ where
     }
 }
 
+impl<T, B, W, DO, TX> I2sTx<T, DuplexTxPins<B, W, DO>, TX>
+where
+    T: RegisterAccess,
+    B: OutputPin,
+    W: OutputPin,
+    DO: OutputPin,
+    TX: Tx,
+{
+    /// Start a DMA transfer in both directions of a full-duplex I2S
+    ///
+    /// TX and RX start on the same frame, the first sample of `rx_words` is
+    /// always taken at the same offset to the first sample of `tx_words`.
+    /// Each direction finishes on its own, TX keeps generating the clocks
+    /// after its buffer was sent and RX can stop before TX.
+    pub fn transfer_dma<DI, RX, TXBUF, RXBUF>(
+        self,
+        tx_words: TXBUF,
+        rx: I2sRx<T, DuplexRxPins<DI>, RX>,
+        rx_words: RXBUF,
+    ) -> Result<
+        (
+            I2sWriteDmaTransfer<T, DuplexTxPins<B, W, DO>, TX, TXBUF>,
+            I2sReadDmaTransfer<T, DuplexRxPins<DI>, RX, RXBUF>,
+        ),
+        Error,
+    >
+    where
+        DI: InputPin,
+        RX: Rx,
+        TXBUF: ReadBuffer<Word = u8>,
+        RXBUF: WriteBuffer<Word = u8>,
+    {
+        self.start_duplex_transfer(tx_words, rx, rx_words, false)
+    }
+
+    /// Continously transfer in both directions of a full-duplex I2S, see
+    /// [Self::transfer_dma]
+    pub fn transfer_dma_circular<DI, RX, TXBUF, RXBUF>(
+        self,
+        tx_words: TXBUF,
+        rx: I2sRx<T, DuplexRxPins<DI>, RX>,
+        rx_words: RXBUF,
+    ) -> Result<
+        (
+            I2sWriteDmaTransfer<T, DuplexTxPins<B, W, DO>, TX, TXBUF>,
+            I2sReadDmaTransfer<T, DuplexRxPins<DI>, RX, RXBUF>,
+        ),
+        Error,
+    >
+    where
+        DI: InputPin,
+        RX: Rx,
+        TXBUF: ReadBuffer<Word = u8>,
+        RXBUF: WriteBuffer<Word = u8>,
+    {
+        self.start_duplex_transfer(tx_words, rx, rx_words, true)
+    }
+
+    fn start_duplex_transfer<DI, RX, TXBUF, RXBUF>(
+        mut self,
+        tx_words: TXBUF,
+        mut rx: I2sRx<T, DuplexRxPins<DI>, RX>,
+        mut rx_words: RXBUF,
+        circular: bool,
+    ) -> Result<
+        (
+            I2sWriteDmaTransfer<T, DuplexTxPins<B, W, DO>, TX, TXBUF>,
+            I2sReadDmaTransfer<T, DuplexRxPins<DI>, RX, RXBUF>,
+        ),
+        Error,
+    >
+    where
+        DI: InputPin,
+        RX: Rx,
+        TXBUF: ReadBuffer<Word = u8>,
+        RXBUF: WriteBuffer<Word = u8>,
+    {
+        self.prepare_tx_transfer(&tx_words, circular)?;
+        let eof_len = rx.prepare_rx_transfer(&mut rx_words, circular)?;
+
+        self.register_access.start_full_duplex(eof_len);
+
+        Ok((
+            I2sWriteDmaTransfer {
+                i2s_tx: self,
+                buffer: tx_words,
+            },
+            I2sReadDmaTransfer {
+                i2s_rx: rx,
+                buffer: rx_words,
+            },
+        ))
+    }
+}
+
 /// I2S RX channel
 pub struct I2sRx<T, P, RX>
 where
@@ -818,6 +1086,26 @@ where
         mut words: RXBUF,
         circular: bool,
     ) -> Result<I2sReadDmaTransfer<T, P, RX, RXBUF>, Error>
+    where
+        RXBUF: WriteBuffer<Word = u8>,
+    {
+        let eof_len = self.prepare_rx_transfer(&mut words, circular)?;
+
+        // start: set I2S_RX_START
+        self.register_access.rx_start(eof_len);
+
+        Ok(I2sReadDmaTransfer {
+            i2s_rx: self,
+            buffer: words,
+        })
+    }
+
+    /// Prepares the DMA inlink and returns the length to pass to `rx_start`
+    fn prepare_rx_transfer<RXBUF>(
+        &mut self,
+        words: &mut RXBUF,
+        circular: bool,
+    ) -> Result<usize, Error>
     where
         RXBUF: WriteBuffer<Word = u8>,
     {
@@ -842,17 +1130,13 @@ where
 
         // set I2S_TX_STOP_EN if needed
 
-        // start: set I2S_RX_START
         #[cfg(not(esp32))]
-        self.register_access.rx_start(len - 1);
+        let eof_len = len - 1;
 
         #[cfg(esp32)]
-        self.register_access.rx_start(len);
+        let eof_len = len;
 
-        Ok(I2sReadDmaTransfer {
-            i2s_rx: self,
-            buffer: words,
-        })
+        Ok(eof_len)
     }
 
     fn wait_rx_dma_done(&self) -> Result<(), Error> {
@@ -904,6 +1188,102 @@ where
     }
 }
 
+pub mod camera {
+    //! Sample packing of parallel camera data
+    //!
+    //! When an 8-bit parallel (DVP) camera is sampled through I2S, every byte
+    //! of the bus ends up in a 16-bit slot of the received buffer. Sensors
+    //! also deliver 16-bit formats like RGB565 in big-endian order.
+    //! [`repack`] turns a received buffer into the layout selected by
+    //! [`SamplePacking`] in place, a word at a time.
+    //!
+    //! This is a CPU pass over the buffer after the DMA transfer completed.
+    //! The I2S driver has no camera receive mode yet, so the packing can't be
+    //! applied by the peripheral's FIFO mode bits while capturing. Once camera
+    //! capture is supported, the packing is meant to move into its RX
+    //! configuration and [`repack`] only remains for layouts the hardware
+    //! can't produce.
+
+    /// Layout of the samples after [`repack`]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum SamplePacking {
+        /// Keep the 16-bit samples as they are
+        Bits16,
+        /// Swap the bytes of each 16-bit sample, e.g. to get little-endian
+        /// RGB565
+        Bits16Swapped,
+        /// Keep the high byte of each 16-bit slot, the data of an 8-bit bus
+        /// connected to the upper data lines
+        Bits8,
+        /// Keep the low byte of each 16-bit slot, e.g. the luma of YUV422 to
+        /// get a grayscale image
+        DiscardHighByte,
+    }
+
+    /// Repack the received samples in `buffer` in place
+    ///
+    /// Returns the number of valid bytes at the start of `buffer` afterwards.
+    /// A trailing odd byte is dropped. Word aligned buffers, like the ones
+    /// used for DMA, are processed 32 bits at a time.
+    pub fn repack(buffer: &mut [u8], packing: SamplePacking) -> usize {
+        match packing {
+            SamplePacking::Bits16 => buffer.len() & !1,
+            SamplePacking::Bits16Swapped => swap_bytes(buffer),
+            SamplePacking::Bits8 => compact(buffer, 1),
+            SamplePacking::DiscardHighByte => compact(buffer, 0),
+        }
+    }
+
+    fn swap_bytes(buffer: &mut [u8]) -> usize {
+        let len = buffer.len() & !1;
+
+        // NOTE(unsafe) every bit pattern is a valid u32
+        let (prefix, words, _) = unsafe { buffer.align_to_mut::<u32>() };
+        let done = if prefix.is_empty() {
+            for word in words.iter_mut() {
+                *word = (*word & 0x00ff_00ff) << 8 | (*word >> 8) & 0x00ff_00ff;
+            }
+            words.len() * 4
+        } else {
+            0
+        };
+
+        for sample in buffer[done..len].chunks_exact_mut(2) {
+            sample.swap(0, 1);
+        }
+
+        len
+    }
+
+    // keeps byte `byte` of every 16-bit slot
+    fn compact(buffer: &mut [u8], byte: usize) -> usize {
+        let samples = buffer.len() / 2;
+        let shift = byte as u32 * 8;
+
+        // NOTE(unsafe) every bit pattern is a valid u32
+        let (prefix, words, _) = unsafe { buffer.align_to_mut::<u32>() };
+        let done = if prefix.is_empty() {
+            // four slots from two words are packed into one word, the write
+            // never overtakes the reads
+            let pairs = words.len() / 2;
+            for i in 0..pairs {
+                let pick =
+                    |word: u32| (word >> shift) & 0xff | ((word >> (16 + shift)) & 0xff) << 8;
+                words[i] = pick(words[2 * i]) | pick(words[2 * i + 1]) << 16;
+            }
+            pairs * 4
+        } else {
+            0
+        };
+
+        for i in done..samples {
+            buffer[i] = buffer[2 * i + byte];
+        }
+
+        samples
+    }
+}
+
 mod private {
     use fugit::HertzU32;
 
@@ -1193,6 +1573,11 @@ mod private {
             i2s.conf.modify(|_, w| w.tx_start().clear_bit());
         }
 
+        fn tx_stop(&self) {
+            let i2s = self.register_block();
+            i2s.conf.modify(|_, w| w.tx_start().clear_bit());
+        }
+
         fn reset_rx(&self) {
             let i2s = self.register_block();
             i2s.conf
@@ -1210,6 +1595,14 @@ mod private {
         fn rx_start(&self, len: usize) {
             let i2s = self.register_block();
 
+            self.set_rx_eof(len);
+
+            i2s.conf.modify(|_, w| w.rx_start().set_bit());
+        }
+
+        fn set_rx_eof(&self, len: usize) {
+            let i2s = self.register_block();
+
             i2s.int_clr.write(|w| w.in_suc_eof_int_clr().set_bit());
 
             #[cfg(not(esp32))]
@@ -1220,8 +1613,32 @@ mod private {
             #[cfg(esp32)]
             i2s.rxeof_num
                 .modify(|_, w| w.rx_eof_num().variant((len / 4) as u32));
+        }
 
-            i2s.conf.modify(|_, w| w.rx_start().set_bit());
+        fn set_full_duplex(&self) {
+            let i2s = self.register_block();
+
+            // RX uses BCLK and WS of TX
+            i2s.conf.modify(|_, w| w.sig_loopback().set_bit());
+
+            // keep the clocks running when TX runs out of data, RX still
+            // needs them
+            i2s.conf1.modify(|_, w| w.tx_stop_en().clear_bit());
+        }
+
+        fn is_full_duplex(&self) -> bool {
+            let i2s = self.register_block();
+            i2s.conf.read().sig_loopback().bit_is_set()
+        }
+
+        fn start_full_duplex(&self, rx_len: usize) {
+            let i2s = self.register_block();
+
+            self.set_rx_eof(rx_len);
+
+            // a single write starts both units on the same frame
+            i2s.conf
+                .modify(|_, w| w.tx_start().set_bit().rx_start().set_bit());
         }
 
         fn wait_for_rx_done(&self) {
@@ -1232,6 +1649,11 @@ mod private {
 
             i2s.int_clr.write(|w| w.in_suc_eof_int_clr().set_bit());
         }
+
+        fn rx_stop(&self) {
+            let i2s = self.register_block();
+            i2s.conf.modify(|_, w| w.rx_start().clear_bit());
+        }
     }
 
     #[cfg(any(esp32c3, esp32s3))]
@@ -1520,6 +1942,11 @@ mod private {
             i2s.tx_conf.modify(|_, w| w.tx_start().clear_bit());
         }
 
+        fn tx_stop(&self) {
+            let i2s = self.register_block();
+            i2s.tx_conf.modify(|_, w| w.tx_start().clear_bit());
+        }
+
         fn reset_rx(&self) {
             let i2s = self.register_block();
             i2s.rx_conf
@@ -1537,6 +1964,32 @@ mod private {
             i2s.rx_conf.modify(|_, w| w.rx_start().set_bit());
         }
 
+        fn set_full_duplex(&self) {
+            let i2s = self.register_block();
+
+            // RX uses BCLK and WS of TX, TX keeps the clocks running when it
+            // runs out of data since RX still needs them
+            i2s.tx_conf
+                .modify(|_, w| w.sig_loopback().set_bit().tx_stop_en().clear_bit());
+            self.update();
+        }
+
+        fn is_full_duplex(&self) -> bool {
+            let i2s = self.register_block();
+            i2s.tx_conf.read().sig_loopback().bit_is_set()
+        }
+
+        fn start_full_duplex(&self, rx_len: usize) {
+            let i2s = self.register_block();
+            i2s.rxeof_num
+                .write(|w| w.rx_eof_num().variant(rx_len as u16));
+
+            // the start bits are in different registers, RX is started first
+            // and samples from the first frame TX generates
+            i2s.rx_conf.modify(|_, w| w.rx_start().set_bit());
+            i2s.tx_conf.modify(|_, w| w.tx_start().set_bit());
+        }
+
         fn wait_for_rx_done(&self) {
             let i2s = self.register_block();
             while i2s.int_raw.read().rx_done_int_raw().bit_is_clear() {
@@ -1545,6 +1998,11 @@ mod private {
 
             i2s.int_clr.write(|w| w.rx_done_int_clr().set_bit());
         }
+
+        fn rx_stop(&self) {
+            let i2s = self.register_block();
+            i2s.rx_conf.modify(|_, w| w.rx_start().clear_bit());
+        }
     }
 
     #[derive(Clone)]
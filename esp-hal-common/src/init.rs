@@ -0,0 +1,293 @@
+//! Bootstrap
+//!
+//! Almost every application starts by configuring the clocks, creating the
+//! [Rtc] and [IO] drivers and dealing with the watchdogs the bootloader left
+//! armed. [init()] does all of this in one go, according to a [Config].
+//!
+//! Using it is entirely optional, all the drivers it creates can still be
+//! constructed one by one as before.
+//!
+//! The [init!](crate::init!) macro takes the required peripherals out of the
+//! `Peripherals` struct. All other peripherals are left untouched and can be
+//! used afterwards.
+//!
+//! Example
+//! ```no_run
+//! let peripherals = Peripherals::take().unwrap();
+//! let hal = init!(peripherals, Config::default());
+//!
+//! let mut led = hal.io.pins.gpio5.into_push_pull_output();
+//! let mut serial0 = Serial::new(peripherals.UART0);
+//! ```
+
+use embedded_hal::watchdog::{WatchdogDisable, WatchdogEnable};
+use fugit::MicrosDurationU64;
+
+#[cfg(all(any(esp32c3, esp32s3), feature = "direct-boot"))]
+use crate::direct_boot::FlashConfig;
+#[cfg(timg1)]
+use crate::pac::TIMG1;
+#[cfg(pdma)]
+use crate::system::Dma;
+use crate::{
+    clock::{ClockControl, Clocks, CpuClock},
+    gpio::IO,
+    pac::{GPIO, IO_MUX, RTC_CNTL, TIMG0},
+    rtc_cntl::Rtc,
+    system::{CpuControl, PeripheralClockControl, SystemExt, SystemPeripheral},
+    timer::TimerGroup,
+};
+
+/// Configuration of a single watchdog
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WatchdogConfig {
+    /// The watchdog is turned off
+    Disabled,
+    /// The watchdog is (re)started with the given timeout
+    Enabled(MicrosDurationU64),
+    /// The watchdog is left as the bootloader configured it, e.g. to stay
+    /// supervised after
+    /// [hand_over_watchdogs](crate::rtc_cntl::hand_over_watchdogs)
+    Keep,
+}
+
+/// Configuration of the watchdogs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchdogsConfig {
+    /// RTC watchdog
+    pub rwdt: WatchdogConfig,
+    /// Watchdog of timer group 0
+    pub timg0: WatchdogConfig,
+    /// Watchdog of timer group 1
+    #[cfg(timg1)]
+    pub timg1: WatchdogConfig,
+    /// Keep the super watchdog armed
+    ///
+    /// The super watchdog has no configurable timeout, if it is kept armed it
+    /// has to be fed by the application.
+    #[cfg(any(esp32c2, esp32c3, esp32s3))]
+    pub swd: bool,
+}
+
+impl Default for WatchdogsConfig {
+    fn default() -> Self {
+        Self {
+            rwdt: WatchdogConfig::Disabled,
+            timg0: WatchdogConfig::Disabled,
+            #[cfg(timg1)]
+            timg1: WatchdogConfig::Disabled,
+            #[cfg(any(esp32c2, esp32c3, esp32s3))]
+            swd: false,
+        }
+    }
+}
+
+/// Configuration of [init()]
+///
+/// The default keeps the clocks the bootloader configured and disables all
+/// watchdogs. With the `direct-boot` feature there is no bootloader, the
+/// default configures the maximum CPU clock and raises the flash clock.
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+    /// CPU clock to configure, `None` keeps the boot defaults
+    pub cpu_clock: Option<CpuClock>,
+    /// Watchdog configuration
+    pub watchdogs: WatchdogsConfig,
+    /// Flash configuration, applied before the clocks
+    #[cfg(all(any(esp32c3, esp32s3), feature = "direct-boot"))]
+    pub flash: FlashConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            #[cfg(not(all(any(esp32c3, esp32s3), feature = "direct-boot")))]
+            cpu_clock: None,
+            #[cfg(all(esp32c3, feature = "direct-boot"))]
+            cpu_clock: Some(CpuClock::Clock160MHz),
+            #[cfg(all(esp32s3, feature = "direct-boot"))]
+            cpu_clock: Some(CpuClock::Clock240MHz),
+            watchdogs: WatchdogsConfig::default(),
+            #[cfg(all(any(esp32c3, esp32s3), feature = "direct-boot"))]
+            flash: FlashConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    /// Configure the given CPU clock
+    pub fn cpu_clock(mut self, cpu_clock: CpuClock) -> Self {
+        self.cpu_clock = Some(cpu_clock);
+        self
+    }
+
+    /// Configure the watchdogs
+    pub fn watchdogs(mut self, watchdogs: WatchdogsConfig) -> Self {
+        self.watchdogs = watchdogs;
+        self
+    }
+
+    /// Configure the flash
+    #[cfg(all(any(esp32c3, esp32s3), feature = "direct-boot"))]
+    pub fn flash(mut self, flash: FlashConfig) -> Self {
+        self.flash = flash;
+        self
+    }
+}
+
+/// Peripherals consumed by [init()]
+///
+/// Usually filled in by the [init!](crate::init!) macro.
+pub struct InitPeripherals {
+    pub system: SystemPeripheral,
+    pub rtc_cntl: RTC_CNTL,
+    pub timg0: TIMG0,
+    #[cfg(timg1)]
+    pub timg1: TIMG1,
+    pub gpio: GPIO,
+    pub io_mux: IO_MUX,
+}
+
+/// Drivers created by [init()]
+pub struct Initialized {
+    pub clocks: Clocks,
+    pub io: IO,
+    pub rtc: Rtc,
+    pub peripheral_clock_control: PeripheralClockControl,
+    pub cpu_control: CpuControl,
+    #[cfg(pdma)]
+    pub dma: Dma,
+    pub timer_group0: TimerGroup<TIMG0>,
+    #[cfg(timg1)]
+    pub timer_group1: TimerGroup<TIMG1>,
+}
+
+/// Configure the clocks and watchdogs and create the basic drivers
+pub fn init(peripherals: InitPeripherals, config: Config) -> Initialized {
+    #[cfg(all(any(esp32c3, esp32s3), feature = "direct-boot"))]
+    crate::direct_boot::configure_flash(config.flash);
+
+    let system = peripherals.system.split();
+    let clocks = match config.cpu_clock {
+        Some(cpu_clock) => ClockControl::configure(system.clock_control, cpu_clock),
+        None => ClockControl::boot_defaults(system.clock_control),
+    }
+    .freeze();
+
+    let mut rtc = Rtc::new(peripherals.rtc_cntl);
+    let mut timer_group0 = TimerGroup::new(peripherals.timg0, &clocks);
+    #[cfg(timg1)]
+    let mut timer_group1 = TimerGroup::new(peripherals.timg1, &clocks);
+
+    let watchdogs = config.watchdogs;
+
+    #[cfg(any(esp32c2, esp32c3, esp32s3))]
+    if !watchdogs.swd {
+        rtc.swd.disable();
+    }
+
+    match watchdogs.rwdt {
+        WatchdogConfig::Disabled => rtc.rwdt.disable(),
+        WatchdogConfig::Enabled(timeout) => rtc.rwdt.start(timeout),
+        WatchdogConfig::Keep => (),
+    }
+
+    match watchdogs.timg0 {
+        WatchdogConfig::Disabled => timer_group0.wdt.disable(),
+        WatchdogConfig::Enabled(timeout) => timer_group0.wdt.start(timeout),
+        WatchdogConfig::Keep => (),
+    }
+
+    #[cfg(timg1)]
+    match watchdogs.timg1 {
+        WatchdogConfig::Disabled => timer_group1.wdt.disable(),
+        WatchdogConfig::Enabled(timeout) => timer_group1.wdt.start(timeout),
+        WatchdogConfig::Keep => (),
+    }
+
+    let io = IO::new(peripherals.gpio, peripherals.io_mux);
+
+    Initialized {
+        clocks,
+        io,
+        rtc,
+        peripheral_clock_control: system.peripheral_clock_control,
+        cpu_control: system.cpu_control,
+        #[cfg(pdma)]
+        dma: system.dma,
+        timer_group0,
+        #[cfg(timg1)]
+        timer_group1,
+    }
+}
+
+/// Call [init()](crate::init::init) with the peripherals taken out of the
+/// given `Peripherals`
+///
+/// Only the peripherals listed in
+/// [InitPeripherals](crate::init::InitPeripherals) are moved, the remaining
+/// fields of `Peripherals` can still be used afterwards.
+#[cfg(esp32)]
+#[macro_export]
+macro_rules! init {
+    ($peripherals:ident, $config:expr) => {
+        $crate::init::init(
+            $crate::init::InitPeripherals {
+                system: $peripherals.DPORT,
+                rtc_cntl: $peripherals.RTC_CNTL,
+                timg0: $peripherals.TIMG0,
+                timg1: $peripherals.TIMG1,
+                gpio: $peripherals.GPIO,
+                io_mux: $peripherals.IO_MUX,
+            },
+            $config,
+        )
+    };
+}
+
+/// Call [init()](crate::init::init) with the peripherals taken out of the
+/// given `Peripherals`
+///
+/// Only the peripherals listed in
+/// [InitPeripherals](crate::init::InitPeripherals) are moved, the remaining
+/// fields of `Peripherals` can still be used afterwards.
+#[cfg(esp32c2)]
+#[macro_export]
+macro_rules! init {
+    ($peripherals:ident, $config:expr) => {
+        $crate::init::init(
+            $crate::init::InitPeripherals {
+                system: $peripherals.SYSTEM,
+                rtc_cntl: $peripherals.RTC_CNTL,
+                timg0: $peripherals.TIMG0,
+                gpio: $peripherals.GPIO,
+                io_mux: $peripherals.IO_MUX,
+            },
+            $config,
+        )
+    };
+}
+
+/// Call [init()](crate::init::init) with the peripherals taken out of the
+/// given `Peripherals`
+///
+/// Only the peripherals listed in
+/// [InitPeripherals](crate::init::InitPeripherals) are moved, the remaining
+/// fields of `Peripherals` can still be used afterwards.
+#[cfg(any(esp32c3, esp32s2, esp32s3))]
+#[macro_export]
+macro_rules! init {
+    ($peripherals:ident, $config:expr) => {
+        $crate::init::init(
+            $crate::init::InitPeripherals {
+                system: $peripherals.SYSTEM,
+                rtc_cntl: $peripherals.RTC_CNTL,
+                timg0: $peripherals.TIMG0,
+                timg1: $peripherals.TIMG1,
+                gpio: $peripherals.GPIO,
+                io_mux: $peripherals.IO_MUX,
+            },
+            $config,
+        )
+    };
+}
@@ -201,7 +201,8 @@ pub unsafe fn set_priority(_core: Cpu, which: CpuInterrupt, priority: Priority)
 }
 
 /// Clear a CPU interrupt
-#[inline]
+#[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+#[cfg_attr(not(feature = "place-isr-in-ram"), inline)]
 pub fn clear(_core: Cpu, which: CpuInterrupt) {
     unsafe {
         let cpu_interrupt_number = which as isize;
@@ -212,7 +213,8 @@ pub fn clear(_core: Cpu, which: CpuInterrupt) {
 }
 
 /// Get status of peripheral interrupts
-#[inline]
+#[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+#[cfg_attr(not(feature = "place-isr-in-ram"), inline)]
 pub fn get_status(_core: Cpu) -> u128 {
     unsafe {
         ((*crate::pac::INTERRUPT_CORE0::PTR)
@@ -232,10 +234,26 @@ pub use vectored::*;
 
 #[cfg(feature = "vectored")]
 mod vectored {
+    #[cfg(feature = "place-isr-in-ram")]
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
     use procmacros::ram;
 
     use super::*;
 
+    /// Number of peripheral interrupts covered by the status registers
+    #[cfg(feature = "place-isr-in-ram")]
+    const INTERRUPTS: usize = 64;
+
+    #[cfg(feature = "place-isr-in-ram")]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NO_VECTOR: AtomicUsize = AtomicUsize::new(0);
+
+    /// Handlers of the interrupts enabled with [enable], copied from the
+    /// interrupt table of the PAC which is placed in flash
+    #[cfg(feature = "place-isr-in-ram")]
+    static VECTORS: [AtomicUsize; INTERRUPTS] = [NO_VECTOR; INTERRUPTS];
+
     // Setup interrupts 1-15 ready for vectoring
     #[doc(hidden)]
     pub(crate) unsafe fn init_vectoring() {
@@ -255,7 +273,8 @@ mod vectored {
     }
 
     /// Get the interrupts configured for the core
-    #[inline]
+    #[cfg_attr(feature = "place-isr-in-ram", ram)]
+    #[cfg_attr(not(feature = "place-isr-in-ram"), inline)]
     fn get_configured_interrupts(_core: Cpu, mut status: u128) -> [u128; 16] {
         unsafe {
             let intr = &*crate::pac::INTERRUPT_CORE0::PTR;
@@ -292,11 +311,19 @@ mod vectored {
     ///
     /// Note that interrupts still need to be enabled globally for interrupts
     /// to be serviced.
+    ///
+    /// With the `place-isr-in-ram` feature the handler of the interrupt has to
+    /// be placed in RAM with `#[ram]`, debug builds panic if it isn't. The
+    /// handler must not use atomic read-modify-write operations (e.g.
+    /// `fetch_add`) while the flash is busy: they are emulated by an exception
+    /// handler which stays in flash.
     pub fn enable(interrupt: Interrupt, level: Priority) -> Result<(), Error> {
         if matches!(level, Priority::None) {
             return Err(Error::InvalidInterruptPriority);
         }
         unsafe {
+            #[cfg(feature = "place-isr-in-ram")]
+            register_vector(interrupt);
             let cpu_interrupt = core::mem::transmute(level as u8 as u32);
             map(crate::get_core(), interrupt, cpu_interrupt);
             enable_cpu_interrupt(cpu_interrupt);
@@ -304,6 +331,43 @@ mod vectored {
         Ok(())
     }
 
+    extern "C" {
+        // defined in each hal
+        fn EspDefaultHandler(interrupt: Interrupt);
+    }
+
+    /// Copy the handler of `interrupt` to [VECTORS]
+    #[cfg(feature = "place-isr-in-ram")]
+    unsafe fn register_vector(interrupt: Interrupt) {
+        let handler = pac::__EXTERNAL_INTERRUPTS[interrupt as usize]._reserved as usize;
+
+        debug_assert!(
+            handler == EspDefaultHandler as usize || crate::IRAM.contains(&handler),
+            "the handler of {:?} is not placed in RAM",
+            interrupt
+        );
+
+        VECTORS[interrupt as usize].store(handler, Ordering::Release);
+    }
+
+    /// The handler of the interrupt `interrupt_nr`, 0 for a reserved one
+    #[inline(always)]
+    unsafe fn vector(interrupt_nr: usize) -> usize {
+        #[cfg(feature = "place-isr-in-ram")]
+        if let Some(vector) = VECTORS.get(interrupt_nr) {
+            let handler = vector.load(Ordering::Acquire);
+            if handler != 0 {
+                return handler;
+            }
+        }
+
+        // without the feature, or mapped without `enable`: looked up in flash
+        match pac::__EXTERNAL_INTERRUPTS.get(interrupt_nr) {
+            Some(vector) => vector._reserved as usize,
+            None => 0,
+        }
+    }
+
     #[ram]
     unsafe fn handle_interrupts(cpu_intr: CpuInterrupt, context: &mut TrapFrame) {
         let status = get_status(crate::get_core());
@@ -316,25 +380,20 @@ mod vectored {
         let mut interrupt_mask = status & configured_interrupts[cpu_intr as usize];
         while interrupt_mask != 0 {
             let interrupt_nr = interrupt_mask.trailing_zeros();
-            // Interrupt::try_from can fail if interrupt already de-asserted:
-            // silently ignore
-            if let Ok(interrupt) = pac::Interrupt::try_from(interrupt_nr as u8) {
-                handle_interrupt(interrupt, context)
-            }
+            handle_interrupt(interrupt_nr as usize, context);
             interrupt_mask &= !(1u128 << interrupt_nr);
         }
     }
 
     #[ram]
-    unsafe fn handle_interrupt(interrupt: Interrupt, save_frame: &mut TrapFrame) {
-        extern "C" {
-            // defined in each hal
-            fn EspDefaultHandler(interrupt: Interrupt);
-        }
-        let handler = pac::__EXTERNAL_INTERRUPTS[interrupt as usize]._handler;
-        if handler as *const _ == EspDefaultHandler as *const unsafe extern "C" fn() {
-            EspDefaultHandler(interrupt);
-        } else {
+    unsafe fn handle_interrupt(interrupt_nr: usize, save_frame: &mut TrapFrame) {
+        // reserved interrupt numbers have no handler: silently ignore
+        let handler = vector(interrupt_nr);
+        if handler == EspDefaultHandler as usize {
+            if let Ok(interrupt) = pac::Interrupt::try_from(interrupt_nr as u8) {
+                EspDefaultHandler(interrupt);
+            }
+        } else if handler != 0 {
             let handler: fn(&mut TrapFrame) = core::mem::transmute(handler);
             handler(save_frame);
         }
@@ -461,11 +520,50 @@ pub struct TrapFrame {
     pub mtval: usize,
 }
 
+/// The handlers of the CPU interrupts 1 to 31, looked up by
+/// `start_trap_rust_hal`. A `match` on the interrupt number compiles to a jump
+/// table in `.rodata`, which is in flash.
+#[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
+static CPU_INTERRUPTS: [unsafe extern "C" fn(&mut TrapFrame); 31] = [
+    interrupt1,
+    interrupt2,
+    interrupt3,
+    interrupt4,
+    interrupt5,
+    interrupt6,
+    interrupt7,
+    interrupt8,
+    interrupt9,
+    interrupt10,
+    interrupt11,
+    interrupt12,
+    interrupt13,
+    interrupt14,
+    interrupt15,
+    interrupt16,
+    interrupt17,
+    interrupt18,
+    interrupt19,
+    interrupt20,
+    interrupt21,
+    interrupt22,
+    interrupt23,
+    interrupt24,
+    interrupt25,
+    interrupt26,
+    interrupt27,
+    interrupt28,
+    interrupt29,
+    interrupt30,
+    interrupt31,
+];
+
 /// # Safety
 ///
 /// This function is called from an assembly trap handler.
 #[doc(hidden)]
-#[link_section = ".trap.rust"]
+#[cfg_attr(not(feature = "place-isr-in-ram"), link_section = ".trap.rust")]
+#[cfg_attr(feature = "place-isr-in-ram", link_section = ".rwtext")]
 #[export_name = "_start_trap_rust_hal"]
 pub unsafe extern "C" fn start_trap_rust_hal(trap_frame: *mut TrapFrame) {
     extern "C" {
@@ -479,51 +577,25 @@ pub unsafe extern "C" fn start_trap_rust_hal(trap_frame: *mut TrapFrame) {
         handle_exception(pc, trap_frame);
     } else {
         let code = riscv::register::mcause::read().code();
-        match code {
-            1 => interrupt1(trap_frame.as_mut().unwrap()),
-            2 => interrupt2(trap_frame.as_mut().unwrap()),
-            3 => interrupt3(trap_frame.as_mut().unwrap()),
-            4 => interrupt4(trap_frame.as_mut().unwrap()),
-            5 => interrupt5(trap_frame.as_mut().unwrap()),
-            6 => interrupt6(trap_frame.as_mut().unwrap()),
-            7 => interrupt7(trap_frame.as_mut().unwrap()),
-            8 => interrupt8(trap_frame.as_mut().unwrap()),
-            9 => interrupt9(trap_frame.as_mut().unwrap()),
-            10 => interrupt10(trap_frame.as_mut().unwrap()),
-            11 => interrupt11(trap_frame.as_mut().unwrap()),
-            12 => interrupt12(trap_frame.as_mut().unwrap()),
-            13 => interrupt13(trap_frame.as_mut().unwrap()),
-            14 => interrupt14(trap_frame.as_mut().unwrap()),
-            15 => interrupt15(trap_frame.as_mut().unwrap()),
-            16 => interrupt16(trap_frame.as_mut().unwrap()),
-            17 => interrupt17(trap_frame.as_mut().unwrap()),
-            18 => interrupt18(trap_frame.as_mut().unwrap()),
-            19 => interrupt19(trap_frame.as_mut().unwrap()),
-            20 => interrupt20(trap_frame.as_mut().unwrap()),
-            21 => interrupt21(trap_frame.as_mut().unwrap()),
-            22 => interrupt22(trap_frame.as_mut().unwrap()),
-            23 => interrupt23(trap_frame.as_mut().unwrap()),
-            24 => interrupt24(trap_frame.as_mut().unwrap()),
-            25 => interrupt25(trap_frame.as_mut().unwrap()),
-            26 => interrupt26(trap_frame.as_mut().unwrap()),
-            27 => interrupt27(trap_frame.as_mut().unwrap()),
-            28 => interrupt28(trap_frame.as_mut().unwrap()),
-            29 => interrupt29(trap_frame.as_mut().unwrap()),
-            30 => interrupt30(trap_frame.as_mut().unwrap()),
-            31 => interrupt31(trap_frame.as_mut().unwrap()),
-            _ => DefaultHandler(),
-        };
+        match CPU_INTERRUPTS.get(code.wrapping_sub(1)) {
+            Some(handler) => handler(&mut *trap_frame),
+            None => DefaultHandler(),
+        }
     }
 }
 
-/// Apply atomic emulation if needed. Call the default exception handler
-/// otherwise.
+/// Report a stack overflow caught by the stack guard, apply atomic emulation
+/// if needed. Call the default exception handler otherwise.
 ///
 /// # Safety
 ///
 /// This function is called from an trap handler.
 #[doc(hidden)]
+#[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
 unsafe fn handle_exception(pc: usize, trap_frame: *mut TrapFrame) {
+    #[cfg(feature = "stack-guard")]
+    crate::debug::check_stack_guard(pc, (*trap_frame).sp);
+
     let insn: usize = *(pc as *const _);
     let needs_atomic_emulation = (insn & 0b1111111) == 0b0101111;
 
@@ -98,6 +98,7 @@ pub fn clear(_core: Cpu, which: CpuInterrupt) {
 }
 
 /// Get status of peripheral interrupts
+#[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
 pub fn get_status(core: Cpu) -> u128 {
     unsafe {
         match core {
@@ -185,11 +186,27 @@ pub use vectored::*;
 
 #[cfg(feature = "vectored")]
 mod vectored {
+    #[cfg(feature = "place-isr-in-ram")]
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
     use procmacros::ram;
 
     use super::*;
     use crate::get_core;
 
+    /// Number of peripheral interrupts covered by the status registers
+    #[cfg(feature = "place-isr-in-ram")]
+    const INTERRUPTS: usize = 96;
+
+    #[cfg(feature = "place-isr-in-ram")]
+    #[allow(clippy::declare_interior_mutable_const)]
+    const NO_VECTOR: AtomicUsize = AtomicUsize::new(0);
+
+    /// Handlers of the interrupts enabled with [enable], copied from the
+    /// interrupt table of the PAC which is placed in flash
+    #[cfg(feature = "place-isr-in-ram")]
+    static VECTORS: [AtomicUsize; INTERRUPTS] = [NO_VECTOR; INTERRUPTS];
+
     #[derive(Copy, Clone, Debug, PartialEq, Eq)]
     pub enum Error {
         InvalidInterrupt,
@@ -215,53 +232,20 @@ mod vectored {
         }
     }
 
-    impl CpuInterrupt {
-        #[inline]
-        fn level(&self) -> Priority {
-            match self {
-                CpuInterrupt::Interrupt0LevelPriority1
-                | CpuInterrupt::Interrupt1LevelPriority1
-                | CpuInterrupt::Interrupt2LevelPriority1
-                | CpuInterrupt::Interrupt3LevelPriority1
-                | CpuInterrupt::Interrupt4LevelPriority1
-                | CpuInterrupt::Interrupt5LevelPriority1
-                | CpuInterrupt::Interrupt6Timer0Priority1
-                | CpuInterrupt::Interrupt7SoftwarePriority1
-                | CpuInterrupt::Interrupt8LevelPriority1
-                | CpuInterrupt::Interrupt9LevelPriority1
-                | CpuInterrupt::Interrupt10EdgePriority1
-                | CpuInterrupt::Interrupt12LevelPriority1
-                | CpuInterrupt::Interrupt13LevelPriority1
-                | CpuInterrupt::Interrupt17LevelPriority1
-                | CpuInterrupt::Interrupt18LevelPriority1 => Priority::Priority1,
-
-                CpuInterrupt::Interrupt19LevelPriority2
-                | CpuInterrupt::Interrupt20LevelPriority2
-                | CpuInterrupt::Interrupt21LevelPriority2 => Priority::Priority2,
-
-                CpuInterrupt::Interrupt11ProfilingPriority3
-                | CpuInterrupt::Interrupt15Timer1Priority3
-                | CpuInterrupt::Interrupt22EdgePriority3
-                | CpuInterrupt::Interrupt27LevelPriority3
-                | CpuInterrupt::Interrupt29SoftwarePriority3
-                | CpuInterrupt::Interrupt23LevelPriority3 => Priority::Priority3,
-
-                // we direct these to None because we do not support interrupts at this level
-                // through Rust
-                CpuInterrupt::Interrupt24LevelPriority4
-                | CpuInterrupt::Interrupt25LevelPriority4
-                | CpuInterrupt::Interrupt28EdgePriority4
-                | CpuInterrupt::Interrupt30EdgePriority4
-                | CpuInterrupt::Interrupt31EdgePriority5
-                | CpuInterrupt::Interrupt16Timer2Priority5
-                | CpuInterrupt::Interrupt26LevelPriority5
-                | CpuInterrupt::Interrupt14NmiPriority7 => Priority::None,
-            }
+    /// Priority level of a CPU interrupt, 0 for the levels above 3 which
+    /// aren't handled through Rust
+    #[inline(always)]
+    fn cpu_interrupt_level(cpu_interrupt: u32) -> usize {
+        let mut level = 3;
+        while level > 0 && CPU_INTERRUPT_LEVELS[level] & (1 << cpu_interrupt) == 0 {
+            level -= 1;
         }
+        level
     }
 
     /// Get the interrupts configured for the core
-    #[inline]
+    #[cfg_attr(feature = "place-isr-in-ram", ram)]
+    #[cfg_attr(not(feature = "place-isr-in-ram"), inline)]
     fn get_configured_interrupts(core: Cpu, mut status: u128) -> [u128; 8] {
         unsafe {
             let intr_map_base = match core {
@@ -278,9 +262,7 @@ mod vectored {
                 let interrupt_nr = status.trailing_zeros();
                 let i = interrupt_nr as isize;
                 let cpu_interrupt = intr_map_base.offset(i).read_volatile();
-                // safety: cast is safe because of repr(u32)
-                let cpu_interrupt: CpuInterrupt = core::mem::transmute(cpu_interrupt);
-                let level = cpu_interrupt.level() as u8 as usize;
+                let level = cpu_interrupt_level(cpu_interrupt);
 
                 levels[level] |= 1 << i;
                 status &= !(1u128 << interrupt_nr);
@@ -290,11 +272,20 @@ mod vectored {
         }
     }
 
+    /// Enables a interrupt at a given priority
+    ///
+    /// With the `place-isr-in-ram` feature the handler of the interrupt has to
+    /// be placed in RAM with `#[ram]`, debug builds panic if it isn't. On the
+    /// ESP32 and ESP32-S3 the spin lock taken by `critical_section::with`
+    /// may be placed in flash, so the handler must not enter a critical
+    /// section while the flash is busy.
     pub fn enable(interrupt: Interrupt, level: Priority) -> Result<(), Error> {
         let cpu_interrupt =
             interrupt_level_to_cpu_interrupt(level, chip_specific::interrupt_is_edge(interrupt))?;
 
         unsafe {
+            #[cfg(feature = "place-isr-in-ram")]
+            register_vector(interrupt);
             map(get_core(), interrupt, cpu_interrupt);
 
             xtensa_lx::interrupt::enable_mask(
@@ -326,7 +317,8 @@ mod vectored {
     }
 
     // TODO use CpuInterrupt::LevelX.mask() // TODO make it const
-    const CPU_INTERRUPT_LEVELS: [u32; 8] = [
+    #[cfg_attr(feature = "place-isr-in-ram", ram)]
+    static CPU_INTERRUPT_LEVELS: [u32; 8] = [
         0b_0000_0000_0000_0000_0000_0000_0000_0000, // Dummy level 0
         0b_0000_0000_0000_0110_0011_0111_1111_1111, // Level_1
         0b_0000_0000_0011_1000_0000_0000_0000_0000, // Level 2
@@ -339,21 +331,24 @@ mod vectored {
     const CPU_INTERRUPT_INTERNAL: u32 = 0b_0010_0000_0000_0001_1000_1000_1100_0000;
     const CPU_INTERRUPT_EDGE: u32 = 0b_0111_0000_0100_0000_0000_1100_1000_0000;
 
-    #[inline]
-    fn cpu_interrupt_nr_to_cpu_interrupt_handler(number: u32) -> Option<unsafe extern "C" fn(u32, save_frame: &mut Context)> {
+    /// Handlers of the internal CPU interrupts (timers, software interrupts,
+    /// ...) by CPU interrupt number
+    #[cfg_attr(feature = "place-isr-in-ram", ram)]
+    static CPU_INTERRUPT_HANDLERS: [Option<unsafe extern "C" fn(u32, save_frame: &mut Context)>;
+        32] = {
         use xtensa_lx_rt::*;
         // we're fortunate that all esp variants use the same CPU interrupt layout
-        Some(match number {
-            6 => Timer0,
-            7 => Software0,
-            11 => Profiling,
-            14 => NMI,
-            15 => Timer1,
-            16 => Timer2,
-            29 => Software1,
-            _ => return None,
-        })
-    }
+        let mut handlers: [Option<unsafe extern "C" fn(u32, save_frame: &mut Context)>; 32] =
+            [None; 32];
+        handlers[6] = Some(Timer0);
+        handlers[7] = Some(Software0);
+        handlers[11] = Some(Profiling);
+        handlers[14] = Some(NMI);
+        handlers[15] = Some(Timer1);
+        handlers[16] = Some(Timer2);
+        handlers[29] = Some(Software1);
+        handlers
+    };
 
     #[no_mangle]
     #[link_section = ".rwtext"]
@@ -385,7 +380,7 @@ mod vectored {
             if (cpu_interrupt_mask & CPU_INTERRUPT_EDGE) != 0 {
                 interrupt::clear(1 << cpu_interrupt_nr);
             }
-            if let Some(handler) = cpu_interrupt_nr_to_cpu_interrupt_handler(cpu_interrupt_nr) {
+            if let Some(handler) = CPU_INTERRUPT_HANDLERS[cpu_interrupt_nr as usize] {
                 handler(level, save_frame);
             }
         } else {
@@ -401,13 +396,9 @@ mod vectored {
                     get_configured_interrupts(crate::get_core(), chip_specific::INTERRUPT_EDGE);
                 let interrupt_mask = interrupt_levels[level as usize];
                 let mut interrupt_mask = interrupt_mask & chip_specific::INTERRUPT_EDGE;
-                loop {
+                while interrupt_mask != 0 {
                     let interrupt_nr = interrupt_mask.trailing_zeros();
-                    if let Ok(interrupt) = pac::Interrupt::try_from(interrupt_nr as u16) {
-                        handle_interrupt(level, interrupt, save_frame)
-                    } else {
-                        break;
-                    }
+                    handle_interrupt(level, interrupt_nr as usize, save_frame);
                     interrupt_mask &= !(1u128 << interrupt_nr);
                 }
             } else {
@@ -418,31 +409,66 @@ mod vectored {
                 let interrupt_mask = status & interrupt_levels[level as usize];
                 let interrupt_nr = interrupt_mask.trailing_zeros();
 
-                // Interrupt::try_from can fail if interrupt already de-asserted:
-                // silently ignore
-                if let Ok(interrupt) = pac::Interrupt::try_from(interrupt_nr as u16) {
-                    handle_interrupt(level, interrupt, save_frame);
+                // the interrupt may already be de-asserted: silently ignore
+                if interrupt_mask != 0 {
+                    handle_interrupt(level, interrupt_nr as usize, save_frame);
                 }
             }
         }
     }
 
     #[ram]
-    unsafe fn handle_interrupt(level: u32, interrupt: Interrupt, save_frame: &mut Context) {
-        extern "C" {
-            // defined in each hal
-            fn EspDefaultHandler(level: u32, interrupt: Interrupt);
-        }
-
-        let handler = pac::__INTERRUPTS[interrupt.number() as usize]._handler;
-        if handler as *const _ == EspDefaultHandler as *const unsafe extern "C" fn() {
-            EspDefaultHandler(level, interrupt);
-        } else {
+    unsafe fn handle_interrupt(level: u32, interrupt_nr: usize, save_frame: &mut Context) {
+        // reserved interrupt numbers have no handler: silently ignore
+        let handler = vector(interrupt_nr);
+        if handler == EspDefaultHandler as usize {
+            if let Ok(interrupt) = pac::Interrupt::try_from(interrupt_nr as u16) {
+                EspDefaultHandler(level, interrupt);
+            }
+        } else if handler != 0 {
             let handler: fn(&mut Context) = core::mem::transmute(handler);
             handler(save_frame);
         }
     }
 
+    extern "C" {
+        // defined in each hal
+        fn EspDefaultHandler(level: u32, interrupt: Interrupt);
+    }
+
+    /// Copy the handler of `interrupt` to [VECTORS]
+    #[cfg(feature = "place-isr-in-ram")]
+    unsafe fn register_vector(interrupt: Interrupt) {
+        let interrupt_nr = interrupt.number() as usize;
+        let handler = pac::__INTERRUPTS[interrupt_nr]._reserved as usize;
+
+        debug_assert!(
+            handler == EspDefaultHandler as usize || crate::IRAM.contains(&handler),
+            "the handler of {:?} is not placed in RAM",
+            interrupt
+        );
+
+        VECTORS[interrupt_nr].store(handler, Ordering::Release);
+    }
+
+    /// The handler of the interrupt `interrupt_nr`, 0 for a reserved one
+    #[inline(always)]
+    unsafe fn vector(interrupt_nr: usize) -> usize {
+        #[cfg(feature = "place-isr-in-ram")]
+        if let Some(vector) = VECTORS.get(interrupt_nr) {
+            let handler = vector.load(Ordering::Acquire);
+            if handler != 0 {
+                return handler;
+            }
+        }
+
+        // without the feature, or mapped without `enable`: looked up in flash
+        match pac::__INTERRUPTS.get(interrupt_nr) {
+            Some(vector) => vector._reserved as usize,
+            None => 0,
+        }
+    }
+
     #[cfg(esp32)]
     mod chip_specific {
         use super::*;
@@ -553,12 +579,18 @@ mod raw {
     #[no_mangle]
     #[link_section = ".rwtext"]
     unsafe fn __level_6_interrupt(_level: u32, save_frame: &mut Context) {
+        // level 6 is the debug level, the data breakpoints end up here
+        #[cfg(feature = "stack-guard")]
+        crate::debug::check_stack_guard(save_frame.PC as usize, save_frame.A1 as usize);
+
         level6_interrupt(save_frame)
     }
 
     #[no_mangle]
     #[link_section = ".rwtext"]
     unsafe fn __level_7_interrupt(_level: u32, save_frame: &mut Context) {
-        level7_interrupt(save_frame)
+        if !crate::gpio::handle_nmi() {
+            level7_interrupt(save_frame)
+        }
     }
 }
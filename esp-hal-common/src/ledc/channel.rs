@@ -7,7 +7,7 @@ use super::{
     LowSpeed,
 };
 use crate::{
-    gpio::{types::OutputSignal, OutputPin},
+    gpio::{types::OutputSignal, OutputPin, PeripheralOutput},
     pac::ledc::RegisterBlock,
 };
 
@@ -20,6 +20,8 @@ pub enum Error {
     Timer,
     /// Channel not configured
     Channel,
+    /// Invalid phase or hpoint value
+    Phase,
 }
 
 /// Channel number
@@ -59,6 +61,22 @@ where
 
     /// Set channel duty HW
     fn set_duty(&self, duty_pct: u8) -> Result<(), Error>;
+
+    /// Delay the rising edge of the output by `degrees` (0 to 359) of the
+    /// timer period, see [`ChannelIFace::set_hpoint`]
+    fn set_phase(&self, degrees: u16) -> Result<(), Error>;
+
+    /// Set the timer count at which the output goes high (the hpoint)
+    ///
+    /// The output stays high for the duty cycle, wrapping around the end of
+    /// the period, so channels of one timer with the same duty and different
+    /// hpoints output the same signal shifted in phase. `hpoint` has to be
+    /// below 2 to the power of the duty resolution of the timer.
+    ///
+    /// Like a new duty, the hpoint takes effect at the start of the next
+    /// period. A duty and an hpoint set within the same period take effect
+    /// together. [`ChannelIFace::configure`] resets the hpoint to 0.
+    fn set_hpoint(&self, hpoint: u32) -> Result<(), Error>;
 }
 
 /// Channel HW interface
@@ -69,6 +87,15 @@ pub trait ChannelHW<O: OutputPin> {
 
     /// Set channel duty HW
     fn set_duty_hw(&self, duty: u32);
+
+    /// Set channel hpoint HW
+    fn set_hpoint_hw(&self, hpoint: u32);
+
+    /// Take over the duty and hpoint at the start of the next period
+    fn update_duty_hw(&self);
+
+    /// Output signal of the channel
+    fn output_signal_hw(&self) -> OutputSignal;
 }
 
 /// Channel struct
@@ -90,6 +117,28 @@ impl<'a, S: TimerSpeed, O: OutputPin> Channel<'a, S, O> {
             output_pin,
         }
     }
+
+    /// The pin the channel drives
+    ///
+    /// E.g. to monitor the output with
+    /// [`InputPin::connect_input_to_peripheral_keeping_function`](crate::gpio::InputPin::connect_input_to_peripheral_keeping_function)
+    /// after [`ChannelIFace::configure`] set up the pin.
+    pub fn output_pin(&mut self) -> &mut O {
+        &mut self.output_pin
+    }
+
+    /// Stop the channel and return the pin it drove
+    ///
+    /// Unlike dropping the channel, this leaves the pin connected to the
+    /// output signal of the channel, which idles low.
+    pub fn release(self) -> O {
+        self.stop_hw();
+
+        // NOTE(unsafe) `self` is forgotten right after moving the pin out
+        let output_pin = unsafe { core::ptr::read(&self.output_pin) };
+        core::mem::forget(self);
+        output_pin
+    }
 }
 
 impl<'a, S: TimerSpeed, O: OutputPin> ChannelIFace<'a, S, O> for Channel<'a, S, O>
@@ -108,18 +157,7 @@ where
 
     /// Set duty % of channel
     fn set_duty(&self, duty_pct: u8) -> Result<(), Error> {
-        let duty_exp;
-        if let Some(timer) = self.timer {
-            if let Some(timer_duty) = timer.get_duty() {
-                duty_exp = timer_duty as u32;
-            } else {
-                return Err(Error::Timer);
-            }
-        } else {
-            return Err(Error::Channel);
-        }
-
-        let duty_range = 2u32.pow(duty_exp);
+        let duty_range = self.duty_range()?;
         let duty_value = (duty_range * duty_pct as u32) as u32 / 100;
 
         if duty_value == 0 || duty_pct > 100u8 {
@@ -129,11 +167,44 @@ where
         }
 
         self.set_duty_hw(duty_value);
+        self.update_duty_hw();
+
+        Ok(())
+    }
+
+    /// Set phase of channel in degrees
+    fn set_phase(&self, degrees: u16) -> Result<(), Error> {
+        if degrees >= 360 {
+            return Err(Error::Phase);
+        }
+
+        let hpoint = self.duty_range()? * degrees as u32 / 360;
+        self.set_hpoint(hpoint)
+    }
+
+    /// Set hpoint of channel
+    fn set_hpoint(&self, hpoint: u32) -> Result<(), Error> {
+        if hpoint >= self.duty_range()? {
+            return Err(Error::Phase);
+        }
+
+        self.set_hpoint_hw(hpoint);
+        self.update_duty_hw();
 
         Ok(())
     }
 }
 
+impl<'a, S: TimerSpeed, O: OutputPin> Channel<'a, S, O> {
+    /// Number of timer counts per period
+    fn duty_range(&self) -> Result<u32, Error> {
+        let timer = self.timer.ok_or(Error::Channel)?;
+        let duty_exp = timer.get_duty().ok_or(Error::Timer)? as u32;
+
+        Ok(2u32.pow(duty_exp))
+    }
+}
+
 #[cfg(esp32)]
 /// Macro to configure channel parameters in hw
 macro_rules! set_channel {
@@ -216,6 +287,54 @@ macro_rules! set_duty {
     };
 }
 
+#[cfg(esp32)]
+/// Macro to set hpoint parameters in hw
+macro_rules! set_hpoint {
+    ($self: ident, $speed: ident, $num: literal, $hpoint: ident) => {
+        paste! {
+            $self.ledc
+                .[<$speed sch $num _hpoint>]
+                .write(|w| unsafe { w.[<hpoint>]().bits($hpoint as _) })
+        }
+    };
+}
+
+#[cfg(not(esp32))]
+/// Macro to set hpoint parameters in hw
+macro_rules! set_hpoint {
+    ($self: ident, $speed: ident, $num: literal, $hpoint: ident) => {
+        paste! {
+            $self.ledc
+                .[<ch $num _hpoint>]
+                .write(|w| unsafe { w.[<hpoint>]().bits($hpoint as _) })
+        }
+    };
+}
+
+#[cfg(esp32)]
+/// Macro to start using the duty parameters in hw
+macro_rules! start_duty {
+    ($self: ident, $speed: ident, $num: literal) => {
+        paste! {
+            $self.ledc
+                .[<$speed sch $num _conf1>]
+                .modify(|_, w| w.[<duty_start>]().set_bit())
+        }
+    };
+}
+
+#[cfg(not(esp32))]
+/// Macro to start using the duty parameters in hw
+macro_rules! start_duty {
+    ($self: ident, $speed: ident, $num: literal) => {
+        paste! {
+            $self.ledc
+                .[<ch $num _conf1>]
+                .modify(|_, w| w.[<duty_start>]().set_bit())
+        }
+    };
+}
+
 #[cfg(esp32)]
 /// Macro to update channel configuration (only for LowSpeed channels)
 macro_rules! update_channel {
@@ -240,6 +359,30 @@ macro_rules! update_channel {
     };
 }
 
+#[cfg(esp32)]
+/// Macro to stop a channel, its output idles low
+macro_rules! stop_channel {
+    ($self: ident, $speed: ident, $num: literal) => {
+        paste! {
+            $self.ledc
+                .[<$speed sch $num _conf0>]
+                .modify(|_, w| w.[<sig_out_en>]().clear_bit().[<idle_lv>]().clear_bit())
+        }
+    };
+}
+
+#[cfg(not(esp32))]
+/// Macro to stop a channel, its output idles low
+macro_rules! stop_channel {
+    ($self: ident, $speed: ident, $num: literal) => {
+        paste! {
+            $self.ledc
+                .[<ch $num _conf0>]
+                .modify(|_, w| w.[<sig_out_en>]().clear_bit().[<idle_lv>]().clear_bit())
+        }
+    };
+}
+
 #[cfg(esp32)]
 /// Channel HW interface for HighSpeed channels
 impl<'a, O> ChannelHW<O> for Channel<'a, HighSpeed, O>
@@ -260,45 +403,32 @@ where
             match self.number {
                 Number::Channel0 => {
                     set_channel!(self, h, 0, timer_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG0);
                 }
                 Number::Channel1 => {
                     set_channel!(self, h, 1, timer_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG1);
                 }
                 Number::Channel2 => {
                     set_channel!(self, h, 2, timer_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG2);
                 }
                 Number::Channel3 => {
                     set_channel!(self, h, 3, timer_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG3);
                 }
                 Number::Channel4 => {
                     set_channel!(self, h, 4, timer_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG4);
                 }
                 Number::Channel5 => {
                     set_channel!(self, h, 5, timer_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG5);
                 }
                 Number::Channel6 => {
                     set_channel!(self, h, 6, timer_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG6);
                 }
                 Number::Channel7 => {
                     set_channel!(self, h, 7, timer_number);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_HS_SIG7);
                 }
             }
+
+            let signal = self.output_signal_hw();
+            self.output_pin.connect_peripheral_to_output(signal);
         } else {
             return Err(Error::Timer);
         }
@@ -319,6 +449,49 @@ where
             Number::Channel7 => set_duty!(self, h, 7, duty),
         };
     }
+
+    /// Set hpoint in channel HW
+    fn set_hpoint_hw(&self, hpoint: u32) {
+        match self.number {
+            Number::Channel0 => set_hpoint!(self, h, 0, hpoint),
+            Number::Channel1 => set_hpoint!(self, h, 1, hpoint),
+            Number::Channel2 => set_hpoint!(self, h, 2, hpoint),
+            Number::Channel3 => set_hpoint!(self, h, 3, hpoint),
+            Number::Channel4 => set_hpoint!(self, h, 4, hpoint),
+            Number::Channel5 => set_hpoint!(self, h, 5, hpoint),
+            Number::Channel6 => set_hpoint!(self, h, 6, hpoint),
+            Number::Channel7 => set_hpoint!(self, h, 7, hpoint),
+        };
+    }
+
+    /// Take over the duty and hpoint at the start of the next period, the
+    /// HighSpeed channels need no update of their configuration for this
+    fn update_duty_hw(&self) {
+        match self.number {
+            Number::Channel0 => start_duty!(self, h, 0),
+            Number::Channel1 => start_duty!(self, h, 1),
+            Number::Channel2 => start_duty!(self, h, 2),
+            Number::Channel3 => start_duty!(self, h, 3),
+            Number::Channel4 => start_duty!(self, h, 4),
+            Number::Channel5 => start_duty!(self, h, 5),
+            Number::Channel6 => start_duty!(self, h, 6),
+            Number::Channel7 => start_duty!(self, h, 7),
+        };
+    }
+
+    /// Output signal of the channel
+    fn output_signal_hw(&self) -> OutputSignal {
+        match self.number {
+            Number::Channel0 => OutputSignal::LEDC_HS_SIG0,
+            Number::Channel1 => OutputSignal::LEDC_HS_SIG1,
+            Number::Channel2 => OutputSignal::LEDC_HS_SIG2,
+            Number::Channel3 => OutputSignal::LEDC_HS_SIG3,
+            Number::Channel4 => OutputSignal::LEDC_HS_SIG4,
+            Number::Channel5 => OutputSignal::LEDC_HS_SIG5,
+            Number::Channel6 => OutputSignal::LEDC_HS_SIG6,
+            Number::Channel7 => OutputSignal::LEDC_HS_SIG7,
+        }
+    }
 }
 
 /// Channel HW interface for LowSpeed channels
@@ -340,54 +513,41 @@ where
                 Number::Channel0 => {
                     set_channel!(self, l, 0, timer_number);
                     update_channel!(self, 0);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG0);
                 }
                 Number::Channel1 => {
                     set_channel!(self, l, 1, timer_number);
                     update_channel!(self, 1);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG1);
                 }
                 Number::Channel2 => {
                     set_channel!(self, l, 2, timer_number);
                     update_channel!(self, 2);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG2);
                 }
                 Number::Channel3 => {
                     set_channel!(self, l, 3, timer_number);
                     update_channel!(self, 3);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG3);
                 }
                 Number::Channel4 => {
                     set_channel!(self, l, 4, timer_number);
                     update_channel!(self, 4);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG4);
                 }
                 Number::Channel5 => {
                     set_channel!(self, l, 5, timer_number);
                     update_channel!(self, 5);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG5);
                 }
                 #[cfg(not(any(esp32c2, esp32c3)))]
                 Number::Channel6 => {
                     set_channel!(self, l, 6, timer_number);
                     update_channel!(self, 6);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG6);
                 }
                 #[cfg(not(any(esp32c2, esp32c3)))]
                 Number::Channel7 => {
                     set_channel!(self, l, 7, timer_number);
                     update_channel!(self, 7);
-                    self.output_pin
-                        .connect_peripheral_to_output(OutputSignal::LEDC_LS_SIG7);
                 }
             }
+
+            let signal = self.output_signal_hw();
+            self.output_pin.connect_peripheral_to_output(signal);
         } else {
             return Err(Error::Timer);
         }
@@ -410,4 +570,151 @@ where
             Number::Channel7 => set_duty!(self, l, 7, duty),
         };
     }
+
+    /// Set hpoint in channel HW
+    fn set_hpoint_hw(&self, hpoint: u32) {
+        match self.number {
+            Number::Channel0 => set_hpoint!(self, l, 0, hpoint),
+            Number::Channel1 => set_hpoint!(self, l, 1, hpoint),
+            Number::Channel2 => set_hpoint!(self, l, 2, hpoint),
+            Number::Channel3 => set_hpoint!(self, l, 3, hpoint),
+            Number::Channel4 => set_hpoint!(self, l, 4, hpoint),
+            Number::Channel5 => set_hpoint!(self, l, 5, hpoint),
+            #[cfg(not(any(esp32c2, esp32c3)))]
+            Number::Channel6 => set_hpoint!(self, l, 6, hpoint),
+            #[cfg(not(any(esp32c2, esp32c3)))]
+            Number::Channel7 => set_hpoint!(self, l, 7, hpoint),
+        };
+    }
+
+    /// Take over the duty and hpoint at the start of the next period, the
+    /// configuration update latches them at the overflow of the timer
+    fn update_duty_hw(&self) {
+        match self.number {
+            Number::Channel0 => {
+                start_duty!(self, l, 0);
+                update_channel!(self, 0);
+            }
+            Number::Channel1 => {
+                start_duty!(self, l, 1);
+                update_channel!(self, 1);
+            }
+            Number::Channel2 => {
+                start_duty!(self, l, 2);
+                update_channel!(self, 2);
+            }
+            Number::Channel3 => {
+                start_duty!(self, l, 3);
+                update_channel!(self, 3);
+            }
+            Number::Channel4 => {
+                start_duty!(self, l, 4);
+                update_channel!(self, 4);
+            }
+            Number::Channel5 => {
+                start_duty!(self, l, 5);
+                update_channel!(self, 5);
+            }
+            #[cfg(not(any(esp32c2, esp32c3)))]
+            Number::Channel6 => {
+                start_duty!(self, l, 6);
+                update_channel!(self, 6);
+            }
+            #[cfg(not(any(esp32c2, esp32c3)))]
+            Number::Channel7 => {
+                start_duty!(self, l, 7);
+                update_channel!(self, 7);
+            }
+        };
+    }
+
+    /// Output signal of the channel
+    fn output_signal_hw(&self) -> OutputSignal {
+        match self.number {
+            Number::Channel0 => OutputSignal::LEDC_LS_SIG0,
+            Number::Channel1 => OutputSignal::LEDC_LS_SIG1,
+            Number::Channel2 => OutputSignal::LEDC_LS_SIG2,
+            Number::Channel3 => OutputSignal::LEDC_LS_SIG3,
+            Number::Channel4 => OutputSignal::LEDC_LS_SIG4,
+            Number::Channel5 => OutputSignal::LEDC_LS_SIG5,
+            #[cfg(not(any(esp32c2, esp32c3)))]
+            Number::Channel6 => OutputSignal::LEDC_LS_SIG6,
+            #[cfg(not(any(esp32c2, esp32c3)))]
+            Number::Channel7 => OutputSignal::LEDC_LS_SIG7,
+        }
+    }
+}
+
+impl<'a, S: TimerSpeed, O: OutputPin> PeripheralOutput for Channel<'a, S, O>
+where
+    Channel<'a, S, O>: ChannelHW<O>,
+{
+    fn output_signal(&self) -> OutputSignal {
+        self.output_signal_hw()
+    }
+}
+
+impl<'a, S: TimerSpeed, O: OutputPin> Channel<'a, S, O> {
+    /// Stop the output of the channel, it idles low
+    fn stop_hw(&self) {
+        #[cfg(esp32)]
+        if S::IS_HS {
+            match self.number {
+                Number::Channel0 => stop_channel!(self, h, 0),
+                Number::Channel1 => stop_channel!(self, h, 1),
+                Number::Channel2 => stop_channel!(self, h, 2),
+                Number::Channel3 => stop_channel!(self, h, 3),
+                Number::Channel4 => stop_channel!(self, h, 4),
+                Number::Channel5 => stop_channel!(self, h, 5),
+                Number::Channel6 => stop_channel!(self, h, 6),
+                Number::Channel7 => stop_channel!(self, h, 7),
+            };
+            return;
+        }
+
+        match self.number {
+            Number::Channel0 => {
+                stop_channel!(self, l, 0);
+                update_channel!(self, 0);
+            }
+            Number::Channel1 => {
+                stop_channel!(self, l, 1);
+                update_channel!(self, 1);
+            }
+            Number::Channel2 => {
+                stop_channel!(self, l, 2);
+                update_channel!(self, 2);
+            }
+            Number::Channel3 => {
+                stop_channel!(self, l, 3);
+                update_channel!(self, 3);
+            }
+            Number::Channel4 => {
+                stop_channel!(self, l, 4);
+                update_channel!(self, 4);
+            }
+            Number::Channel5 => {
+                stop_channel!(self, l, 5);
+                update_channel!(self, 5);
+            }
+            #[cfg(not(any(esp32c2, esp32c3)))]
+            Number::Channel6 => {
+                stop_channel!(self, l, 6);
+                update_channel!(self, 6);
+            }
+            #[cfg(not(any(esp32c2, esp32c3)))]
+            Number::Channel7 => {
+                stop_channel!(self, l, 7);
+                update_channel!(self, 7);
+            }
+        };
+    }
+}
+
+/// Stops the channel and leaves its pin as a floating input
+impl<'a, S: TimerSpeed, O: OutputPin> Drop for Channel<'a, S, O> {
+    fn drop(&mut self) {
+        self.stop_hw();
+        crate::gpio::pad::float_pad(self.output_pin.number());
+    }
 }
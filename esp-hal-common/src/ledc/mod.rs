@@ -58,6 +58,31 @@
 //!     .unwrap();
 //! ```
 //!
+//! # Phase
+//!
+//! The output of a channel goes high when its timer reaches the hpoint of
+//! the channel, 0 after [configure](channel::ChannelIFace::configure). With
+//! [set_phase](channel::ChannelIFace::set_phase) or
+//! [set_hpoint](channel::ChannelIFace::set_hpoint) channels of one timer
+//! output their signals shifted against each other, e.g. for interleaved
+//! multi-phase converters:
+//!
+//! ```rust,ignore
+//! channel0.set_phase(0).unwrap();
+//! channel1.set_phase(180).unwrap();
+//! ```
+//!
+//! New duties and hpoints take effect at the start of the next period, so
+//! updates don't cut a period short or stretch it.
+//!
+//! # Clock changes
+//!
+//! [LEDC] borrows the [Clocks] and doesn't implement
+//! [ClockListener](crate::clock::ClockListener), so
+//! [Clocks::set_cpu_clock] can't be called while it exists. To change the
+//! CPU clock drop the LEDC with its timers and channels first, then create
+//! and configure them again with the new clocks.
+//!
 //! # TODO
 //!
 //! - Source clock selection
@@ -97,12 +122,19 @@ pub struct HighSpeed {}
 /// Used to specify LowSpeed Timer/Channel
 pub struct LowSpeed {}
 
-pub trait Speed {}
+pub trait Speed {
+    /// Whether these are the HighSpeed timers and channels
+    const IS_HS: bool;
+}
 
 #[cfg(esp32)]
-impl Speed for HighSpeed {}
+impl Speed for HighSpeed {
+    const IS_HS: bool = true;
+}
 
-impl Speed for LowSpeed {}
+impl Speed for LowSpeed {
+    const IS_HS: bool = false;
+}
 
 impl<'a> LEDC<'a> {
     /// Return a new LEDC
@@ -17,6 +17,45 @@
 //! [esp32c3-hal]: https://github.com/esp-rs/esp-hal/tree/main/esp32c3-hal
 //! [esp32s2-hal]: https://github.com/esp-rs/esp-hal/tree/main/esp32s2-hal
 //! [esp32s3-hal]: https://github.com/esp-rs/esp-hal/tree/main/esp32s3-hal
+//!
+//! ## Interrupts during flash writes
+//!
+//! While the flash is written or erased its cache is disabled, code and
+//! constants placed in flash can't be read until the operation completed,
+//! which takes milliseconds for an erase. Interrupts whose handlers are in
+//! flash are delayed for that long. The `place-isr-in-ram` feature places
+//! the interrupt path of the HAL in RAM:
+//!
+//! - the trap entry of the RISC-V chips and the dispatch of the vectored
+//!   interrupts, with a copy of the handler of each interrupt enabled with
+//!   `interrupt::enable` and of the CPU interrupt tables of the Xtensa chips
+//! - the internal CPU interrupts of the Xtensa chips, e.g. the software
+//!   interrupts, and the critical section implementation
+//! - the GPIO dispatch of `gpio::dispatch::handle_interrupt`, and the pin
+//!   functions to check and clear the interrupt and to set, read and toggle
+//!   outputs
+//! - the TIMG functions to check, clear and re-arm the alarm
+//!   (`is_interrupt_set`, `clear_interrupt`, `set_alarm_active`, `wait`) and to
+//!   read the counter
+//! - the SYSTIMER functions `now`, `clear_interrupt` and `set_target`
+//!
+//! The handlers have to be placed in RAM with `#[ram]` too, debug builds
+//! check this in `interrupt::enable`. Anything they call has to be in RAM as
+//! well: `CountDown::start` of the timers divides 64 bit numbers, which is a
+//! library call placed in flash, so use an auto-reloading timer and
+//! `wait()` in the handler. On the ESP32-C2/C3 atomic read-modify-write
+//! operations (e.g. `fetch_add`) are emulated by an exception handler placed
+//! in flash, the level events of [gpio::dispatch] use them. On the ESP32 and
+//! ESP32-S3 the critical section takes a spin lock which may be placed in
+//! flash. Interrupts mapped with `interrupt::map` look their handler up in
+//! flash.
+//!
+//! Besides the code, the feature places 256 bytes (RISC-V) or 384 bytes
+//! (Xtensa) of handler copies and 124 bytes (RISC-V) or 160 bytes (Xtensa)
+//! of CPU interrupt tables in RAM. The size of the code depends on the
+//! optimization level and on the drivers used, compare the size of the
+//! `.rwtext` section with and without the feature, e.g. with
+//! `cargo size --release -- -A`.
 
 #![no_std]
 #![cfg_attr(xtensa, feature(asm_experimental_arch))]
@@ -31,6 +70,8 @@ pub use esp32c3 as pac;
 pub use esp32s2 as pac;
 #[cfg(esp32s3)]
 pub use esp32s3 as pac;
+#[doc(hidden)]
+pub use paste;
 pub use procmacros as macros;
 
 #[cfg(rmt)]
@@ -49,33 +90,67 @@ pub use self::{
 };
 
 pub mod analog;
+pub mod chip;
 pub mod clock;
+pub mod coex;
+mod crypto_dma;
+#[cfg(feature = "stack-guard")]
+pub mod debug;
 pub mod delay;
+#[cfg(all(any(esp32c3, esp32s3), feature = "direct-boot"))]
+pub mod direct_boot;
 pub mod dma;
+#[cfg(ds)]
+pub mod ds;
+#[cfg(emac)]
+pub mod emac;
 #[cfg(feature = "embassy")]
 pub mod embassy;
 pub mod gpio;
+#[cfg(hmac)]
+pub mod hmac;
 pub mod i2c;
 #[cfg(i2s)]
 pub mod i2s;
+pub mod init;
 pub mod ledc;
+#[cfg(feature = "logger")]
+pub mod logger;
 #[cfg(mcpwm)]
 pub mod mcpwm;
+pub mod mode;
+pub mod one_wire;
 #[cfg(usb_otg)]
 pub mod otg_fs;
 pub mod prelude;
+pub mod profiling;
+#[cfg(all(esp32s3, feature = "psram"))]
+pub mod psram;
+#[cfg(all(not(esp32s3), feature = "psram"))]
+compile_error!("The `psram` feature is only supported on the ESP32-S3");
 #[cfg(rmt)]
 pub mod pulse_control;
+#[cfg(esp32s3)]
+pub mod reset;
 pub mod rng;
 pub mod rom;
 pub mod rtc_cntl;
+#[cfg(feature = "sdmmc")]
+pub mod sd_spi;
+#[cfg(sdio_slave)]
+pub mod sdio_slave;
 pub mod serial;
 pub mod sha;
 pub mod spi;
 pub mod system;
 #[cfg(systimer)]
 pub mod systimer;
+pub mod time;
 pub mod timer;
+#[cfg(twai)]
+pub mod twai;
+#[cfg(ulp)]
+pub mod ulp;
 #[cfg(usb_serial_jtag)]
 pub mod usb_serial_jtag;
 #[cfg(rmt)]
@@ -92,6 +167,8 @@ pub mod cpu_control;
 #[cfg_attr(esp32s2, path = "efuse/esp32s2.rs")]
 #[cfg_attr(esp32s3, path = "efuse/esp32s3.rs")]
 pub mod efuse;
+#[cfg(all(not(any(esp32c3, esp32s3)), feature = "efuse-writing"))]
+compile_error!("The `efuse-writing` feature is only supported on the ESP32-C3 and ESP32-S3");
 
 #[cfg_attr(riscv, path = "interrupt/riscv.rs")]
 #[cfg_attr(xtensa, path = "interrupt/xtensa.rs")]
@@ -106,6 +183,7 @@ pub enum Cpu {
     AppCpu,
 }
 
+#[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
 pub fn get_core() -> Cpu {
     #[cfg(all(xtensa, multi_core))]
     match ((xtensa_lx::get_processor_id() >> 13) & 1) != 0 {
@@ -121,6 +199,19 @@ pub fn get_core() -> Cpu {
     Cpu::ProCpu
 }
 
+/// Address range of the instruction bus of the internal SRAM, where `#[ram]`
+/// places functions
+#[cfg(esp32)]
+pub(crate) const IRAM: core::ops::Range<usize> = 0x4007_0000..0x400a_0000;
+#[cfg(esp32c2)]
+pub(crate) const IRAM: core::ops::Range<usize> = 0x4037_c000..0x403c_0000;
+#[cfg(esp32c3)]
+pub(crate) const IRAM: core::ops::Range<usize> = 0x4037_c000..0x403e_0000;
+#[cfg(esp32s2)]
+pub(crate) const IRAM: core::ops::Range<usize> = 0x4002_0000..0x4007_0000;
+#[cfg(esp32s3)]
+pub(crate) const IRAM: core::ops::Range<usize> = 0x4037_0000..0x403e_0000;
+
 mod critical_section_impl {
     struct CriticalSection;
 
@@ -129,6 +220,7 @@ mod critical_section_impl {
     #[cfg(xtensa)]
     mod xtensa {
         unsafe impl critical_section::Impl for super::CriticalSection {
+            #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
             unsafe fn acquire() -> critical_section::RawRestoreState {
                 let tkn: critical_section::RawRestoreState;
                 core::arch::asm!("rsil {0}, 15", out(reg) tkn);
@@ -140,6 +232,7 @@ mod critical_section_impl {
                 tkn
             }
 
+            #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
             unsafe fn release(token: critical_section::RawRestoreState) {
                 if token != 0 {
                     #[cfg(multi_core)]
@@ -159,6 +252,7 @@ mod critical_section_impl {
     #[cfg(riscv)]
     mod riscv {
         unsafe impl critical_section::Impl for super::CriticalSection {
+            #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
             unsafe fn acquire() -> critical_section::RawRestoreState {
                 let mut mstatus = 0u32;
                 core::arch::asm!("csrrci {0}, mstatus, 8", inout(reg) mstatus);
@@ -172,6 +266,7 @@ mod critical_section_impl {
                 interrupts_active as _
             }
 
+            #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
             unsafe fn release(token: critical_section::RawRestoreState) {
                 if token != 0 {
                     #[cfg(multi_core)]
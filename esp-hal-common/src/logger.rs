@@ -0,0 +1,452 @@
+//! Logging through UART0 or the USB Serial/JTAG controller
+//!
+//! Unlike `esp-println`, which writes to the hardware directly, this logger
+//! takes the peripheral it logs through, so no other driver can use it at
+//! the same time:
+//!
+//! ```no_run
+//! logger::init_uart0(peripherals.UART0, &clocks, log::LevelFilter::Info);
+//! interrupt::enable(pac::Interrupt::UART0, interrupt::Priority::Priority1).unwrap();
+//!
+//! #[interrupt]
+//! fn UART0() {
+//!     logger::handle_interrupt();
+//! }
+//!
+//! log::info!("logged from anywhere, including interrupts");
+//! ```
+//!
+//! Log records are formatted on the stack and appended to a ring buffer of
+//! [BUFFER_SIZE] bytes in one short critical section, so records logged from
+//! `main`, tasks and interrupts never interleave. Records longer than
+//! [RECORD_SIZE] bytes are appended in pieces of that size, which may
+//! interleave. The buffer is drained by [handle_interrupt], which has to be
+//! called from the (low priority) interrupt of the peripheral. If a record
+//! doesn't fit, logging moves buffered bytes to the hardware itself until it
+//! does, with interrupts enabled in between. Bytes which the hardware
+//! doesn't accept within [STALL_TIMEOUT] (e.g. no USB host reading) are
+//! dropped and counted by [dropped].
+//!
+//! A panic handler should call [flush] and print the panic message with
+//! [write_blocking], which both wait for the hardware instead of the
+//! interrupt.
+//!
+//! With the `defmt` feature the logger is also the `defmt` global logger.
+//! The `defmt` frames share the buffer with the `log` records, use only one
+//! of them per binary to keep the output decodable. `defmt` holds a critical
+//! section for each frame, so frame bytes which don't fit into the buffer
+//! are dropped instead of waited for.
+
+use core::{cell::RefCell, fmt};
+
+use critical_section::Mutex;
+use fugit::MicrosDurationU64;
+use heapless::Deque;
+
+use crate::{clock::Clocks, pac::UART0, time::Deadline, Serial};
+#[cfg(usb_serial_jtag)]
+use crate::{pac::USB_DEVICE, UsbSerialJtag};
+
+/// Size of the ring buffer between the loggers and the hardware
+pub const BUFFER_SIZE: usize = 1024;
+
+/// Size of the stack buffer a record is formatted into before it is
+/// appended to the ring buffer in one piece
+pub const RECORD_SIZE: usize = 256;
+
+/// Time after which bytes the hardware doesn't take are dropped
+pub const STALL_TIMEOUT: MicrosDurationU64 = MicrosDurationU64::millis(50);
+
+/// The UART interrupt is raised once fewer bytes are left in the TX FIFO
+const UART_TX_FIFO_EMPTY_THRESHOLD: u16 = 16;
+
+enum Sink {
+    Uart0(Serial<UART0>),
+    #[cfg(usb_serial_jtag)]
+    UsbSerialJtag(UsbSerialJtag<USB_DEVICE>),
+}
+
+impl Sink {
+    /// Move bytes from `buffer` to the hardware without waiting, returns
+    /// whether it took any
+    fn fill(&mut self, buffer: &mut Deque<u8, BUFFER_SIZE>) -> bool {
+        let mut moved = false;
+
+        match self {
+            Sink::Uart0(serial) => {
+                while let Some(&byte) = buffer.front() {
+                    if serial.write_nb(byte).is_err() {
+                        break;
+                    }
+                    buffer.pop_front();
+                    moved = true;
+                }
+            }
+            #[cfg(usb_serial_jtag)]
+            Sink::UsbSerialJtag(usb_serial) => {
+                while let Some(&byte) = buffer.front() {
+                    if usb_serial.write_byte_nb(byte).is_err() {
+                        break;
+                    }
+                    buffer.pop_front();
+                    moved = true;
+                }
+                if moved {
+                    usb_serial.flush_tx_nb().ok();
+                }
+            }
+        }
+
+        moved
+    }
+
+    fn is_idle(&mut self) -> bool {
+        match self {
+            Sink::Uart0(serial) => serial.flush_nb().is_ok(),
+            #[cfg(usb_serial_jtag)]
+            Sink::UsbSerialJtag(usb_serial) => usb_serial.flush_tx_nb().is_ok(),
+        }
+    }
+
+    fn listen(&mut self, enable: bool) {
+        match (self, enable) {
+            (Sink::Uart0(serial), true) => serial.listen_tx_fifo_empty(),
+            (Sink::Uart0(serial), false) => serial.unlisten_tx_fifo_empty(),
+            #[cfg(usb_serial_jtag)]
+            (Sink::UsbSerialJtag(usb_serial), true) => usb_serial.listen_tx_empty_interrupt(),
+            #[cfg(usb_serial_jtag)]
+            (Sink::UsbSerialJtag(usb_serial), false) => usb_serial.unlisten_tx_empty_interrupt(),
+        }
+    }
+
+    fn reset_interrupt(&mut self) {
+        match self {
+            Sink::Uart0(serial) => serial.reset_tx_fifo_empty_interrupt(),
+            #[cfg(usb_serial_jtag)]
+            Sink::UsbSerialJtag(usb_serial) => usb_serial.reset_tx_empty_interrupt(),
+        }
+    }
+}
+
+struct State {
+    sink: Sink,
+    buffer: Deque<u8, BUFFER_SIZE>,
+    dropped: usize,
+}
+
+impl State {
+    /// Append all of `bytes` if they fit, returns whether they did
+    fn try_push(&mut self, bytes: &[u8]) -> bool {
+        if self.buffer.capacity() - self.buffer.len() < bytes.len() {
+            return false;
+        }
+
+        for &byte in bytes {
+            self.buffer.push_back(byte).ok();
+        }
+        true
+    }
+
+    /// Append the part of `bytes` which fits, the rest is dropped
+    #[cfg(feature = "defmt")]
+    fn push_or_drop(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            if self.buffer.push_back(byte).is_err() {
+                self.dropped += 1;
+            }
+        }
+    }
+
+    /// Drop the buffered bytes
+    fn drop_buffered(&mut self) {
+        self.dropped += self.buffer.len();
+        self.buffer.clear();
+    }
+
+    /// Hand the buffered bytes to the hardware, continued by the interrupt
+    fn start(&mut self) {
+        self.sink.fill(&mut self.buffer);
+        self.sink.listen(!self.buffer.is_empty());
+    }
+
+    /// Wait until the hardware took all buffered bytes, or stopped taking
+    /// bytes for [STALL_TIMEOUT]
+    ///
+    /// Only for `defmt`, which calls it with interrupts disabled anyway, see
+    /// [flush] for everything else.
+    #[cfg(feature = "defmt")]
+    fn drain_blocking(&mut self) {
+        let mut deadline = Deadline::after(STALL_TIMEOUT);
+
+        while !self.buffer.is_empty() {
+            if self.sink.fill(&mut self.buffer) {
+                deadline = Deadline::after(STALL_TIMEOUT);
+            } else if deadline.is_expired() {
+                self.drop_buffered();
+            }
+        }
+
+        while !self.sink.is_idle() && !deadline.is_expired() {}
+    }
+}
+
+/// Outcome of one step of [wait_for], taken in a critical section
+enum Step {
+    Done,
+    Moved,
+    Dropped,
+    Stalled,
+}
+
+/// Append `bytes` to the buffer in pieces of at most [BUFFER_SIZE]
+///
+/// Each piece is appended in one critical section. While a piece doesn't
+/// fit, buffered bytes are moved to the hardware in short critical
+/// sections, interrupts can run in between. Buffered bytes are dropped if
+/// the hardware doesn't take any for [STALL_TIMEOUT].
+fn push(bytes: &[u8]) {
+    for piece in bytes.chunks(BUFFER_SIZE) {
+        wait_for(|state| {
+            if state.try_push(piece) {
+                state.start();
+                true
+            } else {
+                false
+            }
+        });
+    }
+}
+
+/// Move buffered bytes to the hardware in short critical sections until
+/// `done` returns `true`
+///
+/// If the hardware doesn't take any bytes for [STALL_TIMEOUT], the buffered
+/// bytes are dropped, or, with the buffer empty already, waiting stops.
+fn wait_for(mut done: impl FnMut(&mut State) -> bool) {
+    let mut deadline = Deadline::after(STALL_TIMEOUT);
+
+    loop {
+        let step = critical_section::with(|cs| {
+            let mut state = STATE.borrow_ref_mut(cs);
+            let state = match state.as_mut() {
+                Some(state) => state,
+                None => return Step::Done,
+            };
+
+            if done(state) {
+                Step::Done
+            } else if state.sink.fill(&mut state.buffer) {
+                Step::Moved
+            } else if !deadline.is_expired() {
+                Step::Stalled
+            } else if state.buffer.is_empty() {
+                Step::Done
+            } else {
+                state.drop_buffered();
+                Step::Dropped
+            }
+        });
+
+        match step {
+            Step::Done => break,
+            Step::Moved | Step::Dropped => deadline = Deadline::after(STALL_TIMEOUT),
+            Step::Stalled => {}
+        }
+    }
+}
+
+/// Formats into a buffer on the stack, which is appended to the ring buffer
+/// in one piece when it is full or the record is complete
+struct Record {
+    bytes: heapless::Vec<u8, RECORD_SIZE>,
+}
+
+impl Record {
+    fn new() -> Self {
+        Self {
+            bytes: heapless::Vec::new(),
+        }
+    }
+
+    fn push(self) {
+        push(&self.bytes);
+    }
+}
+
+impl fmt::Write for Record {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for &byte in s.as_bytes() {
+            if self.bytes.is_full() {
+                push(&self.bytes);
+                self.bytes.clear();
+            }
+            self.bytes.push(byte).ok();
+        }
+        Ok(())
+    }
+}
+
+static STATE: Mutex<RefCell<Option<State>>> = Mutex::new(RefCell::new(None));
+
+/// Log through UART0 with the console configuration left by the bootloader
+///
+/// Takes the peripheral, no [Serial] driver for UART0 can be created while
+/// the logger uses it. The `UART0` interrupt has to call
+/// [handle_interrupt].
+pub fn init_uart0(uart0: UART0, clocks: &Clocks, level: log::LevelFilter) {
+    let mut serial = Serial::new_console(uart0, clocks);
+    serial.set_tx_fifo_empty_threshold(UART_TX_FIFO_EMPTY_THRESHOLD);
+    install(Sink::Uart0(serial), level);
+}
+
+/// Log through the USB Serial/JTAG controller
+///
+/// Takes the peripheral, the `USB_SERIAL_JTAG` interrupt has to call
+/// [handle_interrupt].
+#[cfg(usb_serial_jtag)]
+pub fn init_usb_serial_jtag(usb_device: USB_DEVICE, level: log::LevelFilter) {
+    install(Sink::UsbSerialJtag(UsbSerialJtag::new(usb_device)), level);
+}
+
+fn install(sink: Sink, level: log::LevelFilter) {
+    critical_section::with(|cs| {
+        STATE.borrow_ref_mut(cs).replace(State {
+            sink,
+            buffer: Deque::new(),
+            dropped: 0,
+        });
+
+        // SAFETY: no other logger is installed concurrently, this runs in a
+        // critical section
+        unsafe {
+            log::set_logger_racy(&Logger).ok();
+        }
+    });
+    log::set_max_level(level);
+}
+
+/// Move buffered bytes to the hardware
+///
+/// To be called from the interrupt of the peripheral passed to the `init_*`
+/// function.
+pub fn handle_interrupt() {
+    critical_section::with(|cs| {
+        if let Some(state) = STATE.borrow_ref_mut(cs).as_mut() {
+            state.sink.reset_interrupt();
+            state.start();
+        }
+    });
+}
+
+/// Wait until all buffered bytes were sent
+///
+/// Moves the bytes to the hardware itself, in short critical sections.
+pub fn flush() {
+    wait_for(|state| {
+        if state.buffer.is_empty() && state.sink.is_idle() {
+            state.sink.listen(false);
+            true
+        } else {
+            false
+        }
+    });
+}
+
+/// Print `args` after the buffered bytes and wait until it was sent, e.g.
+/// the message in a panic handler
+pub fn write_blocking(args: fmt::Arguments) {
+    let mut record = Record::new();
+    fmt::Write::write_fmt(&mut record, args).ok();
+    record.push();
+    flush();
+}
+
+/// Number of bytes dropped because the hardware didn't take them
+pub fn dropped() -> usize {
+    critical_section::with(|cs| {
+        STATE
+            .borrow_ref(cs)
+            .as_ref()
+            .map_or(0, |state| state.dropped)
+    })
+}
+
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, _metadata: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let mut formatted = Record::new();
+        fmt::Write::write_fmt(
+            &mut formatted,
+            format_args!("{} - {}\r\n", record.level(), record.args()),
+        )
+        .ok();
+        formatted.push();
+    }
+
+    fn flush(&self) {
+        flush();
+    }
+}
+
+#[cfg(feature = "defmt")]
+mod defmt_logger {
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use critical_section::{CriticalSection, RestoreState};
+
+    use super::*;
+
+    static TAKEN: AtomicBool = AtomicBool::new(false);
+    static mut RESTORE_STATE: RestoreState = RestoreState::invalid();
+    static mut ENCODER: defmt::Encoder = defmt::Encoder::new();
+
+    #[defmt::global_logger]
+    struct DefmtLogger;
+
+    /// Runs `f` with the state, only called between `acquire` and `release`
+    unsafe fn with_state(f: impl FnOnce(&mut State)) {
+        let cs = CriticalSection::new();
+        if let Some(state) = STATE.borrow_ref_mut(cs).as_mut() {
+            f(state);
+        }
+    }
+
+    unsafe impl defmt::Logger for DefmtLogger {
+        fn acquire() {
+            let restore_state = unsafe { critical_section::acquire() };
+
+            if TAKEN.load(Ordering::Relaxed) {
+                panic!("defmt logger taken reentrantly");
+            }
+            TAKEN.store(true, Ordering::Relaxed);
+
+            unsafe {
+                RESTORE_STATE = restore_state;
+                with_state(|state| ENCODER.start_frame(|bytes| state.push_or_drop(bytes)));
+            }
+        }
+
+        unsafe fn flush() {
+            with_state(|state| state.drain_blocking());
+        }
+
+        unsafe fn release() {
+            with_state(|state| {
+                ENCODER.end_frame(|bytes| state.push_or_drop(bytes));
+                state.start();
+            });
+
+            TAKEN.store(false, Ordering::Relaxed);
+            critical_section::release(RESTORE_STATE);
+        }
+
+        unsafe fn write(bytes: &[u8]) {
+            with_state(|state| ENCODER.write(bytes, |bytes| state.push_or_drop(bytes)));
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use core::marker::PhantomData;
+
+use crate::{mcpwm::PwmPeripheral, InputPin};
+
+/// A MCPWM fault detector
+///
+/// Every fault detector of a particular [`MCPWM`](super::MCPWM) peripheral
+/// monitors one input pin and can trip every
+/// [`Operator`](super::operator::Operator) of that peripheral, see
+/// [`Operator::set_trip`](super::operator::Operator::set_trip).
+pub struct FaultDetector<const F: u8, PWM> {
+    phantom: PhantomData<PWM>,
+}
+
+impl<const F: u8, PWM: PwmPeripheral> FaultDetector<F, PWM> {
+    pub(super) fn new() -> Self {
+        FaultDetector {
+            phantom: PhantomData,
+        }
+    }
+
+    /// Monitor the given pin, the fault is active while the pin is at
+    /// `active_level`
+    pub fn with_pin<Pin: InputPin>(
+        self,
+        mut pin: Pin,
+        active_level: bool,
+    ) -> FaultPin<Pin, PWM, F> {
+        pin.enable_input(true)
+            .connect_input_to_peripheral(PWM::fault_signal::<F>());
+
+        // SAFETY:
+        // We only write to our bits of the FAULT_DETECT register
+        let block = unsafe { &*PWM::block() };
+        critical_section::with(|_| {
+            block.fault_detect.modify(|r, w| unsafe {
+                // Fx_EN is bit F, Fx_POLE bit 3 + F
+                let bits =
+                    (r.bits() & !(1 << (3 + F))) | (1 << F) | ((active_level as u32) << (3 + F));
+                w.bits(bits)
+            })
+        });
+
+        FaultPin {
+            _pin: pin,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// A pin monitored by an MCPWM fault detector
+pub struct FaultPin<Pin, PWM, const F: u8> {
+    _pin: Pin,
+    phantom: PhantomData<PWM>,
+}
+
+impl<Pin: InputPin, PWM: PwmPeripheral, const F: u8> FaultPin<Pin, PWM, F> {
+    /// Whether the fault is currently active
+    pub fn is_active(&self) -> bool {
+        // SAFETY:
+        // We only read from the FAULT_DETECT register
+        let block = unsafe { &*PWM::block() };
+
+        // EVENT_Fx is bit 6 + F
+        block.fault_detect.read().bits() & (1 << (6 + F)) != 0
+    }
+}
@@ -12,14 +12,13 @@
 //!       independently, in symmetric and asymmetric configuration.
 //!     * Software, asynchronously override control of PWM signals.
 //!     * Configurable dead-time on rising and falling edges; each set up
-//!       independently. (Not yet implemented)
+//!       independently.
 //!     * All events can trigger CPU interrupts. (Not yet implemented)
 //!     * Modulating of PWM output by high-frequency carrier signals, useful
-//!       when gate drivers are insulated with a transformer. (Not yet
-//!       implemented)
+//!       when gate drivers are insulated with a transformer.
 //!     * Period, time stamps and important control registers have shadow
 //!       registers with flexible updating methods.
-//! * Fault Detection Module (Not yet implemented)
+//! * Fault Detection Module
 //! * Capture Module (Not yet implemented)
 //!
 //! # Example
@@ -59,6 +58,7 @@
 
 use core::{marker::PhantomData, ops::Deref};
 
+use fault_detector::FaultDetector;
 use fugit::HertzU32;
 use operator::Operator;
 use timer::Timer;
@@ -66,9 +66,11 @@ use timer::Timer;
 use crate::{
     clock::Clocks,
     system::{Peripheral, PeripheralClockControl},
-    types::OutputSignal,
+    types::{InputSignal, OutputSignal},
 };
 
+/// MCPWM fault detectors
+pub mod fault_detector;
 /// MCPWM operators
 pub mod operator;
 /// MCPWM timers
@@ -89,6 +91,12 @@ pub struct MCPWM<PWM> {
     pub operator1: Operator<1, PWM>,
     /// Operator2
     pub operator2: Operator<2, PWM>,
+    /// Fault detector 0
+    pub fault_detector0: FaultDetector<0, PWM>,
+    /// Fault detector 1
+    pub fault_detector1: FaultDetector<1, PWM>,
+    /// Fault detector 2
+    pub fault_detector2: FaultDetector<2, PWM>,
 }
 
 impl<PWM: PwmPeripheral> MCPWM<PWM> {
@@ -117,6 +125,9 @@ impl<PWM: PwmPeripheral> MCPWM<PWM> {
             operator0: Operator::new(),
             operator1: Operator::new(),
             operator2: Operator::new(),
+            fault_detector0: FaultDetector::new(),
+            fault_detector1: FaultDetector::new(),
+            fault_detector2: FaultDetector::new(),
         }
     }
 }
@@ -241,6 +252,8 @@ pub unsafe trait PwmPeripheral: Deref<Target = crate::pac::pwm0::RegisterBlock>
     fn block() -> *const crate::pac::pwm0::RegisterBlock;
     /// Get operator GPIO mux output signal
     fn output_signal<const OP: u8, const IS_A: bool>() -> OutputSignal;
+    /// Get fault detector GPIO mux input signal
+    fn fault_signal<const F: u8>() -> InputSignal;
 }
 
 unsafe impl PwmPeripheral for crate::pac::PWM0 {
@@ -263,6 +276,15 @@ unsafe impl PwmPeripheral for crate::pac::PWM0 {
             _ => unreachable!(),
         }
     }
+
+    fn fault_signal<const F: u8>() -> InputSignal {
+        match F {
+            0 => InputSignal::PWM0_F0,
+            1 => InputSignal::PWM0_F1,
+            2 => InputSignal::PWM0_F2,
+            _ => unreachable!(),
+        }
+    }
 }
 
 unsafe impl PwmPeripheral for crate::pac::PWM1 {
@@ -285,4 +307,13 @@ unsafe impl PwmPeripheral for crate::pac::PWM1 {
             _ => unreachable!(),
         }
     }
+
+    fn fault_signal<const F: u8>() -> InputSignal {
+        match F {
+            0 => InputSignal::PWM1_F0,
+            1 => InputSignal::PWM1_F1,
+            2 => InputSignal::PWM1_F2,
+            _ => unreachable!(),
+        }
+    }
 }
@@ -1,7 +1,16 @@
 use core::marker::PhantomData;
 
+use fugit::HertzU32;
+
 use crate::{
-    mcpwm::{timer::Timer, PwmPeripheral},
+    gpio::{types::OutputSignal, PeripheralOutput},
+    mcpwm::{
+        fault_detector::FaultPin,
+        timer::Timer,
+        FrequencyError,
+        PeripheralClockConfig,
+        PwmPeripheral,
+    },
     OutputPin,
 };
 
@@ -11,10 +20,15 @@ use crate::{
 /// * Generates a PWM signal pair, based on timing references obtained from the
 ///   corresponding PWM timer.
 /// * Each signal out of the PWM signal pair includes a specific pattern of dead
-///   time. (Not yet implemented)
-/// * Superimposes a carrier on the PWM signal, if configured to do so. (Not yet
-///   implemented)
-/// * Handles response under fault conditions. (Not yet implemented)
+///   time, see [`Operator::set_deadtime`].
+/// * Superimposes a carrier on the PWM signal, if configured to do so, see
+///   [`Operator::set_carrier`].
+/// * Handles response under fault conditions, see [`Operator::set_trip`].
+///
+/// The stages are applied in this order: the generators produce the PWM
+/// signals, the dead time is inserted, the carrier chops the result and the
+/// fault handler overrides the outputs while the operator is tripped. A trip
+/// therefore takes effect right away, independent of the carrier.
 pub struct Operator<const OP: u8, PWM> {
     phantom: PhantomData<PWM>,
 }
@@ -51,6 +65,66 @@ impl<const OP: u8, PWM: PwmPeripheral> Operator<OP, PWM> {
         });
     }
 
+    /// Configure the dead time inserted between the A and B outputs
+    ///
+    /// By default the dead time module is bypassed and both outputs follow
+    /// their generators.
+    pub fn set_deadtime(&mut self, config: DeadTimeConfig) {
+        dt_red_cfg::<OP, PWM>().write(|w| unsafe { w.bits(config.rising_edge_delay as u32) });
+        dt_fed_cfg::<OP, PWM>().write(|w| unsafe { w.bits(config.falling_edge_delay as u32) });
+        // update the delays immediately, clocked by the MCPWM clock
+        dt_cfg::<OP, PWM>().write(|w| unsafe { w.bits(config.mode) });
+    }
+
+    /// Enable the carrier with the given configuration or disable it with
+    /// `None`
+    ///
+    /// The carrier chops the outputs after the dead time was inserted, e.g.
+    /// to drive isolated gate drivers through a transformer.
+    pub fn set_carrier(&mut self, config: Option<CarrierConfig>) {
+        let bits = match config {
+            Some(config) => config.bits(),
+            None => 0,
+        };
+        carrier_cfg::<OP, PWM>().write(|w| unsafe { w.bits(bits) });
+    }
+
+    /// Trip the operator when the fault detected by `fault` is active
+    ///
+    /// While tripped the outputs are forced as configured with
+    /// [`Operator::set_trip_actions`]. A [`TripMode::CycleByCycle`] trip ends
+    /// when the timer reaches zero after the fault ended, a
+    /// [`TripMode::OneShot`] trip lasts until it's cleared with
+    /// [`PwmPin::clear_one_shot_trip`].
+    pub fn set_trip<FaultInput, const F: u8>(
+        &mut self,
+        fault: &FaultPin<FaultInput, PWM, F>,
+        mode: TripMode,
+    ) {
+        let _ = fault;
+        // FHx_Fn_CBC are bits 3 - n, FHx_Fn_OST bits 7 - n
+        let cbc = 1 << (3 - F as u32);
+        let ost = 1 << (7 - F as u32);
+        let bits = match mode {
+            TripMode::Off => 0,
+            TripMode::CycleByCycle => cbc,
+            TripMode::OneShot => ost,
+        };
+
+        fh_cfg0::<OP, PWM>().modify(|r, w| unsafe { w.bits((r.bits() & !(cbc | ost)) | bits) });
+        // end cycle-by-cycle trips when the timer equals zero
+        fh_cfg1::<OP, PWM>().modify(|r, w| unsafe { w.bits((r.bits() & !0b110) | 0b010) });
+    }
+
+    /// Choose the levels the outputs are forced to while the operator is
+    /// tripped, `None` leaves an output alone
+    ///
+    /// The actions apply to both trip modes and both counting directions.
+    pub fn set_trip_actions(&mut self, a: Option<UpdateAction>, b: Option<UpdateAction>) {
+        let bits = trip_actions(a.map_or(0, |a| a as u32), b.map_or(0, |b| b as u32));
+        fh_cfg0::<OP, PWM>().modify(|r, w| unsafe { w.bits((r.bits() & 0xff) | bits) });
+    }
+
     /// Use the A output with the given pin and configuration
     pub fn with_pin_a<Pin: OutputPin>(
         self,
@@ -239,6 +313,27 @@ impl<Pin: OutputPin, PWM: PwmPeripheral, const OP: u8, const IS_A: bool>
         }
     }
 
+    /// Whether the operator is tripped, see [`Operator::set_trip`]
+    ///
+    /// The trip is shared by the A and B output of the operator.
+    pub fn trip_status(&self) -> TripStatus {
+        let bits = fh_status::<OP, PWM>().read().bits();
+        TripStatus {
+            cycle_by_cycle: bits & 0b01 != 0,
+            one_shot: bits & 0b10 != 0,
+        }
+    }
+
+    /// End a one-shot trip, for both outputs of the operator
+    ///
+    /// If the fault is still active the operator is tripped again right away.
+    pub fn clear_one_shot_trip(&mut self) {
+        // a rising edge of FHx_CLR_OST clears the trip
+        let fh_cfg1 = fh_cfg1::<OP, PWM>();
+        fh_cfg1.modify(|r, w| unsafe { w.bits(r.bits() | 0b1) });
+        fh_cfg1.modify(|r, w| unsafe { w.bits(r.bits() & !0b1) });
+    }
+
     /// Write a new timestamp.
     /// The written value will take effect according to the set
     /// [`PwmUpdateMethod`].
@@ -261,6 +356,14 @@ impl<Pin: OutputPin, PWM: PwmPeripheral, const OP: u8, const IS_A: bool>
     }
 }
 
+impl<Pin: OutputPin, PWM: PwmPeripheral, const OP: u8, const IS_A: bool> PeripheralOutput
+    for PwmPin<Pin, PWM, OP, IS_A>
+{
+    fn output_signal(&self) -> OutputSignal {
+        PWM::output_signal::<OP, IS_A>()
+    }
+}
+
 /// An action the operator applies to an output
 #[non_exhaustive]
 #[repr(u32)]
@@ -378,3 +481,251 @@ impl PwmUpdateMethod {
         self
     }
 }
+
+/// Dead time configuration of an operator, see [`Operator::set_deadtime`]
+///
+/// The delays count cycles of the peripheral clock
+/// ([`PeripheralClockConfig::frequency`]).
+#[derive(Copy, Clone)]
+pub struct DeadTimeConfig {
+    mode: u32,
+    rising_edge_delay: u16,
+    falling_edge_delay: u16,
+}
+
+impl DeadTimeConfig {
+    /// Bypass the dead time module, both outputs follow their generators
+    pub const BYPASS: Self = DeadTimeConfig {
+        mode: DT_A_OUTBYPASS | DT_B_OUTBYPASS,
+        rising_edge_delay: 0,
+        falling_edge_delay: 0,
+    };
+
+    /// Drive a half bridge from the A generator
+    ///
+    /// The A output follows the A generator with its rising edges delayed
+    /// by `rising_edge_delay`, the B output is its complement with the
+    /// rising edges (the falling edges of A) delayed by
+    /// `falling_edge_delay`. The B generator is not used. Both delays are at
+    /// least one cycle.
+    pub const fn active_high_complementary(
+        rising_edge_delay: u16,
+        falling_edge_delay: u16,
+    ) -> Self {
+        DeadTimeConfig {
+            mode: DT_FED_OUTINVERT,
+            rising_edge_delay: delay_bits(rising_edge_delay),
+            falling_edge_delay: delay_bits(falling_edge_delay),
+        }
+    }
+}
+
+/// The delay registers hold the cycles minus one
+const fn delay_bits(cycles: u16) -> u16 {
+    if cycles == 0 {
+        0
+    } else {
+        cycles - 1
+    }
+}
+
+// bits of the DTx_CFG register
+const DT_FED_OUTINVERT: u32 = 1 << 14;
+const DT_A_OUTBYPASS: u32 = 1 << 15;
+const DT_B_OUTBYPASS: u32 = 1 << 16;
+
+/// Carrier configuration of an operator, see [`Operator::set_carrier`]
+///
+/// The carrier clock is the peripheral clock divided by 1 to 16, the
+/// carrier frequency an eighth of it. The duty and the width of the first
+/// pulse count periods of the carrier clock.
+#[derive(Copy, Clone)]
+pub struct CarrierConfig {
+    frequency: HertzU32,
+    prescaler: u8,
+    duty: u8,
+    first_pulse_width: u8,
+}
+
+impl CarrierConfig {
+    /// Get a carrier configuration with the given frequency, a duty of 4/8
+    /// and a first pulse width of one carrier clock period
+    ///
+    /// The divider of the carrier clock is rounded to the nearest value, if
+    /// it's not in the range `1..=16` [`FrequencyError`] is returned. The
+    /// actual frequency is returned by [`CarrierConfig::frequency`].
+    pub fn with_frequency(
+        clock: &PeripheralClockConfig,
+        target_freq: HertzU32,
+    ) -> Result<Self, FrequencyError> {
+        let divider =
+            carrier_divider(clock.frequency().raw(), target_freq.raw()).ok_or(FrequencyError)?;
+
+        Ok(CarrierConfig {
+            frequency: clock.frequency() / (8 * divider),
+            prescaler: (divider - 1) as u8,
+            duty: 4,
+            first_pulse_width: 1,
+        })
+    }
+
+    /// Set the duty of the carrier in eighths of its period, `0..=7`
+    ///
+    /// Larger values are reduced to 7.
+    pub const fn with_duty(mut self, eighths: u8) -> Self {
+        self.duty = if eighths > 7 { 7 } else { eighths };
+        self
+    }
+
+    /// Set the width of the first pulse of every PWM pulse in carrier clock
+    /// periods (eighths of the carrier period), `1..=16`
+    ///
+    /// The longer first pulse charges the gate through the transformer.
+    /// Values outside the range are clamped.
+    pub const fn with_first_pulse_width(mut self, periods: u8) -> Self {
+        self.first_pulse_width = if periods == 0 {
+            1
+        } else if periods > 16 {
+            16
+        } else {
+            periods
+        };
+        self
+    }
+
+    /// Get the carrier frequency.
+    ///
+    /// ### Note:
+    /// The actual value is rounded down to the nearest `u32` value
+    pub fn frequency(&self) -> HertzU32 {
+        self.frequency
+    }
+
+    const fn bits(&self) -> u32 {
+        // CARRIERx_EN, CARRIERx_PRESCALE, CARRIERx_DUTY, CARRIERx_OSHTWTH
+        1 | ((self.prescaler as u32) << 1)
+            | ((self.duty as u32) << 5)
+            | ((self.first_pulse_width as u32 - 1) << 8)
+    }
+}
+
+/// Divider of the carrier clock which gets closest to `target`, if it's in
+/// the range of the hardware
+const fn carrier_divider(clock: u32, target: u32) -> Option<u32> {
+    if target == 0 {
+        return None;
+    }
+
+    let carrier_clock = target as u64 * 8;
+    let divider = (clock as u64 + carrier_clock / 2) / carrier_clock;
+    if divider == 0 || divider > 16 {
+        None
+    } else {
+        Some(divider as u32)
+    }
+}
+
+const _: () = {
+    // 1 MHz needs a peripheral clock of at most 128 MHz
+    assert!(carrier_divider(160_000_000, 1_000_000).is_none());
+    assert!(matches!(carrier_divider(40_000_000, 1_000_000), Some(5)));
+    assert!(matches!(carrier_divider(8_000_000, 1_000_000), Some(1)));
+    assert!(matches!(carrier_divider(8_000_000, 1_100_000), Some(1)));
+    assert!(carrier_divider(8_000_000, 4_000_000).is_none());
+    assert!(matches!(carrier_divider(160_000_000, 1_250_000), Some(16)));
+    assert!(carrier_divider(160_000_000, 0).is_none());
+};
+
+const _: () = {
+    let config = CarrierConfig {
+        frequency: HertzU32::from_raw(1_000_000),
+        prescaler: 4,
+        duty: 7,
+        first_pulse_width: 16,
+    };
+    // first pulse 15, duty 7, prescaler 4, enabled
+    assert!(config.bits() == 0xfe9);
+};
+
+/// How a fault trips an operator, see [`Operator::set_trip`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum TripMode {
+    /// The fault doesn't trip the operator
+    Off,
+    /// The trip ends with the first timer period after the fault ended
+    CycleByCycle,
+    /// The trip lasts until it's cleared by software
+    OneShot,
+}
+
+/// Trip state of an operator, see [`PwmPin::trip_status`]
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct TripStatus {
+    /// A cycle-by-cycle trip is ongoing
+    pub cycle_by_cycle: bool,
+    /// A one-shot trip is ongoing
+    pub one_shot: bool,
+}
+
+/// The action fields of the FHx_CFG0 register for the A and B output, every
+/// action applies to both modes and both counting directions
+const fn trip_actions(a: u32, b: u32) -> u32 {
+    // A_CBC_D, A_CBC_U, A_OST_D, A_OST_U from bit 8, B_* from bit 16
+    let a = a * 0b01_01_01_01;
+    let b = b * 0b01_01_01_01;
+    (a << 8) | (b << 16)
+}
+
+const _: () = {
+    assert!(trip_actions(1, 0) == 0x0000_5500);
+    assert!(trip_actions(0, 2) == 0x00aa_0000);
+    assert!(trip_actions(3, 3) == 0x00ff_ff00);
+};
+
+macro_rules! operator_register {
+    ($name:ident, $reg:ident, $field0:ident, $field1:ident, $field2:ident) => {
+        fn $name<const OP: u8, PWM: PwmPeripheral>() -> &'static crate::pac::pwm0::$reg {
+            // SAFETY:
+            // The register is only accessed by the operator `OP`
+            let block = unsafe { &*PWM::block() };
+
+            // SAFETY:
+            // The registers are identical for all operators so we can pretend
+            // they're the ones of operator 0
+            match OP {
+                0 => &block.$field0,
+                1 => unsafe { &*(&block.$field1 as *const _ as *const _) },
+                2 => unsafe { &*(&block.$field2 as *const _ as *const _) },
+                _ => {
+                    unreachable!()
+                }
+            }
+        }
+    };
+}
+
+operator_register!(dt_cfg, DT0_CFG, dt0_cfg, dt1_cfg, dt2_cfg);
+operator_register!(
+    dt_fed_cfg,
+    DT0_FED_CFG,
+    dt0_fed_cfg,
+    dt1_fed_cfg,
+    dt2_fed_cfg
+);
+operator_register!(
+    dt_red_cfg,
+    DT0_RED_CFG,
+    dt0_red_cfg,
+    dt1_red_cfg,
+    dt2_red_cfg
+);
+operator_register!(
+    carrier_cfg,
+    CARRIER0_CFG,
+    carrier0_cfg,
+    carrier1_cfg,
+    carrier2_cfg
+);
+operator_register!(fh_cfg0, FH0_CFG0, fh0_cfg0, fh1_cfg0, fh2_cfg0);
+operator_register!(fh_cfg1, FH0_CFG1, fh0_cfg1, fh1_cfg1, fh2_cfg1);
+operator_register!(fh_status, FH0_STATUS, fh0_status, fh1_status, fh2_status);
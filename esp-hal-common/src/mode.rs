@@ -0,0 +1,45 @@
+//! Driver modes
+//!
+//! Drivers which can wait for the hardware either by polling or from an
+//! interrupt take a mode type parameter: [Blocking] drivers implement the
+//! blocking and `nb` APIs, [Async] drivers (feature `async`) return futures
+//! which are woken from the interrupt of the peripheral. Both modes share
+//! the configuration and the register access of the driver, only the way an
+//! operation completes differs.
+//!
+//! Drivers are created in [Blocking] mode. `into_async` enables the
+//! interrupt of the peripheral at priority 1 and returns the driver in
+//! [Async] mode, or the error if the interrupt couldn't be enabled. Its
+//! interrupt handler has to call the `handle_interrupt` function of the
+//! driver's `asynch` module. `into_blocking` disables the interrupt again.
+//!
+//! ```no_run
+//! let serial = Serial::new(peripherals.UART1).into_async()?;
+//!
+//! #[interrupt]
+//! fn UART1() {
+//!     serial::asynch::handle_interrupt::<UART1>();
+//! }
+//! ```
+
+/// Operations poll the hardware until they are done
+pub struct Blocking;
+
+/// Operations return futures woken from the interrupt of the peripheral
+#[cfg(feature = "async")]
+pub struct Async;
+
+/// Mode of a driver, either [Blocking] or [Async]
+pub trait Mode: private::Sealed {}
+
+impl Mode for Blocking {}
+impl private::Sealed for Blocking {}
+
+#[cfg(feature = "async")]
+impl Mode for Async {}
+#[cfg(feature = "async")]
+impl private::Sealed for Async {}
+
+mod private {
+    pub trait Sealed {}
+}
@@ -0,0 +1,272 @@
+//! 1-Wire bus master
+//!
+//! Bit-banged 1-Wire master on an open-drain GPIO with an external pull-up
+//! resistor (typically 4.7 kΩ), using the standard speed timings.
+//!
+//! The time slots are between 60 µs and 70 µs long and the bus is sampled
+//! only a few µs after the slot started, so each slot runs in a critical
+//! section to keep interrupts from stretching it. The long reset pulse only
+//! protects its presence detection window that way.
+//!
+//! Devices powered parasitically from the data line (e.g. a DS18B20 without
+//! VDD) need more current than the pull-up resistor provides while they
+//! convert or write their EEPROM. [OneWire::set_strong_pullup] drives the line
+//! high actively for that time.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let mut bus = OneWire::new(io.pins.gpio4, Delay::new(&clocks));
+//! let mut search = RomSearch::new();
+//! while let Some(rom) = bus.search_rom(&mut search)? {
+//!     println!("Found {:016x}", rom);
+//! }
+//! ```
+
+use fugit::ExtU32;
+
+use crate::{
+    gpio::{InputPin, OutputPin},
+    Delay,
+};
+
+/// ROM command to address a single device
+pub const MATCH_ROM: u8 = 0x55;
+/// ROM command to address all devices
+pub const SKIP_ROM: u8 = 0xcc;
+/// ROM command to enumerate the devices
+pub const SEARCH_ROM: u8 = 0xf0;
+
+/// 1-Wire error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// No device answered the reset pulse
+    NoPresence,
+    /// No device answered a bit of the ROM search
+    SearchFailed,
+    /// The CRC of a received ROM code is wrong
+    CrcMismatch,
+}
+
+/// State of a ROM search, see [OneWire::search_rom]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RomSearch {
+    rom: u64,
+    last_discrepancy: u8,
+    done: bool,
+}
+
+impl RomSearch {
+    /// Start a new search
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+/// 1-Wire bus master
+pub struct OneWire<P> {
+    pin: P,
+    delay: Delay,
+}
+
+impl<P> OneWire<P>
+where
+    P: InputPin + OutputPin,
+{
+    /// Create a bus master on `pin`
+    ///
+    /// The pin is configured as open-drain output with the input enabled and
+    /// the line released.
+    pub fn new(mut pin: P, delay: Delay) -> Self {
+        pin.set_output_high(true);
+        pin.set_to_open_drain_output();
+        pin.enable_input(true);
+
+        Self { pin, delay }
+    }
+
+    /// Send a reset pulse, returns whether a device answered with a
+    /// presence pulse
+    pub fn reset(&mut self) -> bool {
+        self.release_strong_pullup();
+
+        self.pin.set_output_high(false);
+        self.delay.delay_for(480u32.micros());
+
+        let presence = critical_section::with(|_| {
+            self.pin.set_output_high(true);
+            self.delay.delay_for(70u32.micros());
+            !self.pin.is_input_high()
+        });
+
+        self.delay.delay_for(410u32.micros());
+        presence
+    }
+
+    /// Write a single bit
+    pub fn write_bit(&mut self, bit: bool) {
+        critical_section::with(|_| {
+            self.pin.set_output_high(false);
+            if bit {
+                self.delay.delay_for(6u32.micros());
+                self.pin.set_output_high(true);
+                self.delay.delay_for(64u32.micros());
+            } else {
+                self.delay.delay_for(60u32.micros());
+                self.pin.set_output_high(true);
+                self.delay.delay_for(10u32.micros());
+            }
+        });
+    }
+
+    /// Read a single bit
+    pub fn read_bit(&mut self) -> bool {
+        critical_section::with(|_| {
+            self.pin.set_output_high(false);
+            self.delay.delay_for(6u32.micros());
+            self.pin.set_output_high(true);
+            self.delay.delay_for(9u32.micros());
+            let bit = self.pin.is_input_high();
+            self.delay.delay_for(55u32.micros());
+            bit
+        })
+    }
+
+    /// Write a byte, LSB first
+    pub fn write_byte(&mut self, byte: u8) {
+        for i in 0..8 {
+            self.write_bit(byte & (1 << i) != 0);
+        }
+    }
+
+    /// Read a byte, LSB first
+    pub fn read_byte(&mut self) -> u8 {
+        (0..8).fold(0, |byte, i| byte | (self.read_bit() as u8) << i)
+    }
+
+    /// Write all `bytes`
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.write_byte(*byte);
+        }
+    }
+
+    /// Fill `buffer` with read bytes
+    pub fn read_bytes(&mut self, buffer: &mut [u8]) {
+        for byte in buffer.iter_mut() {
+            *byte = self.read_byte();
+        }
+    }
+
+    /// Reset the bus and address all devices
+    pub fn skip_rom(&mut self) -> Result<(), Error> {
+        if !self.reset() {
+            return Err(Error::NoPresence);
+        }
+        self.write_byte(SKIP_ROM);
+        Ok(())
+    }
+
+    /// Reset the bus and address the device with the ROM code `rom`
+    pub fn match_rom(&mut self, rom: u64) -> Result<(), Error> {
+        if !self.reset() {
+            return Err(Error::NoPresence);
+        }
+        self.write_byte(MATCH_ROM);
+        self.write_bytes(&rom.to_le_bytes());
+        Ok(())
+    }
+
+    /// Find the next device on the bus
+    ///
+    /// Returns the ROM codes of all devices one by one, then `None`. The
+    /// family code is in the lowest byte of the ROM code.
+    pub fn search_rom(&mut self, search: &mut RomSearch) -> Result<Option<u64>, Error> {
+        if search.done {
+            return Ok(None);
+        }
+
+        if !self.reset() {
+            return Err(Error::NoPresence);
+        }
+        self.write_byte(SEARCH_ROM);
+
+        let mut last_zero = 0;
+        for index in 1..=64u8 {
+            let bit = self.read_bit();
+            let complement = self.read_bit();
+
+            let direction = match (bit, complement) {
+                (true, true) => return Err(Error::SearchFailed),
+                (true, false) => true,
+                (false, true) => false,
+                // Discrepancy: devices with both values answered
+                (false, false) => {
+                    let direction = if index < search.last_discrepancy {
+                        search.rom & (1 << (index - 1)) != 0
+                    } else {
+                        index == search.last_discrepancy
+                    };
+                    if !direction {
+                        last_zero = index;
+                    }
+                    direction
+                }
+            };
+
+            if direction {
+                search.rom |= 1 << (index - 1);
+            } else {
+                search.rom &= !(1 << (index - 1));
+            }
+            self.write_bit(direction);
+        }
+
+        search.last_discrepancy = last_zero;
+        search.done = last_zero == 0;
+
+        if crc8(&search.rom.to_le_bytes()) != 0 {
+            return Err(Error::CrcMismatch);
+        }
+
+        Ok(Some(search.rom))
+    }
+
+    /// Drive the line high actively to power parasitic devices
+    ///
+    /// Enable it right after the command which needs the power (e.g.
+    /// `Convert T`) and disable it before any further communication. The
+    /// next [OneWire::reset] disables it as well.
+    pub fn set_strong_pullup(&mut self, enable: bool) {
+        self.pin.set_output_high(true);
+        self.pin.enable_open_drain(!enable);
+    }
+
+    fn release_strong_pullup(&mut self) {
+        self.pin.enable_open_drain(true);
+    }
+
+    /// Return the pin and the delay
+    pub fn free(self) -> (P, Delay) {
+        (self.pin, self.delay)
+    }
+}
+
+/// Dallas/Maxim CRC-8 as used in ROM codes and scratchpads
+///
+/// Returns 0 if `bytes` ends with the CRC of the preceding bytes.
+pub fn crc8(bytes: &[u8]) -> u8 {
+    let mut crc = 0u8;
+    for byte in bytes {
+        let mut byte = *byte;
+        for _ in 0..8 {
+            let mix = (crc ^ byte) & 1;
+            crc >>= 1;
+            if mix != 0 {
+                crc ^= 0x8c;
+            }
+            byte >>= 1;
+        }
+    }
+    crc
+}
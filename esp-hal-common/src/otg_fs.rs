@@ -1,14 +1,28 @@
 //! USB OTG full-speed peripheral
+//!
+//! [USB] implements the `UsbPeripheral` trait of the `esp-synopsys-usb-otg`
+//! crate, its [UsbBus] is used with the `usb-device` crate. The bus can be
+//! polled in a loop or from the `USB` interrupt, see the `usb_serial` and
+//! `usb_serial_interrupt` examples.
+//!
+//! By default the device assumes it is always connected to a host, as a
+//! bus-powered device is. Self-powered devices have to detect the host from
+//! the VBUS voltage with [USB::enable_vbus_sensing].
+
+use core::sync::atomic::{AtomicBool, Ordering};
 
 pub use esp_synopsys_usb_otg::UsbBus;
 use esp_synopsys_usb_otg::UsbPeripheral;
 
 use crate::{
+    gpio::InputPin,
     pac,
     system::{Peripheral, PeripheralClockControl},
     types::InputSignal,
 };
 
+static VBUS_SENSING: AtomicBool = AtomicBool::new(false);
+
 #[doc(hidden)]
 pub trait UsbSel {}
 
@@ -51,6 +65,18 @@ where
             _usb_dm: usb_dm,
         }
     }
+
+    /// Detect the connection to a host from VBUS on `pin`
+    ///
+    /// VBUS is 5 V, connect it to the pin through a voltage divider. Has to be
+    /// called before creating the [UsbBus].
+    pub fn enable_vbus_sensing<V: InputPin>(&mut self, pin: &mut V) {
+        pin.set_to_input();
+        pin.connect_input_to_peripheral(InputSignal::USB_OTG_VBUSVALID);
+        pin.connect_input_to_peripheral(InputSignal::USB_SRP_BVALID);
+
+        VBUS_SENSING.store(true, Ordering::Relaxed);
+    }
 }
 
 unsafe impl<S, P, M> Sync for USB<S, P, M>
@@ -97,8 +123,12 @@ where
             }
 
             crate::gpio::connect_high_to_peripheral(InputSignal::USB_OTG_IDDIG); // connected connector is mini-B side
-            crate::gpio::connect_high_to_peripheral(InputSignal::USB_SRP_BVALID); // HIGH to force USB device mode
-            crate::gpio::connect_high_to_peripheral(InputSignal::USB_OTG_VBUSVALID); // receiving a valid Vbus from device
+            if !VBUS_SENSING.load(Ordering::Relaxed) {
+                // HIGH to force USB device mode
+                crate::gpio::connect_high_to_peripheral(InputSignal::USB_SRP_BVALID);
+                // receiving a valid Vbus from device
+                crate::gpio::connect_high_to_peripheral(InputSignal::USB_OTG_VBUSVALID);
+            }
             crate::gpio::connect_low_to_peripheral(InputSignal::USB_OTG_AVALID);
 
             usb_wrap.otg_conf.modify(|_, w| {
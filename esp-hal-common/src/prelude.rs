@@ -29,7 +29,7 @@ pub use crate::pulse_control::{
     ConfiguredChannel as _esp_hal_pulse_control_ConfiguredChannel,
     OutputChannel as _esp_hal_pulse_control_OutputChannel,
 };
-#[cfg(any(esp32, esp32s2))]
+#[cfg(spi3)]
 pub use crate::spi::dma::WithDmaSpi3 as _esp_hal_spi_dma_WithDmaSpi3;
 pub use crate::{
     clock::Clock as _esp_hal_clock_Clock,
@@ -40,6 +40,7 @@ pub use crate::{
     gpio::{
         InputPin as _esp_hal_gpio_InputPin,
         OutputPin as _esp_hal_gpio_OutputPin,
+        PeripheralOutput as _esp_hal_gpio_PeripheralOutput,
         Pin as _esp_hal_gpio_Pin,
     },
     i2c::Instance as _esp_hal_i2c_Instance,
@@ -107,7 +108,7 @@ pub mod eh1 {
         ConfiguredChannel as _esp_hal_pulse_control_ConfiguredChannel,
         OutputChannel as _esp_hal_pulse_control_OutputChannel,
     };
-    #[cfg(any(esp32, esp32s2))]
+    #[cfg(spi3)]
     pub use crate::spi::dma::WithDmaSpi3 as _esp_hal_spi_dma_WithDmaSpi3;
     pub use crate::{
         clock::Clock as _esp_hal_clock_Clock,
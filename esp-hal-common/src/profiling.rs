@@ -0,0 +1,284 @@
+//! Cycle accurate profiling
+//!
+//! [CycleCounter] measures code sections in CPU cycles, e.g. how long an
+//! interrupt handler takes:
+//!
+//! ```no_run
+//! let counter = CycleCounter::start();
+//! handle_event();
+//! println!("{} ns", counter.elapsed_nanos(&clocks));
+//! ```
+//!
+//! [profile_scope!](crate::profile_scope) measures the rest of the enclosing
+//! scope and emits the cycle count with `log` or `defmt` (enable the feature
+//! of the same name) when it ends.
+//!
+//! The Xtensa chips count in the `CCOUNT` special register. The RISC-V chips
+//! don't implement the `mcycle` CSR, they count in the machine performance
+//! counter of the core instead (`mpccr`), which [CycleCounter::start] enables
+//! for cycle counting. Both counters are 32 bit wide and wrap after
+//! 2<sup>32</sup> cycles, about 17.9 s at 240 MHz and 26.8 s at 160 MHz, so
+//! longer sections can't be measured. Cycles in which the CPU waits for an
+//! interrupt are counted as well.
+//!
+//! With the `xtensa-perf-counters` feature [perf] gives access to the
+//! performance monitor of the Xtensa cores, which counts further events such
+//! as retired instructions and pipeline stalls.
+
+use crate::clock::Clocks;
+
+/// Read the cycle counter of the current core
+#[cfg(xtensa)]
+#[inline(always)]
+pub fn cycle_count() -> u32 {
+    xtensa_lx::timer::get_cycle_count()
+}
+
+/// Read the cycle counter of the current core
+///
+/// Only counts after it was enabled by [CycleCounter::start].
+#[cfg(riscv)]
+#[inline(always)]
+pub fn cycle_count() -> u32 {
+    let count: u32;
+    unsafe { core::arch::asm!("csrr {0}, 0x7e2", out(reg) count) };
+    count
+}
+
+/// Enable counting of CPU cycles in the machine performance counter
+#[cfg(riscv)]
+#[inline(always)]
+fn enable_cycle_count() {
+    unsafe {
+        // count clock cycles (event 0) ...
+        core::arch::asm!("csrw 0x7e0, {0}", in(reg) 1);
+        // ... and enable the counter
+        core::arch::asm!("csrw 0x7e1, {0}", in(reg) 1);
+    }
+}
+
+/// Convert a number of CPU cycles to nanoseconds at the current CPU clock
+pub fn cycles_to_nanos(cycles: u32, clocks: &Clocks) -> u64 {
+    cycles as u64 * 1_000_000_000 / clocks.cpu_clock.raw() as u64
+}
+
+/// Measures the CPU cycles since it was started
+#[derive(Debug, Clone, Copy)]
+pub struct CycleCounter {
+    start: u32,
+}
+
+impl CycleCounter {
+    /// Start measuring
+    #[inline(always)]
+    pub fn start() -> Self {
+        #[cfg(riscv)]
+        enable_cycle_count();
+
+        Self {
+            start: cycle_count(),
+        }
+    }
+
+    /// CPU cycles since [CycleCounter::start]
+    #[inline(always)]
+    pub fn elapsed(&self) -> u32 {
+        cycle_count().wrapping_sub(self.start)
+    }
+
+    /// Time since [CycleCounter::start] in nanoseconds
+    ///
+    /// Only valid if the CPU clock didn't change in between.
+    pub fn elapsed_nanos(&self, clocks: &Clocks) -> u64 {
+        cycles_to_nanos(self.elapsed(), clocks)
+    }
+
+    /// Start measuring again and return the CPU cycles of the previous
+    /// measurement
+    #[inline(always)]
+    pub fn restart(&mut self) -> u32 {
+        let now = cycle_count();
+        let elapsed = now.wrapping_sub(self.start);
+        self.start = now;
+        elapsed
+    }
+}
+
+/// Emits the cycles of a scope when it's dropped, see
+/// [profile_scope!](crate::profile_scope)
+#[doc(hidden)]
+pub struct ScopeGuard {
+    name: &'static str,
+    counter: CycleCounter,
+}
+
+impl ScopeGuard {
+    #[inline(always)]
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            counter: CycleCounter::start(),
+        }
+    }
+}
+
+impl Drop for ScopeGuard {
+    #[inline(always)]
+    fn drop(&mut self) {
+        let cycles = self.counter.elapsed();
+
+        #[cfg(feature = "log")]
+        log::info!("{}: {} cycles", self.name, cycles);
+        #[cfg(feature = "defmt")]
+        defmt::info!("{=str}: {=u32} cycles", self.name, cycles);
+        #[cfg(not(any(feature = "log", feature = "defmt")))]
+        let _ = (self.name, cycles);
+    }
+}
+
+/// Measure the CPU cycles until the end of the enclosing scope
+///
+/// The cycles are emitted with `log::info!` or `defmt::info!`, depending on
+/// which of the `log` and `defmt` features is enabled. Without either of them
+/// nothing is emitted.
+///
+/// ```no_run
+/// #[interrupt]
+/// fn GPIO() {
+///     profile_scope!("GPIO");
+///     // ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! profile_scope {
+    ($name:expr) => {
+        let _profile_scope = $crate::profiling::ScopeGuard::new($name);
+    };
+}
+
+/// Performance monitor of the Xtensa cores
+///
+/// Each core has eight counters which can count one event each. Counting is
+/// enabled for all counters of the current core with [enable] and the
+/// counters are started with [PerfCounter::start].
+#[cfg(all(xtensa, feature = "xtensa-perf-counters"))]
+pub mod perf {
+    const ERI_PERFMON_PGM: u32 = 0x0010_1000;
+    const ERI_PERFMON_PM0: u32 = 0x0010_1080;
+    const ERI_PERFMON_PMCTRL0: u32 = 0x0010_1100;
+    const ERI_PERFMON_PMSTAT0: u32 = 0x0010_1180;
+
+    const PMCTRL_KRNLCNT: u32 = 1 << 3;
+    const PMCTRL_TRACELEVEL_SHIFT: u32 = 4;
+    const PMCTRL_SELECT_SHIFT: u32 = 8;
+    const PMCTRL_MASK_SHIFT: u32 = 16;
+
+    /// An event the performance monitor counts
+    ///
+    /// `select` chooses the event group and `mask` the events of the group, a
+    /// counter is incremented once per cycle in which any of them occurs.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Event {
+        pub select: u8,
+        pub mask: u16,
+    }
+
+    impl Event {
+        /// CPU cycles
+        pub const CYCLES: Event = Event {
+            select: 0,
+            mask: 0x0001,
+        };
+        /// Successfully retired instructions
+        pub const INSTRUCTIONS: Event = Event {
+            select: 2,
+            mask: 0xffff,
+        };
+        /// Cycles in which the pipeline is stalled or holds a bubble
+        pub const STALLS: Event = Event {
+            select: 4,
+            mask: 0xffff,
+        };
+    }
+
+    /// Counter number
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Number {
+        Counter0 = 0,
+        Counter1 = 1,
+        Counter2 = 2,
+        Counter3 = 3,
+        Counter4 = 4,
+        Counter5 = 5,
+        Counter6 = 6,
+        Counter7 = 7,
+    }
+
+    #[inline(always)]
+    fn read(address: u32) -> u32 {
+        let value: u32;
+        unsafe { core::arch::asm!("rer {0}, {1}", out(reg) value, in(reg) address) };
+        value
+    }
+
+    #[inline(always)]
+    fn write(address: u32, value: u32) {
+        unsafe { core::arch::asm!("wer {0}, {1}", in(reg) value, in(reg) address) };
+    }
+
+    /// Enable the performance monitor of the current core
+    pub fn enable() {
+        write(ERI_PERFMON_PGM, read(ERI_PERFMON_PGM) | 1);
+    }
+
+    /// Disable the performance monitor of the current core
+    pub fn disable() {
+        write(ERI_PERFMON_PGM, read(ERI_PERFMON_PGM) & !1);
+    }
+
+    /// A counter of the performance monitor of the current core
+    pub struct PerfCounter {
+        number: Number,
+    }
+
+    impl PerfCounter {
+        /// Reset counter `number` and count `event` at all interrupt levels
+        pub fn start(number: Number, event: Event) -> Self {
+            let offset = 4 * number as u32;
+
+            write(ERI_PERFMON_PMCTRL0 + offset, 0);
+            write(ERI_PERFMON_PM0 + offset, 0);
+            write(
+                ERI_PERFMON_PMSTAT0 + offset,
+                read(ERI_PERFMON_PMSTAT0 + offset),
+            );
+            write(
+                ERI_PERFMON_PMCTRL0 + offset,
+                (event.mask as u32) << PMCTRL_MASK_SHIFT
+                    | (event.select as u32) << PMCTRL_SELECT_SHIFT
+                    | 0xf << PMCTRL_TRACELEVEL_SHIFT
+                    | PMCTRL_KRNLCNT,
+            );
+
+            Self { number }
+        }
+
+        /// Events counted since [PerfCounter::start]
+        #[inline(always)]
+        pub fn count(&self) -> u32 {
+            read(ERI_PERFMON_PM0 + 4 * self.number as u32)
+        }
+
+        /// Returns `true` if the counter overflowed
+        pub fn overflowed(&self) -> bool {
+            read(ERI_PERFMON_PMSTAT0 + 4 * self.number as u32) & 1 != 0
+        }
+
+        /// Stop counting and return the final count
+        pub fn stop(self) -> u32 {
+            let offset = 4 * self.number as u32;
+            write(ERI_PERFMON_PMCTRL0 + offset, 0);
+            read(ERI_PERFMON_PM0 + offset)
+        }
+    }
+}
@@ -0,0 +1,407 @@
+//! External SPI RAM (PSRAM)
+//!
+//! Initializes a quad SPI PSRAM connected to chip select 1 of the flash bus
+//! (GPIO26, the in-package PSRAM of the ESP32-S3R2 uses it as well) and maps
+//! it into the data address space right after the flash mappings. The
+//! returned [Psram] tells where it ended up, e.g. to hand it to a heap
+//! allocator:
+//!
+//! ```no_run
+//! let psram = psram::init().unwrap();
+//! unsafe { HEAP.init(psram.start(), psram.size()) };
+//! ```
+//!
+//! The size is read from the ID of the PSRAM. The `psram-2m`, `psram-4m` and
+//! `psram-8m` features override it, e.g. to map only a part of it.
+//!
+//! All accesses go through the data cache, the memory can't be used for DMA.
+//!
+//! [init] talks to the PSRAM on the bus of the flash, with the caches
+//! suspended. Call it before the APP CPU is started, code the APP CPU runs
+//! from flash would stall meanwhile.
+//!
+//! ## Supported configurations
+//!
+//! Only quad PSRAM on the ESP32-S3, clocked at 40 MHz, is supported:
+//! - the ESP32 and the ESP32-S2 are not supported, the `psram` feature doesn't
+//!   build for them
+//! - octal PSRAM (the ESP32-S3R8 and ESP32-S3R8V packages) is not supported,
+//!   [init] returns [Error::OctalNotSupported]
+//! - the bus isn't tuned for higher clocks, so the PSRAM runs at 40 MHz
+//!   whatever the flash speed is
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{
+    efuse::Efuse,
+    gpio::types::get_io_mux_reg,
+    pac::{EXTMEM, SPI0, SPI1},
+    rom::{
+        cache_resume_dcache,
+        cache_resume_icache,
+        cache_suspend_dcache,
+        cache_suspend_icache,
+        esp_rom_delay_us,
+    },
+};
+
+/// Start of the data bus address space of the external memory
+const DBUS_VADDR_START: usize = 0x3c00_0000;
+
+/// MMU table of the external memory, shared between instruction and data bus
+const MMU_TABLE: *mut u32 = 0x600c_5000 as *mut u32;
+const MMU_TABLE_SIZE: usize = 512;
+const MMU_PAGE_SIZE: usize = 0x1_0000;
+const MMU_INVALID: u32 = 1 << 14;
+const MMU_ACCESS_SPIRAM: u32 = 1 << 15;
+
+const CS1_GPIO: u8 = 26;
+/// IO_MUX function 0 of GPIO26 is SPICS1
+const CS1_FUNCTION: u8 = 0;
+
+const CMD_RESET_ENABLE: u8 = 0x66;
+const CMD_RESET: u8 = 0x99;
+const CMD_READ_ID: u8 = 0x9f;
+const CMD_QUAD_READ: u8 = 0xeb;
+const CMD_QUAD_WRITE: u8 = 0x38;
+const QUAD_READ_DUMMY_CYCLES: u32 = 6;
+
+/// "Known good die" byte of the ID
+const KGD_PASS: u32 = 0x5d;
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+/// PSRAM error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// [init] was already called
+    AlreadyInitialized,
+    /// The chip package has no embedded PSRAM and no external PSRAM answered
+    NotPresent,
+    /// The package has embedded PSRAM according to the eFuses, but it didn't
+    /// answer
+    NoResponse,
+    /// The package has octal PSRAM, which isn't supported yet
+    OctalNotSupported,
+    /// The ID of the PSRAM reports an unknown size
+    UnknownSize(u32),
+    /// Not enough free MMU pages to map the PSRAM
+    AddressSpaceExhausted,
+}
+
+/// Initialized and mapped PSRAM
+#[derive(Debug, Clone, Copy)]
+pub struct Psram {
+    start: usize,
+    size: usize,
+}
+
+impl Psram {
+    /// Address the PSRAM is mapped to
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// Size of the mapped PSRAM in bytes
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Pointer to the start of the PSRAM
+    pub fn as_mut_ptr(&self) -> *mut u8 {
+        self.start as *mut u8
+    }
+}
+
+/// Initialize the PSRAM and map it, see the [module documentation](self)
+pub fn init() -> Result<Psram, Error> {
+    if INITIALIZED.swap(true, Ordering::Relaxed) {
+        return Err(Error::AlreadyInitialized);
+    }
+
+    let embedded = Efuse::get_embedded_psram_size();
+    if embedded == Some(8) {
+        return Err(Error::OctalNotSupported);
+    }
+
+    configure_cs1();
+
+    send_command(CMD_RESET_ENABLE, 0);
+    send_command(CMD_RESET, 0);
+    unsafe { esp_rom_delay_us(50) };
+    let id = send_command(CMD_READ_ID, 24) & 0xff_ffff;
+
+    if (id >> 8) & 0xff != KGD_PASS {
+        return Err(match embedded {
+            Some(_) => Error::NoResponse,
+            None => Error::NotPresent,
+        });
+    }
+
+    let probed_size = match (id >> 21) & 0x3 {
+        0 => 2 * 1024 * 1024,
+        1 => 4 * 1024 * 1024,
+        2 => 8 * 1024 * 1024,
+        _ => return Err(Error::UnknownSize(id)),
+    };
+    let size = configured_size().unwrap_or(probed_size);
+
+    configure_cache_access();
+    let start = map(size)?;
+
+    Ok(Psram { start, size })
+}
+
+fn configured_size() -> Option<usize> {
+    if cfg!(feature = "psram-8m") {
+        Some(8 * 1024 * 1024)
+    } else if cfg!(feature = "psram-4m") {
+        Some(4 * 1024 * 1024)
+    } else if cfg!(feature = "psram-2m") {
+        Some(2 * 1024 * 1024)
+    } else {
+        None
+    }
+}
+
+fn configure_cs1() {
+    get_io_mux_reg(CS1_GPIO)
+        .modify(|_, w| unsafe { w.mcu_sel().bits(CS1_FUNCTION).fun_drv().bits(3) });
+
+    let spi0 = unsafe { &*SPI0::PTR };
+    spi0.spi_smem_ac.modify(|_, w| unsafe {
+        w.spi_smem_cs_setup()
+            .set_bit()
+            .spi_smem_cs_hold()
+            .set_bit()
+            .spi_smem_cs_setup_time()
+            .bits(0)
+            .spi_smem_cs_hold_time()
+            .bits(0)
+    });
+}
+
+/// Send a single-line command to the PSRAM with SPI1, returns the
+/// `read_bits` bits read after the command and a 24 bit address of 0
+///
+/// The flash is on the same bus: no interrupt may run and the caches are
+/// suspended while SPI1 drives it.
+fn send_command(command: u8, read_bits: u32) -> u32 {
+    critical_section::with(|_| unsafe { send_command_uncached(command, read_bits) })
+}
+
+/// Runs from RAM, the caches are suspended and nothing may be fetched from
+/// flash. The SPI1 registers used by the ROM flash functions are restored.
+#[procmacros::ram]
+unsafe fn send_command_uncached(command: u8, read_bits: u32) -> u32 {
+    let spi1 = &*SPI1::PTR;
+
+    let icache_autoload = cache_suspend_icache();
+    let dcache_autoload = cache_suspend_dcache();
+
+    let ctrl = spi1.ctrl.read().bits();
+    let user = spi1.user.read().bits();
+    let user1 = spi1.user1.read().bits();
+    let user2 = spi1.user2.read().bits();
+    let misc = spi1.misc.read().bits();
+    let addr = spi1.addr.read().bits();
+    let miso_dlen = spi1.miso_dlen.read().bits();
+
+    spi1.ctrl.modify(|_, w| {
+        w.fread_qio()
+            .clear_bit()
+            .fread_dio()
+            .clear_bit()
+            .fread_quad()
+            .clear_bit()
+            .fread_dual()
+            .clear_bit()
+    });
+    spi1.misc
+        .modify(|_, w| w.cs0_dis().set_bit().cs1_dis().clear_bit());
+
+    spi1.user2.write(|w| {
+        w.usr_command_bitlen()
+            .bits(7)
+            .usr_command_value()
+            .bits(command as u16)
+    });
+    spi1.addr.write(|w| w.bits(0));
+    spi1.user1.write(|w| w.usr_addr_bitlen().bits(23));
+    if read_bits > 0 {
+        spi1.miso_dlen
+            .write(|w| w.usr_miso_dbitlen().bits(read_bits - 1));
+    }
+    spi1.user.write(|w| {
+        w.usr_command()
+            .set_bit()
+            .usr_addr()
+            .bit(read_bits > 0)
+            .usr_miso()
+            .bit(read_bits > 0)
+    });
+
+    spi1.cmd.modify(|_, w| w.usr().set_bit());
+    while spi1.cmd.read().usr().bit_is_set() {}
+
+    let data = spi1.w0.read().bits();
+
+    spi1.ctrl.write(|w| w.bits(ctrl));
+    spi1.user.write(|w| w.bits(user));
+    spi1.user1.write(|w| w.bits(user1));
+    spi1.user2.write(|w| w.bits(user2));
+    spi1.misc.write(|w| w.bits(misc));
+    spi1.addr.write(|w| w.bits(addr));
+    spi1.miso_dlen.write(|w| w.bits(miso_dlen));
+
+    cache_resume_dcache(dcache_autoload);
+    cache_resume_icache(icache_autoload);
+
+    data
+}
+
+/// Let the cache access the PSRAM with quad read and write commands at
+/// 40 MHz
+fn configure_cache_access() {
+    let spi0 = unsafe { &*SPI0::PTR };
+
+    spi0.sram_clk.write(|w| unsafe {
+        w.sclkcnt_n()
+            .bits(1)
+            .sclkcnt_h()
+            .bits(0)
+            .sclkcnt_l()
+            .bits(1)
+    });
+
+    spi0.sram_drd_cmd.write(|w| unsafe {
+        w.cache_sram_usr_rd_cmd_bitlen()
+            .bits(7)
+            .cache_sram_usr_rd_cmd_value()
+            .bits(CMD_QUAD_READ as u16)
+    });
+    spi0.sram_dwr_cmd.write(|w| unsafe {
+        w.cache_sram_usr_wr_cmd_bitlen()
+            .bits(7)
+            .cache_sram_usr_wr_cmd_value()
+            .bits(CMD_QUAD_WRITE as u16)
+    });
+    spi0.cache_sctrl.modify(|_, w| unsafe {
+        w.cache_sram_usr_rcmd()
+            .set_bit()
+            .cache_sram_usr_wcmd()
+            .set_bit()
+            .sram_addr_bitlen()
+            .bits(23)
+            .usr_rd_sram_dummy()
+            .set_bit()
+            .sram_rdummy_cyclelen()
+            .bits(QUAD_READ_DUMMY_CYCLES as u8 - 1)
+            .usr_wr_sram_dummy()
+            .clear_bit()
+            .usr_sram_dio()
+            .clear_bit()
+            .usr_sram_qio()
+            .set_bit()
+    });
+
+    spi0.misc.modify(|_, w| w.cs1_dis().clear_bit());
+}
+
+/// Map `size` bytes of PSRAM after the last used MMU page, returns the start
+/// address
+fn map(size: usize) -> Result<usize, Error> {
+    let first_free = (0..MMU_TABLE_SIZE)
+        .rev()
+        .find(|&i| unsafe { MMU_TABLE.add(i).read_volatile() } & MMU_INVALID == 0)
+        .map_or(0, |last_used| last_used + 1);
+    let pages = size / MMU_PAGE_SIZE;
+
+    if first_free + pages > MMU_TABLE_SIZE {
+        return Err(Error::AddressSpaceExhausted);
+    }
+
+    for page in 0..pages {
+        unsafe {
+            MMU_TABLE
+                .add(first_free + page)
+                .write_volatile(MMU_ACCESS_SPIRAM | page as u32);
+        }
+    }
+
+    let extmem = unsafe { &*EXTMEM::PTR };
+    extmem.dcache_ctrl1.modify(|_, w| {
+        w.dcache_shut_core0_bus()
+            .clear_bit()
+            .dcache_shut_core1_bus()
+            .clear_bit()
+    });
+
+    Ok(DBUS_VADDR_START + first_free * MMU_PAGE_SIZE)
+}
+
+/// Whether the `len` bytes at `address` are mapped to the PSRAM, as opposed
+/// to the flash, which shares the address space of the external memory
+pub(crate) fn is_psram(address: usize, len: usize) -> bool {
+    match mmu_pages(address, len) {
+        Some((first, last)) => {
+            (first..=last).all(|page| maps_psram(unsafe { MMU_TABLE.add(page).read_volatile() }))
+        }
+        None => false,
+    }
+}
+
+/// The first and the last MMU page of the `len` bytes at `address`, `None`
+/// if they aren't all covered by the MMU table
+const fn mmu_pages(address: usize, len: usize) -> Option<(usize, usize)> {
+    if address < DBUS_VADDR_START || len == 0 {
+        return None;
+    }
+
+    let first = (address - DBUS_VADDR_START) / MMU_PAGE_SIZE;
+    let last = match (address - DBUS_VADDR_START).checked_add(len - 1) {
+        Some(end) => end / MMU_PAGE_SIZE,
+        None => return None,
+    };
+    if last >= MMU_TABLE_SIZE {
+        return None;
+    }
+
+    Some((first, last))
+}
+
+/// Whether an MMU table entry maps its page to the PSRAM
+const fn maps_psram(entry: u32) -> bool {
+    entry & MMU_INVALID == 0 && entry & MMU_ACCESS_SPIRAM != 0
+}
+
+const _: () = {
+    const PAGE: usize = MMU_PAGE_SIZE;
+
+    assert!(matches!(mmu_pages(DBUS_VADDR_START, 4), Some((0, 0))));
+    assert!(matches!(
+        mmu_pages(DBUS_VADDR_START + 2 * PAGE, 2 * PAGE),
+        Some((2, 3))
+    ));
+    assert!(matches!(
+        mmu_pages(DBUS_VADDR_START + 2 * PAGE - 4, 8),
+        Some((1, 2))
+    ));
+    assert!(matches!(
+        mmu_pages(DBUS_VADDR_START + (MMU_TABLE_SIZE - 1) * PAGE, PAGE),
+        Some((511, 511))
+    ));
+
+    // outside of the external memory or the MMU table
+    assert!(mmu_pages(DBUS_VADDR_START - 4, 4).is_none());
+    assert!(mmu_pages(DBUS_VADDR_START + MMU_TABLE_SIZE * PAGE, 4).is_none());
+    assert!(mmu_pages(DBUS_VADDR_START + (MMU_TABLE_SIZE - 1) * PAGE, PAGE + 1).is_none());
+    assert!(mmu_pages(DBUS_VADDR_START, usize::MAX).is_none());
+    assert!(mmu_pages(DBUS_VADDR_START, 0).is_none());
+
+    assert!(maps_psram(MMU_ACCESS_SPIRAM | 5));
+    // flash, unmapped
+    assert!(!maps_psram(5));
+    assert!(!maps_psram(MMU_INVALID));
+    assert!(!maps_psram(MMU_INVALID | MMU_ACCESS_SPIRAM));
+};
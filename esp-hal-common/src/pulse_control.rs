@@ -12,6 +12,9 @@
 //! A secondary use case for this peripheral is to drive RGB(W) LEDs
 //! that bear an internal IC and use a pulse code protocol.
 //!
+//! The [ir] module encodes and decodes the pulse codes of common infrared
+//! remote control protocols.
+//!
 //! ### Channels
 //! The RMT peripheral has the following channels available
 //! on individual chips:
@@ -31,8 +34,10 @@
 //! * FIFO mode is not supported (there appear to be some issues with FIFO mode
 //!   in some variants and for consistency all variants therefore we use
 //!   NON-FIFO mode everywhere)
-//! * Non-blocking mode is currently not supported!
-//! * Input channels are currently not supported!
+//! * Non-blocking transmissions need the `async` feature, see the `asynch`
+//!   module
+//! * Input channels are only used by [frequency_ratio] on the ESP32-C3, to
+//!   timestamp the edges of two signals
 //!
 //! ### Example (for ESP32-C3)
 //! ```
@@ -78,13 +83,19 @@
 
 #![deny(missing_docs)]
 
+#[cfg(feature = "async")]
+pub mod asynch;
+#[cfg(esp32c3)]
+pub mod frequency_ratio;
+pub mod ir;
+
 use core::slice::Iter;
 
 use fugit::NanosDurationU32;
 pub use paste::paste;
 
 use crate::{
-    gpio::{types::OutputSignal, OutputPin},
+    gpio::{types::OutputSignal, OutputPin, PeripheralOutput},
     pac::RMT,
     system::PeripheralClockControl,
 };
@@ -177,38 +188,74 @@ pub struct PulseCode {
     pub length2: NanosDurationU32,
 }
 
-/// Convert a pulse code structure into a u32 value that can be written
-/// into the data registers
-impl From<PulseCode> for u32 {
-    #[inline(always)]
-    fn from(p: PulseCode) -> u32 {
-        // The Pulse Code format in the RAM appears to be
-        // little-endian
-
-        // The length1 value resides in bits [14:0]
-        let mut entry: u32 = p.length1.ticks() as u32;
+impl PulseCode {
+    /// The entry in the RMT RAM
+    ///
+    /// The Pulse Code format in the RAM appears to be little-endian: length1
+    /// in bits [14:0], level1 in bit 15, length2 in bits [30:16] and level2 in
+    /// bit 31.
+    pub(crate) const fn to_entry(self) -> u32 {
+        let mut entry: u32 = self.length1.ticks();
 
-        // If level1 is high, set bit 15, otherwise clear it
-        if p.level1 {
+        if self.level1 {
             entry |= 1 << 15;
         } else {
             entry &= !(1 << 15);
         }
 
-        // If level2 is high, set bit 31, otherwise clear it
-        if p.level2 {
+        if self.level2 {
             entry |= 1 << 31;
         } else {
             entry &= !(1 << 31);
         }
 
-        // The length2 value resides in bits [30:16]
-        entry |= (p.length2.ticks() as u32) << 16;
+        entry | self.length2.ticks() << 16
+    }
 
-        entry
+    /// A pulse code from an entry in the RMT RAM, see [PulseCode::to_entry]
+    const fn from_entry(entry: u32) -> PulseCode {
+        PulseCode {
+            level1: entry & (1 << 15) != 0,
+            length1: NanosDurationU32::from_ticks(entry & 0x7fff),
+            level2: entry & (1 << 31) != 0,
+            length2: NanosDurationU32::from_ticks((entry >> 16) & 0x7fff),
+        }
     }
 }
 
+/// Convert a pulse code structure into a u32 value that can be written
+/// into the data registers
+impl From<PulseCode> for u32 {
+    #[inline(always)]
+    fn from(p: PulseCode) -> u32 {
+        p.to_entry()
+    }
+}
+
+/// Convert a value read from the RMT RAM (e.g. a received pulse) into a pulse
+/// code structure
+impl From<u32> for PulseCode {
+    #[inline(always)]
+    fn from(entry: u32) -> PulseCode {
+        PulseCode::from_entry(entry)
+    }
+}
+
+const _: () = {
+    let code = PulseCode {
+        level1: true,
+        length1: NanosDurationU32::from_ticks(0x1234),
+        level2: false,
+        length2: NanosDurationU32::from_ticks(0x7fff),
+    };
+    assert!(code.to_entry() == 0x7fff_9234);
+
+    let code = PulseCode::from_entry(0x8001_0002);
+    assert!(!code.level1 && code.length1.ticks() == 2);
+    assert!(code.level2 && code.length2.ticks() == 1);
+    assert!(code.to_entry() == 0x8001_0002);
+};
+
 /// Functionality that every OutputChannel must support
 pub trait OutputChannel<CC> {
     /// Set the logical level that the connected pin is pulled to
@@ -224,6 +271,10 @@ pub trait OutputChannel<CC> {
     /// Enable/Disable carrier modulation
     fn set_carrier_modulation(&mut self, state: bool) -> &mut Self;
 
+    /// Set the high and low time of the carrier (in cycles of the RMT source
+    /// clock, before the channel divider)
+    fn set_carrier(&mut self, high: u16, low: u16) -> &mut Self;
+
     /// Set the clock source (for the ESP32-S2 abd ESP32 this can be done on a
     /// channel level)
     #[cfg(any(esp32s2, esp32))]
@@ -265,6 +316,140 @@ pub trait ConfiguredChannel {
     fn stop_transmission(&self);
 }
 
+/// Steps of a transmission, used to run several channels in an
+/// [RmtSyncGroup]
+pub trait SyncChannel {
+    /// Number of the channel
+    fn channel_number(&self) -> u8;
+
+    /// Configure the channel and load the start of `sequence` into its RAM
+    fn prepare_transmission(
+        &mut self,
+        repeat_mode: RepeatMode,
+        sequence: &mut Iter<u32>,
+    ) -> Result<(), TransmissionError>;
+
+    /// Start the prepared transmission
+    fn start_transmission(&self);
+
+    /// Refill the RAM from `sequence` if needed and check for completion
+    fn poll_transmission(&mut self, sequence: &mut Iter<u32>) -> nb::Result<(), TransmissionError>;
+
+    /// Stop the transmission and rewind the RAM read pointer
+    ///
+    /// The ESP32 can't stop a channel, the read pointer is rewound to an end
+    /// marker instead, the pulse code being sent still finishes.
+    fn abort_transmission(&mut self);
+}
+
+// Bit enabling simultaneous start in the TX_SIM register, the bits below it
+// select the channels
+#[cfg(esp32c3)]
+const TX_SIM_EN: u32 = 1 << 2;
+#[cfg(any(esp32s2, esp32s3))]
+const TX_SIM_EN: u32 = 1 << 4;
+
+/// Group of TX channels which start their transmissions simultaneously
+///
+/// Useful to keep several LED strips in sync. On the ESP32-C3, ESP32-S2 and
+/// ESP32-S3 the `TX_SIM` register holds back the start until all channels of
+/// the group are started, they begin in the same clock cycle. The ESP32 has
+/// no such register, the channels are started by consecutive register writes
+/// in a critical section instead which leaves a skew of a few APB cycles
+/// between them.
+///
+/// The channels are borrowed by the group and return to independent operation
+/// when it is dropped.
+pub struct RmtSyncGroup<'a, const N: usize> {
+    channels: [&'a mut dyn SyncChannel; N],
+}
+
+impl<'a, const N: usize> RmtSyncGroup<'a, N> {
+    /// Create a group of configured TX channels
+    pub fn new(channels: [&'a mut dyn SyncChannel; N]) -> Self {
+        #[cfg(not(esp32))]
+        {
+            let mask = channels
+                .iter()
+                .fold(0, |mask, channel| mask | 1 << channel.channel_number());
+
+            unsafe { &*RMT::PTR }
+                .tx_sim
+                .modify(|r, w| unsafe { w.bits(r.bits() | mask | TX_SIM_EN) });
+        }
+
+        Self { channels }
+    }
+
+    /// Send one raw sequence per channel in a blocking fashion, all channels
+    /// start at the same time
+    ///
+    /// Like [ConfiguredChannel::send_pulse_sequence_raw] the sequences must
+    /// contain the end marker. Returns after all channels finished, or right
+    /// after the start with [RepeatMode::Forever].
+    pub fn send_pulse_sequences_raw(
+        &mut self,
+        repeat_mode: RepeatMode,
+        sequences: [&[u32]; N],
+    ) -> Result<(), TransmissionError> {
+        let mut sequence_iters = sequences.map(|sequence| sequence.iter());
+
+        for (channel, sequence) in self.channels.iter_mut().zip(sequence_iters.iter_mut()) {
+            channel.prepare_transmission(repeat_mode, sequence)?;
+        }
+
+        // With TX_SIM the channels wait for each other, otherwise start them as
+        // close together as possible
+        critical_section::with(|_| {
+            for channel in self.channels.iter() {
+                channel.start_transmission();
+            }
+        });
+
+        if repeat_mode == RepeatMode::Forever {
+            return Ok(());
+        }
+
+        let mut done = [false; N];
+        while done.iter().any(|done| !done) {
+            for ((channel, sequence), done) in self
+                .channels
+                .iter_mut()
+                .zip(sequence_iters.iter_mut())
+                .zip(done.iter_mut())
+            {
+                if *done {
+                    continue;
+                }
+
+                match channel.poll_transmission(sequence) {
+                    Ok(()) => *done = true,
+                    Err(nb::Error::WouldBlock) => {}
+                    Err(nb::Error::Other(error)) => return Err(error),
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a, const N: usize> Drop for RmtSyncGroup<'a, N> {
+    fn drop(&mut self) {
+        #[cfg(not(esp32))]
+        {
+            let mask = self
+                .channels
+                .iter()
+                .fold(0, |mask, channel| mask | 1 << channel.channel_number());
+
+            unsafe { &*RMT::PTR }
+                .tx_sim
+                .modify(|r, w| unsafe { w.bits(r.bits() & !(mask | TX_SIM_EN)) });
+        }
+    }
+}
+
 macro_rules! channel_instance {
     ($num:literal, $cxi:ident, $output_signal:path
         ) => {
@@ -388,259 +573,335 @@ macro_rules! channel_instance {
                 repeat_mode: RepeatMode,
                 sequence: &[u32; N],
             ) -> Result<(), TransmissionError> {
-                // Check for any configuration error states
-                match repeat_mode {
-                    #[cfg(not(esp32))]
-                    RepeatMode::RepeatNtimes(val) => {
-                        if val >= 1024 {
-                            return Err(TransmissionError::RepetitionOverflow);
+                let mut sequence_iter = sequence.iter();
+
+                self.prepare_transmission(repeat_mode, &mut sequence_iter)?;
+                self.start_transmission();
+
+                // If we're in forever mode, we return right away, otherwise we wait
+                // for completion
+                if repeat_mode != RepeatMode::Forever {
+                    nb::block!(self.poll_transmission(&mut sequence_iter))?;
+                }
+
+                Ok(())
+            }
+
+            /// Stop any ongoing (repetitive) transmission
+            ///
+            /// This function needs to be called to stop sending when
+            /// previously a sequence was sent with `RepeatMode::Forever`.
+            fn stop_transmission(&self) {
+                cfg_if::cfg_if! {
+                    if #[cfg(any(esp32c3, esp32s3))] {
+                        unsafe { &*RMT::PTR }
+                            .ch_tx_conf0[$num]
+                            .modify(|_, w| w.tx_stop().set_bit());
+                    }
+                    else if #[cfg(esp32s2)] {
+                        conf1!($num)
+                            .modify(|_, w| w.tx_stop().set_bit());
+                    }
+                    // The ESP32 variant does not have any way to stop a
+                    // transmission once it has been started!
+                };
+            }
+            }
+
+            impl SyncChannel for [<Configured $cxi>] {
+                fn channel_number(&self) -> u8 {
+                    $num
+                }
+
+                fn prepare_transmission(
+                    &mut self,
+                    repeat_mode: RepeatMode,
+                    sequence: &mut Iter<u32>,
+                ) -> Result<(), TransmissionError> {
+                    // Check for any configuration error states
+                    match repeat_mode {
+                        #[cfg(not(esp32))]
+                        RepeatMode::RepeatNtimes(val) => {
+                            if val >= 1024 {
+                                return Err(TransmissionError::RepetitionOverflow);
+                            }
+                            if sequence.len() > CHANNEL_RAM_SIZE as usize {
+                                return Err(TransmissionError::IncompatibleRepeatMode);
+                            }
                         }
-                        if sequence.len() > CHANNEL_RAM_SIZE as usize {
-                            return Err(TransmissionError::IncompatibleRepeatMode);
+                        RepeatMode::Forever => {
+                            if sequence.len() > CHANNEL_RAM_SIZE as usize {
+                                return Err(TransmissionError::IncompatibleRepeatMode);
+                            }
+                        }
+                        _ => (),
+                    };
+
+                    // Depending on the variant, other registers have to be used here
+                    cfg_if::cfg_if! {
+                        if #[cfg(any(esp32, esp32s2))] {
+                            let conf_reg = & conf1!($num);
+                        } else {
+                            let conf_reg = & unsafe{ &*RMT::PTR }.ch_tx_conf0[$num];
                         }
                     }
-                    RepeatMode::Forever => {
-                        if sequence.len() > CHANNEL_RAM_SIZE as usize {
-                            return Err(TransmissionError::IncompatibleRepeatMode);
+
+                    // The ESP32 does not support loop/count modes, as such we have to
+                    // only configure a subset of registers
+                    cfg_if::cfg_if! {
+                        if #[cfg(esp32)] {
+                            // Configure counting mode and repetitions
+                            unsafe { &*RMT::PTR }.ch_tx_lim[$num].modify(|_, w| unsafe {
+                                // Set the interrupt threshold for sent pulse codes to
+                                // half the size of the RAM in case we use wrap mode
+                                w.tx_lim()
+                                    .bits(CHANNEL_RAM_SIZE as u16 /2)
+                            });
+                        } else {
+                            // Extract repetition value
+                            let mut reps = 0;
+                            if let RepeatMode::RepeatNtimes(val) = repeat_mode {
+                                reps = val;
+                            }
+
+                            // Configure counting mode and repetitions
+                            unsafe { &*RMT::PTR }.ch_tx_lim[$num].modify(|_, w| unsafe {
+                                // Set number of repetitions
+                                w.tx_loop_num()
+                                    .bits(reps)
+                                    // Enable loop counting
+                                    .tx_loop_cnt_en()
+                                    .bit(reps != 0)
+                                    // Reset any pre-existing counting value
+                                    .loop_count_reset()
+                                    .set_bit()
+                                    // Set the interrupt threshold for sent pulse codes to 24
+                                    // (= half the size of the RAM) in case we use wrap mode
+                                    .tx_lim()
+                                    .bits(CHANNEL_RAM_SIZE as u16/2)
+                            });
+
+                            // Stop in hardware when the loop count is reached,
+                            // no extra repetition goes out until the loop
+                            // interrupt was handled
+                            #[cfg(any(esp32c3, esp32s3))]
+                            unsafe { &*RMT::PTR }.ch_tx_lim[$num].modify(|_, w| {
+                                w.loop_stop_en().bit(reps != 0)
+                            });
                         }
                     }
-                    _ => (),
-                };
 
-                // Depending on the variant, other registers have to be used here
-                cfg_if::cfg_if! {
-                    if #[cfg(any(esp32, esp32s2))] {
-                        let conf_reg = & conf1!($num);
+                    #[cfg(any(esp32c3, esp32s3))]
+                    conf_reg.modify(|_, w| {
+                        // Set config update bit
+                        w.conf_update().set_bit()
+                    });
+
+                    // Setup configuration
+                    conf_reg.modify(|_, w| {
+                        // Set configure continuous
+                        // (also reset FIFO buffer pointers)
+                        w.tx_conti_mode()
+                            .bit(repeat_mode != RepeatMode::SingleShot)
+                            .mem_rd_rst()
+                            .set_bit()
+                            .apb_mem_rst()
+                            .set_bit()
+                    });
+
+                    self.channel.reset_fifo();
+
+                    // We have to differentiate here if we can fit the whole sequence
+                    // in the RAM in one go or if we have to use the wrap mode to split
+                    // the sequence into chuncks.
+                    if sequence.len() >= CHANNEL_RAM_SIZE as usize {
+                        // Write the first 48 entries
+                        self.channel.write_sequence(sequence, CHANNEL_RAM_SIZE);
                     } else {
-                        let conf_reg = & unsafe{ &*RMT::PTR }.ch_tx_conf0[$num];
+                        // Write whole sequence to FIFO RAM
+                        self.channel.write_sequence(sequence, CHANNEL_RAM_SIZE);
                     }
-                }
 
-                // The ESP32 does not support loop/count modes, as such we have to
-                // only configure a subset of registers
-                cfg_if::cfg_if! {
-                    if #[cfg(esp32)] {
-                        // Configure counting mode and repetitions
-                        unsafe { &*RMT::PTR }.ch_tx_lim[$num].modify(|_, w| unsafe {
-                            // Set the interrupt threshold for sent pulse codes to
-                            // half the size of the RAM in case we use wrap mode
-                            w.tx_lim()
-                                .bits(CHANNEL_RAM_SIZE as u16 /2)
-                        });
-                    } else {
-                        // Extract repetition value
-                        let mut reps = 0;
-                        if let RepeatMode::RepeatNtimes(val) = repeat_mode {
-                            reps = val;
+                    // Clear the relevant interrupts
+                    //
+                    // (since this is a write-through register, we can do this
+                    // safely for multiple separate channel instances without
+                    // having concurrency issues)
+                    // Depending on the variant, other registers have to be used here
+                    cfg_if::cfg_if! {
+                        if #[cfg(esp32)] {
+                            unsafe { &*RMT::PTR }.int_clr.write(|w| {
+                                // The ESP32 variant does not have the loop functionality
+                                paste!(
+                                    w.[<ch $num _tx_end_int_clr>]()
+                                        .set_bit()
+                                        .[<ch $num _err_int_clr>]()
+                                        .set_bit()
+                                        .[<ch $num _tx_thr_event_int_clr>]()
+                                        .set_bit()
+                                )
+                            });
+                        } else if #[cfg(esp32s2)] {
+                            unsafe { &*RMT::PTR }.int_clr.write(|w| {
+                                paste!(
+                                    w.[<ch $num _tx_end_int_clr>]()
+                                        .set_bit()
+                                        .[<ch $num _tx_loop_int_clr>]()
+                                        .set_bit()
+                                        .[<ch $num _err_int_clr>]()
+                                        .set_bit()
+                                        .[<ch $num _tx_thr_event_int_clr>]()
+                                        .set_bit()
+                                )
+                            });
+                        } else {
+                            unsafe { &*RMT::PTR }.int_clr.write(|w| {
+                                paste!(
+                                    w.[<ch $num _tx_end_int_clr>]()
+                                        .set_bit()
+                                        .[<ch $num _tx_loop_int_clr>]()
+                                        .set_bit()
+                                        .[<ch $num _tx_err_int_clr>]()
+                                        .set_bit()
+                                        .[<ch $num _tx_thr_event_int_clr>]()
+                                        .set_bit()
+                                )
+                            });
                         }
-
-                        // Configure counting mode and repetitions
-                        unsafe { &*RMT::PTR }.ch_tx_lim[$num].modify(|_, w| unsafe {
-                            // Set number of repetitions
-                            w.tx_loop_num()
-                                .bits(reps)
-                                // Enable loop counting
-                                .tx_loop_cnt_en()
-                                .bit(reps != 0)
-                                // Reset any pre-existing counting value
-                                .loop_count_reset()
-                                .set_bit()
-                                // Set the interrupt threshold for sent pulse codes to 24
-                                // (= half the size of the RAM) in case we use wrap mode
-                                .tx_lim()
-                                .bits(CHANNEL_RAM_SIZE as u16/2)
-                        });
                     }
-                }
 
-                #[cfg(any(esp32c3, esp32s3))]
-                conf_reg.modify(|_, w| {
-                    // Set config update bit
-                    w.conf_update().set_bit()
-                });
-
-                // Setup configuration
-                conf_reg.modify(|_, w| {
-                    // Set configure continuous
-                    // (also reset FIFO buffer pointers)
-                    w.tx_conti_mode()
-                        .bit(repeat_mode != RepeatMode::SingleShot)
-                        .mem_rd_rst()
-                        .set_bit()
-                        .apb_mem_rst()
-                        .set_bit()
-                });
+                    // always enable tx wrap
+                    #[cfg(any(esp32c3, esp32s3))]
+                    unsafe { &*RMT::PTR }.ch_tx_conf0[$num].modify(|_, w| {
+                        w.mem_tx_wrap_en()
+                            .set_bit()
+                    });
 
-                self.channel.reset_fifo();
-                let mut sequence_iter = sequence.iter();
+                    // apply configuration updates
+                    #[cfg(any(esp32c3, esp32s3))]
+                    unsafe { &*RMT::PTR }.ch_tx_conf0[$num].modify(|_, w| {
+                        w.conf_update()
+                            .set_bit()
+                    });
 
-                // We have to differentiate here if we can fit the whole sequence
-                // in the RAM in one go or if we have to use the wrap mode to split
-                // the sequence into chuncks.
-                if sequence.len() >= CHANNEL_RAM_SIZE as usize {
-                    // Write the first 48 entries
-                    self.channel.write_sequence(&mut sequence_iter, CHANNEL_RAM_SIZE);
-                } else {
-                    // Write whole sequence to FIFO RAM
-                    self.channel.write_sequence(&mut sequence_iter, CHANNEL_RAM_SIZE);
+                    Ok(())
                 }
 
-                // Clear the relevant interrupts
-                //
-                // (since this is a write-through register, we can do this
-                // safely for multiple separate channel instances without
-                // having concurrency issues)
-                // Depending on the variant, other registers have to be used here
-                cfg_if::cfg_if! {
-                    if #[cfg(esp32)] {
-                        unsafe { &*RMT::PTR }.int_clr.write(|w| {
-                            // The ESP32 variant does not have the loop functionality
-                            paste!(
-                                w.[<ch $num _tx_end_int_clr>]()
-                                    .set_bit()
-                                    .[<ch $num _err_int_clr>]()
-                                    .set_bit()
-                                    .[<ch $num _tx_thr_event_int_clr>]()
-                                    .set_bit()
-                            )
-                        });
-                    } else if #[cfg(esp32s2)] {
-                        unsafe { &*RMT::PTR }.int_clr.write(|w| {
-                            paste!(
-                                w.[<ch $num _tx_end_int_clr>]()
-                                    .set_bit()
-                                    .[<ch $num _tx_loop_int_clr>]()
-                                    .set_bit()
-                                    .[<ch $num _err_int_clr>]()
-                                    .set_bit()
-                                    .[<ch $num _tx_thr_event_int_clr>]()
+                fn start_transmission(&self) {
+                    // Depending on the variant, other registers have to be used here
+                    cfg_if::cfg_if! {
+                        if #[cfg(any(esp32, esp32s2))] {
+                            conf1!($num).modify(|_, w| w.tx_start().set_bit());
+                        } else {
+                            unsafe{ &*RMT::PTR }.ch_tx_conf0[$num].modify(|_, w| w.tx_start().set_bit());
+                        }
+                    }
+                }
+
+                fn abort_transmission(&mut self) {
+                    cfg_if::cfg_if! {
+                        if #[cfg(esp32)] {
+                            conf1!($num).modify(|_, w| w.tx_conti_mode().clear_bit());
+
+                            // The channel stops at the end marker
+                            self.channel.reset_fifo();
+                            self.channel.load_fifo(0);
+                            conf1!($num).modify(|_, w| w.mem_rd_rst().set_bit());
+                        } else if #[cfg(esp32s2)] {
+                            self.stop_transmission();
+                            conf1!($num).modify(|_, w| {
+                                w.tx_conti_mode()
+                                    .clear_bit()
+                                    .mem_rd_rst()
                                     .set_bit()
-                            )
-                        });
-                    } else {
-                        unsafe { &*RMT::PTR }.int_clr.write(|w| {
-                            paste!(
-                                w.[<ch $num _tx_end_int_clr>]()
+                                    .apb_mem_rst()
                                     .set_bit()
-                                    .[<ch $num _tx_loop_int_clr>]()
+                            });
+                        } else {
+                            self.stop_transmission();
+                            unsafe { &*RMT::PTR }.ch_tx_conf0[$num].modify(|_, w| {
+                                w.tx_conti_mode()
+                                    .clear_bit()
+                                    .mem_rd_rst()
                                     .set_bit()
-                                    .[<ch $num _tx_err_int_clr>]()
+                                    .apb_mem_rst()
                                     .set_bit()
-                                    .[<ch $num _tx_thr_event_int_clr>]()
+                                    .conf_update()
                                     .set_bit()
-                            )
-                        });
+                            });
+                        }
                     }
-                }
 
-                // always enable tx wrap
-                #[cfg(any(esp32c3, esp32s3))]
-                unsafe { &*RMT::PTR }.ch_tx_conf0[$num].modify(|_, w| {
-                    w.mem_tx_wrap_en()
-                        .set_bit()
-                });
+                    self.channel.reset_fifo();
+                }
 
-                // apply configuration updates
-                #[cfg(any(esp32c3, esp32s3))]
-                unsafe { &*RMT::PTR }.ch_tx_conf0[$num].modify(|_, w| {
-                    w.conf_update()
-                        .set_bit()
-                });
+                fn poll_transmission(
+                    &mut self,
+                    sequence: &mut Iter<u32>,
+                ) -> nb::Result<(), TransmissionError> {
+                    let interrupts = unsafe { &*RMT::PTR }.int_raw.read();
+
+                    match (
+                        unsafe { interrupts.ch_tx_end_int_raw($num).bit() },
+                        // The ESP32 variant does not support the loop functionality
+                        #[cfg(not(esp32))]
+                        unsafe {interrupts.ch_tx_loop_int_raw($num).bit()},
+                        #[cfg(esp32)]
+                        false,
+                        // The C3/S3 have a slightly different interrupt naming scheme
+                        #[cfg(any(esp32, feature= "esp32s2"))]
+                        unsafe { interrupts.ch_err_int_raw($num).bit() },
+                        #[cfg(any(esp32c3, feature= "esp32s3"))]
+                        unsafe { interrupts.ch_tx_err_int_raw($num).bit() },
+                        unsafe { interrupts.ch_tx_thr_event_int_raw($num).bit() },
+                    ) {
+                        // SingleShot completed and no error -> success
+                        (true, false, false, _) => Ok(()),
+                        // Sequence completed and no error -> success
+                        (false, true, false, _) => {
+                            // Stop transmitting (only necessary in sequence case)
+                            self.stop_transmission();
+                            Ok(())
+                        }
+                        // Refill the buffer
+                        (false, false, false, true) => {
+                            self.channel.write_sequence(sequence, CHANNEL_RAM_SIZE / 2);
 
-                // Depending on the variant, other registers have to be used here
-                cfg_if::cfg_if! {
-                    if #[cfg(any(esp32, esp32s2))] {
-                        conf1!($num).modify(|_, w| w.tx_start().set_bit());
-                    } else {
-                        unsafe{ &*RMT::PTR }.ch_tx_conf0[$num].modify(|_, w| w.tx_start().set_bit());
-                    }
-                }
+                            // Clear the threshold interrupt (write-through)
+                            unsafe { &*RMT::PTR }.int_clr.write(|w| {
+                                paste!(w.[<ch $num _tx_thr_event_int_clr>]().set_bit())
+                            });
 
-                // If we're in forever mode, we return right away, otherwise we wait
-                // for completion
-                if repeat_mode != RepeatMode::Forever {
-                    // Wait for interrupt being raised, either completion or error
-                    loop {
-                        let interrupts = unsafe { &*RMT::PTR }.int_raw.read();
-
-                        match (
-                            unsafe { interrupts.ch_tx_end_int_raw($num).bit() },
-                            // The ESP32 variant does not support the loop functionality
-                            #[cfg(not(esp32))]
-                            unsafe {interrupts.ch_tx_loop_int_raw($num).bit()},
-                            #[cfg(esp32)]
-                            false,
-                            // The C3/S3 have a slightly different interrupt naming scheme
-                            #[cfg(any(esp32, feature= "esp32s2"))]
-                            unsafe { interrupts.ch_err_int_raw($num).bit() },
-                            #[cfg(any(esp32c3, feature= "esp32s3"))]
-                            unsafe { interrupts.ch_tx_err_int_raw($num).bit() },
-                            unsafe { interrupts.ch_tx_thr_event_int_raw($num).bit() },
-                        ) {
-                            // SingleShot completed and no error -> success
-                            (true, false, false, _) => break,
-                            // Sequence completed and no error -> success
-                            (false, true, false, _) => {
-                                // Stop transmitting (only necessary in sequence case)
-                                self.stop_transmission();
-                                break;
-                            }
-                            // Refill the buffer
-                            (false, false, false, true) => {
-                                self.channel.write_sequence(&mut sequence_iter, CHANNEL_RAM_SIZE / 2);
-
-                                // Clear the threshold interrupt (write-through)
-                                unsafe { &*RMT::PTR }.int_clr.write(|w| {
-                                    paste!(w.[<ch $num _tx_thr_event_int_clr>]().set_bit())
-                                });
+                            Err(nb::Error::WouldBlock)
                         }
-                            // Neither completed nor error -> continue busy waiting
-                            (false, false, false, false) => (),
-                            // Anything else constitutes an error state
-                            _ => {
-                                return Err(TransmissionError::Failure(
-                                    unsafe { interrupts.ch_tx_end_int_raw($num).bit() },
-                                    // The ESP32 variant does not support the loop functionality
-                                    #[cfg(not(esp32))]
-                                    unsafe {interrupts.ch_tx_loop_int_raw($num).bit()},
-                                    #[cfg(esp32)]
-                                    false,
-                                    // The C3/S3 have a slightly different interrupt naming scheme
-                                    #[cfg(any(esp32, feature= "esp32s2"))]
-                                    unsafe { interrupts.ch_err_int_raw($num).bit() },
-                                    #[cfg(any(esp32c3, feature= "esp32s3"))]
-                                    unsafe { interrupts.ch_tx_err_int_raw($num).bit() },
-                                    unsafe { interrupts.ch_tx_thr_event_int_raw($num).bit() },
-                                ))
-                            }
+                        // Neither completed nor error -> continue busy waiting
+                        (false, false, false, false) => Err(nb::Error::WouldBlock),
+                        // Anything else constitutes an error state
+                        _ => {
+                            Err(nb::Error::Other(TransmissionError::Failure(
+                                unsafe { interrupts.ch_tx_end_int_raw($num).bit() },
+                                // The ESP32 variant does not support the loop functionality
+                                #[cfg(not(esp32))]
+                                unsafe {interrupts.ch_tx_loop_int_raw($num).bit()},
+                                #[cfg(esp32)]
+                                false,
+                                // The C3/S3 have a slightly different interrupt naming scheme
+                                #[cfg(any(esp32, feature= "esp32s2"))]
+                                unsafe { interrupts.ch_err_int_raw($num).bit() },
+                                #[cfg(any(esp32c3, feature= "esp32s3"))]
+                                unsafe { interrupts.ch_tx_err_int_raw($num).bit() },
+                                unsafe { interrupts.ch_tx_thr_event_int_raw($num).bit() },
+                            )))
                         }
                     }
                 }
-
-                Ok(())
             }
 
-            /// Stop any ongoing (repetitive) transmission
-            ///
-            /// This function needs to be called to stop sending when
-            /// previously a sequence was sent with `RepeatMode::Forever`.
-            fn stop_transmission(&self) {
-                cfg_if::cfg_if! {
-                    if #[cfg(any(esp32c3, esp32s3))] {
-                        unsafe { &*RMT::PTR }
-                            .ch_tx_conf0[$num]
-                            .modify(|_, w| w.tx_stop().set_bit());
-                    }
-                    else if #[cfg(esp32s2)] {
-                        conf1!($num)
-                            .modify(|_, w| w.tx_stop().set_bit());
-                    }
-                    // The ESP32 variant does not have any way to stop a
-                    // transmission once it has been started!
-                };
-            }
+            impl PeripheralOutput for [<Configured $cxi>] {
+                fn output_signal(&self) -> OutputSignal {
+                    $output_signal
+                }
             }
-
         );
     };
 }
@@ -720,6 +981,24 @@ macro_rules! output_channel {
                 self
             }
 
+            /// Set the high and low time of the carrier (in cycles of the RMT source
+            /// clock, before the channel divider)
+            #[inline(always)]
+            fn set_carrier(&mut self, high: u16, low: u16) -> &mut Self {
+                cfg_if::cfg_if! {
+                    if #[cfg(any(esp32c3, esp32s3))] {
+                        unsafe { &*RMT::PTR }
+                            .chcarrier_duty[$num]
+                            .write(|w| unsafe { w.carrier_high().bits(high).carrier_low().bits(low) });
+                    }
+                    else {
+                        carrier_duty!($num)
+                            .write(|w| unsafe { w.carrier_high().bits(high).carrier_low().bits(low) });
+                    }
+                };
+                self
+            }
+
             /// Set the clock source (for the ESP32-S2 and ESP32 this can be done on a
             /// channel level)
             #[cfg(any(esp32s2, esp32))]
@@ -813,6 +1092,36 @@ macro_rules! conf1 {
     };
 }
 
+#[cfg(esp32)]
+macro_rules! carrier_duty {
+    ($channel: literal) => {
+        match $channel {
+            0 => &unsafe { &*RMT::PTR }.ch0carrier_duty,
+            1 => &unsafe { &*RMT::PTR }.ch1carrier_duty,
+            2 => &unsafe { &*RMT::PTR }.ch2carrier_duty,
+            3 => &unsafe { &*RMT::PTR }.ch3carrier_duty,
+            4 => &unsafe { &*RMT::PTR }.ch4carrier_duty,
+            5 => &unsafe { &*RMT::PTR }.ch5carrier_duty,
+            6 => &unsafe { &*RMT::PTR }.ch6carrier_duty,
+            7 => &unsafe { &*RMT::PTR }.ch7carrier_duty,
+            _ => panic!("Attempted access to non-existing channel!"),
+        }
+    };
+}
+
+#[cfg(esp32s2)]
+macro_rules! carrier_duty {
+    ($channel: literal) => {
+        match $channel {
+            0 => &unsafe { &*RMT::PTR }.ch0carrier_duty,
+            1 => &unsafe { &*RMT::PTR }.ch1carrier_duty,
+            2 => &unsafe { &*RMT::PTR }.ch2carrier_duty,
+            3 => &unsafe { &*RMT::PTR }.ch3carrier_duty,
+            _ => panic!("Attempted access to non-existing channel!"),
+        }
+    };
+}
+
 macro_rules! rmt {
     (
         $global_conf_reg:ident,
@@ -829,6 +1138,9 @@ macro_rules! rmt {
             /// RMT channel $cxi
             pub $obj_name: $cxi,
         )+
+        /// The RX channels, see [frequency_ratio]
+        #[cfg(esp32c3)]
+        pub rx_channels: frequency_ratio::RxChannels,
     }
 
     impl PulseControl {
@@ -848,6 +1160,8 @@ macro_rules! rmt {
                 $(
                     $obj_name: $cxi::new(),
                 )+
+                #[cfg(esp32c3)]
+                rx_channels: frequency_ratio::RxChannels::new(),
             };
 
             pc.enable_peripheral(peripheral_clock_control);
@@ -0,0 +1,262 @@
+//! Transmitting asynchronously
+//!
+//! [TransmitAsync::transmit_async] starts a transmission and returns a future
+//! which completes when the channel reached the end marker, or the loop count
+//! with [RepeatMode::RepeatNtimes]. Sequences longer than the channel RAM are
+//! refilled whenever the threshold interrupt signals that half of it was
+//! sent. The futures are woken from the `RMT` interrupt, which has to be
+//! enabled and call [handle_interrupt]:
+//!
+//! ```no_run
+//! interrupt::enable(pac::Interrupt::RMT, interrupt::Priority::Priority1).unwrap();
+//!
+//! #[interrupt]
+//! fn RMT() {
+//!     pulse_control::asynch::handle_interrupt();
+//! }
+//!
+//! channel
+//!     .transmit_async(RepeatMode::SingleShot, &sequence)
+//!     .await
+//!     .unwrap();
+//! ```
+//!
+//! The refill happens when the executor polls the future again, it has to
+//! do so before the other half of the RAM is sent (24 or 32 pulse codes),
+//! otherwise the channel sends stale codes. Sequences which fit into the RAM
+//! don't have this constraint.
+//!
+//! On the ESP32-C3 and ESP32-S3 the channel stops by itself when the loop
+//! count is reached, on the ESP32-S2 it is stopped when the future is polled,
+//! which has to happen within one repetition to send the exact count.
+//!
+//! Dropping a future before it completed stops the channel and rewinds its
+//! RAM read pointer, the next transmission starts from the beginning.
+
+use core::{
+    future::Future,
+    pin::Pin,
+    slice::Iter,
+    sync::atomic::{AtomicU8, Ordering},
+    task::{Context, Poll},
+};
+
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::{RepeatMode, SyncChannel, TransmissionError};
+use crate::pac::RMT;
+
+/// Number of TX channels
+#[cfg(esp32)]
+const CHANNELS: usize = 8;
+#[cfg(any(esp32s2, esp32s3))]
+const CHANNELS: usize = 4;
+#[cfg(esp32c3)]
+const CHANNELS: usize = 2;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEW_WAKER: AtomicWaker = AtomicWaker::new();
+
+static WAKERS: [AtomicWaker; CHANNELS] = [NEW_WAKER; CHANNELS];
+/// Channels with a waiting future, cleared by the interrupt handler
+static WAITING: AtomicU8 = AtomicU8::new(0);
+
+/// The end, error, threshold and loop interrupts of a TX channel in the
+/// `INT_*` registers
+#[cfg(esp32)]
+const fn tx_interrupts(channel: u8) -> u32 {
+    let n = channel as u32;
+    1 << (3 * n) | 1 << (3 * n + 2) | 1 << (24 + n)
+}
+
+#[cfg(esp32s2)]
+const fn tx_interrupts(channel: u8) -> u32 {
+    let n = channel as u32;
+    1 << (3 * n) | 1 << (3 * n + 2) | 1 << (12 + n) | 1 << (16 + n)
+}
+
+#[cfg(esp32c3)]
+const fn tx_interrupts(channel: u8) -> u32 {
+    let n = channel as u32;
+    1 << n | 1 << (4 + n) | 1 << (8 + n) | 1 << (12 + n)
+}
+
+#[cfg(esp32s3)]
+const fn tx_interrupts(channel: u8) -> u32 {
+    let n = channel as u32;
+    1 << n | 1 << (8 + n) | 1 << (16 + n) | 1 << (24 + n)
+}
+
+#[cfg(esp32)]
+const _: () = {
+    assert!(tx_interrupts(0) == 0x0100_0005);
+    assert!(tx_interrupts(7) == 0x8060_0000);
+};
+
+#[cfg(esp32c3)]
+const _: () = {
+    assert!(tx_interrupts(0) == 0x1111);
+    assert!(tx_interrupts(1) == 0x2222);
+};
+
+#[cfg(esp32s3)]
+const _: () = {
+    assert!(tx_interrupts(3) == 0x0808_0808);
+};
+
+fn listen(channel: u8) {
+    critical_section::with(|_| {
+        unsafe { &*RMT::PTR }
+            .int_ena
+            .modify(|r, w| unsafe { w.bits(r.bits() | tx_interrupts(channel)) });
+    });
+}
+
+fn unlisten(channel: u8) {
+    critical_section::with(|_| {
+        unsafe { &*RMT::PTR }
+            .int_ena
+            .modify(|r, w| unsafe { w.bits(r.bits() & !tx_interrupts(channel)) });
+    });
+}
+
+/// Start transmissions which complete asynchronously
+pub trait TransmitAsync: SyncChannel + Sized {
+    /// Send a raw pulse sequence, the returned future completes when it was
+    /// sent
+    ///
+    /// Like [ConfiguredChannel::send_pulse_sequence_raw] the sequence must
+    /// contain the end marker. With [RepeatMode::Forever] the future
+    /// completes right after the start.
+    ///
+    /// [ConfiguredChannel::send_pulse_sequence_raw]: super::ConfiguredChannel::send_pulse_sequence_raw
+    fn transmit_async<'a>(
+        &'a mut self,
+        repeat_mode: RepeatMode,
+        sequence: &'a [u32],
+    ) -> TransmitFuture<'a, Self> {
+        TransmitFuture::new(self, repeat_mode, sequence)
+    }
+}
+
+impl<C> TransmitAsync for C where C: SyncChannel {}
+
+/// Future returned by [TransmitAsync::transmit_async]
+pub struct TransmitFuture<'a, C>
+where
+    C: SyncChannel,
+{
+    channel: &'a mut C,
+    sequence: Iter<'a, u32>,
+    /// Result known before the transmission completes, the start failed or
+    /// it runs forever
+    result: Option<Result<(), TransmissionError>>,
+    done: bool,
+}
+
+impl<'a, C> TransmitFuture<'a, C>
+where
+    C: SyncChannel,
+{
+    fn new(channel: &'a mut C, repeat_mode: RepeatMode, sequence: &'a [u32]) -> Self {
+        let mut sequence = sequence.iter();
+
+        let result = match channel.prepare_transmission(repeat_mode, &mut sequence) {
+            Ok(()) => {
+                channel.start_transmission();
+                if repeat_mode == RepeatMode::Forever {
+                    Some(Ok(()))
+                } else {
+                    None
+                }
+            }
+            Err(error) => Some(Err(error)),
+        };
+
+        Self {
+            channel,
+            sequence,
+            done: result.is_some(),
+            result,
+        }
+    }
+
+    fn is_waiting(&self) -> bool {
+        WAITING.load(Ordering::Acquire) & (1 << self.channel.channel_number()) != 0
+    }
+}
+
+impl<'a, C> Future for TransmitFuture<'a, C>
+where
+    C: SyncChannel,
+{
+    type Output = Result<(), TransmissionError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Some(result) = self.result.take() {
+            return Poll::Ready(result);
+        }
+
+        let number = self.channel.channel_number();
+        WAKERS[number as usize].register(cx.waker());
+
+        // the raw interrupt bits stay set until the channel handles them, an
+        // event between the poll and the listen raises the interrupt right away
+        let this = &mut *self;
+        match this.channel.poll_transmission(&mut this.sequence) {
+            Err(nb::Error::WouldBlock) => {
+                WAITING.fetch_or(1 << number, Ordering::AcqRel);
+                listen(number);
+                Poll::Pending
+            }
+            Ok(()) => {
+                this.done = true;
+                Poll::Ready(Ok(()))
+            }
+            Err(nb::Error::Other(error)) => {
+                this.done = true;
+                Poll::Ready(Err(error))
+            }
+        }
+    }
+}
+
+impl<'a, C> Drop for TransmitFuture<'a, C>
+where
+    C: SyncChannel,
+{
+    fn drop(&mut self) {
+        let number = self.channel.channel_number();
+
+        if self.is_waiting() {
+            unlisten(number);
+            WAITING.fetch_and(!(1 << number), Ordering::AcqRel);
+        }
+
+        if !self.done {
+            self.channel.abort_transmission();
+        }
+    }
+}
+
+/// Wake the futures of the channels with pending interrupts
+///
+/// To be called from the `RMT` interrupt handler. Only the interrupts of
+/// channels with a waiting future are handled, they are disabled until the
+/// future was polled. The RX interrupts are left alone.
+#[procmacros::ram]
+pub fn handle_interrupt() {
+    let rmt = unsafe { &*RMT::PTR };
+    let status = rmt.int_st.read().bits();
+    let waiting = WAITING.load(Ordering::Acquire);
+
+    for channel in 0..CHANNELS as u8 {
+        if waiting & (1 << channel) == 0 || status & tx_interrupts(channel) == 0 {
+            continue;
+        }
+
+        unlisten(channel);
+        WAITING.fetch_and(!(1 << channel), Ordering::AcqRel);
+        WAKERS[channel as usize].wake();
+    }
+}
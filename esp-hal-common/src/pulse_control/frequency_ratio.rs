@@ -0,0 +1,648 @@
+//! Frequency ratio of two signals measured with the RMT receivers
+//!
+//! The ESP32-C3 has no pulse counter, but its two RMT RX channels record the
+//! duration of every high and low level of their input in ticks of the RMT
+//! channel clock. [FrequencyRatio] adds the durations up to timestamp the
+//! rising edges of two inputs over a gate interval and returns the number
+//! of periods and the ticks between the first and the last rising edge of
+//! each input, e.g. to measure the drift of an external oscillator against
+//! the crystal:
+//!
+//! ```no_run
+//! // 80 MHz APB clock, channel clock divider 1
+//! let mut meter =
+//!     FrequencyRatio::new(pulse.rx_channels, 80u32.MHz(), 1, oscillator, crystal).unwrap();
+//! let measurement = meter.measure(1000u32.millis()).unwrap();
+//! // 1 MHz oscillator against a 40 MHz reference derived from the crystal
+//! let nominal = Ratio::from_fraction(1, 40).unwrap();
+//! let drift = measurement.ratio().unwrap().deviation_ppb(nominal);
+//! ```
+//!
+//! Both inputs are counted against the same clock, the ratio doesn't
+//! depend on its frequency. [Count::frequency] relates a single input to the
+//! clock, which is derived from the XTAL with [ClockSource::APB] and
+//! [ClockSource::XTAL](super::ClockSource::XTAL).
+//!
+//! ## Resolution
+//!
+//! An edge is timestamped to one tick, the count of an input is off by at
+//! most one tick at either end of the gate: the relative error of a count
+//! is below 2 / (gate × tick rate), 0.025 ppm for 1 s at 80 MHz, the ratio
+//! adds the errors of both inputs. [Ratio] is a 32.32 bit fixed point
+//! number, its resolution of 2^-32 is well below that.
+//!
+//! ## Limits
+//!
+//! - A level longer than 32767 ticks ends the reception, the inputs have to be
+//!   faster than `tick rate / 65534`, 1.2 kHz at 80 MHz; the divider of
+//!   [FrequencyRatio::new] lowers the tick rate for slower inputs
+//! - [FrequencyRatio::measure] polls the channels with interrupts enabled, each
+//!   channel buffers 48 words of two levels. An interrupt handler running for
+//!   longer than the input needs to fill half of them fails the measurement
+//!   with [MeasureError::Overrun]: about 24 µs for a 1 MHz input. The check
+//!   assumes a periodic input, it estimates the fill time from the average
+//!   level duration so far.
+//! - The gate is at most `u32::MAX` ticks, 53 s at 80 MHz
+//!
+//! [ClockSource::APB]: super::ClockSource::APB
+
+use fugit::{HertzU32, MillisDurationU32};
+
+use super::{CHANNEL_RAM_SIZE, RMT_RAM_START};
+use crate::{
+    gpio::{types::InputSignal, InputPin},
+    pac::RMT,
+    systimer::SystemTimer,
+};
+
+/// RX channels of the ESP32-C3, the second and third block of the RMT memory
+const RX_CHANNELS: [usize; 2] = [2, 3];
+
+// RMT_CHnCONF0_REG of the RX channels
+const DIV_CNT_MASK: u32 = 0xff;
+const IDLE_THRES_SHIFT: u32 = 8;
+const IDLE_THRES_MAX: u32 = 0x7fff;
+const MEM_SIZE_SHIFT: u32 = 23;
+const MEM_SIZE_MASK: u32 = 0x7 << MEM_SIZE_SHIFT;
+const CARRIER_EN: u32 = 1 << 28;
+// RMT_CHnCONF1_REG of the RX channels
+const RX_EN: u32 = 1 << 0;
+const MEM_WR_RST: u32 = 1 << 1;
+const APB_MEM_RST: u32 = 1 << 2;
+const MEM_OWNER: u32 = 1 << 3;
+const RX_FILTER_EN: u32 = 1 << 4;
+const MEM_RX_WRAP_EN: u32 = 1 << 13;
+const CONF_UPDATE: u32 = 1 << 15;
+// RMT_CHnSTATUS_REG of the RX channels, the word the receiver writes next
+const MEM_WADDR_EX_MASK: u32 = 0x1ff;
+// RMT_INT_RAW_REG and RMT_INT_CLR_REG, shifted by the RX channel index
+const RX_END_INT: u32 = 1 << 2;
+const RX_ERR_INT: u32 = 1 << 6;
+
+/// Time the first edges may take on top of the gate
+const START_TIMEOUT_MS: u64 = 100;
+
+/// The RX channels of the RMT, taken from `PulseControl`
+pub struct RxChannels {
+    _private: (),
+}
+
+impl RxChannels {
+    pub(super) fn new() -> Self {
+        Self { _private: () }
+    }
+}
+
+/// Input of a [FrequencyRatio]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Input {
+    /// The first input, `a` of [FrequencyRatio::new]
+    A,
+    /// The second input, `b` of [FrequencyRatio::new]
+    B,
+}
+
+/// Errors of [FrequencyRatio::new]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigError {
+    /// The divider of the channel clock is 0, it has to be 1 to 255
+    InvalidDivider,
+}
+
+/// Errors of [FrequencyRatio::measure]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasureError {
+    /// The input had less than two rising edges within the gate
+    NoSignal(Input),
+    /// A level of the input was longer than the idle threshold of 32767
+    /// ticks, the input is too slow for the tick rate
+    SignalLost(Input),
+    /// The channel memory may have been overwritten before it was read,
+    /// e.g. because an interrupt handler ran for too long
+    Overrun(Input),
+    /// The gate is longer than `u32::MAX` ticks
+    GateTooLong,
+}
+
+/// Periods of an input and the ticks they took
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Count {
+    /// Complete periods between the first and the last rising edge
+    pub periods: u32,
+    /// Ticks of the RMT channel clock from the first to the last rising
+    /// edge
+    pub ticks: u32,
+}
+
+impl Count {
+    /// Frequency of the input in Hz, for a channel clock of `tick_rate`
+    ///
+    /// `None` if [Count::ticks] is 0.
+    pub const fn frequency(&self, tick_rate: HertzU32) -> Option<Ratio> {
+        Ratio::from_fraction(
+            self.periods as u64 * tick_rate.raw() as u64,
+            self.ticks as u64,
+        )
+    }
+}
+
+/// Result of [FrequencyRatio::measure]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement {
+    /// Count of input `a`
+    pub a: Count,
+    /// Count of input `b`
+    pub b: Count,
+}
+
+impl Measurement {
+    /// The frequency of input `a` divided by the one of input `b`
+    ///
+    /// `None` if the periods of `b` or the ticks of `a` are 0.
+    pub const fn ratio(&self) -> Option<Ratio> {
+        // both products fit into 64 bits
+        Ratio::from_fraction(
+            self.a.periods as u64 * self.b.ticks as u64,
+            self.b.periods as u64 * self.a.ticks as u64,
+        )
+    }
+}
+
+/// Unsigned 32.32 bit fixed point number
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Ratio(u64);
+
+impl Ratio {
+    /// Bits after the binary point, the resolution is 2^-32
+    pub const FRACTIONAL_BITS: u32 = 32;
+
+    /// `numerator / denominator`, saturated to the largest value, `None` for
+    /// a denominator of 0
+    ///
+    /// Only uses 64-bit arithmetic: a denominator above 2^48 is scaled down
+    /// together with the numerator first, losing less than 2^-47 of the
+    /// value.
+    pub const fn from_fraction(numerator: u64, denominator: u64) -> Option<Self> {
+        if denominator == 0 {
+            return None;
+        }
+
+        let shift = 16u32.saturating_sub(denominator.leading_zeros());
+        let (numerator, denominator) = (numerator >> shift, denominator >> shift);
+
+        let integer = numerator / denominator;
+        if integer > u32::MAX as u64 {
+            return Some(Self(u64::MAX));
+        }
+
+        // the remainder is below 2^48, 16 bits of the fraction at a time
+        let mut remainder = numerator % denominator;
+        let mut value = integer;
+        let mut i = 0;
+        while i < 2 {
+            remainder <<= 16;
+            value = value << 16 | remainder / denominator;
+            remainder %= denominator;
+            i += 1;
+        }
+
+        Some(Self(value))
+    }
+
+    /// The raw value, scaled by 2^32
+    pub const fn raw(self) -> u64 {
+        self.0
+    }
+
+    /// The integer part
+    pub const fn integer(self) -> u32 {
+        (self.0 >> Self::FRACTIONAL_BITS) as u32
+    }
+
+    /// Deviation from `nominal` in parts per billion, saturated to ±2^31,
+    /// `None` for a `nominal` of 0
+    pub const fn deviation_ppb(self, nominal: Ratio) -> Option<i32> {
+        let relative = match Ratio::from_fraction(self.0, nominal.0) {
+            Some(relative) => relative.0,
+            None => return None,
+        };
+        let relative = if relative > i64::MAX as u64 {
+            i64::MAX
+        } else {
+            relative as i64
+        };
+        let deviation = relative - (1 << Self::FRACTIONAL_BITS);
+        let ppb = deviation.saturating_mul(1_000_000_000) >> Self::FRACTIONAL_BITS;
+
+        Some(if ppb > i32::MAX as i64 {
+            i32::MAX
+        } else if ppb < i32::MIN as i64 {
+            i32::MIN
+        } else {
+            ppb as i32
+        })
+    }
+}
+
+/// Rising edges of one input, followed through the memory of its channel
+#[derive(Clone, Copy)]
+struct Edges {
+    /// Word of the channel memory read next
+    next: usize,
+    /// Ticks since the start of the reception
+    elapsed: u32,
+    /// Levels with a duration, to estimate how fast the memory fills
+    levels: u32,
+    first: Option<u32>,
+    last: u32,
+    periods: u32,
+    lost: bool,
+}
+
+impl Edges {
+    const NONE: Self = Self {
+        next: 0,
+        elapsed: 0,
+        levels: 0,
+        first: None,
+        last: 0,
+        periods: 0,
+        lost: false,
+    };
+
+    fn push(&mut self, level: bool, duration: u32) {
+        *self = self.with_level(level, duration);
+    }
+
+    /// The edges after a `level` of `duration` ticks was received
+    const fn with_level(mut self, level: bool, duration: u32) -> Self {
+        if duration == 0 {
+            // end marker, written after an idle level
+            self.lost = true;
+            return self;
+        }
+
+        // a high level starts with a rising edge
+        if level {
+            match self.first {
+                None => self.first = Some(self.elapsed),
+                Some(_) => self.periods += 1,
+            }
+            self.last = self.elapsed;
+        }
+
+        self.elapsed = self.elapsed.wrapping_add(duration);
+        self.levels = self.levels.saturating_add(1);
+        self
+    }
+
+    /// Whether the receiver may have overwritten words which weren't read
+    /// yet, `gap` ticks after the previous read
+    ///
+    /// Estimated from the average level duration so far: more than half of
+    /// the channel memory filling up within `gap` is taken as an overrun.
+    const fn overrun(&self, gap: u64) -> bool {
+        if self.levels < 2 {
+            return false;
+        }
+
+        // half of the memory holds `CHANNEL_RAM_SIZE` levels, two per word
+        gap.saturating_mul(self.levels as u64) > self.elapsed as u64 * CHANNEL_RAM_SIZE as u64
+    }
+
+    const fn ticks(&self) -> u32 {
+        match self.first {
+            Some(first) => self.last.wrapping_sub(first),
+            None => 0,
+        }
+    }
+
+    const fn count(&self, input: Input) -> Result<Count, MeasureError> {
+        if self.periods == 0 {
+            return Err(MeasureError::NoSignal(input));
+        }
+
+        Ok(Count {
+            periods: self.periods,
+            ticks: self.ticks(),
+        })
+    }
+}
+
+/// Measures the frequency ratio of two inputs with the RMT RX channels, see
+/// the [module documentation](self)
+pub struct FrequencyRatio<A: InputPin, B: InputPin> {
+    channels: RxChannels,
+    tick_rate: HertzU32,
+    a: A,
+    b: B,
+}
+
+impl<A: InputPin, B: InputPin> FrequencyRatio<A, B> {
+    /// Count the rising edges of `a` and `b` with the RMT RX channels
+    ///
+    /// `clock` is the RMT clock configured with `PulseControl::new`, the
+    /// channels count it divided by `divider` (1 to 255).
+    pub fn new(
+        channels: RxChannels,
+        clock: HertzU32,
+        divider: u8,
+        mut a: A,
+        mut b: B,
+    ) -> Result<Self, ConfigError> {
+        if divider == 0 {
+            return Err(ConfigError::InvalidDivider);
+        }
+
+        a.set_to_input()
+            .connect_input_to_peripheral(InputSignal::RMT_SIG_0);
+        b.set_to_input()
+            .connect_input_to_peripheral(InputSignal::RMT_SIG_1);
+
+        let rmt = unsafe { &*RMT::PTR };
+        for index in 0..RX_CHANNELS.len() {
+            rmt.ch_rx_conf0[index].modify(|r, w| unsafe {
+                w.bits(
+                    r.bits() & !(DIV_CNT_MASK | MEM_SIZE_MASK | CARRIER_EN)
+                        | divider as u32
+                        | IDLE_THRES_MAX << IDLE_THRES_SHIFT
+                        | 1 << MEM_SIZE_SHIFT,
+                )
+            });
+            rmt.ch_rx_conf1[index].modify(|r, w| unsafe {
+                w.bits(r.bits() & !(RX_EN | RX_FILTER_EN) | MEM_RX_WRAP_EN | CONF_UPDATE)
+            });
+        }
+
+        Ok(Self {
+            channels,
+            tick_rate: HertzU32::from_raw(clock.raw() / divider as u32),
+            a,
+            b,
+        })
+    }
+
+    /// Count both inputs for at least `gate`
+    ///
+    /// Blocks for the gate and the first edges. The channels are read in
+    /// short critical sections, interrupts can run in between, see the
+    /// [module documentation](self) for how long. Fails if an input has no
+    /// edges within the gate and 100 ms more, or stops toggling.
+    pub fn measure(&mut self, gate: MillisDurationU32) -> Result<Measurement, MeasureError> {
+        let gate_ticks = self.tick_rate.raw() as u64 * gate.ticks() as u64 / 1000;
+        if gate_ticks > u32::MAX as u64 {
+            return Err(MeasureError::GateTooLong);
+        }
+        let gate_ticks = gate_ticks as u32;
+        let timeout =
+            (gate.ticks() as u64 + START_TIMEOUT_MS) * SystemTimer::TICKS_PER_SECOND / 1000;
+
+        let mut a = Edges::NONE;
+        let mut b = Edges::NONE;
+
+        critical_section::with(|_| {
+            start(0);
+            start(1);
+        });
+
+        let started = SystemTimer::now();
+        let mut drained = started;
+        let mut overrun = None;
+        while a.ticks() < gate_ticks || b.ticks() < gate_ticks {
+            let now = critical_section::with(|_| {
+                drain(0, &mut a);
+                drain(1, &mut b);
+                SystemTimer::now()
+            });
+
+            // the time the receivers had to fill the memory, in channel ticks
+            let gap = now.wrapping_sub(drained) * self.tick_rate.raw() as u64
+                / SystemTimer::TICKS_PER_SECOND;
+            drained = now;
+
+            if a.overrun(gap) {
+                overrun = Some(Input::A);
+            } else if b.overrun(gap) {
+                overrun = Some(Input::B);
+            }
+
+            if overrun.is_some() || a.lost || b.lost || now.wrapping_sub(started) > timeout {
+                break;
+            }
+        }
+
+        critical_section::with(|_| {
+            stop(0);
+            stop(1);
+        });
+
+        if let Some(input) = overrun {
+            return Err(MeasureError::Overrun(input));
+        }
+        if a.lost {
+            return Err(MeasureError::SignalLost(Input::A));
+        }
+        if b.lost {
+            return Err(MeasureError::SignalLost(Input::B));
+        }
+
+        Ok(Measurement {
+            a: a.count(Input::A)?,
+            b: b.count(Input::B)?,
+        })
+    }
+
+    /// Rate of the ticks of [Count::ticks]
+    pub fn tick_rate(&self) -> HertzU32 {
+        self.tick_rate
+    }
+
+    /// Return the RX channels and the pins
+    pub fn free(self) -> (RxChannels, A, B) {
+        (self.channels, self.a, self.b)
+    }
+}
+
+/// Restart the reception of RX channel `index` at the start of its memory
+fn start(index: usize) {
+    let rmt = unsafe { &*RMT::PTR };
+
+    rmt.int_clr
+        .write(|w| unsafe { w.bits((RX_END_INT | RX_ERR_INT) << index) });
+    rmt.ch_rx_conf1[index].modify(|r, w| unsafe {
+        w.bits(r.bits() | MEM_WR_RST | APB_MEM_RST | MEM_OWNER | RX_EN | CONF_UPDATE)
+    });
+}
+
+fn stop(index: usize) {
+    let rmt = unsafe { &*RMT::PTR };
+
+    rmt.ch_rx_conf1[index].modify(|r, w| unsafe { w.bits(r.bits() & !RX_EN | CONF_UPDATE) });
+    rmt.int_clr
+        .write(|w| unsafe { w.bits((RX_END_INT | RX_ERR_INT) << index) });
+}
+
+/// Process the words RX channel `index` wrote since the last call
+#[inline(always)]
+fn drain(index: usize, edges: &mut Edges) {
+    let rmt = unsafe { &*RMT::PTR };
+    let size = CHANNEL_RAM_SIZE as usize;
+    let base = RX_CHANNELS[index] * size;
+
+    let written = (rmt.ch_rx_status[index].read().bits() & MEM_WADDR_EX_MASK) as usize;
+    let written = written.wrapping_sub(base) % size;
+
+    while edges.next != written {
+        let address = RMT_RAM_START + (base + edges.next) * 4;
+        // NOTE(unsafe) the word was completed by the receiver
+        let word = unsafe { (address as *const u32).read_volatile() };
+
+        edges.push(word & (1 << 15) != 0, word & 0x7fff);
+        edges.push(word & (1 << 31) != 0, (word >> 16) & 0x7fff);
+        edges.next = (edges.next + 1) % size;
+    }
+}
+
+const _: () = {
+    // the RX channel memory follows the memory of the TX channels
+    assert!(RX_CHANNELS[0] == 2 && RX_CHANNELS[1] == 3);
+    assert!(((RX_CHANNELS[1] + 1) * CHANNEL_RAM_SIZE as usize) <= MEM_WADDR_EX_MASK as usize);
+    assert!(IDLE_THRES_MAX << IDLE_THRES_SHIFT < MEM_SIZE_MASK);
+};
+
+const _: () = {
+    const ONE: u64 = 1 << Ratio::FRACTIONAL_BITS;
+
+    const fn ratio(numerator: u64, denominator: u64) -> Ratio {
+        match Ratio::from_fraction(numerator, denominator) {
+            Some(ratio) => ratio,
+            None => panic!(),
+        }
+    }
+
+    const fn is(ratio: Option<Ratio>, raw: u64) -> bool {
+        match ratio {
+            Some(ratio) => ratio.raw() == raw,
+            None => false,
+        }
+    }
+
+    assert!(ratio(1, 1).raw() == ONE);
+    assert!(ratio(1, 4).raw() == ONE / 4);
+    assert!(ratio(7, 2).raw() == 3 * ONE + ONE / 2);
+    assert!(ratio(80_000_000, 3).integer() == 26_666_666);
+    assert!(ratio(0, 5).raw() == 0);
+    // rounded down, 1/3 is 0x5555_5555.5...
+    assert!(ratio(1, 3).raw() == 0x5555_5555);
+    assert!(ratio(2, 3).raw() == 0xaaaa_aaaa);
+    // saturated, 2^32 doesn't fit into 32 integer bits
+    assert!(ratio(u64::MAX, 1).raw() == u64::MAX);
+    assert!(ratio(1 << 32, 1).raw() == u64::MAX);
+    assert!(ratio(u32::MAX as u64, 1).integer() == u32::MAX);
+    assert!(Ratio::from_fraction(1, 0).is_none());
+    assert!(Ratio::from_fraction(0, 0).is_none());
+    // a large denominator is scaled down by 2^16, exact for multiples of
+    // 2^16 and otherwise within 2^-47 of the value
+    assert!(ratio(1 << 50, 3 << 50).raw() == ratio(1, 3).raw());
+    assert!(ratio(1, 2).raw() - ratio(u64::MAX / 2, u64::MAX).raw() <= 1);
+
+    let nominal = ratio(40, 1);
+    assert!(matches!(nominal.deviation_ppb(nominal), Some(0)));
+    assert!(matches!(
+        ratio(40_000_040, 1_000_000).deviation_ppb(nominal),
+        Some(999)
+    ));
+    assert!(matches!(
+        ratio(39_999_960, 1_000_000).deviation_ppb(nominal),
+        Some(-1_001)
+    ));
+    // 1/40 is only resolved to 2^-32, 9 ppb of its value
+    assert!(matches!(
+        ratio(1_000_001, 40_000_000).deviation_ppb(ratio(1, 40)),
+        Some(990..=1_010)
+    ));
+    // saturated
+    let nominal = ratio(1, 1000);
+    assert!(matches!(
+        ratio(1000, 1).deviation_ppb(nominal),
+        Some(i32::MAX)
+    ));
+    assert!(matches!(
+        ratio(0, 1).deviation_ppb(nominal),
+        Some(-1_000_000_000)
+    ));
+    assert!(ratio(1, 1).deviation_ppb(ratio(0, 1)).is_none());
+
+    const fn toggle(mut edges: Edges, periods: u32, high: u32, low: u32) -> Edges {
+        let mut i = 0;
+        while i < periods {
+            edges = edges.with_level(true, high).with_level(false, low);
+            i += 1;
+        }
+        edges
+    }
+
+    // starts low, the first rising edge is at tick 5
+    let edges = toggle(Edges::NONE.with_level(false, 5), 10, 3, 7);
+    assert!(matches!(
+        edges.count(Input::A),
+        Ok(Count {
+            periods: 9,
+            ticks: 90
+        })
+    ));
+    assert!(!edges.lost);
+
+    assert!(matches!(
+        Edges::NONE.count(Input::B),
+        Err(MeasureError::NoSignal(Input::B))
+    ));
+    let edges = Edges::NONE.with_level(true, 3).with_level(false, 7);
+    assert!(matches!(
+        edges.count(Input::B),
+        Err(MeasureError::NoSignal(Input::B))
+    ));
+
+    // end marker
+    let edges = toggle(Edges::NONE, 2, 3, 7).with_level(true, 0);
+    assert!(edges.lost);
+    assert!(edges.ticks() == 10);
+
+    // 20 ticks per level, half of the memory fills in 20 * CHANNEL_RAM_SIZE
+    assert!(!Edges::NONE.overrun(u64::MAX));
+    let edges = toggle(Edges::NONE, 4, 20, 20);
+    let fill = 20 * CHANNEL_RAM_SIZE as u64;
+    assert!(!edges.overrun(0));
+    assert!(!edges.overrun(fill));
+    assert!(edges.overrun(fill + 1));
+    assert!(edges.overrun(u64::MAX));
+
+    let measurement = Measurement {
+        a: Count {
+            periods: 1_000,
+            ticks: 80_000,
+        },
+        b: Count {
+            periods: 40_000,
+            ticks: 80_000,
+        },
+    };
+    assert!(is(measurement.ratio(), ratio(1, 40).raw()));
+    assert!(is(
+        measurement.a.frequency(HertzU32::from_raw(80_000_000)),
+        ratio(1_000_000, 1).raw()
+    ));
+
+    // no ticks
+    let none = Count {
+        periods: 0,
+        ticks: 0,
+    };
+    let some = Count {
+        periods: 1,
+        ticks: 80,
+    };
+    assert!(Measurement { a: none, b: some }.ratio().is_none());
+    assert!(Measurement { a: some, b: none }.ratio().is_none());
+    assert!(none.frequency(HertzU32::from_raw(80_000_000)).is_none());
+};
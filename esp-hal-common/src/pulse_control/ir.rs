@@ -0,0 +1,390 @@
+//! Infrared remote control protocols
+//!
+//! Encoders and decoders for the pulse codes of the NEC and RC5 protocols.
+//! All durations are in microseconds, [configure] sets up a TX channel
+//! accordingly (1 µs ticks, 38 kHz carrier with a duty cycle of 1/3):
+//!
+//! ```no_run
+//! ir::configure(&mut rmt_channel0, clocks.apb_clock);
+//! let mut rmt_channel0 = rmt_channel0.assign_pin(io.pins.gpio4);
+//!
+//! let frame = NecEncoder.encode(0x04, 0x08);
+//! rmt_channel0
+//!     .send_pulse_sequence(RepeatMode::SingleShot, &frame)
+//!     .unwrap();
+//! ```
+//!
+//! The decoders take the captured pulses, starting with the first mark (the
+//! burst of carrier) and ending with a length of 0 or the end of the slice.
+//! Only the durations are evaluated, so the inverted output of common IR
+//! receivers can be decoded as well. Every duration may be off by ±25% by
+//! default, see e.g. [NecDecoder::with_tolerance].
+
+use fugit::{HertzU32, NanosDurationU32};
+
+use super::{OutputChannel, PulseCode};
+
+/// Frequency of the carrier set by [configure]
+pub const CARRIER_FREQUENCY: u32 = 38_000;
+
+/// Tick frequency of the channels set up by [configure]
+pub const TICK_FREQUENCY: u32 = 1_000_000;
+
+/// Default tolerance of the decoders in percent
+pub const DEFAULT_TOLERANCE: u32 = 25;
+
+const NEC_LEADER_MARK: u32 = 9000;
+const NEC_LEADER_SPACE: u32 = 4500;
+const NEC_REPEAT_SPACE: u32 = 2250;
+const NEC_BIT_MARK: u32 = 560;
+const NEC_ZERO_SPACE: u32 = 560;
+const NEC_ONE_SPACE: u32 = 1690;
+
+/// Number of pulse codes of an NEC frame, including the end marker
+pub const NEC_FRAME_LEN: usize = 34;
+/// Number of pulse codes of an NEC repeat frame, including the end marker
+pub const NEC_REPEAT_LEN: usize = 2;
+
+const RC5_HALF_BIT: u32 = 889;
+const RC5_BITS: usize = 14;
+
+/// Maximum number of pulse codes of an RC5 frame, including the end marker
+pub const RC5_FRAME_LEN: usize = RC5_BITS;
+
+/// Errors that can occur when decoding captured pulses
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The pulses ended before the frame was complete
+    TooShort,
+    /// The pulse code at this index doesn't match the timing of the protocol
+    UnexpectedTiming(usize),
+    /// The command doesn't match its inverted copy in an NEC frame
+    InvalidChecksum,
+    /// The bit at this index of an RC5 frame isn't Manchester coded
+    InvalidBit(usize),
+}
+
+/// Configure a TX channel for the encoders of this module
+///
+/// `source_clock` is the clock the channel counts, i.e. the APB clock divided
+/// by the RMT-wide divider on the ESP32-C3 and ESP32-S3, and the APB clock or
+/// the reference tick on the ESP32 and ESP32-S2. It has to be a multiple of 1
+/// MHz up to 255 MHz.
+pub fn configure<CC, C>(channel: &mut C, source_clock: HertzU32)
+where
+    C: OutputChannel<CC>,
+{
+    let divider = source_clock.raw() / TICK_FREQUENCY;
+    assert!(
+        (1..=255).contains(&divider),
+        "The source clock can't be divided to 1 MHz ticks"
+    );
+
+    let period = source_clock.raw() / CARRIER_FREQUENCY;
+    let high = period / 3;
+
+    channel
+        .set_channel_divider(divider as u8)
+        .set_carrier(high as u16, (period - high) as u16)
+        .set_carrier_modulation(true)
+        .set_idle_output_level(false)
+        .set_idle_output(true);
+}
+
+/// An NEC command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NecCommand {
+    /// Address, values above `0xff` are sent as extended 16 bit address
+    pub address: u16,
+    /// Command
+    pub command: u8,
+}
+
+/// A decoded NEC frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NecFrame {
+    /// A full frame
+    Command(NecCommand),
+    /// A repeat frame, sent every 108 ms while the button of the last command
+    /// is held
+    Repeat,
+}
+
+/// Encoder for the NEC protocol
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NecEncoder;
+
+impl NecEncoder {
+    /// Pulse codes of a full frame
+    pub fn encode(&self, address: u16, command: u8) -> [PulseCode; NEC_FRAME_LEN] {
+        let address = if address > 0xff {
+            address as u32
+        } else {
+            address as u32 | (!address as u32 & 0xff) << 8
+        };
+        let data = address | (command as u32) << 16 | (!command as u32) << 24;
+
+        let mut codes = [pulse(0, 0); NEC_FRAME_LEN];
+        codes[0] = pulse(NEC_LEADER_MARK, NEC_LEADER_SPACE);
+        for (bit, code) in codes[1..33].iter_mut().enumerate() {
+            *code = if data & (1 << bit) != 0 {
+                pulse(NEC_BIT_MARK, NEC_ONE_SPACE)
+            } else {
+                pulse(NEC_BIT_MARK, NEC_ZERO_SPACE)
+            };
+        }
+        codes[33] = pulse(NEC_BIT_MARK, 0);
+
+        codes
+    }
+
+    /// Pulse codes of a repeat frame
+    pub fn encode_repeat(&self) -> [PulseCode; NEC_REPEAT_LEN] {
+        [
+            pulse(NEC_LEADER_MARK, NEC_REPEAT_SPACE),
+            pulse(NEC_BIT_MARK, 0),
+        ]
+    }
+}
+
+/// Decoder for the NEC protocol
+#[derive(Debug, Clone, Copy)]
+pub struct NecDecoder {
+    tolerance: u32,
+}
+
+impl Default for NecDecoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NecDecoder {
+    /// Create a decoder with the [default tolerance](DEFAULT_TOLERANCE)
+    pub const fn new() -> Self {
+        Self {
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    /// Create a decoder accepting durations off by `tolerance` percent
+    pub const fn with_tolerance(tolerance: u32) -> Self {
+        Self { tolerance }
+    }
+
+    /// Decode a full or repeat frame
+    pub fn decode(&self, pulses: &[PulseCode]) -> Result<NecFrame, DecodeError> {
+        let leader = pulses.first().ok_or(DecodeError::TooShort)?;
+        if !matches(mark(leader), NEC_LEADER_MARK, self.tolerance) {
+            return Err(DecodeError::UnexpectedTiming(0));
+        }
+
+        let repeat = if matches(space(leader), NEC_LEADER_SPACE, self.tolerance) {
+            false
+        } else if matches(space(leader), NEC_REPEAT_SPACE, self.tolerance) {
+            true
+        } else {
+            return Err(DecodeError::UnexpectedTiming(0));
+        };
+
+        let bits = if repeat { 0 } else { 32 };
+        let mut data = 0u32;
+        for index in 1..=bits {
+            let code = pulses.get(index).ok_or(DecodeError::TooShort)?;
+            if !matches(mark(code), NEC_BIT_MARK, self.tolerance) {
+                return Err(DecodeError::UnexpectedTiming(index));
+            }
+
+            if matches(space(code), NEC_ONE_SPACE, self.tolerance) {
+                data |= 1 << (index - 1);
+            } else if !matches(space(code), NEC_ZERO_SPACE, self.tolerance) {
+                return Err(DecodeError::UnexpectedTiming(index));
+            }
+        }
+
+        let stop = pulses.get(bits + 1).ok_or(DecodeError::TooShort)?;
+        if !matches(mark(stop), NEC_BIT_MARK, self.tolerance) {
+            return Err(DecodeError::UnexpectedTiming(bits + 1));
+        }
+
+        if repeat {
+            return Ok(NecFrame::Repeat);
+        }
+
+        let command = (data >> 16) as u8;
+        if command != !(data >> 24) as u8 {
+            return Err(DecodeError::InvalidChecksum);
+        }
+
+        let address = data as u16;
+        let address = if (address >> 8) as u8 == !(address as u8) {
+            address & 0xff
+        } else {
+            address
+        };
+
+        Ok(NecFrame::Command(NecCommand { address, command }))
+    }
+}
+
+/// An RC5 command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rc5Command {
+    /// Address (5 bits)
+    pub address: u8,
+    /// Command (7 bits, values above 63 use the extended RC5 field bit)
+    pub command: u8,
+    /// Toggled with every press of a button, to tell a new press from a held
+    /// button
+    pub toggle: bool,
+}
+
+/// Encoder for the RC5 protocol
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rc5Encoder;
+
+impl Rc5Encoder {
+    /// Pulse codes of a frame
+    ///
+    /// Depending on the bits the frame is shorter than [RC5_FRAME_LEN], the
+    /// remaining pulse codes are end markers.
+    pub fn encode(&self, command: Rc5Command) -> [PulseCode; RC5_FRAME_LEN] {
+        let bits = 1 << 13
+            | ((!command.command as u16 >> 6) & 1) << 12
+            | (command.toggle as u16) << 11
+            | (command.address as u16 & 0x1f) << 6
+            | (command.command as u16 & 0x3f);
+
+        // Manchester coded, a 1 is a space followed by a mark. The frame starts
+        // with the mark of the first start bit, adjacent half bits of the same
+        // level are joined.
+        let mut durations = [0u32; 2 * RC5_FRAME_LEN];
+        let mut index = 0;
+        let mut level = true;
+        for half in 1..2 * RC5_BITS {
+            let bit = bits & (1 << (RC5_BITS - 1 - half / 2)) != 0;
+            let half_level = (half % 2 == 1) == bit;
+            if half_level != level {
+                index += 1;
+                level = half_level;
+            }
+            durations[index] += RC5_HALF_BIT;
+        }
+
+        // A trailing space is part of the idle time
+        if !level {
+            durations[index] = 0;
+        }
+
+        let mut codes = [pulse(0, 0); RC5_FRAME_LEN];
+        for (code, pair) in codes.iter_mut().zip(durations.chunks(2)) {
+            *code = pulse(pair[0], pair[1]);
+        }
+
+        codes
+    }
+}
+
+/// Decoder for the RC5 protocol
+#[derive(Debug, Clone, Copy)]
+pub struct Rc5Decoder {
+    tolerance: u32,
+}
+
+impl Default for Rc5Decoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rc5Decoder {
+    /// Create a decoder with the [default tolerance](DEFAULT_TOLERANCE)
+    pub const fn new() -> Self {
+        Self {
+            tolerance: DEFAULT_TOLERANCE,
+        }
+    }
+
+    /// Create a decoder accepting durations off by `tolerance` percent
+    pub const fn with_tolerance(tolerance: u32) -> Self {
+        Self { tolerance }
+    }
+
+    /// Decode a frame
+    pub fn decode(&self, pulses: &[PulseCode]) -> Result<Rc5Command, DecodeError> {
+        // Levels of the half bits, starting with the space of the first start
+        // bit which isn't part of the captured pulses
+        let mut halves = 0u32;
+        let mut count = 1;
+
+        'pulses: for (index, code) in pulses.iter().enumerate() {
+            for (duration, level) in [(mark(code), true), (space(code), false)] {
+                if duration == 0 {
+                    break 'pulses;
+                }
+
+                let length = if matches(duration, RC5_HALF_BIT, self.tolerance) {
+                    1
+                } else if matches(duration, 2 * RC5_HALF_BIT, self.tolerance) {
+                    2
+                } else if !level && count + 1 >= 2 * RC5_BITS {
+                    // The space after the last bit merges into the idle time
+                    break 'pulses;
+                } else {
+                    return Err(DecodeError::UnexpectedTiming(index));
+                };
+
+                for _ in 0..length {
+                    if count == 2 * RC5_BITS {
+                        return Err(DecodeError::UnexpectedTiming(index));
+                    }
+                    halves |= (level as u32) << count;
+                    count += 1;
+                }
+            }
+        }
+
+        // Only a trailing space may be missing
+        if count < 2 * RC5_BITS - 1 {
+            return Err(DecodeError::TooShort);
+        }
+
+        let mut bits = 0u16;
+        for bit in 0..RC5_BITS {
+            let first = halves & (1 << (2 * bit)) != 0;
+            let second = halves & (1 << (2 * bit + 1)) != 0;
+            if first == second {
+                return Err(DecodeError::InvalidBit(bit));
+            }
+            bits = bits << 1 | second as u16;
+        }
+
+        Ok(Rc5Command {
+            address: ((bits >> 6) & 0x1f) as u8,
+            command: (bits & 0x3f) as u8 | (((!bits >> 12) & 1) as u8) << 6,
+            toggle: bits & (1 << 11) != 0,
+        })
+    }
+}
+
+fn pulse(mark: u32, space: u32) -> PulseCode {
+    PulseCode {
+        level1: true,
+        length1: NanosDurationU32::from_ticks(mark),
+        level2: false,
+        length2: NanosDurationU32::from_ticks(space),
+    }
+}
+
+fn mark(code: &PulseCode) -> u32 {
+    code.length1.ticks()
+}
+
+fn space(code: &PulseCode) -> u32 {
+    code.length2.ticks()
+}
+
+fn matches(duration: u32, expected: u32, tolerance: u32) -> bool {
+    let deviation = expected * tolerance / 100;
+    (expected.saturating_sub(deviation)..=expected + deviation).contains(&duration)
+}
@@ -0,0 +1,283 @@
+//! Deep sleep wake stub
+//!
+//! Waking up from deep sleep resets the chip, the ROM loads the bootloader
+//! from flash, which loads the application before the first line of `main`
+//! runs. A wake stub is a function in RTC fast memory the ROM jumps to
+//! instead, right after the reset, before anything is loaded from flash. It
+//! can sample a sensor or toggle a pin and go back to sleep without waiting
+//! for the boot, and only boot the application when there is something to
+//! do:
+//!
+//! ```no_run
+//! #[ram(rtc_fast)]
+//! static mut PERIOD: Option<WakePeriod> = None;
+//!
+//! #[ram(rtc_fast)]
+//! fn stub() -> ! {
+//!     // ... sample, decide
+//!     match unsafe { PERIOD } {
+//!         Some(period) if !done => reset::stub_sleep_for(period),
+//!         _ => reset::stub_continue_boot(),
+//!     }
+//! }
+//!
+//! unsafe { PERIOD = Some(WakePeriod::new(100u64.millis())) };
+//! reset::set_wake_deep_sleep_stub(stub).unwrap();
+//! rtc.sleep_deep_for(&SleepConfig::default(), WakePeriod::new(100u64.millis()));
+//! ```
+//!
+//! The stub runs on a stack of [STUB_STACK_SIZE] bytes in RTC fast memory,
+//! on the PRO CPU with the clocks the ROM set up.
+//!
+//! ## Constraints
+//!
+//! The flash isn't mapped while the stub runs, everything it touches has to
+//! be in RTC fast memory or in the ROM:
+//! - the stub and every function it calls have to be placed with
+//!   `#[ram(rtc_fast)]`, or be `#[inline(always)]`; this includes the helpers
+//!   of this module whose name starts with `stub_`
+//! - its variables have to be statics placed with `#[ram(rtc_fast)]`, they keep
+//!   their values across deep sleep; constant data in flash, string literals
+//!   and `println!` are not available
+//! - panics, formatting and 64-bit divisions call into flash, a [WakePeriod] is
+//!   computed by the application for that reason
+//! - the peripherals of the digital domain were reset, only the RTC domain kept
+//!   its configuration
+//!
+//! A stub which touches flash crashes into a reset, after which the
+//! application boots normally. The RTC fast memory has to stay powered, see
+//! [SleepConfig::rtc_fast_mem](crate::rtc_cntl::SleepConfig::rtc_fast_mem).
+//!
+//! The ROM only jumps to the stub when the CRC of `.rtc_fast.text` it
+//! computes on wake matches the one computed when going to sleep, so a
+//! corrupted stub isn't run. Variables are outside of the checked range.
+
+use core::{mem::MaybeUninit, ptr::addr_of};
+
+use fugit::MicrosDurationU64;
+
+use crate::{pac::RTC_CNTL, rtc_cntl::SleepConfigError};
+
+/// Size of the stack the stub runs on, in bytes
+pub const STUB_STACK_SIZE: usize = 1024;
+
+/// RTC fast memory, the same addresses on the instruction and the data bus
+const RTC_FAST_MEM: core::ops::Range<usize> = 0x600f_e000..0x6010_0000;
+
+// RTC_CNTL_RTC_MEM_CONFIG_REG, the range is in words from the start of the
+// RTC fast memory
+const MEM_CRC_START: u32 = 1 << 8;
+const MEM_CRC_ADDR_SHIFT: u32 = 9;
+const MEM_CRC_LEN_SHIFT: u32 = 20;
+const MEM_CRC_FINISH: u32 = 1 << 31;
+// RTC_CNTL_SLP_TIMER1_REG
+const MAIN_TIMER_ALARM_EN: u32 = 1 << 16;
+// RTC_CNTL_TIME_UPDATE_REG
+const TIME_UPDATE: u32 = 1 << 31;
+// RTC_CNTL_STATE0_REG
+const SLEEP_EN: u32 = 1 << 31;
+// RTC_CNTL_OPTIONS0_REG
+const SW_SYS_RST: u32 = 1 << 31;
+
+/// The registered stub
+#[procmacros::ram(rtc_fast)]
+static mut STUB: Option<fn() -> !> = None;
+
+#[procmacros::ram(rtc_fast, uninitialized)]
+static mut STACK: MaybeUninit<[u32; STUB_STACK_SIZE / 4]> = MaybeUninit::uninit();
+
+/// Time until the RTC timer wakes the chip, in RTC_SLOW_CLK cycles
+///
+/// Converted with the RTC_SLOW_CLK calibration of [Rtc::new] by the
+/// application, the stub can't divide.
+///
+/// [Rtc::new]: crate::rtc_cntl::Rtc::new
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WakePeriod {
+    ticks: u64,
+}
+
+impl WakePeriod {
+    /// Wake up after `duration`
+    ///
+    /// The internal RC oscillator drifts with the temperature, the period
+    /// is only as accurate as the calibration.
+    pub fn new(duration: MicrosDurationU64) -> Self {
+        // STORE1 holds the period of RTC_SLOW_CLK in microseconds, Q13.19
+        let cal = unsafe { &*RTC_CNTL::ptr() }.store1.read().bits() as u64;
+
+        Self {
+            ticks: (duration.ticks() << 19) / cal.max(1),
+        }
+    }
+}
+
+/// Run `stub` instead of booting on the following wakes from deep sleep
+///
+/// `stub` has to be placed with `#[ram(rtc_fast)]`, otherwise
+/// [SleepConfigError::StubNeedsFastMemory] is returned. See the
+/// [module documentation](self) for what else it may use.
+pub fn set_wake_deep_sleep_stub(stub: fn() -> !) -> Result<(), SleepConfigError> {
+    if !RTC_FAST_MEM.contains(&(stub as usize)) {
+        return Err(SleepConfigError::StubNeedsFastMemory);
+    }
+
+    unsafe { STUB = Some(stub) };
+
+    Ok(())
+}
+
+/// Boot the application again on the following wakes
+pub fn clear_wake_deep_sleep_stub() {
+    unsafe { STUB = None };
+
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl.store6.write(|w| unsafe { w.bits(0) });
+}
+
+pub(crate) fn is_stub_set() -> bool {
+    unsafe { STUB.is_some() }
+}
+
+/// Program the RTC timer and, with a stub, its entry point and CRC
+///
+/// To be called right before entering deep sleep.
+pub(crate) fn prepare_deep_sleep(period: WakePeriod) {
+    set_wakeup_timer(period);
+
+    if is_stub_set() {
+        set_entry_and_crc();
+    }
+}
+
+/// Go back to deep sleep from the stub, waking up again after `period`
+///
+/// The wake sources of the first sleep stay enabled.
+#[procmacros::ram(rtc_fast)]
+pub fn stub_sleep_for(period: WakePeriod) -> ! {
+    set_wakeup_timer(period);
+    set_entry_and_crc();
+
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl
+        .state0
+        .modify(|r, w| unsafe { w.bits(r.bits() & !SLEEP_EN) });
+    rtc_cntl
+        .state0
+        .modify(|r, w| unsafe { w.bits(r.bits() | SLEEP_EN) });
+
+    // the sleep starts a few RTC_SLOW_CLK cycles later
+    loop {}
+}
+
+/// Leave the stub and boot the application
+///
+/// Resets the digital system, the ROM doesn't run the stub after this reset.
+/// `Rtc::wake_reason` still reports the source which woke the chip.
+#[procmacros::ram(rtc_fast)]
+pub fn stub_continue_boot() -> ! {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl
+        .options0
+        .modify(|r, w| unsafe { w.bits(r.bits() | SW_SYS_RST) });
+
+    loop {}
+}
+
+#[inline(always)]
+fn set_wakeup_timer(period: WakePeriod) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl
+        .time_update
+        .modify(|r, w| unsafe { w.bits(r.bits() | TIME_UPDATE) });
+    let now =
+        (rtc_cntl.time_high0.read().bits() as u64) << 32 | rtc_cntl.time_low0.read().bits() as u64;
+    let target = now.wrapping_add(period.ticks);
+
+    rtc_cntl
+        .slp_timer0
+        .write(|w| unsafe { w.bits(target as u32) });
+    rtc_cntl
+        .slp_timer1
+        .write(|w| unsafe { w.bits((target >> 32) as u32 & 0xffff | MAIN_TIMER_ALARM_EN) });
+}
+
+/// Point the ROM to [stub_entry] and have the CRC engine checksum
+/// `.rtc_fast.text` into STORE7, where the ROM compares it on wake
+#[inline(always)]
+fn set_entry_and_crc() {
+    extern "C" {
+        static _rtc_fast_text_start: u32;
+        static _rtc_fast_text_end: u32;
+    }
+
+    let (start, end) = unsafe {
+        (
+            addr_of!(_rtc_fast_text_start) as usize,
+            addr_of!(_rtc_fast_text_end) as usize,
+        )
+    };
+    let range = (start.wrapping_sub(RTC_FAST_MEM.start) / 4) << MEM_CRC_ADDR_SHIFT
+        | ((end - start) / 4) << MEM_CRC_LEN_SHIFT;
+
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl
+        .store6
+        .write(|w| unsafe { w.bits(stub_entry as usize as u32) });
+
+    rtc_cntl
+        .rtc_mem_config
+        .write(|w| unsafe { w.bits(range as u32) });
+    rtc_cntl
+        .rtc_mem_config
+        .write(|w| unsafe { w.bits(range as u32 | MEM_CRC_START) });
+    while rtc_cntl.rtc_mem_config.read().bits() & MEM_CRC_FINISH == 0 {}
+    rtc_cntl
+        .rtc_mem_config
+        .write(|w| unsafe { w.bits(range as u32) });
+
+    let crc = rtc_cntl.rtc_mem_crc_res.read().bits();
+    rtc_cntl.store7.write(|w| unsafe { w.bits(crc) });
+}
+
+/// Entry point the ROM jumps to on wake
+///
+/// Switches to the stack in RTC fast memory: the frames of the ROM below
+/// are dropped from the register window state, so that nothing is ever
+/// spilled to or reloaded from the ROM's stack, and the base save area of
+/// this frame points into the new stack for the window overflow handlers.
+#[procmacros::ram(rtc_fast)]
+unsafe extern "C" fn stub_entry() -> ! {
+    let top = (addr_of!(STACK) as usize + STUB_STACK_SIZE) & !0xf;
+
+    core::arch::asm!(
+        "rsr.windowbase a4",
+        "movi a5, 1",
+        "ssl a4",
+        "sll a5, a5",
+        "wsr.windowstart a5",
+        "rsync",
+        "addi a1, a2, -32",
+        "s32i a2, a1, -12",
+        "callx8 a3",
+        in("a2") top,
+        in("a3") run_stub as usize,
+        options(noreturn),
+    );
+}
+
+/// Run the registered stub, boot if there is none
+#[procmacros::ram(rtc_fast)]
+extern "C" fn run_stub() -> ! {
+    match unsafe { STUB } {
+        Some(stub) => stub(),
+        None => stub_continue_boot(),
+    }
+}
+
+const _: () = {
+    assert!(STUB_STACK_SIZE % 16 == 0);
+    // the CRC range fields are 11 bits of words
+    assert!((RTC_FAST_MEM.end - RTC_FAST_MEM.start) / 4 <= 0x800);
+};
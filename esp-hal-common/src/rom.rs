@@ -114,6 +114,82 @@ pub unsafe fn regi2c_ctrl_write_reg_mask(
     );
 }
 
+/// Calculate the 12 Reed-Solomon check bytes of the 32 bytes of an eFuse
+/// block
+#[cfg(any(esp32c3, esp32s3))]
+#[inline(always)]
+pub unsafe fn ets_efuse_rs_calculate(data: *const u8, rs_values: *mut u8) {
+    #[cfg(esp32c3)]
+    const ETS_EFUSE_RS_CALCULATE: u32 = 0x4000_184c;
+    #[cfg(esp32s3)]
+    const ETS_EFUSE_RS_CALCULATE: u32 = 0x4000_1964;
+
+    // cast to usize is just needed because of the way we run clippy in CI
+    let rom_ets_efuse_rs_calculate: unsafe extern "C" fn(data: *const u8, rs_values: *mut u8) =
+        core::mem::transmute(ETS_EFUSE_RS_CALCULATE as usize);
+
+    rom_ets_efuse_rs_calculate(data, rs_values);
+}
+
+// The addresses of the ESP32-S3 cache functions are those of the
+// `Cache_Suspend_ICache`, `Cache_Resume_ICache`, `Cache_Suspend_DCache` and
+// `Cache_Resume_DCache` symbols in `components/esp_rom/esp32s3/ld/
+// esp32s3.rom.ld` of ESP-IDF.
+
+/// Suspend the instruction cache once its pending accesses completed,
+/// returns the autoload setting to pass to [cache_resume_icache]
+#[cfg(esp32s3)]
+#[inline(always)]
+pub unsafe fn cache_suspend_icache() -> u32 {
+    const CACHE_SUSPEND_ICACHE: u32 = 0x4000_18e4;
+
+    // cast to usize is just needed because of the way we run clippy in CI
+    let rom_cache_suspend_icache: unsafe extern "C" fn() -> u32 =
+        core::mem::transmute(CACHE_SUSPEND_ICACHE as usize);
+
+    rom_cache_suspend_icache()
+}
+
+/// Resume the instruction cache suspended with [cache_suspend_icache]
+#[cfg(esp32s3)]
+#[inline(always)]
+pub unsafe fn cache_resume_icache(autoload: u32) {
+    const CACHE_RESUME_ICACHE: u32 = 0x4000_18f0;
+
+    // cast to usize is just needed because of the way we run clippy in CI
+    let rom_cache_resume_icache: unsafe extern "C" fn(autoload: u32) =
+        core::mem::transmute(CACHE_RESUME_ICACHE as usize);
+
+    rom_cache_resume_icache(autoload);
+}
+
+/// Suspend the data cache once its pending accesses completed, returns the
+/// autoload setting to pass to [cache_resume_dcache]
+#[cfg(esp32s3)]
+#[inline(always)]
+pub unsafe fn cache_suspend_dcache() -> u32 {
+    const CACHE_SUSPEND_DCACHE: u32 = 0x4000_18fc;
+
+    // cast to usize is just needed because of the way we run clippy in CI
+    let rom_cache_suspend_dcache: unsafe extern "C" fn() -> u32 =
+        core::mem::transmute(CACHE_SUSPEND_DCACHE as usize);
+
+    rom_cache_suspend_dcache()
+}
+
+/// Resume the data cache suspended with [cache_suspend_dcache]
+#[cfg(esp32s3)]
+#[inline(always)]
+pub unsafe fn cache_resume_dcache(autoload: u32) {
+    const CACHE_RESUME_DCACHE: u32 = 0x4000_1908;
+
+    // cast to usize is just needed because of the way we run clippy in CI
+    let rom_cache_resume_dcache: unsafe extern "C" fn(autoload: u32) =
+        core::mem::transmute(CACHE_RESUME_DCACHE as usize);
+
+    rom_cache_resume_dcache(autoload);
+}
+
 #[macro_export]
 macro_rules! regi2c_write_mask {
     ( $block: ident, $reg_add: ident, $indata: expr ) => {
@@ -1,7 +1,7 @@
 use crate::{
     clock::XtalClock,
-    pac::RTC_CNTL,
-    rtc_cntl::{RtcCalSel, RtcClock, RtcFastClock, RtcSlowClock},
+    pac::{RTCIO, RTC_CNTL},
+    rtc_cntl::{RtcCalSel, RtcClock, RtcFastClock, RtcSlowClock, Xtal32kMode},
 };
 
 pub(crate) fn init() {}
@@ -28,3 +28,73 @@ pub(crate) fn configure_clock() {
         rtc_cntl.store1.write(|w| w.bits(cal_val));
     }
 }
+
+/// Power up the 32 kHz oscillator on the 32K_XP and 32K_XN pads
+pub(crate) fn xtal32k_enable(mode: Xtal32kMode) {
+    // Current, resistance and bias of the oscillator, an external clock on
+    // 32K_XN needs a stronger amplifier
+    let (dac, dres, dbias) = match mode {
+        Xtal32kMode::Crystal => (1, 3, 0),
+        Xtal32kMode::ExternalClock => (2, 3, 1),
+    };
+
+    let rtcio = unsafe { &*RTCIO::ptr() };
+    rtcio.xtal_32k_pad.modify(|_, w| unsafe {
+        w.x32n_mux_sel()
+            .set_bit()
+            .x32p_mux_sel()
+            .set_bit()
+            .x32n_rue()
+            .clear_bit()
+            .x32n_rde()
+            .clear_bit()
+            .x32n_fun_ie()
+            .clear_bit()
+            .x32p_rue()
+            .clear_bit()
+            .x32p_rde()
+            .clear_bit()
+            .x32p_fun_ie()
+            .clear_bit()
+            .dac_xtal_32k()
+            .bits(dac)
+            .dres_xtal_32k()
+            .bits(dres)
+            .dbias_xtal_32k()
+            .bits(dbias)
+            .xpd_xtal_32k()
+            .set_bit()
+    });
+}
+
+/// Power down the 32 kHz oscillator
+pub(crate) fn xtal32k_disable() {
+    let rtcio = unsafe { &*RTCIO::ptr() };
+    rtcio.xtal_32k_pad.modify(|_, w| {
+        w.xpd_xtal_32k()
+            .clear_bit()
+            .x32n_mux_sel()
+            .clear_bit()
+            .x32p_mux_sel()
+            .clear_bit()
+    });
+}
+
+/// Keep the RTC slow and fast memory powered in sleep or power them down
+pub(crate) fn set_sleep_memory_power(slow_mem: bool, fast_mem: bool) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl.pwc.modify(|_, w| {
+        w.slowmem_pd_en()
+            .bit(!slow_mem)
+            .slowmem_force_pu()
+            .bit(slow_mem)
+            .slowmem_force_noiso()
+            .bit(slow_mem)
+            .fastmem_pd_en()
+            .bit(!fast_mem)
+            .fastmem_force_pu()
+            .bit(fast_mem)
+            .fastmem_force_noiso()
+            .bit(fast_mem)
+    });
+}
@@ -51,8 +51,14 @@ pub(crate) fn init() {
     power_control_init();
 
     unsafe {
-        rtc_cntl.int_ena_rtc.write(|w| w.bits(0));
-        rtc_cntl.int_clr_rtc.write(|w| w.bits(u32::MAX));
+        // everything but the interrupt of the RTC watchdog, which belongs to
+        // its driver
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|r, w| w.bits(0).wdt_int_ena().bit(r.wdt_int_ena().bit_is_set()));
+        rtc_cntl
+            .int_clr_rtc
+            .write(|w| w.bits(u32::MAX).wdt_int_clr().clear_bit());
 
         regi2c_write_mask!(I2C_ULP, I2C_ULP_IR_FORCE_XPD_CK, 0);
     }
@@ -200,3 +206,6 @@ fn rtc_sleep_pu() {
         .mem_power_up
         .modify(|_, w| unsafe { w.sram_power_up().bits(0u8).rom_power_up().bits(0u8) });
 }
+
+/// There's no RTC memory
+pub(crate) fn set_sleep_memory_power(_slow_mem: bool, _fast_mem: bool) {}
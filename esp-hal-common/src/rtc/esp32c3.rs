@@ -2,12 +2,21 @@ use paste::paste;
 
 use crate::{
     clock::XtalClock,
+    gpio::types::get_io_mux_reg,
     pac::{APB_CTRL, EXTMEM, RTC_CNTL, SPI0, SPI1, SYSTEM},
     regi2c_write_mask,
     rom::regi2c_ctrl_write_reg_mask,
-    rtc_cntl::{RtcCalSel, RtcClock, RtcFastClock, RtcSlowClock},
+    rtc_cntl::{RtcCalSel, RtcClock, RtcFastClock, RtcSlowClock, Xtal32kMode},
 };
 
+// Current, resistance and gm of the 32 kHz crystal oscillator
+const XTAL32K_DAC: u8 = 3;
+const XTAL32K_DRES: u8 = 3;
+const XTAL32K_DGM: u8 = 3;
+
+const XTAL32K_P_GPIO: u8 = 0;
+const XTAL32K_N_GPIO: u8 = 1;
+
 const I2C_DIG_REG: u32 = 0x6d;
 const I2C_DIG_REG_HOSTID: u32 = 0;
 
@@ -81,8 +90,14 @@ pub(crate) fn init() {
     power_control_init();
 
     unsafe {
-        rtc_cntl.int_ena_rtc.write(|w| w.bits(0));
-        rtc_cntl.int_clr_rtc.write(|w| w.bits(u32::MAX));
+        // everything but the interrupt of the RTC watchdog, which belongs to
+        // its driver
+        rtc_cntl
+            .int_ena_rtc
+            .modify(|r, w| w.bits(0).wdt_int_ena().bit(r.wdt_int_ena().bit_is_set()));
+        rtc_cntl
+            .int_clr_rtc
+            .write(|w| w.bits(u32::MAX).wdt_int_clr().clear_bit());
 
         regi2c_write_mask!(I2C_ULP, I2C_ULP_IR_FORCE_XPD_CK, 0);
     }
@@ -253,3 +268,78 @@ fn rtc_sleep_pu() {
         .mem_power_up
         .modify(|_, w| unsafe { w.sram_power_up().bits(0u8).rom_power_up().bits(0u8) });
 }
+
+/// Power up the 32 kHz oscillator on the XTAL_32K_P and XTAL_32K_N pads
+pub(crate) fn xtal32k_enable(mode: Xtal32kMode) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    match mode {
+        Xtal32kMode::Crystal => {
+            for gpio in [XTAL32K_P_GPIO, XTAL32K_N_GPIO] {
+                get_io_mux_reg(gpio).modify(|_, w| {
+                    w.fun_wpu()
+                        .clear_bit()
+                        .fun_wpd()
+                        .clear_bit()
+                        .fun_ie()
+                        .clear_bit()
+                });
+            }
+
+            rtc_cntl.ext_xtl_conf.modify(|_, w| unsafe {
+                w.dac_xtal_32k()
+                    .bits(XTAL32K_DAC)
+                    .dres_xtal_32k()
+                    .bits(XTAL32K_DRES)
+                    .dgm_xtal_32k()
+                    .bits(XTAL32K_DGM)
+                    .dbuf_xtal_32k()
+                    .set_bit()
+                    .xtal32k_gpio_sel()
+                    .clear_bit()
+            });
+        }
+        Xtal32kMode::ExternalClock => {
+            get_io_mux_reg(XTAL32K_P_GPIO).modify(|_, w| {
+                w.fun_wpu()
+                    .clear_bit()
+                    .fun_wpd()
+                    .clear_bit()
+                    .fun_ie()
+                    .set_bit()
+            });
+            rtc_cntl
+                .ext_xtl_conf
+                .modify(|_, w| w.xtal32k_gpio_sel().set_bit());
+        }
+    }
+
+    rtc_cntl
+        .ext_xtl_conf
+        .modify(|_, w| w.xtal32k_xpd_force().clear_bit().xpd_xtal_32k().set_bit());
+}
+
+/// Power down the 32 kHz oscillator
+pub(crate) fn xtal32k_disable() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl.ext_xtl_conf.modify(|_, w| {
+        w.xtal32k_xpd_force()
+            .set_bit()
+            .xpd_xtal_32k()
+            .clear_bit()
+            .xtal32k_gpio_sel()
+            .clear_bit()
+    });
+}
+
+/// Keep the RTC fast memory powered in sleep or power it down, there's no
+/// RTC slow memory
+pub(crate) fn set_sleep_memory_power(_slow_mem: bool, fast_mem: bool) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl.dig_pwc.modify(|_, w| {
+        w.fastmem_force_lpu()
+            .bit(fast_mem)
+            .fastmem_force_lpd()
+            .bit(!fast_mem)
+    });
+}
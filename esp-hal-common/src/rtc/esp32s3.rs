@@ -1,9 +1,14 @@
 use crate::{
     clock::XtalClock,
-    pac::RTC_CNTL,
-    rtc_cntl::{RtcCalSel, RtcClock, RtcFastClock, RtcSlowClock},
+    pac::{RTCIO, RTC_CNTL},
+    rtc_cntl::{RtcCalSel, RtcClock, RtcFastClock, RtcSlowClock, Xtal32kMode},
 };
 
+// Current, resistance and gm of the 32 kHz crystal oscillator
+const XTAL32K_DAC: u8 = 3;
+const XTAL32K_DRES: u8 = 3;
+const XTAL32K_DGM: u8 = 3;
+
 pub(crate) fn init() {}
 
 pub(crate) fn configure_clock() {
@@ -28,3 +33,97 @@ pub(crate) fn configure_clock() {
         rtc_cntl.store1.write(|w| w.bits(cal_val));
     }
 }
+
+/// Power up the 32 kHz oscillator on the XTAL_32K_P and XTAL_32K_N pads
+pub(crate) fn xtal32k_enable(mode: Xtal32kMode) {
+    let rtcio = unsafe { &*RTCIO::ptr() };
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    // Connect the pads to the oscillator
+    rtcio.xtal_32p_pad.modify(|_, w| {
+        w.x32p_mux_sel()
+            .set_bit()
+            .x32p_rue()
+            .clear_bit()
+            .x32p_rde()
+            .clear_bit()
+            .x32p_fun_ie()
+            .clear_bit()
+    });
+    rtcio.xtal_32n_pad.modify(|_, w| {
+        w.x32n_mux_sel()
+            .set_bit()
+            .x32n_rue()
+            .clear_bit()
+            .x32n_rde()
+            .clear_bit()
+            .x32n_fun_ie()
+            .clear_bit()
+    });
+
+    match mode {
+        Xtal32kMode::Crystal => {
+            rtc_cntl.ext_xtl_conf.modify(|_, w| unsafe {
+                w.dac_xtal_32k()
+                    .bits(XTAL32K_DAC)
+                    .dres_xtal_32k()
+                    .bits(XTAL32K_DRES)
+                    .dgm_xtal_32k()
+                    .bits(XTAL32K_DGM)
+                    .dbuf_xtal_32k()
+                    .set_bit()
+                    .xtal32k_gpio_sel()
+                    .clear_bit()
+            });
+        }
+        Xtal32kMode::ExternalClock => {
+            rtc_cntl
+                .ext_xtl_conf
+                .modify(|_, w| w.xtal32k_gpio_sel().set_bit());
+        }
+    }
+
+    rtc_cntl
+        .ext_xtl_conf
+        .modify(|_, w| w.xtal32k_xpd_force().clear_bit().xpd_xtal_32k().set_bit());
+}
+
+/// Power down the 32 kHz oscillator
+pub(crate) fn xtal32k_disable() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl.ext_xtl_conf.modify(|_, w| {
+        w.xtal32k_xpd_force()
+            .set_bit()
+            .xpd_xtal_32k()
+            .clear_bit()
+            .xtal32k_gpio_sel()
+            .clear_bit()
+    });
+
+    let rtcio = unsafe { &*RTCIO::ptr() };
+    rtcio
+        .xtal_32p_pad
+        .modify(|_, w| w.x32p_mux_sel().clear_bit());
+    rtcio
+        .xtal_32n_pad
+        .modify(|_, w| w.x32n_mux_sel().clear_bit());
+}
+
+/// Keep the RTC slow and fast memory powered in sleep or power them down
+pub(crate) fn set_sleep_memory_power(slow_mem: bool, fast_mem: bool) {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    rtc_cntl.pwc.modify(|_, w| {
+        w.slowmem_pd_en()
+            .bit(!slow_mem)
+            .slowmem_force_pu()
+            .bit(slow_mem)
+            .slowmem_force_noiso()
+            .bit(slow_mem)
+            .fastmem_pd_en()
+            .bit(!fast_mem)
+            .fastmem_force_pu()
+            .bit(fast_mem)
+            .fastmem_force_noiso()
+            .bit(fast_mem)
+    });
+}
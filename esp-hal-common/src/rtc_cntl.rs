@@ -1,12 +1,29 @@
+#[cfg(any(esp32s2, esp32s3))]
+use core::convert::Infallible;
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
 use embedded_hal::watchdog::{Watchdog, WatchdogDisable, WatchdogEnable};
+#[cfg(not(esp32c2))]
+use fugit::MillisDurationU32;
 use fugit::{HertzU32, MicrosDurationU64};
 
 #[cfg(not(esp32))]
 use crate::efuse::Efuse;
+#[cfg(esp32c3)]
+use crate::gpio::{Gpio0 as Xtal32kP, Gpio1 as Xtal32kN};
+#[cfg(any(esp32s2, esp32s3))]
+use crate::gpio::{Gpio15 as Xtal32kP, Gpio16 as Xtal32kN};
+#[cfg(esp32)]
+use crate::gpio::{Gpio32 as Xtal32kP, Gpio33 as Xtal32kN};
+#[cfg(timg1)]
+use crate::pac::TIMG1;
+#[cfg(ulp)]
+use crate::ulp::Ulp;
 use crate::{
     clock::{Clock, XtalClock},
     pac::{RTC_CNTL, TIMG0},
     rom::esp_rom_delay_us,
+    timer::{Wdt, WdtStatus},
 };
 
 #[cfg_attr(esp32, path = "rtc/esp32.rs")]
@@ -83,14 +100,366 @@ pub(crate) enum RtcCalSel {
     RtcCalInternalOsc = 3,
 }
 
+/// General-purpose retention register
+///
+/// The RTC_CNTL `STOREn` registers keep their value across software resets,
+/// watchdog resets of the digital system and deep sleep. They are only
+/// cleared on power-up, by a brown-out and by an RTC reset.
+///
+/// The layout is the same on all chips, most of the registers are already
+/// taken by the ROM, the bootloader or the HAL and are not available here:
+///
+/// | Register | Use                                                 |
+/// |----------|-----------------------------------------------------|
+/// | STORE1   | RTC_SLOW_CLK calibration value                      |
+/// | STORE3   | deep sleep counter, see [Rtc::sleep_count]          |
+/// | STORE4   | XTAL frequency, written by the bootloader           |
+/// | STORE5   | APB frequency                                       |
+/// | STORE6   | wake stub entry point, jumped to by the ROM on wake |
+/// | STORE7   | wake stub CRC, checked by the ROM on wake           |
+///
+/// ESP-IDF keeps the boot time in STORE2 and STORE3, which is of no concern
+/// to applications built on this HAL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetentionRegister {
+    /// STORE0
+    Store0,
+    /// STORE2
+    Store2,
+}
+
+/// Operating mode of the 32 kHz oscillator circuit, see
+/// [Rtc::enable_external_32k]
+#[cfg(not(esp32c2))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Xtal32kMode {
+    /// A 32.768 kHz crystal between the XTAL_32K_P and XTAL_32K_N pads
+    Crystal,
+    /// A 32.768 kHz clock signal fed into XTAL_32K_P (32K_XN on the ESP32)
+    ExternalClock,
+}
+
+/// Errors of [Rtc::enable_external_32k]
+#[cfg(not(esp32c2))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Xtal32kError {
+    /// No clock was measured within the timeout
+    NotRunning,
+    /// The last measured frequency was off by more than 1%
+    InvalidFrequency(HertzU32),
+}
+
+/// Bit of the RTC timer in the RTC_CNTL wake up enable and cause fields
+#[cfg(esp32s3)]
+const TIMER_WAKEUP: u32 = 1 << 3;
+
+/// Bit of UART0 in the RTC_CNTL wake up enable and cause fields, UART1 is
+/// the next one
+const UART0_WAKEUP: u32 = 1 << 6;
+
+/// Bit of the touch sensor in the RTC_CNTL wake up enable and cause fields
+#[cfg(any(esp32s2, esp32s3))]
+const TOUCH_WAKEUP: u32 = 1 << 8;
+
+/// Bit of the ULP in the RTC_CNTL wake up enable and cause fields
+#[cfg(esp32)]
+const ULP_WAKEUP: u32 = 1 << 9;
+#[cfg(any(esp32s2, esp32s3))]
+const ULP_WAKEUP: u32 = 1 << 11;
+
+/// Wake sources enabled for [Rtc::sleep_light] and `Rtc::sleep_deep`
+static WAKE_SOURCES: AtomicU32 = AtomicU32::new(0);
+
+// State of the 8M clock outputs when a radio driver claimed them, restored
+// when the claim is released
+static RC8M_ENABLED_BEFORE_CLAIM: AtomicBool = AtomicBool::new(false);
+static RC8M_D256_ENABLED_BEFORE_CLAIM: AtomicBool = AtomicBool::new(false);
+
+/// Reason the chip woke up from sleep, see [Rtc::sleep_light] and
+/// [Rtc::wake_reason]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WakeReason {
+    /// UART0 or UART1 saw the number of RX edges set with `enable_wakeup` of
+    /// the serial driver
+    Uart(u8),
+    /// The sleep channel of the touch sensor, with the number of its pad,
+    /// see `Touch::enable_wakeup`
+    #[cfg(any(esp32s2, esp32s3))]
+    Touch(u8),
+    /// The RTC timer, see `Rtc::sleep_deep_for`
+    #[cfg(esp32s3)]
+    Timer,
+    /// The ULP program, see `Ulp::sleep_until_wakeup`
+    #[cfg(ulp)]
+    Ulp,
+    /// Any other source, the raw RTC_CNTL wake up cause
+    Other(u32),
+}
+
+impl WakeReason {
+    fn from_cause(cause: u32) -> Self {
+        #[cfg(any(esp32s2, esp32s3))]
+        if cause & TOUCH_WAKEUP != 0 {
+            return WakeReason::Touch(crate::analog::touch::wakeup_pad());
+        }
+
+        #[cfg(esp32s3)]
+        if cause & TIMER_WAKEUP != 0 {
+            return WakeReason::Timer;
+        }
+
+        #[cfg(ulp)]
+        if cause & ULP_WAKEUP != 0 {
+            return WakeReason::Ulp;
+        }
+
+        if cause & UART0_WAKEUP != 0 {
+            WakeReason::Uart(0)
+        } else if cause & (UART0_WAKEUP << 1) != 0 {
+            WakeReason::Uart(1)
+        } else {
+            WakeReason::Other(cause)
+        }
+    }
+}
+
+/// Enable or disable UART `uart` (0 or 1) as a wake source for
+/// [Rtc::sleep_light]
+pub(crate) fn set_uart_wake_source(uart: u8, enable: bool) {
+    set_wake_source(UART0_WAKEUP << uart, enable);
+}
+
+/// Enable or disable the touch sensor as a wake source for [Rtc::sleep_light]
+/// and `Rtc::sleep_deep`
+#[cfg(any(esp32s2, esp32s3))]
+pub(crate) fn set_touch_wake_source(enable: bool) {
+    set_wake_source(TOUCH_WAKEUP, enable);
+}
+
+fn set_wake_source(source: u32, enable: bool) {
+    if enable {
+        WAKE_SOURCES.fetch_or(source, Ordering::Relaxed);
+    } else {
+        WAKE_SOURCES.fetch_and(!source, Ordering::Relaxed);
+    }
+}
+
+/// Power of a domain while the chip sleeps, see [SleepConfig]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PowerDownOption {
+    /// Up to the sleep mode and the clock configuration, see [SleepConfig]
+    Auto,
+    /// Kept powered
+    On,
+    /// Powered down
+    Off,
+}
+
+/// Errors of an invalid [SleepConfig]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SleepConfigError {
+    /// The ULP runs from the RTC slow memory, which can't be powered down
+    UlpNeedsSlowMemory,
+    /// The ULP is part of the RTC peripherals, which can't be powered down
+    UlpNeedsPeripherals,
+    /// The ULP is clocked by the internal 8M oscillator, which can't be
+    /// powered down
+    UlpNeeds8m,
+    /// RTC_SLOW_CLK is derived from the internal 8M oscillator, which can't
+    /// be powered down
+    SlowClockNeeds8m,
+    /// A memory to be kept on doesn't exist on this chip
+    NotAvailable,
+    /// No wake source for the sleep mode is enabled, the chip would only
+    /// wake up from a reset
+    NoWakeSource,
+    /// The touch sensor is part of the RTC peripherals, which can't be
+    /// powered down
+    TouchNeedsPeripherals,
+    /// The wake stub runs from the RTC fast memory: it has to be placed
+    /// there and the memory can't be powered down, see [crate::reset]
+    #[cfg(esp32s3)]
+    StubNeedsFastMemory,
+}
+
+/// Power domains kept on while the chip sleeps
+///
+/// Used by [Rtc::sleep_light_with_config] and, for deep sleep with the ULP,
+/// `Ulp::sleep_until_wakeup_with_config`.
+///
+/// [PowerDownOption::Auto] keeps the RTC peripherals and the RTC memories
+/// powered, so the memories keep their contents, and leaves the XTAL and
+/// the 8M oscillator to the clock configuration. This is the
+/// [Default](SleepConfig::default).
+///
+/// The ESP32-C3 only has RTC fast memory, the ESP32-C2 has no RTC memory at
+/// all. A memory the chip doesn't have can't be kept on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SleepConfig {
+    /// RTC peripherals, e.g. the ULP, the touch sensor and the RTC GPIOs
+    pub rtc_peripherals: PowerDownOption,
+    /// RTC slow memory, holds the ULP program and its variables
+    pub rtc_slow_mem: PowerDownOption,
+    /// RTC fast memory
+    pub rtc_fast_mem: PowerDownOption,
+    /// Main XTAL
+    pub xtal: PowerDownOption,
+    /// Internal 8M oscillator
+    pub internal_8m: PowerDownOption,
+}
+
+impl Default for SleepConfig {
+    fn default() -> Self {
+        SleepConfig {
+            rtc_peripherals: PowerDownOption::Auto,
+            rtc_slow_mem: PowerDownOption::Auto,
+            rtc_fast_mem: PowerDownOption::Auto,
+            xtal: PowerDownOption::Auto,
+            internal_8m: PowerDownOption::Auto,
+        }
+    }
+}
+
+impl SleepConfig {
+    /// Everything powered down
+    ///
+    /// The contents of the RTC memories are lost. RTC_SLOW_CLK must not be
+    /// derived from the 8M oscillator.
+    pub const fn max_savings() -> Self {
+        SleepConfig {
+            rtc_peripherals: PowerDownOption::Off,
+            rtc_slow_mem: PowerDownOption::Off,
+            rtc_fast_mem: PowerDownOption::Off,
+            xtal: PowerDownOption::Off,
+            internal_8m: PowerDownOption::Off,
+        }
+    }
+
+    /// Only what the ULP needs: the RTC peripherals, the RTC slow memory and
+    /// the 8M oscillator
+    pub const fn ulp_monitoring() -> Self {
+        SleepConfig {
+            rtc_peripherals: PowerDownOption::On,
+            rtc_slow_mem: PowerDownOption::On,
+            rtc_fast_mem: PowerDownOption::Off,
+            xtal: PowerDownOption::Off,
+            internal_8m: PowerDownOption::On,
+        }
+    }
+
+    /// Check the configuration for a sleep with the ULP running or not, with
+    /// RTC_SLOW_CLK derived from the 8M oscillator or not
+    const fn check(&self, ulp: bool, slow_clock_8m: bool) -> Result<(), SleepConfigError> {
+        let slow_mem_off = matches!(self.rtc_slow_mem, PowerDownOption::Off);
+        let peripherals_off = matches!(self.rtc_peripherals, PowerDownOption::Off);
+        let internal_8m_off = matches!(self.internal_8m, PowerDownOption::Off);
+
+        if ulp && slow_mem_off {
+            Err(SleepConfigError::UlpNeedsSlowMemory)
+        } else if ulp && peripherals_off {
+            Err(SleepConfigError::UlpNeedsPeripherals)
+        } else if ulp && internal_8m_off {
+            Err(SleepConfigError::UlpNeeds8m)
+        } else if slow_clock_8m && internal_8m_off {
+            Err(SleepConfigError::SlowClockNeeds8m)
+        } else if cfg!(any(esp32c2, esp32c3)) && matches!(self.rtc_slow_mem, PowerDownOption::On) {
+            Err(SleepConfigError::NotAvailable)
+        } else if cfg!(esp32c2) && matches!(self.rtc_fast_mem, PowerDownOption::On) {
+            Err(SleepConfigError::NotAvailable)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Check the configuration against the current clock configuration
+    pub(crate) fn validate(&self, ulp: bool) -> Result<(), SleepConfigError> {
+        let slow_clock_8m = matches!(RtcClock::get_slow_freq(), RtcSlowClock::RtcSlowClock8mD256);
+        self.check(ulp, slow_clock_8m)
+    }
+
+    /// Program the power domains for the next sleep
+    ///
+    /// The 8M oscillator stays on while a radio driver claims it, see
+    /// [crate::coex].
+    pub(crate) fn apply(&self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        let peripherals = !matches!(self.rtc_peripherals, PowerDownOption::Off);
+        rtc_cntl.pwc.modify(|_, w| {
+            if peripherals {
+                w.pd_en().clear_bit()
+            } else {
+                w.pd_en().set_bit().force_pu().clear_bit()
+            }
+        });
+
+        rtc::set_sleep_memory_power(
+            !matches!(self.rtc_slow_mem, PowerDownOption::Off),
+            !matches!(self.rtc_fast_mem, PowerDownOption::Off),
+        );
+
+        match self.xtal {
+            PowerDownOption::Auto => {}
+            xtal => rtc_cntl
+                .options0
+                .modify(|_, w| w.xtl_force_pu().bit(xtal == PowerDownOption::On)),
+        }
+
+        match self.internal_8m {
+            PowerDownOption::Auto => {}
+            internal_8m => {
+                let claimed = crate::coex::is_claimed(crate::coex::Resource::Rc8m);
+                rtc_cntl.clk_conf.modify(|_, w| {
+                    w.ck8m_force_pu()
+                        .bit(internal_8m == PowerDownOption::On || claimed)
+                });
+            }
+        }
+    }
+}
+
+const _: () = {
+    assert!(matches!(
+        SleepConfig::max_savings().check(false, false),
+        Ok(())
+    ));
+    assert!(matches!(
+        SleepConfig::max_savings().check(true, false),
+        Err(SleepConfigError::UlpNeedsSlowMemory)
+    ));
+    assert!(matches!(
+        SleepConfig::max_savings().check(false, true),
+        Err(SleepConfigError::SlowClockNeeds8m)
+    ));
+    assert!(matches!(
+        SleepConfig::ulp_monitoring().check(true, true),
+        Ok(()) | Err(SleepConfigError::NotAvailable)
+    ));
+    let ulp_without_8m = SleepConfig {
+        internal_8m: PowerDownOption::Off,
+        ..SleepConfig::ulp_monitoring()
+    };
+    assert!(matches!(
+        ulp_without_8m.check(true, false),
+        Err(SleepConfigError::UlpNeeds8m)
+    ));
+};
+
 pub struct Rtc {
     _inner: RTC_CNTL,
     pub rwdt: Rwdt,
     #[cfg(any(esp32c2, esp32c3, esp32s3))]
     pub swd: Swd,
+    #[cfg(ulp)]
+    pub ulp: Ulp,
 }
 
 impl Rtc {
+    /// Initialize the RTC and calibrate RTC_SLOW_CLK
+    ///
+    /// RTC_SLOW_CLK is switched to the internal RC oscillator, the clock the
+    /// ROM armed the RTC watchdog with. The watchdogs are left as they are,
+    /// also their interrupts.
     pub fn new(rtc_cntl: RTC_CNTL) -> Self {
         rtc::init();
         rtc::configure_clock();
@@ -100,14 +469,398 @@ impl Rtc {
             rwdt: Rwdt::default(),
             #[cfg(any(esp32c2, esp32c3, esp32s3))]
             swd: Swd::new(),
+            #[cfg(ulp)]
+            ulp: Ulp::new(),
         }
     }
 
+    /// Estimate the frequency of the main XTAL in MHz
+    #[deprecated(note = "use `Rtc::measure_xtal_frequency`, which returns a `HertzU32`")]
     pub fn estimate_xtal_frequency(&mut self) -> u32 {
-        RtcClock::estimate_xtal_frequency()
+        self.measure_xtal_frequency().to_MHz()
+    }
+
+    /// Measure the frequency of the main XTAL against the internal 8M
+    /// oscillator
+    ///
+    /// The 8M oscillator is only accurate to a few percent, the result is
+    /// rounded down to whole MHz. Enough to tell a 40 MHz XTAL from a 26 MHz
+    /// one.
+    pub fn measure_xtal_frequency(&mut self) -> HertzU32 {
+        HertzU32::MHz(RtcClock::estimate_xtal_frequency())
+    }
+
+    /// Nominal frequency of the current RTC_SLOW_CLK source
+    ///
+    /// The internal RC oscillators vary by several percent between chips and
+    /// with the temperature.
+    pub fn slow_clock_frequency(&self) -> HertzU32 {
+        RtcClock::get_slow_freq().frequency()
+    }
+
+    /// Start the external 32 kHz oscillator and use it as RTC_SLOW_CLK
+    ///
+    /// Takes the two pads of the oscillator, they are connected to it for
+    /// good. A crystal can take several hundred milliseconds to start up,
+    /// this waits up to `timeout` until the measured frequency is within 1%
+    /// of 32.768 kHz and returns the frequency measured over 1024 cycles,
+    /// e.g. to check the accuracy of the crystal.
+    ///
+    /// If the oscillator doesn't start, it's powered down again and the
+    /// internal RC oscillator stays selected.
+    #[cfg(not(esp32c2))]
+    pub fn enable_external_32k<PMODE, NMODE>(
+        &mut self,
+        mode: Xtal32kMode,
+        _xtal_32k_p: Xtal32kP<PMODE>,
+        _xtal_32k_n: Xtal32kN<NMODE>,
+        timeout: MillisDurationU32,
+    ) -> Result<HertzU32, Xtal32kError> {
+        // Number of 32k cycles to check whether the oscillator is running (~1 ms)
+        const CHECK_CYCLES: u32 = 32;
+        // Consecutive valid checks to consider the oscillator stable
+        const STABLE_CHECKS: u32 = 3;
+        const CHECK_INTERVAL_MS: u32 = 10;
+
+        rtc::xtal32k_enable(mode);
+
+        let mut stable = 0;
+        let mut invalid_frequency = HertzU32::Hz(0);
+        for _ in 0..=timeout.to_millis() / CHECK_INTERVAL_MS {
+            let frequency = RtcClock::measure_32k_xtal(CHECK_CYCLES);
+            if RtcClock::is_valid_32k_frequency(frequency) {
+                stable += 1;
+                if stable == STABLE_CHECKS {
+                    break;
+                }
+            } else {
+                stable = 0;
+                invalid_frequency = frequency;
+            }
+
+            unsafe {
+                esp_rom_delay_us(CHECK_INTERVAL_MS * 1000);
+            }
+        }
+
+        let frequency = RtcClock::measure_32k_xtal(1024);
+        if stable == STABLE_CHECKS && !RtcClock::is_valid_32k_frequency(frequency) {
+            invalid_frequency = frequency;
+        }
+
+        if stable < STABLE_CHECKS || !RtcClock::is_valid_32k_frequency(frequency) {
+            rtc::xtal32k_disable();
+
+            return Err(if invalid_frequency.raw() == 0 {
+                Xtal32kError::NotRunning
+            } else {
+                Xtal32kError::InvalidFrequency(invalid_frequency)
+            });
+        }
+
+        RtcClock::set_slow_freq(RtcSlowClock::RtcSlowClock32kXtal);
+        let cal_val = RtcClock::calibrate(RtcCalSel::RtcCal32kXtal, 1024);
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        rtc_cntl.store1.write(|w| unsafe { w.bits(cal_val) });
+
+        Ok(frequency)
+    }
+
+    /// Write `value` to a general-purpose retention register
+    pub fn store(&mut self, register: RetentionRegister, value: u32) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        match register {
+            RetentionRegister::Store0 => rtc_cntl.store0.write(|w| unsafe { w.bits(value) }),
+            RetentionRegister::Store2 => rtc_cntl.store2.write(|w| unsafe { w.bits(value) }),
+        }
+    }
+
+    /// Read a general-purpose retention register
+    ///
+    /// Returns 0 after power-up.
+    pub fn load(&self, register: RetentionRegister) -> u32 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        match register {
+            RetentionRegister::Store0 => rtc_cntl.store0.read().bits(),
+            RetentionRegister::Store2 => rtc_cntl.store2.read().bits(),
+        }
+    }
+
+    /// Number of times the chip went into deep sleep since power-up or the
+    /// last [Rtc::reset_sleep_count]
+    pub fn sleep_count(&self) -> u32 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        rtc_cntl.store3.read().bits()
+    }
+
+    /// Set the deep sleep counter back to 0
+    pub fn reset_sleep_count(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        rtc_cntl.store3.write(|w| unsafe { w.bits(0) });
+    }
+
+    /// Put the chip into light sleep until an enabled wake source fires
+    ///
+    /// The CPU is stopped and the digital system keeps its state, execution
+    /// continues after the wake up with the RAM and the peripheral
+    /// configuration intact. The clocks of the digital peripherals are
+    /// stopped, timers don't count and UARTs don't receive while asleep.
+    ///
+    /// Wake sources are enabled by the drivers, e.g. with `enable_wakeup` of
+    /// the serial driver. Returns `None` right away if none is enabled.
+    ///
+    /// A radio driver registered with [crate::coex::register] is notified
+    /// before and after the sleep.
+    pub fn sleep_light(&mut self) -> Option<WakeReason> {
+        // the default configuration is always valid
+        self.sleep_light_with_config(&SleepConfig::default())
+            .unwrap_or(None)
+    }
+
+    /// Put the chip into light sleep like [Rtc::sleep_light], with the power
+    /// domains of `config`
+    ///
+    /// The XTAL and 8M oscillator settings are restored after the wake up.
+    pub fn sleep_light_with_config(
+        &mut self,
+        config: &SleepConfig,
+    ) -> Result<Option<WakeReason>, SleepConfigError> {
+        config.validate(false)?;
+
+        let sources = WAKE_SOURCES.load(Ordering::Relaxed);
+        if sources == 0 {
+            return Ok(None);
+        }
+
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        crate::coex::notify_sleep(crate::coex::SleepMode::Light);
+
+        let xtal_force_pu = rtc_cntl.options0.read().xtl_force_pu().bit();
+        let ck8m_force_pu = rtc_cntl.clk_conf.read().ck8m_force_pu().bit();
+        config.apply();
+
+        // keep the digital domain powered, it holds the state to resume from
+        rtc_cntl
+            .dig_pwc
+            .modify(|_, w| w.dg_wrap_pd_en().clear_bit());
+
+        rtc_cntl.int_clr.write(|w| {
+            w.slp_wakeup_int_clr()
+                .set_bit()
+                .slp_reject_int_clr()
+                .set_bit()
+        });
+        rtc_cntl
+            .wakeup_state
+            .modify(|_, w| unsafe { w.wakeup_ena().bits(sources) });
+
+        rtc_cntl.state0.modify(|_, w| w.sleep_en().set_bit());
+
+        // the CPU is stalled until the wake up
+        while rtc_cntl.int_raw.read().slp_wakeup_int_raw().bit_is_clear() {}
+        rtc_cntl.int_clr.write(|w| w.slp_wakeup_int_clr().set_bit());
+
+        let cause = wakeup_cause();
+
+        rtc_cntl
+            .options0
+            .modify(|_, w| w.xtl_force_pu().bit(xtal_force_pu));
+        rtc_cntl
+            .clk_conf
+            .modify(|_, w| w.ck8m_force_pu().bit(ck8m_force_pu));
+
+        crate::coex::notify_wake_up();
+
+        Ok(Some(WakeReason::from_cause(cause)))
+    }
+
+    /// Put the chip into deep sleep until an enabled wake source fires
+    ///
+    /// Only the sleep channel of the touch sensor wakes the chip from deep
+    /// sleep, see `Touch::enable_wakeup`; for the ULP see
+    /// `Ulp::sleep_until_wakeup`. The touch sensor scans only its sleep
+    /// channel from here on. Waking up resets the chip, the application
+    /// starts from the beginning and [Rtc::wake_reason] tells what woke it.
+    ///
+    /// A radio driver registered with [crate::coex::register] is notified
+    /// first.
+    ///
+    /// Only returns if no wake source is enabled or `config` powers down
+    /// something the wake sources need.
+    #[cfg(any(esp32s2, esp32s3))]
+    pub fn sleep_deep(&mut self, config: &SleepConfig) -> Result<Infallible, SleepConfigError> {
+        config.validate(false)?;
+
+        // UART wake up needs the digital system, which is powered down
+        let sources = WAKE_SOURCES.load(Ordering::Relaxed) & TOUCH_WAKEUP;
+        if sources == 0 {
+            return Err(SleepConfigError::NoWakeSource);
+        }
+        if matches!(config.rtc_peripherals, PowerDownOption::Off) {
+            return Err(SleepConfigError::TouchNeedsPeripherals);
+        }
+
+        crate::coex::notify_sleep(crate::coex::SleepMode::Deep);
+
+        crate::analog::touch::prepare_deep_sleep();
+        config.apply();
+
+        enter_deep_sleep(sources)
+    }
+
+    /// Put the chip into deep sleep for `period`
+    ///
+    /// The RTC timer wakes the chip after `period`, the sleep channel of the
+    /// touch sensor also does if it is enabled. Waking up resets the chip and
+    /// runs the wake stub registered with
+    /// [crate::reset::set_wake_deep_sleep_stub], or starts the application
+    /// from the beginning, where [Rtc::wake_reason] returns
+    /// [WakeReason::Timer].
+    ///
+    /// A radio driver registered with [crate::coex::register] is notified
+    /// first.
+    ///
+    /// Only returns if `config` powers down something the wake sources or
+    /// the wake stub need.
+    #[cfg(esp32s3)]
+    pub fn sleep_deep_for(
+        &mut self,
+        config: &SleepConfig,
+        period: crate::reset::WakePeriod,
+    ) -> Result<Infallible, SleepConfigError> {
+        config.validate(false)?;
+
+        let touch = WAKE_SOURCES.load(Ordering::Relaxed) & TOUCH_WAKEUP;
+        if touch != 0 && matches!(config.rtc_peripherals, PowerDownOption::Off) {
+            return Err(SleepConfigError::TouchNeedsPeripherals);
+        }
+        if crate::reset::is_stub_set() && matches!(config.rtc_fast_mem, PowerDownOption::Off) {
+            return Err(SleepConfigError::StubNeedsFastMemory);
+        }
+
+        crate::coex::notify_sleep(crate::coex::SleepMode::Deep);
+
+        if touch != 0 {
+            crate::analog::touch::prepare_deep_sleep();
+        }
+        crate::reset::prepare_deep_sleep(period);
+        config.apply();
+
+        enter_deep_sleep(TIMER_WAKEUP | touch)
+    }
+
+    /// Reason of the last wake up from sleep, `None` after power-up
+    ///
+    /// After a deep sleep, this is the source which reset the chip.
+    pub fn wake_reason(&self) -> Option<WakeReason> {
+        match wakeup_cause() {
+            0 => None,
+            cause => Some(WakeReason::from_cause(cause)),
+        }
+    }
+
+    /// Reset the digital system
+    ///
+    /// The RTC domain, including the retention registers and the RTC memory,
+    /// is not reset.
+    pub fn software_reset(&mut self) -> ! {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        rtc_cntl.options0.modify(|_, w| w.sw_sys_rst().set_bit());
+
+        loop {}
     }
 }
 
+/// Count a deep sleep in STORE3, to be called right before entering it
+#[allow(unused)]
+pub(crate) fn increment_sleep_count() {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+    let count = rtc_cntl.store3.read().bits();
+    rtc_cntl
+        .store3
+        .write(|w| unsafe { w.bits(count.wrapping_add(1)) });
+}
+
+/// The raw RTC_CNTL wake up cause of the last sleep
+fn wakeup_cause() -> u32 {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    #[cfg(esp32)]
+    let cause = rtc_cntl.wakeup_state.read().wakeup_cause().bits() as u32;
+    #[cfg(not(esp32))]
+    let cause = rtc_cntl.slp_wakeup_cause.read().wakeup_cause().bits();
+
+    cause
+}
+
+/// Power down the digital system and enter deep sleep until one of the
+/// wake `sources` fires
+///
+/// The power domains of the [SleepConfig] have to be applied already.
+#[cfg(any(esp32, esp32s2, esp32s3))]
+/// Put the chip into deep sleep with the ULP running, until the ULP or the
+/// sleep channel of the touch sensor, if enabled, wakes it
+///
+/// Only returns if `config` powers down something the ULP or the touch
+/// sensor need.
+#[cfg(ulp)]
+pub(crate) fn sleep_deep_with_ulp(config: &SleepConfig) -> Result<Infallible, SleepConfigError> {
+    config.validate(true)?;
+
+    // the ULP keeps the RTC peripherals on, which is all the touch sensor
+    // needs
+    #[cfg(any(esp32s2, esp32s3))]
+    let touch = WAKE_SOURCES.load(Ordering::Relaxed) & TOUCH_WAKEUP;
+    #[cfg(esp32)]
+    let touch = 0;
+
+    crate::coex::notify_sleep(crate::coex::SleepMode::Deep);
+
+    #[cfg(any(esp32s2, esp32s3))]
+    if touch != 0 {
+        crate::analog::touch::prepare_deep_sleep();
+    }
+    config.apply();
+
+    enter_deep_sleep(ULP_WAKEUP | touch)
+}
+
+pub(crate) fn enter_deep_sleep(sources: u32) -> ! {
+    let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+    rtc_cntl.dig_iso.modify(|_, w| {
+        w.dg_pad_force_iso()
+            .clear_bit()
+            .dg_pad_force_noiso()
+            .clear_bit()
+    });
+    rtc_cntl.dig_pwc.modify(|_, w| {
+        w.dg_wrap_pd_en()
+            .set_bit()
+            .dg_wrap_force_pu()
+            .clear_bit()
+            .dg_wrap_force_pd()
+            .clear_bit()
+    });
+    rtc_cntl
+        .options0
+        .modify(|_, w| w.bias_force_nosleep().clear_bit());
+
+    rtc_cntl
+        .wakeup_state
+        .modify(|_, w| unsafe { w.wakeup_ena().bits(sources) });
+
+    increment_sleep_count();
+
+    // `modify`, the ESP32 also has the ULP timer enable in this register
+    rtc_cntl.state0.modify(|_, w| w.sleep_en().set_bit());
+
+    loop {}
+}
+
 /// RTC Watchdog Timer
 pub struct RtcClock;
 
@@ -130,6 +883,11 @@ impl RtcClock {
     fn enable_8m(clk_8m_en: bool, d256_en: bool) {
         let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
 
+        // a radio driver may depend on both outputs
+        let claimed = crate::coex::is_claimed(crate::coex::Resource::Rc8m);
+        let clk_8m_en = clk_8m_en || claimed;
+        let d256_en = d256_en || claimed;
+
         if clk_8m_en {
             rtc_cntl.clk_conf.modify(|_, w| w.enb_ck8m().clear_bit());
             unsafe {
@@ -152,6 +910,64 @@ impl RtcClock {
         }
     }
 
+    /// Keep the 8M clock and its divided output running and powered in sleep
+    /// while a radio driver claims them, see [crate::coex]
+    ///
+    /// Releasing the claim restores the outputs as they were before the
+    /// claim and removes the force power up, unless the slow clock runs from
+    /// the 8M clock.
+    pub(crate) fn keep_8m_alive(enabled: bool) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        // only called by `coex` in a critical section
+        if enabled {
+            let clk_conf = rtc_cntl.clk_conf.read();
+            RC8M_ENABLED_BEFORE_CLAIM.store(clk_conf.enb_ck8m().bit_is_clear(), Ordering::Relaxed);
+            RC8M_D256_ENABLED_BEFORE_CLAIM
+                .store(clk_conf.enb_ck8m_div().bit_is_clear(), Ordering::Relaxed);
+            RtcClock::enable_8m(true, true);
+        } else {
+            RtcClock::enable_8m(
+                RC8M_ENABLED_BEFORE_CLAIM.load(Ordering::Relaxed),
+                RC8M_D256_ENABLED_BEFORE_CLAIM.load(Ordering::Relaxed),
+            );
+        }
+
+        let slow_clock_8md256 =
+            matches!(RtcClock::get_slow_freq(), RtcSlowClock::RtcSlowClock8mD256);
+        rtc_cntl
+            .clk_conf
+            .modify(|_, w| w.ck8m_force_pu().bit(enabled || slow_clock_8md256));
+    }
+
+    /// Enable the 8M clock to run the CPU from it, returns its frequency
+    /// measured against the main XTAL
+    ///
+    /// Its divided output is only enabled during the measurement. The nominal
+    /// frequency is returned if the measurement timed out.
+    pub(crate) fn enable_8m_for_cpu() -> HertzU32 {
+        // Number of 8M/256 clock cycles to measure, about 1.5 ms at 17.5 MHz
+        const RC_FAST_CAL_CYCLES: u32 = 100;
+
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let clk_8md256_enabled = rtc_cntl.clk_conf.read().enb_ck8m_div().bit_is_clear();
+
+        RtcClock::enable_8m(true, true);
+        let xtal_cycles =
+            RtcClock::calibrate_internal(RtcCalSel::RtcCal8mD256, RC_FAST_CAL_CYCLES) as u64;
+        RtcClock::enable_8m(true, clk_8md256_enabled);
+
+        if xtal_cycles == 0 {
+            return RtcFastClock::RtcFastClock8m.frequency();
+        }
+
+        let xtal_freq = RtcClock::get_xtal_freq().hz() as u64;
+        let frequency =
+            (xtal_freq * RC_FAST_CAL_CYCLES as u64 * 256 + xtal_cycles / 2) / xtal_cycles;
+
+        HertzU32::Hz(frequency as u32)
+    }
+
     /// Get main XTAL frequency
     /// This is the value stored in RTC register RTC_XTAL_FREQ_REG by the
     /// bootloader, as passed to rtc_clk_init function.
@@ -179,7 +995,7 @@ impl RtcClock {
             26 => XtalClock::RtcXtalFreq26M,
             #[cfg(esp32)]
             24 => XtalClock::RtcXtalFreq24M,
-            other => XtalClock::RtcXtalFreqOther(other),
+            other => XtalClock::RtcXtalFreqOther(HertzU32::MHz(other)),
         }
     }
 
@@ -215,7 +1031,7 @@ impl RtcClock {
                     .ck8m_force_pu()
                     .bit(match slow_freq {
                         RtcSlowClock::RtcSlowClock8mD256 => true,
-                        _ => false,
+                        _ => crate::coex::is_claimed(crate::coex::Resource::Rc8m),
                     })
             });
 
@@ -409,8 +1225,31 @@ impl RtcClock {
         (period_64 & u32::MAX as u64) as u32
     }
 
+    /// Measure the frequency of the 32k XTAL over `slowclk_cycles` cycles,
+    /// 0 Hz if it isn't running
+    #[cfg(not(esp32c2))]
+    fn measure_32k_xtal(slowclk_cycles: u32) -> HertzU32 {
+        let xtal_cycles = RtcClock::calibrate_internal(RtcCalSel::RtcCal32kXtal, slowclk_cycles);
+        if xtal_cycles == 0 {
+            return HertzU32::Hz(0);
+        }
+
+        let xtal_freq = RtcClock::get_xtal_freq().hz() as u64;
+        let frequency =
+            (xtal_freq * slowclk_cycles as u64 + xtal_cycles as u64 / 2) / xtal_cycles as u64;
+
+        HertzU32::Hz(frequency as u32)
+    }
+
+    /// Check whether a measured frequency is within 1% of the 32k XTAL
+    #[cfg(not(esp32c2))]
+    fn is_valid_32k_frequency(frequency: HertzU32) -> bool {
+        let nominal = RtcSlowClock::RtcSlowClock32kXtal.hz();
+        frequency.raw().abs_diff(nominal) <= nominal / 100
+    }
+
     /// Calculate the necessary RTC_SLOW_CLK cycles to complete 1 millisecond.
-    fn cycles_to_1ms() -> u16 {
+    pub(crate) fn cycles_to_1ms() -> u16 {
         let period_13q19 = RtcClock::calibrate(
             match RtcClock::get_slow_freq() {
                 RtcSlowClock::RtcSlowClockRtc => RtcCalSel::RtcCalRtcMux,
@@ -451,16 +1290,52 @@ impl RtcClock {
 
 /// Behavior of the RWDT stage if it times out
 #[allow(unused)]
-#[derive(Debug, Clone, Copy)]
-enum RwdtStageAction {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RwdtStageAction {
+    /// Stage is disabled
     RwdtStageActionOff         = 0,
+    /// Trigger an interrupt
     RwdtStageActionInterrupt   = 1,
+    /// Reset the CPU core
     RwdtStageActionResetCpu    = 2,
+    /// Reset the main system
     RwdtStageActionResetSystem = 3,
+    /// Reset the main system and the RTC
     RwdtStageActionResetRtc    = 4,
 }
 
+impl RwdtStageAction {
+    /// Decode the raw value of a `WDT_STGn` register field
+    fn from_bits(bits: u8) -> Self {
+        match bits {
+            1 => RwdtStageAction::RwdtStageActionInterrupt,
+            2 => RwdtStageAction::RwdtStageActionResetCpu,
+            3 => RwdtStageAction::RwdtStageActionResetSystem,
+            4 => RwdtStageAction::RwdtStageActionResetRtc,
+            _ => RwdtStageAction::RwdtStageActionOff,
+        }
+    }
+}
+
+/// Snapshot of the RWDT configuration as read back from the hardware
+#[derive(Debug, Clone, Copy)]
+pub struct RwdtStatus {
+    /// Whether the watchdog is armed
+    pub enabled: bool,
+    /// Whether the flash boot protection mode is active
+    pub flashboot_mode: bool,
+    /// Configured action of each of the four stages
+    pub stage_actions: [RwdtStageAction; 4],
+    /// Timeout of stage 0
+    pub timeout: MicrosDurationU64,
+}
+
 /// RTC Watchdog Timer
+///
+/// The ROM arms this watchdog and the one of timer group 0 in flash boot
+/// protection mode before it loads the bootloader. Creating the driver (or
+/// the [Rtc]) doesn't change the state of the watchdog, [WatchdogEnable::start]
+/// keeps the protection mode, [WatchdogDisable::disable] leaves it.
 pub struct Rwdt {
     stg0_action: RwdtStageAction,
     stg1_action: RwdtStageAction,
@@ -550,6 +1425,85 @@ impl Rwdt {
         }
     }
 
+    /// Returns `true` if the watchdog is currently armed, either normally or
+    /// by the flash boot protection
+    pub fn is_enabled(&self) -> bool {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let config = rtc_cntl.wdtconfig0.read();
+
+        config.wdt_en().bit_is_set() || config.wdt_flashboot_mod_en().bit_is_set()
+    }
+
+    /// Returns `true` if the flash boot protection armed by the ROM is still
+    /// active
+    pub fn is_flashboot_protection_enabled(&self) -> bool {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        rtc_cntl
+            .wdtconfig0
+            .read()
+            .wdt_flashboot_mod_en()
+            .bit_is_set()
+    }
+
+    /// Leave the flash boot protection mode
+    ///
+    /// The rest of the configuration is left alone: if the watchdog wasn't
+    /// started with [WatchdogEnable::start] before, it stops. See
+    /// [hand_over_watchdogs] to pass supervision on to the next boot stage.
+    pub fn disable_flashboot_protection(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        self.set_write_protection(false);
+
+        rtc_cntl
+            .wdtconfig0
+            .modify(|_, w| w.wdt_flashboot_mod_en().clear_bit());
+
+        self.set_write_protection(true);
+    }
+
+    /// Read back the action configured for each of the four stages
+    pub fn stage_actions(&self) -> [RwdtStageAction; 4] {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let config = rtc_cntl.wdtconfig0.read();
+
+        [
+            RwdtStageAction::from_bits(config.wdt_stg0().bits()),
+            RwdtStageAction::from_bits(config.wdt_stg1().bits()),
+            RwdtStageAction::from_bits(config.wdt_stg2().bits()),
+            RwdtStageAction::from_bits(config.wdt_stg3().bits()),
+        ]
+    }
+
+    /// Read back the timeout of stage 0
+    ///
+    /// The raw hold value is converted using a fresh calibration of the
+    /// currently selected RTC_SLOW_CLK. The hardware offers no way to read
+    /// the current counter value, so the remaining time until the watchdog
+    /// bites cannot be determined.
+    pub fn timeout(&self) -> MicrosDurationU64 {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let hold = rtc_cntl.wdtconfig1.read().wdt_stg0_hold().bits() as u64;
+
+        #[cfg(esp32)]
+        let timeout_raw = hold;
+        #[cfg(not(esp32))]
+        let timeout_raw = hold << (1 + Efuse::get_rwdt_multiplier());
+
+        MicrosDurationU64::micros(timeout_raw * 1000 / RtcClock::cycles_to_1ms() as u64)
+    }
+
+    /// Read back the complete watchdog configuration
+    pub fn status(&self) -> RwdtStatus {
+        RwdtStatus {
+            enabled: self.is_enabled(),
+            flashboot_mode: self.is_flashboot_protection_enabled(),
+            stage_actions: self.stage_actions(),
+            timeout: self.timeout(),
+        }
+    }
+
     /// Enable/disable write protection for WDT registers
     fn set_write_protection(&mut self, enable: bool) {
         let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
@@ -642,6 +1596,16 @@ impl Swd {
         Self
     }
 
+    /// Returns `true` if the super watchdog is armed
+    ///
+    /// The super watchdog cannot be turned off, it is considered disabled
+    /// while it is being fed automatically by the hardware.
+    pub fn is_enabled(&self) -> bool {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        rtc_cntl.swd_conf.read().swd_auto_feed_en().bit_is_clear()
+    }
+
     /// Enable/disable write protection for WDT registers
     fn set_write_protection(&mut self, enable: bool) {
         let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
@@ -665,3 +1629,66 @@ impl WatchdogDisable for Swd {
         self.set_write_protection(true);
     }
 }
+
+/// Snapshot of the state of every watchdog on the chip
+///
+/// Intended for diagnostics during bring-up, e.g. to find out whether the
+/// bootloader left one of the watchdogs armed. Implements `Debug` so it can
+/// be printed directly.
+#[derive(Debug, Clone, Copy)]
+pub struct WatchdogsStatus {
+    /// RTC watchdog
+    pub rwdt: RwdtStatus,
+    /// Whether the super watchdog is armed
+    #[cfg(any(esp32c2, esp32c3, esp32s3))]
+    pub swd_enabled: bool,
+    /// Watchdog of timer group 0
+    pub timg0_wdt: WdtStatus,
+    /// Watchdog of timer group 1
+    #[cfg(timg1)]
+    pub timg1_wdt: WdtStatus,
+}
+
+/// Pass the supervision of the boot on to the next stage
+///
+/// To be called by a bootloader right before it jumps to the application,
+/// which runs under the RTC watchdog from then on:
+///
+/// 1. the RTC watchdog is started with `timeout`, its stage 0 resets the chip
+///    and the RTC
+/// 2. the RTC watchdog is fed: its counter kept running since the flash boot
+///    protection armed it, without the feed the new timeout would count from
+///    that point and could expire right away
+/// 3. the flash boot protection of the RTC watchdog is disabled, it keeps
+///    running with the timeout of step 1
+/// 4. the watchdog of timer group 0, armed by the flash boot protection of the
+///    ROM, is disabled
+///
+/// No step leaves the chip without an armed watchdog. The application has to
+/// feed or reconfigure the RTC watchdog within `timeout`, e.g. by keeping it
+/// with [WatchdogConfig::Keep](crate::init::WatchdogConfig::Keep).
+pub fn hand_over_watchdogs(
+    rwdt: &mut Rwdt,
+    timg0_wdt: &mut Wdt<TIMG0>,
+    timeout: MicrosDurationU64,
+) {
+    rwdt.start(timeout);
+    rwdt.feed();
+    rwdt.disable_flashboot_protection();
+    timg0_wdt.disable();
+}
+
+/// Read back the state of every watchdog on the chip
+///
+/// This only reads registers and therefore does not require ownership of
+/// any of the watchdog drivers.
+pub fn watchdogs_status() -> WatchdogsStatus {
+    WatchdogsStatus {
+        rwdt: Rwdt::default().status(),
+        #[cfg(any(esp32c2, esp32c3, esp32s3))]
+        swd_enabled: Swd::new().is_enabled(),
+        timg0_wdt: Wdt::<TIMG0>::new().status(),
+        #[cfg(timg1)]
+        timg1_wdt: Wdt::<TIMG1>::new().status(),
+    }
+}
@@ -0,0 +1,526 @@
+//! # SD cards in SPI mode
+//!
+//! [`SdSpi`] drives an SD/SDHC card through an [`Spi`] instance and a GPIO
+//! used as chip select, and implements the [`BlockDevice`] trait of the
+//! `embedded-sdmmc` crate so a FAT file system can be used on top of it.
+//!
+//! The SPI instance must be created without a CS pin (see
+//! [`Spi::new_no_cs`]), as the card needs clocks with CS deasserted during
+//! initialization and CS to stay asserted for a whole command.
+//!
+//! ```no_run
+//! let spi = Spi::new_no_cs(
+//!     peripherals.SPI2,
+//!     sclk,
+//!     mosi,
+//!     miso,
+//!     400u32.kHz(),
+//!     SpiMode::Mode0,
+//!     &mut system.peripheral_clock_control,
+//!     &clocks,
+//! );
+//! let mut sd = SdSpi::new(spi, cs.into_push_pull_output());
+//! sd.init(20u32.MHz(), &clocks).unwrap();
+//! let mut controller = Controller::new(sd, TimeSource);
+//! ```
+
+use core::cell::RefCell;
+
+use embedded_hal::{
+    blocking::spi::{Transfer, Write},
+    digital::v2::OutputPin,
+};
+use embedded_sdmmc::{Block, BlockCount, BlockDevice, BlockIdx};
+use fugit::{HertzU32, RateExtU32};
+
+use crate::{
+    clock::Clocks,
+    spi::{self, Instance, Spi},
+};
+
+// Number of bytes to wait for a response or a data token
+const RESPONSE_RETRIES: usize = 64;
+const TOKEN_RETRIES: usize = 100_000;
+// Number of ACMD41 to send before giving up, the card has up to 1s to get ready
+const INIT_RETRIES: usize = 10_000;
+
+const CMD0: u8 = 0;
+const CMD8: u8 = 8;
+const CMD9: u8 = 9;
+const CMD12: u8 = 12;
+const CMD16: u8 = 16;
+const CMD17: u8 = 17;
+const CMD18: u8 = 18;
+const CMD24: u8 = 24;
+const CMD25: u8 = 25;
+const CMD55: u8 = 55;
+const CMD58: u8 = 58;
+const ACMD41: u8 = 41;
+
+const R1_IDLE: u8 = 0x01;
+const R1_ILLEGAL_COMMAND: u8 = 0x04;
+
+const DATA_START_BLOCK: u8 = 0xfe;
+const WRITE_MULTIPLE_TOKEN: u8 = 0xfc;
+const STOP_TRAN_TOKEN: u8 = 0xfd;
+const DATA_RES_MASK: u8 = 0x1f;
+const DATA_RES_ACCEPTED: u8 = 0x05;
+
+/// SD card errors
+#[derive(Debug, Clone, Copy)]
+pub enum Error {
+    /// The underlying SPI transfer failed
+    Spi(spi::Error),
+    /// Driving the chip select pin failed
+    ChipSelect,
+    /// The card didn't respond in time
+    Timeout,
+    /// The card answered a command with an error
+    Command { command: u8, response: u8 },
+    /// The card is not a supported SD card
+    UnsupportedCard,
+    /// The card answered a read with an error token
+    ReadError(u8),
+    /// The card rejected written data
+    WriteError(u8),
+    /// [`SdSpi::init`] has not been called successfully
+    NotInitialized,
+}
+
+impl From<spi::Error> for Error {
+    fn from(error: spi::Error) -> Self {
+        Error::Spi(error)
+    }
+}
+
+/// Detected card type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CardType {
+    /// SD version 1, byte addressed
+    Sd1,
+    /// SD version 2 standard capacity, byte addressed
+    Sd2,
+    /// SD version 2 high/extended capacity, block addressed
+    Sdhc,
+}
+
+struct Inner<T, CS> {
+    spi: Spi<T>,
+    cs: CS,
+    card_type: Option<CardType>,
+}
+
+/// SD card connected via SPI
+pub struct SdSpi<T, CS> {
+    inner: RefCell<Inner<T, CS>>,
+}
+
+impl<T, CS> SdSpi<T, CS>
+where
+    T: Instance,
+    CS: OutputPin,
+{
+    pub fn new(spi: Spi<T>, cs: CS) -> Self {
+        Self {
+            inner: RefCell::new(Inner {
+                spi,
+                cs,
+                card_type: None,
+            }),
+        }
+    }
+
+    /// Initializes the card.
+    ///
+    /// The card is initialized at 400 kHz as required by the specification,
+    /// afterwards the bus is switched to `frequency` (at most 25 MHz).
+    pub fn init(&mut self, frequency: HertzU32, clocks: &Clocks) -> Result<CardType, Error> {
+        let inner = self.inner.get_mut();
+        inner.card_type = None;
+        inner.spi.change_bus_frequency(400u32.kHz(), clocks);
+
+        let card_type = inner.init()?;
+
+        inner.spi.change_bus_frequency(frequency, clocks);
+        inner.card_type = Some(card_type);
+
+        Ok(card_type)
+    }
+
+    /// The detected card type, `None` if the card is not initialized
+    pub fn card_type(&self) -> Option<CardType> {
+        self.inner.borrow().card_type
+    }
+
+    /// Returns the SPI instance and the chip select pin
+    pub fn free(self) -> (Spi<T>, CS) {
+        let inner = self.inner.into_inner();
+        (inner.spi, inner.cs)
+    }
+}
+
+impl<T, CS> BlockDevice for SdSpi<T, CS>
+where
+    T: Instance,
+    CS: OutputPin,
+{
+    type Error = Error;
+
+    fn read(
+        &self,
+        blocks: &mut [Block],
+        start_block_idx: BlockIdx,
+        _reason: &str,
+    ) -> Result<(), Self::Error> {
+        let mut inner = self.inner.borrow_mut();
+        let address = inner.address(start_block_idx)?;
+
+        inner.with_cs(|inner| {
+            if blocks.len() == 1 {
+                inner.command(CMD17, address)?;
+                inner.read_data(&mut blocks[0].contents)
+            } else {
+                inner.command(CMD18, address)?;
+                for block in blocks.iter_mut() {
+                    inner.read_data(&mut block.contents)?;
+                }
+                inner.command(CMD12, 0)?;
+                inner.wait_not_busy()
+            }
+        })
+    }
+
+    fn write(&self, blocks: &[Block], start_block_idx: BlockIdx) -> Result<(), Self::Error> {
+        let mut inner = self.inner.borrow_mut();
+        let address = inner.address(start_block_idx)?;
+
+        inner.with_cs(|inner| {
+            if blocks.len() == 1 {
+                inner.command(CMD24, address)?;
+                inner.write_data(DATA_START_BLOCK, &blocks[0].contents)
+            } else {
+                inner.command(CMD25, address)?;
+                for block in blocks.iter() {
+                    inner.write_data(WRITE_MULTIPLE_TOKEN, &block.contents)?;
+                }
+                inner.write(&[STOP_TRAN_TOKEN, 0xff])?;
+                inner.wait_not_busy()
+            }
+        })
+    }
+
+    fn num_blocks(&self) -> Result<BlockCount, Self::Error> {
+        let mut inner = self.inner.borrow_mut();
+        if inner.card_type.is_none() {
+            return Err(Error::NotInitialized);
+        }
+
+        let mut csd = [0u8; 16];
+        inner.with_cs(|inner| {
+            inner.command(CMD9, 0)?;
+            inner.read_data(&mut csd)
+        })?;
+
+        let blocks = csd_blocks(&csd).ok_or(Error::UnsupportedCard)?;
+
+        Ok(BlockCount(blocks))
+    }
+}
+
+impl<T, CS> Inner<T, CS>
+where
+    T: Instance,
+    CS: OutputPin,
+{
+    fn init(&mut self) -> Result<CardType, Error> {
+        // at least 74 clocks with CS deasserted to enter native mode
+        self.cs.set_high().map_err(|_| Error::ChipSelect)?;
+        self.write(&[0xff; 10])?;
+
+        self.with_cs(|inner| {
+            // the card enters SPI mode when receiving CMD0 with CS asserted
+            go_idle(|| inner.command(CMD0, 0))?;
+
+            let mut card_type = if inner.command(CMD8, 0x1aa)? & R1_ILLEGAL_COMMAND != 0 {
+                CardType::Sd1
+            } else {
+                let mut r7 = [0xffu8; 4];
+                inner.transfer(&mut r7)?;
+                if r7[3] != 0xaa {
+                    return Err(Error::UnsupportedCard);
+                }
+                CardType::Sd2
+            };
+
+            // request high capacity support for version 2 cards
+            let arg = if card_type == CardType::Sd1 {
+                0
+            } else {
+                0x4000_0000
+            };
+            let mut ready = false;
+            for _ in 0..INIT_RETRIES {
+                if inner.app_command(ACMD41, arg)? == 0 {
+                    ready = true;
+                    break;
+                }
+            }
+            if !ready {
+                return Err(Error::Timeout);
+            }
+
+            if card_type == CardType::Sd2 {
+                inner.check(CMD58, 0)?;
+                let mut ocr = [0xffu8; 4];
+                inner.transfer(&mut ocr)?;
+                // card capacity status
+                if ocr[0] & 0x40 != 0 {
+                    card_type = CardType::Sdhc;
+                }
+            }
+
+            if card_type != CardType::Sdhc {
+                inner.check(CMD16, Block::LEN as u32)?;
+            }
+
+            Ok(card_type)
+        })
+    }
+
+    fn address(&self, block: BlockIdx) -> Result<u32, Error> {
+        match self.card_type {
+            Some(CardType::Sdhc) => Ok(block.0),
+            Some(_) => Ok(block.0 * Block::LEN as u32),
+            None => Err(Error::NotInitialized),
+        }
+    }
+
+    fn with_cs<R>(&mut self, f: impl FnOnce(&mut Self) -> Result<R, Error>) -> Result<R, Error> {
+        self.cs.set_low().map_err(|_| Error::ChipSelect)?;
+        let result = f(self);
+        self.cs.set_high().map_err(|_| Error::ChipSelect)?;
+        // the card releases MISO only with the next clock
+        self.write(&[0xff])?;
+        result
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<(), Error> {
+        Write::write(&mut self.spi, data)?;
+        Ok(())
+    }
+
+    fn transfer(&mut self, data: &mut [u8]) -> Result<(), Error> {
+        Transfer::transfer(&mut self.spi, data)?;
+        Ok(())
+    }
+
+    fn read_byte(&mut self) -> Result<u8, Error> {
+        let mut byte = [0xff];
+        self.transfer(&mut byte)?;
+        Ok(byte[0])
+    }
+
+    // Sends a command and returns its R1 response
+    fn command(&mut self, command: u8, arg: u32) -> Result<u8, Error> {
+        // CMD12 is sent while the card is still sending data
+        if command != CMD0 && command != CMD12 {
+            self.wait_not_busy()?;
+        }
+
+        let arg = arg.to_be_bytes();
+        // the CRC is only checked for CMD0 and CMD8
+        let crc = match command {
+            CMD0 => 0x95,
+            CMD8 => 0x87,
+            _ => 0xff,
+        };
+        self.write(&[0x40 | command, arg[0], arg[1], arg[2], arg[3], crc])?;
+
+        // the response to CMD12 is preceded by a stuff byte
+        if command == CMD12 {
+            self.read_byte()?;
+        }
+
+        for _ in 0..RESPONSE_RETRIES {
+            let response = self.read_byte()?;
+            if response & 0x80 == 0 {
+                return Ok(response);
+            }
+        }
+
+        Err(Error::Timeout)
+    }
+
+    fn app_command(&mut self, command: u8, arg: u32) -> Result<u8, Error> {
+        self.command(CMD55, 0)?;
+        self.command(command, arg)
+    }
+
+    // Sends a command which must succeed
+    fn check(&mut self, command: u8, arg: u32) -> Result<(), Error> {
+        match self.command(command, arg)? {
+            0 => Ok(()),
+            response => Err(Error::Command { command, response }),
+        }
+    }
+
+    fn wait_not_busy(&mut self) -> Result<(), Error> {
+        for _ in 0..TOKEN_RETRIES {
+            if self.read_byte()? == 0xff {
+                return Ok(());
+            }
+        }
+
+        Err(Error::Timeout)
+    }
+
+    fn read_data(&mut self, data: &mut [u8]) -> Result<(), Error> {
+        let mut token = 0xff;
+        for _ in 0..TOKEN_RETRIES {
+            token = self.read_byte()?;
+            if token != 0xff {
+                break;
+            }
+        }
+
+        match token {
+            DATA_START_BLOCK => {}
+            0xff => return Err(Error::Timeout),
+            token => return Err(Error::ReadError(token)),
+        }
+
+        data.fill(0xff);
+        self.transfer(data)?;
+
+        // the CRC is not checked
+        let mut crc = [0xff; 2];
+        self.transfer(&mut crc)
+    }
+
+    fn write_data(&mut self, token: u8, data: &[u8]) -> Result<(), Error> {
+        self.write(&[token])?;
+        self.write(data)?;
+        self.write(&[0xff, 0xff])?;
+
+        let response = self.read_byte()?;
+        if response & DATA_RES_MASK != DATA_RES_ACCEPTED {
+            return Err(Error::WriteError(response));
+        }
+
+        self.wait_not_busy()
+    }
+}
+
+// Sends CMD0 until the card answers with the idle state
+//
+// A card which is still busy with a previous command might not answer at
+// all, so a timeout is retried like any other response.
+fn go_idle(mut command: impl FnMut() -> Result<u8, Error>) -> Result<(), Error> {
+    for _ in 0..RESPONSE_RETRIES {
+        if let Some(result) = idle_result(command()) {
+            return result;
+        }
+    }
+
+    Err(Error::Timeout)
+}
+
+// The result of `go_idle` after the card answered CMD0 with `response`, `None`
+// to send CMD0 again
+const fn idle_result(response: Result<u8, Error>) -> Option<Result<(), Error>> {
+    match response {
+        Ok(R1_IDLE) => Some(Ok(())),
+        Ok(_) | Err(Error::Timeout) => None,
+        Err(error) => Some(Err(error)),
+    }
+}
+
+// Number of 512 byte blocks from the CSD register, `None` for an unknown
+// CSD structure or invalid field values
+const fn csd_blocks(csd: &[u8; 16]) -> Option<u32> {
+    match csd[0] >> 6 {
+        0 => {
+            let c_size =
+                ((csd[6] as u32 & 0x03) << 10) | ((csd[7] as u32) << 2) | ((csd[8] as u32) >> 6);
+            let c_size_mult = ((csd[9] as u32 & 0x03) << 1) | ((csd[10] as u32) >> 7);
+            let read_bl_len = csd[5] as u32 & 0x0f;
+            // capacity = (C_SIZE + 1) * 2^(C_SIZE_MULT + 2) * 2^READ_BL_LEN,
+            // READ_BL_LEN is 9..=11 for valid cards
+            let shift = match (c_size_mult + 2 + read_bl_len).checked_sub(9) {
+                Some(shift) => shift,
+                None => return None,
+            };
+            match 1u32.checked_shl(shift) {
+                Some(multiplier) => (c_size + 1).checked_mul(multiplier),
+                None => None,
+            }
+        }
+        1 => {
+            let c_size = ((csd[7] as u32 & 0x3f) << 16) | ((csd[8] as u32) << 8) | (csd[9] as u32);
+            (c_size + 1).checked_mul(1024)
+        }
+        _ => None,
+    }
+}
+
+const _: () = {
+    const fn csd_v1(c_size: u32, c_size_mult: u32, read_bl_len: u32) -> [u8; 16] {
+        let mut csd = [0u8; 16];
+        csd[5] = read_bl_len as u8;
+        csd[6] = (c_size >> 10) as u8;
+        csd[7] = (c_size >> 2) as u8;
+        csd[8] = (c_size << 6) as u8;
+        csd[9] = (c_size_mult >> 1) as u8;
+        csd[10] = (c_size_mult << 7) as u8;
+        csd
+    }
+
+    const fn csd_v2(c_size: u32) -> [u8; 16] {
+        let mut csd = [0u8; 16];
+        csd[0] = 0x40;
+        csd[7] = (c_size >> 16) as u8;
+        csd[8] = (c_size >> 8) as u8;
+        csd[9] = c_size as u8;
+        csd
+    }
+
+    const fn blocks(csd: [u8; 16], expected: Option<u32>) -> bool {
+        match (csd_blocks(&csd), expected) {
+            (Some(blocks), Some(expected)) => blocks == expected,
+            (None, None) => true,
+            _ => false,
+        }
+    }
+
+    // 2 GB card with 1024 byte blocks
+    assert!(blocks(csd_v1(4095, 7, 10), Some(4096 * 512 * 2)));
+    // 128 MB card with 512 byte blocks
+    assert!(blocks(csd_v1(3839, 4, 9), Some(3840 * 64)));
+    // smallest possible values
+    assert!(blocks(csd_v1(0, 0, 9), Some(4)));
+    // 2^(0 + 2 + 6) bytes is less than one block
+    assert!(blocks(csd_v1(0, 0, 6), None));
+    assert!(blocks(csd_v1(4095, 0, 0), None));
+    assert!(blocks(csd_v1(4095, 7, 15), Some(4096 << 15)));
+
+    // 32 GB card
+    assert!(blocks(csd_v2(60_999), Some(61_000 * 1024)));
+    // the largest C_SIZE doesn't fit into 32 bits of blocks
+    assert!(blocks(csd_v2(0x3f_ffff), None));
+    assert!(blocks(csd_v2(0x3f_fffe), Some(0x3f_ffff * 1024)));
+
+    // unknown CSD structure
+    let mut csd = [0u8; 16];
+    csd[0] = 0x80;
+    assert!(blocks(csd, None));
+
+    // CMD0 is sent again after a timeout or another response than idle
+    assert!(idle_result(Err(Error::Timeout)).is_none());
+    assert!(idle_result(Ok(0xff)).is_none());
+    assert!(idle_result(Ok(0x00)).is_none());
+    assert!(matches!(idle_result(Ok(R1_IDLE)), Some(Ok(()))));
+    assert!(matches!(
+        idle_result(Err(Error::ChipSelect)),
+        Some(Err(Error::ChipSelect))
+    ));
+};
@@ -0,0 +1,573 @@
+//! SDIO slave (ESP32 only)
+//!
+//! The SDIO slave lets an SDIO host, e.g. a Linux SoC or another MCU, talk to
+//! the chip through function 1 of an SDIO card. The pads are fixed IO MUX
+//! functions (function 4), the GPIO matrix can't route them:
+//!
+//! | SDIO signal | GPIO |
+//! |-------------|------|
+//! | CLK         | 14   |
+//! | CMD         | 15   |
+//! | DATA0       | 2    |
+//! | DATA1       | 4    |
+//! | DATA2       | 12   |
+//! | DATA3       | 13   |
+//!
+//! GPIO12 is a strapping pin selecting the flash voltage, the pull-up the
+//! SDIO bus needs on DATA2 selects 1.8 V unless the `XPD_SDIO` eFuses
+//! override the strapping.
+//!
+//! Packets are exchanged with DMA:
+//! - [SdioSlave::send] hands a buffer to the host. The slave counts the bytes
+//!   it sent, the host reads the `PKT_LEN` register and fetches the difference
+//!   to the count it already read.
+//! - [SdioSlave::receive] gives the host one buffer to write to by incrementing
+//!   the `TOKEN1` register. The host writes at most one buffer per token, so it
+//!   has to agree with the slave on the buffer size.
+//!
+//! Both return a transfer, waiting for it returns the buffer and the driver:
+//!
+//! ```no_run
+//! let mut tx_descriptors = [0u32; 3];
+//! let mut rx_descriptors = [0u32; 3];
+//! let mut slave = SdioSlave::new(
+//!     pins,
+//!     Timing::default(),
+//!     &mut tx_descriptors,
+//!     &mut rx_descriptors,
+//!     &mut peripheral_clock_control,
+//! );
+//!
+//! let (buffer, slave) = slave.receive(buffer)?.wait();
+//! let len = slave.received_len();
+//! ```
+//!
+//! Besides the packets, the host and the slave share 52 byte registers, see
+//! [SdioSlave::shared_register], and can raise 8 interrupts at each other.
+
+use core::mem;
+
+use embedded_dma::{ReadBuffer, WriteBuffer};
+
+use crate::{
+    dma::{memory_region, DmaError, DmaTransfer},
+    gpio::{
+        Alternate,
+        DriveStrength,
+        Gpio12,
+        Gpio13,
+        Gpio14,
+        Gpio15,
+        Gpio2,
+        Gpio4,
+        InputPin,
+        OutputPin,
+        AF4,
+    },
+    system::{Peripheral, PeripheralClockControl},
+};
+
+/// Largest buffer of one DMA descriptor
+const CHUNK_SIZE: usize = 4092;
+
+const SLC_BASE: u32 = 0x3ff5_8000;
+const SLCHOST_BASE: u32 = 0x3ff5_5000;
+const HINF_BASE: u32 = 0x3ff4_b000;
+
+const SLC_CONF0: u32 = SLC_BASE;
+const SLC_INT_RAW: u32 = SLC_BASE + 0x04;
+const SLC_INT_ENA: u32 = SLC_BASE + 0x0c;
+const SLC_INT_CLR: u32 = SLC_BASE + 0x10;
+const SLC_0RX_LINK: u32 = SLC_BASE + 0x38;
+const SLC_0TX_LINK: u32 = SLC_BASE + 0x3c;
+const SLC_INTVEC_TOHOST: u32 = SLC_BASE + 0x48;
+const SLC_0TOKEN1: u32 = SLC_BASE + 0x50;
+const SLC_CONF1: u32 = SLC_BASE + 0x5c;
+const SLC_RX_DSCR_CONF: u32 = SLC_BASE + 0x98;
+const SLC_0_LEN_CONF: u32 = SLC_BASE + 0xe4;
+
+const CONF0_TX_RST: u32 = 1 << 0;
+const CONF0_RX_RST: u32 = 1 << 1;
+const CONF0_TX_LOOP_TEST: u32 = 1 << 4;
+const CONF0_RX_LOOP_TEST: u32 = 1 << 5;
+const CONF0_RX_AUTO_WRBACK: u32 = 1 << 6;
+const CONF0_TOKEN_AUTO_CLR: u32 = 1 << 14;
+
+const CONF1_LEN_AUTO_CLR_MASK: u32 = 0b111 << 4;
+
+const RX_DSCR_CONF_TOKEN_NO_REPLACE: u32 = 1 << 0;
+
+const LINK_ADDR_MASK: u32 = 0xf_ffff;
+const LINK_STOP: u32 = 1 << 28;
+const LINK_START: u32 = 1 << 29;
+
+const INT_FRHOST_MASK: u32 = 0xff;
+const INT_TX_DONE: u32 = 1 << 14;
+const INT_RX_EOF: u32 = 1 << 17;
+
+const TOKEN1_INC_MORE: u32 = 1 << 14;
+
+const LEN_CONF_WDATA_MASK: u32 = 0xf_ffff;
+const LEN_CONF_WR: u32 = 1 << 20;
+
+const SLCHOST_CONF_W0: u32 = SLCHOST_BASE + 0x6c;
+const SLCHOST_FUNC1_INT_ENA: u32 = SLCHOST_BASE + 0xdc;
+const SLCHOST_CONF: u32 = SLCHOST_BASE + 0x1f0;
+
+const HOST_INT_TOHOST_MASK: u32 = 0xff;
+const HOST_INT_RX_NEW_PACKET: u32 = 1 << 23;
+
+const HOST_CONF_FRC_SDIO11_SHIFT: u32 = 0;
+const HOST_CONF_FRC_SDIO20_SHIFT: u32 = 5;
+const HOST_CONF_FRC_NEG_SAMP_SHIFT: u32 = 10;
+const HOST_CONF_FRC_POS_SAMP_SHIFT: u32 = 15;
+const HOST_CONF_TIMING_MASK: u32 = 0xf_ffff;
+
+const HINF_CFG_DATA1: u32 = HINF_BASE + 0x04;
+
+const CFG_DATA1_IOREADY1: u32 = 1 << 1;
+const CFG_DATA1_HIGHSPEED_ENABLE: u32 = 1 << 2;
+
+const DESCRIPTOR_OWNER_DMA: u32 = 1 << 31;
+const DESCRIPTOR_EOF: u32 = 1 << 30;
+const DESCRIPTOR_LENGTH_SHIFT: u32 = 12;
+const DESCRIPTOR_SIZE_MASK: u32 = 0xfff;
+
+/// SDIO slave errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    DmaError(DmaError),
+    /// The buffer is larger than the descriptors can hold, or larger than
+    /// 4092 bytes for a receive
+    MaxDmaTransferSizeExceeded,
+}
+
+impl From<DmaError> for Error {
+    fn from(value: DmaError) -> Self {
+        Error::DmaError(value)
+    }
+}
+
+/// The fixed SDIO pads
+pub struct SdioPins {
+    pub clk: Gpio14<Alternate<AF4>>,
+    pub cmd: Gpio15<Alternate<AF4>>,
+    pub data0: Gpio2<Alternate<AF4>>,
+    pub data1: Gpio4<Alternate<AF4>>,
+    pub data2: Gpio12<Alternate<AF4>>,
+    pub data3: Gpio13<Alternate<AF4>>,
+}
+
+/// Clock edges the slave drives its outputs on and samples its inputs on
+///
+/// The host usually samples on the rising edge, driving on the falling edge
+/// gives it more hold time at low clock rates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timing {
+    /// Drive on the rising edge, sample on the rising edge
+    PsendPsample,
+    /// Drive on the rising edge, sample on the falling edge
+    PsendNsample,
+    /// Drive on the falling edge, sample on the rising edge
+    NsendPsample,
+    /// Drive on the falling edge, sample on the falling edge
+    NsendNsample,
+}
+
+impl Default for Timing {
+    fn default() -> Self {
+        Timing::PsendPsample
+    }
+}
+
+impl Timing {
+    /// The `frc_sdio11`, `frc_sdio20`, `frc_neg_samp` and `frc_pos_samp`
+    /// fields of the host `CONF` register
+    const fn bits(self) -> u32 {
+        let (sdio11, sdio20) = match self {
+            Timing::PsendPsample | Timing::PsendNsample => (0, 0x1f),
+            Timing::NsendPsample | Timing::NsendNsample => (0x1f, 0),
+        };
+        let (neg_samp, pos_samp) = match self {
+            Timing::PsendPsample | Timing::NsendPsample => (0, 0x1f),
+            Timing::PsendNsample | Timing::NsendNsample => (0x1f, 0),
+        };
+
+        sdio11 << HOST_CONF_FRC_SDIO11_SHIFT
+            | sdio20 << HOST_CONF_FRC_SDIO20_SHIFT
+            | neg_samp << HOST_CONF_FRC_NEG_SAMP_SHIFT
+            | pos_samp << HOST_CONF_FRC_POS_SAMP_SHIFT
+    }
+}
+
+/// SDIO slave driver
+pub struct SdioSlave<'d> {
+    pins: SdioPins,
+    tx_descriptors: &'d mut [u32],
+    rx_descriptors: &'d mut [u32],
+    sent: u32,
+    received: usize,
+}
+
+impl<'d> SdioSlave<'d> {
+    /// Configure the pads and enable the SDIO slave
+    ///
+    /// `tx_descriptors` are used to send to the host, 3 words per 4092
+    /// bytes of the largest packet, `rx_descriptors` to receive from the
+    /// host, 3 words are enough. The host can enumerate the card once this
+    /// returns.
+    pub fn new(
+        mut pins: SdioPins,
+        timing: Timing,
+        tx_descriptors: &'d mut [u32],
+        rx_descriptors: &'d mut [u32],
+        peripheral_clock_control: &mut PeripheralClockControl,
+    ) -> Self {
+        configure_pad(&mut pins.clk);
+        configure_pad(&mut pins.cmd);
+        configure_pad(&mut pins.data0);
+        configure_pad(&mut pins.data1);
+        configure_pad(&mut pins.data2);
+        configure_pad(&mut pins.data3);
+
+        peripheral_clock_control.enable(Peripheral::SdioSlave);
+
+        write_register(SLC_INT_ENA, 0);
+        write_register(SLC_INT_CLR, u32::MAX);
+
+        modify_register(SLC_CONF0, |r| r | CONF0_TX_RST | CONF0_RX_RST);
+        modify_register(SLC_CONF0, |r| r & !(CONF0_TX_RST | CONF0_RX_RST));
+        modify_register(SLC_CONF0, |r| {
+            (r | CONF0_RX_AUTO_WRBACK)
+                & !(CONF0_TOKEN_AUTO_CLR | CONF0_TX_LOOP_TEST | CONF0_RX_LOOP_TEST)
+        });
+        modify_register(SLC_CONF1, |r| r & !CONF1_LEN_AUTO_CLR_MASK);
+        modify_register(SLC_RX_DSCR_CONF, |r| r | RX_DSCR_CONF_TOKEN_NO_REPLACE);
+
+        modify_register(SLCHOST_CONF, |r| r & !HOST_CONF_TIMING_MASK | timing.bits());
+        write_register(
+            SLCHOST_FUNC1_INT_ENA,
+            HOST_INT_TOHOST_MASK | HOST_INT_RX_NEW_PACKET,
+        );
+
+        // the host starts to use function 1 as soon as it is ready
+        modify_register(HINF_CFG_DATA1, |r| {
+            r | CFG_DATA1_HIGHSPEED_ENABLE | CFG_DATA1_IOREADY1
+        });
+
+        Self {
+            pins,
+            tx_descriptors,
+            rx_descriptors,
+            sent: 0,
+            received: 0,
+        }
+    }
+
+    /// Send `buffer` to the host
+    ///
+    /// The buffer has to be in internal RAM, it is split into descriptors of
+    /// 4092 bytes, the host sees it as a single packet. The transfer finishes
+    /// when the host read all of it.
+    pub fn send<TXBUF>(mut self, buffer: TXBUF) -> Result<SdioSendTransfer<'d, TXBUF>, Error>
+    where
+        TXBUF: ReadBuffer<Word = u8>,
+    {
+        let (ptr, len) = unsafe { buffer.read_buffer() };
+
+        if self.tx_descriptors.len() % 3 != 0 {
+            return Err(Error::DmaError(DmaError::InvalidDescriptorSize));
+        }
+        if len == 0 {
+            return Err(Error::DmaError(DmaError::BufferTooSmall));
+        }
+        if len > self.tx_descriptors.len() / 3 * CHUNK_SIZE {
+            return Err(Error::MaxDmaTransferSizeExceeded);
+        }
+        memory_region(ptr as usize, len).map_err(Error::DmaError)?;
+
+        let descriptors = self.tx_descriptors.as_mut_ptr();
+        let mut offset = 0;
+        let mut descriptor = descriptors;
+        loop {
+            let size = usize::min(CHUNK_SIZE, len - offset);
+            let last = offset + size == len;
+            let (eof, next) = if last {
+                (DESCRIPTOR_EOF, 0)
+            } else {
+                (0, unsafe { descriptor.add(3) as u32 })
+            };
+
+            unsafe {
+                descriptor.write_volatile(
+                    DESCRIPTOR_OWNER_DMA
+                        | eof
+                        | (size as u32) << DESCRIPTOR_LENGTH_SHIFT
+                        | size as u32,
+                );
+                descriptor.add(1).write_volatile(ptr.add(offset) as u32);
+                descriptor.add(2).write_volatile(next);
+            }
+
+            if last {
+                break;
+            }
+            offset += size;
+            descriptor = unsafe { descriptor.add(3) };
+        }
+
+        write_register(SLC_INT_CLR, INT_RX_EOF);
+        // the SLC "RX" link moves data from memory to the host
+        write_register(
+            SLC_0RX_LINK,
+            descriptors as u32 & LINK_ADDR_MASK | LINK_START,
+        );
+
+        // the host reads the total number of bytes sent so far, it wraps at
+        // 20 bits
+        self.sent = self.sent.wrapping_add(len as u32) & LEN_CONF_WDATA_MASK;
+        write_register(SLC_0_LEN_CONF, self.sent | LEN_CONF_WR);
+
+        Ok(SdioSendTransfer {
+            slave: self,
+            buffer,
+        })
+    }
+
+    /// Receive a packet from the host into `buffer`
+    ///
+    /// The buffer has to be in internal RAM, word aligned, a multiple of 4
+    /// bytes long and at most 4092 bytes. Once the transfer is done,
+    /// [SdioSlave::received_len] returns the number of bytes the host wrote.
+    pub fn receive<RXBUF>(
+        mut self,
+        mut buffer: RXBUF,
+    ) -> Result<SdioReceiveTransfer<'d, RXBUF>, Error>
+    where
+        RXBUF: WriteBuffer<Word = u8>,
+    {
+        let (ptr, len) = unsafe { buffer.write_buffer() };
+
+        if self.rx_descriptors.len() < 3 {
+            return Err(Error::DmaError(DmaError::DescriptorsExhausted));
+        }
+        if len > CHUNK_SIZE {
+            return Err(Error::MaxDmaTransferSizeExceeded);
+        }
+        if len % 4 != 0 || ptr as u32 % 4 != 0 {
+            return Err(Error::DmaError(DmaError::Unaligned));
+        }
+        memory_region(ptr as usize, len).map_err(Error::DmaError)?;
+
+        let descriptor = self.rx_descriptors.as_mut_ptr();
+        unsafe {
+            descriptor.write_volatile(DESCRIPTOR_OWNER_DMA | len as u32);
+            descriptor.add(1).write_volatile(ptr as u32);
+            descriptor.add(2).write_volatile(0);
+        }
+
+        write_register(SLC_INT_CLR, INT_TX_DONE);
+        // the SLC "TX" link moves data from the host to memory
+        write_register(
+            SLC_0TX_LINK,
+            descriptor as u32 & LINK_ADDR_MASK | LINK_START,
+        );
+
+        // one more buffer the host may write to
+        write_register(SLC_0TOKEN1, 1 | TOKEN1_INC_MORE);
+
+        Ok(SdioReceiveTransfer {
+            slave: self,
+            buffer,
+        })
+    }
+
+    /// Number of bytes the host wrote in the last finished receive
+    pub fn received_len(&self) -> usize {
+        self.received
+    }
+
+    /// Read a register shared with the host
+    ///
+    /// `position` is 0 to 63 without 28 to 31, which the host uses for its
+    /// interrupt registers.
+    pub fn shared_register(&self, position: usize) -> u8 {
+        let (address, shift) = shared_register_address(position);
+        (read_register(address) >> shift) as u8
+    }
+
+    /// Write a register shared with the host, see
+    /// [SdioSlave::shared_register]
+    pub fn set_shared_register(&mut self, position: usize, value: u8) {
+        let (address, shift) = shared_register_address(position);
+        modify_register(address, |r| r & !(0xff << shift) | (value as u32) << shift);
+    }
+
+    /// Raise the host interrupts of the bits set in `mask`
+    pub fn send_host_interrupt(&mut self, mask: u8) {
+        write_register(SLC_INTVEC_TOHOST, mask as u32);
+    }
+
+    /// The interrupts the host raised since the last call, one bit each
+    pub fn take_host_interrupts(&mut self) -> u8 {
+        let raised = read_register(SLC_INT_RAW) & INT_FRHOST_MASK;
+        write_register(SLC_INT_CLR, raised);
+        raised as u8
+    }
+
+    /// Trigger the `SLC0` interrupt for the host interrupts of the bits set
+    /// in `mask`
+    pub fn listen_host_interrupts(&mut self, mask: u8) {
+        modify_register(SLC_INT_ENA, |r| r | mask as u32);
+    }
+
+    /// Stop triggering the `SLC0` interrupt for the host interrupts of the
+    /// bits set in `mask`
+    pub fn unlisten_host_interrupts(&mut self, mask: u8) {
+        modify_register(SLC_INT_ENA, |r| r & !(mask as u32));
+    }
+
+    /// Release the pads, the host sees the card as not ready
+    pub fn free(self) -> SdioPins {
+        modify_register(HINF_CFG_DATA1, |r| r & !CFG_DATA1_IOREADY1);
+        write_register(SLC_INT_ENA, 0);
+
+        self.pins
+    }
+}
+
+/// A send to the host in progress
+pub struct SdioSendTransfer<'d, BUFFER> {
+    slave: SdioSlave<'d>,
+    buffer: BUFFER,
+}
+
+impl<'d, BUFFER> SdioSendTransfer<'d, BUFFER> {
+    /// Check if the host read the whole packet
+    pub fn is_done(&self) -> bool {
+        read_register(SLC_INT_RAW) & INT_RX_EOF != 0
+    }
+}
+
+impl<'d, BUFFER> DmaTransfer<BUFFER, SdioSlave<'d>> for SdioSendTransfer<'d, BUFFER> {
+    /// Wait until the host read the whole packet and return the buffer and
+    /// the driver
+    fn wait(self) -> (BUFFER, SdioSlave<'d>) {
+        while !self.is_done() {}
+
+        // see `SpiDmaTransfer::wait`, the fields can't be moved out of a
+        // type implementing `Drop`
+        //
+        // NOTE(unsafe) There is no panic branch between getting the resources
+        // and forgetting `self`.
+        unsafe {
+            let buffer = core::ptr::read(&self.buffer);
+            let slave = core::ptr::read(&self.slave);
+            mem::forget(self);
+            (buffer, slave)
+        }
+    }
+}
+
+impl<'d, BUFFER> Drop for SdioSendTransfer<'d, BUFFER> {
+    fn drop(&mut self) {
+        // the host may never read the packet, abort instead of waiting
+        if !self.is_done() {
+            write_register(SLC_0RX_LINK, LINK_STOP);
+        }
+    }
+}
+
+/// A receive from the host in progress
+pub struct SdioReceiveTransfer<'d, BUFFER> {
+    slave: SdioSlave<'d>,
+    buffer: BUFFER,
+}
+
+impl<'d, BUFFER> SdioReceiveTransfer<'d, BUFFER> {
+    /// Check if the host wrote a packet
+    pub fn is_done(&self) -> bool {
+        read_register(SLC_INT_RAW) & INT_TX_DONE != 0
+    }
+}
+
+impl<'d, BUFFER> DmaTransfer<BUFFER, SdioSlave<'d>> for SdioReceiveTransfer<'d, BUFFER> {
+    /// Wait until the host wrote a packet and return the buffer and the
+    /// driver
+    fn wait(mut self) -> (BUFFER, SdioSlave<'d>) {
+        while !self.is_done() {}
+
+        let descriptor = unsafe { self.slave.rx_descriptors.as_ptr().read_volatile() };
+        self.slave.received =
+            (descriptor >> DESCRIPTOR_LENGTH_SHIFT & DESCRIPTOR_SIZE_MASK) as usize;
+
+        // NOTE(unsafe) There is no panic branch between getting the resources
+        // and forgetting `self`.
+        unsafe {
+            let buffer = core::ptr::read(&self.buffer);
+            let slave = core::ptr::read(&self.slave);
+            mem::forget(self);
+            (buffer, slave)
+        }
+    }
+}
+
+impl<'d, BUFFER> Drop for SdioReceiveTransfer<'d, BUFFER> {
+    fn drop(&mut self) {
+        // the host may never write a packet, abort instead of waiting
+        if !self.is_done() {
+            write_register(SLC_0TX_LINK, LINK_STOP);
+        }
+    }
+}
+
+fn configure_pad<P>(pin: &mut P)
+where
+    P: InputPin + OutputPin,
+{
+    // the IO MUX function drives the pad, the GPIO output stays disabled
+    pin.enable_input(true)
+        .internal_pull_up(true)
+        .set_drive_strength(DriveStrength::I40mA)
+        .enable_output(false);
+}
+
+/// Address and bit offset of a shared register
+///
+/// The registers are bytes of the host `CONF_W0` to `CONF_W15` words, which
+/// aren't contiguous.
+const fn shared_register_address(position: usize) -> (u32, u32) {
+    assert!(
+        matches!(position, 0..=27 | 32..=63),
+        "shared registers are 0 to 27 and 32 to 63"
+    );
+
+    let mut offset = position as u32;
+    if position >= 24 {
+        offset += 4;
+    }
+    if position >= 32 {
+        offset += 12;
+    }
+
+    (SLCHOST_CONF_W0 + (offset & !3), (offset % 4) * 8)
+}
+
+const _: () = {
+    assert!(shared_register_address(0).0 == SLCHOST_BASE + 0x6c);
+    assert!(shared_register_address(27).0 == SLCHOST_BASE + 0x88);
+    assert!(shared_register_address(27).1 == 24);
+    assert!(shared_register_address(32).0 == SLCHOST_BASE + 0x9c);
+};
+
+fn read_register(address: u32) -> u32 {
+    unsafe { (address as *const u32).read_volatile() }
+}
+
+fn write_register(address: u32, value: u32) {
+    unsafe { (address as *mut u32).write_volatile(value) }
+}
+
+fn modify_register(address: u32, f: impl FnOnce(u32) -> u32) {
+    write_register(address, f(read_register(address)));
+}
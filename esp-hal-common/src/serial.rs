@@ -1,25 +1,87 @@
 //! UART driver
+//!
+//! ## UART0 and the console
+//!
+//! The ROM bootloader configures UART0 as the console, which is also used by
+//! `esp-println` and `esp-backtrace` for their output. A driver for UART0 which
+//! keeps this configuration can be created with [`Serial::new_console`]. It
+//! doesn't touch the pins or the baud rate, so it can be used alongside
+//! `esp-println`.
+//!
+//! Any UART0 driver records the console configuration when it is created and
+//! restores it, including the console pins, when it is dropped or released
+//! with [`Serial::free`].
+//!
+//! The console output always goes through UART0. To move it to other pins,
+//! route UART0 to them with [`Serial::new_with_config`] and keep the driver
+//! alive; printing and panic messages follow UART0 to the new pins.
+//!
+//! ## Splitting
+//!
+//! [`Serial::split`] divides the driver into a [`Tx`] and an [`Rx`] half which
+//! can be owned independently, e.g. the RX half by an interrupt handler and
+//! the TX half by the main loop. [`Tx`] keeps the configuration of the driver,
+//! [`Serial::join`] puts both halves back together.
+//!
+//! ## Frames
+//!
+//! Protocols like Modbus RTU delimit frames by silence on the line.
+//! [`Serial::read_frame`] returns once the line stayed idle for the time set
+//! with [`Serial::set_rx_idle_bits`] after at least one byte, measured by the
+//! RX timeout of the UART.
+//!
+//! ## Driver modes
+//!
+//! The driver is created in [`Blocking`] mode, see [`crate::mode`].
+//! [`Serial::into_async`] (feature `async`) turns it into an [`Async`]
+//! driver, whose operations are described in the [`asynch`] module. The
+//! configuration methods are available in both modes.
+//!
+//! [`Async`]: crate::mode::Async
+
+#[cfg(feature = "async")]
+pub mod asynch;
+
+use core::marker::PhantomData;
 
 use self::config::Config;
 #[cfg(uart2)]
 use crate::pac::UART2;
 use crate::{
-    clock::Clocks,
+    clock::{ClockListener, Clocks},
+    mode::{Blocking, Mode},
     pac::{
         uart0::{fifo::FIFO_SPEC, RegisterBlock},
+        Interrupt,
         UART0,
         UART1,
     },
+    time::Deadline,
     types::{InputSignal, OutputSignal},
     InputPin,
     OutputPin,
 };
 
 const UART_FIFO_SIZE: u16 = 128;
+/// Address marker of a byte in 9-bit mode
+const NINTH_BIT: u16 = 0x100;
 
 /// Custom serial error type
-#[derive(Debug)]
-pub enum Error {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The deadline of a `*_timeout` operation passed after `transferred`
+    /// bytes
+    Timeout { transferred: usize },
+    /// A frame read with `read_frame` was longer than the buffer, the buffer
+    /// holds its first `transferred` bytes and the rest was dropped
+    FrameTooLong { transferred: usize },
+    /// A 9-bit operation was used without [Serial::set_nine_bit_mode]
+    NineBitModeDisabled,
+    /// A byte with an unexpected parity bit was received while `dropped`
+    /// bytes were in the RX FIFO, their 9th bits can't be told apart and
+    /// all of them were dropped
+    NinthBitLost { dropped: usize },
+}
 
 /// UART configuration
 pub mod config {
@@ -103,6 +165,31 @@ pub mod config {
         }
     }
 
+    /// 9-bit framing of multiprocessor buses, see
+    /// [Serial::set_nine_bit_mode](super::Serial::set_nine_bit_mode)
+    #[derive(PartialEq, Eq, Copy, Clone, Debug, Default)]
+    pub struct NineBitMode {
+        /// Address of this node, if set only address bytes equal to it or
+        /// to `broadcast` and the data following them are received
+        pub address: Option<u8>,
+        /// Address all nodes listen to
+        pub broadcast: Option<u8>,
+    }
+
+    impl NineBitMode {
+        /// Receive only frames sent to `address`
+        pub fn address(mut self, address: u8) -> Self {
+            self.address = Some(address);
+            self
+        }
+
+        /// Receive frames sent to `broadcast` as well
+        pub fn broadcast(mut self, broadcast: u8) -> Self {
+            self.broadcast = Some(broadcast);
+            self
+        }
+    }
+
     /// Configuration for the AT-CMD detection functionality
     pub struct AtCmdConfig {
         pub pre_idle_count: Option<u16>,
@@ -233,8 +320,170 @@ impl embedded_hal_1::serial::Error for Error {
 }
 
 /// UART driver
-pub struct Serial<T> {
+///
+/// `P` are the pins passed to [Serial::new_with_config], they are handed back
+/// by [Serial::release]. `M` is the [driver mode](crate::mode).
+///
+/// Dropping the driver disables its interrupts. The pins passed to
+/// [Serial::new_with_config] are left as floating inputs, UART0 goes back to
+/// the console configuration instead.
+pub struct Serial<T, P = (), M = Blocking>
+where
+    T: Instance,
+{
     uart: T,
+    pins: Option<P>,
+    baudrate: Option<u32>,
+    console: Option<ConsoleState>,
+    nine_bit: Option<NineBitState>,
+    /// Whether an [Rx] half was split off, which owns the RX side
+    split: bool,
+    mode: PhantomData<M>,
+}
+
+/// State of the 9-bit mode, see [Serial::set_nine_bit_mode]
+struct NineBitState {
+    mode: config::NineBitMode,
+    /// Format and RX FIFO full threshold to restore when the mode is disabled
+    parity: config::Parity,
+    data_bits: u8,
+    rx_threshold: u16,
+    /// Whether the last address byte selected this node
+    selected: bool,
+}
+
+impl NineBitState {
+    /// Whether `data` belongs to a frame for this node, follows the address
+    /// bytes
+    fn accept(&mut self, data: u16) -> bool {
+        self.selected = selected_after(&self.mode, self.selected, data);
+        self.selected
+    }
+}
+
+/// Whether a node in `mode` is selected after receiving `data`, `selected`
+/// tells whether it was before
+///
+/// Only address bytes change the selection. A node without an address is
+/// always selected.
+const fn selected_after(mode: &config::NineBitMode, selected: bool, data: u16) -> bool {
+    let address = match mode.address {
+        Some(address) => address,
+        None => return true,
+    };
+    if data & NINTH_BIT == 0 {
+        return selected;
+    }
+
+    let byte = data as u8;
+    byte == address || matches!(mode.broadcast, Some(broadcast) if broadcast == byte)
+}
+
+const _: () = {
+    use config::NineBitMode;
+
+    /// Whether a node in `mode` accepts exactly the `data` marked in
+    /// `accepted`, received in this order
+    const fn accepts(mode: NineBitMode, data: &[u16], accepted: &[bool]) -> bool {
+        let mut selected = false;
+        let mut i = 0;
+        while i < data.len() {
+            selected = selected_after(&mode, selected, data[i]);
+            if selected != accepted[i] {
+                return false;
+            }
+            i += 1;
+        }
+        true
+    }
+
+    let everything = NineBitMode {
+        address: None,
+        broadcast: None,
+    };
+    assert!(accepts(
+        everything,
+        &[0x42, NINTH_BIT | 0x10, 0x43],
+        &[true, true, true]
+    ));
+
+    // data before the first address byte is dropped, an address value without
+    // the 9th bit is data
+    let node = NineBitMode {
+        address: Some(0x10),
+        broadcast: None,
+    };
+    assert!(accepts(
+        node,
+        &[
+            0x01,
+            NINTH_BIT | 0x10,
+            0x02,
+            0x10,
+            NINTH_BIT | 0x11,
+            0x03,
+            0x10
+        ],
+        &[false, true, true, true, false, false, false]
+    ));
+    assert!(accepts(node, &[NINTH_BIT | 0x10, 0x04], &[true, true]));
+
+    let broadcast = NineBitMode {
+        address: Some(0x10),
+        broadcast: Some(0xff),
+    };
+    assert!(accepts(
+        broadcast,
+        &[
+            NINTH_BIT | 0xff,
+            0x01,
+            NINTH_BIT | 0x20,
+            0x02,
+            NINTH_BIT | 0x10,
+            0x03
+        ],
+        &[true, true, false, false, true, true]
+    ));
+
+    // without an address of its own the node receives everything
+    let broadcast_only = NineBitMode {
+        address: None,
+        broadcast: Some(0xff),
+    };
+    assert!(accepts(
+        broadcast_only,
+        &[NINTH_BIT | 0x20, 0x01],
+        &[true, true]
+    ));
+};
+
+/// Configuration of UART0 as set up by the ROM bootloader
+struct ConsoleState {
+    conf0: u32,
+    conf1: u32,
+    clkdiv: u32,
+    #[cfg(any(esp32c2, esp32c3, esp32s3))]
+    clk_conf: u32,
+}
+
+impl ConsoleState {
+    fn save(uart: &RegisterBlock) -> Self {
+        ConsoleState {
+            conf0: uart.conf0.read().bits(),
+            conf1: uart.conf1.read().bits(),
+            clkdiv: uart.clkdiv.read().bits(),
+            #[cfg(any(esp32c2, esp32c3, esp32s3))]
+            clk_conf: uart.clk_conf.read().bits(),
+        }
+    }
+
+    fn restore(&self, uart: &RegisterBlock) {
+        #[cfg(any(esp32c2, esp32c3, esp32s3))]
+        uart.clk_conf.write(|w| unsafe { w.bits(self.clk_conf) });
+        uart.clkdiv.write(|w| unsafe { w.bits(self.clkdiv) });
+        uart.conf1.write(|w| unsafe { w.bits(self.conf1) });
+        uart.conf0.write(|w| unsafe { w.bits(self.conf0) });
+    }
 }
 
 impl<T> Serial<T>
@@ -247,11 +496,22 @@ where
         config: Option<Config>,
         mut pins: Option<P>,
         clocks: &Clocks,
-    ) -> Self
+    ) -> Serial<T, P>
     where
         P: UartPins,
     {
-        let mut serial = Serial { uart };
+        let console = uart
+            .console_pins()
+            .map(|_| ConsoleState::save(uart.register_block()));
+        let mut serial = Serial {
+            uart,
+            pins: None,
+            baudrate: None,
+            console,
+            nine_bit: None,
+            split: false,
+            mode: PhantomData,
+        };
         serial.uart.disable_rx_interrupts();
         serial.uart.disable_tx_interrupts();
 
@@ -269,29 +529,133 @@ where
             serial.change_parity(config.parity);
             serial.change_stop_bits(config.stop_bits);
             serial.change_baud(config.baudrate, clocks);
+            serial.baudrate = Some(config.baudrate);
         });
 
+        serial.pins = pins;
         serial
     }
 
     /// Create a new UART instance with defaults
     pub fn new(uart: T) -> Self {
-        let mut serial = Serial { uart };
+        let console = uart
+            .console_pins()
+            .map(|_| ConsoleState::save(uart.register_block()));
+        let mut serial = Serial {
+            uart,
+            pins: None,
+            baudrate: None,
+            console,
+            nine_bit: None,
+            split: false,
+            mode: PhantomData,
+        };
         serial.uart.disable_rx_interrupts();
         serial.uart.disable_tx_interrupts();
 
         serial
     }
+}
 
+impl<T, P, M> Serial<T, P, M>
+where
+    T: Instance,
+{
     /// Return the raw interface to the underlying UART instance
+    ///
+    /// For UART0 the console configuration is restored first.
     pub fn free(self) -> T {
-        self.uart
+        self.release().0
+    }
+
+    /// Return the raw interface to the underlying UART instance and the pins
+    /// passed to [Serial::new_with_config]
+    ///
+    /// For UART0 the console configuration is restored first. The pins stay
+    /// connected to the UART signals until they are configured otherwise.
+    pub fn release(self) -> (T, Option<P>) {
+        let mut serial = core::mem::ManuallyDrop::new(self);
+        serial.restore_console();
+
+        // NOTE(unsafe) `serial` is never used or dropped afterwards
+        unsafe { (core::ptr::read(&serial.uart), core::ptr::read(&serial.pins)) }
+    }
+
+    /// Split the driver into independently owned TX and RX halves
+    ///
+    /// The [Tx] half keeps the configuration, the console state and the pins
+    /// of the driver. Interrupts are left as they are, so an RX interrupt
+    /// enabled before splitting keeps firing for the owner of the [Rx] half.
+    ///
+    /// Each half cleans up its own side when dropped: the [Tx] half disables
+    /// the TX interrupts and floats the TX and CTS pins, the [Rx] half
+    /// disables the RX interrupts and floats the RX and RTS pins. UART0 only
+    /// goes back to the console configuration when the halves are joined
+    /// again and the driver is dropped, restoring it would disturb the other
+    /// half.
+    pub fn split(mut self) -> (Tx<T, P, M>, Rx<T, M>) {
+        let float_pins = self.pins.is_some() && self.console.is_none();
+        self.split = true;
+
+        let rx = Rx {
+            float_pins: float_pins.then(|| (self.uart.rx_signal(), self.uart.rts_signal())),
+            _uart: PhantomData,
+            mode: PhantomData,
+        };
+
+        (Tx { serial: self }, rx)
+    }
+
+    /// Put the halves returned by [Serial::split] back together
+    pub fn join(tx: Tx<T, P, M>, rx: Rx<T, M>) -> Self {
+        // the driver owns the RX side again
+        core::mem::forget(rx);
+
+        let mut serial = tx.serial;
+        serial.split = false;
+        serial
     }
 
-    /// Writes bytes
-    pub fn write_bytes(&mut self, data: &[u8]) -> Result<(), Error> {
-        data.iter()
-            .try_for_each(|c| nb::block!(self.write_byte(*c)))
+    fn restore_console(&mut self) {
+        if let (Some(console), Some((tx, rx))) = (self.console.take(), self.uart.console_pins()) {
+            nb::block!(self.flush_tx()).ok();
+            console.restore(self.uart.register_block());
+
+            crate::gpio::connect_iomux_output(tx);
+            crate::gpio::connect_iomux_input(rx, self.uart.rx_signal());
+        }
+    }
+
+    /// Move the driver into another mode, the interrupts are left alone
+    fn into_mode<N>(self) -> Serial<T, P, N>
+    where
+        N: Mode,
+    {
+        let serial = core::mem::ManuallyDrop::new(self);
+
+        // NOTE(unsafe) `serial` is never used or dropped afterwards
+        unsafe {
+            Serial {
+                uart: core::ptr::read(&serial.uart),
+                pins: core::ptr::read(&serial.pins),
+                baudrate: serial.baudrate,
+                console: core::ptr::read(&serial.console),
+                nine_bit: core::ptr::read(&serial.nine_bit),
+                split: serial.split,
+                mode: PhantomData,
+            }
+        }
+    }
+
+    /// Set the silence on the RX line which ends a frame for
+    /// [Serial::read_frame], in bit times
+    ///
+    /// The time counts from the end of the last stop bit and scales with the
+    /// baud rate. The hardware counts up to 1023 bit times, on the ESP32 up to
+    /// 127 characters of the configured format, rounded up to whole
+    /// characters; larger values saturate.
+    pub fn set_rx_idle_bits(&mut self, bits: u16) {
+        set_rx_idle_bits(self.uart.register_block(), bits)
     }
 
     /// Configures the AT-CMD detection settings.
@@ -337,6 +701,153 @@ where
             .modify(|_, w| w.sclk_en().set_bit());
     }
 
+    /// Enable or disable the internal loopback
+    ///
+    /// When enabled, the transmitter output is connected to the receiver
+    /// input inside the UART, no pins are involved. This allows for a
+    /// self-test of the peripheral without external wiring.
+    pub fn enable_loopback(&mut self, enable: bool) {
+        self.uart
+            .register_block()
+            .conf0
+            .modify(|_, w| w.loopback().bit(enable));
+    }
+
+    /// Use the parity bit as the 9th data bit, e.g. to mark the address
+    /// bytes of a multiprocessor bus
+    ///
+    /// The UARTs have neither a 9-bit format nor mark and space parity. The
+    /// mode switches to 8 data bits with parity and [Serial::write_9bit]
+    /// picks even or odd parity for each byte, so that the parity bit equals
+    /// its 9th bit. The parity can only change once the TX FIFO was sent,
+    /// bytes needing different parities go out with a gap of about a byte
+    /// time between them.
+    ///
+    /// [Serial::read_9bit] derives the 9th bit of a received byte from its
+    /// parity and the parity error flag. This has limits the driver can't
+    /// work around:
+    /// - The flag isn't kept per byte. Each byte has to be read before the next
+    ///   one is received. The mode sets the RX FIFO full threshold to 1, so the
+    ///   RX FIFO full interrupt fires for every byte; read from its handler.
+    ///   When a byte with the 9th bit set arrives while other bytes are in the
+    ///   FIFO, all of them are dropped, data of this node's frames included,
+    ///   and [Error::NinthBitLost] reports how many.
+    /// - The flag and the FIFO counter aren't updated atomically. A byte
+    ///   arriving while [Serial::read_9bit] runs can lend its 9th bit to the
+    ///   byte read, the same requirement avoids this.
+    /// - Nothing detects transmission errors anymore, a corrupted bit makes
+    ///   data look like an address byte and the other way round.
+    /// - TX and RX share the parity setting, which [Serial::write_9bit]
+    ///   changes. Bytes received before a change and read after it get the
+    ///   wrong 9th bit, read everything before transmitting. The mode is meant
+    ///   for half-duplex buses on which a node doesn't receive while it
+    ///   transmits.
+    ///
+    /// With an `address` in `mode` only address bytes equal to it (or to the
+    /// `broadcast` address) and the data following them are received, the
+    /// frames of other nodes are dropped in software. `None` disables the
+    /// mode and restores the previous format.
+    pub fn set_nine_bit_mode(&mut self, mode: Option<config::NineBitMode>) {
+        nb::block!(self.flush_tx()).ok();
+        let previous = self.nine_bit.take();
+
+        match mode {
+            Some(mode) => {
+                let (parity, data_bits, rx_threshold) = match previous {
+                    Some(state) => (state.parity, state.data_bits, state.rx_threshold),
+                    None => {
+                        let uart = self.uart.register_block();
+                        let conf0 = uart.conf0.read();
+                        let parity = match (conf0.parity_en().bit(), conf0.parity().bit()) {
+                            (false, _) => config::Parity::ParityNone,
+                            (true, false) => config::Parity::ParityEven,
+                            (true, true) => config::Parity::ParityOdd,
+                        };
+                        let rx_threshold = u16::from(uart.conf1.read().rxfifo_full_thrhd().bits());
+                        (parity, conf0.bit_num().bits(), rx_threshold)
+                    }
+                };
+
+                self.change_data_bits(config::DataBits::DataBits8);
+                self.change_parity(config::Parity::ParityEven);
+                self.set_rx_fifo_full_threshold(1);
+                self.uart
+                    .register_block()
+                    .int_clr
+                    .write(|w| w.parity_err_int_clr().set_bit());
+
+                self.nine_bit = Some(NineBitState {
+                    mode,
+                    parity,
+                    data_bits,
+                    rx_threshold,
+                    selected: false,
+                });
+            }
+            None => {
+                if let Some(state) = previous {
+                    self.uart
+                        .register_block()
+                        .conf0
+                        .modify(|_, w| unsafe { w.bit_num().bits(state.data_bits) });
+                    self.change_parity(state.parity);
+                    self.set_rx_fifo_full_threshold(state.rx_threshold);
+                }
+            }
+        }
+    }
+
+    /// Use a single open drain pin for transmitting and receiving
+    ///
+    /// Both TXD and RXD are routed to `pin`, several nodes can share it as a
+    /// half-duplex bus (with the internal pull-up enabled, add a stronger
+    /// external one for long lines or high baud rates). The receiver sees
+    /// every sent byte as well, read the echo back before the next received
+    /// byte is expected.
+    ///
+    /// The UART compares the echo with the sent bits and reports a
+    /// difference, i.e. another node transmitting at the same time, with
+    /// [Serial::collision_detected].
+    pub fn single_wire_mode<P>(&mut self, pin: &mut P)
+    where
+        P: InputPin + OutputPin,
+    {
+        pin.set_to_open_drain_output()
+            .internal_pull_up(true)
+            .enable_input(true)
+            .connect_peripheral_to_output(self.uart.tx_signal())
+            .connect_input_to_peripheral(self.uart.rx_signal());
+
+        self.uart.register_block().rs485_conf.modify(|_, w| {
+            w.rs485_en()
+                .set_bit()
+                .rs485tx_rx_en()
+                .set_bit()
+                .rs485rxby_tx_en()
+                .set_bit()
+        });
+        self.reset_collision_detected();
+    }
+
+    /// Whether a collision on the single-wire bus was detected since the last
+    /// [Serial::reset_collision_detected]
+    pub fn collision_detected(&self) -> bool {
+        self.uart
+            .register_block()
+            .int_raw
+            .read()
+            .rs485_clash_int_raw()
+            .bit_is_set()
+    }
+
+    /// Clear the collision flag
+    pub fn reset_collision_detected(&mut self) {
+        self.uart
+            .register_block()
+            .int_clr
+            .write(|w| w.rs485_clash_int_clr().set_bit());
+    }
+
     /// Configures the RX-FIFO threshold
     pub fn set_rx_fifo_full_threshold(&mut self, threshold: u16) {
         #[cfg(esp32)]
@@ -348,52 +859,96 @@ where
             .modify(|_, w| unsafe { w.rxfifo_full_thrhd().bits(threshold) });
     }
 
-    /// Listen for AT-CMD interrupts
-    pub fn listen_at_cmd(&mut self) {
+    /// Configures the TX-FIFO-EMPTY threshold, the interrupt is raised while
+    /// fewer bytes are in the TX FIFO
+    pub fn set_tx_fifo_empty_threshold(&mut self, threshold: u16) {
+        #[cfg(esp32)]
+        let threshold: u8 = threshold as u8;
+
         self.uart
             .register_block()
-            .int_ena
-            .modify(|_, w| w.at_cmd_char_det_int_ena().set_bit());
+            .conf1
+            .modify(|_, w| unsafe { w.txfifo_empty_thrhd().bits(threshold) });
+    }
+
+    /// Listen for AT-CMD interrupts
+    pub fn listen_at_cmd(&mut self) {
+        critical_section::with(|_| {
+            self.uart
+                .register_block()
+                .int_ena
+                .modify(|_, w| w.at_cmd_char_det_int_ena().set_bit());
+        });
     }
 
     /// Stop listening for AT-CMD interrupts
     pub fn unlisten_at_cmd(&mut self) {
-        self.uart
-            .register_block()
-            .int_ena
-            .modify(|_, w| w.at_cmd_char_det_int_ena().clear_bit());
+        critical_section::with(|_| {
+            self.uart
+                .register_block()
+                .int_ena
+                .modify(|_, w| w.at_cmd_char_det_int_ena().clear_bit());
+        });
     }
 
     /// Listen for TX-DONE interrupts
     pub fn listen_tx_done(&mut self) {
-        self.uart
-            .register_block()
-            .int_ena
-            .modify(|_, w| w.tx_done_int_ena().set_bit());
+        critical_section::with(|_| {
+            self.uart
+                .register_block()
+                .int_ena
+                .modify(|_, w| w.tx_done_int_ena().set_bit());
+        });
     }
 
     /// Stop listening for TX-DONE interrupts
     pub fn unlisten_tx_done(&mut self) {
-        self.uart
-            .register_block()
-            .int_ena
-            .modify(|_, w| w.tx_done_int_ena().clear_bit());
+        critical_section::with(|_| {
+            self.uart
+                .register_block()
+                .int_ena
+                .modify(|_, w| w.tx_done_int_ena().clear_bit());
+        });
+    }
+
+    /// Listen for TX-FIFO-EMPTY interrupts
+    pub fn listen_tx_fifo_empty(&mut self) {
+        critical_section::with(|_| {
+            self.uart
+                .register_block()
+                .int_ena
+                .modify(|_, w| w.txfifo_empty_int_ena().set_bit());
+        });
+    }
+
+    /// Stop listening for TX-FIFO-EMPTY interrupts
+    pub fn unlisten_tx_fifo_empty(&mut self) {
+        critical_section::with(|_| {
+            self.uart
+                .register_block()
+                .int_ena
+                .modify(|_, w| w.txfifo_empty_int_ena().clear_bit());
+        });
     }
 
     /// Listen for RX-FIFO-FULL interrupts
     pub fn listen_rx_fifo_full(&mut self) {
-        self.uart
-            .register_block()
-            .int_ena
-            .modify(|_, w| w.rxfifo_full_int_ena().set_bit());
+        critical_section::with(|_| {
+            self.uart
+                .register_block()
+                .int_ena
+                .modify(|_, w| w.rxfifo_full_int_ena().set_bit());
+        });
     }
 
     /// Stop listening for RX-FIFO-FULL interrupts
     pub fn unlisten_rx_fifo_full(&mut self) {
-        self.uart
-            .register_block()
-            .int_ena
-            .modify(|_, w| w.rxfifo_full_int_ena().clear_bit());
+        critical_section::with(|_| {
+            self.uart
+                .register_block()
+                .int_ena
+                .modify(|_, w| w.rxfifo_full_int_ena().clear_bit());
+        });
     }
 
     /// Checks if AT-CMD interrupt is set
@@ -416,6 +971,16 @@ where
             .bit_is_set()
     }
 
+    /// Checks if TX-FIFO-EMPTY interrupt is set
+    pub fn tx_fifo_empty_interrupt_set(&self) -> bool {
+        self.uart
+            .register_block()
+            .int_raw
+            .read()
+            .txfifo_empty_int_raw()
+            .bit_is_set()
+    }
+
     /// Checks if RX-FIFO-FULL interrupt is set
     pub fn rx_fifo_full_interrupt_set(&self) -> bool {
         self.uart
@@ -442,6 +1007,14 @@ where
             .write(|w| w.tx_done_int_clr().set_bit());
     }
 
+    /// Reset TX-FIFO-EMPTY interrupt
+    pub fn reset_tx_fifo_empty_interrupt(&self) {
+        self.uart
+            .register_block()
+            .int_clr
+            .write(|w| w.txfifo_empty_int_clr().set_bit());
+    }
+
     /// Reset RX-FIFO-FULL interrupt
     pub fn reset_rx_fifo_full_interrupt(&self) {
         self.uart
@@ -451,16 +1024,7 @@ where
     }
 
     fn write_byte(&mut self, word: u8) -> nb::Result<(), Error> {
-        if self.uart.get_tx_fifo_count() < UART_FIFO_SIZE {
-            self.uart
-                .register_block()
-                .fifo
-                .write(|w| unsafe { w.rxfifo_rd_byte().bits(word) });
-
-            Ok(())
-        } else {
-            Err(nb::Error::WouldBlock)
-        }
+        write_byte(self.uart.register_block(), word)
     }
 
     fn flush_tx(&self) -> nb::Result<(), Error> {
@@ -472,24 +1036,7 @@ where
     }
 
     fn read_byte(&mut self) -> nb::Result<u8, Error> {
-        #[allow(unused_variables)]
-        let offset = 0;
-
-        // on ESP32-S2 we need to use PeriBus2 to read the FIFO
-        #[cfg(esp32s2)]
-        let offset = 0x20c00000;
-
-        if self.uart.get_rx_fifo_count() > 0 {
-            let value = unsafe {
-                let fifo = (self.uart.register_block().fifo.as_ptr() as *mut u8).offset(offset)
-                    as *mut crate::pac::generic::Reg<FIFO_SPEC>;
-                (*fifo).read().rxfifo_rd_byte().bits()
-            };
-
-            Ok(value)
-        } else {
-            Err(nb::Error::WouldBlock)
-        }
+        read_byte(self.uart.register_block())
     }
 
     /// Change the number of stop bits
@@ -556,8 +1103,7 @@ where
         // we force the clock source to be APB and don't use the decimal part of the
         // divider
         let clk = clocks.apb_clock.to_Hz();
-        let max_div = 0b1111_1111_1111 - 1;
-        let clk_div = ((clk) + (max_div * baudrate) - 1) / (max_div * baudrate);
+        let clk_div = sclk_divider(clk, baudrate);
 
         self.uart.register_block().clk_conf.write(|w| unsafe {
             w.sclk_sel()
@@ -574,9 +1120,7 @@ where
                 .bit(true)
         });
 
-        let clk = clk / clk_div;
-        let divider = clk / baudrate;
-        let divider = divider as u16;
+        let divider = baud_divider(clk / clk_div, baudrate) as u16;
 
         self.uart
             .register_block()
@@ -594,7 +1138,7 @@ where
             .register_block()
             .conf0
             .modify(|_, w| w.tick_ref_always_on().bit(true));
-        let divider = clk / baudrate;
+        let divider = baud_divider(clk, baudrate);
 
         self.uart
             .register_block()
@@ -603,127 +1147,913 @@ where
     }
 }
 
-/// UART peripheral instance
-pub trait Instance {
-    fn register_block(&self) -> &RegisterBlock;
-
-    fn disable_tx_interrupts(&mut self) {
-        self.register_block().int_clr.write(|w| {
-            w.txfifo_empty_int_clr()
-                .set_bit()
-                .tx_brk_done_int_clr()
-                .set_bit()
-                .tx_brk_idle_done_int_clr()
-                .set_bit()
-                .tx_done_int_clr()
-                .set_bit()
-        });
-
-        self.register_block().int_ena.write(|w| {
-            w.txfifo_empty_int_ena()
-                .clear_bit()
-                .tx_brk_done_int_ena()
-                .clear_bit()
-                .tx_brk_idle_done_int_ena()
-                .clear_bit()
-                .tx_done_int_ena()
-                .clear_bit()
-        });
+impl<T, P> Serial<T, P, Blocking>
+where
+    T: Instance,
+{
+    /// Writes bytes
+    pub fn write_bytes(&mut self, data: &[u8]) -> Result<(), Error> {
+        write_all(self.uart.register_block(), data);
+        Ok(())
     }
 
-    fn disable_rx_interrupts(&mut self) {
-        self.register_block().int_clr.write(|w| {
-            w.rxfifo_full_int_clr()
-                .set_bit()
-                .rxfifo_ovf_int_clr()
-                .set_bit()
-                .rxfifo_tout_int_clr()
-                .set_bit()
-        });
-
-        self.register_block().int_ena.write(|w| {
-            w.rxfifo_full_int_ena()
-                .clear_bit()
-                .rxfifo_ovf_int_ena()
-                .clear_bit()
-                .rxfifo_tout_int_ena()
-                .clear_bit()
-        });
+    /// Read a byte from the RX FIFO without waiting
+    ///
+    /// Returns `WouldBlock` if the FIFO (128 bytes) is empty. Bytes arriving
+    /// while it is full are lost, poll at least every 128 byte times.
+    pub fn read_nb(&mut self) -> nb::Result<u8, Error> {
+        self.read_byte()
     }
 
-    fn get_tx_fifo_count(&mut self) -> u16 {
-        self.register_block()
-            .status
-            .read()
-            .txfifo_cnt()
-            .bits()
-            .into()
+    /// Fill `buffer`, giving up once `deadline` passed
+    ///
+    /// On [Error::Timeout] the first `transferred` bytes of `buffer` hold
+    /// what was received until then.
+    pub fn read_exact_timeout(
+        &mut self,
+        buffer: &mut [u8],
+        deadline: Deadline,
+    ) -> Result<(), Error> {
+        read_exact_timeout(self.uart.register_block(), buffer, deadline)
+    }
+
+    /// Read one frame into `buffer`, delimited by the silence set with
+    /// [Serial::set_rx_idle_bits], and return its length
+    ///
+    /// Waits for the first byte, then until the line stayed idle for the
+    /// configured time. Bytes arriving after that belong to the next frame,
+    /// with Modbus RTU pick a time between the 1.5 characters allowed within
+    /// and the 3.5 characters required between frames. A
+    /// frame longer than `buffer` is received until its end, the excess is
+    /// dropped and [Error::FrameTooLong] is returned, so the next call starts
+    /// at the next frame.
+    pub fn read_frame(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        read_frame(self.uart.register_block(), buffer)
+    }
+
+    /// Write `data` into the TX FIFO, giving up once `deadline` passed
+    ///
+    /// Returns once the last byte is in the FIFO, use [Serial::flush_nb] to
+    /// wait until it was sent. On [Error::Timeout] the first `transferred`
+    /// bytes of `data` were written.
+    pub fn write_all_timeout(&mut self, data: &[u8], deadline: Deadline) -> Result<(), Error> {
+        write_all_timeout(self.uart.register_block(), data, deadline)
+    }
+
+    /// Put a byte into the TX FIFO without waiting
+    ///
+    /// Returns `WouldBlock` if all 128 entries of the FIFO are in use.
+    pub fn write_nb(&mut self, word: u8) -> nb::Result<(), Error> {
+        self.write_byte(word)
     }
 
-    fn get_rx_fifo_count(&mut self) -> u16 {
-        self.register_block()
-            .status
-            .read()
-            .rxfifo_cnt()
-            .bits()
-            .into()
+    /// Check if all bytes of the TX FIFO were sent, without waiting
+    pub fn flush_nb(&mut self) -> nb::Result<(), Error> {
+        self.flush_tx()
     }
 
-    fn is_tx_idle(&self) -> bool {
-        #[cfg(esp32)]
-        let idle = self.register_block().status.read().st_utx_out().bits() == 0x0u8;
-        #[cfg(not(esp32))]
-        let idle = self.register_block().fsm_status.read().st_utx_out().bits() == 0x0u8;
+    /// Write a byte with a 9th bit, e.g. `0x100 | address` for an address
+    /// byte
+    ///
+    /// Needs [Serial::set_nine_bit_mode], bits above the 9th are ignored.
+    /// Returns once the byte is in the TX FIFO.
+    pub fn write_9bit(&mut self, data: u16) -> Result<(), Error> {
+        if self.nine_bit.is_none() {
+            return Err(Error::NineBitModeDisabled);
+        }
 
-        idle
-    }
+        let word = data as u8;
+        let odd = tx_parity_odd(data);
 
-    fn is_rx_idle(&self) -> bool {
-        #[cfg(esp32)]
-        let idle = self.register_block().status.read().st_urx_out().bits() == 0x0u8;
-        #[cfg(not(esp32))]
-        let idle = self.register_block().fsm_status.read().st_urx_out().bits() == 0x0u8;
+        let uart = self.uart.register_block();
+        if uart.conf0.read().parity().bit() != odd {
+            // the setting applies to the bytes still in the FIFO as well
+            while uart.status.read().txfifo_cnt().bits() > 0 {}
+            nb::block!(self.flush_tx())?;
+            uart.conf0.modify(|_, w| w.parity().bit(odd));
+        }
 
-        idle
-    }
+        nb::block!(write_byte(uart, word))
+    }
+
+    /// Read a byte and its 9th bit from the RX FIFO without waiting
+    ///
+    /// Needs [Serial::set_nine_bit_mode], address bytes have bit 8 set.
+    /// Returns `WouldBlock` if the FIFO is empty or only held bytes of frames
+    /// for other nodes. After [Error::NinthBitLost] data is dropped until the
+    /// next address byte for this node.
+    pub fn read_9bit(&mut self) -> nb::Result<u16, Error> {
+        let uart = self.uart.register_block();
+        let state = self
+            .nine_bit
+            .as_mut()
+            .ok_or(nb::Error::Other(Error::NineBitModeDisabled))?;
+
+        loop {
+            let data = match read_9bit(uart) {
+                Ok(data) => data,
+                Err(nb::Error::Other(error)) => {
+                    state.selected = false;
+                    return Err(nb::Error::Other(error));
+                }
+                Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+            };
 
-    fn tx_signal(&self) -> OutputSignal;
+            if state.accept(data) {
+                return Ok(data);
+            }
+        }
+    }
+}
 
-    fn rx_signal(&self) -> InputSignal;
+impl Serial<UART0> {
+    /// Create a driver for UART0 which adopts the console configuration left
+    /// behind by the ROM bootloader
+    ///
+    /// Neither the pins nor the baud rate are changed, so output of
+    /// `esp-println` isn't disturbed. The baud rate is kept when the clocks
+    /// change.
+    pub fn new_console(uart: UART0, clocks: &Clocks) -> Self {
+        let mut serial = Self::new(uart);
+        serial.baudrate = Some(serial.current_baud(clocks));
 
-    fn cts_signal(&self) -> InputSignal;
+        serial
+    }
 
-    fn rts_signal(&self) -> OutputSignal;
-}
+    #[cfg(any(esp32, esp32s2))]
+    fn current_baud(&self, clocks: &Clocks) -> u32 {
+        let uart = self.uart.register_block();
 
-impl Instance for UART0 {
-    #[inline(always)]
-    fn register_block(&self) -> &RegisterBlock {
-        self
-    }
+        let clk = if uart.conf0.read().tick_ref_always_on().bit_is_set() {
+            clocks.apb_clock.to_Hz()
+        } else {
+            1_000_000 // REF_TICK
+        };
 
-    fn tx_signal(&self) -> OutputSignal {
-        OutputSignal::U0TXD
+        baud_from_divider(clk, uart.clkdiv.read().bits())
     }
 
-    fn rx_signal(&self) -> InputSignal {
-        InputSignal::U0RXD
-    }
+    #[cfg(any(esp32c2, esp32c3, esp32s3))]
+    fn current_baud(&self, clocks: &Clocks) -> u32 {
+        let uart = self.uart.register_block();
 
-    fn cts_signal(&self) -> InputSignal {
-        InputSignal::U0CTS
-    }
+        let clk_conf = uart.clk_conf.read();
+        let clk = match clk_conf.sclk_sel().bits() {
+            1 => clocks.apb_clock.to_Hz(),
+            2 => 17_500_000, // RC_FAST, approximately
+            _ => clocks.xtal_clock.to_Hz(),
+        } / (clk_conf.sclk_div_num().bits() as u32 + 1);
 
-    fn rts_signal(&self) -> OutputSignal {
-        OutputSignal::U0RTS
+        baud_from_divider(clk, uart.clkdiv.read().bits())
     }
 }
 
-impl Instance for UART1 {
-    #[inline(always)]
-    fn register_block(&self) -> &RegisterBlock {
-        self
-    }
+/// Fewest RX edges the wake up logic can count
+#[cfg(esp32)]
+const MIN_WAKEUP_EDGES: u8 = 2;
+#[cfg(not(esp32))]
+const MIN_WAKEUP_EDGES: u8 = 3;
+
+macro_rules! impl_wakeup {
+    ($uart:ident, $number:literal) => {
+        impl<P, M> Serial<$uart, P, M> {
+            /// Wake the chip up from [light sleep] after `edges` edges on the
+            /// RX line
+            ///
+            /// `edges` is at least 3 (2 on the ESP32), smaller values are
+            /// raised to it. Every character has at least two edges, the
+            /// falling edge of the start bit and the rising edge before the
+            /// stop bit. [light sleep] reports `WakeReason::Uart` afterwards.
+            ///
+            /// The UART is clocked from the APB clock, which is stopped in
+            /// light sleep on all chips: the characters which made up the
+            /// edges are lost, only the bytes arriving after the wake up are
+            /// received. Protocols which wake a device this way usually send
+            /// a dummy character first.
+            ///
+            /// [light sleep]: crate::Rtc::sleep_light
+            pub fn enable_wakeup(&mut self, edges: u8) {
+                let threshold = edges.max(MIN_WAKEUP_EDGES) - MIN_WAKEUP_EDGES;
+                self.uart
+                    .register_block()
+                    .sleep_conf
+                    .write(|w| unsafe { w.active_threshold().bits(threshold as u16) });
+
+                crate::rtc_cntl::set_uart_wake_source($number, true);
+            }
+
+            /// Stop waking the chip up from light sleep
+            pub fn disable_wakeup(&mut self) {
+                crate::rtc_cntl::set_uart_wake_source($number, false);
+            }
+        }
+    };
+}
+
+impl_wakeup!(UART0, 0);
+impl_wakeup!(UART1, 1);
+
+/// Divider of the UART core clock, so that the baud rate divider fits into
+/// its 12 bits
+#[cfg(any(esp32c2, esp32c3, esp32s3))]
+const fn sclk_divider(clk: u32, baudrate: u32) -> u32 {
+    let max_div = 0b1111_1111_1111 - 1;
+    (clk + (max_div * baudrate) - 1) / (max_div * baudrate)
+}
+
+/// Integral baud rate divider, the fractional part isn't used
+const fn baud_divider(clk: u32, baudrate: u32) -> u32 {
+    clk / baudrate
+}
+
+// CLKDIV holds the integral part of the divider in bits 0..20 and sixteenths
+// in bits 20..24
+const fn baud_from_divider(clk: u32, clkdiv: u32) -> u32 {
+    let divider = (clkdiv & 0xf_ffff) as u64 * 16 + ((clkdiv >> 20) & 0xf) as u64;
+    if divider == 0 {
+        return 0;
+    }
+
+    (clk as u64 * 16 / divider) as u32
+}
+
+const _: () = {
+    assert!(baud_divider(80_000_000, 115_200) == 694);
+    assert!(baud_from_divider(80_000_000, 694) == 115_273);
+    // 694 and 7 sixteenths
+    assert!(baud_from_divider(80_000_000, 7 << 20 | 694) == 115_201);
+    assert!(baud_from_divider(80_000_000, 0) == 0);
+};
+
+#[cfg(any(esp32c2, esp32c3, esp32s3))]
+const _: () = {
+    assert!(sclk_divider(80_000_000, 115_200) == 1);
+    assert!(sclk_divider(80_000_000, 9600) == 3);
+    assert!(baud_divider(80_000_000 / 3, 9600) == 2777);
+};
+
+impl<T, P, M> Drop for Serial<T, P, M>
+where
+    T: Instance,
+{
+    fn drop(&mut self) {
+        self.uart.disable_tx_interrupts();
+
+        if self.split {
+            // only the TX side, the `Rx` half still uses the RX side
+            if self.pins.is_some() && self.console.is_none() {
+                nb::block!(self.flush_tx()).ok();
+
+                crate::gpio::find_output_pad(self.uart.tx_signal())
+                    .into_iter()
+                    .chain(crate::gpio::find_input_pad(self.uart.cts_signal()))
+                    .for_each(crate::gpio::pad::float_pad);
+            }
+            return;
+        }
+
+        self.uart.disable_rx_interrupts();
+
+        if self.console.is_some() {
+            self.restore_console();
+        } else if self.pins.is_some() {
+            // the pins are connected via the GPIO matrix, see `UartPins`
+            nb::block!(self.flush_tx()).ok();
+
+            let outputs = [self.uart.tx_signal(), self.uart.rts_signal()];
+            let inputs = [self.uart.rx_signal(), self.uart.cts_signal()];
+            outputs
+                .into_iter()
+                .filter_map(crate::gpio::find_output_pad)
+                .chain(inputs.into_iter().filter_map(crate::gpio::find_input_pad))
+                .for_each(crate::gpio::pad::float_pad);
+        }
+    }
+}
+
+fn write_byte(uart: &RegisterBlock, word: u8) -> nb::Result<(), Error> {
+    if tx_fifo_free(uart) > 0 {
+        uart.fifo
+            .write(|w| unsafe { w.rxfifo_rd_byte().bits(word) });
+
+        Ok(())
+    } else {
+        Err(nb::Error::WouldBlock)
+    }
+}
+
+/// Write `data`, filling the free space of the TX FIFO at once instead of
+/// checking it before every byte
+fn write_all(uart: &RegisterBlock, mut data: &[u8]) {
+    while !data.is_empty() {
+        let free = (tx_fifo_free(uart) as usize).min(data.len());
+        let (chunk, rest) = data.split_at(free);
+
+        for &word in chunk {
+            uart.fifo
+                .write(|w| unsafe { w.rxfifo_rd_byte().bits(word) });
+        }
+
+        data = rest;
+    }
+}
+
+fn write_all_timeout(uart: &RegisterBlock, data: &[u8], deadline: Deadline) -> Result<(), Error> {
+    let mut transferred = 0;
+
+    while transferred < data.len() {
+        let free = (tx_fifo_free(uart) as usize).min(data.len() - transferred);
+
+        if free == 0 && deadline.is_expired() {
+            return Err(Error::Timeout { transferred });
+        }
+
+        for &word in &data[transferred..][..free] {
+            uart.fifo
+                .write(|w| unsafe { w.rxfifo_rd_byte().bits(word) });
+        }
+
+        transferred += free;
+    }
+
+    Ok(())
+}
+
+fn tx_fifo_free(uart: &RegisterBlock) -> u16 {
+    UART_FIFO_SIZE - uart.status.read().txfifo_cnt().bits() as u16
+}
+
+fn read_byte(uart: &RegisterBlock) -> nb::Result<u8, Error> {
+    #[allow(unused_variables)]
+    let offset = 0;
+
+    // on ESP32-S2 we need to use PeriBus2 to read the FIFO
+    #[cfg(esp32s2)]
+    let offset = 0x20c00000;
+
+    if uart.status.read().rxfifo_cnt().bits() > 0 {
+        let value = unsafe {
+            let fifo = (uart.fifo.as_ptr() as *mut u8).offset(offset)
+                as *mut crate::pac::generic::Reg<FIFO_SPEC>;
+            (*fifo).read().rxfifo_rd_byte().bits()
+        };
+
+        Ok(value)
+    } else {
+        Err(nb::Error::WouldBlock)
+    }
+}
+
+/// Read a byte, its 9th bit is its parity bit: the expected one, or the
+/// other one if the parity error flag is set
+fn read_9bit(uart: &RegisterBlock) -> nb::Result<u16, Error> {
+    // the flag has to belong to the counted bytes
+    let (count, flagged) = loop {
+        let count = uart.status.read().rxfifo_cnt().bits() as usize;
+        let flagged = uart.int_raw.read().parity_err_int_raw().bit_is_set();
+        if uart.status.read().rxfifo_cnt().bits() as usize == count {
+            break (count, flagged);
+        }
+    };
+
+    if count == 0 {
+        return Err(nb::Error::WouldBlock);
+    }
+
+    if flagged {
+        uart.int_clr.write(|w| w.parity_err_int_clr().set_bit());
+
+        if count > 1 {
+            for _ in 0..count {
+                read_byte(uart).ok();
+            }
+            return Err(nb::Error::Other(Error::NinthBitLost { dropped: count }));
+        }
+    }
+
+    let word = read_byte(uart)?;
+    let odd = uart.conf0.read().parity().bit();
+    let ninth = ninth_bit(word, odd, flagged);
+
+    Ok(word as u16 | if ninth { NINTH_BIT } else { 0 })
+}
+
+/// Parity setting under which the parity bit of the low byte of `data`
+/// equals its 9th bit: odd parity where even parity would give the wrong bit
+const fn tx_parity_odd(data: u16) -> bool {
+    parity_bit(data as u8, false) != (data & NINTH_BIT != 0)
+}
+
+/// 9th bit of a received `word`: the parity bit expected with the `odd`
+/// setting, or the other one if the parity error was `flagged`
+const fn ninth_bit(word: u8, odd: bool, flagged: bool) -> bool {
+    parity_bit(word, odd) != flagged
+}
+
+/// Parity bit of `word` with even or odd parity
+const fn parity_bit(word: u8, odd: bool) -> bool {
+    (word.count_ones() % 2 == 1) != odd
+}
+
+const _: () = {
+    assert!(!parity_bit(0x00, false));
+    assert!(parity_bit(0x00, true));
+    assert!(parity_bit(0x01, false));
+    assert!(!parity_bit(0x01, true));
+    assert!(!parity_bit(0xff, false));
+    assert!(parity_bit(0x7f, false));
+
+    // The 9th bit survives the line with either parity setting of the
+    // receiver
+    let mut data = 0;
+    while data < 0x200 {
+        let word = data as u8;
+        let sent = parity_bit(word, tx_parity_odd(data));
+        assert!(sent == (data & NINTH_BIT != 0));
+        assert!(ninth_bit(word, false, sent != parity_bit(word, false)) == sent);
+        assert!(ninth_bit(word, true, sent != parity_bit(word, true)) == sent);
+        data += 1;
+    }
+};
+
+fn read_exact_timeout(
+    uart: &RegisterBlock,
+    buffer: &mut [u8],
+    deadline: Deadline,
+) -> Result<(), Error> {
+    let mut transferred = 0;
+
+    while transferred < buffer.len() {
+        match read_byte(uart) {
+            Ok(byte) => {
+                buffer[transferred] = byte;
+                transferred += 1;
+            }
+            Err(nb::Error::WouldBlock) if deadline.is_expired() => {
+                return Err(Error::Timeout { transferred });
+            }
+            Err(nb::Error::WouldBlock) => {}
+            Err(nb::Error::Other(error)) => return Err(error),
+        }
+    }
+
+    Ok(())
+}
+
+fn set_rx_idle_bits(uart: &RegisterBlock, bits: u16) {
+    #[cfg(esp32)]
+    {
+        let conf0 = uart.conf0.read();
+        let data_bits = conf0.bit_num().bits() as u16 + 5;
+        let parity_bits = conf0.parity_en().bit() as u16;
+        let stop_bits =
+            if conf0.stop_bit_num().bits() == 1 && uart.rs485_conf.read().dl1_en().bit_is_clear() {
+                1
+            } else {
+                2
+            };
+        let character = 1 + data_bits + parity_bits + stop_bits;
+
+        let threshold = ((bits + character - 1) / character).min(0x7f) as u8;
+        uart.conf1
+            .modify(|_, w| unsafe { w.rx_tout_thrhd().bits(threshold).rx_tout_en().set_bit() });
+    }
+
+    #[cfg(not(esp32))]
+    {
+        uart.mem_conf
+            .modify(|_, w| unsafe { w.rx_tout_thrhd().bits(bits.min(0x3ff)) });
+        uart.conf1.modify(|_, w| w.rx_tout_en().set_bit());
+    }
+}
+
+/// Progress of a frame read by [read_frame]
+#[derive(Default)]
+struct FrameReader {
+    /// Bytes of the frame received so far, including those dropped because
+    /// the buffer was full
+    received: usize,
+}
+
+impl FrameReader {
+    /// Move the received bytes into `buffer`, returns the result once the
+    /// line went idle after the frame
+    ///
+    /// Until then one byte is left in the RX FIFO: the RX timeout only runs
+    /// while the FIFO isn't empty.
+    fn poll(&mut self, uart: &RegisterBlock, buffer: &mut [u8]) -> Option<Result<usize, Error>> {
+        let idle = uart.int_raw.read().rxfifo_tout_int_raw().bit_is_set();
+        let count = uart.status.read().rxfifo_cnt().bits() as usize;
+
+        let count = if idle {
+            // bytes arriving from here on belong to the next frame
+            uart.int_clr.write(|w| w.rxfifo_tout_int_clr().set_bit());
+            count
+        } else {
+            count.saturating_sub(1)
+        };
+
+        for _ in 0..count {
+            if let Ok(byte) = read_byte(uart) {
+                if let Some(slot) = buffer.get_mut(self.received) {
+                    *slot = byte;
+                }
+                self.received += 1;
+            }
+        }
+
+        // a timeout without bytes is left over from a frame read otherwise
+        if !idle || self.received == 0 {
+            return None;
+        }
+
+        let received = core::mem::take(&mut self.received);
+        if received > buffer.len() {
+            Some(Err(Error::FrameTooLong {
+                transferred: buffer.len(),
+            }))
+        } else {
+            Some(Ok(received))
+        }
+    }
+}
+
+fn read_frame(uart: &RegisterBlock, buffer: &mut [u8]) -> Result<usize, Error> {
+    let mut reader = FrameReader::default();
+
+    loop {
+        if let Some(result) = reader.poll(uart, buffer) {
+            return result;
+        }
+    }
+}
+
+/// TX half of a [Serial] driver, see [Serial::split]
+pub struct Tx<T, P = (), M = Blocking>
+where
+    T: Instance,
+{
+    serial: Serial<T, P, M>,
+}
+
+impl<T, P> Tx<T, P, Blocking>
+where
+    T: Instance,
+{
+    /// Writes bytes
+    pub fn write_bytes(&mut self, data: &[u8]) -> Result<(), Error> {
+        self.serial.write_bytes(data)
+    }
+
+    /// Put a byte into the TX FIFO without waiting
+    ///
+    /// Returns `WouldBlock` if all 128 entries of the FIFO are in use.
+    pub fn write_nb(&mut self, word: u8) -> nb::Result<(), Error> {
+        self.serial.write_byte(word)
+    }
+
+    /// Write `data` into the TX FIFO, giving up once `deadline` passed, see
+    /// [Serial::write_all_timeout]
+    pub fn write_all_timeout(&mut self, data: &[u8], deadline: Deadline) -> Result<(), Error> {
+        self.serial.write_all_timeout(data, deadline)
+    }
+
+    /// Wait until all bytes of the TX FIFO were sent
+    pub fn flush(&mut self) -> Result<(), Error> {
+        nb::block!(self.serial.flush_tx())
+    }
+
+    /// Check if all bytes of the TX FIFO were sent, without waiting
+    pub fn flush_nb(&mut self) -> nb::Result<(), Error> {
+        self.serial.flush_tx()
+    }
+}
+
+impl<T, P, M> Tx<T, P, M>
+where
+    T: Instance,
+{
+    /// Listen for TX-DONE interrupts
+    pub fn listen_tx_done(&mut self) {
+        self.serial.listen_tx_done();
+    }
+
+    /// Stop listening for TX-DONE interrupts
+    pub fn unlisten_tx_done(&mut self) {
+        self.serial.unlisten_tx_done();
+    }
+
+    /// Checks if TX-DONE interrupt is set
+    pub fn tx_done_interrupt_set(&self) -> bool {
+        self.serial.tx_done_interrupt_set()
+    }
+
+    /// Reset TX-DONE interrupt
+    pub fn reset_tx_done_interrupt(&self) {
+        self.serial.reset_tx_done_interrupt();
+    }
+}
+
+/// RX half of a [Serial] driver, see [Serial::split]
+pub struct Rx<T, M = Blocking>
+where
+    T: Instance,
+{
+    /// Signals whose pins are floated on drop, if the driver had pins
+    float_pins: Option<(InputSignal, OutputSignal)>,
+    _uart: PhantomData<T>,
+    mode: PhantomData<M>,
+}
+
+impl<T, M> Drop for Rx<T, M>
+where
+    T: Instance,
+{
+    fn drop(&mut self) {
+        let uart = self.register_block();
+        uart.int_clr.write(|w| {
+            w.rxfifo_full_int_clr()
+                .set_bit()
+                .rxfifo_ovf_int_clr()
+                .set_bit()
+                .rxfifo_tout_int_clr()
+                .set_bit()
+        });
+        // `INT_ENA` is shared with the TX half
+        critical_section::with(|_| {
+            uart.int_ena.modify(|_, w| {
+                w.rxfifo_full_int_ena()
+                    .clear_bit()
+                    .rxfifo_ovf_int_ena()
+                    .clear_bit()
+                    .rxfifo_tout_int_ena()
+                    .clear_bit()
+            });
+        });
+
+        if let Some((rx, rts)) = self.float_pins {
+            crate::gpio::find_input_pad(rx)
+                .into_iter()
+                .chain(crate::gpio::find_output_pad(rts))
+                .for_each(crate::gpio::pad::float_pad);
+        }
+    }
+}
+
+impl<T> Rx<T, Blocking>
+where
+    T: Instance,
+{
+    /// Read a byte from the RX FIFO without waiting
+    ///
+    /// Returns `WouldBlock` if the FIFO (128 bytes) is empty. Bytes arriving
+    /// while it is full are lost, poll at least every 128 byte times.
+    pub fn read_nb(&mut self) -> nb::Result<u8, Error> {
+        read_byte(self.register_block())
+    }
+
+    /// Fill `buffer`, giving up once `deadline` passed, see
+    /// [Serial::read_exact_timeout]
+    pub fn read_exact_timeout(
+        &mut self,
+        buffer: &mut [u8],
+        deadline: Deadline,
+    ) -> Result<(), Error> {
+        read_exact_timeout(self.register_block(), buffer, deadline)
+    }
+
+    /// Read one frame into `buffer` and return its length, see
+    /// [Serial::read_frame]
+    pub fn read_frame(&mut self, buffer: &mut [u8]) -> Result<usize, Error> {
+        read_frame(self.register_block(), buffer)
+    }
+}
+
+impl<T, M> Rx<T, M>
+where
+    T: Instance,
+{
+    fn register_block(&self) -> &RegisterBlock {
+        unsafe { &*T::register_block_ptr() }
+    }
+
+    /// Set the silence which ends a frame, see [Serial::set_rx_idle_bits]
+    pub fn set_rx_idle_bits(&mut self, bits: u16) {
+        set_rx_idle_bits(self.register_block(), bits)
+    }
+
+    /// Configures the RX-FIFO threshold
+    pub fn set_rx_fifo_full_threshold(&mut self, threshold: u16) {
+        #[cfg(esp32)]
+        let threshold: u8 = threshold as u8;
+
+        self.register_block()
+            .conf1
+            .modify(|_, w| unsafe { w.rxfifo_full_thrhd().bits(threshold) });
+    }
+
+    /// Listen for RX-FIFO-FULL interrupts
+    pub fn listen_rx_fifo_full(&mut self) {
+        critical_section::with(|_| {
+            self.register_block()
+                .int_ena
+                .modify(|_, w| w.rxfifo_full_int_ena().set_bit());
+        });
+    }
+
+    /// Stop listening for RX-FIFO-FULL interrupts
+    pub fn unlisten_rx_fifo_full(&mut self) {
+        critical_section::with(|_| {
+            self.register_block()
+                .int_ena
+                .modify(|_, w| w.rxfifo_full_int_ena().clear_bit());
+        });
+    }
+
+    /// Checks if RX-FIFO-FULL interrupt is set
+    pub fn rx_fifo_full_interrupt_set(&self) -> bool {
+        self.register_block()
+            .int_raw
+            .read()
+            .rxfifo_full_int_raw()
+            .bit_is_set()
+    }
+
+    /// Reset RX-FIFO-FULL interrupt
+    pub fn reset_rx_fifo_full_interrupt(&self) {
+        self.register_block()
+            .int_clr
+            .write(|w| w.rxfifo_full_int_clr().set_bit());
+    }
+}
+
+/// UART peripheral instance
+pub trait Instance {
+    fn register_block(&self) -> &RegisterBlock;
+
+    /// Register block of this UART without an instance, used by the [Rx]
+    /// half of a split driver
+    fn register_block_ptr() -> *const RegisterBlock;
+
+    fn disable_tx_interrupts(&mut self) {
+        self.register_block().int_clr.write(|w| {
+            w.txfifo_empty_int_clr()
+                .set_bit()
+                .tx_brk_done_int_clr()
+                .set_bit()
+                .tx_brk_idle_done_int_clr()
+                .set_bit()
+                .tx_done_int_clr()
+                .set_bit()
+        });
+
+        // `INT_ENA` is shared with the RX half of a split driver
+        critical_section::with(|_| {
+            self.register_block().int_ena.modify(|_, w| {
+                w.txfifo_empty_int_ena()
+                    .clear_bit()
+                    .tx_brk_done_int_ena()
+                    .clear_bit()
+                    .tx_brk_idle_done_int_ena()
+                    .clear_bit()
+                    .tx_done_int_ena()
+                    .clear_bit()
+            });
+        });
+    }
+
+    fn disable_rx_interrupts(&mut self) {
+        self.register_block().int_clr.write(|w| {
+            w.rxfifo_full_int_clr()
+                .set_bit()
+                .rxfifo_ovf_int_clr()
+                .set_bit()
+                .rxfifo_tout_int_clr()
+                .set_bit()
+        });
+
+        // `INT_ENA` is shared with the TX half of a split driver
+        critical_section::with(|_| {
+            self.register_block().int_ena.modify(|_, w| {
+                w.rxfifo_full_int_ena()
+                    .clear_bit()
+                    .rxfifo_ovf_int_ena()
+                    .clear_bit()
+                    .rxfifo_tout_int_ena()
+                    .clear_bit()
+            });
+        });
+    }
+
+    fn get_tx_fifo_count(&mut self) -> u16 {
+        self.register_block()
+            .status
+            .read()
+            .txfifo_cnt()
+            .bits()
+            .into()
+    }
+
+    fn get_rx_fifo_count(&mut self) -> u16 {
+        self.register_block()
+            .status
+            .read()
+            .rxfifo_cnt()
+            .bits()
+            .into()
+    }
+
+    fn is_tx_idle(&self) -> bool {
+        #[cfg(esp32)]
+        let idle = self.register_block().status.read().st_utx_out().bits() == 0x0u8;
+        #[cfg(not(esp32))]
+        let idle = self.register_block().fsm_status.read().st_utx_out().bits() == 0x0u8;
+
+        idle
+    }
+
+    fn is_rx_idle(&self) -> bool {
+        #[cfg(esp32)]
+        let idle = self.register_block().status.read().st_urx_out().bits() == 0x0u8;
+        #[cfg(not(esp32))]
+        let idle = self.register_block().fsm_status.read().st_urx_out().bits() == 0x0u8;
+
+        idle
+    }
+
+    fn tx_signal(&self) -> OutputSignal;
+
+    fn rx_signal(&self) -> InputSignal;
+
+    fn cts_signal(&self) -> InputSignal;
+
+    fn rts_signal(&self) -> OutputSignal;
+
+    /// Interrupt of this UART, e.g. to enable it with `interrupt::enable`
+    fn interrupt(&self) -> Interrupt;
+
+    /// TX and RX pins the ROM bootloader uses for the console, if this UART
+    /// is the console
+    fn console_pins(&self) -> Option<(u8, u8)> {
+        None
+    }
+}
+
+impl Instance for UART0 {
+    #[inline(always)]
+    fn register_block(&self) -> &RegisterBlock {
+        self
+    }
+
+    #[inline(always)]
+    fn register_block_ptr() -> *const RegisterBlock {
+        UART0::ptr()
+    }
+
+    fn tx_signal(&self) -> OutputSignal {
+        OutputSignal::U0TXD
+    }
+
+    fn rx_signal(&self) -> InputSignal {
+        InputSignal::U0RXD
+    }
+
+    fn cts_signal(&self) -> InputSignal {
+        InputSignal::U0CTS
+    }
+
+    fn rts_signal(&self) -> OutputSignal {
+        OutputSignal::U0RTS
+    }
+
+    fn interrupt(&self) -> Interrupt {
+        Interrupt::UART0
+    }
+
+    fn console_pins(&self) -> Option<(u8, u8)> {
+        #[cfg(esp32)]
+        let pins = (1, 3);
+        #[cfg(esp32c2)]
+        let pins = (20, 19);
+        #[cfg(esp32c3)]
+        let pins = (21, 20);
+        #[cfg(any(esp32s2, esp32s3))]
+        let pins = (43, 44);
+
+        Some(pins)
+    }
+}
+
+impl Instance for UART1 {
+    #[inline(always)]
+    fn register_block(&self) -> &RegisterBlock {
+        self
+    }
+
+    #[inline(always)]
+    fn register_block_ptr() -> *const RegisterBlock {
+        UART1::ptr()
+    }
 
     fn tx_signal(&self) -> OutputSignal {
         OutputSignal::U1TXD
@@ -740,6 +2070,10 @@ impl Instance for UART1 {
     fn rts_signal(&self) -> OutputSignal {
         OutputSignal::U1RTS
     }
+
+    fn interrupt(&self) -> Interrupt {
+        Interrupt::UART1
+    }
 }
 
 #[cfg(uart2)]
@@ -749,6 +2083,11 @@ impl Instance for UART2 {
         self
     }
 
+    #[inline(always)]
+    fn register_block_ptr() -> *const RegisterBlock {
+        UART2::ptr()
+    }
+
     fn tx_signal(&self) -> OutputSignal {
         OutputSignal::U2TXD
     }
@@ -764,10 +2103,14 @@ impl Instance for UART2 {
     fn rts_signal(&self) -> OutputSignal {
         OutputSignal::U2RTS
     }
+
+    fn interrupt(&self) -> Interrupt {
+        Interrupt::UART2
+    }
 }
 
 #[cfg(feature = "ufmt")]
-impl<T> ufmt_write::uWrite for Serial<T>
+impl<T, P> ufmt_write::uWrite for Serial<T, P>
 where
     T: Instance,
 {
@@ -785,7 +2128,21 @@ where
     }
 }
 
-impl<T> core::fmt::Write for Serial<T>
+impl<T, P, M> ClockListener for Serial<T, P, M>
+where
+    T: Instance,
+{
+    /// Re-derives the baud rate divider, if the baud rate was configured by
+    /// the driver.
+    fn clocks_changed(&mut self, clocks: &Clocks) {
+        if let Some(baudrate) = self.baudrate {
+            nb::block!(self.flush_tx()).ok();
+            self.change_baud(baudrate, clocks);
+        }
+    }
+}
+
+impl<T, P> core::fmt::Write for Serial<T, P>
 where
     T: Instance,
 {
@@ -793,9 +2150,19 @@ where
     fn write_str(&mut self, s: &str) -> core::fmt::Result {
         self.write_bytes(s.as_bytes()).map_err(|_| core::fmt::Error)
     }
+
+    /// Writes arguments without placeholders, e.g. `write!(serial, "text")`,
+    /// directly instead of going through the formatting machinery
+    #[inline]
+    fn write_fmt(&mut self, args: core::fmt::Arguments<'_>) -> core::fmt::Result {
+        match args.as_str() {
+            Some(s) => self.write_str(s),
+            None => core::fmt::write(self, args),
+        }
+    }
 }
 
-impl<T> embedded_hal::serial::Write<u8> for Serial<T>
+impl<T, P> embedded_hal::serial::Write<u8> for Serial<T, P>
 where
     T: Instance,
 {
@@ -810,7 +2177,7 @@ where
     }
 }
 
-impl<T> embedded_hal::serial::Read<u8> for Serial<T>
+impl<T, P> embedded_hal::serial::Read<u8> for Serial<T, P>
 where
     T: Instance,
 {
@@ -822,12 +2189,15 @@ where
 }
 
 #[cfg(feature = "eh1")]
-impl<T> embedded_hal_1::serial::ErrorType for Serial<T> {
+impl<T, P> embedded_hal_1::serial::ErrorType for Serial<T, P>
+where
+    T: Instance,
+{
     type Error = Error;
 }
 
 #[cfg(feature = "eh1")]
-impl<T> embedded_hal_nb::serial::Read for Serial<T>
+impl<T, P> embedded_hal_nb::serial::Read for Serial<T, P>
 where
     T: Instance,
 {
@@ -837,7 +2207,7 @@ where
 }
 
 #[cfg(feature = "eh1")]
-impl<T> embedded_hal_nb::serial::Write for Serial<T>
+impl<T, P> embedded_hal_nb::serial::Write for Serial<T, P>
 where
     T: Instance,
 {
@@ -849,3 +2219,394 @@ where
         self.flush_tx()
     }
 }
+
+#[cfg(feature = "ufmt")]
+impl<T, P> ufmt_write::uWrite for Tx<T, P>
+where
+    T: Instance,
+{
+    type Error = Error;
+
+    #[inline]
+    fn write_str(&mut self, s: &str) -> Result<(), Self::Error> {
+        self.serial.write_bytes(s.as_bytes())
+    }
+
+    #[inline]
+    fn write_char(&mut self, ch: char) -> Result<(), Self::Error> {
+        ufmt_write::uWrite::write_char(&mut self.serial, ch)
+    }
+}
+
+impl<T, P, M> ClockListener for Tx<T, P, M>
+where
+    T: Instance,
+{
+    fn clocks_changed(&mut self, clocks: &Clocks) {
+        self.serial.clocks_changed(clocks);
+    }
+}
+
+impl<T, P> core::fmt::Write for Tx<T, P>
+where
+    T: Instance,
+{
+    #[inline]
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        core::fmt::Write::write_str(&mut self.serial, s)
+    }
+
+    #[inline]
+    fn write_fmt(&mut self, args: core::fmt::Arguments<'_>) -> core::fmt::Result {
+        core::fmt::Write::write_fmt(&mut self.serial, args)
+    }
+}
+
+impl<T, P> embedded_hal::serial::Write<u8> for Tx<T, P>
+where
+    T: Instance,
+{
+    type Error = Error;
+
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.serial.write_byte(word)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.serial.flush_tx()
+    }
+}
+
+impl<T> embedded_hal::serial::Read<u8> for Rx<T>
+where
+    T: Instance,
+{
+    type Error = Error;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.read_nb()
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<T, P> embedded_hal_1::serial::ErrorType for Tx<T, P>
+where
+    T: Instance,
+{
+    type Error = Error;
+}
+
+#[cfg(feature = "eh1")]
+impl<T> embedded_hal_1::serial::ErrorType for Rx<T>
+where
+    T: Instance,
+{
+    type Error = Error;
+}
+
+#[cfg(feature = "eh1")]
+impl<T> embedded_hal_nb::serial::Read for Rx<T>
+where
+    T: Instance,
+{
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        self.read_nb()
+    }
+}
+
+#[cfg(feature = "eh1")]
+impl<T, P> embedded_hal_nb::serial::Write for Tx<T, P>
+where
+    T: Instance,
+{
+    fn write(&mut self, word: u8) -> nb::Result<(), Self::Error> {
+        self.serial.write_byte(word)
+    }
+
+    fn flush(&mut self) -> nb::Result<(), Self::Error> {
+        self.serial.flush_tx()
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl embedded_io::Error for Error {
+    fn kind(&self) -> embedded_io::ErrorKind {
+        embedded_io::ErrorKind::Other
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<T, P> embedded_io::Io for Serial<T, P>
+where
+    T: Instance,
+{
+    type Error = Error;
+}
+
+#[cfg(feature = "embedded-io")]
+impl<T, P> embedded_io::blocking::Read for Serial<T, P>
+where
+    T: Instance,
+{
+    /// Wait for the first byte, then read the bytes already in the RX FIFO
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        buf[0] = nb::block!(self.read_byte())?;
+
+        let mut count = 1;
+        while count < buf.len() {
+            match self.read_byte() {
+                Ok(byte) => buf[count] = byte,
+                Err(nb::Error::WouldBlock) => break,
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<T, P> embedded_io::blocking::Write for Serial<T, P>
+where
+    T: Instance,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write_bytes(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        nb::block!(self.flush_tx())
+    }
+}
+
+/// Errors reported by [`LineReader`]
+#[derive(Debug)]
+pub enum LineReaderError<E> {
+    /// The line did not fit into the buffer. The remainder of the line has
+    /// been discarded, reading resumes with the next line.
+    Overflow,
+    /// The line is not valid UTF-8. The remainder of the line has been
+    /// discarded, reading resumes with the next line.
+    InvalidUtf8,
+    /// The underlying reader reported an error
+    Read(E),
+}
+
+/// Accumulates bytes received from a serial reader into lines
+///
+/// Lines may be terminated by CR, LF or CRLF; the terminator is not part of
+/// the returned line. Lines are collected into a [`heapless::String`] of
+/// `N` bytes, lines which are longer or aren't valid UTF-8 are reported as
+/// errors.
+///
+/// Bytes are fed one at a time with [`LineReader::push`], e.g. to echo them
+/// as they arrive. With the `embedded-io` feature [`LineReader::read_line`]
+/// reads them from any [`embedded_io::blocking::Read`], which [`Serial`] and
+/// the USB Serial/JTAG driver implement.
+///
+/// There is no async variant: [`Serial`] in async mode only reads whole
+/// frames with `read_frame_async`, pass their bytes to
+/// [`LineReader::push`].
+///
+/// # Example
+///
+/// ```
+/// let mut reader = LineReader::<64>::new().with_backspace(true);
+/// let line = reader.read_line(&mut serial0).unwrap();
+/// ```
+pub struct LineReader<const N: usize> {
+    line: heapless::String<N>,
+    /// Leading bytes of a multi-byte character
+    partial: [u8; 4],
+    partial_len: usize,
+    complete: bool,
+    overflow: bool,
+    invalid: bool,
+    last_was_cr: bool,
+    backspace: bool,
+}
+
+impl<const N: usize> LineReader<N> {
+    /// Create a new, empty line reader
+    pub const fn new() -> Self {
+        Self {
+            line: heapless::String::new(),
+            partial: [0u8; 4],
+            partial_len: 0,
+            complete: false,
+            overflow: false,
+            invalid: false,
+            last_was_cr: false,
+            backspace: false,
+        }
+    }
+
+    /// Enable or disable line editing using backspace (`0x08`) and delete
+    /// (`0x7f`), which remove the last character of the line
+    pub fn with_backspace(mut self, enable: bool) -> Self {
+        self.backspace = enable;
+        self
+    }
+
+    /// Discard everything received so far
+    pub fn clear(&mut self) {
+        self.line.clear();
+        self.partial_len = 0;
+        self.complete = false;
+        self.overflow = false;
+        self.invalid = false;
+    }
+
+    /// The part of the current line received so far, empty after a line
+    /// was completed
+    pub fn buffered(&self) -> &str {
+        if self.complete {
+            ""
+        } else {
+            self.line.as_str()
+        }
+    }
+
+    /// Process a single byte
+    ///
+    /// Returns the completed line once a line terminator has been received.
+    pub fn push(&mut self, byte: u8) -> nb::Result<&str, LineReaderError<void::Void>> {
+        match self.push_byte(byte) {
+            Ok(()) => Ok(self.line.as_str()),
+            Err(Some(error)) => Err(nb::Error::Other(error)),
+            Err(None) => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    /// Block until a complete line has been received from `reader`
+    ///
+    /// The bytes are read one at a time, so nothing after the line
+    /// terminator is taken from the reader.
+    #[cfg(feature = "embedded-io")]
+    pub fn read_line<R>(&mut self, reader: &mut R) -> Result<&str, LineReaderError<R::Error>>
+    where
+        R: embedded_io::blocking::Read,
+    {
+        loop {
+            let mut byte = [0u8];
+            // a reader at the end of its stream returns nothing, a serial
+            // port never ends so keep waiting
+            if reader.read(&mut byte).map_err(LineReaderError::Read)? == 0 {
+                continue;
+            }
+
+            match self.push_byte(byte[0]) {
+                Ok(()) => return Ok(self.line.as_str()),
+                Err(Some(LineReaderError::Overflow)) => return Err(LineReaderError::Overflow),
+                Err(Some(LineReaderError::InvalidUtf8)) => {
+                    return Err(LineReaderError::InvalidUtf8)
+                }
+                Err(_) => {}
+            }
+        }
+    }
+
+    /// Returns `Ok` once a line is complete, `Err(None)` while it isn't
+    fn push_byte(&mut self, byte: u8) -> Result<(), Option<LineReaderError<void::Void>>> {
+        // start a new line after the previous one has been handed out
+        if self.complete {
+            self.line.clear();
+            self.complete = false;
+        }
+
+        let last_was_cr = self.last_was_cr;
+        self.last_was_cr = byte == b'\r';
+
+        match byte {
+            // the LF of a CRLF sequence terminates nothing
+            b'\n' if last_was_cr => Err(None),
+            b'\r' | b'\n' => {
+                let error = if self.overflow {
+                    Some(LineReaderError::Overflow)
+                } else if self.invalid || self.partial_len != 0 {
+                    Some(LineReaderError::InvalidUtf8)
+                } else {
+                    None
+                };
+
+                match error {
+                    Some(error) => {
+                        self.clear();
+                        Err(Some(error))
+                    }
+                    None => {
+                        self.complete = true;
+                        Ok(())
+                    }
+                }
+            }
+            0x08 | 0x7f if self.backspace => {
+                if !self.overflow && !self.invalid {
+                    if self.partial_len != 0 {
+                        self.partial_len = 0;
+                    } else {
+                        self.line.pop();
+                    }
+                }
+                Err(None)
+            }
+            _ => {
+                if !self.overflow && !self.invalid {
+                    self.append(byte);
+                }
+                Err(None)
+            }
+        }
+    }
+
+    /// Add `byte` to the line once the character it belongs to is complete
+    fn append(&mut self, byte: u8) {
+        let expected = match self.partial_len {
+            0 => utf8_len(byte),
+            _ => utf8_len(self.partial[0]),
+        };
+        if expected == 0 {
+            self.invalid = true;
+            return;
+        }
+
+        self.partial[self.partial_len] = byte;
+        self.partial_len += 1;
+        if self.partial_len < expected {
+            return;
+        }
+
+        let len = core::mem::take(&mut self.partial_len);
+        match core::str::from_utf8(&self.partial[..len]) {
+            Ok(c) => {
+                if self.line.push_str(c).is_err() {
+                    self.overflow = true;
+                }
+            }
+            Err(_) => self.invalid = true,
+        }
+    }
+}
+
+impl<const N: usize> Default for LineReader<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Length of the UTF-8 sequence started by `byte`, 0 if it can't start one
+const fn utf8_len(byte: u8) -> usize {
+    match byte {
+        0x00..=0x7f => 1,
+        0xc2..=0xdf => 2,
+        0xe0..=0xef => 3,
+        0xf0..=0xf4 => 4,
+        _ => 0,
+    }
+}
@@ -0,0 +1,377 @@
+//! Async UART driver mode
+//!
+//! [Serial::into_async] enables the interrupt of the UART and returns the
+//! driver in [Async] mode, where writing, flushing and reading frames return
+//! futures. They are woken from the interrupt of the UART, whose handler has
+//! to call [handle_interrupt]:
+//!
+//! ```no_run
+//! let mut serial = Serial::new(peripherals.UART1).into_async()?;
+//!
+//! #[interrupt]
+//! fn UART1() {
+//!     serial::asynch::handle_interrupt::<UART1>();
+//! }
+//!
+//! serial.set_rx_idle_bits(39);
+//! let len = serial.read_frame_async(&mut buffer).await?;
+//! serial.write_async(&buffer[..len]).await?;
+//! serial.flush_async().await?;
+//! ```
+//!
+//! [Serial::into_blocking] disables the interrupt again.
+//!
+//! [Serial::read_frame_async] completes like [Serial::read_frame] once the
+//! line went idle after a frame. Frames longer than the RX FIFO are moved
+//! into the buffer whenever the RX-FIFO-full interrupt fires, keep its
+//! threshold (96 bytes after reset) at 2 or more and poll the future before
+//! the FIFO overflows.
+//!
+//! Dropping a future before it completed stops listening, the bytes of the
+//! frame received until then stay in the FIFO or the buffer, the bytes
+//! written until then are sent.
+
+use core::{
+    future::Future,
+    marker::PhantomData,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::{write_byte, Error, FrameReader, Instance, RegisterBlock, Rx, Serial, Tx};
+use crate::{
+    interrupt,
+    mode::{Async, Blocking},
+    pac::{UART0, UART1},
+};
+
+#[cfg(uart2)]
+const UARTS: usize = 3;
+#[cfg(not(uart2))]
+const UARTS: usize = 2;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEW_WAKER: AtomicWaker = AtomicWaker::new();
+
+static RX_WAKERS: [AtomicWaker; UARTS] = [NEW_WAKER; UARTS];
+static TX_WAKERS: [AtomicWaker; UARTS] = [NEW_WAKER; UARTS];
+
+fn index<T>() -> usize
+where
+    T: Instance,
+{
+    let uart = T::register_block_ptr();
+    if uart == UART0::ptr() {
+        0
+    } else if uart == UART1::ptr() {
+        1
+    } else {
+        2
+    }
+}
+
+fn listen_rx(uart: &RegisterBlock) {
+    critical_section::with(|_| {
+        uart.int_ena.modify(|_, w| {
+            w.rxfifo_tout_int_ena()
+                .set_bit()
+                .rxfifo_full_int_ena()
+                .set_bit()
+        });
+    });
+}
+
+fn unlisten_rx(uart: &RegisterBlock) {
+    critical_section::with(|_| {
+        uart.int_ena.modify(|_, w| {
+            w.rxfifo_tout_int_ena()
+                .clear_bit()
+                .rxfifo_full_int_ena()
+                .clear_bit()
+        });
+    });
+}
+
+fn unlisten_tx(uart: &RegisterBlock) {
+    critical_section::with(|_| {
+        uart.int_ena.modify(|_, w| {
+            w.txfifo_empty_int_ena()
+                .clear_bit()
+                .tx_done_int_ena()
+                .clear_bit()
+        });
+    });
+}
+
+impl<T, P> Serial<T, P, Blocking>
+where
+    T: Instance,
+{
+    /// Turn the driver into an [Async] one
+    ///
+    /// Enables the interrupt of the UART at priority 1, its handler has to
+    /// call [handle_interrupt]. Fails if the interrupt can't be enabled, the
+    /// driver is dropped in that case.
+    pub fn into_async(self) -> Result<Serial<T, P, Async>, interrupt::Error> {
+        interrupt::enable(self.uart.interrupt(), interrupt::Priority::Priority1)?;
+        Ok(self.into_mode())
+    }
+}
+
+impl<T, P> Serial<T, P, Async>
+where
+    T: Instance,
+{
+    /// Turn the driver back into a [Blocking] one
+    ///
+    /// Disables the interrupt of the UART, together with all of its RX and
+    /// TX interrupt sources.
+    pub fn into_blocking(mut self) -> Serial<T, P, Blocking> {
+        self.uart.disable_rx_interrupts();
+        self.uart.disable_tx_interrupts();
+        interrupt::disable(crate::get_core(), self.uart.interrupt());
+        self.into_mode()
+    }
+
+    /// Write `data` into the TX FIFO, the returned future completes once the
+    /// last byte is in the FIFO
+    pub fn write_async<'a>(&'a mut self, data: &'a [u8]) -> WriteFuture<'a, T> {
+        WriteFuture::new(&self.uart, data)
+    }
+
+    /// Wait until all bytes of the TX FIFO were sent
+    pub fn flush_async(&mut self) -> FlushFuture<'_, T> {
+        FlushFuture::new(&self.uart)
+    }
+
+    /// Read one frame into `buffer`, the returned future completes with its
+    /// length, see [Serial::read_frame]
+    pub fn read_frame_async<'a>(&'a mut self, buffer: &'a mut [u8]) -> FrameFuture<'a, T> {
+        FrameFuture::new(buffer)
+    }
+}
+
+impl<T, P> Tx<T, P, Async>
+where
+    T: Instance,
+{
+    /// Write `data` into the TX FIFO, see [Serial::write_async]
+    pub fn write_async<'a>(&'a mut self, data: &'a [u8]) -> WriteFuture<'a, T> {
+        self.serial.write_async(data)
+    }
+
+    /// Wait until all bytes of the TX FIFO were sent
+    pub fn flush_async(&mut self) -> FlushFuture<'_, T> {
+        self.serial.flush_async()
+    }
+}
+
+impl<T> Rx<T, Async>
+where
+    T: Instance,
+{
+    /// Read one frame into `buffer`, the returned future completes with its
+    /// length, see [Serial::read_frame]
+    pub fn read_frame_async<'a>(&'a mut self, buffer: &'a mut [u8]) -> FrameFuture<'a, T> {
+        FrameFuture::new(buffer)
+    }
+}
+
+/// Future returned by [Serial::write_async] and [Tx::write_async]
+pub struct WriteFuture<'a, T>
+where
+    T: Instance,
+{
+    uart: &'a T,
+    data: &'a [u8],
+}
+
+impl<'a, T> WriteFuture<'a, T>
+where
+    T: Instance,
+{
+    fn new(uart: &'a T, data: &'a [u8]) -> Self {
+        Self { uart, data }
+    }
+}
+
+impl<'a, T> Future for WriteFuture<'a, T>
+where
+    T: Instance,
+{
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        TX_WAKERS[index::<T>()].register(cx.waker());
+
+        let uart = self.uart;
+        let uart = uart.register_block();
+        let mut data = self.data;
+        while let Some((&byte, rest)) = data.split_first() {
+            if write_byte(uart, byte).is_err() {
+                break;
+            }
+            data = rest;
+        }
+        self.data = data;
+
+        if self.data.is_empty() {
+            Poll::Ready(Ok(()))
+        } else {
+            // raised again as long as the FIFO is below the threshold
+            uart.int_clr.write(|w| w.txfifo_empty_int_clr().set_bit());
+            critical_section::with(|_| {
+                uart.int_ena
+                    .modify(|_, w| w.txfifo_empty_int_ena().set_bit());
+            });
+            Poll::Pending
+        }
+    }
+}
+
+impl<'a, T> Drop for WriteFuture<'a, T>
+where
+    T: Instance,
+{
+    fn drop(&mut self) {
+        unlisten_tx(self.uart.register_block());
+    }
+}
+
+/// Future returned by [Serial::flush_async] and [Tx::flush_async]
+pub struct FlushFuture<'a, T>
+where
+    T: Instance,
+{
+    uart: &'a T,
+}
+
+impl<'a, T> FlushFuture<'a, T>
+where
+    T: Instance,
+{
+    fn new(uart: &'a T) -> Self {
+        Self { uart }
+    }
+}
+
+impl<'a, T> Future for FlushFuture<'a, T>
+where
+    T: Instance,
+{
+    type Output = Result<(), Error>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        TX_WAKERS[index::<T>()].register(cx.waker());
+
+        // clear the flag before looking at the transmitter, so that a byte
+        // sent in between raises it again
+        let uart = self.uart.register_block();
+        uart.int_clr.write(|w| w.tx_done_int_clr().set_bit());
+
+        if self.uart.is_tx_idle() {
+            Poll::Ready(Ok(()))
+        } else {
+            critical_section::with(|_| {
+                uart.int_ena.modify(|_, w| w.tx_done_int_ena().set_bit());
+            });
+            Poll::Pending
+        }
+    }
+}
+
+impl<'a, T> Drop for FlushFuture<'a, T>
+where
+    T: Instance,
+{
+    fn drop(&mut self) {
+        unlisten_tx(self.uart.register_block());
+    }
+}
+
+/// Future returned by [Serial::read_frame_async] and [Rx::read_frame_async]
+pub struct FrameFuture<'a, T>
+where
+    T: Instance,
+{
+    buffer: &'a mut [u8],
+    reader: FrameReader,
+    _uart: PhantomData<&'a mut T>,
+}
+
+impl<'a, T> FrameFuture<'a, T>
+where
+    T: Instance,
+{
+    fn new(buffer: &'a mut [u8]) -> Self {
+        Self {
+            buffer,
+            reader: FrameReader::default(),
+            _uart: PhantomData,
+        }
+    }
+
+    fn register_block(&self) -> &'static RegisterBlock {
+        unsafe { &*T::register_block_ptr() }
+    }
+}
+
+impl<'a, T> Future for FrameFuture<'a, T>
+where
+    T: Instance,
+{
+    type Output = Result<usize, Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        RX_WAKERS[index::<T>()].register(cx.waker());
+
+        let uart = self.register_block();
+        let this = &mut *self;
+        match this.reader.poll(uart, this.buffer) {
+            Some(result) => Poll::Ready(result),
+            None => {
+                // the FIFO was drained below the threshold, the flag is raised
+                // again by the next byte reaching it
+                uart.int_clr.write(|w| w.rxfifo_full_int_clr().set_bit());
+                listen_rx(uart);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<'a, T> Drop for FrameFuture<'a, T>
+where
+    T: Instance,
+{
+    fn drop(&mut self) {
+        unlisten_rx(self.register_block());
+    }
+}
+
+/// Wake the futures waiting for the UART `T`
+///
+/// To be called from the interrupt handler of the UART. The interrupts the
+/// futures listen for are disabled until they were polled again, the flags
+/// are left for the futures to handle. Other interrupts of the UART are left
+/// alone.
+#[procmacros::ram]
+pub fn handle_interrupt<T>()
+where
+    T: Instance,
+{
+    let uart = unsafe { &*T::register_block_ptr() };
+    let status = uart.int_st.read();
+
+    if status.rxfifo_tout_int_st().bit_is_set() || status.rxfifo_full_int_st().bit_is_set() {
+        unlisten_rx(uart);
+        RX_WAKERS[index::<T>()].wake();
+    }
+
+    if status.txfifo_empty_int_st().bit_is_set() || status.tx_done_int_st().bit_is_set() {
+        unlisten_tx(uart);
+        TX_WAKERS[index::<T>()].wake();
+    }
+}
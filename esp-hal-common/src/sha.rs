@@ -1,6 +1,6 @@
 use core::convert::Infallible;
 
-use crate::pac::SHA;
+use crate::{crypto_dma::CryptoDma, pac::SHA};
 
 // All the hash algorithms introduced in FIPS PUB 180-4 Spec.
 // – SHA-1
@@ -165,6 +165,7 @@ pub struct Sha {
     cursor: usize,
     first_run: bool,
     finished: bool,
+    lock: Option<CryptoDma>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -226,11 +227,6 @@ fn mode_as_bits(mode: ShaMode) -> u8 {
 // ::finish() length/self.cursor usage
 impl Sha {
     pub fn new(sha: SHA, mode: ShaMode) -> Self {
-        // Setup SHA Mode
-        #[cfg(not(esp32))]
-        sha.mode
-            .write(|w| unsafe { w.mode().bits(mode_as_bits(mode)) });
-
         Self {
             sha,
             mode,
@@ -238,6 +234,7 @@ impl Sha {
             first_run: true,
             finished: false,
             alignment_helper: AlignmentHelper::default(),
+            lock: None,
         }
     }
 
@@ -391,7 +388,19 @@ impl Sha {
         }
     }
 
+    // Returns `WouldBlock` while the accelerator is used by another driver, e.g.
+    // the HMAC peripheral. It is held from the first `update()` until `finish()`.
     pub fn update<'a>(&mut self, buffer: &'a [u8]) -> nb::Result<&'a [u8], Infallible> {
+        if self.lock.is_none() {
+            self.lock = Some(CryptoDma::try_acquire().ok_or(nb::Error::WouldBlock)?);
+
+            // Other users of the accelerator may have changed the mode
+            #[cfg(not(esp32))]
+            self.sha
+                .mode
+                .write(|w| unsafe { w.mode().bits(mode_as_bits(self.mode)) });
+        }
+
         if self.is_busy() {
             return Err(nb::Error::WouldBlock);
         }
@@ -425,7 +434,7 @@ impl Sha {
         if !self.finished {
             // Store message length for padding
             let length = self.cursor * 8;
-            nb::block!(self.update(&[0x80]))?; // Append "1" bit
+            self.update(&[0x80])?; // Append "1" bit, `WouldBlock` if the accelerator is taken
             nb::block!(self.flush_data())?; // Flush partial data, ensures aligned cursor
             debug_assert!(self.cursor % 4 == 0);
 
@@ -491,6 +500,7 @@ impl Sha {
             }
 
             self.finished = true;
+            self.lock = None;
         }
 
         unsafe {
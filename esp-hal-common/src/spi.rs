@@ -47,21 +47,64 @@
 //! [`SpiBusDevice`] implemented here. These give exclusive access to the
 //! underlying SPI bus by means of a Mutex. This ensures that device
 //! transactions do not interfere with each other.
+//!
+//! Each device can bring its own [`Config`], the bus frequency and SPI mode
+//! are switched for every transaction, e.g. to run a display at 40 MHz next
+//! to an SD card at 400 kHz. A device is selected by one of the CS lines 0
+//! to 2 of the SPI host or by a GPIO, see [`ChipSelect`].
+//!
+//! ## Multiple SPI buses
+//!
+//! [`Spi`] is generic over the SPI host, so on chips with more than one
+//! general purpose host (SPI2 and SPI3 on the ESP32, ESP32-S2 and ESP32-S3)
+//! both can be used at the same time, each with its own pins, frequency, mode
+//! and DMA channel. On the ESP32 the hosts are also known as HSPI and VSPI,
+//! `spi::HSPI` and `spi::VSPI` are provided as aliases.
+//!
+//! ## Pins and bus frequency
+//!
+//! Signals bypass the GPIO matrix when each pin is the IO MUX pin of its
+//! signal (e.g. GPIO6 for SCLK of SPI2 on the ESP32-C3),
+//! [`Spi::routed_via_io_mux`] tells if the bypass was possible. On the ESP32
+//! the GPIO matrix can't carry more than 40 MHz, so there the bus frequency
+//! is silently limited to 40 MHz unless all pins are IO MUX pins. The other
+//! chips use the requested frequency with either routing, the delay of the
+//! GPIO matrix may still require a lower frequency for reading at high
+//! clocks.
+//!
+//! ## Driver modes
+//!
+//! The driver is created in [`Blocking`] mode, see [`crate::mode`].
+//! [`Spi::into_async`] (feature `async`) turns it into an [`Async`] driver,
+//! whose transfers are described in the [`asynch`] module. The bus
+//! configuration is available in both modes.
+//!
+//! [`Async`]: crate::mode::Async
+
+#[cfg(feature = "async")]
+pub mod asynch;
+
+use core::marker::PhantomData;
 
 use fugit::HertzU32;
 
+#[cfg(esp32)]
+pub use crate::pac::{SPI2 as HSPI, SPI3 as VSPI};
 use crate::{
-    clock::Clocks,
+    clock::{ClockListener, Clocks},
     dma::{
         private::{Rx, Tx},
         DmaError,
         DmaPeripheral,
     },
-    pac::spi2::RegisterBlock,
+    mode::{Blocking, Mode},
+    pac::{spi2::RegisterBlock, Interrupt},
     system::PeripheralClockControl,
+    time::Deadline,
     types::{InputSignal, OutputSignal},
     InputPin,
     OutputPin,
+    RoutedVia,
 };
 
 /// The size of the FIFO buffer for SPI
@@ -71,6 +114,9 @@ const FIFO_SIZE: usize = 64;
 const FIFO_SIZE: usize = 72;
 /// Padding byte for empty write transfers
 const EMPTY_WRITE_PAD: u8 = 0x00u8;
+/// Highest bus frequency the GPIO matrix of the ESP32 can carry
+#[cfg(esp32)]
+const MATRIX_MAX_FREQUENCY: HertzU32 = HertzU32::MHz(40);
 
 #[allow(unused)]
 const MAX_DMA_SIZE: usize = 32736;
@@ -79,6 +125,10 @@ const MAX_DMA_SIZE: usize = 32736;
 pub enum Error {
     DmaError(DmaError),
     MaxDmaTransferSizeExceeded,
+    /// The deadline of a `*_timeout` method passed after `transferred` bytes
+    Timeout {
+        transferred: usize,
+    },
     Unknown,
 }
 
@@ -95,7 +145,7 @@ impl embedded_hal_1::spi::Error for Error {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SpiMode {
     Mode0,
     Mode1,
@@ -103,8 +153,30 @@ pub enum SpiMode {
     Mode3,
 }
 
-pub struct Spi<T> {
+/// Bus settings, e.g. of one of the devices on a shared bus
+///
+/// See [`Spi::apply_config`], the devices of an `SpiBusController` (feature
+/// `eh1`) apply their settings for each transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    /// Bus frequency, limited to 40 MHz on the ESP32 unless
+    /// [`Spi::routed_via_io_mux`]
+    pub frequency: HertzU32,
+    pub mode: SpiMode,
+}
+
+/// SPI master driver
+///
+/// `M` is the [driver mode](crate::mode).
+pub struct Spi<T, M = Blocking> {
     spi: T,
+    miso_selection: Option<crate::gpio::Loopback>,
+    frequency: HertzU32,
+    data_mode: SpiMode,
+    apb_clock: HertzU32,
+    via_io_mux: bool,
+    idle: IdleOnDrop,
+    mode: PhantomData<M>,
 }
 
 impl<T> Spi<T>
@@ -123,19 +195,28 @@ where
         peripheral_clock_control: &mut PeripheralClockControl,
         clocks: &Clocks,
     ) -> Self {
-        sck.set_to_push_pull_output()
-            .connect_peripheral_to_output(spi.sclk_signal());
-
-        mosi.set_to_push_pull_output()
-            .connect_peripheral_to_output(spi.mosi_signal());
-
-        miso.set_to_input()
-            .connect_input_to_peripheral(spi.miso_signal());
-
-        cs.set_to_push_pull_output()
-            .connect_peripheral_to_output(spi.cs_signal());
-
-        Self::new_internal(spi, frequency, mode, peripheral_clock_control, clocks)
+        let routes = [
+            connect_output(&mut sck, spi.sclk_signal()),
+            connect_output(&mut mosi, spi.mosi_signal()),
+            connect_input(&mut miso, spi.miso_signal()),
+            connect_output(&mut cs, spi.cs_signal()),
+        ];
+        let pads = [
+            Some(sck.number()),
+            Some(mosi.number()),
+            Some(miso.number()),
+            Some(cs.number()),
+        ];
+
+        Self::new_internal(
+            spi,
+            frequency,
+            mode,
+            !routes.contains(&RoutedVia::Matrix),
+            pads,
+            peripheral_clock_control,
+            clocks,
+        )
     }
 
     /// Constructs an SPI instance in 8bit dataframe mode without CS pin.
@@ -149,16 +230,27 @@ where
         peripheral_clock_control: &mut PeripheralClockControl,
         clocks: &Clocks,
     ) -> Self {
-        sck.set_to_push_pull_output()
-            .connect_peripheral_to_output(spi.sclk_signal());
-
-        mosi.set_to_push_pull_output()
-            .connect_peripheral_to_output(spi.mosi_signal());
-
-        miso.set_to_input()
-            .connect_input_to_peripheral(spi.miso_signal());
-
-        Self::new_internal(spi, frequency, mode, peripheral_clock_control, clocks)
+        let routes = [
+            connect_output(&mut sck, spi.sclk_signal()),
+            connect_output(&mut mosi, spi.mosi_signal()),
+            connect_input(&mut miso, spi.miso_signal()),
+        ];
+        let pads = [
+            Some(sck.number()),
+            Some(mosi.number()),
+            Some(miso.number()),
+            None,
+        ];
+
+        Self::new_internal(
+            spi,
+            frequency,
+            mode,
+            !routes.contains(&RoutedVia::Matrix),
+            pads,
+            peripheral_clock_control,
+            clocks,
+        )
     }
 
     /// Constructs an SPI instance in 8bit dataframe mode without CS and MISO
@@ -172,13 +264,21 @@ where
         peripheral_clock_control: &mut PeripheralClockControl,
         clocks: &Clocks,
     ) -> Self {
-        sck.set_to_push_pull_output()
-            .connect_peripheral_to_output(spi.sclk_signal());
-
-        mosi.set_to_push_pull_output()
-            .connect_peripheral_to_output(spi.mosi_signal());
-
-        Self::new_internal(spi, frequency, mode, peripheral_clock_control, clocks)
+        let routes = [
+            connect_output(&mut sck, spi.sclk_signal()),
+            connect_output(&mut mosi, spi.mosi_signal()),
+        ];
+        let pads = [Some(sck.number()), Some(mosi.number()), None, None];
+
+        Self::new_internal(
+            spi,
+            frequency,
+            mode,
+            !routes.contains(&RoutedVia::Matrix),
+            pads,
+            peripheral_clock_control,
+            clocks,
+        )
     }
 
     /// Constructs an SPI instance in 8bit dataframe mode with only MOSI
@@ -193,37 +293,322 @@ where
         peripheral_clock_control: &mut PeripheralClockControl,
         clocks: &Clocks,
     ) -> Self {
-        mosi.set_to_push_pull_output()
-            .connect_peripheral_to_output(spi.mosi_signal());
+        let route = connect_output(&mut mosi, spi.mosi_signal());
+        let pads = [None, Some(mosi.number()), None, None];
 
-        Self::new_internal(spi, frequency, mode, peripheral_clock_control, clocks)
+        Self::new_internal(
+            spi,
+            frequency,
+            mode,
+            route == RoutedVia::IoMux,
+            pads,
+            peripheral_clock_control,
+            clocks,
+        )
     }
 
     pub(crate) fn new_internal(
         spi: T,
         frequency: HertzU32,
         mode: SpiMode,
+        via_io_mux: bool,
+        pads: [Option<u8>; 4],
         peripheral_clock_control: &mut PeripheralClockControl,
         clocks: &Clocks,
     ) -> Self {
         spi.enable_peripheral(peripheral_clock_control);
 
-        let mut spi = Self { spi };
-        spi.spi.setup(frequency, clocks);
+        let mut spi = Self {
+            idle: IdleOnDrop::new(&spi, pads),
+            spi,
+            miso_selection: None,
+            frequency,
+            data_mode: mode,
+            apb_clock: clocks.apb_clock,
+            via_io_mux,
+            mode: PhantomData,
+        };
+        spi.spi.setup(spi.bus_frequency(), spi.apb_clock);
         spi.spi.init();
         spi.spi.set_data_mode(mode);
 
         spi
     }
+}
 
+impl<T, M> Spi<T, M>
+where
+    T: Instance,
+{
+    /// Change the bus frequency, limited to 40 MHz on the ESP32 unless
+    /// [`Spi::routed_via_io_mux`]
     pub fn change_bus_frequency(&mut self, frequency: HertzU32, clocks: &Clocks) {
-        self.spi.ch_bus_freq(frequency, clocks);
+        self.frequency = frequency;
+        self.apb_clock = clocks.apb_clock;
+        self.spi.ch_bus_freq(self.bus_frequency(), self.apb_clock);
+    }
+
+    /// Change the bus frequency and the SPI mode
+    ///
+    /// Only the settings which differ from the current ones are written, so
+    /// this is cheap when switching between devices with the same settings.
+    pub fn apply_config(&mut self, config: &Config) {
+        if config.frequency != self.frequency {
+            self.frequency = config.frequency;
+            self.spi.ch_bus_freq(self.bus_frequency(), self.apb_clock);
+        }
+        if config.mode != self.data_mode {
+            self.data_mode = config.mode;
+            self.spi.set_data_mode(config.mode);
+        }
+    }
+
+    /// Check if all pins bypass the GPIO matrix, which allows more than
+    /// 40 MHz on the ESP32
+    pub fn routed_via_io_mux(&self) -> bool {
+        self.via_io_mux
+    }
+
+    /// The requested frequency, limited to what the pin routing allows
+    #[cfg(esp32)]
+    fn bus_frequency(&self) -> HertzU32 {
+        if !self.via_io_mux && self.frequency > MATRIX_MAX_FREQUENCY {
+            MATRIX_MAX_FREQUENCY
+        } else {
+            self.frequency
+        }
+    }
+
+    /// The requested frequency, the GPIO matrix doesn't limit it on this chip
+    #[cfg(not(esp32))]
+    fn bus_frequency(&self) -> HertzU32 {
+        self.frequency
+    }
+
+    /// Enable or disable looping back MOSI to MISO
+    ///
+    /// When enabled, MISO is taken from the pad MOSI is routed to, so every
+    /// transmitted byte is received again. This allows for a self-test of the
+    /// peripheral without external wiring. When disabled, the previous MISO
+    /// connection is restored.
+    ///
+    /// Returns `false` if MOSI is not routed to any pad, in which case the
+    /// loopback cannot be enabled.
+    pub fn enable_loopback(&mut self, enable: bool) -> bool {
+        if enable {
+            if self.miso_selection.is_none() {
+                self.miso_selection = crate::gpio::loopback_from_output_pad(
+                    self.spi.mosi_signal(),
+                    self.spi.miso_signal(),
+                );
+            }
+            self.miso_selection.is_some()
+        } else {
+            if let Some(previous) = self.miso_selection.take() {
+                crate::gpio::restore_input_selection(self.spi.miso_signal(), previous);
+            }
+            true
+        }
     }
 
     /// Return the raw interface to the underlying peripheral instance
+    ///
+    /// Unlike dropping the driver, this leaves the peripheral and the pins
+    /// as they are.
     pub fn free(self) -> T {
+        self.idle.release();
         self.spi
     }
+
+    /// Move the driver into another mode, the interrupts are left alone
+    fn into_mode<N>(self) -> Spi<T, N>
+    where
+        N: Mode,
+    {
+        Spi {
+            spi: self.spi,
+            miso_selection: self.miso_selection,
+            frequency: self.frequency,
+            data_mode: self.data_mode,
+            apb_clock: self.apb_clock,
+            via_io_mux: self.via_io_mux,
+            idle: self.idle,
+            mode: PhantomData,
+        }
+    }
+}
+
+impl<T> Spi<T, Blocking>
+where
+    T: Instance,
+{
+    /// Start a single byte transfer without waiting
+    ///
+    /// Returns `WouldBlock` while the previous transfer is in progress. Only
+    /// one byte is transferred at a time, the received byte is fetched with
+    /// [Spi::read_nb].
+    pub fn send_nb(&mut self, word: u8) -> nb::Result<(), Error> {
+        self.spi.write_byte(word)
+    }
+
+    /// Fetch the byte received by the last [Spi::send_nb] without waiting
+    ///
+    /// Returns `WouldBlock` while the transfer is in progress.
+    pub fn read_nb(&mut self) -> nb::Result<u8, Error> {
+        self.spi.read_byte()
+    }
+
+    /// Check if the bus is idle, without waiting
+    pub fn flush_nb(&mut self) -> nb::Result<(), Error> {
+        if self.spi.register_block().cmd.read().usr().bit_is_set() {
+            Err(nb::Error::WouldBlock)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Transfer `words` in place, giving up once `deadline` passed
+    ///
+    /// The bytes are transferred in chunks of the FIFO size. On
+    /// [Error::Timeout] the first `transferred` bytes of `words` were
+    /// exchanged, the chunk in flight is finished by the hardware but isn't
+    /// read back.
+    pub fn transfer_timeout(&mut self, words: &mut [u8], deadline: Deadline) -> Result<(), Error> {
+        let mut transferred = 0;
+
+        for chunk in words.chunks_mut(FIFO_SIZE) {
+            self.wait_idle(deadline, transferred)?;
+            self.spi.write_bytes(chunk)?;
+            self.wait_idle(deadline, transferred)?;
+            self.spi.read_bytes_from_fifo(chunk)?;
+            transferred += chunk.len();
+        }
+
+        Ok(())
+    }
+
+    /// Write `words`, giving up once `deadline` passed
+    ///
+    /// Returns once the bus is idle again. On [Error::Timeout] the first
+    /// `transferred` bytes of `words` were written, see
+    /// [Spi::transfer_timeout].
+    pub fn write_timeout(&mut self, words: &[u8], deadline: Deadline) -> Result<(), Error> {
+        let mut transferred = 0;
+
+        for chunk in words.chunks(FIFO_SIZE) {
+            self.wait_idle(deadline, transferred)?;
+            self.spi.write_bytes(chunk)?;
+            self.wait_idle(deadline, transferred)?;
+            transferred += chunk.len();
+        }
+
+        Ok(())
+    }
+
+    fn wait_idle(&mut self, deadline: Deadline, transferred: usize) -> Result<(), Error> {
+        while self.flush_nb().is_err() {
+            if deadline.is_expired() {
+                return Err(Error::Timeout { transferred });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Connect an output pin to `signal`, preferring its IO MUX function
+fn connect_output<P: OutputPin>(pin: &mut P, signal: OutputSignal) -> RoutedVia {
+    match pin
+        .set_to_push_pull_output()
+        .try_connect_peripheral_to_output_with_options(signal, false, false, false, false)
+    {
+        Ok(route) => route,
+        Err(_) => panic!("Cannot connect this peripheral to GPIO"),
+    }
+}
+
+/// Connect an input pin to `signal`, preferring its IO MUX function
+fn connect_input<P: InputPin>(pin: &mut P, signal: InputSignal) -> RoutedVia {
+    match pin
+        .set_to_input()
+        .try_connect_input_to_peripheral_with_options(signal, false, false)
+    {
+        Ok(route) => route,
+        Err(_) => panic!("Cannot connect GPIO to this peripheral"),
+    }
+}
+
+/// Returns the SPI host to an idle state when the driver owning it is dropped
+///
+/// [Spi] and [SpiDma](dma::SpiDma) pass their fields on to each other, so
+/// this is a field of both instead of a `Drop` implementation. Waits for the
+/// transaction in progress, disables the interrupts and leaves the pins given
+/// to the constructor as floating inputs. With the `gate-clocks-on-drop`
+/// feature the clock of the peripheral is gated as well.
+struct IdleOnDrop {
+    register_block: fn() -> *const RegisterBlock,
+    spi_num: u8,
+    pads: [Option<u8>; 4],
+}
+
+impl IdleOnDrop {
+    fn new<T: Instance>(spi: &T, pads: [Option<u8>; 4]) -> Self {
+        Self {
+            register_block: T::register_block_ptr,
+            spi_num: spi.spi_num(),
+            pads,
+        }
+    }
+
+    /// Leave the peripheral and the pins as they are, the driver was freed
+    fn release(self) {
+        core::mem::forget(self);
+    }
+}
+
+impl Drop for IdleOnDrop {
+    fn drop(&mut self) {
+        let reg_block = unsafe { &*(self.register_block)() };
+
+        while reg_block.cmd.read().usr().bit_is_set() {}
+
+        #[cfg(esp32)]
+        critical_section::with(|_| {
+            reg_block.slave.modify(|_, w| w.trans_inten().clear_bit());
+        });
+        #[cfg(esp32s2)]
+        critical_section::with(|_| {
+            reg_block
+                .slave
+                .modify(|_, w| w.int_trans_done_en().clear_bit());
+        });
+        reg_block.dma_int_ena.write(|w| unsafe { w.bits(0) });
+        reg_block.dma_int_clr.write(|w| unsafe { w.bits(u32::MAX) });
+
+        for pad in self.pads.iter().flatten() {
+            crate::gpio::pad::float_pad(*pad);
+        }
+
+        #[cfg(feature = "gate-clocks-on-drop")]
+        crate::system::set_peripheral_clock(
+            match self.spi_num {
+                #[cfg(spi3)]
+                3 => crate::system::Peripheral::Spi3,
+                _ => crate::system::Peripheral::Spi2,
+            },
+            false,
+        );
+    }
+}
+
+impl<T, M> ClockListener for Spi<T, M>
+where
+    T: Instance,
+{
+    /// Re-derives the clock divider for the configured bus frequency.
+    fn clocks_changed(&mut self, clocks: &Clocks) {
+        self.apb_clock = clocks.apb_clock;
+        self.spi.ch_bus_freq(self.bus_frequency(), self.apb_clock);
+    }
 }
 
 impl<T> embedded_hal::spi::FullDuplex<u8> for Spi<T>
@@ -270,10 +655,10 @@ pub mod dma {
 
     use embedded_dma::{ReadBuffer, WriteBuffer};
 
-    #[cfg(any(esp32, esp32s2))]
+    #[cfg(spi3)]
     use super::Spi3Instance;
-    use super::{Instance, InstanceDma, Spi, Spi2Instance, MAX_DMA_SIZE};
-    #[cfg(any(esp32, esp32s2))]
+    use super::{IdleOnDrop, Instance, InstanceDma, Spi, Spi2Instance, MAX_DMA_SIZE};
+    #[cfg(spi3)]
     use crate::dma::private::Spi3Peripheral;
     use crate::dma::{
         private::{Rx, Spi2Peripheral, SpiPeripheral, Tx},
@@ -292,7 +677,7 @@ pub mod dma {
         fn with_dma(self, channel: Channel<TX, RX, P>) -> SpiDma<T, TX, RX, P>;
     }
 
-    #[cfg(any(esp32, esp32s2))]
+    #[cfg(spi3)]
     pub trait WithDmaSpi3<T, RX, TX, P>
     where
         T: Instance + Spi3Instance,
@@ -316,11 +701,12 @@ pub mod dma {
             SpiDma {
                 spi: self.spi,
                 channel,
+                idle: self.idle,
             }
         }
     }
 
-    #[cfg(any(esp32, esp32s2))]
+    #[cfg(spi3)]
     impl<T, RX, TX, P> WithDmaSpi3<T, RX, TX, P> for Spi<T>
     where
         T: Instance + Spi3Instance,
@@ -334,10 +720,14 @@ pub mod dma {
             SpiDma {
                 spi: self.spi,
                 channel,
+                idle: self.idle,
             }
         }
     }
     /// An in-progress DMA transfer
+    ///
+    /// Dropping it aborts the DMA transfer, unlike [DmaTransfer::wait], the
+    /// bytes clocked out after that are undefined.
     pub struct SpiDmaTransferRxTx<T, TX, RX, P, RBUFFER, TBUFFER>
     where
         T: InstanceDma<TX, RX>,
@@ -388,11 +778,17 @@ pub mod dma {
         P: SpiPeripheral,
     {
         fn drop(&mut self) {
+            self.spi_dma.channel.tx.stop();
+            self.spi_dma.channel.rx.stop();
+            // the transaction is clocked out nonetheless
             self.spi_dma.spi.flush().ok();
         }
     }
 
     /// An in-progress DMA transfer.
+    ///
+    /// Dropping it aborts the DMA transfer, unlike [DmaTransfer::wait], the
+    /// bytes clocked out after that are undefined.
     pub struct SpiDmaTransfer<T, TX, RX, P, BUFFER>
     where
         T: InstanceDma<TX, RX>,
@@ -441,6 +837,9 @@ pub mod dma {
         P: SpiPeripheral,
     {
         fn drop(&mut self) {
+            self.spi_dma.channel.tx.stop();
+            self.spi_dma.channel.rx.stop();
+            // the transaction is clocked out nonetheless
             self.spi_dma.spi.flush().ok();
         }
     }
@@ -454,6 +853,7 @@ pub mod dma {
     {
         pub(crate) spi: T,
         pub(crate) channel: Channel<TX, RX, P>,
+        idle: IdleOnDrop,
     }
 
     impl<T, TX, RX, P> SpiDma<T, TX, RX, P>
@@ -464,7 +864,11 @@ pub mod dma {
         P: SpiPeripheral,
     {
         /// Return the raw interface to the underlying peripheral instance
+        ///
+        /// Unlike dropping the driver, this leaves the peripheral and the pins
+        /// as they are.
         pub fn free(self) -> T {
+            self.idle.release();
             self.spi
         }
 
@@ -557,6 +961,155 @@ pub mod dma {
         }
     }
 
+    /// A 4-byte aligned DMA buffer of `N` bytes, see [DmaDoubleBuffer]
+    #[repr(C, align(4))]
+    pub struct DmaBuffer<const N: usize>(pub [u8; N]);
+
+    impl<const N: usize> DmaBuffer<N> {
+        pub const fn new() -> Self {
+            Self([0; N])
+        }
+    }
+
+    /// Ping-pong buffers for DMA writes, e.g. to a display
+    ///
+    /// While the front buffer is sent, the back buffer is rendered through
+    /// [DmaDoubleBuffer::back_mut]. [DmaDoubleBuffer::swap] waits for the
+    /// front transfer and starts sending the back buffer right away, which
+    /// becomes the front buffer:
+    ///
+    /// ```no_run
+    /// static mut BUFFERS: [DmaBuffer<25600>; 2] = [DmaBuffer::new(), DmaBuffer::new()];
+    ///
+    /// let mut frames = DmaDoubleBuffer::new(spi_dma, unsafe { &mut BUFFERS });
+    /// loop {
+    ///     render(frames.back_mut());
+    ///     frames.swap().unwrap();
+    /// }
+    /// ```
+    ///
+    /// `N` is checked at compile time, it has to be a multiple of 4 and at
+    /// most 32736 bytes. The TX channel needs `N / 4092 + 1` descriptors
+    /// (3 words each), otherwise the first swap returns
+    /// [DmaError::DescriptorsExhausted](crate::dma::DmaError::DescriptorsExhausted).
+    pub struct DmaDoubleBuffer<T, TX, RX, P, const N: usize>
+    where
+        TX: Tx,
+        RX: Rx,
+        P: SpiPeripheral,
+    {
+        spi_dma: SpiDma<T, TX, RX, P>,
+        buffers: &'static mut [DmaBuffer<N>; 2],
+        back: usize,
+        in_flight: bool,
+        swaps: u32,
+        missed_swaps: u32,
+    }
+
+    impl<T, TX, RX, P, const N: usize> DmaDoubleBuffer<T, TX, RX, P, N>
+    where
+        T: InstanceDma<TX, RX>,
+        TX: Tx,
+        RX: Rx,
+        P: SpiPeripheral,
+    {
+        const VALID: () = {
+            assert!(
+                N > 0 && N % 4 == 0,
+                "DMA buffers have to be a multiple of 4 bytes"
+            );
+            assert!(N <= MAX_DMA_SIZE, "DMA buffers can be at most 32736 bytes");
+        };
+
+        /// Send the two `buffers` alternately through `spi_dma`
+        pub fn new(spi_dma: SpiDma<T, TX, RX, P>, buffers: &'static mut [DmaBuffer<N>; 2]) -> Self {
+            #[allow(clippy::let_unit_value)]
+            let _ = Self::VALID;
+
+            Self {
+                spi_dma,
+                buffers,
+                back: 0,
+                in_flight: false,
+                swaps: 0,
+                missed_swaps: 0,
+            }
+        }
+
+        /// The buffer to render the next frame into
+        pub fn back_mut(&mut self) -> &mut [u8] {
+            &mut self.buffers[self.back].0
+        }
+
+        /// Wait for the front transfer and start sending the back buffer
+        pub fn swap(&mut self) -> Result<(), super::Error> {
+            self.swap_with(|_| Ok(()))
+        }
+
+        /// Like [DmaDoubleBuffer::swap], but runs `before` between the two
+        /// transfers, e.g. to send the command which starts a frame
+        pub fn swap_with<F>(&mut self, before: F) -> Result<(), super::Error>
+        where
+            F: FnOnce(&mut SpiDma<T, TX, RX, P>) -> Result<(), super::Error>,
+        {
+            if self.in_flight && self.is_idle() {
+                self.missed_swaps += 1;
+            }
+            self.flush();
+
+            before(&mut self.spi_dma)?;
+
+            let buffer = &self.buffers[self.back].0;
+            self.spi_dma.spi.start_write_bytes_dma(
+                buffer.as_ptr(),
+                N,
+                &mut self.spi_dma.channel.tx,
+            )?;
+
+            self.in_flight = true;
+            self.back ^= 1;
+            self.swaps += 1;
+            Ok(())
+        }
+
+        /// Whether the front buffer was sent completely
+        pub fn is_idle(&self) -> bool {
+            self.spi_dma
+                .spi
+                .register_block()
+                .cmd
+                .read()
+                .usr()
+                .bit_is_clear()
+        }
+
+        /// Wait until the front buffer was sent completely
+        pub fn flush(&mut self) {
+            if self.in_flight {
+                self.spi_dma.spi.flush().ok();
+                self.in_flight = false;
+            }
+        }
+
+        /// Number of swaps
+        pub fn swaps(&self) -> u32 {
+            self.swaps
+        }
+
+        /// Number of swaps which found the front transfer already finished,
+        /// i.e. the bus was idle waiting for the next buffer to be rendered
+        pub fn missed_swaps(&self) -> u32 {
+            self.missed_swaps
+        }
+
+        /// Wait for the front transfer and return the SPI instance and the
+        /// buffers
+        pub fn free(mut self) -> (SpiDma<T, TX, RX, P>, &'static mut [DmaBuffer<N>; 2]) {
+            self.flush();
+            (self.spi_dma, self.buffers)
+        }
+    }
+
     impl<T, TX, RX, P> embedded_hal::blocking::spi::Transfer<u8> for SpiDma<T, TX, RX, P>
     where
         T: InstanceDma<TX, RX>,
@@ -755,10 +1308,10 @@ mod ehal1 {
         /// simultaneously.
         fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
             // Optimizations
-            if read.len() == 0 {
-                SpiBusWrite::write(self, write)?;
-            } else if write.len() == 0 {
-                SpiBusRead::read(self, read)?;
+            if read.is_empty() {
+                return SpiBusWrite::write(self, write);
+            } else if write.is_empty() {
+                return SpiBusRead::read(self, read);
             }
 
             let mut write_from = 0;
@@ -780,7 +1333,7 @@ mod ehal1 {
                     // Read more than we write, must pad writing part with zeros
                     let mut empty = [EMPTY_WRITE_PAD; FIFO_SIZE];
                     empty[0..write_inc].copy_from_slice(&write[write_from..write_to]);
-                    SpiBusWrite::write(self, &empty)?;
+                    SpiBusWrite::write(self, &empty[..read_inc])?;
                 } else {
                     SpiBusWrite::write(self, &write[write_from..write_to])?;
                 }
@@ -829,6 +1382,9 @@ mod ehal1 {
     /// Has exclusive access to an SPI bus, which is managed via a `Mutex`. Used
     /// as basis for the [`SpiBusDevice`] implementation. Note that the
     /// wrapped [`RefCell`] is used solely to achieve interior mutability.
+    ///
+    /// The bus is locked in a critical section for every transaction, so
+    /// devices can be used from interrupt handlers of any priority.
     pub struct SpiBusController<I: Instance> {
         lock: critical_section::Mutex<RefCell<Spi<I>>>,
     }
@@ -844,15 +1400,64 @@ mod ehal1 {
             }
         }
 
+        /// Add a device selected by [`ChipSelect::Routed`], using the settings
+        /// of the bus
         pub fn add_device<'a, CS: OutputPin>(&'a self, cs: CS) -> SpiBusDevice<'a, I, CS> {
             SpiBusDevice::new(self, cs)
         }
+
+        /// Add a device selected by `chip_select` on the pin `cs`, the bus is
+        /// switched to `config` for each of its transactions
+        pub fn add_device_with_config<'a, CS: OutputPin>(
+            &'a self,
+            cs: CS,
+            chip_select: ChipSelect,
+            config: Config,
+        ) -> SpiBusDevice<'a, I, CS> {
+            SpiBusDevice::new_with_config(self, cs, chip_select, config)
+        }
     }
 
     impl<I: Instance> ErrorType for SpiBusController<I> {
         type Error = spi::ErrorKind;
     }
 
+    /// How an [`SpiBusDevice`] selects its device
+    ///
+    /// The CS lines of the SPI host are driven by the hardware for every
+    /// chunk of a transaction (the size of the FIFO), so they go high
+    /// between chunks. Devices which need CS to stay low for the whole
+    /// transaction, e.g. SD cards, are selected by [`ChipSelect::Software`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ChipSelect {
+        /// CS line 0 of the SPI host, connected to the pin only during the
+        /// transactions of the device
+        ///
+        /// Any number of devices can share this line. Don't combine it with
+        /// a device on [`ChipSelect::Cs0`], whose pin would be selected too.
+        Routed,
+        /// CS line 0 of the SPI host, connected to the pin for good
+        Cs0,
+        /// CS line 1 of the SPI host, connected to the pin for good
+        Cs1,
+        /// CS line 2 of the SPI host, connected to the pin for good
+        Cs2,
+        /// The pin is driven low before and high after each transaction
+        Software,
+    }
+
+    impl ChipSelect {
+        /// The CS line of the host driven during transactions
+        fn line(self) -> Option<u8> {
+            match self {
+                ChipSelect::Routed | ChipSelect::Cs0 => Some(0),
+                ChipSelect::Cs1 => Some(1),
+                ChipSelect::Cs2 => Some(2),
+                ChipSelect::Software => None,
+            }
+        }
+    }
+
     /// An SPI device on a shared SPI bus.
     ///
     /// Provides device specific access on a shared SPI bus. Enables attaching
@@ -865,6 +1470,8 @@ mod ehal1 {
     {
         bus: &'a SpiBusController<I>,
         cs: CS,
+        chip_select: ChipSelect,
+        config: Option<Config>,
     }
 
     impl<'a, I, CS> SpiBusDevice<'a, I, CS>
@@ -874,7 +1481,45 @@ mod ehal1 {
     {
         pub fn new(bus: &'a SpiBusController<I>, mut cs: CS) -> Self {
             cs.set_to_push_pull_output().set_output_high(true);
-            SpiBusDevice { bus, cs }
+            SpiBusDevice {
+                bus,
+                cs,
+                chip_select: ChipSelect::Routed,
+                config: None,
+            }
+        }
+
+        /// Create a device selected by `chip_select` on the pin `cs`, the bus
+        /// is switched to `config` for each of its transactions
+        pub fn new_with_config(
+            bus: &'a SpiBusController<I>,
+            mut cs: CS,
+            chip_select: ChipSelect,
+            config: Config,
+        ) -> Self {
+            cs.set_to_push_pull_output().set_output_high(true);
+            match (chip_select, chip_select.line()) {
+                (ChipSelect::Routed, _) | (_, None) => (),
+                (_, Some(line)) => {
+                    let signal = critical_section::with(|token| {
+                        bus.lock.borrow_ref(token).spi.cs_signals()[line as usize]
+                    });
+                    cs.connect_peripheral_to_output(signal);
+                }
+            }
+
+            SpiBusDevice {
+                bus,
+                cs,
+                chip_select,
+                config: Some(config),
+            }
+        }
+
+        /// Change the bus settings used for the transactions of this device,
+        /// e.g. to speed up an SD card after its initialization
+        pub fn set_config(&mut self, config: Config) {
+            self.config = Some(config);
         }
     }
 
@@ -900,14 +1545,34 @@ mod ehal1 {
             critical_section::with(|cs| {
                 let mut bus = self.bus.lock.borrow_ref_mut(cs);
 
-                self.cs.connect_peripheral_to_output(bus.spi.cs_signal());
+                if let Some(config) = &self.config {
+                    bus.apply_config(config);
+                }
+                bus.spi.enable_cs(self.chip_select.line());
+                match self.chip_select {
+                    ChipSelect::Routed => {
+                        self.cs.connect_peripheral_to_output(bus.spi.cs_signal());
+                    }
+                    ChipSelect::Software => {
+                        self.cs.set_output_high(false);
+                    }
+                    _ => (),
+                }
 
                 // We postpone handling these errors until AFTER we raised CS again, so the bus
                 // is free (Or we die trying if CS errors).
                 let f_res = f(&mut bus);
                 let flush_res = bus.flush();
 
-                self.cs.disconnect_peripheral_from_output();
+                match self.chip_select {
+                    ChipSelect::Routed => {
+                        self.cs.disconnect_peripheral_from_output();
+                    }
+                    ChipSelect::Software => {
+                        self.cs.set_output_high(true);
+                    }
+                    _ => (),
+                }
 
                 let f_res = f_res.map_err(|_| spi::ErrorKind::Other)?;
                 flush_res.map_err(|_| spi::ErrorKind::Other)?;
@@ -1076,7 +1741,7 @@ where
     fn dma_peripheral(&self) -> DmaPeripheral {
         match self.spi_num() {
             2 => DmaPeripheral::Spi2,
-            #[cfg(any(esp32, esp32s2))]
+            #[cfg(spi3)]
             3 => DmaPeripheral::Spi3,
             _ => panic!("Illegal SPI instance"),
         }
@@ -1144,7 +1809,7 @@ where
 {
 }
 
-#[cfg(any(esp32, esp32s2, esp32s3))]
+#[cfg(spi3)]
 impl<TX, RX> InstanceDma<TX, RX> for crate::pac::SPI3
 where
     TX: Tx,
@@ -1155,6 +1820,13 @@ where
 pub trait Instance {
     fn register_block(&self) -> &RegisterBlock;
 
+    /// Register block of this SPI without an instance, used by the interrupt
+    /// handler of the async mode
+    fn register_block_ptr() -> *const RegisterBlock;
+
+    /// Interrupt of this SPI, e.g. to enable it with `interrupt::enable`
+    fn interrupt(&self) -> Interrupt;
+
     fn sclk_signal(&self) -> OutputSignal;
 
     fn mosi_signal(&self) -> OutputSignal;
@@ -1163,6 +1835,9 @@ pub trait Instance {
 
     fn cs_signal(&self) -> OutputSignal;
 
+    /// Signals of the CS lines 0 to 2, [`Instance::cs_signal`] is the first
+    fn cs_signals(&self) -> [OutputSignal; 3];
+
     fn enable_peripheral(&self, peripheral_clock_control: &mut PeripheralClockControl);
 
     fn spi_num(&self) -> u8;
@@ -1209,9 +1884,8 @@ pub trait Instance {
     }
 
     // taken from https://github.com/apache/incubator-nuttx/blob/8267a7618629838231256edfa666e44b5313348e/arch/risc-v/src/esp32c3/esp32c3_spi.c#L496
-    fn setup(&mut self, frequency: HertzU32, clocks: &Clocks) {
-        // FIXME: this might not be always true
-        let apb_clk_freq: HertzU32 = HertzU32::Hz(clocks.apb_clock.to_Hz());
+    fn setup(&mut self, frequency: HertzU32, apb_clk_freq: HertzU32) {
+        // FIXME: this might not be always true, the APB clock is assumed as the source
 
         let reg_val: u32;
         let duty_cycle = 128;
@@ -1341,7 +2015,7 @@ pub trait Instance {
         self
     }
 
-    fn ch_bus_freq(&mut self, frequency: HertzU32, clocks: &Clocks) {
+    fn ch_bus_freq(&mut self, frequency: HertzU32, apb_clk_freq: HertzU32) {
         // Disable clock source
         #[cfg(not(any(feature = "esp32", feature = "esp32s2")))]
         self.register_block().clk_gate.modify(|_, w| {
@@ -1354,7 +2028,7 @@ pub trait Instance {
         });
 
         // Change clock frequency
-        self.setup(frequency, clocks);
+        self.setup(frequency, apb_clk_freq);
 
         // Enable clock source
         #[cfg(not(any(feature = "esp32", feature = "esp32s2")))]
@@ -1406,46 +2080,46 @@ pub trait Instance {
     /// [`flush`].
     // FIXME: See below.
     fn write_bytes(&mut self, words: &[u8]) -> Result<(), Error> {
-        let reg_block = self.register_block();
-        let num_chunks = words.len() / FIFO_SIZE;
-
         // The fifo has a limited fixed size, so the data must be chunked and then
         // transmitted
-        for (i, chunk) in words.chunks(FIFO_SIZE).enumerate() {
-            self.configure_datalen(chunk.len() as u32 * 8);
-
-            let fifo_ptr = reg_block.w0.as_ptr();
-            unsafe {
-                // It seems that `copy_nonoverlapping` is significantly faster than regular
-                // `copy`, by about 20%... ?
-                core::ptr::copy_nonoverlapping::<u32>(
-                    chunk.as_ptr() as *const u32,
-                    fifo_ptr as *mut u32,
-                    // FIXME: Using any other transfer length **does not work**. I don't understand
-                    // why.
-                    FIFO_SIZE / 4,
-                );
-            }
-
-            self.update();
-
-            reg_block.cmd.modify(|_, w| w.usr().set_bit());
-
-            // Wait for all chunks to complete except the last one.
-            // The function is allowed to return before the bus is idle.
+        for chunk in words.chunks(FIFO_SIZE) {
+            // Wait for the previous chunk to complete, which may be the last one of
+            // the previous call: the function is allowed to return before the bus is
+            // idle.
             // see [embedded-hal flushing](https://docs.rs/embedded-hal/1.0.0-alpha.8/embedded_hal/spi/blocking/index.html#flushing)
             //
             // THIS IS NOT TRUE FOR EH 0.2.X! MAKE SURE TO FLUSH IN EH 0.2.X TRAIT
             // IMPLEMENTATIONS!
-            if i < num_chunks {
-                while reg_block.cmd.read().usr().bit_is_set() {
-                    // wait
-                }
-            }
+            self.flush()?;
+            self.start_chunk(chunk);
         }
         Ok(())
     }
 
+    /// Copy `chunk`, at most the size of the FIFO, into the FIFO and start
+    /// transferring it without waiting
+    fn start_chunk(&mut self, chunk: &[u8]) {
+        let reg_block = self.register_block();
+        self.configure_datalen(chunk.len() as u32 * 8);
+
+        let fifo_ptr = reg_block.w0.as_ptr();
+        unsafe {
+            // It seems that `copy_nonoverlapping` is significantly faster than regular
+            // `copy`, by about 20%... ?
+            core::ptr::copy_nonoverlapping::<u32>(
+                chunk.as_ptr() as *const u32,
+                fifo_ptr as *mut u32,
+                // FIXME: Using any other transfer length **does not work**. I don't understand
+                // why.
+                FIFO_SIZE / 4,
+            );
+        }
+
+        self.update();
+
+        reg_block.cmd.modify(|_, w| w.usr().set_bit());
+    }
+
     /// Read bytes from SPI.
     ///
     /// Sends out a stuffing byte for every byte to read. This function doesn't
@@ -1530,6 +2204,32 @@ pub trait Instance {
         // not need/available on ESP32/ESP32S2
     }
 
+    /// Let transfers drive only the CS line `line` (0 to 2), or none
+    #[cfg(esp32)]
+    fn enable_cs(&self, line: Option<u8>) {
+        self.register_block().pin.modify(|_, w| {
+            w.cs0_dis()
+                .bit(line != Some(0))
+                .cs1_dis()
+                .bit(line != Some(1))
+                .cs2_dis()
+                .bit(line != Some(2))
+        });
+    }
+
+    /// Let transfers drive only the CS line `line` (0 to 2), or none
+    #[cfg(not(esp32))]
+    fn enable_cs(&self, line: Option<u8>) {
+        self.register_block().misc.modify(|_, w| {
+            w.cs0_dis()
+                .bit(line != Some(0))
+                .cs1_dis()
+                .bit(line != Some(1))
+                .cs2_dis()
+                .bit(line != Some(2))
+        });
+    }
+
     fn configure_datalen(&self, len: u32) {
         let reg_block = self.register_block();
 
@@ -1558,6 +2258,16 @@ impl Instance for crate::pac::SPI2 {
         self
     }
 
+    #[inline(always)]
+    fn register_block_ptr() -> *const RegisterBlock {
+        crate::pac::SPI2::ptr()
+    }
+
+    #[inline(always)]
+    fn interrupt(&self) -> Interrupt {
+        Interrupt::SPI2
+    }
+
     #[inline(always)]
     fn sclk_signal(&self) -> OutputSignal {
         OutputSignal::FSPICLK_MUX
@@ -1578,6 +2288,15 @@ impl Instance for crate::pac::SPI2 {
         OutputSignal::FSPICS0
     }
 
+    #[inline(always)]
+    fn cs_signals(&self) -> [OutputSignal; 3] {
+        [
+            OutputSignal::FSPICS0,
+            OutputSignal::FSPICS1,
+            OutputSignal::FSPICS2,
+        ]
+    }
+
     #[inline(always)]
     fn enable_peripheral(&self, peripheral_clock_control: &mut PeripheralClockControl) {
         peripheral_clock_control.enable(crate::system::Peripheral::Spi2);
@@ -1596,6 +2315,16 @@ impl Instance for crate::pac::SPI2 {
         self
     }
 
+    #[inline(always)]
+    fn register_block_ptr() -> *const RegisterBlock {
+        crate::pac::SPI2::ptr()
+    }
+
+    #[inline(always)]
+    fn interrupt(&self) -> Interrupt {
+        Interrupt::SPI2
+    }
+
     #[inline(always)]
     fn sclk_signal(&self) -> OutputSignal {
         OutputSignal::HSPICLK
@@ -1616,6 +2345,15 @@ impl Instance for crate::pac::SPI2 {
         OutputSignal::HSPICS0
     }
 
+    #[inline(always)]
+    fn cs_signals(&self) -> [OutputSignal; 3] {
+        [
+            OutputSignal::HSPICS0,
+            OutputSignal::HSPICS1,
+            OutputSignal::HSPICS2,
+        ]
+    }
+
     #[inline(always)]
     fn enable_peripheral(&self, peripheral_clock_control: &mut PeripheralClockControl) {
         peripheral_clock_control.enable(crate::system::Peripheral::Spi2);
@@ -1634,6 +2372,16 @@ impl Instance for crate::pac::SPI3 {
         self
     }
 
+    #[inline(always)]
+    fn register_block_ptr() -> *const RegisterBlock {
+        crate::pac::SPI3::ptr()
+    }
+
+    #[inline(always)]
+    fn interrupt(&self) -> Interrupt {
+        Interrupt::SPI3
+    }
+
     #[inline(always)]
     fn sclk_signal(&self) -> OutputSignal {
         OutputSignal::VSPICLK
@@ -1654,6 +2402,15 @@ impl Instance for crate::pac::SPI3 {
         OutputSignal::VSPICS0
     }
 
+    #[inline(always)]
+    fn cs_signals(&self) -> [OutputSignal; 3] {
+        [
+            OutputSignal::VSPICS0,
+            OutputSignal::VSPICS1,
+            OutputSignal::VSPICS2,
+        ]
+    }
+
     #[inline(always)]
     fn enable_peripheral(&self, peripheral_clock_control: &mut PeripheralClockControl) {
         peripheral_clock_control.enable(crate::system::Peripheral::Spi3)
@@ -1672,6 +2429,16 @@ impl Instance for crate::pac::SPI2 {
         self
     }
 
+    #[inline(always)]
+    fn register_block_ptr() -> *const RegisterBlock {
+        crate::pac::SPI2::ptr()
+    }
+
+    #[inline(always)]
+    fn interrupt(&self) -> Interrupt {
+        Interrupt::SPI2
+    }
+
     #[inline(always)]
     fn sclk_signal(&self) -> OutputSignal {
         OutputSignal::FSPICLK
@@ -1692,6 +2459,15 @@ impl Instance for crate::pac::SPI2 {
         OutputSignal::FSPICS0
     }
 
+    #[inline(always)]
+    fn cs_signals(&self) -> [OutputSignal; 3] {
+        [
+            OutputSignal::FSPICS0,
+            OutputSignal::FSPICS1,
+            OutputSignal::FSPICS2,
+        ]
+    }
+
     #[inline(always)]
     fn enable_peripheral(&self, peripheral_clock_control: &mut PeripheralClockControl) {
         peripheral_clock_control.enable(crate::system::Peripheral::Spi2)
@@ -1710,6 +2486,16 @@ impl Instance for crate::pac::SPI3 {
         self
     }
 
+    #[inline(always)]
+    fn register_block_ptr() -> *const RegisterBlock {
+        crate::pac::SPI3::ptr()
+    }
+
+    #[inline(always)]
+    fn interrupt(&self) -> Interrupt {
+        Interrupt::SPI3
+    }
+
     #[inline(always)]
     fn sclk_signal(&self) -> OutputSignal {
         OutputSignal::SPI3_CLK
@@ -1730,6 +2516,15 @@ impl Instance for crate::pac::SPI3 {
         OutputSignal::SPI3_CS0
     }
 
+    #[inline(always)]
+    fn cs_signals(&self) -> [OutputSignal; 3] {
+        [
+            OutputSignal::SPI3_CS0,
+            OutputSignal::SPI3_CS1,
+            OutputSignal::SPI3_CS2,
+        ]
+    }
+
     #[inline(always)]
     fn enable_peripheral(&self, peripheral_clock_control: &mut PeripheralClockControl) {
         peripheral_clock_control.enable(crate::system::Peripheral::Spi3)
@@ -1743,10 +2538,10 @@ impl Instance for crate::pac::SPI3 {
 
 pub trait Spi2Instance {}
 
-#[cfg(any(esp32, esp32s2, esp32s3))]
+#[cfg(spi3)]
 pub trait Spi3Instance {}
 
 impl Spi2Instance for crate::pac::SPI2 {}
 
-#[cfg(any(esp32, esp32s2, esp32s3))]
+#[cfg(spi3)]
 impl Spi3Instance for crate::pac::SPI3 {}
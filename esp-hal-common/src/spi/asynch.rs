@@ -0,0 +1,271 @@
+//! Async SPI driver mode
+//!
+//! [Spi::into_async] enables the interrupt of the SPI host and returns the
+//! driver in [Async] mode, where transfers return futures. The bytes are
+//! transferred in chunks of the FIFO size like in [Blocking] mode, the
+//! futures are woken by the transfer-done interrupt after every chunk. Its
+//! handler has to call [handle_interrupt]:
+//!
+//! ```no_run
+//! let mut spi = Spi::new(peripherals.SPI2 /* ... */).into_async()?;
+//!
+//! #[interrupt]
+//! fn SPI2() {
+//!     spi::asynch::handle_interrupt::<SPI2>();
+//! }
+//!
+//! spi.write_async(&[0x9f]).await?;
+//! spi.transfer_async(&mut buffer).await?;
+//! ```
+//!
+//! [Spi::into_blocking] waits for the bus to be idle and disables the
+//! interrupt again.
+//!
+//! Dropping a future before it completed stops listening, the chunk in
+//! flight is finished by the hardware but isn't read back.
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use embassy_sync::waitqueue::AtomicWaker;
+
+use super::{Error, Instance, RegisterBlock, Spi, FIFO_SIZE};
+use crate::{
+    interrupt,
+    mode::{Async, Blocking},
+    pac::SPI2,
+};
+
+#[cfg(spi3)]
+const HOSTS: usize = 2;
+#[cfg(not(spi3))]
+const HOSTS: usize = 1;
+
+#[allow(clippy::declare_interior_mutable_const)]
+const NEW_WAKER: AtomicWaker = AtomicWaker::new();
+
+static WAKERS: [AtomicWaker; HOSTS] = [NEW_WAKER; HOSTS];
+
+fn waker<T>() -> &'static AtomicWaker
+where
+    T: Instance,
+{
+    let index = if T::register_block_ptr() == SPI2::ptr() {
+        0
+    } else {
+        1
+    };
+
+    &WAKERS[index]
+}
+
+// The ESP32 and ESP32-S2 signal the end of a transfer in the SLAVE register,
+// which also holds the configuration of the slave mode. The enable bit is
+// named differently on both chips.
+#[cfg(esp32)]
+fn listen(spi: &RegisterBlock, enable: bool) {
+    critical_section::with(|_| {
+        spi.slave.modify(|_, w| w.trans_inten().bit(enable));
+    });
+}
+
+#[cfg(esp32s2)]
+fn listen(spi: &RegisterBlock, enable: bool) {
+    critical_section::with(|_| {
+        spi.slave.modify(|_, w| w.int_trans_done_en().bit(enable));
+    });
+}
+
+#[cfg(any(esp32, esp32s2))]
+fn clear_trans_done(spi: &RegisterBlock) {
+    critical_section::with(|_| {
+        spi.slave.modify(|_, w| w.trans_done().clear_bit());
+    });
+}
+
+#[cfg(esp32)]
+fn trans_done_pending(spi: &RegisterBlock) -> bool {
+    let slave = spi.slave.read();
+    slave.trans_inten().bit_is_set() && slave.trans_done().bit_is_set()
+}
+
+#[cfg(esp32s2)]
+fn trans_done_pending(spi: &RegisterBlock) -> bool {
+    let slave = spi.slave.read();
+    slave.int_trans_done_en().bit_is_set() && slave.trans_done().bit_is_set()
+}
+
+#[cfg(not(any(esp32, esp32s2)))]
+fn listen(spi: &RegisterBlock, enable: bool) {
+    critical_section::with(|_| {
+        spi.dma_int_ena
+            .modify(|_, w| w.trans_done_int_ena().bit(enable));
+    });
+}
+
+#[cfg(not(any(esp32, esp32s2)))]
+fn clear_trans_done(spi: &RegisterBlock) {
+    spi.dma_int_clr.write(|w| w.trans_done_int_clr().set_bit());
+}
+
+#[cfg(not(any(esp32, esp32s2)))]
+fn trans_done_pending(spi: &RegisterBlock) -> bool {
+    spi.dma_int_st.read().trans_done_int_st().bit_is_set()
+}
+
+impl<T> Spi<T, Blocking>
+where
+    T: Instance,
+{
+    /// Turn the driver into an [Async] one
+    ///
+    /// Enables the interrupt of the SPI host at priority 1, its handler has
+    /// to call [handle_interrupt]. Fails if the interrupt can't be enabled,
+    /// the driver is dropped in that case.
+    pub fn into_async(self) -> Result<Spi<T, Async>, interrupt::Error> {
+        interrupt::enable(self.spi.interrupt(), interrupt::Priority::Priority1)?;
+        Ok(self.into_mode())
+    }
+}
+
+impl<T> Spi<T, Async>
+where
+    T: Instance,
+{
+    /// Turn the driver back into a [Blocking] one
+    ///
+    /// Waits for the bus to be idle, then disables the transfer-done
+    /// interrupt and the interrupt of the SPI host.
+    pub fn into_blocking(mut self) -> Spi<T, Blocking> {
+        self.spi.flush().ok();
+        listen(self.spi.register_block(), false);
+        interrupt::disable(crate::get_core(), self.spi.interrupt());
+        self.into_mode()
+    }
+
+    /// Transfer `words` in place, the returned future completes once the
+    /// last chunk was read back
+    pub fn transfer_async<'a>(&'a mut self, words: &'a mut [u8]) -> TransferFuture<'a, T> {
+        TransferFuture::new(&mut self.spi, Words::ReadWrite(words))
+    }
+
+    /// Write `words`, the returned future completes once the bus is idle
+    /// again
+    pub fn write_async<'a>(&'a mut self, words: &'a [u8]) -> TransferFuture<'a, T> {
+        TransferFuture::new(&mut self.spi, Words::Write(words))
+    }
+}
+
+enum Words<'a> {
+    Write(&'a [u8]),
+    ReadWrite(&'a mut [u8]),
+}
+
+impl<'a> Words<'a> {
+    fn len(&self) -> usize {
+        match self {
+            Words::Write(words) => words.len(),
+            Words::ReadWrite(words) => words.len(),
+        }
+    }
+}
+
+/// Future returned by [Spi::transfer_async] and [Spi::write_async]
+pub struct TransferFuture<'a, T>
+where
+    T: Instance,
+{
+    spi: &'a mut T,
+    words: Words<'a>,
+    /// Start of the chunk in flight, or of the next chunk
+    position: usize,
+    in_flight: bool,
+}
+
+impl<'a, T> TransferFuture<'a, T>
+where
+    T: Instance,
+{
+    fn new(spi: &'a mut T, words: Words<'a>) -> Self {
+        Self {
+            spi,
+            words,
+            position: 0,
+            in_flight: false,
+        }
+    }
+}
+
+impl<'a, T> Future for TransferFuture<'a, T>
+where
+    T: Instance,
+{
+    type Output = Result<(), Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        waker::<T>().register(cx.waker());
+
+        let this = &mut *self;
+        loop {
+            if this.spi.register_block().cmd.read().usr().bit_is_set() {
+                // the flag was cleared before the chunk was started, it is
+                // raised once the chunk is done
+                listen(this.spi.register_block(), true);
+                return Poll::Pending;
+            }
+
+            let end = this.words.len().min(this.position + FIFO_SIZE);
+            if this.in_flight {
+                if let Words::ReadWrite(words) = &mut this.words {
+                    this.spi
+                        .read_bytes_from_fifo(&mut words[this.position..end])?;
+                }
+                this.position = end;
+                this.in_flight = false;
+                continue;
+            }
+
+            if this.position == this.words.len() {
+                return Poll::Ready(Ok(()));
+            }
+
+            let chunk = match &this.words {
+                Words::Write(words) => &words[this.position..end],
+                Words::ReadWrite(words) => &words[this.position..end],
+            };
+            clear_trans_done(this.spi.register_block());
+            this.spi.start_chunk(chunk);
+            this.in_flight = true;
+        }
+    }
+}
+
+impl<'a, T> Drop for TransferFuture<'a, T>
+where
+    T: Instance,
+{
+    fn drop(&mut self) {
+        listen(self.spi.register_block(), false);
+    }
+}
+
+/// Wake the future transferring on the SPI host `T`
+///
+/// To be called from the interrupt handler of the SPI host. The
+/// transfer-done interrupt is disabled until the future was polled again,
+/// the flag is left for the future to handle.
+#[procmacros::ram]
+pub fn handle_interrupt<T>()
+where
+    T: Instance,
+{
+    let spi = unsafe { &*T::register_block_ptr() };
+
+    if trans_done_pending(spi) {
+        listen(spi, false);
+        waker::<T>().wake();
+    }
+}
@@ -9,9 +9,9 @@
 //! let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
 //! ```
 #[cfg(not(esp32))]
-type SystemPeripheral = crate::pac::SYSTEM;
+pub(crate) type SystemPeripheral = crate::pac::SYSTEM;
 #[cfg(esp32)]
-type SystemPeripheral = crate::pac::DPORT;
+pub(crate) type SystemPeripheral = crate::pac::DPORT;
 
 /// Peripherals which can be enabled via [PeripheralClockControl]
 pub enum Peripheral {
@@ -28,6 +28,8 @@ pub enum Peripheral {
     Mcpwm0,
     #[cfg(any(esp32, esp32s3))]
     Mcpwm1,
+    #[cfg(pcnt)]
+    Pcnt,
     #[cfg(any(esp32c2, esp32c3))]
     ApbSarAdc,
     #[cfg(gdma)]
@@ -40,6 +42,18 @@ pub enum Peripheral {
     I2s1,
     #[cfg(usb_otg)]
     Usb,
+    #[cfg(hmac)]
+    Hmac,
+    #[cfg(ds)]
+    Ds,
+    #[cfg(twai)]
+    Twai,
+    #[cfg(emac)]
+    Emac,
+    #[cfg(sdio_slave)]
+    SdioSlave,
+    #[cfg(dedicated_gpio)]
+    DedicatedGpio,
 }
 
 /// Controls the enablement of peripheral clocks.
@@ -50,104 +64,213 @@ pub struct PeripheralClockControl {
 impl PeripheralClockControl {
     /// Enables and resets the given peripheral
     pub fn enable(&mut self, peripheral: Peripheral) {
-        let system = unsafe { &*SystemPeripheral::PTR };
+        set_peripheral_clock(peripheral, true);
+    }
 
-        #[cfg(not(esp32))]
-        let (perip_clk_en0, perip_rst_en0) = { (&system.perip_clk_en0, &system.perip_rst_en0) };
-        #[cfg(esp32)]
-        let (perip_clk_en0, perip_rst_en0) = { (&system.perip_clk_en, &system.perip_rst_en) };
+    /// Gates the clock of the given peripheral and holds it in reset
+    ///
+    /// [PeripheralClockControl::enable] brings it back in its reset state.
+    /// Drivers sharing the peripheral (e.g. the channels of a DMA controller)
+    /// stop working as well. The accelerators used internally by
+    /// `Peripheral::Hmac` and `Peripheral::Ds` (SHA, AES, RSA and HMAC) are
+    /// left running, other drivers may use them.
+    pub fn disable(&mut self, peripheral: Peripheral) {
+        set_peripheral_clock(peripheral, false);
+    }
+}
 
-        #[cfg(any(esp32c2, esp32c3, esp32s3))]
-        let (perip_clk_en1, perip_rst_en1) = { (&system.perip_clk_en1, &system.perip_rst_en1) };
+/// Enable (and release from reset) or gate (and hold in reset) the clock of
+/// `peripheral`
+///
+/// The drivers use this to gate the clock of their peripheral when they are
+/// dropped with the `gate-clocks-on-drop` feature.
+pub(crate) fn set_peripheral_clock(peripheral: Peripheral, enable: bool) {
+    let system = unsafe { &*SystemPeripheral::PTR };
 
-        match peripheral {
-            Peripheral::Spi2 => {
-                perip_clk_en0.modify(|_, w| w.spi2_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.spi2_rst().clear_bit());
-            }
-            #[cfg(spi3)]
-            Peripheral::Spi3 => {
-                perip_clk_en0.modify(|_, w| w.spi3_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.spi3_rst().clear_bit());
-            }
-            #[cfg(esp32)]
-            Peripheral::I2cExt0 => {
-                perip_clk_en0.modify(|_, w| w.i2c0_ext0_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.i2c0_ext0_rst().clear_bit());
-            }
-            #[cfg(not(esp32))]
-            Peripheral::I2cExt0 => {
-                perip_clk_en0.modify(|_, w| w.i2c_ext0_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.i2c_ext0_rst().clear_bit());
-            }
-            #[cfg(i2c1)]
-            Peripheral::I2cExt1 => {
-                perip_clk_en0.modify(|_, w| w.i2c_ext1_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.i2c_ext1_rst().clear_bit());
-            }
-            #[cfg(rmt)]
-            Peripheral::Rmt => {
-                perip_clk_en0.modify(|_, w| w.rmt_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.rmt_rst().clear_bit());
-            }
-            Peripheral::Ledc => {
-                perip_clk_en0.modify(|_, w| w.ledc_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.ledc_rst().clear_bit());
-            }
-            #[cfg(any(esp32, esp32s3))]
-            Peripheral::Mcpwm0 => {
-                perip_clk_en0.modify(|_, w| w.pwm0_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.pwm0_rst().clear_bit());
-            }
-            #[cfg(any(esp32, esp32s3))]
-            Peripheral::Mcpwm1 => {
-                perip_clk_en0.modify(|_, w| w.pwm1_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.pwm1_rst().clear_bit());
-            }
-            #[cfg(any(esp32c2, esp32c3))]
-            Peripheral::ApbSarAdc => {
-                perip_clk_en0.modify(|_, w| w.apb_saradc_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.apb_saradc_rst().clear_bit());
-            }
-            #[cfg(gdma)]
-            Peripheral::Gdma => {
-                perip_clk_en1.modify(|_, w| w.dma_clk_en().set_bit());
-                perip_rst_en1.modify(|_, w| w.dma_rst().clear_bit());
-            }
-            #[cfg(esp32)]
-            Peripheral::Dma => {
-                perip_clk_en0.modify(|_, w| w.spi_dma_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.spi_dma_rst().clear_bit());
-            }
-            #[cfg(esp32s2)]
-            Peripheral::Dma => {
-                perip_clk_en0.modify(|_, w| w.spi2_dma_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.spi2_dma_rst().clear_bit());
-                perip_clk_en0.modify(|_, w| w.spi3_dma_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.spi3_dma_rst().clear_bit());
-            }
-            #[cfg(esp32c3)]
-            Peripheral::I2s0 => {
-                // on ESP32-C3 note that i2s1_clk_en / rst is really I2s0
-                perip_clk_en0.modify(|_, w| w.i2s1_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.i2s1_rst().clear_bit());
-            }
-            #[cfg(any(esp32s3, esp32, esp32s2))]
-            Peripheral::I2s0 => {
-                perip_clk_en0.modify(|_, w| w.i2s0_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.i2s0_rst().clear_bit());
-            }
-            #[cfg(any(esp32s3, esp32))]
-            Peripheral::I2s1 => {
-                perip_clk_en0.modify(|_, w| w.i2s1_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.i2s1_rst().clear_bit());
+    #[cfg(not(esp32))]
+    let (perip_clk_en0, perip_rst_en0) = { (&system.perip_clk_en0, &system.perip_rst_en0) };
+    #[cfg(esp32)]
+    let (perip_clk_en0, perip_rst_en0) = { (&system.perip_clk_en, &system.perip_rst_en) };
+
+    #[cfg(any(esp32c2, esp32c3, esp32s3))]
+    let (perip_clk_en1, perip_rst_en1) = { (&system.perip_clk_en1, &system.perip_rst_en1) };
+
+    match peripheral {
+        Peripheral::Spi2 => {
+            perip_clk_en0.modify(|_, w| w.spi2_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.spi2_rst().bit(!enable));
+        }
+        #[cfg(spi3)]
+        Peripheral::Spi3 => {
+            perip_clk_en0.modify(|_, w| w.spi3_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.spi3_rst().bit(!enable));
+        }
+        #[cfg(esp32)]
+        Peripheral::I2cExt0 => {
+            perip_clk_en0.modify(|_, w| w.i2c0_ext0_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.i2c0_ext0_rst().bit(!enable));
+        }
+        #[cfg(not(esp32))]
+        Peripheral::I2cExt0 => {
+            perip_clk_en0.modify(|_, w| w.i2c_ext0_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.i2c_ext0_rst().bit(!enable));
+        }
+        #[cfg(i2c1)]
+        Peripheral::I2cExt1 => {
+            perip_clk_en0.modify(|_, w| w.i2c_ext1_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.i2c_ext1_rst().bit(!enable));
+        }
+        #[cfg(rmt)]
+        Peripheral::Rmt => {
+            perip_clk_en0.modify(|_, w| w.rmt_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.rmt_rst().bit(!enable));
+        }
+        Peripheral::Ledc => {
+            perip_clk_en0.modify(|_, w| w.ledc_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.ledc_rst().bit(!enable));
+        }
+        #[cfg(any(esp32, esp32s3))]
+        Peripheral::Mcpwm0 => {
+            perip_clk_en0.modify(|_, w| w.pwm0_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.pwm0_rst().bit(!enable));
+        }
+        #[cfg(any(esp32, esp32s3))]
+        Peripheral::Mcpwm1 => {
+            perip_clk_en0.modify(|_, w| w.pwm1_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.pwm1_rst().bit(!enable));
+        }
+        #[cfg(pcnt)]
+        Peripheral::Pcnt => {
+            perip_clk_en0.modify(|_, w| w.pcnt_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.pcnt_rst().bit(!enable));
+        }
+        #[cfg(any(esp32c2, esp32c3))]
+        Peripheral::ApbSarAdc => {
+            perip_clk_en0.modify(|_, w| w.apb_saradc_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.apb_saradc_rst().bit(!enable));
+        }
+        #[cfg(gdma)]
+        Peripheral::Gdma => {
+            perip_clk_en1.modify(|_, w| w.dma_clk_en().bit(enable));
+            perip_rst_en1.modify(|_, w| w.dma_rst().bit(!enable));
+        }
+        #[cfg(esp32)]
+        Peripheral::Dma => {
+            perip_clk_en0.modify(|_, w| w.spi_dma_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.spi_dma_rst().bit(!enable));
+        }
+        #[cfg(esp32s2)]
+        Peripheral::Dma => {
+            perip_clk_en0.modify(|_, w| w.spi2_dma_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.spi2_dma_rst().bit(!enable));
+            perip_clk_en0.modify(|_, w| w.spi3_dma_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.spi3_dma_rst().bit(!enable));
+        }
+        #[cfg(esp32c3)]
+        Peripheral::I2s0 => {
+            // on ESP32-C3 note that i2s1_clk_en / rst is really I2s0
+            perip_clk_en0.modify(|_, w| w.i2s1_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.i2s1_rst().bit(!enable));
+        }
+        #[cfg(any(esp32s3, esp32, esp32s2))]
+        Peripheral::I2s0 => {
+            perip_clk_en0.modify(|_, w| w.i2s0_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.i2s0_rst().bit(!enable));
+        }
+        #[cfg(any(esp32s3, esp32))]
+        Peripheral::I2s1 => {
+            perip_clk_en0.modify(|_, w| w.i2s1_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.i2s1_rst().bit(!enable));
+        }
+        #[cfg(usb_otg)]
+        Peripheral::Usb => {
+            perip_clk_en0.modify(|_, w| w.usb_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.usb_rst().bit(!enable));
+        }
+        #[cfg(hmac)]
+        Peripheral::Hmac => {
+            perip_clk_en1.modify(|_, w| w.crypto_hmac_clk_en().bit(enable));
+            perip_rst_en1.modify(|_, w| w.crypto_hmac_rst().bit(!enable));
+            // the HMAC peripheral uses the SHA accelerator internally, which
+            // may be used by other drivers as well, so it's only enabled here
+            if enable {
+                perip_clk_en1.modify(|_, w| w.crypto_sha_clk_en().set_bit());
+                perip_rst_en1.modify(|_, w| w.crypto_sha_rst().clear_bit());
             }
-            #[cfg(usb_otg)]
-            Peripheral::Usb => {
-                perip_clk_en0.modify(|_, w| w.usb_clk_en().set_bit());
-                perip_rst_en0.modify(|_, w| w.usb_rst().clear_bit());
+        }
+        #[cfg(ds)]
+        Peripheral::Ds => {
+            perip_clk_en1.modify(|_, w| w.crypto_ds_clk_en().bit(enable));
+            perip_rst_en1.modify(|_, w| w.crypto_ds_rst().bit(!enable));
+            // the DS peripheral uses the AES, RSA and SHA accelerators as well
+            // as the HMAC peripheral internally, they are shared and stay on
+            if enable {
+                perip_clk_en1.modify(|_, w| {
+                    w.crypto_hmac_clk_en()
+                        .set_bit()
+                        .crypto_aes_clk_en()
+                        .set_bit()
+                        .crypto_rsa_clk_en()
+                        .set_bit()
+                        .crypto_sha_clk_en()
+                        .set_bit()
+                });
+                perip_rst_en1.modify(|_, w| {
+                    w.crypto_hmac_rst()
+                        .clear_bit()
+                        .crypto_aes_rst()
+                        .clear_bit()
+                        .crypto_rsa_rst()
+                        .clear_bit()
+                        .crypto_sha_rst()
+                        .clear_bit()
+                });
             }
         }
+        #[cfg(twai)]
+        Peripheral::Twai => {
+            perip_clk_en0.modify(|_, w| w.can_clk_en().bit(enable));
+            perip_rst_en0.modify(|_, w| w.can_rst().bit(!enable));
+        }
+        #[cfg(emac)]
+        Peripheral::Emac => {
+            // the EMAC bits aren't described in the PAC
+            system
+                .wifi_clk_en
+                .modify(|r, w| unsafe { w.bits(with_bit(r.bits(), 1 << 14, enable)) });
+            system
+                .core_rst_en
+                .modify(|r, w| unsafe { w.bits(with_bit(r.bits(), 1 << 7, !enable)) });
+        }
+        #[cfg(sdio_slave)]
+        Peripheral::SdioSlave => {
+            // the SDIO slave bits aren't described in the PAC
+            system
+                .wifi_clk_en
+                .modify(|r, w| unsafe { w.bits(with_bit(r.bits(), 1 << 4, enable)) });
+            system
+                .core_rst_en
+                .modify(|r, w| unsafe { w.bits(with_bit(r.bits(), 1 << 4, !enable)) });
+        }
+        #[cfg(dedicated_gpio)]
+        Peripheral::DedicatedGpio => {
+            system
+                .cpu_peri_clk_en
+                .modify(|_, w| w.clk_en_dedicated_gpio().bit(enable));
+            system
+                .cpu_peri_rst_en
+                .modify(|_, w| w.rst_en_dedicated_gpio().bit(!enable));
+        }
+    }
+}
+
+/// `bits` with the bits of `mask` set or cleared
+#[allow(unused)]
+fn with_bit(bits: u32, mask: u32, set: bool) -> u32 {
+    if set {
+        bits | mask
+    } else {
+        bits & !mask
     }
 }
 
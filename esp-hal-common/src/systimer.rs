@@ -2,14 +2,17 @@ use core::{intrinsics::transmute, marker::PhantomData};
 
 use fugit::MillisDurationU32;
 
-use crate::pac::{
-    generic::Reg,
-    systimer::{
-        target0_conf::TARGET0_CONF_SPEC,
-        target0_hi::TARGET0_HI_SPEC,
-        target0_lo::TARGET0_LO_SPEC,
+use crate::{
+    pac::{
+        generic::Reg,
+        systimer::{
+            target0_conf::TARGET0_CONF_SPEC,
+            target0_hi::TARGET0_HI_SPEC,
+            target0_lo::TARGET0_LO_SPEC,
+        },
+        SYSTIMER,
     },
-    SYSTIMER,
+    timer::{check_deadline, DeadlinePassed, PeriodicDeadline, SCHEDULE_MARGIN},
 };
 
 // TODO this only handles unit0 of the systimer
@@ -43,6 +46,7 @@ impl SystemTimer {
     }
 
     // TODO use fugit types
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     pub fn now() -> u64 {
         // This should be safe to access from multiple contexts
         // worst case scenario the second accesor ends up reading
@@ -99,6 +103,7 @@ impl<T, const CHANNEL: u8> Alarm<T, CHANNEL> {
         }
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     pub fn clear_interrupt(&self) {
         let systimer = unsafe { &*SYSTIMER::ptr() };
         match CHANNEL {
@@ -109,6 +114,7 @@ impl<T, const CHANNEL: u8> Alarm<T, CHANNEL> {
         }
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn configure(
         &self,
         conf: impl FnOnce(&Reg<TARGET0_CONF_SPEC>, &Reg<TARGET0_HI_SPEC>, &Reg<TARGET0_LO_SPEC>),
@@ -188,6 +194,7 @@ impl<T, const CHANNEL: u8> Alarm<T, CHANNEL> {
 }
 
 impl<const CHANNEL: u8> Alarm<Target, CHANNEL> {
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     pub fn set_target(&self, timestamp: u64) {
         self.configure(|tconf, hi, lo| unsafe {
             tconf.write(|w| w.target0_period_mode().clear_bit()); // target mode
@@ -196,6 +203,28 @@ impl<const CHANNEL: u8> Alarm<Target, CHANNEL> {
         })
     }
 
+    /// Fire the alarm when the counter reaches `target`
+    ///
+    /// Fails without touching the alarm if `target` isn't at least
+    /// [SCHEDULE_MARGIN] ahead of [SystemTimer::now].
+    pub fn schedule_at(&self, target: u64) -> Result<(), DeadlinePassed> {
+        let now = SystemTimer::now();
+        check_deadline(target, now, schedule_margin(), SystemTimer::BIT_MASK)?;
+
+        self.set_target(target);
+        Ok(())
+    }
+
+    /// Fire the alarm at the next deadline of `deadline` and return the
+    /// number of periods that were skipped because they already passed
+    pub fn schedule_next(&self, deadline: &mut PeriodicDeadline) -> u64 {
+        let now = SystemTimer::now();
+        let missed = deadline.advance(now, schedule_margin(), SystemTimer::BIT_MASK);
+
+        self.set_target(deadline.target());
+        missed
+    }
+
     pub fn into_periodic(self) -> Alarm<Periodic, CHANNEL> {
         Alarm { _pd: PhantomData }
     }
@@ -222,6 +251,11 @@ impl<const CHANNEL: u8> Alarm<Periodic, CHANNEL> {
     }
 }
 
+/// [SCHEDULE_MARGIN] in ticks of the system timer
+const fn schedule_margin() -> u64 {
+    SCHEDULE_MARGIN.to_micros() * SystemTimer::TICKS_PER_SECOND / 1_000_000
+}
+
 impl<T> Alarm<T, 0> {
     pub const unsafe fn conjure() -> Self {
         Self { _pd: PhantomData }
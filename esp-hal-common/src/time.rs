@@ -0,0 +1,182 @@
+//! Monotonic time and deadlines
+//!
+//! [now] reads a monotonic tick counter which runs from the first call on,
+//! independent of any driver:
+//! - ESP32: the LACT timer of `TIMG0`, which isn't used by the timer drivers,
+//!   counts the 80 MHz APB clock divided by 5
+//! - other chips: unit 0 of the `SYSTIMER`, the same counter
+//!   [SystemTimer::now](crate::systimer::SystemTimer::now) reads
+//!
+//! A [Deadline] bounds blocking operations, e.g. the `*_timeout` methods of
+//! the UART, I2C and SPI drivers:
+//!
+//! ```no_run
+//! let mut buffer = [0u8; 10];
+//! match serial.read_exact_timeout(&mut buffer, Deadline::after(50u32.millis())) {
+//!     Ok(()) => process(&buffer),
+//!     Err(serial::Error::Timeout { transferred }) => retry(&buffer[..transferred]),
+//! }
+//! ```
+//!
+//! A deadline expires exactly at the first tick at or after the requested
+//! time, i.e. it's never early and at most one tick late (plus the time the
+//! caller needs to notice). Deadlines handle the counter wrapping around.
+
+use fugit::MicrosDurationU64;
+
+#[cfg(esp32)]
+use crate::pac::TIMG0;
+#[cfg(not(esp32))]
+use crate::systimer::SystemTimer;
+
+/// Frequency of the ticks returned by [now]
+#[cfg(esp32)]
+pub const TICKS_PER_SECOND: u64 = 16_000_000;
+/// Frequency of the ticks returned by [now]
+#[cfg(not(esp32))]
+pub const TICKS_PER_SECOND: u64 = SystemTimer::TICKS_PER_SECOND;
+
+/// The counter wraps around after `BIT_MASK` ticks
+#[cfg(esp32)]
+pub const BIT_MASK: u64 = u64::MAX;
+/// The counter wraps around after `BIT_MASK` ticks
+#[cfg(not(esp32))]
+pub const BIT_MASK: u64 = SystemTimer::BIT_MASK;
+
+const TICKS_PER_MICRO: u64 = TICKS_PER_SECOND / 1_000_000;
+
+const _: () = assert!(TICKS_PER_SECOND % 1_000_000 == 0);
+
+/// Read the monotonic tick counter
+#[cfg(esp32)]
+pub fn now() -> u64 {
+    const LACT_EN: u32 = 1 << 31;
+    const LACT_INCREASE: u32 = 1 << 30;
+    const LACT_AUTORELOAD: u32 = 1 << 29;
+    const LACT_DIVIDER_SHIFT: u32 = 13;
+
+    let timg0 = unsafe { &*TIMG0::PTR };
+
+    // The timer is configured by the first call, which may race with a call
+    // on the other core or from an interrupt. The timer latches the counter
+    // for both cores, reading it is guarded as well to not combine the words
+    // of two different updates.
+    critical_section::with(|_| {
+        if timg0.lactconfig.read().bits() & LACT_EN == 0 {
+            timg0.lactalarmhi.write(|w| unsafe { w.bits(u32::MAX) });
+            timg0.lactalarmlo.write(|w| unsafe { w.bits(u32::MAX) });
+            timg0.lactloadhi.write(|w| unsafe { w.bits(0) });
+            timg0.lactloadlo.write(|w| unsafe { w.bits(0) });
+            timg0.lactload.write(|w| unsafe { w.bits(1) });
+            timg0.lactconfig.write(|w| unsafe {
+                w.bits(
+                    LACT_EN
+                        | LACT_INCREASE
+                        | LACT_AUTORELOAD
+                        | (80_000_000 / TICKS_PER_SECOND as u32) << LACT_DIVIDER_SHIFT,
+                )
+            });
+        }
+
+        // There is no flag telling that the update is done, the low word is
+        // polled until it changes, which takes less than a tick
+        let before = timg0.lactlo.read().bits();
+        timg0.lactupdate.write(|w| unsafe { w.bits(1) });
+        let mut low = timg0.lactlo.read().bits();
+        for _ in 0..5 {
+            if low != before {
+                break;
+            }
+            low = timg0.lactlo.read().bits();
+        }
+        let high = timg0.lacthi.read().bits();
+
+        (high as u64) << 32 | low as u64
+    })
+}
+
+/// Read the monotonic tick counter
+#[cfg(not(esp32))]
+#[inline(always)]
+pub fn now() -> u64 {
+    SystemTimer::now()
+}
+
+/// A point in time after which an operation gives up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadline {
+    start: u64,
+    ticks: u64,
+}
+
+impl Deadline {
+    /// The deadline `timeout` from now
+    pub fn after<T>(timeout: T) -> Self
+    where
+        T: Into<MicrosDurationU64>,
+    {
+        Self::from_ticks(
+            now(),
+            timeout.into().ticks().saturating_mul(TICKS_PER_MICRO),
+        )
+    }
+
+    /// The deadline `ticks` ticks after the tick count `start`
+    pub const fn from_ticks(start: u64, ticks: u64) -> Self {
+        Self {
+            start: start & BIT_MASK,
+            ticks: if ticks > BIT_MASK { BIT_MASK } else { ticks },
+        }
+    }
+
+    /// Returns `true` once the deadline passed
+    pub fn is_expired(&self) -> bool {
+        self.is_expired_at(now())
+    }
+
+    /// Returns `true` if the deadline passed at the tick count `now`
+    pub const fn is_expired_at(&self, now: u64) -> bool {
+        self.elapsed_at(now) >= self.ticks
+    }
+
+    /// Ticks left until the deadline passes, 0 once it passed
+    pub fn remaining_ticks(&self) -> u64 {
+        self.ticks.saturating_sub(self.elapsed_at(now()))
+    }
+
+    /// Time left until the deadline passes, rounded up to whole microseconds
+    pub fn remaining(&self) -> MicrosDurationU64 {
+        MicrosDurationU64::micros((self.remaining_ticks() + TICKS_PER_MICRO - 1) / TICKS_PER_MICRO)
+    }
+
+    const fn elapsed_at(&self, now: u64) -> u64 {
+        now.wrapping_sub(self.start) & BIT_MASK
+    }
+}
+
+// The deadline math around the wrap-around of the counter
+const _: () = {
+    // not expired one tick early, expired exactly on time
+    let deadline = Deadline::from_ticks(100, 50);
+    assert!(!deadline.is_expired_at(149));
+    assert!(deadline.is_expired_at(150));
+
+    // the counter wraps between start and deadline
+    let deadline = Deadline::from_ticks(BIT_MASK - 9, 20);
+    assert!(!deadline.is_expired_at(BIT_MASK));
+    assert!(!deadline.is_expired_at(9));
+    assert!(deadline.is_expired_at(10));
+
+    // the counter wraps exactly at the deadline
+    let deadline = Deadline::from_ticks(BIT_MASK - 9, 10);
+    assert!(!deadline.is_expired_at(BIT_MASK));
+    assert!(deadline.is_expired_at(0));
+
+    // a zero timeout is expired right away
+    assert!(Deadline::from_ticks(BIT_MASK, 0).is_expired_at(BIT_MASK));
+
+    // bits above the counter width are ignored
+    let deadline = Deadline::from_ticks(u64::MAX, 1);
+    assert!(!deadline.is_expired_at(u64::MAX & BIT_MASK));
+    assert!(deadline.is_expired_at(0));
+};
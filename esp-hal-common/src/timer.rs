@@ -15,7 +15,7 @@ use void::Void;
 #[cfg(timg1)]
 use crate::pac::TIMG1;
 use crate::{
-    clock::Clocks,
+    clock::{ClockListener, Clocks},
     pac::{timg0::RegisterBlock, TIMG0},
 };
 
@@ -27,8 +27,176 @@ pub enum Error {
     AlarmInactive,
 }
 
-// A timergroup consisting of up to 2 timers (chip dependent) and a watchdog
-// timer
+/// Minimum distance between the counter and a scheduled deadline
+///
+/// Covers the time between reading the counter and loading the alarm, a
+/// deadline closer than that could pass unnoticed and only fire after the
+/// counter wrapped.
+pub const SCHEDULE_MARGIN: MicrosDurationU64 = MicrosDurationU64::micros(10);
+
+/// The counter of the general-purpose timers is 54 bits wide
+const COUNTER_MASK: u64 = 0x3F_FFFF_FFFF_FFFF;
+
+/// A deadline was in the past, or closer to the counter than
+/// [SCHEDULE_MARGIN]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlinePassed {
+    /// The requested deadline
+    pub target: u64,
+    /// The counter when the deadline was checked
+    pub now: u64,
+}
+
+/// Check that `target` is at least `margin` ticks ahead of `now`, on a
+/// counter which wraps at `mask`
+///
+/// Deadlines more than half the counter range ahead count as passed, they
+/// are the result of a subtraction which underflowed.
+pub const fn check_deadline(
+    target: u64,
+    now: u64,
+    margin: u64,
+    mask: u64,
+) -> Result<(), DeadlinePassed> {
+    let ahead = target.wrapping_sub(now) & mask;
+    if ahead > margin && ahead <= mask / 2 {
+        Ok(())
+    } else {
+        Err(DeadlinePassed { target, now })
+    }
+}
+
+/// The first deadline after `previous` in steps of `period` which is at least
+/// `margin` ticks ahead of `now`, on a counter which wraps at `mask`
+///
+/// Returns the deadline and the number of periods skipped to reach it, 0 if
+/// `previous + period` was still ahead.
+pub const fn next_deadline(
+    previous: u64,
+    period: u64,
+    now: u64,
+    margin: u64,
+    mask: u64,
+) -> (u64, u64) {
+    let next = previous.wrapping_add(period) & mask;
+    if check_deadline(next, now, margin, mask).is_ok() {
+        return (next, 0);
+    }
+
+    let late = now.wrapping_add(margin).wrapping_sub(previous) & mask;
+    let periods = late / period + 1;
+    let next = previous.wrapping_add(periods.wrapping_mul(period)) & mask;
+
+    (next, periods - 1)
+}
+
+const _: () = {
+    const MASK: u64 = COUNTER_MASK;
+
+    assert!(check_deadline(1_000, 500, 100, MASK).is_ok());
+    assert!(check_deadline(1_000, 899, 100, MASK).is_ok());
+    // within the margin, exactly now and in the past
+    assert!(matches!(
+        check_deadline(1_000, 900, 100, MASK),
+        Err(DeadlinePassed {
+            target: 1_000,
+            now: 900
+        })
+    ));
+    assert!(check_deadline(1_000, 1_000, 100, MASK).is_err());
+    assert!(check_deadline(1_000, 2_000, 100, MASK).is_err());
+    // ahead across the wrap of the counter
+    assert!(check_deadline(50, MASK - 100, 100, MASK).is_ok());
+    assert!(check_deadline(50, MASK - 10, 100, MASK).is_err());
+    // more than half of the counter range ahead is an underflow
+    assert!(check_deadline(MASK / 2 + 1_000, 1_000, 0, MASK).is_ok());
+    assert!(check_deadline(MASK / 2 + 1_000, 999, 0, MASK).is_err());
+
+    assert!(matches!(
+        next_deadline(1_000, 1_000, 1_500, 100, MASK),
+        (2_000, 0)
+    ));
+    assert!(matches!(
+        next_deadline(1_000, 1_000, 1_950, 100, MASK),
+        (3_000, 1)
+    ));
+    assert!(matches!(
+        next_deadline(1_000, 1_000, 5_500, 100, MASK),
+        (6_000, 4)
+    ));
+    assert!(matches!(
+        next_deadline(MASK - 499, 1_000, MASK - 100, 100, MASK),
+        (500, 0)
+    ));
+    assert!(matches!(
+        next_deadline(MASK - 499, 1_000, 400, 100, MASK),
+        (1_500, 1)
+    ));
+
+    // The steps `PeriodicDeadline::advance` takes, 4_000 is within the margin
+    let deadline = PeriodicDeadline::new(0, 1_000);
+    assert!(matches!(
+        next_deadline(deadline.target, deadline.period, 100, 100, MASK),
+        (1_000, 0)
+    ));
+    assert!(matches!(
+        next_deadline(1_000, deadline.period, 3_950, 100, MASK),
+        (5_000, 3)
+    ));
+};
+
+/// A periodic deadline, e.g. of a control loop
+///
+/// Passed to `schedule_next` of an alarm, which skips the periods that were
+/// missed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PeriodicDeadline {
+    target: u64,
+    period: u64,
+}
+
+impl PeriodicDeadline {
+    /// Deadlines every `period` ticks, the first one `period` ticks after
+    /// `start`
+    pub const fn new(start: u64, period: u64) -> Self {
+        assert!(period != 0, "the period must not be 0");
+        Self {
+            target: start,
+            period,
+        }
+    }
+
+    /// The last scheduled deadline
+    pub fn target(&self) -> u64 {
+        self.target
+    }
+
+    /// The period in ticks
+    pub fn period(&self) -> u64 {
+        self.period
+    }
+
+    /// Move to the next deadline which is at least `margin` ticks ahead of
+    /// `now`, returns the number of periods missed
+    pub(crate) fn advance(&mut self, now: u64, margin: u64, mask: u64) -> u64 {
+        let (target, missed) = next_deadline(self.target, self.period, now, margin, mask);
+        self.target = target;
+        missed
+    }
+}
+
+/// A timer group, consisting of up to two general-purpose timers and a
+/// watchdog timer
+///
+/// | Chip                 | Timer groups | Timers per group |
+/// |----------------------|--------------|------------------|
+/// | ESP32, ESP32-S2/S3   | TIMG0, TIMG1 | 2                |
+/// | ESP32-C3             | TIMG0, TIMG1 | 1                |
+/// | ESP32-C2             | TIMG0        | 1                |
+///
+/// Code written for a chip with two timer groups doesn't compile for the
+/// ESP32-C2 if it uses TIMG1, the PAC of that chip has no `TIMG1` peripheral
+/// to pass to [TimerGroup::new].
 pub struct TimerGroup<T>
 where
     T: TimerGroupInstance,
@@ -39,6 +207,7 @@ where
     pub wdt: Wdt<T>,
 }
 
+/// A timer group peripheral, implemented only for the groups the chip has
 pub trait TimerGroupInstance {
     fn register_block() -> *const RegisterBlock;
 }
@@ -111,6 +280,53 @@ where
     pub fn free(self) -> T {
         self.timg
     }
+
+    /// Fire the alarm when the counter reaches `target`
+    ///
+    /// The counter keeps running through the alarm, the auto-reload of
+    /// [CountDown::start] is turned off. Fails without touching the alarm if
+    /// `target` isn't at least [SCHEDULE_MARGIN] ahead of the counter.
+    pub fn schedule_at(&mut self, target: u64) -> Result<(), DeadlinePassed> {
+        let now = self.timg.now();
+        check_deadline(target, now, self.schedule_margin(), COUNTER_MASK)?;
+
+        self.load_deadline(target);
+        Ok(())
+    }
+
+    /// Fire the alarm at the next deadline of `deadline` and return the
+    /// number of periods that were skipped because they already passed
+    pub fn schedule_next(&mut self, deadline: &mut PeriodicDeadline) -> u64 {
+        let now = self.timg.now();
+        let missed = deadline.advance(now, self.schedule_margin(), COUNTER_MASK);
+
+        self.load_deadline(deadline.target());
+        missed
+    }
+
+    fn schedule_margin(&self) -> u64 {
+        timeout_to_ticks(SCHEDULE_MARGIN, self.apb_clk_freq, self.timg.divider()).max(1)
+    }
+
+    fn load_deadline(&mut self, target: u64) {
+        self.timg.set_alarm_active(false);
+        self.timg.set_auto_reload(false);
+        self.timg.load_alarm_value(target);
+        self.timg.set_counter_decrementing(false);
+        self.timg.set_counter_active(true);
+        self.timg.set_alarm_active(true);
+    }
+}
+
+impl<T> ClockListener for Timer<T>
+where
+    T: Instance,
+{
+    /// Updates the APB frequency used to convert timeouts into ticks. A
+    /// running countdown is not adjusted.
+    fn clocks_changed(&mut self, clocks: &Clocks) {
+        self.apb_clk_freq = clocks.apb_clock;
+    }
 }
 
 impl<T> Deref for Timer<T>
@@ -191,6 +407,7 @@ where
         reg_block.t0config.modify(|_, w| w.en().bit(state));
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn is_counter_active(&self) -> bool {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -213,6 +430,7 @@ where
             .modify(|_, w| w.autoreload().bit(auto_reload));
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn set_alarm_active(&mut self, state: bool) {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -226,7 +444,7 @@ where
     }
 
     fn load_alarm_value(&mut self, value: u64) {
-        let value = value & 0x3F_FFFF_FFFF_FFFF;
+        let value = value & COUNTER_MASK;
         let high = (value >> 32) as u32;
         let low = (value & 0xFFFF_FFFF) as u32;
 
@@ -261,12 +479,14 @@ where
             .modify(|_, w| w.t0_int_ena().clear_bit());
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn clear_interrupt(&mut self) {
         let reg_block = unsafe { &*TG::register_block() };
 
         reg_block.int_clr_timers.write(|w| w.t0_int_clr().set_bit());
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn now(&self) -> u64 {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -294,6 +514,7 @@ where
         }
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn is_interrupt_set(&self) -> bool {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -336,6 +557,7 @@ where
         reg_block.t1config.modify(|_, w| w.en().bit(state));
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn is_counter_active(&self) -> bool {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -358,6 +580,7 @@ where
             .modify(|_, w| w.autoreload().bit(auto_reload));
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn set_alarm_active(&mut self, state: bool) {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -371,7 +594,7 @@ where
     }
 
     fn load_alarm_value(&mut self, value: u64) {
-        let value = value & 0x3F_FFFF_FFFF_FFFF;
+        let value = value & COUNTER_MASK;
         let high = (value >> 32) as u32;
         let low = (value & 0xFFFF_FFFF) as u32;
 
@@ -406,12 +629,14 @@ where
             .modify(|_, w| w.t1_int_ena().clear_bit());
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn clear_interrupt(&mut self) {
         let reg_block = unsafe { &*TG::register_block() };
 
         reg_block.int_clr_timers.write(|w| w.t1_int_clr().set_bit());
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn now(&self) -> u64 {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -439,6 +664,7 @@ where
         }
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn is_interrupt_set(&self) -> bool {
         let reg_block = unsafe { &*TG::register_block() };
 
@@ -496,6 +722,7 @@ where
         self.timg.set_alarm_active(true);
     }
 
+    #[cfg_attr(feature = "place-isr-in-ram", procmacros::ram)]
     fn wait(&mut self) -> nb::Result<(), Void> {
         if !self.timg.is_counter_active() {
             panic!("Called wait on an inactive timer!")
@@ -533,7 +760,51 @@ where
 
 impl<T> Periodic for Timer<T> where T: Instance {}
 
+/// Behavior of a timer group watchdog stage if it times out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WdtStageAction {
+    /// Stage is disabled
+    Off         = 0,
+    /// Trigger an interrupt
+    Interrupt   = 1,
+    /// Reset the CPU core
+    ResetCpu    = 2,
+    /// Reset the main system
+    ResetSystem = 3,
+}
+
+impl WdtStageAction {
+    /// Decode the raw value of a `WDT_STGn` register field
+    fn from_bits(bits: u8) -> Self {
+        match bits {
+            1 => WdtStageAction::Interrupt,
+            2 => WdtStageAction::ResetCpu,
+            3 => WdtStageAction::ResetSystem,
+            _ => WdtStageAction::Off,
+        }
+    }
+}
+
+/// Snapshot of a timer group watchdog configuration as read back from the
+/// hardware
+#[derive(Debug, Clone, Copy)]
+pub struct WdtStatus {
+    /// Whether the watchdog is armed
+    pub enabled: bool,
+    /// Whether the flash boot protection mode is active
+    pub flashboot_mode: bool,
+    /// Configured action of each of the four stages
+    pub stage_actions: [WdtStageAction; 4],
+    /// Timeout of stage 0
+    pub timeout: MicrosDurationU64,
+}
+
 /// Watchdog timer
+///
+/// The ROM arms the watchdog of timer group 0 in flash boot protection mode
+/// before it loads the bootloader. Creating the driver doesn't change the
+/// state of the watchdog, [WatchdogEnable::start] and
+/// [WatchdogDisable::disable] leave the protection mode.
 pub struct Wdt<TG> {
     phantom: PhantomData<TG>,
 }
@@ -550,6 +821,100 @@ where
         }
     }
 
+    /// Returns `true` if the watchdog is currently armed, either normally or
+    /// by the flash boot protection
+    pub fn is_enabled(&self) -> bool {
+        let reg_block = unsafe { &*TG::register_block() };
+        let config = reg_block.wdtconfig0.read();
+
+        config.wdt_en().bit_is_set() || config.wdt_flashboot_mod_en().bit_is_set()
+    }
+
+    /// Returns `true` if the flash boot protection armed by the ROM is still
+    /// active
+    pub fn is_flashboot_protection_enabled(&self) -> bool {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .wdtconfig0
+            .read()
+            .wdt_flashboot_mod_en()
+            .bit_is_set()
+    }
+
+    /// Feed the watchdog while it's armed by the flash boot protection
+    ///
+    /// Unlike [WatchdogEnable::start] this doesn't touch the configuration
+    /// the ROM armed the watchdog with, so a bootloader can keep it running
+    /// while it e.g. verifies the application, and hand it over with
+    /// [hand_over_watchdogs](crate::rtc_cntl::hand_over_watchdogs)
+    /// afterwards.
+    pub fn feed_flashboot(&mut self) {
+        self.feed();
+    }
+
+    /// Leave the flash boot protection mode
+    ///
+    /// The rest of the configuration is left alone: if the watchdog wasn't
+    /// started with [WatchdogEnable::start] before, it stops.
+    pub fn disable_flashboot_protection(&mut self) {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        reg_block
+            .wdtwprotect
+            .write(|w| unsafe { w.wdt_wkey().bits(0x50D8_3AA1u32) });
+
+        reg_block
+            .wdtconfig0
+            .modify(|_, w| w.wdt_flashboot_mod_en().clear_bit());
+
+        #[cfg(any(esp32c2, esp32c3))]
+        reg_block
+            .wdtconfig0
+            .modify(|_, w| w.wdt_conf_update_en().set_bit());
+
+        reg_block
+            .wdtwprotect
+            .write(|w| unsafe { w.wdt_wkey().bits(0u32) });
+    }
+
+    /// Read back the action configured for each of the four stages
+    pub fn stage_actions(&self) -> [WdtStageAction; 4] {
+        let reg_block = unsafe { &*TG::register_block() };
+        let config = reg_block.wdtconfig0.read();
+
+        [
+            WdtStageAction::from_bits(config.wdt_stg0().bits()),
+            WdtStageAction::from_bits(config.wdt_stg1().bits()),
+            WdtStageAction::from_bits(config.wdt_stg2().bits()),
+            WdtStageAction::from_bits(config.wdt_stg3().bits()),
+        ]
+    }
+
+    /// Read back the timeout of stage 0
+    ///
+    /// Like [`WatchdogEnable::start`], this assumes an 80 MHz APB_CLK. The
+    /// hardware offers no way to read the current counter value, so the
+    /// remaining time until the watchdog bites cannot be determined.
+    pub fn timeout(&self) -> MicrosDurationU64 {
+        let reg_block = unsafe { &*TG::register_block() };
+
+        let prescale = reg_block.wdtconfig1.read().wdt_clk_prescale().bits() as u64;
+        let hold = reg_block.wdtconfig2.read().wdt_stg0_hold().bits() as u64;
+
+        MicrosDurationU64::micros(hold * prescale * 125 / 10_000)
+    }
+
+    /// Read back the complete watchdog configuration
+    pub fn status(&self) -> WdtStatus {
+        WdtStatus {
+            enabled: self.is_enabled(),
+            flashboot_mode: self.is_flashboot_protection_enabled(),
+            stage_actions: self.stage_actions(),
+            timeout: self.timeout(),
+        }
+    }
+
     fn set_wdt_enabled(&mut self, enabled: bool) {
         let reg_block = unsafe { &*TG::register_block() };
 
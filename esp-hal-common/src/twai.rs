@@ -0,0 +1,1119 @@
+//! Two-Wire Automotive Interface (TWAI)
+//!
+//! The TWAI controller is compatible with CAN 2.0, an external transceiver is
+//! needed to connect to a bus.
+//!
+//! Besides the normal mode the controller supports
+//! - [TwaiMode::ListenOnly]: frames are received but never acknowledged, the
+//!   controller never drives the bus. Useful for bus sniffers.
+//! - [TwaiMode::SelfTest]: frames are transmitted without requiring an
+//!   acknowledgement and are received by the controller itself, so it can be
+//!   tested without a second node.
+//!
+//! Bus errors and receive FIFO overruns are reported as alerts, see
+//! [Twai::listen_alerts] and [Twai::read_alerts].
+//!
+//! The driver follows the register layout of the ESP32-C3, the only chip
+//! with the `twai` feature so far.
+//!
+//! The controller has a single transmit buffer. [Twai::with_tx_queue] adds a
+//! software queue which is drained from the `TWAI` interrupt, so frames can
+//! be sent back-to-back without waiting, see [QueuedTwai].
+//!
+//! Example
+//! ```no_run
+//! let mut twai = Twai::new(
+//!     peripherals.TWAI,
+//!     io.pins.gpio2,
+//!     io.pins.gpio3,
+//!     BaudRate::B500K,
+//!     TwaiMode::Normal,
+//!     &mut system.peripheral_clock_control,
+//!     &clocks,
+//! )
+//! .unwrap();
+//!
+//! let frame = Frame::new(Id::Standard(0x123), &[1, 2, 3]).unwrap();
+//! nb::block!(twai.transmit(&frame)).unwrap();
+//! let frame = nb::block!(twai.receive()).unwrap();
+//! ```
+
+use core::ops::{BitOr, Deref, DerefMut};
+
+use fugit::HertzU32;
+use heapless::Deque;
+
+use crate::{
+    clock::Clocks,
+    gpio::{InputPin, OutputPin},
+    pac::{twai::RegisterBlock, TWAI},
+    system::{Peripheral, PeripheralClockControl},
+    types::{InputSignal, OutputSignal},
+};
+
+#[cfg(not(esp32c3))]
+compile_error!("The TWAI driver only knows the register layout of the ESP32-C3");
+
+// The controller is error passive from an error count of 128 on
+const ERROR_PASSIVE_LIMIT: u8 = 128;
+
+/// TWAI errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The controller is bus-off and has to be restarted
+    BusOff,
+    /// Frames were lost because the receive FIFO overran, the FIFO has been
+    /// cleared
+    Overrun,
+    /// Frames can't be transmitted in [TwaiMode::ListenOnly]
+    ListenOnly,
+    /// The bit timing is out of range, or a predefined [BaudRate] can't be
+    /// derived from the APB clock
+    InvalidBaudRate,
+}
+
+/// Operating mode of the controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TwaiMode {
+    Normal,
+    /// Transmitted frames don't need to be acknowledged and are received by
+    /// the controller itself
+    SelfTest,
+    /// Frames are received without acknowledging them, nothing is ever
+    /// transmitted
+    ListenOnly,
+}
+
+/// Bit timing, in time quanta of the given prescaler
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimingConfig {
+    pub baud_rate_prescaler: u16,
+    pub sync_jump_width: u8,
+    pub tseg_1: u8,
+    pub tseg_2: u8,
+    pub triple_sample: bool,
+}
+
+/// Bit rate of the bus
+///
+/// The predefined rates use 20 time quanta per bit, their prescaler is
+/// derived from the APB clock, which has to be a multiple of 40 times the
+/// rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BaudRate {
+    B125K,
+    B250K,
+    B500K,
+    B1000K,
+    Custom(TimingConfig),
+}
+
+impl BaudRate {
+    // see https://github.com/espressif/esp-idf/blob/master/components/hal/include/hal/twai_types.h
+    const fn timing(self, apb_clock: HertzU32) -> Result<TimingConfig, Error> {
+        let bit_rate = match self {
+            Self::B125K => 125_000,
+            Self::B250K => 250_000,
+            Self::B500K => 500_000,
+            Self::B1000K => 1_000_000,
+            Self::Custom(timing) => return timing.validate(),
+        };
+
+        let quanta = bit_rate * TimingConfig::PREDEFINED_QUANTA;
+        if apb_clock.raw() % quanta != 0 {
+            return Err(Error::InvalidBaudRate);
+        }
+        let baud_rate_prescaler = apb_clock.raw() / quanta;
+        if baud_rate_prescaler > u16::MAX as u32 {
+            return Err(Error::InvalidBaudRate);
+        }
+
+        TimingConfig {
+            baud_rate_prescaler: baud_rate_prescaler as u16,
+            sync_jump_width: 3,
+            tseg_1: 15,
+            tseg_2: 4,
+            triple_sample: false,
+        }
+        .validate()
+    }
+}
+
+impl TimingConfig {
+    /// Time quanta per bit of the predefined [BaudRate]s, with the sync
+    /// segment
+    const PREDEFINED_QUANTA: u32 = 1 + 15 + 4;
+
+    /// Check the ranges of the fields of the bus timing registers
+    const fn validate(self) -> Result<Self, Error> {
+        let valid = matches!(self.baud_rate_prescaler, 2..=16384)
+            && self.baud_rate_prescaler % 2 == 0
+            && matches!(self.sync_jump_width, 1..=4)
+            && matches!(self.tseg_1, 1..=16)
+            && matches!(self.tseg_2, 1..=8);
+
+        if valid {
+            Ok(self)
+        } else {
+            Err(Error::InvalidBaudRate)
+        }
+    }
+}
+
+const _: () = {
+    /// The prescaler of a predefined `baud_rate` at an APB clock of
+    /// `apb_mhz`, 0 if the rate can't be derived from it
+    const fn prescaler(baud_rate: BaudRate, apb_mhz: u32) -> u16 {
+        match baud_rate.timing(HertzU32::MHz(apb_mhz)) {
+            Ok(timing) => {
+                assert!(timing.sync_jump_width == 3);
+                assert!(timing.tseg_1 == 15 && timing.tseg_2 == 4);
+                timing.baud_rate_prescaler
+            }
+            Err(_) => 0,
+        }
+    }
+
+    assert!(prescaler(BaudRate::B125K, 80) == 32);
+    assert!(prescaler(BaudRate::B250K, 80) == 16);
+    assert!(prescaler(BaudRate::B500K, 80) == 8);
+    assert!(prescaler(BaudRate::B1000K, 80) == 4);
+    assert!(prescaler(BaudRate::B500K, 40) == 4);
+    assert!(prescaler(BaudRate::B125K, 20) == 8);
+    // not a multiple of the time quanta, an odd prescaler and one below 2
+    assert!(prescaler(BaudRate::B1000K, 30) == 0);
+    assert!(prescaler(BaudRate::B1000K, 60) == 0);
+    assert!(prescaler(BaudRate::B1000K, 20) == 0);
+
+    const fn custom(timing: TimingConfig) -> bool {
+        match BaudRate::Custom(timing).timing(HertzU32::MHz(80)) {
+            Ok(custom) => {
+                assert!(custom.baud_rate_prescaler == timing.baud_rate_prescaler);
+                assert!(custom.sync_jump_width == timing.sync_jump_width);
+                assert!(custom.tseg_1 == timing.tseg_1 && custom.tseg_2 == timing.tseg_2);
+                assert!(custom.triple_sample == timing.triple_sample);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    let timing = TimingConfig {
+        baud_rate_prescaler: 2,
+        sync_jump_width: 1,
+        tseg_1: 16,
+        tseg_2: 8,
+        triple_sample: true,
+    };
+    assert!(custom(timing));
+    assert!(!custom(TimingConfig {
+        baud_rate_prescaler: 0,
+        ..timing
+    }));
+    assert!(!custom(TimingConfig {
+        baud_rate_prescaler: 3,
+        ..timing
+    }));
+    assert!(custom(TimingConfig {
+        baud_rate_prescaler: 16384,
+        ..timing
+    }));
+    assert!(!custom(TimingConfig {
+        baud_rate_prescaler: 16386,
+        ..timing
+    }));
+    assert!(!custom(TimingConfig {
+        sync_jump_width: 0,
+        ..timing
+    }));
+    assert!(!custom(TimingConfig {
+        sync_jump_width: 5,
+        ..timing
+    }));
+    assert!(!custom(TimingConfig {
+        tseg_1: 0,
+        ..timing
+    }));
+    assert!(!custom(TimingConfig {
+        tseg_1: 17,
+        ..timing
+    }));
+    assert!(!custom(TimingConfig {
+        tseg_2: 0,
+        ..timing
+    }));
+    assert!(!custom(TimingConfig {
+        tseg_2: 9,
+        ..timing
+    }));
+};
+
+/// Frame identifier
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Id {
+    /// 11-bit identifier
+    Standard(u16),
+    /// 29-bit identifier
+    Extended(u32),
+}
+
+impl Id {
+    fn is_valid(&self) -> bool {
+        match *self {
+            Id::Standard(id) => id <= 0x7ff,
+            Id::Extended(id) => id <= 0x1fff_ffff,
+        }
+    }
+}
+
+/// A data or remote frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    id: Id,
+    remote: bool,
+    dlc: u8,
+    data: [u8; 8],
+}
+
+impl Frame {
+    /// Create a data frame, returns `None` if the identifier is out of range or
+    /// there are more than 8 bytes of data
+    pub fn new(id: Id, data: &[u8]) -> Option<Self> {
+        if !id.is_valid() || data.len() > 8 {
+            return None;
+        }
+
+        let mut frame = Frame {
+            id,
+            remote: false,
+            dlc: data.len() as u8,
+            data: [0; 8],
+        };
+        frame.data[..data.len()].copy_from_slice(data);
+
+        Some(frame)
+    }
+
+    /// Create a remote frame, requesting `dlc` bytes
+    pub fn new_remote(id: Id, dlc: u8) -> Option<Self> {
+        if !id.is_valid() || dlc > 8 {
+            return None;
+        }
+
+        Some(Frame {
+            id,
+            remote: true,
+            dlc,
+            data: [0; 8],
+        })
+    }
+
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
+    pub fn is_remote(&self) -> bool {
+        self.remote
+    }
+
+    pub fn dlc(&self) -> u8 {
+        self.dlc
+    }
+
+    /// The data of the frame, empty for remote frames
+    pub fn data(&self) -> &[u8] {
+        if self.remote {
+            &[]
+        } else {
+            &self.data[..self.dlc as usize]
+        }
+    }
+}
+
+/// Set of alerts, combined with `|`
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct AlertFlags(u8);
+
+impl AlertFlags {
+    /// An error counter reached the error warning limit
+    pub const ERROR_WARNING: Self = Self(1 << 0);
+    /// An error counter reached 128, the controller only sends passive error
+    /// flags
+    pub const ERROR_PASSIVE: Self = Self(1 << 1);
+    /// The transmit error counter exceeded 255, the controller doesn't
+    /// participate in bus activities anymore
+    pub const BUS_OFF: Self = Self(1 << 2);
+    /// Frames were lost because the receive FIFO was full
+    pub const RX_OVERRUN: Self = Self(1 << 3);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn all() -> Self {
+        Self(0b1111)
+    }
+
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Returns `true` if all flags of `other` are set
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns `true` if any flag of `other` is set
+    pub const fn intersects(self, other: Self) -> bool {
+        self.0 & other.0 != 0
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOr for AlertFlags {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        self.union(rhs)
+    }
+}
+
+/// Pending interrupts of the controller
+///
+/// Reading INT_RAW clears all of them, the ones not handled by the reader
+/// are kept in the driver for later.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+struct Interrupts {
+    tx: bool,
+    error_warning: bool,
+    overrun: bool,
+    error_passive: bool,
+}
+
+impl Interrupts {
+    fn read(regs: &RegisterBlock) -> Self {
+        let int_raw = regs.int_raw.read();
+
+        Self {
+            tx: int_raw.tx_int_st().bit_is_set(),
+            error_warning: int_raw.err_warn_int_st().bit_is_set(),
+            overrun: int_raw.overrun_int_st().bit_is_set(),
+            error_passive: int_raw.err_passive_int_st().bit_is_set(),
+        }
+    }
+
+    const fn union(self, other: Self) -> Self {
+        Self {
+            tx: self.tx || other.tx,
+            error_warning: self.error_warning || other.error_warning,
+            overrun: self.overrun || other.overrun,
+            error_passive: self.error_passive || other.error_passive,
+        }
+    }
+
+    /// The interrupts of `self` which aren't in `other`
+    const fn difference(self, other: Self) -> Self {
+        Self {
+            tx: self.tx && !other.tx,
+            error_warning: self.error_warning && !other.error_warning,
+            overrun: self.overrun && !other.overrun,
+            error_passive: self.error_passive && !other.error_passive,
+        }
+    }
+
+    /// The alert interrupts, the others cleared
+    const fn alerts(self) -> Self {
+        Self { tx: false, ..self }
+    }
+
+    /// The other interrupts, the alerts cleared
+    const fn without_alerts(self) -> Self {
+        Self {
+            tx: self.tx,
+            error_warning: false,
+            overrun: false,
+            error_passive: false,
+        }
+    }
+}
+
+const _: () = {
+    const fn same(a: Interrupts, b: Interrupts) -> bool {
+        a.tx == b.tx
+            && a.error_warning == b.error_warning
+            && a.overrun == b.overrun
+            && a.error_passive == b.error_passive
+    }
+
+    const NONE: Interrupts = Interrupts {
+        tx: false,
+        error_warning: false,
+        overrun: false,
+        error_passive: false,
+    };
+    const TX: Interrupts = Interrupts { tx: true, ..NONE };
+
+    // Taking the alerts keeps the transmit interrupt
+    let pending = Interrupts {
+        tx: true,
+        error_warning: true,
+        overrun: false,
+        error_passive: true,
+    };
+    let taken = pending.alerts();
+    assert!(!taken.tx && taken.error_warning && taken.error_passive);
+    assert!(same(pending.difference(taken), TX));
+
+    // Taking the transmit interrupt keeps the alerts
+    let pending = Interrupts {
+        tx: true,
+        error_warning: false,
+        overrun: true,
+        error_passive: false,
+    };
+    let taken = pending.without_alerts();
+    assert!(same(taken, TX));
+    assert!(same(pending.difference(taken), pending.alerts()));
+
+    // Interrupts read later add to the pending ones
+    let pending = TX.union(Interrupts {
+        overrun: true,
+        ..NONE
+    });
+    assert!(pending.tx && pending.overrun);
+    assert!(same(pending.difference(pending), NONE));
+};
+
+/// TWAI driver
+pub struct Twai {
+    twai: TWAI,
+    mode: TwaiMode,
+    // INT_RAW is cleared by reading it, interrupts read but not handled yet
+    int_pending: Interrupts,
+}
+
+impl Twai {
+    /// Create a new driver, all frames are accepted
+    ///
+    /// In [TwaiMode::ListenOnly] the TX pin is left untouched, the
+    /// transceiver's TX input should be pulled recessive (high). Returns
+    /// [Error::InvalidBaudRate] if the bit timing can't be set up with the
+    /// APB clock of `clocks`.
+    pub fn new<TX: OutputPin, RX: InputPin>(
+        twai: TWAI,
+        mut tx: TX,
+        mut rx: RX,
+        baud_rate: BaudRate,
+        mode: TwaiMode,
+        peripheral_clock_control: &mut PeripheralClockControl,
+        clocks: &Clocks,
+    ) -> Result<Self, Error> {
+        let timing = baud_rate.timing(clocks.apb_clock)?;
+
+        peripheral_clock_control.enable(Peripheral::Twai);
+
+        if mode != TwaiMode::ListenOnly {
+            tx.set_to_push_pull_output()
+                .connect_peripheral_to_output(OutputSignal::TWAI_TX);
+        }
+        rx.set_to_input()
+            .connect_input_to_peripheral(InputSignal::TWAI_RX);
+
+        let mut twai = Twai {
+            twai,
+            mode,
+            int_pending: Interrupts::default(),
+        };
+
+        let regs = twai.register_block();
+        regs.mode.write(|w| w.reset_mode().set_bit());
+        regs.int_ena.write(|w| unsafe { w.bits(0) });
+        regs.err_warning_limit
+            .write(|w| unsafe { w.err_warning_limit().bits(96) });
+
+        twai.set_timing(timing);
+        twai.accept_all();
+        twai.start();
+
+        Ok(twai)
+    }
+
+    fn register_block(&self) -> &RegisterBlock {
+        &self.twai
+    }
+
+    fn set_timing(&mut self, timing: TimingConfig) {
+        let regs = self.register_block();
+
+        // Most fields encode their value minus one, the prescaler counts in
+        // steps of two
+        regs.bus_timing_0.write(|w| unsafe {
+            w.baud_presc()
+                .bits(timing.baud_rate_prescaler / 2 - 1)
+                .sync_jump_width()
+                .bits(timing.sync_jump_width - 1)
+        });
+        regs.bus_timing_1.write(|w| unsafe {
+            w.time_seg1()
+                .bits(timing.tseg_1 - 1)
+                .time_seg2()
+                .bits(timing.tseg_2 - 1)
+                .time_samp()
+                .bit(timing.triple_sample)
+        });
+
+        regs.clock_divider.write(|w| w.clock_off().set_bit());
+    }
+
+    fn accept_all(&mut self) {
+        let regs = self.register_block();
+
+        // In reset mode the data registers hold the acceptance code (0) and
+        // mask (all bits "don't care") of the filter
+        let data = &regs.data_0 as *const _ as *mut u32;
+        for i in 0..8 {
+            let value = if i < 4 { 0x00 } else { 0xff };
+            unsafe { data.add(i).write_volatile(value) };
+        }
+    }
+
+    fn start(&mut self) {
+        let regs = self.register_block();
+
+        regs.tx_err_cnt.write(|w| unsafe { w.tx_err_cnt().bits(0) });
+
+        // Errata: in listen only mode the controller can still send active
+        // error flags. Starting as error passive makes it send only recessive
+        // bits, the error counters are frozen in listen only mode.
+        let rec = match self.mode {
+            TwaiMode::ListenOnly => ERROR_PASSIVE_LIMIT,
+            _ => 0,
+        };
+        regs.rx_err_cnt
+            .write(|w| unsafe { w.rx_err_cnt().bits(rec) });
+
+        // clear pending interrupts, the register is cleared by reading it
+        regs.int_raw.read();
+        self.int_pending = Interrupts::default();
+
+        let mode = self.mode;
+        regs.mode.write(|w| {
+            w.rx_filter_mode()
+                .set_bit()
+                .self_test_mode()
+                .bit(mode == TwaiMode::SelfTest)
+                .listen_only_mode()
+                .bit(mode == TwaiMode::ListenOnly)
+        });
+    }
+
+    /// Restart the controller after it went bus-off
+    pub fn restart(&mut self) {
+        self.register_block()
+            .mode
+            .write(|w| w.reset_mode().set_bit());
+        self.start();
+    }
+
+    pub fn mode(&self) -> TwaiMode {
+        self.mode
+    }
+
+    pub fn is_bus_off(&self) -> bool {
+        self.register_block()
+            .status
+            .read()
+            .bus_off_st()
+            .bit_is_set()
+    }
+
+    pub fn transmit_error_count(&self) -> u8 {
+        self.register_block().tx_err_cnt.read().tx_err_cnt().bits()
+    }
+
+    pub fn receive_error_count(&self) -> u8 {
+        self.register_block().rx_err_cnt.read().rx_err_cnt().bits()
+    }
+
+    /// Set the error count from which [AlertFlags::ERROR_WARNING] is raised
+    pub fn set_error_warning_limit(&mut self, limit: u8) {
+        let regs = self.register_block();
+        regs.mode.modify(|_, w| w.reset_mode().set_bit());
+        regs.err_warning_limit
+            .write(|w| unsafe { w.err_warning_limit().bits(limit) });
+        regs.mode.modify(|_, w| w.reset_mode().clear_bit());
+    }
+
+    /// Transmit a frame
+    ///
+    /// In [TwaiMode::SelfTest] the frame is received by the controller as
+    /// well.
+    pub fn transmit(&mut self, frame: &Frame) -> nb::Result<(), Error> {
+        self.transmit_with(frame, false)
+    }
+
+    /// Transmit a frame once, without retransmitting it when arbitration is
+    /// lost or an error occurs
+    ///
+    /// This is the single-shot transmission of time-triggered protocols, a
+    /// frame which can't be sent in its time slot is dropped. Whether the
+    /// frame was sent can be checked with [Twai::transmission_succeeded]
+    /// once the transmit buffer is free again.
+    pub fn transmit_single_shot(&mut self, frame: &Frame) -> nb::Result<(), Error> {
+        self.transmit_with(frame, true)
+    }
+
+    /// Returns `true` if the last transmission completed successfully
+    pub fn transmission_succeeded(&self) -> bool {
+        self.register_block()
+            .status
+            .read()
+            .tx_complete()
+            .bit_is_set()
+    }
+
+    fn transmit_with(&mut self, frame: &Frame, single_shot: bool) -> nb::Result<(), Error> {
+        if self.mode == TwaiMode::ListenOnly {
+            return Err(nb::Error::Other(Error::ListenOnly));
+        }
+
+        let regs = self.register_block();
+        let status = regs.status.read();
+        if status.bus_off_st().bit_is_set() {
+            return Err(nb::Error::Other(Error::BusOff));
+        }
+        if status.tx_buf_st().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let data = &regs.data_0 as *const _ as *mut u32;
+        let write = |index: usize, value: u8| unsafe {
+            data.add(index).write_volatile(value as u32);
+        };
+
+        let frame_info = (matches!(frame.id, Id::Extended(_)) as u8) << 7
+            | (frame.remote as u8) << 6
+            | frame.dlc;
+        write(0, frame_info);
+
+        let payload = match frame.id {
+            Id::Standard(id) => {
+                write(1, (id >> 3) as u8);
+                write(2, (id << 5) as u8);
+                3
+            }
+            Id::Extended(id) => {
+                write(1, (id >> 21) as u8);
+                write(2, (id >> 13) as u8);
+                write(3, (id >> 5) as u8);
+                write(4, (id << 3) as u8);
+                5
+            }
+        };
+
+        for (i, byte) in frame.data().iter().enumerate() {
+            write(payload + i, *byte);
+        }
+
+        let self_test = self.mode == TwaiMode::SelfTest;
+        regs.cmd.write(|w| {
+            w.abort_tx()
+                .bit(single_shot)
+                .tx_req()
+                .bit(!self_test)
+                .self_rx_req()
+                .bit(self_test)
+        });
+
+        Ok(())
+    }
+
+    /// Receive a frame
+    ///
+    /// Returns [Error::Overrun] once if frames were lost, the receive FIFO is
+    /// cleared in that case.
+    pub fn receive(&mut self) -> nb::Result<Frame, Error> {
+        let regs = self.register_block();
+        let status = regs.status.read();
+        if status.bus_off_st().bit_is_set() {
+            return Err(nb::Error::Other(Error::BusOff));
+        }
+        if status.overrun_st().bit_is_set() {
+            self.clear_overrun();
+            return Err(nb::Error::Other(Error::Overrun));
+        }
+        if status.rx_buf_st().bit_is_clear() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let data = &regs.data_0 as *const _ as *mut u32;
+        let read = |index: usize| unsafe { data.add(index).read_volatile() as u8 };
+
+        let frame_info = read(0);
+        let extended = frame_info & (1 << 7) != 0;
+        let remote = frame_info & (1 << 6) != 0;
+        // DLC values above 8 are allowed on the bus but mean 8 bytes
+        let dlc = (frame_info & 0x0f).min(8);
+
+        let (id, payload) = if extended {
+            let id = (read(1) as u32) << 21
+                | (read(2) as u32) << 13
+                | (read(3) as u32) << 5
+                | (read(4) as u32) >> 3;
+            (Id::Extended(id), 5)
+        } else {
+            let id = (read(1) as u16) << 3 | (read(2) as u16) >> 5;
+            (Id::Standard(id), 3)
+        };
+
+        let mut frame = Frame {
+            id,
+            remote,
+            dlc,
+            data: [0; 8],
+        };
+        if !remote {
+            for i in 0..dlc as usize {
+                frame.data[i] = read(payload + i);
+            }
+        }
+
+        regs.cmd.write(|w| w.release_buf().set_bit());
+
+        Ok(frame)
+    }
+
+    // Errata: after an overrun the message counter also counts the lost
+    // frames, the buffer has to be released for each of them before the
+    // overrun is cleared. Otherwise the FIFO gets out of sync.
+    fn clear_overrun(&mut self) {
+        let regs = self.register_block();
+
+        let count = regs.rx_message_cnt.read().rx_message_counter().bits();
+        for _ in 0..count {
+            regs.cmd.write(|w| w.release_buf().set_bit());
+        }
+        regs.cmd.write(|w| w.clr_overrun().set_bit());
+    }
+
+    /// Raise the `TWAI` interrupt for the given alerts
+    pub fn listen_alerts(&mut self, alerts: AlertFlags) {
+        self.register_block().int_ena.modify(|_, w| {
+            if alerts.intersects(AlertFlags::ERROR_WARNING | AlertFlags::BUS_OFF) {
+                w.err_warn_int_ena().set_bit();
+            }
+            if alerts.contains(AlertFlags::ERROR_PASSIVE) {
+                w.err_passive_int_ena().set_bit();
+            }
+            if alerts.contains(AlertFlags::RX_OVERRUN) {
+                w.overrun_int_ena().set_bit();
+            }
+            w
+        });
+    }
+
+    /// Stop raising the `TWAI` interrupt for all alerts
+    pub fn unlisten_alerts(&mut self) {
+        self.register_block().int_ena.modify(|_, w| {
+            w.err_warn_int_ena()
+                .clear_bit()
+                .err_passive_int_ena()
+                .clear_bit()
+                .overrun_int_ena()
+                .clear_bit()
+        });
+    }
+
+    /// Read the pending interrupts, keeping the ones `take` doesn't return
+    /// for later
+    fn take_interrupts(&mut self, take: fn(Interrupts) -> Interrupts) -> Interrupts {
+        let pending = self
+            .int_pending
+            .union(Interrupts::read(self.register_block()));
+        let taken = take(pending);
+        self.int_pending = pending.difference(taken);
+
+        taken
+    }
+
+    /// Returns the alerts raised since the last call
+    ///
+    /// Only the alert interrupts are acknowledged. The controller clears all
+    /// of its interrupts when they are read, the others (the transmit
+    /// interrupt of [QueuedTwai]) are kept by the driver until they are
+    /// handled. An overrun receive FIFO is cleared.
+    pub fn read_alerts(&mut self) -> AlertFlags {
+        let interrupts = self.take_interrupts(Interrupts::alerts);
+        let regs = self.register_block();
+        let status = regs.status.read();
+
+        let mut alerts = AlertFlags::empty();
+
+        if interrupts.error_warning {
+            if status.bus_off_st().bit_is_set() {
+                alerts = alerts | AlertFlags::BUS_OFF;
+            } else if status.err_st().bit_is_set() {
+                alerts = alerts | AlertFlags::ERROR_WARNING;
+            }
+        }
+
+        // the interrupt is raised when entering and when leaving the error
+        // passive state
+        if interrupts.error_passive
+            && (self.transmit_error_count() >= ERROR_PASSIVE_LIMIT
+                || self.receive_error_count() >= ERROR_PASSIVE_LIMIT)
+            && self.mode != TwaiMode::ListenOnly
+        {
+            alerts = alerts | AlertFlags::ERROR_PASSIVE;
+        }
+
+        if interrupts.overrun || status.overrun_st().bit_is_set() {
+            self.clear_overrun();
+            alerts = alerts | AlertFlags::RX_OVERRUN;
+        }
+
+        alerts
+    }
+
+    /// Add a software transmit queue of `N` frames, see [QueuedTwai]
+    pub fn with_tx_queue<const N: usize>(self, policy: RetryPolicy) -> QueuedTwai<N> {
+        QueuedTwai::new(self, policy)
+    }
+
+    /// Return the raw interface to the underlying peripheral
+    pub fn free(self) -> TWAI {
+        self.register_block()
+            .mode
+            .write(|w| w.reset_mode().set_bit());
+        self.twai
+    }
+}
+
+/// What the transmit queue does with a frame that lost arbitration or was
+/// destroyed by an error
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryPolicy {
+    /// The controller retransmits the frame until it's sent, the queue only
+    /// advances after a successful transmission
+    Automatic,
+    /// Frames are sent single-shot and submitted again up to the given number
+    /// of times, then dropped
+    Resubmit(u8),
+    /// Frames are sent single-shot and dropped if they fail
+    Drop,
+}
+
+impl RetryPolicy {
+    /// Whether a failed frame is submitted again after `retries` retries
+    const fn allows_retry(self, retries: u8) -> bool {
+        match self {
+            RetryPolicy::Resubmit(max) => retries < max,
+            _ => false,
+        }
+    }
+}
+
+const _: () = {
+    assert!(RetryPolicy::Resubmit(2).allows_retry(0));
+    assert!(RetryPolicy::Resubmit(2).allows_retry(1));
+    assert!(!RetryPolicy::Resubmit(2).allows_retry(2));
+    assert!(RetryPolicy::Resubmit(u8::MAX).allows_retry(u8::MAX - 1));
+    assert!(!RetryPolicy::Resubmit(u8::MAX).allows_retry(u8::MAX));
+
+    assert!(!RetryPolicy::Resubmit(0).allows_retry(0));
+    assert!(!RetryPolicy::Drop.allows_retry(0));
+    assert!(!RetryPolicy::Automatic.allows_retry(0));
+};
+
+/// The transmit queue is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueFull(pub Frame);
+
+/// TWAI driver with a software transmit queue of `N` frames
+///
+/// [QueuedTwai::transmit_queued] puts a frame into the queue and returns
+/// immediately. The frames are handed to the controller one after the other
+/// from [QueuedTwai::on_interrupt], which has to be called from the `TWAI`
+/// interrupt:
+///
+/// ```no_run
+/// let mut twai = Twai::new(/* ... */).with_tx_queue::<16>(RetryPolicy::Automatic);
+/// interrupt::enable(pac::Interrupt::TWAI, interrupt::Priority::Priority1).unwrap();
+///
+/// twai.transmit_queued(frame)?;
+///
+/// #[interrupt]
+/// fn TWAI() {
+///     critical_section::with(|cs| {
+///         TWAI.borrow_ref_mut(cs).as_mut().unwrap().on_interrupt();
+///     });
+/// }
+/// ```
+///
+/// Failed transmissions are handled according to the [RetryPolicy], a frame
+/// never blocks the queue longer than its retries. While the controller is
+/// bus-off nothing is sent, after [QueuedTwai::restart] the frame which was
+/// in flight is submitted again.
+///
+/// All other methods of [Twai] are available through `Deref`. Alerts are
+/// reported by [QueuedTwai::read_alerts], also if they were raised together
+/// with a transmit interrupt.
+pub struct QueuedTwai<const N: usize> {
+    twai: Twai,
+    policy: RetryPolicy,
+    queue: Deque<Frame, N>,
+    in_flight: Option<Frame>,
+    // failed transmissions of the frame in flight which were submitted again
+    retries: u8,
+    dropped: u32,
+}
+
+impl<const N: usize> QueuedTwai<N> {
+    fn new(twai: Twai, policy: RetryPolicy) -> Self {
+        twai.register_block()
+            .int_ena
+            .modify(|_, w| w.tx_int_ena().set_bit());
+
+        Self {
+            twai,
+            policy,
+            queue: Deque::new(),
+            in_flight: None,
+            retries: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Queue a frame for transmission
+    ///
+    /// The frame is handed to the controller right away if it's idle.
+    pub fn transmit_queued(&mut self, frame: Frame) -> Result<(), QueueFull> {
+        self.queue.push_back(frame).map_err(QueueFull)?;
+        if self.in_flight.is_none() {
+            self.submit_next();
+        }
+
+        Ok(())
+    }
+
+    /// Number of frames waiting in the queue, excluding the frame being sent
+    pub fn queued(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Returns `true` if all frames were sent (or dropped)
+    pub fn is_idle(&self) -> bool {
+        self.in_flight.is_none() && self.queue.is_empty()
+    }
+
+    /// Number of frames dropped according to the [RetryPolicy]
+    pub fn dropped(&self) -> u32 {
+        self.dropped
+    }
+
+    /// Handle a finished transmission and submit the next frame
+    ///
+    /// To be called from the `TWAI` interrupt.
+    pub fn on_interrupt(&mut self) {
+        if !self.twai.take_interrupts(Interrupts::without_alerts).tx {
+            return;
+        }
+
+        if let Some(frame) = self.in_flight.take() {
+            if !self.twai.transmission_succeeded() {
+                // with automatic retransmission only an aborted frame ends up
+                // here, it's never retried
+                if self.policy.allows_retry(self.retries) {
+                    self.retries += 1;
+                    self.submit(frame);
+                    return;
+                }
+                self.dropped += 1;
+            }
+        }
+
+        self.submit_next();
+    }
+
+    /// Returns the alerts raised since the last call, see [Twai::read_alerts]
+    ///
+    /// Reading the alerts clears the transmit interrupt of the controller as
+    /// well, a transmission it reported is handled right away: the queue
+    /// doesn't stall if this is called outside of the `TWAI` interrupt.
+    pub fn read_alerts(&mut self) -> AlertFlags {
+        let alerts = self.twai.read_alerts();
+        if self.twai.int_pending.tx {
+            self.on_interrupt();
+        }
+
+        alerts
+    }
+
+    /// Restart the controller after it went bus-off and submit the frame
+    /// which was in flight again
+    pub fn restart(&mut self) {
+        self.twai.restart();
+        self.twai
+            .register_block()
+            .int_ena
+            .modify(|_, w| w.tx_int_ena().set_bit());
+
+        match self.in_flight.take() {
+            Some(frame) => self.submit(frame),
+            None => self.submit_next(),
+        }
+    }
+
+    /// Return the driver, frames which weren't sent yet are discarded
+    pub fn into_inner(self) -> Twai {
+        self.twai
+            .register_block()
+            .int_ena
+            .modify(|_, w| w.tx_int_ena().clear_bit());
+        self.twai
+    }
+
+    fn submit_next(&mut self) {
+        if let Some(frame) = self.queue.pop_front() {
+            self.retries = 0;
+            self.submit(frame);
+        }
+    }
+
+    fn submit(&mut self, frame: Frame) {
+        let result = match self.policy {
+            RetryPolicy::Automatic => self.twai.transmit(&frame),
+            _ => self.twai.transmit_single_shot(&frame),
+        };
+
+        match result {
+            // bus-off: keep the frame for the restart
+            Ok(()) | Err(nb::Error::Other(Error::BusOff)) => self.in_flight = Some(frame),
+            // the buffer is used by a frame sent with `Twai::transmit`, try
+            // again when its transmit interrupt arrives
+            Err(nb::Error::WouldBlock) => {
+                if self.queue.push_front(frame).is_err() {
+                    self.dropped += 1;
+                }
+            }
+            Err(nb::Error::Other(_)) => self.dropped += 1,
+        }
+    }
+}
+
+impl<const N: usize> Deref for QueuedTwai<N> {
+    type Target = Twai;
+
+    fn deref(&self) -> &Self::Target {
+        &self.twai
+    }
+}
+
+impl<const N: usize> DerefMut for QueuedTwai<N> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.twai
+    }
+}
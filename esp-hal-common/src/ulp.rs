@@ -0,0 +1,470 @@
+//! ULP coprocessor
+//!
+//! The ULP coprocessor keeps running while the main CPU(s) are in deep sleep.
+//! It is woken up periodically by the ULP timer, runs a program from RTC slow
+//! memory and can wake up the main CPU again.
+//!
+//! The ESP32 only has the ULP-FSM, the ESP32-S2 and ESP32-S3 additionally
+//! have a RISC-V based ULP. Only one of them can run at a time.
+//!
+//! Programs and shared variables have to live in the part of RTC slow memory
+//! reserved for the ULP, which otherwise is used by the application. It's
+//! reserved by enabling the `ulp` feature of the chip crate.
+//!
+//! The driver is part of [crate::Rtc] and accessed as `rtc.ulp`.
+
+use core::{convert::Infallible, marker::PhantomData};
+
+use fugit::MicrosDurationU64;
+
+#[cfg(esp32)]
+use crate::pac::SENS;
+use crate::{
+    pac::RTC_CNTL,
+    rom::esp_rom_delay_us,
+    rtc_cntl::{RtcClock, SleepConfig, SleepConfigError},
+};
+
+const RTC_SLOW_MEM: usize = 0x5000_0000;
+
+// ULP-FSM binaries as produced by the ESP-IDF toolchain start with this header
+const FSM_BINARY_MAGIC: u32 = 0x0070_6c75;
+const FSM_BINARY_HEADER_SIZE: usize = 12;
+
+#[cfg(esp32)]
+const WAKEUP_TIMERS: usize = 5;
+#[cfg(any(esp32s2, esp32s3))]
+const WAKEUP_TIMERS: usize = 1;
+
+extern "C" {
+    // End of the RTC slow memory reserved for the ULP, defined by the linker
+    // script
+    static _rtc_slow_reserved_end: u32;
+}
+
+/// ULP errors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    /// The program or variable doesn't fit into the RTC slow memory reserved
+    /// for the ULP
+    OutOfBounds,
+    /// The offset or size isn't a multiple of 4 bytes
+    Misaligned,
+    /// The binary doesn't start with a valid ULP-FSM header
+    InvalidBinary,
+    /// The wakeup timer doesn't exist on this chip
+    InvalidTimer,
+}
+
+/// ULP coprocessor driver
+pub struct Ulp {
+    _private: (),
+}
+
+impl Ulp {
+    pub(crate) fn new() -> Self {
+        Self { _private: () }
+    }
+
+    /// Size of the RTC slow memory reserved for the ULP, in bytes.
+    pub fn reserved_size(&self) -> usize {
+        reserved_size()
+    }
+
+    /// Loads a ULP-FSM binary as produced by the ESP-IDF ULP toolchain.
+    ///
+    /// The text and data sections are copied to `offset` (in bytes from the
+    /// start of RTC slow memory) and the bss section is cleared.
+    pub fn load_binary(&mut self, offset: usize, binary: &[u8]) -> Result<(), Error> {
+        if binary.len() < FSM_BINARY_HEADER_SIZE {
+            return Err(Error::InvalidBinary);
+        }
+
+        let magic = u32::from_le_bytes([binary[0], binary[1], binary[2], binary[3]]);
+        let text_offset = u16::from_le_bytes([binary[4], binary[5]]) as usize;
+        let text_size = u16::from_le_bytes([binary[6], binary[7]]) as usize;
+        let data_size = u16::from_le_bytes([binary[8], binary[9]]) as usize;
+        let bss_size = u16::from_le_bytes([binary[10], binary[11]]) as usize;
+
+        let image_size = text_size + data_size;
+        if magic != FSM_BINARY_MAGIC || text_offset + image_size > binary.len() {
+            return Err(Error::InvalidBinary);
+        }
+
+        check_bounds(offset, image_size + bss_size)?;
+
+        let image = &binary[text_offset..][..image_size];
+        unsafe {
+            write_bytes(offset, image);
+            for i in (0..bss_size).step_by(4) {
+                word_ptr(offset + image_size + i).write_volatile(0);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Loads a program given as 32 bit words, e.g. ULP-FSM instructions, to
+    /// `offset` (in bytes from the start of RTC slow memory).
+    pub fn load_program(&mut self, offset: usize, program: &[u32]) -> Result<(), Error> {
+        check_bounds(offset, program.len() * 4)?;
+
+        for (i, word) in program.iter().enumerate() {
+            unsafe { word_ptr(offset + i * 4).write_volatile(*word) };
+        }
+
+        Ok(())
+    }
+
+    /// Loads a ULP-RISC-V binary to the start of RTC slow memory, where the
+    /// ULP-RISC-V starts executing.
+    #[cfg(ulp_riscv)]
+    pub fn load_riscv_binary(&mut self, binary: &[u8]) -> Result<(), Error> {
+        // the image doesn't need to be padded, round up to whole words
+        check_bounds(0, (binary.len() + 3) & !3)?;
+
+        unsafe { write_bytes(0, binary) };
+
+        Ok(())
+    }
+
+    /// Sets the period in which the ULP timer starts the program.
+    ///
+    /// The ESP32 has 5 periods (`timer` 0 to 4), which the ULP-FSM program
+    /// can switch between with the `SLEEP` instruction. The ESP32-S2 and
+    /// ESP32-S3 only have timer 0.
+    pub fn set_wakeup_period(
+        &mut self,
+        timer: usize,
+        period: MicrosDurationU64,
+    ) -> Result<(), Error> {
+        if timer >= WAKEUP_TIMERS {
+            return Err(Error::InvalidTimer);
+        }
+
+        let cycles = (period.to_micros() * RtcClock::cycles_to_1ms() as u64 / 1000) as u32;
+
+        #[cfg(esp32)]
+        {
+            let sens = unsafe { &*SENS::ptr() };
+            let cycles = cycles.min(0xff_ffff);
+            match timer {
+                0 => sens.ulp_cp_sleep_cyc0.write(|w| unsafe { w.bits(cycles) }),
+                1 => sens.ulp_cp_sleep_cyc1.write(|w| unsafe { w.bits(cycles) }),
+                2 => sens.ulp_cp_sleep_cyc2.write(|w| unsafe { w.bits(cycles) }),
+                3 => sens.ulp_cp_sleep_cyc3.write(|w| unsafe { w.bits(cycles) }),
+                _ => sens.ulp_cp_sleep_cyc4.write(|w| unsafe { w.bits(cycles) }),
+            }
+        }
+
+        #[cfg(any(esp32s2, esp32s3))]
+        {
+            let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+            let cycles = cycles.min(0xff_ffff);
+            rtc_cntl
+                .ulp_cp_timer_1
+                .modify(|_, w| unsafe { w.ulp_cp_timer_slp_cycle().bits(cycles) });
+        }
+
+        Ok(())
+    }
+
+    /// Starts the ULP-FSM.
+    ///
+    /// `entry_point` is the offset of the first instruction in bytes from the
+    /// start of RTC slow memory. The program is run immediately and then
+    /// every time the ULP timer expires, until [Ulp::stop] is called or the
+    /// program disables the timer itself.
+    pub fn start_fsm(&mut self, entry_point: usize) -> Result<(), Error> {
+        if entry_point % 4 != 0 {
+            return Err(Error::Misaligned);
+        }
+        check_bounds(entry_point, 4)?;
+
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+        let entry_point = (entry_point / 4) as u16;
+
+        #[cfg(esp32)]
+        {
+            let sens = unsafe { &*SENS::ptr() };
+
+            rtc_cntl
+                .state0
+                .modify(|_, w| w.ulp_cp_slp_timer_en().clear_bit());
+            // wait for at least one RTC_SLOW_CLK cycle
+            unsafe { esp_rom_delay_us(10) };
+
+            sens.sar_start_force.modify(|_, w| unsafe {
+                w.pc_init()
+                    .bits(entry_point)
+                    .ulp_cp_force_start_top()
+                    .clear_bit()
+            });
+
+            // keep the voltage up while the ULP uses the 8 MHz clock
+            rtc_cntl.options0.modify(|_, w| {
+                w.bias_i2c_folw_8m()
+                    .set_bit()
+                    .bias_core_folw_8m()
+                    .set_bit()
+                    .bias_sleep_folw_8m()
+                    .set_bit()
+            });
+
+            rtc_cntl
+                .state0
+                .modify(|_, w| w.ulp_cp_slp_timer_en().set_bit());
+        }
+
+        #[cfg(any(esp32s2, esp32s3))]
+        {
+            // reset the coprocessor, it might have been running the RISC-V
+            // ULP before
+            rtc_cntl
+                .cocpu_ctrl
+                .modify(|_, w| w.cocpu_shut_reset_en().set_bit());
+            unsafe { esp_rom_delay_us(20) };
+            rtc_cntl
+                .cocpu_ctrl
+                .modify(|_, w| w.cocpu_shut_reset_en().clear_bit());
+
+            rtc_cntl
+                .ulp_cp_timer
+                .modify(|_, w| w.ulp_cp_slp_timer_en().clear_bit());
+            // wait for at least one RTC_SLOW_CLK cycle
+            unsafe { esp_rom_delay_us(10) };
+
+            rtc_cntl
+                .ulp_cp_timer
+                .modify(|_, w| unsafe { w.ulp_cp_pc_init().bits(entry_point) });
+
+            // let the ULP-FSM signal DONE and select it as the timer target
+            rtc_cntl
+                .cocpu_ctrl
+                .modify(|_, w| w.cocpu_done_force().clear_bit().cocpu_sel().set_bit());
+            rtc_cntl
+                .ulp_cp_ctrl
+                .modify(|_, w| w.ulp_cp_force_start_top().clear_bit());
+
+            rtc_cntl
+                .ulp_cp_timer
+                .modify(|_, w| w.ulp_cp_slp_timer_en().set_bit());
+        }
+
+        Ok(())
+    }
+
+    /// Starts the ULP-RISC-V.
+    ///
+    /// The program is started from the beginning of RTC slow memory
+    /// immediately and then every time the ULP timer expires, until
+    /// [Ulp::stop] is called.
+    #[cfg(ulp_riscv)]
+    pub fn start_riscv(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        // reset the coprocessor and force its clock on, the trap signal doesn't
+        // have a stable reset value and would wake up the main CPU otherwise
+        rtc_cntl
+            .cocpu_ctrl
+            .modify(|_, w| w.cocpu_shut_reset_en().set_bit().cocpu_clk_fo().set_bit());
+
+        rtc_cntl
+            .ulp_cp_timer
+            .modify(|_, w| w.ulp_cp_slp_timer_en().clear_bit());
+        // wait for at least one RTC_SLOW_CLK cycle
+        unsafe { esp_rom_delay_us(20) };
+
+        // let the ULP-RISC-V signal DONE
+        rtc_cntl
+            .cocpu_ctrl
+            .modify(|_, w| w.cocpu_done_force().set_bit());
+        #[cfg(esp32s3)]
+        rtc_cntl
+            .cocpu_ctrl
+            .modify(|_, w| w.cocpu_clkgate_en().set_bit());
+
+        rtc_cntl
+            .ulp_cp_ctrl
+            .modify(|_, w| w.ulp_cp_force_start_top().clear_bit());
+        rtc_cntl
+            .ulp_cp_timer
+            .modify(|_, w| w.ulp_cp_slp_timer_en().set_bit());
+
+        // only select the RISC-V ULP as timer target once the timer is running,
+        // selecting it earlier can hang the main CPU
+        rtc_cntl.cocpu_ctrl.modify(|_, w| {
+            w.cocpu_sel()
+                .clear_bit()
+                .cocpu_shut_reset_en()
+                .clear_bit()
+                .cocpu_clk_fo()
+                .clear_bit()
+        });
+
+        // clear spurious wakeup triggers from starting up
+        unsafe { esp_rom_delay_us(20) };
+        rtc_cntl.int_clr_rtc.write(|w| {
+            w.cocpu_int_clr()
+                .set_bit()
+                .cocpu_trap_int_clr()
+                .set_bit()
+                .ulp_cp_int_clr()
+                .set_bit()
+        });
+    }
+
+    /// Stops the ULP timer, so the program isn't started again.
+    ///
+    /// A program which is currently running still runs to completion. The
+    /// ULP-RISC-V is additionally held in reset.
+    pub fn stop(&mut self) {
+        let rtc_cntl = unsafe { &*RTC_CNTL::ptr() };
+
+        #[cfg(esp32)]
+        rtc_cntl
+            .state0
+            .modify(|_, w| w.ulp_cp_slp_timer_en().clear_bit());
+
+        #[cfg(any(esp32s2, esp32s3))]
+        {
+            rtc_cntl
+                .ulp_cp_timer
+                .modify(|_, w| w.ulp_cp_slp_timer_en().clear_bit());
+
+            #[cfg(ulp_riscv)]
+            if rtc_cntl.cocpu_ctrl.read().cocpu_sel().bit_is_clear() {
+                rtc_cntl
+                    .cocpu_ctrl
+                    .modify(|_, w| w.cocpu_shut_reset_en().set_bit());
+            }
+        }
+    }
+
+    /// Puts the chip into deep sleep until the ULP program wakes it up (the
+    /// ULP-FSM `WAKE` instruction or `ulp_riscv_wakeup_main_processor`).
+    ///
+    /// The RTC slow memory and the RTC peripherals stay powered, so the ULP
+    /// keeps running and the shared variables are retained. On the ESP32-S2
+    /// and ESP32-S3 the sleep channel of the touch sensor wakes the chip as
+    /// well if it is enabled. Waking up resets the main CPU(s), i.e. the
+    /// application starts from the beginning, where
+    /// [Rtc::wake_reason](crate::Rtc::wake_reason) returns
+    /// [WakeReason::Ulp](crate::rtc_cntl::WakeReason::Ulp).
+    ///
+    /// A radio driver registered with [crate::coex::register] is notified
+    /// first.
+    pub fn sleep_until_wakeup(&mut self) -> ! {
+        // the default configuration keeps everything the ULP needs on
+        match self.sleep_until_wakeup_with_config(&SleepConfig::default()) {
+            Ok(never) => match never {},
+            Err(_) => unreachable!(),
+        }
+    }
+
+    /// Puts the chip into deep sleep like [Ulp::sleep_until_wakeup], with the
+    /// power domains of `config`, e.g. [SleepConfig::ulp_monitoring]
+    ///
+    /// Only returns if `config` powers down something the ULP needs.
+    pub fn sleep_until_wakeup_with_config(
+        &mut self,
+        config: &SleepConfig,
+    ) -> Result<Infallible, SleepConfigError> {
+        crate::rtc_cntl::sleep_deep_with_ulp(config)
+    }
+}
+
+/// Typed window onto a variable in the RTC slow memory reserved for the ULP
+///
+/// Accesses are volatile, so values written by the ULP are always read from
+/// memory.
+///
+/// Note that the ULP-FSM only reads and writes the lower 16 bits of a word;
+/// when it stores a value, the upper 16 bits are overwritten with the address
+/// of the storing instruction. Variables written by a ULP-FSM program should
+/// therefore be accessed as `u32` and masked with `0xffff`.
+pub struct UlpSharedMemory<T>
+where
+    T: SharedValue,
+{
+    offset: usize,
+    _type: PhantomData<T>,
+}
+
+impl<T> UlpSharedMemory<T>
+where
+    T: SharedValue,
+{
+    /// Creates a window onto the variable at `offset` (in bytes from the start
+    /// of RTC slow memory).
+    pub fn at(offset: usize) -> Result<Self, Error> {
+        if offset % core::mem::align_of::<T>() != 0 {
+            return Err(Error::Misaligned);
+        }
+        check_bounds_unaligned(offset, core::mem::size_of::<T>())?;
+
+        Ok(Self {
+            offset,
+            _type: PhantomData,
+        })
+    }
+
+    pub fn read(&self) -> T {
+        unsafe { ((RTC_SLOW_MEM + self.offset) as *const T).read_volatile() }
+    }
+
+    pub fn write(&mut self, value: T) {
+        unsafe { ((RTC_SLOW_MEM + self.offset) as *mut T).write_volatile(value) }
+    }
+}
+
+/// Types which can be shared with the ULP, i.e. plain integers which are valid
+/// for any bit pattern
+pub trait SharedValue: Copy + private::Sealed {}
+
+macro_rules! shared_value {
+    ($($ty:ty),+) => {
+        $(
+            impl SharedValue for $ty {}
+            impl private::Sealed for $ty {}
+        )+
+    };
+}
+
+shared_value!(u8, u16, u32, i8, i16, i32);
+
+mod private {
+    pub trait Sealed {}
+}
+
+fn reserved_size() -> usize {
+    unsafe { core::ptr::addr_of!(_rtc_slow_reserved_end) as usize - RTC_SLOW_MEM }
+}
+
+fn check_bounds(offset: usize, size: usize) -> Result<(), Error> {
+    if offset % 4 != 0 || size % 4 != 0 {
+        return Err(Error::Misaligned);
+    }
+
+    check_bounds_unaligned(offset, size)
+}
+
+fn check_bounds_unaligned(offset: usize, size: usize) -> Result<(), Error> {
+    match offset.checked_add(size) {
+        Some(end) if end <= reserved_size() => Ok(()),
+        _ => Err(Error::OutOfBounds),
+    }
+}
+
+fn word_ptr(offset: usize) -> *mut u32 {
+    (RTC_SLOW_MEM + offset) as *mut u32
+}
+
+// Copies `data` word by word, padding the last word with zeros
+unsafe fn write_bytes(offset: usize, data: &[u8]) {
+    for (i, chunk) in data.chunks(4).enumerate() {
+        let mut word = [0u8; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        word_ptr(offset + i * 4).write_volatile(u32::from_le_bytes(word));
+    }
+}
@@ -127,6 +127,31 @@ where
             .modify(|_, w| w.serial_out_recv_pkt_int_ena().clear_bit());
     }
 
+    /// Listen for TX-EMPTY interrupts, raised when the host read the last
+    /// flushed packet
+    pub fn listen_tx_empty_interrupt(&mut self) {
+        let reg_block = self.usb_serial.register_block();
+        reg_block
+            .int_ena
+            .modify(|_, w| w.serial_in_empty_int_ena().set_bit());
+    }
+
+    /// Stop listening for TX-EMPTY interrupts
+    pub fn unlisten_tx_empty_interrupt(&mut self) {
+        let reg_block = self.usb_serial.register_block();
+        reg_block
+            .int_ena
+            .modify(|_, w| w.serial_in_empty_int_ena().clear_bit());
+    }
+
+    /// Reset TX-EMPTY interrupt
+    pub fn reset_tx_empty_interrupt(&mut self) {
+        let reg_block = self.usb_serial.register_block();
+        reg_block
+            .int_clr
+            .write(|w| w.serial_in_empty_int_clr().set_bit())
+    }
+
     /// Checks if RX-PACKET-RECV interrupt is set
     pub fn rx_packet_recv_interrupt_set(&mut self) -> bool {
         let reg_block = unsafe { &*USB_DEVICE::PTR };
@@ -227,3 +252,52 @@ where
         self.flush_tx_nb()
     }
 }
+
+#[cfg(feature = "embedded-io")]
+impl<T> embedded_io::Io for UsbSerialJtag<T>
+where
+    T: Instance,
+{
+    type Error = Error;
+}
+
+#[cfg(feature = "embedded-io")]
+impl<T> embedded_io::blocking::Read for UsbSerialJtag<T>
+where
+    T: Instance,
+{
+    /// Wait for the first byte, then read the bytes already received
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        buf[0] = nb::block!(self.read_byte())?;
+
+        let mut count = 1;
+        while count < buf.len() {
+            match self.read_byte() {
+                Ok(byte) => buf[count] = byte,
+                Err(_) => break,
+            }
+            count += 1;
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(feature = "embedded-io")]
+impl<T> embedded_io::blocking::Write for UsbSerialJtag<T>
+where
+    T: Instance,
+{
+    fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.write_bytes(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<(), Self::Error> {
+        self.flush_tx()
+    }
+}
@@ -9,48 +9,175 @@
 //! but in case this is used in combination with interrupts that might disturb
 //! the sequential sending, an alternative implementation (addressing the LEDs
 //! in a sequence in a single RMT send operation) might be required!_
+//!
+//! ## Timing
+//!
+//! The pulse lengths are derived from the RMT source clock in [Clocks] when
+//! the adapter is created and again on every clock change, see
+//! [ClockListener]. The SK68XX timing (which most WS2812B accept as well) is
+//! used by default, [SmartLedsAdapter::set_timing] selects another one.
+//! Timings which can't be represented at the configured source clock and
+//! divider within [TIMING_TOLERANCE] are rejected with
+//! [LedAdapterError::InvalidTiming].
 #![deny(missing_docs)]
 
 use core::{marker::PhantomData, slice::IterMut};
 
-use fugit::NanosDuration;
+use fugit::NanosDurationU32;
 use smart_leds_trait::{SmartLedsWrite, RGB8};
 
 #[cfg(any(esp32, esp32s2))]
 use crate::pulse_control::ClockSource;
 use crate::{
+    clock::{ClockListener, Clocks},
     gpio::OutputPin,
     pulse_control::{ConfiguredChannel, OutputChannel, PulseCode, RepeatMode, TransmissionError},
 };
 
-// Specifies what clock frequency we're using for the RMT peripheral (if
-// properly configured)
-//
-// TODO: Factor in clock configuration, this needs to be revisited once #24 and
-// #44 have been addressed.
-#[cfg(esp32c3)]
-const SOURCE_CLK_FREQ: u32 = 40_000_000;
-#[cfg(esp32s2)]
-const SOURCE_CLK_FREQ: u32 = 40_000_000;
-#[cfg(esp32)]
-const SOURCE_CLK_FREQ: u32 = 40_000_000;
-#[cfg(esp32s3)]
-const SOURCE_CLK_FREQ: u32 = 40_000_000;
-
-const SK68XX_CODE_PERIOD: u32 = 1200;
-const SK68XX_T0H_NS: u32 = 320;
-const SK68XX_T0L_NS: u32 = SK68XX_CODE_PERIOD - SK68XX_T0H_NS;
-const SK68XX_T1H_NS: u32 = 640;
-const SK68XX_T1L_NS: u32 = SK68XX_CODE_PERIOD - SK68XX_T1H_NS;
-
-const SK68XX_T0H_CYCLES: NanosDuration<u32> =
-    NanosDuration::<u32>::from_ticks((SK68XX_T0H_NS * (SOURCE_CLK_FREQ / 1_000_000)) / 500);
-const SK68XX_T0L_CYCLES: NanosDuration<u32> =
-    NanosDuration::<u32>::from_ticks((SK68XX_T0L_NS * (SOURCE_CLK_FREQ / 1_000_000)) / 500);
-const SK68XX_T1H_CYCLES: NanosDuration<u32> =
-    NanosDuration::<u32>::from_ticks((SK68XX_T1H_NS * (SOURCE_CLK_FREQ / 1_000_000)) / 500);
-const SK68XX_T1L_CYCLES: NanosDuration<u32> =
-    NanosDuration::<u32>::from_ticks((SK68XX_T1L_NS * (SOURCE_CLK_FREQ / 1_000_000)) / 500);
+/// The largest deviation of a pulse from its requested length which is
+/// accepted when converting a [Ws2812Timing] to source clock ticks
+pub const TIMING_TOLERANCE: NanosDurationU32 = NanosDurationU32::from_ticks(150);
+
+/// Longest pulse the RMT can send, in ticks
+const MAX_PULSE_TICKS: u64 = 0x7fff;
+
+/// Pulse lengths of the single wire protocol of the WS2812 and compatibles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ws2812Timing {
+    /// High time of a 0 bit
+    pub t0h: NanosDurationU32,
+    /// Low time of a 0 bit
+    pub t0l: NanosDurationU32,
+    /// High time of a 1 bit
+    pub t1h: NanosDurationU32,
+    /// Low time of a 1 bit
+    pub t1l: NanosDurationU32,
+}
+
+impl Ws2812Timing {
+    /// Timing of the SK68XX, 1.2 µs per bit
+    pub const SK68XX: Ws2812Timing = Ws2812Timing {
+        t0h: NanosDurationU32::from_ticks(320),
+        t0l: NanosDurationU32::from_ticks(1200 - 320),
+        t1h: NanosDurationU32::from_ticks(640),
+        t1l: NanosDurationU32::from_ticks(1200 - 640),
+    };
+}
+
+impl Default for Ws2812Timing {
+    fn default() -> Self {
+        Ws2812Timing::SK68XX
+    }
+}
+
+/// Length of a pulse of `ns` nanoseconds in ticks of a `tick_hz` clock,
+/// `None` if it is longer than the RMT can send or can't be matched within
+/// [TIMING_TOLERANCE]
+const fn pulse_ticks(ns: u32, tick_hz: u32) -> Option<u32> {
+    // Nanoseconds times ticks per second, to round and compare exactly
+    let requested = ns as u64 * tick_hz as u64;
+    let ticks = (requested + 500_000_000) / 1_000_000_000;
+    let actual = ticks * 1_000_000_000;
+    let error = if actual > requested {
+        actual - requested
+    } else {
+        requested - actual
+    };
+
+    if ticks == 0
+        || ticks > MAX_PULSE_TICKS
+        || error > TIMING_TOLERANCE.ticks() as u64 * tick_hz as u64
+    {
+        None
+    } else {
+        Some(ticks as u32)
+    }
+}
+
+/// The RMT RAM entries of a 0 and a 1 bit
+const fn pulse_entries(timing: Ws2812Timing, tick_hz: u32) -> Option<(u32, u32)> {
+    match (
+        pulse_ticks(timing.t0h.ticks(), tick_hz),
+        pulse_ticks(timing.t0l.ticks(), tick_hz),
+        pulse_ticks(timing.t1h.ticks(), tick_hz),
+        pulse_ticks(timing.t1l.ticks(), tick_hz),
+    ) {
+        (Some(t0h), Some(t0l), Some(t1h), Some(t1l)) => Some((
+            PulseCode {
+                level1: true,
+                length1: NanosDurationU32::from_ticks(t0h),
+                level2: false,
+                length2: NanosDurationU32::from_ticks(t0l),
+            }
+            .to_entry(),
+            PulseCode {
+                level1: true,
+                length1: NanosDurationU32::from_ticks(t1h),
+                level2: false,
+                length2: NanosDurationU32::from_ticks(t1l),
+            }
+            .to_entry(),
+        )),
+        _ => None,
+    }
+}
+
+// The SK68XX timing at the APB clock (80 MHz), the XTAL clock (40 MHz) and
+// the fast RC oscillator (17.5 MHz) of the ESP32-C3 and ESP32-S3
+const _: () = {
+    assert!(matches!(pulse_ticks(320, 80_000_000), Some(26)));
+    assert!(matches!(pulse_ticks(880, 80_000_000), Some(70)));
+    assert!(matches!(pulse_ticks(640, 80_000_000), Some(51)));
+    assert!(matches!(pulse_ticks(560, 80_000_000), Some(45)));
+
+    assert!(matches!(pulse_ticks(320, 40_000_000), Some(13)));
+    assert!(matches!(pulse_ticks(880, 40_000_000), Some(35)));
+    assert!(matches!(pulse_ticks(640, 40_000_000), Some(26)));
+    assert!(matches!(pulse_ticks(560, 40_000_000), Some(22)));
+
+    assert!(matches!(pulse_ticks(320, 17_500_000), Some(6)));
+    assert!(matches!(pulse_ticks(880, 17_500_000), Some(15)));
+    assert!(matches!(pulse_ticks(640, 17_500_000), Some(11)));
+    assert!(matches!(pulse_ticks(560, 17_500_000), Some(10)));
+
+    assert!(pulse_entries(Ws2812Timing::SK68XX, 80_000_000).is_some());
+    assert!(pulse_entries(Ws2812Timing::SK68XX, 40_000_000).is_some());
+    assert!(pulse_entries(Ws2812Timing::SK68XX, 17_500_000).is_some());
+
+    // A 1 MHz REF_TICK is too coarse, 0.5 ms don't fit into 15 bits at 80 MHz
+    assert!(pulse_ticks(320, 1_000_000).is_none());
+    assert!(pulse_ticks(500_000, 80_000_000).is_none());
+    assert!(pulse_entries(Ws2812Timing::SK68XX, 1_000_000).is_none());
+};
+
+/// Frequency of the RMT channel clock, the source clock selected by
+/// `PulseControl::new` divided by its divider (the channel divider is 1)
+#[cfg(any(esp32c3, esp32s3))]
+fn tick_frequency(clocks: &Clocks) -> u32 {
+    use crate::{clock::Clock, rtc_cntl::RtcFastClock};
+
+    let sys_conf = unsafe { &*crate::pac::RMT::PTR }.sys_conf.read();
+    let source = match sys_conf.sclk_sel().bits() {
+        2 => RtcFastClock::RtcFastClock8m.frequency(),
+        3 => clocks.xtal_clock,
+        _ => clocks.apb_clock,
+    };
+
+    // The divider is `sclk_div_num + 1 + sclk_div_a / sclk_div_b`
+    let num = sys_conf.sclk_div_num().bits() as u64 + 1;
+    let a = sys_conf.sclk_div_a().bits() as u64;
+    let b = sys_conf.sclk_div_b().bits() as u64;
+    let (numerator, denominator) = if b == 0 { (1, num) } else { (b, num * b + a) };
+
+    (source.to_Hz() as u64 * numerator / denominator) as u32
+}
+
+/// Frequency of the RMT channel clock, the APB clock (the channel divider is
+/// 1)
+#[cfg(any(esp32, esp32s2))]
+fn tick_frequency(clocks: &Clocks) -> u32 {
+    clocks.apb_clock.to_Hz()
+}
 
 /// All types of errors that can happen during the conversion and transmission
 /// of LED commands
@@ -60,6 +187,8 @@ pub enum LedAdapterError {
     BufferSizeExceeded,
     /// Raised if something goes wrong in the transmission,
     TransmissionError(TransmissionError),
+    /// Raised if the timing can't be represented at the RMT clock
+    InvalidTiming,
 }
 
 /// Macro to generate adapters with an arbitrary buffer size fitting for a
@@ -84,6 +213,11 @@ macro_rules! smartLedAdapter {
 pub struct SmartLedsAdapter<CHANNEL, PIN, const BUFFER_SIZE: usize> {
     channel: CHANNEL,
     rmt_buffer: [u32; BUFFER_SIZE],
+    timing: Ws2812Timing,
+    tick_hz: u32,
+    /// The RMT RAM entries of a 0 and a 1 bit, `None` if [Self::timing] can't
+    /// be represented at the current clocks
+    pulses: Option<(u32, u32)>,
     _pin: PhantomData<PIN>,
 }
 
@@ -93,10 +227,15 @@ where
     PIN: OutputPin,
 {
     /// Create a new adapter object that drives the pin using the RMT channel.
+    ///
+    /// The pulses use the SK68XX timing at the RMT clock derived from
+    /// `clocks`, on the ESP32-C3 and ESP32-S3 this includes the source clock
+    /// and divider passed to `PulseControl::new`.
     pub fn new<UnconfiguredChannel>(
         mut channel: UnconfiguredChannel,
         pin: PIN,
-    ) -> SmartLedsAdapter<CHANNEL, PIN, BUFFER_SIZE>
+        clocks: &Clocks,
+    ) -> Result<SmartLedsAdapter<CHANNEL, PIN, BUFFER_SIZE>, LedAdapterError>
     where
         UnconfiguredChannel: OutputChannel<CHANNEL>,
     {
@@ -115,26 +254,51 @@ where
             .set_idle_output(true)
             .set_clock_source(ClockSource::APB);
 
+        let timing = Ws2812Timing::default();
+        let tick_hz = tick_frequency(clocks);
+        let pulses = pulse_entries(timing, tick_hz).ok_or(LedAdapterError::InvalidTiming)?;
+
         let channel = channel.assign_pin(pin);
-        Self {
+        Ok(Self {
             channel,
             rmt_buffer: [0; BUFFER_SIZE],
+            timing,
+            tick_hz,
+            pulses: Some(pulses),
             _pin: PhantomData,
-        }
+        })
+    }
+
+    /// Use `timing` for the following writes
+    ///
+    /// Returns [LedAdapterError::InvalidTiming] and keeps the previous timing
+    /// if a pulse is longer than the RMT can send or can't be matched within
+    /// [TIMING_TOLERANCE] at the RMT clock.
+    pub fn set_timing(&mut self, timing: Ws2812Timing) -> Result<(), LedAdapterError> {
+        let pulses = pulse_entries(timing, self.tick_hz).ok_or(LedAdapterError::InvalidTiming)?;
+        self.timing = timing;
+        self.pulses = Some(pulses);
+        Ok(())
+    }
+
+    /// The timing used for the pulses
+    pub fn timing(&self) -> Ws2812Timing {
+        self.timing
     }
 
     fn convert_rgb_to_pulse(
         value: RGB8,
+        pulses: (u32, u32),
         mut_iter: &mut IterMut<u32>,
     ) -> Result<(), LedAdapterError> {
         SmartLedsAdapter::<CHANNEL, PIN, BUFFER_SIZE>::convert_rgb_channel_to_pulses(
-            value.g, mut_iter,
+            value.g, pulses, mut_iter,
         )?;
         SmartLedsAdapter::<CHANNEL, PIN, BUFFER_SIZE>::convert_rgb_channel_to_pulses(
-            value.r, mut_iter,
+            value.r, pulses, mut_iter,
         )?;
         SmartLedsAdapter::<CHANNEL, PIN, BUFFER_SIZE>::convert_rgb_channel_to_pulses(
-            value.b, mut_iter,
+            value.b, pulses, mut_iter,
         )?;
 
         Ok(())
@@ -142,25 +306,14 @@ where
 
     fn convert_rgb_channel_to_pulses(
         channel_value: u8,
+        (zero, one): (u32, u32),
         mut_iter: &mut IterMut<u32>,
     ) -> Result<(), LedAdapterError> {
         for position in [128, 64, 32, 16, 8, 4, 2, 1] {
             *mut_iter.next().ok_or(LedAdapterError::BufferSizeExceeded)? =
                 match channel_value & position {
-                    0 => PulseCode {
-                        level1: true,
-                        length1: SK68XX_T0H_CYCLES,
-                        level2: false,
-                        length2: SK68XX_T0L_CYCLES,
-                    }
-                    .into(),
-                    _ => PulseCode {
-                        level1: true,
-                        length1: SK68XX_T1H_CYCLES,
-                        level2: false,
-                        length2: SK68XX_T1L_CYCLES,
-                    }
-                    .into(),
+                    0 => zero,
+                    _ => one,
                 }
         }
 
@@ -168,6 +321,18 @@ where
     }
 }
 
+impl<CHANNEL, PIN, const BUFFER_SIZE: usize> ClockListener
+    for SmartLedsAdapter<CHANNEL, PIN, BUFFER_SIZE>
+{
+    /// The RMT clock follows the APB (or the XTAL) clock, so the pulses are
+    /// converted again. Writes fail with [LedAdapterError::InvalidTiming] if
+    /// the timing can't be represented at the new clocks.
+    fn clocks_changed(&mut self, clocks: &Clocks) {
+        self.tick_hz = tick_frequency(clocks);
+        self.pulses = pulse_entries(self.timing, self.tick_hz);
+    }
+}
+
 impl<CHANNEL, PIN, const BUFFER_SIZE: usize> SmartLedsWrite
     for SmartLedsAdapter<CHANNEL, PIN, BUFFER_SIZE>
 where
@@ -186,6 +351,7 @@ where
         I: Into<Self::Color>,
     {
         // We always start from the beginning of the buffer
+        let pulses = self.pulses.ok_or(LedAdapterError::InvalidTiming)?;
         let mut seq_iter = self.rmt_buffer.iter_mut();
 
         // Add all converted iterator items to the buffer.
@@ -194,6 +360,7 @@ where
         for item in iterator {
             SmartLedsAdapter::<CHANNEL, PIN, BUFFER_SIZE>::convert_rgb_to_pulse(
                 item.into(),
+                pulses,
                 &mut seq_iter,
             )?;
         }
@@ -48,6 +48,12 @@ fn generate_memory_extras() -> Vec<u8> {
         "0x0"
     };
 
+    let reserve_rtc_slow = if cfg!(feature = "ulp") {
+        "0x1000"
+    } else {
+        "0x0"
+    };
+
     format!(
         "
     /* reserved at the start of DRAM for e.g. the BT stack */
@@ -55,7 +61,8 @@ fn generate_memory_extras() -> Vec<u8> {
     
     /* reserved at the start of the RTC memories for use by the ULP processor */
     RESERVE_RTC_FAST = 0;
-    RESERVE_RTC_SLOW = 0;
+    RESERVE_RTC_SLOW = {reserve_rtc_slow};
+    _rtc_slow_reserved_end = 0x50000000 + RESERVE_RTC_SLOW;
     
     /* define stack size for both cores */
     STACK_SIZE = 8k;"
@@ -0,0 +1,106 @@
+//! Calibrates the ADC and DAC1 against each other
+//!
+//! Connect DAC1 (GPIO25) to GPIO32 (ADC1 channel 5). The DAC is swept across
+//! its range to measure the calibration curve, which is stored to bytes and
+//! loaded again as an application would do with flash.
+//!
+//! A second sweep measures the codes halfway between the calibration points
+//! and prints the error of the ADC against the nominal DAC voltage, once
+//! with only gain and offset corrected (a straight line through the lowest
+//! and highest calibration point, i.e. the INL) and once corrected with the
+//! curve.
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{
+    adc::{AdcConfig, Attenuation, ADC, ADC1},
+    analog::{self_calibrate, CalibrationCurve},
+    dac,
+    init,
+    pac::Peripherals,
+    prelude::*,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+    let pins = hal.io.pins;
+
+    let analog = peripherals.SENS.split();
+    let mut dac1 = dac::DAC1::dac(analog.dac1, pins.gpio25.into_analog()).unwrap();
+
+    let mut adc1_config = AdcConfig::new();
+    let mut pin = adc1_config.enable_pin(pins.gpio32.into_analog(), Attenuation::Attenuation11dB);
+    let mut adc1 = ADC::<ADC1>::adc(analog.adc1, adc1_config).unwrap();
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    let curve = self_calibrate(&mut dac1, &mut adc1, &mut pin, &mut delay).unwrap();
+    println!("{} calibration points", curve.len);
+
+    let bytes = curve.to_bytes();
+    let curve = CalibrationCurve::from_bytes(&bytes).unwrap();
+
+    let len = curve.len as usize;
+    let first = (curve.adc_raw[0] as i32, nominal_mv(&curve, 0));
+    let last = (curve.adc_raw[len - 1] as i32, nominal_mv(&curve, len - 1));
+
+    let mut max_linear = 0;
+    let mut max_corrected = 0;
+
+    for index in 0..len - 1 {
+        let code = ((curve.dac_codes[index] + curve.dac_codes[index + 1]) / 2) as u8;
+        dac1.write(code);
+        delay.delay_ms(2u32);
+
+        let mut sum = 0u32;
+        for _ in 0..64 {
+            let raw: u16 = nb::block!(adc1.read(&mut pin)).unwrap();
+            sum += raw as u32;
+        }
+        let raw = ((sum + 32) / 64) as u16;
+
+        let expected = dac::mv_for_code(code, curve.vdd_mv) as i32;
+        let linear = line(raw as i32, first, last) - expected;
+        let corrected = curve.adc_mv(raw) as i32 - expected;
+
+        println!(
+            "code {:3}: {:4} mV, error {:4} mV linear, {:4} mV corrected",
+            code, expected, linear, corrected
+        );
+
+        max_linear = max_linear.max(linear.abs());
+        max_corrected = max_corrected.max(corrected.abs());
+    }
+
+    println!(
+        "INL {} mV before, {} mV after correction",
+        max_linear, max_corrected
+    );
+
+    for mv in [500, 1000, 1500, 2000] {
+        println!(
+            "{} mV: DAC code {} nominal, {} corrected",
+            mv,
+            dac::code_for_mv(mv, curve.vdd_mv),
+            curve.dac_code_for_mv(mv)
+        );
+    }
+
+    loop {}
+}
+
+fn nominal_mv(curve: &CalibrationCurve, index: usize) -> i32 {
+    dac::mv_for_code(curve.dac_codes[index] as u8, curve.vdd_mv) as i32
+}
+
+/// The `y` of the line through `a` and `b` at `x`
+fn line(x: i32, a: (i32, i32), b: (i32, i32)) -> i32 {
+    a.1 + (x - a.0) * (b.1 - a.1) / (b.0 - a.0)
+}
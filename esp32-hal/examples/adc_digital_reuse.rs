@@ -0,0 +1,54 @@
+//! Uses a pin for the ADC and turns it back into a digital pin afterwards.
+//!
+//! GPIO36 (an input-only pin) and GPIO32 are read with ADC1 a couple of times.
+//! After that GPIO32 is turned into a push-pull output and toggled, while
+//! GPIO36 keeps being read.
+//!
+//! Connect a potentiometer to GPIO36 and a LED to GPIO32.
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{
+    adc::{AdcConfig, Attenuation, ADC, ADC1},
+    init,
+    pac::Peripherals,
+    prelude::*,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+    let pins = hal.io.pins;
+
+    let analog = peripherals.SENS.split();
+
+    let mut adc1_config = AdcConfig::new();
+    let mut pin36 = adc1_config.enable_pin(pins.gpio36.into_analog(), Attenuation::Attenuation11dB);
+    let mut pin32 = adc1_config.enable_pin(pins.gpio32.into_analog(), Attenuation::Attenuation11dB);
+    let mut adc1 = ADC::<ADC1>::adc(analog.adc1, adc1_config).unwrap();
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    for _ in 0..5 {
+        let pin36_value: u16 = nb::block!(adc1.read(&mut pin36)).unwrap();
+        let pin32_value: u16 = nb::block!(adc1.read(&mut pin32)).unwrap();
+        println!("GPIO36 = {}, GPIO32 = {}", pin36_value, pin32_value);
+        delay.delay_ms(500u32);
+    }
+
+    // Detach GPIO32 from the RTC domain and use it as a digital output
+    let mut led = pin32.pin.into_push_pull_output();
+
+    loop {
+        let pin36_value: u16 = nb::block!(adc1.read(&mut pin36)).unwrap();
+        println!("GPIO36 = {}", pin36_value);
+        led.toggle().unwrap();
+        delay.delay_ms(500u32);
+    }
+}
@@ -0,0 +1,123 @@
+//! Compares the noise of single and oversampled ADC reads
+//!
+//! Connect GPIO32 and GPIO33 to the same voltage, e.g. the middle of a
+//! 10 kOhm / 10 kOhm divider between 3V3 and GND. GPIO32 is read with single
+//! conversions, GPIO33 averages 64 conversions per read. Both are read 256
+//! times, the spread of the oversampled reads should be several times
+//! smaller.
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{
+    adc::{AdcConfig, Attenuation, Oversample, ADC, ADC1},
+    init,
+    pac::Peripherals,
+    prelude::*,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+const READS: u32 = 256;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+    let pins = hal.io.pins;
+
+    let analog = peripherals.SENS.split();
+    let mut adc1_config = AdcConfig::new();
+    let mut single =
+        adc1_config.enable_pin(pins.gpio32.into_analog(), Attenuation::Attenuation11dB);
+    let mut oversampled = adc1_config.enable_pin_with_oversampling(
+        pins.gpio33.into_analog(),
+        Attenuation::Attenuation11dB,
+        Oversample::X64,
+    );
+    let mut adc1 = ADC::<ADC1>::adc(analog.adc1, adc1_config).unwrap();
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    loop {
+        let mut stats = [Stats::new(), Stats::new()];
+        for _ in 0..READS {
+            let value: u16 = nb::block!(adc1.read(&mut single)).unwrap();
+            stats[0].add(value);
+            let value: u16 = nb::block!(adc1.read(&mut oversampled)).unwrap();
+            stats[1].add(value);
+        }
+
+        for (name, stats) in ["single", "X64"].iter().zip(stats.iter()) {
+            println!(
+                "{:>6}: mean {}, min {}, max {}, standard deviation {}.{:02}",
+                name,
+                stats.sum / READS,
+                stats.min,
+                stats.max,
+                stats.std_dev_centi() / 100,
+                stats.std_dev_centi() % 100
+            );
+        }
+        println!("");
+
+        delay.delay_ms(1000u32);
+    }
+}
+
+struct Stats {
+    sum: u32,
+    sum_of_squares: u64,
+    min: u16,
+    max: u16,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Self {
+            sum: 0,
+            sum_of_squares: 0,
+            min: u16::MAX,
+            max: 0,
+        }
+    }
+
+    fn add(&mut self, value: u16) {
+        self.sum += value as u32;
+        self.sum_of_squares += value as u64 * value as u64;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Standard deviation in hundredths of an LSB
+    fn std_dev_centi(&self) -> u64 {
+        let n = READS as u64;
+        let sum = self.sum as u64;
+        // n² · variance, scaled by 100² for two decimals
+        let variance = (n * self.sum_of_squares - sum * sum) * 10_000;
+        isqrt(variance) / n
+    }
+}
+
+fn isqrt(value: u64) -> u64 {
+    let mut root = 0u64;
+    let mut bit = 1u64 << 62;
+    let mut rest = value;
+
+    while bit > rest {
+        bit >>= 2;
+    }
+    while bit != 0 {
+        if rest >= root + bit {
+            rest -= root + bit;
+            root = (root >> 1) + bit;
+        } else {
+            root >>= 1;
+        }
+        bit >>= 2;
+    }
+
+    root
+}
@@ -0,0 +1,45 @@
+//! Ramps DAC1 (GPIO25) from 0 to 3.3 V in steps of 100 mV
+//!
+//! Each step is held for a second, check it with a voltmeter on GPIO25.
+//! DAC2 (GPIO26) is calibrated with two points measured on a typical board,
+//! measure the lowest and highest code of your board and replace them to
+//! compare both channels.
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{dac, init, pac::Peripherals, prelude::*, Delay};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+    let pins = hal.io.pins;
+
+    let analog = peripherals.SENS.split();
+    let mut dac1 = dac::DAC1::dac(analog.dac1, pins.gpio25.into_analog()).unwrap();
+    let mut dac2 = dac::DAC2::dac(analog.dac2, pins.gpio26.into_analog()).unwrap();
+
+    dac1.set_vdd_mv(3300);
+    dac2.set_calibration(dac::Calibration::new((0, 85), (255, 3180)));
+
+    println!(
+        "DAC1 reaches {} mV, DAC2 {} mV",
+        dac1.max_mv(),
+        dac2.max_mv()
+    );
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    loop {
+        for mv in (0..=3300).step_by(100) {
+            dac1.write_mv(mv);
+            dac2.write_mv(mv);
+            println!("{} mV", mv);
+            delay.delay_ms(1000u32);
+        }
+    }
+}
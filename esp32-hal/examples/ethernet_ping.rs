@@ -0,0 +1,112 @@
+//! Answers pings on Ethernet, on a WT32-ETH01 style board
+//!
+//! The LAN8720 PHY is at SMI address 1, its 50 MHz reference clock comes
+//! from an oscillator on GPIO0 which is enabled by GPIO16:
+//! - MDC  => GPIO23
+//! - MDIO => GPIO18
+//! - RMII => GPIO19, 21, 22 (TX) and GPIO25, 26, 27 (RX)
+//!
+//! The board gets the address 192.168.1.50/24, `ping 192.168.1.50` from a
+//! host in the same network. Link changes are printed.
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{
+    emac::{self, phy::Phy, DmaBuffers, Emac, RmiiClock, RmiiPins},
+    init,
+    pac::Peripherals,
+    prelude::*,
+    time,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use smoltcp::{
+    iface::{InterfaceBuilder, NeighborCache, SocketStorage},
+    time::Instant,
+    wire::{EthernetAddress, IpAddress, IpCidr},
+};
+use xtensa_lx_rt::entry;
+
+static mut BUFFERS: DmaBuffers<8, 4> = DmaBuffers::new();
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    // the oscillator has to run before the MAC is reset
+    let mut oscillator_enable = hal.io.pins.gpio16.into_push_pull_output();
+    oscillator_enable.set_high().unwrap();
+    delay.delay_ms(10u32);
+
+    let pins = RmiiPins {
+        tx_en: hal.io.pins.gpio21.into_alternate_5(),
+        txd0: hal.io.pins.gpio19.into_alternate_5(),
+        txd1: hal.io.pins.gpio22.into_alternate_5(),
+        rxd0: hal.io.pins.gpio25.into_alternate_5(),
+        rxd1: hal.io.pins.gpio26.into_alternate_5(),
+        crs_dv: hal.io.pins.gpio27.into_alternate_5(),
+    };
+    let mac_address = emac::default_mac_address();
+
+    let mut emac = Emac::new(
+        pins,
+        RmiiClock::ExternalInput(hal.io.pins.gpio0.into_alternate_5()),
+        hal.io.pins.gpio23,
+        hal.io.pins.gpio18,
+        mac_address,
+        unsafe { &mut BUFFERS },
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    )
+    .unwrap();
+
+    let phy = Phy::lan8720(1);
+    phy.init(&mut emac).unwrap();
+    println!(
+        "PHY {:08x} ready, MAC address {:02x?}",
+        phy.id(&mut emac).unwrap(),
+        mac_address
+    );
+
+    let mut neighbor_storage = [None; 8];
+    let mut ip_addresses = [IpCidr::new(IpAddress::v4(192, 168, 1, 50), 24)];
+    let mut sockets: [SocketStorage; 0] = [];
+    let mut interface = InterfaceBuilder::new(emac, &mut sockets[..])
+        .hardware_addr(EthernetAddress(mac_address).into())
+        .neighbor_cache(NeighborCache::new(&mut neighbor_storage[..]))
+        .ip_addrs(&mut ip_addresses[..])
+        .finalize();
+
+    let mut link = None;
+    let mut next_link_check = 0;
+
+    loop {
+        let now = time::now() / (time::TICKS_PER_SECOND / 1_000_000);
+
+        if now >= next_link_check {
+            next_link_check = now + 1_000_000;
+
+            let emac = interface.device_mut();
+            let current = phy.link(emac).unwrap();
+            if current != link {
+                match current {
+                    Some(current) => {
+                        emac.set_link(current);
+                        println!("Link up: {:?}", current);
+                    }
+                    None => println!("Link down"),
+                }
+                link = current;
+            }
+        }
+
+        if let Err(error) = interface.poll(Instant::from_micros(now as i64)) {
+            println!("Poll failed: {:?}", error);
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Per-pin GPIO handlers in two priority tiers
+//!
+//! The LEDC outputs a 1 kHz square wave on GPIO18, connect it to GPIO4. Every
+//! rising edge on GPIO4 is counted by a handler in the IRAM tier, which runs
+//! at priority 3. Pressing the boot button (GPIO0) calls a handler in the
+//! normal tier at priority 1, which keeps the CPU busy for a second. The
+//! counter still advances by about 1000 in the meantime, because the IRAM
+//! tier preempts the slow handler.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicU32, Ordering};
+
+use esp32_hal::{
+    clock::CpuClock,
+    gpio::{dispatch, Event},
+    init,
+    interrupt,
+    ledc::{
+        channel::{self, ChannelIFace},
+        timer::{self, TimerIFace},
+        HighSpeed,
+        LEDC,
+    },
+    macros::ram,
+    pac::{self, Peripherals},
+    prelude::*,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+static EDGES: AtomicU32 = AtomicU32::new(0);
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(
+        peripherals,
+        init::Config::default().cpu_clock(CpuClock::Clock240MHz)
+    );
+
+    let ledc = LEDC::new(
+        peripherals.LEDC,
+        &hal.clocks,
+        &mut hal.peripheral_clock_control,
+    );
+    let mut hstimer0 = ledc.get_timer::<HighSpeed>(timer::Number::Timer0);
+    hstimer0
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty10Bit,
+            clock_source: timer::HSClockSource::APBClk,
+            frequency: 1u32.kHz(),
+        })
+        .unwrap();
+
+    let mut channel0 = ledc.get_channel(
+        channel::Number::Channel0,
+        hal.io.pins.gpio18.into_push_pull_output(),
+    );
+    channel0
+        .configure(channel::config::Config {
+            timer: &hstimer0,
+            duty_pct: 50,
+        })
+        .unwrap();
+
+    let mut signal = hal.io.pins.gpio4.into_floating_input();
+    let mut button = hal.io.pins.gpio0.into_pull_up_input();
+
+    dispatch::register_iram_handler(&mut signal, Event::RisingEdge, count_edge);
+    dispatch::register_handler(&mut button, Event::FallingEdge, busy_for_a_second);
+
+    interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority1).unwrap();
+    interrupt::enable(pac::Interrupt::GPIO_NMI, interrupt::Priority::Priority3).unwrap();
+
+    loop {}
+}
+
+#[ram]
+fn count_edge() {
+    EDGES.fetch_add(1, Ordering::Relaxed);
+}
+
+fn busy_for_a_second() {
+    let before = EDGES.load(Ordering::Relaxed);
+    xtensa_lx::timer::delay(240_000_000);
+    let after = EDGES.load(Ordering::Relaxed);
+
+    println!("{} edges counted while busy", after - before);
+}
+
+#[interrupt]
+fn GPIO() {
+    dispatch::handle_interrupt();
+}
+
+#[ram]
+#[interrupt]
+fn GPIO_NMI() {
+    dispatch::handle_iram_interrupt();
+}
@@ -0,0 +1,123 @@
+//! Decodes a rotary encoder with the GPIO dispatcher
+//!
+//! Connect the A and B outputs of a mechanical quadrature encoder to GPIO18
+//! and GPIO19 and its common pin to ground. Both pins listen for any edge,
+//! the handler of each pin gets the levels of both pins as read when the
+//! dispatcher was entered, so the state of the encoder is never assembled
+//! from levels read at different times.
+//!
+//! The position is printed in detents (four steps). Turned back to where it
+//! started it reads the same as before, no matter how fast it was turned.
+//! Skipped states, which would mean a lost step, are counted and printed as
+//! well and have to stay at 0.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+
+use esp32_hal::{
+    gpio::{
+        dispatch::{self, Snapshot, SnapshotPins},
+        Event,
+    },
+    init,
+    interrupt,
+    macros::ram,
+    pac::{self, Peripherals},
+    prelude::*,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+/// Marks a transition which skipped a state
+const SKIPPED: i8 = 2;
+
+/// Steps for the transitions from the previous state (`A << 1 | B`, times 4)
+/// to the new state
+const STEPS: [i8; 16] = [
+    0, -1, 1, SKIPPED, // from 00
+    1, 0, SKIPPED, -1, // from 01
+    -1, SKIPPED, 0, 1, // from 10
+    SKIPPED, 1, -1, 0, // from 11
+];
+
+static STATE: AtomicU32 = AtomicU32::new(0);
+static POSITION: AtomicI32 = AtomicI32::new(0);
+static SKIPPED_STATES: AtomicU32 = AtomicU32::new(0);
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let mut a = hal.io.pins.gpio18.into_pull_up_input();
+    let mut b = hal.io.pins.gpio19.into_pull_up_input();
+
+    let state = (a.is_high().unwrap() as u8) << 1 | b.is_high().unwrap() as u8;
+    STATE.store(state as u32, Ordering::Relaxed);
+
+    dispatch::register_iram_snapshot_handler(
+        &mut a,
+        Event::AnyEdge,
+        SnapshotPins::new().with(&b),
+        on_a,
+    );
+    dispatch::register_iram_snapshot_handler(
+        &mut b,
+        Event::AnyEdge,
+        SnapshotPins::new().with(&a),
+        on_b,
+    );
+
+    interrupt::enable(pac::Interrupt::GPIO_NMI, interrupt::Priority::Priority3).unwrap();
+
+    let mut delay = Delay::new(&hal.clocks);
+    let mut printed = None;
+
+    loop {
+        let position = POSITION.load(Ordering::Relaxed).div_euclid(4);
+        let skipped = SKIPPED_STATES.load(Ordering::Relaxed);
+
+        if printed != Some((position, skipped)) {
+            println!("position {}, skipped states {}", position, skipped);
+            printed = Some((position, skipped));
+        }
+
+        delay.delay_ms(50u32);
+    }
+}
+
+#[ram]
+fn on_a(snapshot: Snapshot) {
+    step((snapshot.is_high() as u8) << 1 | snapshot.is_high_at(0) as u8);
+}
+
+#[ram]
+fn on_b(snapshot: Snapshot) {
+    step((snapshot.is_high_at(0) as u8) << 1 | snapshot.is_high() as u8);
+}
+
+/// Move to `state`, both handlers run at the same priority and don't
+/// preempt each other
+#[inline(always)]
+fn step(state: u8) {
+    let previous = STATE.swap(state as u32, Ordering::Relaxed) as u8;
+
+    match STEPS[(previous << 2 | state) as usize] {
+        SKIPPED => {
+            SKIPPED_STATES.fetch_add(1, Ordering::Relaxed);
+        }
+        steps => {
+            POSITION.fetch_add(steps as i32, Ordering::Relaxed);
+        }
+    }
+}
+
+#[ram]
+#[interrupt]
+fn GPIO_NMI() {
+    dispatch::handle_iram_interrupt();
+}
@@ -40,9 +40,10 @@ fn main() -> ! {
     wdt.disable();
     rtc.rwdt.disable();
 
-    // Set GPIO15 as an output, and set its state high initially.
+    // Set GPIO15 as an output, and set its state high initially. GPIO15 is a
+    // strapping pin, it only silences the boot messages if held low at reset.
     let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
-    let mut led = io.pins.gpio15.into_push_pull_output();
+    let mut led = io.pins.gpio15.into_push_pull_output_unchecked();
     let mut button = io.pins.gpio0.into_pull_down_input();
     button.listen(Event::FallingEdge);
 
@@ -0,0 +1,90 @@
+//! GPIO interrupts handled on both cores
+//!
+//! The interrupt of GPIO0 (the boot button) is delivered to core 0, the one of
+//! GPIO4 to core 1. The handler prints the pin and the core it runs on.
+//!
+//! Connect a button between GPIO4 and GND to trigger the second interrupt.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use esp32_hal::{
+    clock::ClockControl,
+    get_core,
+    gpio::{Event, Gpio0, Gpio4, Input, PullUp, IO},
+    interrupt,
+    macros::ram,
+    pac::{self, Peripherals},
+    prelude::*,
+    timer::TimerGroup,
+    Cpu,
+    CpuControl,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+static BUTTON0: Mutex<RefCell<Option<Gpio0<Input<PullUp>>>>> = Mutex::new(RefCell::new(None));
+static BUTTON4: Mutex<RefCell<Option<Gpio4<Input<PullUp>>>>> = Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.DPORT.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let mut button0 = io.pins.gpio0.into_pull_up_input();
+    button0.listen_on_core(Event::FallingEdge, Cpu::ProCpu);
+    let mut button4 = io.pins.gpio4.into_pull_up_input();
+    button4.listen_on_core(Event::FallingEdge, Cpu::AppCpu);
+
+    critical_section::with(|cs| {
+        BUTTON0.borrow_ref_mut(cs).replace(button0);
+        BUTTON4.borrow_ref_mut(cs).replace(button4);
+    });
+
+    // the interrupt is enabled on the core calling `interrupt::enable`
+    interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority2).unwrap();
+
+    let mut cpu_control = CpuControl::new(system.cpu_control);
+    let mut cpu1_fnctn = || {
+        interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority2).unwrap();
+        loop {}
+    };
+    let _guard = cpu_control.start_app_core(&mut cpu1_fnctn).unwrap();
+
+    loop {}
+}
+
+#[ram]
+#[interrupt]
+unsafe fn GPIO() {
+    critical_section::with(|cs| {
+        let mut button0 = BUTTON0.borrow_ref_mut(cs);
+        let button0 = button0.as_mut().unwrap();
+        if button0.is_interrupt_set() {
+            println!("GPIO0 interrupt on core {}", get_core() as u8);
+            button0.clear_interrupt();
+        }
+
+        let mut button4 = BUTTON4.borrow_ref_mut(cs);
+        let button4 = button4.as_mut().unwrap();
+        if button4.is_interrupt_set() {
+            println!("GPIO4 interrupt on core {}", get_core() as u8);
+            button4.clear_interrupt();
+        }
+    });
+}
@@ -0,0 +1,69 @@
+//! Level events in the GPIO dispatcher
+//!
+//! The boot button (GPIO0) is registered for its low level with the default
+//! re-arm policy: holding it down calls the handler once, releasing it
+//! re-arms the pin. A second button between GPIO4 and GND is registered with
+//! [dispatch::Rearm::Manual], the main loop re-arms it a second after each
+//! press. Without the masking both handlers would be called in a loop for as
+//! long as a button is held down.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use esp32_hal::{
+    gpio::{
+        dispatch::{self, Rearm},
+        Event,
+    },
+    init,
+    interrupt,
+    pac::{self, Peripherals},
+    prelude::*,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+static BOOT_PRESSES: AtomicU32 = AtomicU32::new(0);
+static MANUAL_PRESSED: AtomicBool = AtomicBool::new(false);
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let mut boot = hal.io.pins.gpio0.into_pull_up_input();
+    let mut manual = hal.io.pins.gpio4.into_pull_up_input();
+
+    dispatch::register_handler(&mut boot, Event::LowLevel, on_boot);
+    dispatch::register_handler_with_rearm(&mut manual, Event::LowLevel, on_manual, Rearm::Manual);
+
+    interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority1).unwrap();
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    loop {
+        if MANUAL_PRESSED.swap(false, Ordering::Relaxed) {
+            println!("GPIO4 pressed, re-arming in a second");
+            delay.delay_ms(1000u32);
+            dispatch::re_arm(&mut manual);
+        }
+    }
+}
+
+fn on_boot() {
+    let presses = BOOT_PRESSES.fetch_add(1, Ordering::Relaxed) + 1;
+    println!("Boot button pressed, {} presses", presses);
+}
+
+fn on_manual() {
+    MANUAL_PRESSED.store(true, Ordering::Relaxed);
+}
+
+#[interrupt]
+fn GPIO() {
+    dispatch::handle_interrupt();
+}
@@ -0,0 +1,74 @@
+//! GPIO NMI
+//!
+//! Latches the CPU cycle count in the non-maskable interrupt when the boot
+//! button is pressed. The main loop keeps all maskable interrupts disabled
+//! for most of the time, which doesn't delay the NMI.
+//!
+//! The NMI handler only communicates through atomics, as it can preempt
+//! critical sections.
+
+#![no_std]
+#![no_main]
+
+use core::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+
+use esp32_hal::{
+    clock::ClockControl,
+    gpio::{self, Event, InterruptTarget, IO},
+    macros::ram,
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Delay,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+static LATCHED: AtomicBool = AtomicBool::new(false);
+static TIMESTAMP: AtomicU32 = AtomicU32::new(0);
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.DPORT.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let mut button = io.pins.gpio0.into_pull_down_input();
+    button.listen_with_target(Event::FallingEdge, InterruptTarget::Nmi, false);
+
+    gpio::enable_nmi(latch_timestamp);
+
+    let mut delay = Delay::new(&clocks);
+
+    loop {
+        // Maskable interrupts are disabled here, the NMI still fires
+        critical_section::with(|_| delay.delay_ms(1000u32));
+
+        if LATCHED.load(Ordering::Relaxed) {
+            LATCHED.store(false, Ordering::Relaxed);
+            println!(
+                "Button pressed at cycle {}",
+                TIMESTAMP.load(Ordering::Relaxed)
+            );
+        }
+    }
+}
+
+#[ram]
+unsafe fn latch_timestamp() {
+    // Only a plain store, read-modify-write atomics are not safe to use here
+    // on chips emulating them
+    TIMESTAMP.store(xtensa_lx::timer::get_cycle_count(), Ordering::Relaxed);
+    LATCHED.store(true, Ordering::Relaxed);
+}
@@ -1,19 +1,14 @@
 //! This shows how to write text to serial0.
 //! You can see the output with `espflash` if you provide the `--monitor` option
+//!
+//! The CPU runs at its maximum speed, configured by `init!`.
 
 #![no_std]
 #![no_main]
 
 use core::fmt::Write;
 
-use esp32_hal::{
-    clock::ClockControl,
-    pac::Peripherals,
-    prelude::*,
-    timer::TimerGroup,
-    Rtc,
-    Serial,
-};
+use esp32_hal::{clock::CpuClock, init, pac::Peripherals, prelude::*, Serial};
 use esp_backtrace as _;
 use nb::block;
 use xtensa_lx_rt::entry;
@@ -21,18 +16,16 @@ use xtensa_lx_rt::entry;
 #[entry]
 fn main() -> ! {
     let peripherals = Peripherals::take().unwrap();
-    let system = peripherals.DPORT.split();
-    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
 
-    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
-    let mut timer0 = timer_group0.timer0;
-    let mut wdt = timer_group0.wdt;
-    let mut serial0 = Serial::new(peripherals.UART0);
-    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    // The watchdogs are disabled by the default configuration
+    let hal = init!(
+        peripherals,
+        init::Config::default().cpu_clock(CpuClock::Clock240MHz)
+    );
 
-    // Disable MWDT and RWDT (Watchdog) flash boot protection
-    wdt.disable();
-    rtc.rwdt.disable();
+    // UART0 was not used by `init!` and is still available
+    let mut serial0 = Serial::new(peripherals.UART0);
+    let mut timer0 = hal.timer_group0.timer0;
 
     timer0.start(1u64.secs());
 
@@ -0,0 +1,100 @@
+//! Counts the pulses of an LEDC output with the PCNT on the same pad
+//!
+//! The LEDC outputs 1 kHz on GPIO4. The pad is also connected to the input
+//! of PCNT unit 0 with `connect_input_to_peripheral_keeping_function`, which
+//! leaves the LEDC output in place. No wiring is needed, every 100 ms about
+//! 100 rising edges are counted.
+//!
+//! There is no PCNT driver yet, the unit is set up with its registers.
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{
+    gpio::{InputPin, InputSignal},
+    init,
+    ledc::{
+        channel::{self, ChannelIFace},
+        timer::{self, TimerIFace},
+        HighSpeed,
+        LEDC,
+    },
+    pac::{self, Peripherals},
+    prelude::*,
+    system::Peripheral,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+const PCNT_U0_CONF0: usize = 0x00;
+const PCNT_U0_CNT: usize = 0x60;
+const PCNT_CTRL: usize = 0xb0;
+
+/// Channel 0 increments on rising edges and ignores its control input
+const CH0_POS_MODE_INCREMENT: u32 = 1 << 18;
+const CNT_RST_U0: u32 = 1 << 0;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let ledc = LEDC::new(
+        peripherals.LEDC,
+        &hal.clocks,
+        &mut hal.peripheral_clock_control,
+    );
+    let mut hstimer0 = ledc.get_timer::<HighSpeed>(timer::Number::Timer0);
+    hstimer0
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty10Bit,
+            clock_source: timer::HSClockSource::APBClk,
+            frequency: 1u32.kHz(),
+        })
+        .unwrap();
+
+    let mut channel0 = ledc.get_channel(
+        channel::Number::Channel0,
+        hal.io.pins.gpio4.into_push_pull_output(),
+    );
+    channel0
+        .configure(channel::config::Config {
+            timer: &hstimer0,
+            duty_pct: 50,
+        })
+        .unwrap();
+
+    // after `configure`, which sets the pad up as an output
+    channel0
+        .output_pin()
+        .connect_input_to_peripheral_keeping_function(InputSignal::PCNT_SIG_CH0_0);
+
+    hal.peripheral_clock_control.enable(Peripheral::Pcnt);
+    write_pcnt(PCNT_U0_CONF0, CH0_POS_MODE_INCREMENT);
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    loop {
+        write_pcnt(PCNT_CTRL, read_pcnt(PCNT_CTRL) | CNT_RST_U0);
+        write_pcnt(PCNT_CTRL, read_pcnt(PCNT_CTRL) & !CNT_RST_U0);
+        delay.delay_ms(100u32);
+
+        let count = read_pcnt(PCNT_U0_CNT) & 0xffff;
+        let verdict = if (99..=101).contains(&count) {
+            "PASS"
+        } else {
+            "FAIL"
+        };
+        println!("{} rising edges in 100 ms: {}", count, verdict);
+    }
+}
+
+fn read_pcnt(offset: usize) -> u32 {
+    unsafe { ((pac::PCNT::PTR as usize + offset) as *const u32).read_volatile() }
+}
+
+fn write_pcnt(offset: usize, value: u32) {
+    unsafe { ((pac::PCNT::PTR as usize + offset) as *mut u32).write_volatile(value) }
+}
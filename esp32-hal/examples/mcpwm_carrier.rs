@@ -0,0 +1,106 @@
+//! Drives a half bridge through gate drive transformers with MCPWM0
+//!
+//! Operator0 outputs a 5 kHz PWM with 50% duty on GPIO4 and its complement
+//! on GPIO5, with 500 ns of dead time between them. Both outputs are chopped
+//! by a 1 MHz carrier with a duty of 4/8, the first pulse of every PWM pulse
+//! is a full carrier period wide.
+//!
+//! The carrier frequency is an eighth of the peripheral clock divided by
+//! 1 to 16. From the default 160 MHz 1 MHz would need a divider of 20, so
+//! the peripheral clock is set to 40 MHz (divider 5). The timer then counts
+//! 8000 cycles per PWM period without a prescaler.
+//!
+//! Pulling GPIO13 low is a fault: both outputs are forced low right away,
+//! independent of the carrier, until the fault is gone and the trip was
+//! cleared. Watch GPIO4, GPIO5 and GPIO13 with a scope.
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{
+    init,
+    mcpwm::{
+        operator::{CarrierConfig, DeadTimeConfig, PwmPinConfig, TripMode, UpdateAction},
+        timer::PwmWorkingMode,
+        PeripheralClockConfig,
+        MCPWM,
+    },
+    pac::Peripherals,
+    prelude::*,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+    let pins = hal.io.pins;
+
+    let clock_cfg = PeripheralClockConfig::with_frequency(&hal.clocks, 160u32.MHz()).unwrap();
+    if CarrierConfig::with_frequency(&clock_cfg, 1u32.MHz()).is_err() {
+        println!("No 1 MHz carrier from a 160 MHz peripheral clock");
+    }
+
+    let clock_cfg = PeripheralClockConfig::with_frequency(&hal.clocks, 40u32.MHz()).unwrap();
+    let carrier = CarrierConfig::with_frequency(&clock_cfg, 1u32.MHz())
+        .unwrap()
+        .with_duty(4)
+        .with_first_pulse_width(8);
+    println!("Carrier at {}", carrier.frequency());
+
+    let mut mcpwm = MCPWM::new(
+        peripherals.PWM0,
+        clock_cfg,
+        &mut hal.peripheral_clock_control,
+    );
+
+    let fault = mcpwm
+        .fault_detector0
+        .with_pin(pins.gpio13.into_pull_up_input(), false);
+
+    mcpwm.operator0.set_timer(&mcpwm.timer0);
+    // 20 cycles of the 40 MHz peripheral clock
+    mcpwm
+        .operator0
+        .set_deadtime(DeadTimeConfig::active_high_complementary(20, 20));
+    mcpwm.operator0.set_carrier(Some(carrier));
+    mcpwm.operator0.set_trip(&fault, TripMode::OneShot);
+    mcpwm
+        .operator0
+        .set_trip_actions(Some(UpdateAction::SetLow), Some(UpdateAction::SetLow));
+
+    // the B output is the complement of the A generator
+    let (mut high_side, _low_side) = mcpwm.operator0.with_pins(
+        pins.gpio4,
+        PwmPinConfig::UP_ACTIVE_HIGH,
+        pins.gpio5,
+        PwmPinConfig::UP_ACTIVE_HIGH,
+    );
+
+    let timer_clock_cfg = clock_cfg
+        .timer_clock_with_frequency(7999, PwmWorkingMode::Increase, 5u32.kHz())
+        .unwrap();
+    println!("PWM at {}", timer_clock_cfg.frequency());
+    mcpwm.timer0.start(timer_clock_cfg);
+
+    high_side.set_timestamp(4000);
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    loop {
+        let status = high_side.trip_status();
+        if status.one_shot {
+            if fault.is_active() {
+                println!("Tripped, fault active");
+            } else {
+                println!("Fault gone, clearing the trip");
+                high_side.clear_one_shot_trip();
+            }
+        }
+
+        delay.delay_ms(500u32);
+    }
+}
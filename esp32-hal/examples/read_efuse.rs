@@ -5,6 +5,7 @@
 #![no_main]
 
 use esp32_hal::{
+    chip,
     clock::ClockControl,
     efuse::Efuse,
     pac::Peripherals,
@@ -30,6 +31,8 @@ fn main() -> ! {
     wdt.disable();
     rtc.rwdt.disable();
 
+    println!("Chip {}", chip::info());
+    println!("Features {:?}", chip::features());
     println!("MAC address {:02x?}", Efuse::get_mac_address());
     println!("Core Count {}", Efuse::get_core_count());
     println!("Bluetooth enabled {}", Efuse::is_bluetooth_enabled());
@@ -0,0 +1,107 @@
+//! Echoes the packets an SDIO host writes and prints the throughput
+//!
+//! The SDIO pads are fixed, connect them to the host and pull all of them
+//! up with 10 kOhm:
+//! - CLK   => GPIO14
+//! - CMD   => GPIO15
+//! - DATA0 => GPIO2
+//! - DATA1 => GPIO4
+//! - DATA2 => GPIO12 (burn the `XPD_SDIO` eFuses to keep the flash at 3.3 V)
+//! - DATA3 => GPIO13
+//!
+//! The host, e.g. the ESP-IDF `esp_serial_slave_link` component, writes
+//! packets of 4092 bytes to function 1 and reads each one back after the
+//! slave signalled it with the new packet interrupt. Shared register 0
+//! counts the echoed packets, host interrupt 0 resets the statistics.
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{
+    init,
+    pac::Peripherals,
+    prelude::*,
+    sdio_slave::{SdioPins, SdioSlave, Timing},
+    time,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+const PACKET_SIZE: usize = 4092;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let pins = SdioPins {
+        clk: hal.io.pins.gpio14.into_alternate_4(),
+        cmd: hal.io.pins.gpio15.into_alternate_4(),
+        data0: hal.io.pins.gpio2.into_alternate_4(),
+        data1: hal.io.pins.gpio4.into_alternate_4(),
+        data2: hal.io.pins.gpio12.into_alternate_4(),
+        data3: hal.io.pins.gpio13.into_alternate_4(),
+    };
+
+    let mut tx_descriptors = [0u32; 3];
+    let mut rx_descriptors = [0u32; 3];
+    let mut slave = SdioSlave::new(
+        pins,
+        Timing::default(),
+        &mut tx_descriptors,
+        &mut rx_descriptors,
+        &mut hal.peripheral_clock_control,
+    );
+
+    // DMA buffer require a static life-time
+    let mut buffer = buffer();
+    let mut packets = 0u32;
+    let mut bytes = 0;
+    let mut short_packets = 0;
+    let mut start = time::now();
+
+    println!("Waiting for the host");
+
+    loop {
+        (buffer, slave) = slave.receive(buffer).unwrap().wait();
+        let len = slave.received_len();
+        if len != PACKET_SIZE {
+            short_packets += 1;
+        }
+
+        // the whole buffer goes back, the host compares the first `len` bytes
+        (buffer, slave) = slave.send(buffer).unwrap().wait();
+
+        packets = packets.wrapping_add(1);
+        bytes += len as u64;
+        slave.set_shared_register(0, packets as u8);
+
+        if slave.take_host_interrupts() & 1 != 0 {
+            bytes = 0;
+            short_packets = 0;
+            start = time::now();
+        }
+
+        let elapsed = time::now() - start;
+        if elapsed >= time::TICKS_PER_SECOND {
+            println!(
+                "{} kB/s echoed, {} short packets",
+                bytes * time::TICKS_PER_SECOND / elapsed / 1000,
+                short_packets
+            );
+            bytes = 0;
+            short_packets = 0;
+            start = time::now();
+        }
+    }
+}
+
+fn buffer() -> &'static mut [u8; PACKET_SIZE] {
+    // the DMA writes whole words
+    #[repr(align(4))]
+    struct Aligned([u8; PACKET_SIZE]);
+
+    static mut BUFFER: Aligned = Aligned([0u8; PACKET_SIZE]);
+    unsafe { &mut BUFFER.0 }
+}
@@ -0,0 +1,94 @@
+//! Self-test of UART and SPI without external wiring
+//!
+//! UART1 uses its internal loopback, SPI2 loops MOSI back to MISO through the
+//! GPIO matrix. Nothing must be connected to the pins used below.
+//!
+//! Following pins are used:
+//! UART1 TX GPIO16
+//! UART1 RX GPIO17
+//! SCLK     GPIO19
+//! MISO     GPIO25
+//! MOSI     GPIO23
+//! CS       GPIO22
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{
+    clock::ClockControl,
+    pac::Peripherals,
+    prelude::*,
+    serial::{config::Config, TxRxPins},
+    spi::{Spi, SpiMode},
+    timer::TimerGroup,
+    Rtc,
+    Serial,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use nb::block;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.DPORT.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    // UART: the transmitter is connected to the receiver inside the peripheral
+    let pins = TxRxPins::new_tx_rx(
+        io.pins.gpio16.into_push_pull_output(),
+        io.pins.gpio17.into_floating_input(),
+    );
+    let mut serial1 = Serial::new_with_config(
+        peripherals.UART1,
+        Some(Config::default()),
+        Some(pins),
+        &clocks,
+    );
+    serial1.enable_loopback(true);
+
+    let uart_ok = b"UART".iter().all(|&byte| {
+        block!(serial1.write(byte)).unwrap();
+        matches!(block!(serial1.read()), Ok(read) if read == byte)
+    });
+    serial1.enable_loopback(false);
+
+    // SPI: MISO is taken from the MOSI pad through the GPIO matrix
+    let mut spi = Spi::new(
+        peripherals.SPI2,
+        io.pins.gpio19,
+        io.pins.gpio23,
+        io.pins.gpio25,
+        io.pins.gpio22,
+        100u32.kHz(),
+        SpiMode::Mode0,
+        &mut system.peripheral_clock_control,
+        &clocks,
+    );
+    assert!(spi.enable_loopback(true));
+
+    let expected = [0xde, 0xca, 0xfb, 0xad];
+    let mut data = expected;
+    spi.transfer(&mut data).unwrap();
+    let spi_ok = data == expected;
+    spi.enable_loopback(false);
+
+    println!("UART: {}", if uart_ok { "ok" } else { "FAILED" });
+    println!("SPI:  {}", if spi_ok { "ok" } else { "FAILED" });
+
+    assert!(uart_ok && spi_ok);
+
+    loop {}
+}
@@ -0,0 +1,98 @@
+//! Runs all three UARTs at the same time
+//!
+//! UART1 and UART2 talk to each other, UART0 reports what they received.
+//! Connect GPIO25 (UART1 TX) to GPIO33 (UART2 RX) and GPIO32 (UART2 TX) to
+//! GPIO26 (UART1 RX).
+//!
+//! After ten rounds both are released and set up again with a different baud
+//! rate on the same pins.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32_hal::{
+    init,
+    pac::Peripherals,
+    prelude::*,
+    serial::{config::Config, TxRxPins},
+    Delay,
+    Serial,
+};
+use esp_backtrace as _;
+use nb::block;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let mut serial0 = Serial::new_console(peripherals.UART0, &hal.clocks);
+
+    let pins1 = TxRxPins::new_tx_rx(
+        hal.io.pins.gpio25.into_push_pull_output(),
+        hal.io.pins.gpio26.into_floating_input(),
+    );
+    let mut serial1 = Serial::new_with_config(
+        peripherals.UART1,
+        Some(Config::default().baudrate(115_200)),
+        Some(pins1),
+        &hal.clocks,
+    );
+
+    let pins2 = TxRxPins::new_tx_rx(
+        hal.io.pins.gpio32.into_push_pull_output(),
+        hal.io.pins.gpio33.into_floating_input(),
+    );
+    let mut serial2 = Serial::new_with_config(
+        peripherals.UART2,
+        Some(Config::default().baudrate(115_200)),
+        Some(pins2),
+        &hal.clocks,
+    );
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    for round in 0..=255u8 {
+        if round == 10 {
+            let (uart1, pins1) = serial1.release();
+            let (uart2, pins2) = serial2.release();
+            writeln!(
+                serial0,
+                "Switching to 9600 baud ({:?}, {:?})",
+                uart1.interrupt(),
+                uart2.interrupt()
+            )
+            .unwrap();
+
+            let config = Config::default().baudrate(9600);
+            serial1 = Serial::new_with_config(uart1, Some(config), pins1, &hal.clocks);
+            serial2 = Serial::new_with_config(uart2, Some(config), pins2, &hal.clocks);
+        }
+
+        serial1.write_bytes(&[b'1', round]).unwrap();
+        let received2 = [
+            block!(serial2.read()).unwrap(),
+            block!(serial2.read()).unwrap(),
+        ];
+
+        serial2.write_bytes(&[b'2', round]).unwrap();
+        let received1 = [
+            block!(serial1.read()).unwrap(),
+            block!(serial1.read()).unwrap(),
+        ];
+
+        writeln!(
+            serial0,
+            "UART1 got {:?}, UART2 got {:?}",
+            received1, received2
+        )
+        .unwrap();
+
+        delay.delay_ms(500u32);
+    }
+
+    loop {}
+}
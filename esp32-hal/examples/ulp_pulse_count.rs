@@ -0,0 +1,125 @@
+//! Counts pulses on GPIO4 with the ULP while the main CPU is in deep sleep.
+//!
+//! The ULP samples GPIO4 (RTC_GPIO10) every 10 ms and counts rising edges.
+//! Once 10 pulses have been counted it wakes up the main CPU, which prints the
+//! count, restarts the ULP and goes back to sleep.
+//!
+//! Connect a push button between GPIO4 and GND, the internal pull-up is
+//! enabled.
+//!
+//! The chip sleeps with `SleepConfig::ulp_monitoring()`: only the RTC
+//! peripherals, the RTC slow memory and the 8M oscillator stay on. The
+//! default configuration additionally keeps the RTC fast memory powered.
+
+#![no_std]
+#![no_main]
+
+use esp32_hal::{
+    clock::ClockControl,
+    pac::{self, Peripherals},
+    prelude::*,
+    rtc_cntl::SleepConfig,
+    timer::TimerGroup,
+    ulp::UlpSharedMemory,
+    Rtc,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+// Variables of the ULP program, in bytes from the start of RTC slow memory
+const COUNT: usize = 68;
+const THRESHOLD: usize = 72;
+
+// Prebuilt ULP-FSM binary (ESP-IDF format), assembled from:
+//
+//         .bss
+//     prev:      .long 0
+//     count:     .long 0
+//     threshold: .long 0
+//
+//         .text
+//     entry:
+//         move r3, prev
+//         reg_rd RTC_GPIO_IN_REG, 24, 24    // level of RTC_GPIO10
+//         ld r1, r3, 0                      // previous level
+//         st r0, r3, 0
+//         move r2, r0
+//         add r1, r1, 1                     // r1 = !previous
+//         and r1, r1, 1
+//         and r2, r2, r1                    // r2 = rising edge
+//         ld r1, r3, 4
+//         add r1, r1, r2
+//         st r1, r3, 4                      // count += rising edge
+//         ld r2, r3, 8
+//         sub r0, r1, r2                    // overflows if count < threshold
+//         jump done, ov
+//         wake
+//     done:
+//         halt
+#[rustfmt::skip]
+static ULP_PROGRAM: [u8; 76] = [
+    // magic, text offset, text size, data size, bss size
+    0x75, 0x6c, 0x70, 0x00, 0x0c, 0x00, 0x40, 0x00, 0x00, 0x00, 0x0c, 0x00,
+    0x03, 0x01, 0x80, 0x72, // move r3, prev
+    0x09, 0x01, 0x60, 0x2c, // reg_rd RTC_GPIO_IN_REG, 24, 24
+    0x0d, 0x00, 0x00, 0xd0, // ld r1, r3, 0
+    0x0c, 0x00, 0x00, 0x68, // st r0, r3, 0
+    0x02, 0x00, 0x80, 0x70, // move r2, r0
+    0x15, 0x00, 0x00, 0x72, // add r1, r1, 1
+    0x15, 0x00, 0x40, 0x72, // and r1, r1, 1
+    0x1a, 0x00, 0x40, 0x70, // and r2, r2, r1
+    0x0d, 0x04, 0x00, 0xd0, // ld r1, r3, 4
+    0x25, 0x00, 0x00, 0x70, // add r1, r1, r2
+    0x0d, 0x04, 0x00, 0x68, // st r1, r3, 4
+    0x0e, 0x08, 0x00, 0xd0, // ld r2, r3, 8
+    0x24, 0x00, 0x20, 0x70, // sub r0, r1, r2
+    0x3c, 0x00, 0x80, 0x80, // jump done, ov
+    0x01, 0x00, 0x00, 0x90, // wake
+    0x00, 0x00, 0x00, 0xb0, // halt
+];
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.DPORT.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let _pulse_input = io.pins.gpio4.into_analog();
+
+    // Route the pad to the RTC domain as an input with pull-up, so the ULP can
+    // read it while the digital domain is powered down
+    let rtcio = unsafe { &*pac::RTCIO::ptr() };
+    rtcio
+        .touch_pad0
+        .modify(|_, w| w.fun_ie().set_bit().rue().set_bit());
+
+    // The ULP only writes the lower 16 bits. After power-on the RTC memory
+    // contents are random.
+    let count = UlpSharedMemory::<u32>::at(COUNT).unwrap();
+    println!("Pulses counted: {}", count.read() & 0xffff);
+
+    rtc.ulp.stop();
+    rtc.ulp.load_binary(0, &ULP_PROGRAM).unwrap();
+    UlpSharedMemory::<u32>::at(THRESHOLD).unwrap().write(10);
+
+    rtc.ulp.set_wakeup_period(0, 10_000u64.micros()).unwrap();
+    rtc.ulp.start_fsm(0).unwrap();
+
+    println!("Going to deep sleep");
+    let error = rtc
+        .ulp
+        .sleep_until_wakeup_with_config(&SleepConfig::ulp_monitoring())
+        .unwrap_err();
+    panic!("Invalid sleep configuration: {:?}", error);
+}
@@ -5,7 +5,14 @@
 #![no_std]
 #![no_main]
 
-use esp32_hal::{clock::ClockControl, pac::Peripherals, prelude::*, timer::TimerGroup, Rtc};
+use esp32_hal::{
+    clock::ClockControl,
+    pac::Peripherals,
+    prelude::*,
+    rtc_cntl::watchdogs_status,
+    timer::TimerGroup,
+    Rtc,
+};
 use esp_backtrace as _;
 use esp_println::println;
 use nb::block;
@@ -27,6 +34,9 @@ fn main() -> ! {
     wdt.start(2u64.secs());
     timer0.start(1u64.secs());
 
+    // Show which watchdogs are armed and how long until they bite
+    println!("{:?}", watchdogs_status());
+
     loop {
         wdt.feed();
         println!("Hello world!");
@@ -5,25 +5,38 @@ pub use embedded_hal as ehal;
 pub use esp_hal_common::{
     analog::adc::implementation as adc,
     analog::dac::implementation as dac,
+    board_pins,
+    chip,
     clock,
+    coex,
     cpu_control::CpuControl,
     dma,
     dma::pdma,
     efuse,
+    emac,
+    get_core,
     gpio,
     i2c,
     i2s,
+    init,
     interrupt,
     ledc,
     macros,
     mcpwm,
+    mode,
+    one_wire,
     pac,
     prelude,
+    profiling,
     pulse_control,
+    rtc_cntl,
+    sdio_slave,
     serial,
     spi,
     system,
+    time,
     timer,
+    ulp,
     utils,
     Cpu,
     Delay,
@@ -37,12 +50,22 @@ pub use esp_hal_common::{
 
 pub use self::gpio::IO;
 
+#[cfg(feature = "stack-guard")]
+pub use esp_hal_common::debug;
 #[cfg(feature = "embassy")]
 pub use esp_hal_common::embassy;
+#[cfg(feature = "logger")]
+pub use esp_hal_common::logger;
+#[cfg(feature = "sdmmc")]
+pub use esp_hal_common::sd_spi;
 
 /// Common module for analog functions
 pub mod analog {
-    pub use esp_hal_common::analog::{AvailableAnalog, SensExt};
+    pub use esp_hal_common::analog::{
+        calibration::{self_calibrate, CalibrationCurve, CalibrationError},
+        AvailableAnalog,
+        SensExt,
+    };
 }
 
 #[no_mangle]
@@ -1,47 +1,31 @@
 //! Blinks an LED
 //!
 //! This assumes that a LED is connected to the pin assigned to `led`. (GPIO5)
+//!
+//! The clocks, the watchdogs and `IO` are set up by `init!`.
 
 #![no_std]
 #![no_main]
 
-use esp32c2_hal::{
-    clock::ClockControl,
-    gpio::IO,
-    pac::Peripherals,
-    prelude::*,
-    timer::TimerGroup,
-    Delay,
-    Rtc,
-};
+use esp32c2_hal::{init, pac::Peripherals, prelude::*, Delay};
 use esp_backtrace as _;
 use riscv_rt::entry;
 
 #[entry]
 fn main() -> ! {
     let peripherals = Peripherals::take().unwrap();
-    let system = peripherals.SYSTEM.split();
-    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
-
-    // Disable the watchdog timers. For the ESP32-C2, this includes the Super WDT,
-    // the RTC WDT, and the TIMG WDTs.
-    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
-    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
-    let mut wdt0 = timer_group0.wdt;
 
-    rtc.swd.disable();
-    rtc.rwdt.disable();
-    wdt0.disable();
+    // Keep the boot default clocks and disable all watchdogs
+    let hal = init!(peripherals, init::Config::default());
 
     // Set GPIO5 as an output, and set its state high initially.
-    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
-    let mut led = io.pins.gpio5.into_push_pull_output();
+    let mut led = hal.io.pins.gpio5.into_push_pull_output();
 
     led.set_high().unwrap();
 
     // Initialize the Delay peripheral, and use it to toggle the LED state in a
     // loop.
-    let mut delay = Delay::new(&clocks);
+    let mut delay = Delay::new(&hal.clocks);
 
     loop {
         led.toggle().unwrap();
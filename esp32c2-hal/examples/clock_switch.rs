@@ -0,0 +1,65 @@
+//! Switches the CPU clock at runtime while keeping UART1 at 115200 baud.
+//!
+//! Connect a USB-to-serial adapter to GPIO1 (TX) to see the output
+//! of UART1, which must not change across the frequency switches.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32c2_hal::{
+    clock::{ClockControl, CpuClock},
+    pac::Peripherals,
+    prelude::*,
+    serial::{config::Config, TxRxPins},
+    timer::TimerGroup,
+    Delay,
+    Rtc,
+    Serial,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let mut clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let pins = TxRxPins::new_tx_rx(
+        io.pins.gpio1.into_push_pull_output(),
+        io.pins.gpio2.into_floating_input(),
+    );
+
+    let config = Config::default().baudrate(115_200);
+    let mut serial1 = Serial::new_with_config(peripherals.UART1, Some(config), Some(pins), &clocks);
+    let mut delay = Delay::new(&clocks);
+
+    let mut cpu_clock = CpuClock::Clock80MHz;
+    loop {
+        writeln!(serial1, "CPU clock {} MHz", clocks.cpu_clock.to_MHz()).unwrap();
+        println!("CPU clock {} MHz", clocks.cpu_clock.to_MHz());
+        delay.delay_ms(1000u32);
+
+        cpu_clock = match cpu_clock {
+            CpuClock::Clock80MHz => CpuClock::Clock120MHz,
+            _ => CpuClock::Clock80MHz,
+        };
+
+        // Both drivers derived dividers from the clocks, let them re-derive them
+        clocks.set_cpu_clock(cpu_clock, &mut [&mut serial1, &mut delay]);
+    }
+}
@@ -1,19 +1,14 @@
 //! This shows how to write text to serial0.
 //! You can see the output with `espflash` if you provide the `--monitor` option
+//!
+//! The CPU runs at its maximum speed, configured by `init!`.
 
 #![no_std]
 #![no_main]
 
 use core::fmt::Write;
 
-use esp32c2_hal::{
-    clock::ClockControl,
-    pac::Peripherals,
-    prelude::*,
-    timer::TimerGroup,
-    Rtc,
-    Serial,
-};
+use esp32c2_hal::{clock::CpuClock, init, pac::Peripherals, prelude::*, Serial};
 use esp_backtrace as _;
 use nb::block;
 use riscv_rt::entry;
@@ -21,19 +16,16 @@ use riscv_rt::entry;
 #[entry]
 fn main() -> ! {
     let peripherals = Peripherals::take().unwrap();
-    let system = peripherals.SYSTEM.split();
-    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
 
-    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    // The watchdogs are disabled by the default configuration
+    let hal = init!(
+        peripherals,
+        init::Config::default().cpu_clock(CpuClock::Clock120MHz)
+    );
+
+    // UART0 was not used by `init!` and is still available
     let mut serial0 = Serial::new(peripherals.UART0);
-    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
-    let mut timer0 = timer_group0.timer0;
-    let mut wdt0 = timer_group0.wdt;
-
-    // Disable watchdog timers
-    rtc.swd.disable();
-    rtc.rwdt.disable();
-    wdt0.disable();
+    let mut timer0 = hal.timer_group0.timer0;
 
     timer0.start(1u64.secs());
 
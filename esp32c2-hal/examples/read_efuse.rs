@@ -5,6 +5,7 @@
 #![no_main]
 
 use esp32c2_hal::{
+    chip,
     clock::ClockControl,
     efuse::Efuse,
     pac::Peripherals,
@@ -31,6 +32,8 @@ fn main() -> ! {
     rtc.rwdt.disable();
     wdt0.disable();
 
+    println!("Chip {}", chip::info());
+    println!("Features {:?}", chip::features());
     println!("MAC address {:02x?}", Efuse::get_mac_address());
     println!("Flash Encryption {:?}", Efuse::get_flash_encryption());
 
@@ -0,0 +1,101 @@
+//! Drives eight software PWM outputs next to the application
+//!
+//! GPIO0 to GPIO7 run a 100 Hz PWM with a tick of 100 µs, i.e. a duty
+//! resolution of 1%. The duties start at 10% to 80% and are changed every
+//! second, watch the pins with a logic analyzer or connect LEDs.
+//!
+//! The main loop counts how often it gets through a busy loop in one second,
+//! once before the PWM is started and then with the PWM running. The
+//! difference is the CPU time taken by the timer interrupt.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use esp32c2_hal::{
+    gpio::soft_pwm::SoftPwm,
+    init,
+    interrupt,
+    pac::{self, Peripherals, TIMG0},
+    prelude::*,
+    systimer::SystemTimer,
+    timer::Timer0,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const CHANNELS: usize = 8;
+
+static SOFT_PWM: Mutex<RefCell<Option<SoftPwm<Timer0<TIMG0>, CHANNELS>>>> =
+    Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let idle = busy_loops();
+    println!("{} loops per second without PWM", idle);
+
+    let mut pwm = SoftPwm::new(hal.timer_group0.timer0, 100u64.micros());
+    let pins = hal.io.pins;
+    pwm.add(pins.gpio0, 10u64.millis());
+    pwm.add(pins.gpio1, 10u64.millis());
+    pwm.add(pins.gpio2, 10u64.millis());
+    pwm.add(pins.gpio3, 10u64.millis());
+    pwm.add(pins.gpio4, 10u64.millis());
+    pwm.add(pins.gpio5, 10u64.millis());
+    pwm.add(pins.gpio6, 10u64.millis());
+    pwm.add(pins.gpio7, 10u64.millis());
+    for channel in 0..CHANNELS {
+        pwm.set_duty_pct(channel, 10 * (channel as u8 + 1));
+    }
+
+    critical_section::with(|cs| SOFT_PWM.borrow_ref_mut(cs).replace(pwm));
+
+    interrupt::enable(pac::Interrupt::TG0_T0_LEVEL, interrupt::Priority::Priority1).unwrap();
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    let mut step = 0u8;
+    loop {
+        let busy = busy_loops();
+        println!(
+            "{} loops per second with PWM, {}% taken by the interrupt",
+            busy,
+            100u64.saturating_sub(busy * 100 / idle)
+        );
+
+        // shift the duties by 10%, applied at the next period of every pin
+        step = (step + 1) % 10;
+        critical_section::with(|cs| {
+            let mut pwm = SOFT_PWM.borrow_ref_mut(cs);
+            let pwm = pwm.as_mut().unwrap();
+            for channel in 0..CHANNELS {
+                pwm.set_duty_pct(channel, (10 * (channel as u8 + 1 + step)) % 100);
+            }
+        });
+    }
+}
+
+/// Iterations of a busy loop in one second
+fn busy_loops() -> u64 {
+    let end = SystemTimer::now() + SystemTimer::TICKS_PER_SECOND;
+    let mut loops = 0;
+    while SystemTimer::now() < end {
+        loops += 1;
+    }
+    loops
+}
+
+#[interrupt]
+fn TG0_T0_LEVEL() {
+    critical_section::with(|cs| {
+        SOFT_PWM.borrow_ref_mut(cs).as_mut().unwrap().on_interrupt();
+    });
+}
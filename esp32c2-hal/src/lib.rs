@@ -3,25 +3,40 @@
 use core::arch::global_asm;
 
 pub use embedded_hal as ehal;
+#[cfg(feature = "stack-guard")]
+pub use esp_hal_common::debug;
 #[cfg(feature = "embassy")]
 pub use esp_hal_common::embassy;
+#[cfg(feature = "logger")]
+pub use esp_hal_common::logger;
+#[cfg(feature = "sdmmc")]
+pub use esp_hal_common::sd_spi;
 #[doc(inline)]
 pub use esp_hal_common::{
     analog::adc::implementation as adc,
+    board_pins,
+    chip,
     clock,
+    coex,
     dma::{self, gdma},
     efuse,
     gpio,
     i2c,
+    init,
     interrupt,
     ledc,
     macros,
+    mode,
+    one_wire,
     pac,
     prelude,
+    profiling,
+    rtc_cntl,
     serial,
     spi,
     system,
     systimer,
+    time,
     timer,
     Cpu,
     Delay,
@@ -50,9 +65,24 @@ extern "C" {
     static mut _sbss: u32;
 }
 
-global_asm!(
+// The trap entry is placed in RAM with the `place-isr-in-ram` feature, so that
+// interrupts are taken while the flash cache is disabled
+#[cfg(not(feature = "place-isr-in-ram"))]
+macro_rules! trap_section {
+    () => {
+        ".section .trap, \"ax\""
+    };
+}
+#[cfg(feature = "place-isr-in-ram")]
+macro_rules! trap_section {
+    () => {
+        ".section .rwtext, \"ax\""
+    };
+}
+
+global_asm!(concat!(
+    trap_section!(),
     r#"
-.section .trap, "ax"
 .balign 0x100
 .global _vector_table_hal
 .type _vector_table_hal, @function
@@ -65,16 +95,16 @@ _vector_table_hal:
     j _start_trap_hal
     .endr
 "#
-);
+));
 
-global_asm!(
+global_asm!(concat!(
+    trap_section!(),
     r#"
     /*
     Trap entry point (_start_trap_hal)
     Saves registers and calls _start_trap_rust_hal,
     restores registers and then returns.
 */
-.section .trap, "ax"
 .global _start_trap_hal
 .option norelax
 .align 6
@@ -169,7 +199,7 @@ _start_trap_hal:
     mret
 
 "#
-);
+));
 
 global_asm!(
     r#"
@@ -0,0 +1,127 @@
+//! Mains zero-cross detection with the ADC threshold monitor
+//!
+//! A mains derived sine wave, scaled down and biased to half the supply
+//! (e.g. with a transformer and a voltage divider), is connected to GPIO2.
+//! The ADC samples it continuously and the threshold monitor raises an
+//! interrupt when it rises above mid scale, re-arming once it fell below a
+//! slightly lower value so noise around the crossing doesn't retrigger.
+//! The handler pulses the triac trigger on GPIO5.
+//!
+//! The latency from the zero-crossing to the trigger pulse can be observed
+//! with an oscilloscope on GPIO2 and GPIO5. The number of crossings per
+//! second is printed.
+
+#![no_std]
+#![no_main]
+
+use core::cell::{Cell, RefCell};
+
+use critical_section::Mutex;
+use esp32c3_hal::{
+    adc::{AdcConfig, AdcContinuous, AdcMonitor, Attenuation, Crossing, ThresholdMode, ADC, ADC1},
+    clock::ClockControl,
+    gpio::{Gpio5, Output, PushPull, IO},
+    interrupt,
+    pac::{self, Peripherals},
+    prelude::*,
+    timer::TimerGroup,
+    Delay,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+// Raw 12-bit readings of the crossing and the re-arm level
+const ZERO: u16 = 2048;
+const REARM: u16 = 1900;
+
+static MONITOR: Mutex<RefCell<Option<AdcMonitor<ADC1>>>> = Mutex::new(RefCell::new(None));
+static TRIAC: Mutex<RefCell<Option<Gpio5<Output<PushPull>>>>> = Mutex::new(RefCell::new(None));
+static CROSSINGS: Mutex<Cell<u32>> = Mutex::new(Cell::new(0));
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    // Disable the watchdog timers. For the ESP32-C3, this includes the Super WDT,
+    // the RTC WDT, and the TIMG WDTs.
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let triac = io.pins.gpio5.into_push_pull_output();
+
+    // Create ADC instances
+    let analog = peripherals.APB_SARADC.split();
+
+    let mut adc1_config = AdcConfig::new();
+    let pin = adc1_config.enable_pin(io.pins.gpio2.into_analog(), Attenuation::Attenuation11dB);
+
+    let adc1 = ADC::<ADC1>::adc(
+        &mut system.peripheral_clock_control,
+        analog.adc1,
+        adc1_config,
+    )
+    .unwrap();
+
+    let adc1 = AdcContinuous::new(adc1, 20u32.kHz(), &clocks);
+    let mut monitor =
+        AdcMonitor::new(adc1, &pin, ThresholdMode::Above(ZERO)).with_hysteresis(REARM);
+    monitor.listen();
+    monitor.start();
+
+    critical_section::with(|cs| {
+        MONITOR.borrow_ref_mut(cs).replace(monitor);
+        TRIAC.borrow_ref_mut(cs).replace(triac);
+    });
+
+    interrupt::enable(pac::Interrupt::APB_ADC, interrupt::Priority::Priority3).unwrap();
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    let mut delay = Delay::new(&clocks);
+
+    loop {
+        delay.delay_ms(1000u32);
+        let crossings = critical_section::with(|cs| CROSSINGS.borrow(cs).replace(0));
+        println!("{} crossings/s", crossings);
+    }
+}
+
+#[interrupt]
+fn APB_ADC() {
+    critical_section::with(|cs| {
+        let crossing = MONITOR
+            .borrow_ref_mut(cs)
+            .as_mut()
+            .unwrap()
+            .clear_interrupt();
+
+        if crossing == Some(Crossing::Triggered) {
+            let mut triac = TRIAC.borrow_ref_mut(cs);
+            let triac = triac.as_mut().unwrap();
+            triac.set_high().unwrap();
+            // a few microseconds are enough to fire the triac
+            for _ in 0..200 {
+                core::hint::spin_loop();
+            }
+            triac.set_low().unwrap();
+
+            let crossings = CROSSINGS.borrow(cs);
+            crossings.set(crossings.get() + 1);
+        }
+    });
+}
@@ -63,7 +63,7 @@ fn RTC_CORE() {
         esp_println::println!(
             "{: <10} XTAL frequency: {} MHz",
             "[Monitor]",
-            rtc.estimate_xtal_frequency()
+            rtc.measure_xtal_frequency().to_MHz()
         );
 
         rtc.rwdt.clear_interrupt();
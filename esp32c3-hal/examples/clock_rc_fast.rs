@@ -0,0 +1,68 @@
+//! Runs the CPU from the internal fast RC oscillator while idle
+//!
+//! The CPU does some work at 160 MHz from the PLL, then waits for five seconds
+//! at 17.5 MHz from the RC oscillator with the PLL powered down, and so on.
+//! UART0 (GPIO21 TX, GPIO20 RX) keeps printing at 9600 baud across the
+//! switches, the RC oscillator is too inaccurate for much higher baud rates.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32c3_hal::{
+    clock::CpuClock,
+    init,
+    pac::Peripherals,
+    prelude::*,
+    serial::{config::Config, TxRxPins},
+    Delay,
+    Serial,
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(
+        peripherals,
+        init::Config::default().cpu_clock(CpuClock::Clock160MHz)
+    );
+    let mut clocks = hal.clocks;
+
+    let pins = TxRxPins::new_tx_rx(
+        hal.io.pins.gpio21.into_push_pull_output(),
+        hal.io.pins.gpio20.into_floating_input(),
+    );
+    let config = Config::default().baudrate(9600);
+    let mut serial0 = Serial::new_with_config(peripherals.UART0, Some(config), Some(pins), &clocks);
+    let mut delay = Delay::new(&clocks);
+
+    loop {
+        // some work which benefits from the fast clock
+        let mut checksum = 0u32;
+        for i in 0..1_000_000u32 {
+            checksum = checksum.rotate_left(5) ^ i;
+        }
+        writeln!(
+            serial0,
+            "CPU clock {} Hz, checksum {:08x}",
+            clocks.cpu_clock.to_Hz(),
+            checksum
+        )
+        .unwrap();
+
+        // both drivers derived dividers from the clocks, let them re-derive them
+        clocks.set_cpu_clock(CpuClock::RcFast, &mut [&mut serial0, &mut delay]);
+        writeln!(
+            serial0,
+            "CPU clock {} Hz (measured RC oscillator)",
+            clocks.cpu_clock.to_Hz()
+        )
+        .unwrap();
+        delay.delay_ms(5000u32);
+
+        clocks.set_cpu_clock(CpuClock::Clock160MHz, &mut [&mut serial0, &mut delay]);
+    }
+}
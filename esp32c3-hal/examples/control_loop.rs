@@ -0,0 +1,87 @@
+//! A 1 kHz control loop on absolute SYSTIMER deadlines
+//!
+//! Every iteration is scheduled one period after the previous deadline, so
+//! the loop doesn't drift with the time the work takes. Every 1000th
+//! iteration the work takes 3.5 ms instead, which misses the next three
+//! deadlines. `schedule_next` skips them and reports how many were missed.
+
+#![no_std]
+#![no_main]
+
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use critical_section::Mutex;
+use esp32c3_hal::{
+    init,
+    interrupt,
+    interrupt::Priority,
+    pac::{self, Peripherals},
+    prelude::*,
+    systimer::{Alarm, SystemTimer, Target},
+    timer::PeriodicDeadline,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const PERIOD: u64 = SystemTimer::TICKS_PER_SECOND / 1000;
+
+static ALARM: Mutex<RefCell<Option<Alarm<Target, 0>>>> = Mutex::new(RefCell::new(None));
+static TICK: AtomicBool = AtomicBool::new(false);
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+    let mut delay = Delay::new(&hal.clocks);
+
+    let syst = SystemTimer::new(peripherals.SYSTIMER);
+    let alarm = syst.alarm0;
+    alarm.clear_interrupt();
+    alarm.interrupt_enable(true);
+
+    let mut deadline = PeriodicDeadline::new(SystemTimer::now(), PERIOD);
+    alarm.schedule_next(&mut deadline);
+
+    critical_section::with(|cs| ALARM.borrow_ref_mut(cs).replace(alarm));
+    interrupt::enable(pac::Interrupt::SYSTIMER_TARGET0, Priority::Priority1).unwrap();
+
+    let mut iterations = 0u32;
+    let mut missed_total = 0u64;
+
+    loop {
+        while !TICK.swap(false, Ordering::Acquire) {}
+
+        iterations += 1;
+        if iterations % 1000 == 0 {
+            // artificial load
+            delay.delay_us(3500u32);
+        }
+
+        let missed = critical_section::with(|cs| {
+            ALARM
+                .borrow_ref_mut(cs)
+                .as_mut()
+                .unwrap()
+                .schedule_next(&mut deadline)
+        });
+
+        if missed != 0 {
+            missed_total += missed;
+            println!(
+                "Iteration {}: missed {} deadlines, {} in total",
+                iterations, missed, missed_total
+            );
+        }
+    }
+}
+
+#[interrupt]
+fn SYSTIMER_TARGET0() {
+    critical_section::with(|cs| ALARM.borrow_ref_mut(cs).as_mut().unwrap().clear_interrupt());
+    TICK.store(true, Ordering::Release);
+}
@@ -0,0 +1,132 @@
+//! Serves a UART echo and an I2C sensor from one polling loop
+//!
+//! No interrupts and no blocking calls are used: every pass of the main loop
+//! advances each task by at most one step with the `nb` APIs of the drivers
+//! and moves on.
+//!
+//! - Echo: bytes received on UART0 are sent back.
+//! - Sensor: once per second the chip id of a BMP180 (0x55) is read and
+//!   reported on UART0.
+//!
+//! The following wiring is assumed:
+//! - SDA => GPIO1
+//! - SCL => GPIO2
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{i2c::I2C, init, pac::Peripherals, prelude::*, systimer::SystemTimer, Serial};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+const BMP180_ADDRESS: u8 = 0x77;
+const BMP180_CHIP_ID: u8 = 0xd0;
+
+/// Bytes waiting to be sent on the UART
+struct TxQueue {
+    buffer: [u8; 64],
+    head: usize,
+    len: usize,
+}
+
+impl TxQueue {
+    fn push(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            if self.len < self.buffer.len() {
+                self.buffer[(self.head + self.len) % self.buffer.len()] = *byte;
+                self.len += 1;
+            }
+        }
+    }
+
+    fn front(&self) -> Option<u8> {
+        (self.len > 0).then(|| self.buffer[self.head])
+    }
+
+    fn pop(&mut self) {
+        self.head = (self.head + 1) % self.buffer.len();
+        self.len -= 1;
+    }
+}
+
+enum SensorState {
+    Waiting { until: u64 },
+    Reading,
+}
+
+fn hex(value: u8) -> [u8; 2] {
+    let digit = |nibble: u8| b"0123456789abcdef"[nibble as usize];
+    [digit(value >> 4), digit(value & 0xf)]
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let mut serial0 = Serial::new(peripherals.UART0);
+    let mut i2c = I2C::new(
+        peripherals.I2C0,
+        hal.io.pins.gpio1,
+        hal.io.pins.gpio2,
+        100u32.kHz(),
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    );
+
+    let mut tx = TxQueue {
+        buffer: [0; 64],
+        head: 0,
+        len: 0,
+    };
+    let mut sensor = SensorState::Waiting { until: 0 };
+
+    loop {
+        // Echo task
+        if let Ok(byte) = serial0.read_nb() {
+            tx.push(&[byte]);
+        }
+
+        // Sensor task
+        match sensor {
+            SensorState::Waiting { until } => {
+                if SystemTimer::now() >= until
+                    && i2c
+                        .start_write_read(BMP180_ADDRESS, &[BMP180_CHIP_ID], 1)
+                        .is_ok()
+                {
+                    sensor = SensorState::Reading;
+                }
+            }
+            SensorState::Reading => {
+                let mut id = [0u8; 1];
+                let done = match i2c.poll_transaction(&mut id) {
+                    Ok(()) => {
+                        tx.push(b"\r\nchip id ");
+                        tx.push(&hex(id[0]));
+                        tx.push(b"\r\n");
+                        true
+                    }
+                    Err(nb::Error::WouldBlock) => false,
+                    Err(nb::Error::Other(_)) => {
+                        tx.push(b"\r\nsensor error\r\n");
+                        true
+                    }
+                };
+
+                if done {
+                    sensor = SensorState::Waiting {
+                        until: SystemTimer::now() + SystemTimer::TICKS_PER_SECOND,
+                    };
+                }
+            }
+        }
+
+        // Output task
+        if let Some(byte) = tx.front() {
+            if serial0.write_nb(byte).is_ok() {
+                tx.pop();
+            }
+        }
+    }
+}
@@ -0,0 +1,103 @@
+//! Copy a buffer with a GDMA channel and with the CPU
+//!
+//! Every second the example copies 32 kB with the DMA and with
+//! `copy_from_slice`, checks the copies and prints the rates of both. While
+//! the DMA copies, the CPU counts how often it could poll the transfer, a
+//! measure of the time it has for other work.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    dma::{mem2mem::Mem2Mem, DmaPriority, DmaTransferRxTx},
+    gdma::Gdma,
+    init,
+    pac::Peripherals,
+    prelude::*,
+    profiling::{cycles_to_nanos, CycleCounter},
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const LEN: usize = 32 * 1024;
+/// 4092 bytes per descriptor
+const DESCRIPTORS: usize = LEN / 4092 + 1;
+
+/// DMA buffers in internal RAM have to start at a 4 byte boundary
+#[repr(C, align(4))]
+struct Aligned([u8; LEN]);
+
+static mut SRC: Aligned = Aligned([0; LEN]);
+static mut DST: Aligned = Aligned([0; LEN]);
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+    let mut delay = Delay::new(&hal.clocks);
+
+    let mut tx_descriptors = [0u32; DESCRIPTORS * 3];
+    let mut rx_descriptors = [0u32; DESCRIPTORS * 3];
+
+    let dma = Gdma::new(peripherals.DMA, &mut hal.peripheral_clock_control);
+    let channel = dma.channel0.configure(
+        false,
+        &mut tx_descriptors,
+        &mut rx_descriptors,
+        DmaPriority::Priority0,
+    );
+    let mut mem2mem = Mem2Mem::new(channel);
+
+    // the transfer owns the buffers, `wait` hands them back
+    let mut src: &'static mut [u8; LEN] = unsafe { &mut SRC.0 };
+    let mut dst: &'static mut [u8; LEN] = unsafe { &mut DST.0 };
+    let mut round = 0u8;
+
+    loop {
+        for (i, byte) in src.iter_mut().enumerate() {
+            *byte = (i as u8).wrapping_add(round);
+        }
+        dst.fill(0);
+
+        let counter = CycleCounter::start();
+        let mut transfer = mem2mem.copy(dst, src).unwrap();
+        let mut polls = 0u32;
+        while !transfer.is_done() {
+            polls += 1;
+        }
+        let dma_cycles = counter.elapsed();
+        (dst, src, mem2mem) = transfer.wait();
+        let dma_ok = dst[..] == src[..];
+
+        dst.fill(0);
+        let counter = CycleCounter::start();
+        dst.copy_from_slice(&src[..]);
+        let cpu_cycles = counter.elapsed();
+        let cpu_ok = dst[..] == src[..];
+
+        println!(
+            "DMA: {} cycles, {} kB/s, {} polls, ok {}",
+            dma_cycles,
+            rate(dma_cycles, &hal.clocks),
+            polls,
+            dma_ok
+        );
+        println!(
+            "CPU: {} cycles, {} kB/s, ok {}",
+            cpu_cycles,
+            rate(cpu_cycles, &hal.clocks),
+            cpu_ok
+        );
+
+        round = round.wrapping_add(1);
+        delay.delay_ms(1000u32);
+    }
+}
+
+/// kB/s for copying [LEN] bytes in `cycles`
+fn rate(cycles: u32, clocks: &esp32c3_hal::clock::Clocks) -> u64 {
+    let nanos = cycles_to_nanos(cycles, clocks).max(1);
+    LEN as u64 * 1_000_000 / nanos
+}
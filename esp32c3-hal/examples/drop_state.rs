@@ -0,0 +1,174 @@
+//! Checks the state drivers leave behind when they are dropped
+//!
+//! SPI2, UART1 and a LEDC channel are set up, used and dropped, twice. After
+//! each round the interrupt enables of SPI2 and UART1, the channel
+//! configuration and the pads are read back: the interrupts have to be
+//! disabled, the channel stopped and the pads floating inputs, and the
+//! second round has to leave exactly the same state as the first one.
+//!
+//! Built with the `gate-clocks-on-drop` feature, the clock of SPI2 has to be
+//! gated as well. The example idles in a loop at the end, measure the current
+//! before it starts and then to see the drivers leave nothing running.
+//!
+//! Nothing must be connected to the pins used below.
+//!
+//! Following pins are used:
+//! SCLK     GPIO6
+//! MISO     GPIO2
+//! MOSI     GPIO7
+//! CS       GPIO10
+//! UART1 TX GPIO1
+//! UART1 RX GPIO3
+//! LEDC     GPIO4
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    clock::Clocks,
+    gpio::pad::PadSnapshot,
+    init,
+    ledc::{
+        channel::{self, ChannelIFace},
+        timer::{self, TimerIFace},
+        LSGlobalClkSource,
+        LowSpeed,
+        LEDC,
+    },
+    pac::{Peripherals, LEDC as LEDC_REGS, SPI2, SYSTEM, UART1},
+    prelude::*,
+    serial::{config::Config, TxRxPins},
+    spi::{Spi, SpiMode},
+    system::PeripheralClockControl,
+    Serial,
+    IO,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use nb::block;
+use riscv_rt::entry;
+
+const PADS: [u8; 7] = [6, 2, 7, 10, 1, 3, 4];
+
+/// What the dropped drivers left behind
+#[derive(Debug, PartialEq, Eq)]
+struct State {
+    spi_int_ena: u32,
+    spi_clock: bool,
+    uart_int_ena: u32,
+    ledc_conf0: u32,
+    pads: [PadSnapshot; 7],
+}
+
+impl State {
+    fn read() -> Self {
+        Self {
+            spi_int_ena: unsafe { &*SPI2::PTR }.dma_int_ena.read().bits(),
+            spi_clock: unsafe { &*SYSTEM::PTR }
+                .perip_clk_en0
+                .read()
+                .spi2_clk_en()
+                .bit_is_set(),
+            uart_int_ena: unsafe { &*UART1::PTR }.int_ena.read().bits(),
+            ledc_conf0: unsafe { &*LEDC_REGS::PTR }.ch0_conf0.read().bits(),
+            pads: PADS.map(PadSnapshot::capture),
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        let pads_floating = self.pads.iter().all(|pad| !pad.output_enable);
+        // SIG_OUT_EN
+        let channel_stopped = self.ledc_conf0 & (1 << 2) == 0;
+        let clock_gated = !cfg!(feature = "gate-clocks-on-drop") || !self.spi_clock;
+
+        self.spi_int_ena == 0
+            && self.uart_int_ena == 0
+            && channel_stopped
+            && pads_floating
+            && clock_gated
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let first = construct_and_drop(&hal.clocks, &mut hal.peripheral_clock_control);
+    let second = construct_and_drop(&hal.clocks, &mut hal.peripheral_clock_control);
+
+    println!("first round:  {:?}", first);
+    println!("second round: {:?}", second);
+    println!(
+        "idle after dropping: {}, same state both rounds: {}",
+        first.is_idle() && second.is_idle(),
+        first == second
+    );
+
+    loop {}
+}
+
+/// Set up the drivers, use them and drop them again
+fn construct_and_drop(
+    clocks: &Clocks,
+    peripheral_clock_control: &mut PeripheralClockControl,
+) -> State {
+    // NOTE(unsafe) the drivers created from these are dropped before this
+    // returns, the peripherals are never owned twice
+    let peripherals = unsafe { Peripherals::steal() };
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    let mut spi = Spi::new(
+        peripherals.SPI2,
+        io.pins.gpio6,
+        io.pins.gpio7,
+        io.pins.gpio2,
+        io.pins.gpio10,
+        100u32.kHz(),
+        SpiMode::Mode0,
+        peripheral_clock_control,
+        clocks,
+    );
+    let mut data = [0xde, 0xad, 0xbe, 0xef];
+    spi.transfer(&mut data).unwrap();
+
+    let pins = TxRxPins::new_tx_rx(
+        io.pins.gpio1.into_push_pull_output(),
+        io.pins.gpio3.into_floating_input(),
+    );
+    let mut serial1 = Serial::new_with_config(
+        peripherals.UART1,
+        Some(Config::default()),
+        Some(pins),
+        clocks,
+    );
+    serial1.listen_rx_fifo_full();
+    block!(serial1.write(b'x')).unwrap();
+
+    let mut ledc = LEDC::new(peripherals.LEDC, clocks, peripheral_clock_control);
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+    let mut lstimer0 = ledc.get_timer::<LowSpeed>(timer::Number::Timer0);
+    lstimer0
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty5Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: 24u32.kHz(),
+        })
+        .unwrap();
+    let mut channel0 = ledc.get_channel(
+        channel::Number::Channel0,
+        io.pins.gpio4.into_push_pull_output(),
+    );
+    channel0
+        .configure(channel::config::Config {
+            timer: &lstimer0,
+            duty_pct: 50,
+        })
+        .unwrap();
+
+    drop(channel0);
+    drop(serial1);
+    drop(spi);
+
+    State::read()
+}
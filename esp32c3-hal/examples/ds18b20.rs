@@ -0,0 +1,84 @@
+//! Reads the temperature of all DS18B20 sensors on a 1-Wire bus
+//!
+//! Connect the data line of the sensors to GPIO4 and pull it up to 3.3 V with
+//! a 4.7 kΩ resistor. Sensors powered parasitically (VDD connected to GND) are
+//! supported, the line is driven high during the conversion.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    init,
+    one_wire::{crc8, OneWire, RomSearch},
+    pac::Peripherals,
+    prelude::*,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const DS18B20_FAMILY: u8 = 0x28;
+const CONVERT_T: u8 = 0x44;
+const READ_SCRATCHPAD: u8 = 0xbe;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let mut bus = OneWire::new(hal.io.pins.gpio4, Delay::new(&hal.clocks));
+    let mut delay = Delay::new(&hal.clocks);
+
+    loop {
+        // Start the conversion on all sensors at once
+        if bus.skip_rom().is_err() {
+            println!("No devices found");
+            delay.delay_ms(1000u32);
+            continue;
+        }
+        bus.write_byte(CONVERT_T);
+        bus.set_strong_pullup(true);
+        delay.delay_ms(750u32);
+        bus.set_strong_pullup(false);
+
+        let mut search = RomSearch::new();
+        loop {
+            let rom = match bus.search_rom(&mut search) {
+                Ok(Some(rom)) => rom,
+                Ok(None) => break,
+                Err(error) => {
+                    println!("Search failed: {:?}", error);
+                    break;
+                }
+            };
+
+            if rom as u8 != DS18B20_FAMILY {
+                continue;
+            }
+
+            let mut scratchpad = [0u8; 9];
+            bus.match_rom(rom).unwrap();
+            bus.write_byte(READ_SCRATCHPAD);
+            bus.read_bytes(&mut scratchpad);
+
+            if crc8(&scratchpad) != 0 {
+                println!("{:016x}: CRC error", rom);
+                continue;
+            }
+
+            // 1/16 °C per LSB
+            let raw = i16::from_le_bytes([scratchpad[0], scratchpad[1]]) as i32;
+            let centi = raw * 100 / 16;
+            println!(
+                "{:016x}: {}{}.{:02} °C",
+                rom,
+                if centi < 0 { "-" } else { "" },
+                centi.abs() / 100,
+                centi.abs() % 100
+            );
+        }
+
+        delay.delay_ms(1000u32);
+    }
+}
@@ -0,0 +1,69 @@
+//! This shows how to provision the user data eFuse block.
+//!
+//! By default only a dry run is done, which prints the bits that would be
+//! burned. Set `BURN` to `true` to really write the block.
+//!
+//! WARNING: burning eFuses is permanent, the user data block can only be
+//! written once.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    clock::ClockControl,
+    efuse::{Efuse, UserBlock, WriteToken},
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const BURN: bool = false;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    // A custom MAC address followed by a serial number
+    let mut data = [0u8; 32];
+    data[..6].copy_from_slice(&[0x02, 0x00, 0x00, 0x12, 0x34, 0x56]);
+    data[6..14].copy_from_slice(b"SN000001");
+
+    match Efuse::write_block_dry_run(UserBlock::UserData, &data) {
+        Ok(diff) if diff.is_unchanged() => println!("Block already provisioned"),
+        Ok(diff) => {
+            println!("{} bits to burn", diff.bits_to_set_count());
+            for (i, word) in diff.bits_to_set().iter().enumerate() {
+                println!("  word {}: {:08x}", i, word);
+            }
+
+            if BURN {
+                let token = unsafe { WriteToken::new() };
+                match Efuse::write_block(UserBlock::UserData, &data, &token) {
+                    Ok(()) => println!("Block written"),
+                    Err(error) => println!("Write failed: {:?}", error),
+                }
+            }
+        }
+        Err(error) => println!("Can't write the block: {:?}", error),
+    }
+
+    loop {}
+}
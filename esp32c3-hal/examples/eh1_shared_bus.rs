@@ -0,0 +1,142 @@
+//! Drivers written against the `embedded-hal` 1.0 traits sharing buses
+//!
+//! The following pins are used:
+//! SCLK            GPIO6
+//! MISO            GPIO2
+//! MOSI            GPIO7
+//! Flash CS        GPIO10
+//! BME280 CS       GPIO5
+//! I2C SDA         GPIO1
+//! I2C SCL         GPIO8
+//!
+//! A SPI NOR flash (e.g. a W25Q32) and a BME280 share SPI2, each is a
+//! `SpiDevice` of the bus locked by a critical section. A second BME280 (at
+//! 0x76) and a 24C02 EEPROM (at 0x50) share I2C0, kept in a
+//! `Mutex<RefCell<_>>` and lent to the drivers as `&mut` for each access.
+//!
+//! The drivers below only know the `embedded-hal` 1.0 traits, like the ones
+//! on crates.io.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use embedded_hal_1::{
+    i2c::{I2c, Operation},
+    spi::{SpiBus, SpiDevice},
+};
+use esp32c3_hal::{
+    i2c::I2C,
+    init,
+    pac::Peripherals,
+    prelude::*,
+    spi::{Spi, SpiBusController, SpiMode},
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+    let pins = hal.io.pins;
+    let mut delay = Delay::new(&hal.clocks);
+
+    let controller = SpiBusController::from_spi(Spi::new_no_cs(
+        peripherals.SPI2,
+        pins.gpio6,
+        pins.gpio7,
+        pins.gpio2,
+        1u32.MHz(),
+        SpiMode::Mode0,
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    ));
+    let mut flash = controller.add_device(pins.gpio10);
+    let mut bme280_spi = controller.add_device(pins.gpio5);
+
+    let i2c = Mutex::new(RefCell::new(I2C::new(
+        peripherals.I2C0,
+        pins.gpio1,
+        pins.gpio8,
+        100u32.kHz(),
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    )));
+
+    loop {
+        println!("Flash JEDEC ID {:02x?}", jedec_id(&mut flash));
+        println!(
+            "BME280 (SPI) chip ID {:02x?}",
+            bme280_chip_id(&mut bme280_spi)
+        );
+
+        critical_section::with(|cs| {
+            let mut i2c = i2c.borrow_ref_mut(cs);
+
+            println!(
+                "BME280 (I2C) chip ID {:02x?}",
+                bme280_i2c_chip_id(&mut *i2c, 0x76)
+            );
+
+            let mut page = [0u8; 8];
+            match eeprom_read(&mut *i2c, 0x50, 0x00, &mut page) {
+                Ok(()) => println!("EEPROM {:02x?}", page),
+                Err(error) => println!("EEPROM {:?}", error),
+            }
+        });
+
+        delay.delay_ms(1000u32);
+    }
+}
+
+/// Read the manufacturer and device ID of a SPI NOR flash
+fn jedec_id<D>(flash: &mut D) -> Result<[u8; 3], D::Error>
+where
+    D: SpiDevice,
+    D::Bus: SpiBus,
+{
+    let mut id = [0u8; 3];
+    flash.transaction(|bus| {
+        bus.write(&[0x9f])?;
+        bus.read(&mut id)
+    })?;
+    Ok(id)
+}
+
+/// Read the chip ID register (0xd0) of a BME280 in SPI mode, where bit 7 of
+/// the register address selects a read
+fn bme280_chip_id<D>(bme280: &mut D) -> Result<u8, D::Error>
+where
+    D: SpiDevice,
+    D::Bus: SpiBus,
+{
+    let mut id = [0xd0 | 0x80, 0];
+    bme280.transaction(|bus| bus.transfer_in_place(&mut id))?;
+    Ok(id[1])
+}
+
+/// Read the chip ID register (0xd0) of a BME280 in I2C mode
+fn bme280_i2c_chip_id<B>(i2c: &mut B, address: u8) -> Result<u8, B::Error>
+where
+    B: I2c,
+{
+    let mut id = [0];
+    i2c.write_read(address, &[0xd0], &mut id)?;
+    Ok(id[0])
+}
+
+/// Read `buffer.len()` bytes of a 24C02 EEPROM starting at `offset`
+fn eeprom_read<B>(i2c: &mut B, address: u8, offset: u8, buffer: &mut [u8]) -> Result<(), B::Error>
+where
+    B: I2c,
+{
+    i2c.transaction(
+        address,
+        &mut [Operation::Write(&[offset]), Operation::Read(buffer)],
+    )
+}
@@ -0,0 +1,170 @@
+//! Rolling RMS of an ADC input streamed with DMA
+//!
+//! GPIO2 is sampled at 8 kHz, a GDMA channel writes the samples into a ring
+//! buffer and the task awaits them chunk by chunk. It keeps the RMS of the
+//! signal around its mean over the last 100 ms (800 samples) and prints it
+//! ten times per second, e.g. connect a microphone module with an analog
+//! output biased to half the supply.
+//!
+//! Overruns are printed, they happen if something keeps the executor busy
+//! for longer than three chunks (75 ms).
+
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use embassy_executor::Executor;
+use esp32c3_hal::{
+    adc::{asynch::AdcStream, AdcConfig, AdcContinuous, Attenuation, ADC, ADC1},
+    dma::{self, DmaPriority},
+    embassy,
+    gdma::Gdma,
+    init,
+    interrupt,
+    pac::{self, Peripherals},
+    prelude::*,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use static_cell::StaticCell;
+
+const SAMPLE_RATE: u32 = 8_000;
+const WINDOW: usize = 800;
+const SAMPLES_PER_CHUNK: usize = 200;
+const CHUNKS: usize = 4;
+
+/// DMA buffers in internal RAM have to start at a 4 byte boundary
+#[repr(C, align(4))]
+struct Aligned([u8; CHUNKS * SAMPLES_PER_CHUNK * 4]);
+
+static mut BUFFER: Aligned = Aligned([0; CHUNKS * SAMPLES_PER_CHUNK * 4]);
+static mut TX_DESCRIPTORS: [u32; 3] = [0; 3];
+static mut RX_DESCRIPTORS: [u32; CHUNKS * 3] = [0; CHUNKS * 3];
+
+/// Sum and sum of squares of the samples in a sliding window
+struct Window {
+    samples: [u16; WINDOW],
+    next: usize,
+    sum: u64,
+    sum_of_squares: u64,
+}
+
+impl Window {
+    const fn new() -> Self {
+        Self {
+            samples: [0; WINDOW],
+            next: 0,
+            sum: 0,
+            sum_of_squares: 0,
+        }
+    }
+
+    fn push(&mut self, sample: u16) {
+        let old = self.samples[self.next] as u64;
+        self.sum -= old;
+        self.sum_of_squares -= old * old;
+
+        self.samples[self.next] = sample;
+        self.sum += sample as u64;
+        self.sum_of_squares += sample as u64 * sample as u64;
+        self.next = (self.next + 1) % WINDOW;
+    }
+
+    /// RMS around the mean, in raw ADC counts
+    fn rms(&self) -> u32 {
+        let n = WINDOW as u64;
+        let variance = (n * self.sum_of_squares - self.sum * self.sum) / (n * n);
+        isqrt(variance)
+    }
+}
+
+fn isqrt(value: u64) -> u32 {
+    if value < 2 {
+        return value as u32;
+    }
+
+    let mut x = value;
+    let mut y = (x + 1) / 2;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x as u32
+}
+
+#[embassy_executor::task]
+async fn rms(adc: AdcContinuous<ADC1>, dma: Gdma) {
+    // NOTE(unsafe) only this task uses the buffer and the descriptors
+    let (buffer, tx_descriptors, rx_descriptors) =
+        unsafe { (&mut BUFFER.0, &mut TX_DESCRIPTORS, &mut RX_DESCRIPTORS) };
+
+    let channel = dma.channel0.configure(
+        false,
+        tx_descriptors,
+        rx_descriptors,
+        DmaPriority::Priority0,
+    );
+    let mut stream = AdcStream::new(adc, channel, buffer, SAMPLES_PER_CHUNK).unwrap();
+
+    let mut window = Window::new();
+    let mut samples = [0u16; SAMPLES_PER_CHUNK];
+    let mut count = 0;
+
+    loop {
+        match stream.next_samples(&mut samples).await {
+            Ok(len) => {
+                for sample in &samples[..len] {
+                    window.push(*sample);
+                }
+
+                count += len;
+                if count >= SAMPLE_RATE as usize / 10 {
+                    count = 0;
+                    println!("RMS {} counts", window.rms());
+                }
+            }
+            Err(error) => println!("{:?}", error),
+        }
+    }
+}
+
+#[interrupt]
+fn DMA_CH0() {
+    dma::asynch::handle_interrupt(0);
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+#[riscv_rt::entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    #[cfg(feature = "embassy-time-systick")]
+    embassy::init(
+        &hal.clocks,
+        esp32c3_hal::systimer::SystemTimer::new(peripherals.SYSTIMER),
+    );
+
+    #[cfg(feature = "embassy-time-timg0")]
+    embassy::init(&hal.clocks, hal.timer_group0.timer0);
+
+    let analog = peripherals.APB_SARADC.split();
+    let mut adc1_config = AdcConfig::new();
+    let _pin = adc1_config.enable_pin(
+        hal.io.pins.gpio2.into_analog(),
+        Attenuation::Attenuation11dB,
+    );
+    let adc1 =
+        ADC::<ADC1>::adc(&mut hal.peripheral_clock_control, analog.adc1, adc1_config).unwrap();
+    let adc1 = AdcContinuous::new(adc1, SAMPLE_RATE.Hz(), &hal.clocks);
+
+    let dma = Gdma::new(peripherals.DMA, &mut hal.peripheral_clock_control);
+
+    interrupt::enable(pac::Interrupt::DMA_CH0, interrupt::Priority::Priority1).unwrap();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(rms(adc1, dma)).ok();
+    });
+}
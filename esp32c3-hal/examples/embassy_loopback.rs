@@ -0,0 +1,109 @@
+//! Uses UART1 and SPI2 in async mode
+//!
+//! Both peripherals loop their output back internally, no wiring needed. One
+//! task writes a frame to the TX half of UART1 every second, another one
+//! reads it from the RX half. A third task exchanges four bytes over SPI2,
+//! with SCLK on GPIO6, MOSI on GPIO7, MISO on GPIO2 and CS on GPIO10.
+
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use embassy_executor::Executor;
+use embassy_time::{Duration, Timer};
+use esp32c3_hal::{
+    embassy,
+    init,
+    interrupt,
+    mode::Async,
+    pac::{Peripherals, SPI2, UART1},
+    prelude::*,
+    serial::{self, Rx, Tx},
+    spi::{self, Spi, SpiMode},
+    Serial,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use static_cell::StaticCell;
+
+#[embassy_executor::task]
+async fn writer(mut tx: Tx<UART1, (), Async>) {
+    loop {
+        tx.write_async(b"Hello async world").await.unwrap();
+        tx.flush_async().await.unwrap();
+        Timer::after(Duration::from_millis(1_000)).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn reader(mut rx: Rx<UART1, Async>) {
+    let mut buffer = [0u8; 64];
+    loop {
+        let len = rx.read_frame_async(&mut buffer).await.unwrap();
+        println!("UART1 received {:?}", core::str::from_utf8(&buffer[..len]));
+    }
+}
+
+#[embassy_executor::task]
+async fn transfer(mut spi: Spi<SPI2, Async>) {
+    loop {
+        let mut data = [0xde, 0xca, 0xfb, 0xad];
+        spi.transfer_async(&mut data).await.unwrap();
+        println!("SPI2 received {:x?}", data);
+        Timer::after(Duration::from_millis(250)).await;
+    }
+}
+
+#[interrupt]
+fn UART1() {
+    serial::asynch::handle_interrupt::<UART1>();
+}
+
+#[interrupt]
+fn SPI2() {
+    spi::asynch::handle_interrupt::<SPI2>();
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+#[riscv_rt::entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    #[cfg(feature = "embassy-time-systick")]
+    embassy::init(
+        &hal.clocks,
+        esp32c3_hal::systimer::SystemTimer::new(peripherals.SYSTIMER),
+    );
+
+    #[cfg(feature = "embassy-time-timg0")]
+    embassy::init(&hal.clocks, hal.timer_group0.timer0);
+
+    let mut serial = Serial::new(peripherals.UART1);
+    serial.enable_loopback(true);
+    // a frame ends after 3 characters of silence
+    serial.set_rx_idle_bits(30);
+    let (tx, rx) = serial.into_async().unwrap().split();
+
+    let mut spi = Spi::new(
+        peripherals.SPI2,
+        hal.io.pins.gpio6,
+        hal.io.pins.gpio7,
+        hal.io.pins.gpio2,
+        hal.io.pins.gpio10,
+        100u32.kHz(),
+        SpiMode::Mode0,
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    );
+    spi.enable_loopback(true);
+    let spi = spi.into_async().unwrap();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(writer(tx)).ok();
+        spawner.spawn(reader(rx)).ok();
+        spawner.spawn(transfer(spi)).ok();
+    });
+}
@@ -0,0 +1,79 @@
+//! Waits for GPIO events asynchronously
+//!
+//! One task waits for the BOOT button (GPIO9), the other one pulls an open
+//! drain line on GPIO4 low for a moment and then waits until all other
+//! devices released it. Connect a pull-up resistor to GPIO4.
+
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use embassy_executor::Executor;
+use embassy_time::{Duration, Timer};
+use embedded_hal_async::digital::Wait;
+use esp32c3_hal::{
+    embassy,
+    gpio::{asynch, Gpio4, Gpio9, Input, OpenDrain, Output, PullUp},
+    init,
+    interrupt,
+    pac::{self, Peripherals},
+    prelude::*,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use static_cell::StaticCell;
+
+#[embassy_executor::task]
+async fn button(mut button: Gpio9<Input<PullUp>>) {
+    loop {
+        button.wait_for_falling_edge().await.unwrap();
+        println!("Button pressed");
+    }
+}
+
+#[embassy_executor::task]
+async fn line(mut line: Gpio4<Output<OpenDrain>>) {
+    loop {
+        line.set_low().unwrap();
+        Timer::after(Duration::from_millis(10)).await;
+        line.set_high().unwrap();
+
+        line.wait_for_high().await.unwrap();
+        println!("Line released");
+        Timer::after(Duration::from_millis(1_000)).await;
+    }
+}
+
+#[interrupt]
+fn GPIO() {
+    asynch::handle_interrupt();
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+#[riscv_rt::entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    #[cfg(feature = "embassy-time-systick")]
+    embassy::init(
+        &hal.clocks,
+        esp32c3_hal::systimer::SystemTimer::new(peripherals.SYSTIMER),
+    );
+
+    #[cfg(feature = "embassy-time-timg0")]
+    embassy::init(&hal.clocks, hal.timer_group0.timer0);
+
+    interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority1).unwrap();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner
+            .spawn(button(hal.io.pins.gpio9.into_pull_up_input()))
+            .ok();
+        spawner
+            .spawn(line(hal.io.pins.gpio4.into_open_drain_output()))
+            .ok();
+    });
+}
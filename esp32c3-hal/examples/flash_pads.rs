@@ -0,0 +1,71 @@
+//! Checks that the drive strength of the flash pads can be changed
+//!
+//! Every drive strength is written to each flash pad and read back, while
+//! the code keeps running from flash. The original settings are restored
+//! afterwards and a summary of the pads is printed.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    gpio::{DriveStrength, FlashPad},
+    init,
+    pac::Peripherals,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const PADS: [FlashPad; 6] = [
+    FlashPad::Hd,
+    FlashPad::Wp,
+    FlashPad::Cs0,
+    FlashPad::Clk,
+    FlashPad::D,
+    FlashPad::Q,
+];
+
+const STRENGTHS: [DriveStrength; 4] = [
+    DriveStrength::I5mA,
+    DriveStrength::I10mA,
+    DriveStrength::I20mA,
+    DriveStrength::I40mA,
+];
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+    let pads = &mut hal.io.flash_pads;
+
+    let mut passed = true;
+
+    for pad in PADS {
+        let original = pads.drive_strength(pad);
+
+        for strength in STRENGTHS {
+            pads.set_drive_strength(pad, strength);
+            let read_back = pads.drive_strength(pad);
+            if read_back != strength {
+                println!("{:?}: wrote {:?}, read {:?}", pad, strength, read_back);
+                passed = false;
+            }
+        }
+
+        pads.set_drive_strength(pad, original);
+        println!(
+            "{:?}: {:?}, input {}",
+            pad,
+            pads.drive_strength(pad),
+            if pads.is_input_enabled(pad) {
+                "enabled"
+            } else {
+                "disabled"
+            }
+        );
+    }
+
+    println!("{}", if passed { "PASS" } else { "FAIL" });
+
+    loop {}
+}
@@ -0,0 +1,63 @@
+//! Measures the flow rate with a hall effect flow sensor
+//!
+//! The ESP32-C3 has no pulse counter peripheral, the pulses of the sensor are
+//! counted in software by the GPIO edge counter.
+//!
+//! Connect the signal of a YF-S201 style sensor (7.5 pulses per second per
+//! liter per minute) to GPIO4. The flow rate and the total volume are printed
+//! every second.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    gpio::{edge_counter, Event},
+    init,
+    interrupt,
+    pac::{self, Peripherals},
+    prelude::*,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let mut sensor = hal.io.pins.gpio4.into_pull_up_input();
+    sensor.enable_edge_counter(Event::RisingEdge).unwrap();
+
+    interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority3).unwrap();
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    let mut delay = Delay::new(&hal.clocks);
+    let mut last_count = 0u32;
+    let mut total_pulses = 0u64;
+
+    loop {
+        delay.delay_ms(1000u32);
+
+        // The counter wraps around, the difference is correct nevertheless
+        let count = sensor.edge_count();
+        let pulses = count.wrapping_sub(last_count);
+        last_count = count;
+        total_pulses += pulses as u64;
+
+        // 7.5 pulses per second are 1 l/min, 450 pulses are 1 l
+        let flow_ml_per_min = pulses * 400 / 3;
+        let total_ml = total_pulses * 20 / 9;
+
+        println!("{} ml/min, {} ml total", flow_ml_per_min, total_ml);
+    }
+}
+
+#[interrupt]
+fn GPIO() {
+    edge_counter::handle_interrupt();
+}
@@ -0,0 +1,109 @@
+//! Measures the frequency of two LEDC outputs with frequency counters
+//!
+//! Connect GPIO4 to GPIO5 and GPIO6 to GPIO7. LEDC drives 1 kHz on GPIO4 and
+//! 5 Hz on GPIO6, GPIO5 and GPIO7 measure them with a gate time of 100 ms.
+//! The 5 Hz signal has no full period during the gate time, its measurement
+//! switches to the period automatically. Both results are checked against
+//! the LEDC frequencies.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    gpio::{edge_counter, frequency_counter::FrequencyCounter},
+    init,
+    interrupt,
+    ledc::{
+        channel::{self, ChannelIFace},
+        timer::{self, TimerIFace},
+        LSGlobalClkSource,
+        LowSpeed,
+        LEDC,
+    },
+    pac::{self, Peripherals},
+    prelude::*,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let mut ledc = LEDC::new(
+        peripherals.LEDC,
+        &hal.clocks,
+        &mut hal.peripheral_clock_control,
+    );
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+
+    let mut fast_timer = ledc.get_timer::<LowSpeed>(timer::Number::Timer0);
+    fast_timer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty5Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: 1u32.kHz(),
+        })
+        .unwrap();
+    let mut slow_timer = ledc.get_timer::<LowSpeed>(timer::Number::Timer1);
+    slow_timer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty14Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: 5u32.Hz(),
+        })
+        .unwrap();
+
+    let mut fast_channel = ledc.get_channel(
+        channel::Number::Channel0,
+        hal.io.pins.gpio4.into_push_pull_output(),
+    );
+    fast_channel
+        .configure(channel::config::Config {
+            timer: &fast_timer,
+            duty_pct: 50,
+        })
+        .unwrap();
+    let mut slow_channel = ledc.get_channel(
+        channel::Number::Channel1,
+        hal.io.pins.gpio6.into_push_pull_output(),
+    );
+    slow_channel
+        .configure(channel::config::Config {
+            timer: &slow_timer,
+            duty_pct: 50,
+        })
+        .unwrap();
+
+    let mut fast = FrequencyCounter::new(hal.io.pins.gpio5.into_floating_input(), 100u64.millis());
+    let mut slow = FrequencyCounter::new(hal.io.pins.gpio7.into_floating_input(), 100u64.millis());
+
+    interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority3).unwrap();
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    loop {
+        check("GPIO5", fast.measure().to_Hz(), 1000);
+        check("GPIO7", slow.measure().to_Hz(), 5);
+    }
+}
+
+fn check(pin: &str, measured: u32, expected: u32) {
+    let ok = measured.abs_diff(expected) <= expected / 100;
+    println!(
+        "{}: {} Hz, expected {} Hz: {}",
+        pin,
+        measured,
+        expected,
+        if ok { "OK" } else { "FAIL" }
+    );
+}
+
+#[interrupt]
+fn GPIO() {
+    edge_counter::handle_interrupt();
+}
@@ -0,0 +1,127 @@
+//! Self-test of the RMT frequency ratio measurement against the LEDC
+//!
+//! The LEDC outputs 10 kHz on GPIO4 and 2.5 kHz on GPIO5, both derived from
+//! the APB clock. Connect GPIO4 to GPIO6 and GPIO5 to GPIO7: the RMT RX
+//! channels count the inputs over a one second gate, the example prints
+//! the measured frequency of GPIO6 and the ratio of the inputs with their
+//! deviation from the nominal values. Both should stay within a few ppm
+//! (parts per million), nonzero only because of the one tick quantization
+//! at the ends of the gate.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    init,
+    ledc::{
+        channel::{self, ChannelIFace},
+        timer::{self, TimerIFace},
+        LSGlobalClkSource,
+        LowSpeed,
+        LEDC,
+    },
+    pac::Peripherals,
+    prelude::*,
+    pulse_control::{
+        frequency_ratio::{FrequencyRatio, Ratio},
+        ClockSource,
+    },
+    PulseControl,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const FAST: u32 = 10_000;
+const SLOW: u32 = 2_500;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let mut ledc = LEDC::new(
+        peripherals.LEDC,
+        &hal.clocks,
+        &mut hal.peripheral_clock_control,
+    );
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+
+    let mut fast_timer = ledc.get_timer::<LowSpeed>(timer::Number::Timer0);
+    fast_timer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty5Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: FAST.Hz(),
+        })
+        .unwrap();
+    let mut slow_timer = ledc.get_timer::<LowSpeed>(timer::Number::Timer1);
+    slow_timer
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty5Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: SLOW.Hz(),
+        })
+        .unwrap();
+
+    let mut fast = ledc.get_channel(
+        channel::Number::Channel0,
+        hal.io.pins.gpio4.into_push_pull_output(),
+    );
+    fast.configure(channel::config::Config {
+        timer: &fast_timer,
+        duty_pct: 50,
+    })
+    .unwrap();
+    let mut slow = ledc.get_channel(
+        channel::Number::Channel1,
+        hal.io.pins.gpio5.into_push_pull_output(),
+    );
+    slow.configure(channel::config::Config {
+        timer: &slow_timer,
+        duty_pct: 50,
+    })
+    .unwrap();
+
+    let pulse = PulseControl::new(
+        peripherals.RMT,
+        &mut hal.peripheral_clock_control,
+        ClockSource::APB,
+        0,
+        0,
+        0,
+    )
+    .unwrap();
+
+    let mut meter = FrequencyRatio::new(
+        pulse.rx_channels,
+        hal.clocks.apb_clock,
+        1,
+        hal.io.pins.gpio6,
+        hal.io.pins.gpio7,
+    )
+    .unwrap();
+
+    let nominal_frequency = Ratio::from_fraction(FAST as u64, 1).unwrap();
+    let nominal_ratio = Ratio::from_fraction(FAST as u64, SLOW as u64).unwrap();
+
+    loop {
+        match meter.measure(1000u32.millis()) {
+            Ok(measurement) => {
+                // a successful measurement has periods and ticks on both inputs
+                let frequency = measurement.a.frequency(meter.tick_rate()).unwrap();
+                let ratio = measurement.ratio().unwrap();
+
+                println!(
+                    "{} Hz ({:?} ppb), ratio {} + {}/2^32 ({:?} ppb)",
+                    frequency.integer(),
+                    frequency.deviation_ppb(nominal_frequency),
+                    ratio.integer(),
+                    ratio.raw() as u32,
+                    ratio.deviation_ppb(nominal_ratio)
+                );
+            }
+            Err(error) => println!("{:?}", error),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+//! Measures how fast a 4-bit pattern on the inputs is detected with the bank
+//! registers
+//!
+//! GPIO4..GPIO7 are outputs driving the pattern, connect them to the inputs
+//! GPIO0..GPIO3 with jumper wires. For every pattern the outputs are set with
+//! two register writes and the inputs are polled until they match. The
+//! average time per pattern and the number of polls which didn't match yet are
+//! printed.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{init, pac::Peripherals, prelude::*, systimer::SystemTimer};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const INPUTS: u32 = 0b1111;
+const OUTPUTS: u32 = 0b1111 << 4;
+const ROUNDS: u32 = 10_000;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    // The typed pins configure the pads, levels are handled by the bank
+    let pins = hal.io.pins;
+    let _inputs = (
+        pins.gpio0.into_floating_input(),
+        pins.gpio1.into_floating_input(),
+        pins.gpio2.into_floating_input(),
+        pins.gpio3.into_floating_input(),
+    );
+    let _outputs = (
+        pins.gpio4.into_push_pull_output(),
+        pins.gpio5.into_push_pull_output(),
+        pins.gpio6.into_push_pull_output(),
+        pins.gpio7.into_push_pull_output(),
+    );
+    let mut bank = hal.io.bank0;
+
+    loop {
+        let mut polls = 0u32;
+        let start = SystemTimer::now();
+
+        for round in 0..ROUNDS {
+            let pattern = round & INPUTS;
+
+            bank.write_output_clear(!(pattern << 4) & OUTPUTS);
+            bank.write_output_set(pattern << 4);
+
+            while bank.read_input() & INPUTS != pattern {
+                polls += 1;
+            }
+        }
+
+        let ticks = SystemTimer::now() - start;
+        let nanos = ticks * 1_000_000_000 / SystemTimer::TICKS_PER_SECOND;
+        println!(
+            "{} ns per pattern, {} extra polls",
+            nanos / ROUNDS as u64,
+            polls
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! Compares the cost of toggling a typed GPIO with one behind a trait object
+//!
+//! The typed pin knows its register and bit at compile time, the trait object
+//! has to dispatch every call at runtime. Both toggle GPIO5 1000 times, the
+//! cycles are measured with the CPU cycle counter.
+
+#![no_std]
+#![no_main]
+
+use core::convert::Infallible;
+
+use embedded_hal::digital::v2::OutputPin;
+use esp32c3_hal::{
+    init,
+    pac::Peripherals,
+    prelude::*,
+    profiling::{self, CycleCounter},
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const TOGGLES: u32 = 1000;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let mut pin = hal.io.pins.gpio5.into_push_pull_output();
+    let mut delay = Delay::new(&hal.clocks);
+
+    loop {
+        let counter = CycleCounter::start();
+        for _ in 0..TOGGLES / 2 {
+            pin.set_high().unwrap();
+            pin.set_low().unwrap();
+        }
+        let typed = counter.elapsed();
+
+        let counter = CycleCounter::start();
+        toggle_dyn(&mut pin);
+        let erased = counter.elapsed();
+
+        println!(
+            "typed: {} ns, trait object: {} ns ({} / {} cycles per toggle)",
+            profiling::cycles_to_nanos(typed, &hal.clocks),
+            profiling::cycles_to_nanos(erased, &hal.clocks),
+            typed / TOGGLES,
+            erased / TOGGLES
+        );
+
+        delay.delay_ms(1000u32);
+    }
+}
+
+#[inline(never)]
+fn toggle_dyn(pin: &mut dyn OutputPin<Error = Infallible>) {
+    for _ in 0..TOGGLES / 2 {
+        pin.set_high().unwrap();
+        pin.set_low().unwrap();
+    }
+}
@@ -0,0 +1,170 @@
+//! Checks that borrowed pads are restored exactly as they were
+//!
+//! The following pins are used:
+//! - I2C SDA => GPIO1
+//! - I2C SCL => GPIO2
+//! - GPIO4, an input with pull-down listening for falling edges
+//! - GPIO5, a push-pull output driving high at 40 mA
+//!
+//! Add pull-up resistors to SDA and SCL, nothing else needs to be connected.
+//!
+//! Each pad is borrowed and reconfigured in every way [Flex] allows, then its
+//! configuration is compared with a snapshot taken before. The same is done
+//! for a closure returning early and for the bus recovery of the I2C driver.
+//! Borrowing a pad again from within the closure has to fail. The result of
+//! each check is printed.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    gpio::{
+        self,
+        pad::{Flex, PadError, PadSnapshot},
+        DriveStrength,
+        Event,
+        Pull,
+    },
+    i2c::I2C,
+    init,
+    pac::Peripherals,
+    prelude::*,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+    let mut delay = Delay::new(&hal.clocks);
+
+    let mut i2c = I2C::new(
+        peripherals.I2C0,
+        hal.io.pins.gpio1,
+        hal.io.pins.gpio2,
+        100u32.kHz(),
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    );
+
+    let mut input = hal.io.pins.gpio4.into_pull_down_input();
+    input.listen(Event::FallingEdge);
+
+    let mut output = hal.io.pins.gpio5.into_push_pull_output();
+    output.set_drive_strength(DriveStrength::I40mA);
+    output.set_high().unwrap();
+
+    let mut passed = true;
+    for gpio_num in [1, 2, 4, 5] {
+        passed &= check(gpio_num, "reconfigured", || {
+            gpio::borrow_pad(gpio_num, |flex| exercise(flex, &mut delay)).unwrap();
+        });
+
+        passed &= check(gpio_num, "early return", || {
+            let _: Result<(), ()> = gpio::borrow_pad(gpio_num, |flex| {
+                flex.set_as_output();
+                flex.set_low();
+                if flex.is_low() {
+                    return Err(());
+                }
+                flex.set_high();
+                Ok(())
+            })
+            .unwrap();
+        });
+
+        let mut nested = Ok(());
+        passed &= check(gpio_num, "nested borrow", || {
+            nested = gpio::borrow_pad(gpio_num, |flex| {
+                flex.set_as_output();
+                gpio::borrow_pad(gpio_num, |_| ())
+            })
+            .unwrap();
+        });
+        if nested != Err(PadError::AlreadyBorrowed) {
+            println!("GPIO{} borrowed twice: {:?}", gpio_num, nested);
+            passed = false;
+        }
+    }
+
+    for gpio_num in [1, 2] {
+        passed &= check(gpio_num, "I2C bus recovery", || i2c.reset().unwrap());
+    }
+
+    if passed {
+        println!("All pads restored");
+    } else {
+        println!("Some pads were not restored");
+    }
+
+    loop {}
+}
+
+/// Run every mode of the pad, toggling it in between
+fn exercise(flex: &mut Flex, delay: &mut Delay) {
+    flex.set_as_output();
+    toggle(flex, delay);
+
+    flex.set_as_open_drain(Pull::Up);
+    toggle(flex, delay);
+
+    flex.set_as_open_drain(Pull::None);
+    toggle(flex, delay);
+
+    flex.set_as_input(Pull::Down);
+    delay.delay_us(10u32);
+    println!("GPIO{} reads {}", flex.gpio_num(), flex.is_high());
+
+    flex.set_pull(Pull::Up);
+}
+
+fn toggle(flex: &mut Flex, delay: &mut Delay) {
+    for _ in 0..4 {
+        flex.set_low();
+        delay.delay_us(10u32);
+        flex.set_high();
+        delay.delay_us(10u32);
+    }
+}
+
+/// Compare the pad before and after `f`, print the fields which differ
+fn check(gpio_num: u8, name: &str, f: impl FnOnce()) -> bool {
+    let before = PadSnapshot::capture(gpio_num).unwrap();
+    f();
+    let after = PadSnapshot::capture(gpio_num).unwrap();
+
+    if before == after {
+        println!("GPIO{} {}: ok", gpio_num, name);
+        return true;
+    }
+
+    println!("GPIO{} {}: FAILED", gpio_num, name);
+    if before.io_mux != after.io_mux {
+        println!("  IO_MUX {:08x} != {:08x}", before.io_mux, after.io_mux);
+    }
+    if before.pin != after.pin {
+        println!("  pin {:08x} != {:08x}", before.pin, after.pin);
+    }
+    if before.out_sel != after.out_sel {
+        println!("  out_sel {:08x} != {:08x}", before.out_sel, after.out_sel);
+    }
+    if before.output_enable != after.output_enable {
+        println!(
+            "  output enable {} != {}",
+            before.output_enable, after.output_enable
+        );
+    }
+    if before.output != after.output {
+        println!("  output {} != {}", before.output, after.output);
+    }
+    if before.interrupt_pending != after.interrupt_pending {
+        println!(
+            "  interrupt pending {} != {}",
+            before.interrupt_pending, after.interrupt_pending
+        );
+    }
+    false
+}
@@ -0,0 +1,136 @@
+//! Debounces four buttons in a timer interrupt
+//!
+//! Connect buttons from GPIO4, GPIO5 and GPIO6 to ground. The fourth input,
+//! GPIO7, is fed by a simulated bouncy switch: connect it to GPIO10, which
+//! toggles a random number of times before it settles on every press and
+//! release.
+//!
+//! The pins are sampled every millisecond and filtered with an integrator
+//! over 10 samples. The simulated bounces last at most 5 ms, which can't
+//! move the integrator from one end to the other. Every button press is
+//! printed once, and the number of presses detected on GPIO7 always matches
+//! the number of simulated presses.
+//!
+//! The pins are declared as a board definition with `board_pins!`.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use esp32c3_hal::{
+    board_pins,
+    gpio::debounce::{Debouncer, Filter},
+    init,
+    interrupt,
+    pac::{self, Peripherals, TIMG0},
+    prelude::*,
+    timer::Timer0,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+board_pins! {
+    /// Three buttons and a simulated bouncy switch
+    struct Board {
+        button_a: gpio4 => Input<PullUp>,
+        button_b: gpio5 => Input<PullUp>,
+        button_c: gpio6 => Input<PullUp>,
+        /// Connected to `switch`
+        bouncy: gpio7 => Input<PullUp>,
+        switch: gpio10 => Output<PushPull>,
+    }
+}
+
+static DEBOUNCER: Mutex<RefCell<Option<Debouncer<Timer0<TIMG0>, 4>>>> =
+    Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+    let (board, _unused) = Board::new(hal.io.pins);
+
+    let mut debouncer = Debouncer::new(
+        hal.timer_group0.timer0,
+        1u64.millis(),
+        Filter::Integrator { samples: 10 },
+    );
+    debouncer.add(board.button_a, true);
+    debouncer.add(board.button_b, true);
+    debouncer.add(board.button_c, true);
+    let bouncy = debouncer.add(board.bouncy, true).unwrap();
+    debouncer.on_change(bouncy, |pressed| {
+        if pressed {
+            println!("GPIO7 pressed");
+        }
+    });
+
+    critical_section::with(|cs| DEBOUNCER.borrow_ref_mut(cs).replace(debouncer));
+
+    interrupt::enable(pac::Interrupt::TG0_T0_LEVEL, interrupt::Priority::Priority1).unwrap();
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    let mut switch = board.switch;
+    switch.set_high().unwrap();
+
+    let mut delay = Delay::new(&hal.clocks);
+    let mut random = 0x1234_5678u32;
+    let mut simulated = 0u32;
+    let mut detected = 0u32;
+
+    loop {
+        // press: bounce, then stay low
+        for _ in 0..random % 16 {
+            random = random.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            switch.toggle().unwrap();
+            delay.delay_us(50 + random % 300);
+        }
+        switch.set_low().unwrap();
+        simulated += 1;
+        delay.delay_ms(200u32);
+
+        // release: bounce, then stay high
+        for _ in 0..random % 16 {
+            random = random.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            switch.toggle().unwrap();
+            delay.delay_us(50 + random % 300);
+        }
+        switch.set_high().unwrap();
+        delay.delay_ms(800u32);
+
+        critical_section::with(|cs| {
+            let mut debouncer = DEBOUNCER.borrow_ref_mut(cs);
+            let debouncer = debouncer.as_mut().unwrap();
+
+            for button in 0..3 {
+                if debouncer.rising(button) {
+                    println!("GPIO{} pressed", 4 + button);
+                }
+            }
+
+            if debouncer.rising(bouncy) {
+                detected += 1;
+            }
+        });
+
+        println!("{} simulated presses, {} detected", simulated, detected);
+    }
+}
+
+#[interrupt]
+fn TG0_T0_LEVEL() {
+    critical_section::with(|cs| {
+        DEBOUNCER
+            .borrow_ref_mut(cs)
+            .as_mut()
+            .unwrap()
+            .on_interrupt();
+    });
+}
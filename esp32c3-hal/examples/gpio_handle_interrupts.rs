@@ -0,0 +1,64 @@
+//! Decodes the GPIO interrupt in the application's own handler
+//!
+//! The boot button (GPIO9) counts presses, a second button between GPIO3
+//! and GND reports presses and releases. The `GPIO` handler only calls
+//! `gpio::handle_interrupts`, which is how an RTIC task bound to `GPIO`
+//! would use it; the counter in a `Mutex` stands in for an RTIC resource.
+//! The pins stay in `main`, the handler doesn't need them.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use esp32c3_hal::{
+    gpio::{self, Event},
+    init,
+    interrupt,
+    pac::{self, Peripherals},
+    prelude::*,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+static PRESSES: Mutex<RefCell<u32>> = Mutex::new(RefCell::new(0));
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let mut boot = hal.io.pins.gpio9.into_pull_up_input();
+    let mut button = hal.io.pins.gpio3.into_pull_up_input();
+    boot.listen(Event::FallingEdge);
+    button.listen(Event::AnyEdge);
+
+    interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority1).unwrap();
+
+    loop {}
+}
+
+#[interrupt]
+fn GPIO() {
+    gpio::handle_interrupts(|pin, status| match pin {
+        9 => {
+            let presses = critical_section::with(|cs| {
+                let mut presses = PRESSES.borrow_ref_mut(cs);
+                *presses += 1;
+                *presses
+            });
+            println!("Boot button pressed, {} presses", presses);
+        }
+        3 => println!(
+            "GPIO3 {}",
+            if status.is_high() {
+                "released"
+            } else {
+                "pressed"
+            }
+        ),
+        _ => println!("Unexpected interrupt on GPIO{}", pin),
+    });
+}
@@ -0,0 +1,38 @@
+//! Checks the board for solder bridges between the GPIOs
+//!
+//! All pins but the strapping pins, the flash pins and the ones used for the
+//! console (USB on GPIO18/GPIO19, UART0 on GPIO20/GPIO21) are tested for
+//! shorts to each other and to GND or 3.3 V. Nothing may be connected to
+//! them while the test runs.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{gpio::self_test::SelfTestConfig, init, pac::Peripherals};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let report = hal.io.self_test(SelfTestConfig {
+        exclude: 1 << 18 | 1 << 19 | 1 << 20 | 1 << 21,
+        ..SelfTestConfig::default()
+    });
+
+    println!("tested     {:#014x}", report.tested);
+    println!("stuck high {:#014x}", report.stuck_high);
+    println!("stuck low  {:#014x}", report.stuck_low);
+    for (a, b) in report.shorts() {
+        println!("GPIO{} shorted to GPIO{}", a, b);
+    }
+    if report.truncated {
+        println!("more shorts not listed");
+    }
+    println!("{}", if report.passed() { "PASS" } else { "FAIL" });
+
+    loop {}
+}
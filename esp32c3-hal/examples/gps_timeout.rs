@@ -0,0 +1,82 @@
+//! Reads NMEA sentences from a GPS module which might not be connected
+//!
+//! Connect the TX pin of a GPS module (9600 baud) to GPIO2. Every sentence
+//! has to arrive within two seconds, otherwise the read gives up and the
+//! module is reported as absent together with the bytes of the partial
+//! sentence. Unplugging and plugging the module switches between the two
+//! reports without blocking the loop.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    init,
+    pac::Peripherals,
+    prelude::*,
+    serial::{
+        config::{Config, DataBits, Parity, StopBits},
+        Error,
+        TxRxPins,
+    },
+    time::Deadline,
+    Serial,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let config = Config {
+        baudrate: 9600,
+        data_bits: DataBits::DataBits8,
+        parity: Parity::ParityNone,
+        stop_bits: StopBits::STOP1,
+    };
+    let pins = TxRxPins::new_tx_rx(
+        hal.io.pins.gpio1.into_push_pull_output(),
+        hal.io.pins.gpio2.into_floating_input(),
+    );
+    let mut gps = Serial::new_with_config(peripherals.UART1, Some(config), Some(pins), &hal.clocks);
+
+    let mut sentence = [0u8; 82];
+
+    loop {
+        let deadline = Deadline::after(2u64.secs());
+        let mut len = 0;
+
+        let result = loop {
+            if len == sentence.len() {
+                break Ok(());
+            }
+
+            if let Err(error) = gps.read_exact_timeout(&mut sentence[len..][..1], deadline) {
+                break Err(error);
+            }
+
+            match sentence[len] {
+                b'$' => {
+                    sentence[0] = b'$';
+                    len = 1;
+                }
+                b'\n' if len > 0 => break Ok(()),
+                _ if len > 0 => len += 1,
+                _ => {}
+            }
+        };
+
+        match result {
+            Ok(()) => println!(
+                "{}",
+                core::str::from_utf8(&sentence[..len]).unwrap_or("<invalid>")
+            ),
+            Err(Error::Timeout { .. }) => {
+                println!("GPS absent ({} bytes of a sentence received)", len)
+            }
+            Err(error) => panic!("{:?}", error),
+        }
+    }
+}
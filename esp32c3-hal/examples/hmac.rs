@@ -0,0 +1,89 @@
+//! Computes an HMAC-SHA256 with a key stored in eFuse and compares it against
+//! a software implementation.
+//!
+//! The development key has to be burned to BLOCK_KEY4 with the `HMAC_UP`
+//! purpose first, e.g.:
+//!
+//! ```text
+//! espefuse.py burn_key BLOCK_KEY4 key.bin HMAC_UP
+//! ```
+//!
+//! where `key.bin` contains the 32 bytes of `DEVELOPMENT_KEY`. Never use this
+//! key in production, burning a key to eFuse is irreversible.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    clock::ClockControl,
+    hmac::{Hmac, HmacPurpose, KeyId},
+    pac::Peripherals,
+    prelude::*,
+    timer::TimerGroup,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+use sha2::{Digest, Sha256};
+
+const DEVELOPMENT_KEY: [u8; 32] = *b"ThisIsARandomKeyForTestingOnly!!";
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let mut hmac = Hmac::new(peripherals.HMAC, &mut system.peripheral_clock_control);
+
+    for message in ["".as_bytes(), "Hello, world!".as_bytes(), &[0x5au8; 200]] {
+        let mut hw_result = [0u8; 32];
+        match hmac.configure(HmacPurpose::ToUser, KeyId::Key4) {
+            Ok(()) => {
+                hmac.update(message).unwrap();
+                hmac.finalize(&mut hw_result).unwrap();
+            }
+            Err(err) => {
+                println!("Key block not usable: {:?}", err);
+                loop {}
+            }
+        }
+
+        let sw_result = software_hmac(&DEVELOPMENT_KEY, message);
+
+        println!("HMAC hardware {:02x?}", hw_result);
+        println!("HMAC software {:02x?}", sw_result);
+        println!("Match: {}", hw_result == sw_result);
+    }
+
+    loop {}
+}
+
+fn software_hmac(key: &[u8; 32], message: &[u8]) -> [u8; 32] {
+    let mut ipad = [0x36u8; 64];
+    let mut opad = [0x5cu8; 64];
+    for (i, byte) in key.iter().enumerate() {
+        ipad[i] ^= byte;
+        opad[i] ^= byte;
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(ipad);
+    hasher.update(message);
+    let inner = hasher.finalize();
+
+    let mut hasher = Sha256::new();
+    hasher.update(opad);
+    hasher.update(inner);
+
+    hasher.finalize().into()
+}
@@ -0,0 +1,60 @@
+//! Scans the I2C bus and prints a table of the devices found
+//!
+//! The following wiring is assumed:
+//! - SDA => GPIO1
+//! - SCL => GPIO2
+//!
+//! The table looks like the one of `i2cdetect`, addresses which acknowledged
+//! are printed, the others are shown as `--`. A stuck bus is reported as an
+//! error. The scan repeats every five seconds, so devices can be plugged in
+//! and out.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{i2c::I2C, init, pac::Peripherals, prelude::*, Delay};
+use esp_backtrace as _;
+use esp_println::{print, println};
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let mut i2c = I2C::new(
+        peripherals.I2C0,
+        hal.io.pins.gpio1,
+        hal.io.pins.gpio2,
+        100u32.kHz(),
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    );
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    loop {
+        match i2c.scan() {
+            Ok(found) => {
+                println!("     0  1  2  3  4  5  6  7  8  9  a  b  c  d  e  f");
+                for row in (0x00..0x80u8).step_by(16) {
+                    print!("{:02x}:", row);
+                    for address in row..row + 16 {
+                        if found.contains(&address) {
+                            print!(" {:02x}", address);
+                        } else if (0x08..=0x77).contains(&address) {
+                            print!(" --");
+                        } else {
+                            print!("   ");
+                        }
+                    }
+                    println!("");
+                }
+                println!("{} devices found", found.len());
+            }
+            Err(error) => println!("Scan failed: {:?}", error),
+        }
+
+        delay.delay_ms(5000u32);
+    }
+}
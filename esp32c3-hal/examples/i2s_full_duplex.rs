@@ -0,0 +1,132 @@
+//! Sends and receives at the same time on one I2S with shared clocks
+//!
+//! Connect DOUT to DIN. TX sends stereo frames with a counter, RX records
+//! them on the clocks of TX. The counter shows which sent frame every
+//! received frame belongs to, the distance between them has to be the same
+//! in every run since both directions are started together.
+//!
+//! Pins used
+//! MCLK    GPIO4
+//! BCLK    GPIO1
+//! WS      GPIO2
+//! DOUT    GPIO3
+//! DIN     GPIO5
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    dma::DmaPriority,
+    gdma::Gdma,
+    i2s::{DataFormat, I2s, I2s0New, MclkPin, PinsBclkWsDoutDin, Standard},
+    init,
+    pac::Peripherals,
+    prelude::*,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const TX_FRAMES: usize = 1000;
+const RX_FRAMES: usize = 500;
+const RUNS: usize = 10;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let dma = Gdma::new(peripherals.DMA, &mut hal.peripheral_clock_control);
+    let dma_channel = dma.channel0;
+
+    let mut tx_descriptors = [0u32; 8 * 3];
+    let mut rx_descriptors = [0u32; 8 * 3];
+
+    let i2s = I2s::new(
+        peripherals.I2S,
+        MclkPin {
+            mclk: hal.io.pins.gpio4,
+        },
+        Standard::Philips,
+        DataFormat::Data16Channel16,
+        44100u32.Hz(),
+        dma_channel.configure(
+            false,
+            &mut tx_descriptors,
+            &mut rx_descriptors,
+            DmaPriority::Priority0,
+        ),
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    );
+
+    let (mut tx, mut rx) = i2s.split_full_duplex(PinsBclkWsDoutDin {
+        bclk: hal.io.pins.gpio1,
+        ws: hal.io.pins.gpio2,
+        dout: hal.io.pins.gpio3,
+        din: hal.io.pins.gpio5,
+    });
+
+    let (mut tx_buffer, mut rx_buffer) = dma_buffers();
+
+    // left carries the frame number, right its complement, frame numbers
+    // start at 1 so silence can't be mistaken for a frame
+    for (i, frame) in tx_buffer.chunks_exact_mut(4).enumerate() {
+        let number = i as u16 + 1;
+        frame[0..2].copy_from_slice(&number.to_le_bytes());
+        frame[2..4].copy_from_slice(&(!number).to_le_bytes());
+    }
+
+    let mut expected = None;
+    let mut passed = true;
+
+    for run in 0..RUNS {
+        rx_buffer.fill(0);
+
+        let (tx_transfer, rx_transfer) = tx.transfer_dma(tx_buffer, rx, rx_buffer).unwrap();
+        (rx_buffer, rx) = rx_transfer.wait();
+        (tx_buffer, tx) = tx_transfer.wait();
+
+        let offset = alignment(&rx_buffer[..]);
+        println!("run {}: {:?}", run, offset);
+
+        match (offset, expected) {
+            (None, _) => passed = false,
+            (Some(_), None) => expected = offset,
+            (Some(offset), Some(expected)) => passed &= offset == expected,
+        }
+    }
+
+    println!("{}", if passed { "PASS" } else { "FAIL" });
+
+    loop {}
+}
+
+/// The number of received frames before the first sent frame, `None` if the
+/// received frames are not the sent ones in order
+fn alignment(rx_buffer: &[u8]) -> Option<i32> {
+    let mut offset = None;
+
+    for (i, frame) in rx_buffer.chunks_exact(4).enumerate() {
+        let left = u16::from_le_bytes([frame[0], frame[1]]);
+        let right = u16::from_le_bytes([frame[2], frame[3]]);
+
+        match offset {
+            None if left == 0 && right == 0 => continue,
+            None if right == !left => offset = Some(i as i32 - (left as i32 - 1)),
+            Some(offset) if left as i32 == i as i32 - offset + 1 && right == !left => continue,
+            _ => return None,
+        }
+    }
+
+    offset
+}
+
+fn dma_buffers() -> (
+    &'static mut [u8; TX_FRAMES * 4],
+    &'static mut [u8; RX_FRAMES * 4],
+) {
+    static mut TX_BUFFER: [u8; TX_FRAMES * 4] = [0u8; TX_FRAMES * 4];
+    static mut RX_BUFFER: [u8; RX_FRAMES * 4] = [0u8; RX_FRAMES * 4];
+    unsafe { (&mut TX_BUFFER, &mut RX_BUFFER) }
+}
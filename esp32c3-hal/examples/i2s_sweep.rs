@@ -0,0 +1,144 @@
+//! Sweeps a sine tone from 200 Hz to 2 kHz and back via I2S without clicks
+//!
+//! The samples are generated with a phase accumulator, changing the frequency
+//! only changes the phase increment so the waveform stays continuous. The
+//! frequency is stepped whenever the DMA finished sending a descriptor, the
+//! freed space is refilled with samples of the new frequency.
+//!
+//! Pins used
+//! MCLK    GPIO4
+//! BCLK    GPIO1
+//! WS      GPIO2
+//! DOUT    GPIO3
+//!
+//! Connect e.g. a PCM510x like in the `i2s_sound` example and turn down the
+//! volume before running this example.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    dma::DmaPriority,
+    gdma::Gdma,
+    i2s::{DataFormat, I2s, I2s0New, I2sWriteDma, MclkPin, PinsBclkWsDout, Standard},
+    init,
+    pac::Peripherals,
+    prelude::*,
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+const SAMPLE_RATE: u32 = 44100;
+const MIN_FREQUENCY: u32 = 200;
+const MAX_FREQUENCY: u32 = 2000;
+const STEP: u32 = 20;
+
+const SINE: [i16; 64] = [
+    0, 3211, 6392, 9511, 12539, 15446, 18204, 20787, 23169, 25329, 27244, 28897, 30272, 31356,
+    32137, 32609, 32767, 32609, 32137, 31356, 30272, 28897, 27244, 25329, 23169, 20787, 18204,
+    15446, 12539, 9511, 6392, 3211, 0, -3211, -6392, -9511, -12539, -15446, -18204, -20787, -23169,
+    -25329, -27244, -28897, -30272, -31356, -32137, -32609, -32767, -32609, -32137, -31356, -30272,
+    -28897, -27244, -25329, -23169, -20787, -18204, -15446, -12539, -9511, -6392, -3211,
+];
+
+struct Oscillator {
+    phase: u32,
+    increment: u32,
+}
+
+impl Oscillator {
+    fn set_frequency(&mut self, frequency: u32) {
+        self.increment = (((frequency as u64) << 32) / SAMPLE_RATE as u64) as u32;
+    }
+
+    /// Fill `buffer` with stereo frames of 16 bit samples
+    fn fill(&mut self, buffer: &mut [u8]) {
+        for frame in buffer.chunks_exact_mut(4) {
+            let sample = SINE[(self.phase >> 26) as usize].to_le_bytes();
+            frame[0..2].copy_from_slice(&sample);
+            frame[2..4].copy_from_slice(&sample);
+            self.phase = self.phase.wrapping_add(self.increment);
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let dma = Gdma::new(peripherals.DMA, &mut hal.peripheral_clock_control);
+    let dma_channel = dma.channel0;
+
+    let mut tx_descriptors = [0u32; 20 * 3];
+    let mut rx_descriptors = [0u32; 8 * 3];
+
+    let i2s = I2s::new(
+        peripherals.I2S,
+        MclkPin {
+            mclk: hal.io.pins.gpio4,
+        },
+        Standard::Philips,
+        DataFormat::Data16Channel16,
+        SAMPLE_RATE.Hz(),
+        dma_channel.configure(
+            false,
+            &mut tx_descriptors,
+            &mut rx_descriptors,
+            DmaPriority::Priority0,
+        ),
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    );
+
+    let i2s_tx = i2s.i2s_tx.with_pins(PinsBclkWsDout {
+        bclk: hal.io.pins.gpio1,
+        ws: hal.io.pins.gpio2,
+        dout: hal.io.pins.gpio3,
+    });
+
+    let mut oscillator = Oscillator {
+        phase: 0,
+        increment: 0,
+    };
+    let mut frequency = MIN_FREQUENCY;
+    let mut rising = true;
+    oscillator.set_frequency(frequency);
+
+    let buffer = dma_buffer();
+    oscillator.fill(buffer);
+
+    let mut filler = [0u8; 4096];
+
+    let mut transfer = i2s_tx.write_dma_circular(buffer).unwrap();
+    loop {
+        if !transfer.is_tx_done() {
+            continue;
+        }
+        transfer.clear_tx_done();
+
+        if rising && frequency >= MAX_FREQUENCY {
+            rising = false;
+        } else if !rising && frequency <= MIN_FREQUENCY {
+            rising = true;
+        }
+        frequency = if rising {
+            frequency + STEP
+        } else {
+            frequency - STEP
+        };
+        oscillator.set_frequency(frequency);
+
+        // Only whole frames, the phase continues where the last push ended
+        let avail = usize::min(filler.len(), transfer.available()) & !3;
+        if avail > 0 {
+            oscillator.fill(&mut filler[..avail]);
+            transfer.push(&filler[..avail]).unwrap();
+        }
+    }
+}
+
+fn dma_buffer() -> &'static mut [u8; 32000] {
+    static mut BUFFER: [u8; 32000] = [0u8; 32000];
+    unsafe { &mut BUFFER }
+}
@@ -0,0 +1,160 @@
+//! Sends NEC and RC5 frames with an IR LED and decodes them from an IR
+//! receiver
+//!
+//! Connect an IR LED (with a transistor and series resistor) to GPIO4 and the
+//! output of a 38 kHz IR receiver module (e.g. TSOP38238) to GPIO5, and point
+//! the LED at the receiver. The receiver output is captured by polling the pin
+//! while the RMT sends the frame in the background.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    gpio::{Gpio5, Input, PullUp},
+    init,
+    pac::Peripherals,
+    prelude::*,
+    pulse_control::{
+        ir::{self, NecDecoder, NecEncoder, NecFrame, Rc5Command, Rc5Decoder, Rc5Encoder},
+        ClockSource,
+        OutputChannel,
+        PulseCode,
+        RepeatMode,
+        SyncChannel,
+    },
+    systimer::SystemTimer,
+    Delay,
+    PulseControl,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use nb::block;
+use riscv_rt::entry;
+
+const TICKS_PER_US: u64 = SystemTimer::TICKS_PER_SECOND / 1_000_000;
+/// Longer than any space within a frame
+const IDLE_US: u32 = 10_000;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let pulse = PulseControl::new(
+        peripherals.RMT,
+        &mut hal.peripheral_clock_control,
+        ClockSource::APB,
+        0,
+        0,
+        0,
+    )
+    .unwrap();
+
+    let mut rmt_channel0 = pulse.channel0;
+    ir::configure(&mut rmt_channel0, hal.clocks.apb_clock);
+    let mut transmitter = rmt_channel0.assign_pin(hal.io.pins.gpio4);
+
+    let receiver = hal.io.pins.gpio5.into_pull_up_input();
+    let mut delay = Delay::new(&hal.clocks);
+
+    let mut command = 0u8;
+    let mut toggle = false;
+    let mut pulses = [PulseCode::from(0); 48];
+
+    loop {
+        let frame = NecEncoder.encode(0x04, command).map(u32::from);
+        let count = send_and_capture(&mut transmitter, &frame, &receiver, &mut pulses);
+        match NecDecoder::new().decode(&pulses[..count]) {
+            Ok(NecFrame::Command(received)) => println!("NEC {:?}", received),
+            Ok(NecFrame::Repeat) => println!("NEC unexpected repeat"),
+            Err(error) => println!("NEC error {:?}", error),
+        }
+
+        delay.delay_ms(40u32);
+
+        let frame = NecEncoder.encode_repeat().map(u32::from);
+        let count = send_and_capture(&mut transmitter, &frame, &receiver, &mut pulses);
+        match NecDecoder::new().decode(&pulses[..count]) {
+            Ok(NecFrame::Repeat) => println!("NEC repeat"),
+            Ok(NecFrame::Command(received)) => println!("NEC unexpected {:?}", received),
+            Err(error) => println!("NEC error {:?}", error),
+        }
+
+        let sent = Rc5Command {
+            address: 0x05,
+            command: command & 0x7f,
+            toggle,
+        };
+        let frame = Rc5Encoder.encode(sent).map(u32::from);
+        let count = send_and_capture(&mut transmitter, &frame, &receiver, &mut pulses);
+        match Rc5Decoder::new().decode(&pulses[..count]) {
+            Ok(received) if received == sent => println!("RC5 {:?}", received),
+            Ok(received) => println!("RC5 mismatch, sent {:?} got {:?}", sent, received),
+            Err(error) => println!("RC5 error {:?}", error),
+        }
+
+        command = command.wrapping_add(1);
+        toggle = !toggle;
+        delay.delay_ms(500u32);
+    }
+}
+
+/// Start sending `frame` and record the receiver output (low while it sees
+/// the carrier) until it stays idle, returns the number of captured pulses
+fn send_and_capture<C: SyncChannel>(
+    transmitter: &mut C,
+    frame: &[u32],
+    receiver: &Gpio5<Input<PullUp>>,
+    pulses: &mut [PulseCode],
+) -> usize {
+    let mut sequence = frame.iter();
+    transmitter
+        .prepare_transmission(RepeatMode::SingleShot, &mut sequence)
+        .unwrap();
+    transmitter.start_transmission();
+
+    let mut count = 0;
+    let mut in_mark = false;
+    let mut mark = 0;
+    let mut edge = SystemTimer::now();
+
+    while count < pulses.len() {
+        let level = receiver.is_low().unwrap();
+        let now = SystemTimer::now();
+        let elapsed = ((now - edge) / TICKS_PER_US) as u32;
+
+        if level == in_mark {
+            if elapsed > IDLE_US {
+                if !in_mark && mark > 0 {
+                    pulses[count] = pulse(mark, 0);
+                    count += 1;
+                }
+                break;
+            }
+            continue;
+        }
+
+        if in_mark {
+            mark = elapsed;
+        } else if mark > 0 {
+            pulses[count] = pulse(mark, elapsed);
+            count += 1;
+        }
+
+        in_mark = level;
+        edge = now;
+    }
+
+    block!(transmitter.poll_transmission(&mut sequence)).unwrap();
+
+    count
+}
+
+fn pulse(mark: u32, space: u32) -> PulseCode {
+    PulseCode {
+        level1: true,
+        length1: mark.nanos(),
+        level2: false,
+        length2: space.nanos(),
+    }
+}
@@ -0,0 +1,59 @@
+//! Mirrors an LEDC channel to a second pin
+//!
+//! The channel drives GPIO4, the GPIO matrix connects the same signal to
+//! GPIO5. Both pins show the identical 24 kHz / 10% duty signal on a scope.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    init,
+    ledc::{
+        channel::{self, ChannelIFace},
+        timer::{self, TimerIFace},
+        LSGlobalClkSource,
+        LowSpeed,
+        LEDC,
+    },
+    pac::Peripherals,
+    prelude::*,
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let led = hal.io.pins.gpio4.into_push_pull_output();
+    let mut mirror = hal.io.pins.gpio5.into_push_pull_output();
+
+    let mut ledc = LEDC::new(
+        peripherals.LEDC,
+        &hal.clocks,
+        &mut hal.peripheral_clock_control,
+    );
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+    let mut lstimer0 = ledc.get_timer::<LowSpeed>(timer::Number::Timer2);
+
+    lstimer0
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty5Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: 24u32.kHz(),
+        })
+        .unwrap();
+
+    let mut channel0 = ledc.get_channel(channel::Number::Channel0, led);
+    channel0
+        .configure(channel::config::Config {
+            timer: &lstimer0,
+            duty_pct: 10,
+        })
+        .unwrap();
+
+    channel0.mirror_to(&mut mirror);
+
+    loop {}
+}
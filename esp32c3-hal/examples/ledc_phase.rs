@@ -0,0 +1,93 @@
+//! Two LEDC channels 180° apart
+//!
+//! Channel 0 drives GPIO4, channel 1 drives GPIO5, both at 1 kHz with 50%
+//! duty from the same timer. Channel 1 is shifted by half a period, so
+//! exactly one of the outputs is high at any time.
+//!
+//! As a self-check, both outputs are read back through their input buffers
+//! and their XOR is driven onto GPIO6, which should be high but for short
+//! glitches at the edges. The share of samples in which the outputs differ
+//! is printed, it should be close to 100%.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    init,
+    ledc::{
+        channel::{self, ChannelIFace},
+        timer::{self, TimerIFace},
+        LSGlobalClkSource,
+        LowSpeed,
+        LEDC,
+    },
+    pac::Peripherals,
+    prelude::*,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const SAMPLES: u32 = 100_000;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let phase0 = hal.io.pins.gpio4.into_push_pull_output();
+    let phase1 = hal.io.pins.gpio5.into_push_pull_output();
+    let mut xor = hal.io.pins.gpio6.into_push_pull_output();
+
+    let mut ledc = LEDC::new(
+        peripherals.LEDC,
+        &hal.clocks,
+        &mut hal.peripheral_clock_control,
+    );
+    ledc.set_global_slow_clock(LSGlobalClkSource::APBClk);
+    let mut lstimer0 = ledc.get_timer::<LowSpeed>(timer::Number::Timer0);
+
+    lstimer0
+        .configure(timer::config::Config {
+            duty: timer::config::Duty::Duty10Bit,
+            clock_source: timer::LSClockSource::APBClk,
+            frequency: 1u32.kHz(),
+        })
+        .unwrap();
+
+    let mut channel0 = ledc.get_channel(channel::Number::Channel0, phase0);
+    channel0
+        .configure(channel::config::Config {
+            timer: &lstimer0,
+            duty_pct: 50,
+        })
+        .unwrap();
+
+    let mut channel1 = ledc.get_channel(channel::Number::Channel1, phase1);
+    channel1
+        .configure(channel::config::Config {
+            timer: &lstimer0,
+            duty_pct: 50,
+        })
+        .unwrap();
+    channel1.set_phase(180).unwrap();
+
+    channel0.output_pin().enable_input(true);
+    channel1.output_pin().enable_input(true);
+
+    loop {
+        let mut differing = 0;
+        for _ in 0..SAMPLES {
+            let high0 = channel0.output_pin().is_input_high();
+            let high1 = channel1.output_pin().is_input_high();
+            xor.set_output_high(high0 != high1);
+            differing += (high0 != high1) as u32;
+        }
+
+        println!(
+            "Outputs differ in {}.{:02}% of the samples",
+            differing * 100 / SAMPLES,
+            differing * 10_000 / SAMPLES % 100
+        );
+    }
+}
@@ -0,0 +1,82 @@
+//! Logs from the main loop and from a timer interrupt through the HAL logger
+//!
+//! The logger owns UART0 and drains its buffer in the low priority `UART0`
+//! interrupt. The `TG0_T0_LEVEL` interrupt preempts the main loop every 7 ms
+//! and logs as well. Every line on the console is complete, the records of
+//! the main loop and the interrupt never mix within a line.
+
+#![no_std]
+#![no_main]
+
+use core::{
+    cell::RefCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+use critical_section::Mutex;
+use esp32c3_hal::{
+    init,
+    interrupt,
+    logger,
+    pac::{self, Peripherals, TIMG0},
+    prelude::*,
+    timer::{Timer, Timer0},
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+static TIMER0: Mutex<RefCell<Option<Timer<Timer0<TIMG0>>>>> = Mutex::new(RefCell::new(None));
+static TICKS: AtomicU32 = AtomicU32::new(0);
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    logger::init_uart0(peripherals.UART0, &hal.clocks, log::LevelFilter::Info);
+    interrupt::enable(pac::Interrupt::UART0, interrupt::Priority::Priority1).unwrap();
+
+    let mut timer0 = hal.timer_group0.timer0;
+    timer0.start(7u64.millis());
+    timer0.listen();
+    critical_section::with(|cs| TIMER0.borrow_ref_mut(cs).replace(timer0));
+    interrupt::enable(pac::Interrupt::TG0_T0_LEVEL, interrupt::Priority::Priority2).unwrap();
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    let mut iteration = 0u32;
+
+    loop {
+        log::info!(
+            "main loop iteration {}, {} timer interrupts so far",
+            iteration,
+            TICKS.load(Ordering::Relaxed)
+        );
+        iteration += 1;
+
+        if iteration % 1000 == 0 {
+            log::warn!("{} bytes dropped", logger::dropped());
+        }
+    }
+}
+
+#[interrupt]
+fn UART0() {
+    logger::handle_interrupt();
+}
+
+#[interrupt]
+fn TG0_T0_LEVEL() {
+    let tick = TICKS.fetch_add(1, Ordering::Relaxed);
+    log::info!("timer interrupt {}", tick);
+
+    critical_section::with(|cs| {
+        let mut timer0 = TIMER0.borrow_ref_mut(cs);
+        let timer0 = timer0.as_mut().unwrap();
+
+        timer0.clear_interrupt();
+        timer0.start(7u64.millis());
+    });
+}
@@ -0,0 +1,153 @@
+//! Answers Modbus RTU "read holding registers" requests
+//!
+//! Connect an RS-485 transceiver to UART1: DI to GPIO1, RO to GPIO2 and the
+//! joined DE and /RE to GPIO3. The slave has the address 1 and ten holding
+//! registers: register 0 counts the valid requests, register 1 the frames
+//! dropped because of a wrong CRC or length, the others hold `100 * n`.
+//!
+//! The bus runs at 19200 baud, 8 data bits, even parity and one stop bit. A
+//! frame ends after 2.5 characters of silence, between the 1.5 characters
+//! allowed within and the 3.5 characters required between frames. Try it
+//! with any Modbus master, e.g. mbpoll:
+//!
+//! ```text
+//! mbpoll -a 1 -r 1 -c 10 -b 19200 -P even /dev/ttyUSB0
+//! ```
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    init,
+    pac::Peripherals,
+    prelude::*,
+    serial::{
+        config::{Config, Parity},
+        Error,
+        TxRxPins,
+    },
+    Serial,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const ADDRESS: u8 = 1;
+const READ_HOLDING_REGISTERS: u8 = 3;
+
+const ILLEGAL_FUNCTION: u8 = 1;
+const ILLEGAL_DATA_ADDRESS: u8 = 2;
+const ILLEGAL_DATA_VALUE: u8 = 3;
+
+/// Start bit, 8 data bits, parity and stop bit
+const CHARACTER_BITS: u16 = 11;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let config = Config {
+        baudrate: 19_200,
+        parity: Parity::ParityEven,
+        ..Config::default()
+    };
+    let pins = TxRxPins::new_tx_rx(
+        hal.io.pins.gpio1.into_push_pull_output(),
+        hal.io.pins.gpio2.into_floating_input(),
+    );
+    let mut serial =
+        Serial::new_with_config(peripherals.UART1, Some(config), Some(pins), &hal.clocks);
+    serial.set_rx_idle_bits(CHARACTER_BITS * 5 / 2);
+
+    let mut driver_enable = hal.io.pins.gpio3.into_push_pull_output();
+    driver_enable.set_low().unwrap();
+
+    let mut registers = [0u16; 10];
+    for (n, register) in registers.iter_mut().enumerate().skip(2) {
+        *register = 100 * n as u16;
+    }
+
+    let mut request = [0u8; 256];
+    let mut response = [0u8; 256];
+
+    loop {
+        let len = match serial.read_frame(&mut request) {
+            Ok(len) => len,
+            Err(Error::FrameTooLong { .. }) => {
+                registers[1] = registers[1].wrapping_add(1);
+                continue;
+            }
+            Err(error) => panic!("{:?}", error),
+        };
+
+        let request = &request[..len];
+        if len < 4 || crc16(&request[..len - 2]).to_le_bytes() != request[len - 2..] {
+            registers[1] = registers[1].wrapping_add(1);
+            continue;
+        }
+        if request[0] != ADDRESS {
+            continue;
+        }
+
+        registers[0] = registers[0].wrapping_add(1);
+        let len = answer(&request[..len - 2], &registers, &mut response);
+        let crc = crc16(&response[..len]).to_le_bytes();
+        response[len..len + 2].copy_from_slice(&crc);
+
+        driver_enable.set_high().unwrap();
+        serial.write_bytes(&response[..len + 2]).unwrap();
+        nb::block!(serial.flush_nb()).unwrap();
+        driver_enable.set_low().unwrap();
+
+        println!("answered {:02x?}", request);
+    }
+}
+
+/// Write the answer to `request` (without the CRC) into `response` and
+/// return its length without the CRC
+fn answer(request: &[u8], registers: &[u16], response: &mut [u8]) -> usize {
+    response[0] = ADDRESS;
+    response[1] = request[1];
+
+    let exception = match request[1] {
+        READ_HOLDING_REGISTERS if request.len() == 6 => {
+            let start = u16::from_be_bytes([request[2], request[3]]) as usize;
+            let count = u16::from_be_bytes([request[4], request[5]]) as usize;
+
+            if !(1..=125).contains(&count) {
+                ILLEGAL_DATA_VALUE
+            } else if start + count > registers.len() {
+                ILLEGAL_DATA_ADDRESS
+            } else {
+                response[2] = (2 * count) as u8;
+                for (n, register) in registers[start..start + count].iter().enumerate() {
+                    response[3 + 2 * n..5 + 2 * n].copy_from_slice(&register.to_be_bytes());
+                }
+                return 3 + 2 * count;
+            }
+        }
+        READ_HOLDING_REGISTERS => ILLEGAL_DATA_VALUE,
+        _ => ILLEGAL_FUNCTION,
+    };
+
+    response[1] |= 0x80;
+    response[2] = exception;
+    3
+}
+
+/// CRC-16/MODBUS
+fn crc16(data: &[u8]) -> u16 {
+    let mut crc = 0xffff;
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xa001
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    crc
+}
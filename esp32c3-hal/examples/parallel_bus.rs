@@ -0,0 +1,103 @@
+//! Bus cycles on an 8-bit parallel bus, to be checked with a logic analyzer
+//!
+//! The following pins are used:
+//! - data bits 0 to 7 => GPIO0 to GPIO7
+//! - WR => GPIO8
+//! - RD => GPIO9
+//! - ALE => GPIO10
+//!
+//! Every second the bus writes `0x5a` to address `0x12` and reads address
+//! `0x12` with each of the timings below, one timing after the other. The
+//! widths on the analyzer have to match the printed number of CPU cycles
+//! within a cycle.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    gpio::{
+        dedicated::DedicatedGpio,
+        parallel_bus::{BusTiming, Control, ParallelBus8},
+    },
+    init,
+    pac::Peripherals,
+    prelude::*,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+    let mut delay = Delay::new(&hal.clocks);
+    let cpu_hz = hal.clocks.cpu_clock.to_Hz();
+
+    let timings = [
+        BusTiming::default(),
+        BusTiming {
+            setup: 100u32.nanos(),
+            strobe: 250u32.nanos(),
+            hold: 75u32.nanos(),
+            ..BusTiming::default()
+        },
+        BusTiming {
+            address_setup: 0u32.nanos(),
+            address_hold: 0u32.nanos(),
+            setup: 0u32.nanos(),
+            strobe: 0u32.nanos(),
+            hold: 0u32.nanos(),
+        },
+    ];
+
+    let dedicated = DedicatedGpio::new(&mut hal.peripheral_clock_control);
+    let mut bus = ParallelBus8::new(dedicated, timings[0], &hal.clocks).unwrap();
+
+    let mut pins = hal.io.pins;
+    bus.connect_data(0, &mut pins.gpio0);
+    bus.connect_data(1, &mut pins.gpio1);
+    bus.connect_data(2, &mut pins.gpio2);
+    bus.connect_data(3, &mut pins.gpio3);
+    bus.connect_data(4, &mut pins.gpio4);
+    bus.connect_data(5, &mut pins.gpio5);
+    bus.connect_data(6, &mut pins.gpio6);
+    bus.connect_data(7, &mut pins.gpio7);
+    bus.connect_control(Control::Wr, &mut pins.gpio8);
+    bus.connect_control(Control::Rd, &mut pins.gpio9);
+    bus.connect_control(Control::Ale, &mut pins.gpio10);
+
+    println!(
+        "CPU clock {} MHz, a phase takes at least {} cycles",
+        cpu_hz / 1_000_000,
+        bus.phase_overhead_cycles()
+    );
+
+    let overhead = bus.phase_overhead_cycles();
+    for timing in timings.iter() {
+        println!(
+            "setup {} / strobe {} / hold {} cycles",
+            to_cycles(timing.setup.ticks(), cpu_hz, overhead),
+            to_cycles(timing.strobe.ticks(), cpu_hz, overhead),
+            to_cycles(timing.hold.ticks(), cpu_hz, overhead)
+        );
+    }
+
+    loop {
+        for timing in timings.iter() {
+            bus.set_timing(*timing).unwrap();
+            bus.write_cycle(0x12, 0x5a).unwrap();
+            let value = bus.read_cycle(0x12).unwrap();
+            println!("read 0x{:02x}", value);
+        }
+
+        delay.delay_ms(1000u32);
+    }
+}
+
+/// `ns` in CPU cycles, rounded up like the bus does, 0 is the shortest phase
+fn to_cycles(ns: u32, cpu_hz: u32, overhead: u32) -> u32 {
+    let cycles = ((ns as u64 * cpu_hz as u64 + 999_999_999) / 1_000_000_000) as u32;
+    cycles.max(overhead)
+}
@@ -5,6 +5,7 @@
 #![no_main]
 
 use esp32c3_hal::{
+    chip,
     clock::ClockControl,
     efuse::Efuse,
     pac::Peripherals,
@@ -34,6 +35,8 @@ fn main() -> ! {
     wdt0.disable();
     wdt1.disable();
 
+    println!("Chip {}", chip::info());
+    println!("Features {:?}", chip::features());
     println!("MAC address {:02x?}", Efuse::get_mac_address());
     println!("Flash Encryption {:?}", Efuse::get_flash_encryption());
 
@@ -0,0 +1,27 @@
+//! Keeps a boot counter in a retention register
+//!
+//! The counter survives the software reset triggered every two seconds, it is
+//! only cleared by power-cycling the board.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{init, pac::Peripherals, prelude::*, rtc_cntl::RetentionRegister, Delay};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let boots = hal.rtc.load(RetentionRegister::Store0) + 1;
+    hal.rtc.store(RetentionRegister::Store0, boots);
+    println!("Boot number {}", boots);
+
+    let mut delay = Delay::new(&hal.clocks);
+    delay.delay_ms(2000u32);
+
+    hal.rtc.software_reset();
+}
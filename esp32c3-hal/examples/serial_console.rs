@@ -0,0 +1,64 @@
+//! Shares UART0 between `esp-println` and the UART driver.
+//!
+//! A UART0 driver adopting the console configuration is created, used and
+//! released again in turns with `esp-println`. Releasing the driver restores
+//! the console configuration, so all lines show up without garbage.
+//! You can see the output with `espflash` if you provide the `--monitor` option
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32c3_hal::{
+    clock::ClockControl,
+    pac::Peripherals,
+    prelude::*,
+    serial::config::StopBits,
+    timer::TimerGroup,
+    Rtc,
+    Serial,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use nb::block;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let mut timer0 = timer_group0.timer0;
+    timer0.start(1u64.secs());
+
+    let mut uart0 = peripherals.UART0;
+
+    loop {
+        println!("Printed by esp-println");
+
+        let mut console = Serial::new_console(uart0, &clocks);
+        writeln!(console, "Printed by the UART0 driver").unwrap();
+
+        // changed settings are reverted when the driver is released
+        console.change_stop_bits(StopBits::STOP2);
+        uart0 = console.free();
+
+        println!("Printed by esp-println again");
+
+        block!(timer0.wait()).unwrap();
+    }
+}
@@ -0,0 +1,98 @@
+//! Interactive command prompt on serial0.
+//!
+//! Received bytes are echoed back and collected into lines using
+//! `LineReader`. Backspace can be used to edit the current line. Type `help`
+//! to get a list of the available commands.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32c3_hal::{
+    clock::ClockControl,
+    pac::Peripherals,
+    prelude::*,
+    serial::{LineReader, LineReaderError},
+    timer::TimerGroup,
+    Rtc,
+    Serial,
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt0 = timer_group0.wdt;
+    let timer_group1 = TimerGroup::new(peripherals.TIMG1, &clocks);
+    let mut wdt1 = timer_group1.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable watchdog timers
+    rtc.swd.disable();
+    rtc.rwdt.disable();
+    wdt0.disable();
+    wdt1.disable();
+
+    let mut serial0 = Serial::new(peripherals.UART0);
+    let mut reader = LineReader::<32>::new().with_backspace(true);
+    let mut counter = 0u32;
+
+    write!(serial0, "> ").ok();
+
+    loop {
+        let byte = match serial0.read() {
+            Ok(byte) => byte,
+            Err(_) => continue,
+        };
+
+        // echo the input, erasing the previous character on backspace as long
+        // as there is one, so the prompt isn't erased
+        match byte {
+            b'\r' | b'\n' => {
+                write!(serial0, "\r\n").ok();
+            }
+            0x08 | 0x7f => {
+                if !reader.buffered().is_empty() {
+                    write!(serial0, "\x08 \x08").ok();
+                }
+            }
+            _ => {
+                serial0.write_bytes(&[byte]).ok();
+            }
+        }
+
+        match reader.push(byte) {
+            Ok(line) => {
+                match line.trim() {
+                    "" => {}
+                    "help" => writeln!(serial0, "commands: help, count, reset\r").unwrap(),
+                    "count" => {
+                        counter += 1;
+                        writeln!(serial0, "counter = {}\r", counter).unwrap();
+                    }
+                    "reset" => {
+                        counter = 0;
+                        writeln!(serial0, "counter reset\r").unwrap();
+                    }
+                    other => writeln!(serial0, "unknown command `{}`\r", other).unwrap(),
+                }
+                write!(serial0, "> ").ok();
+            }
+            Err(nb::Error::Other(LineReaderError::Overflow)) => {
+                writeln!(serial0, "line too long\r").unwrap();
+                write!(serial0, "> ").ok();
+            }
+            Err(nb::Error::Other(LineReaderError::InvalidUtf8)) => {
+                writeln!(serial0, "invalid UTF-8\r").unwrap();
+                write!(serial0, "> ").ok();
+            }
+            Err(_) => {}
+        }
+    }
+}
@@ -0,0 +1,81 @@
+//! Splits the console UART into an RX half owned by the interrupt handler
+//! and a TX half owned by the main loop
+//!
+//! The UART0 interrupt only reads, it counts the received bytes and keeps the
+//! last one. The main loop only writes, it reports both every second. Type
+//! into a serial terminal connected to the console to see the count rise.
+
+#![no_std]
+#![no_main]
+
+use core::{
+    cell::RefCell,
+    fmt::Write,
+    sync::atomic::{AtomicU32, AtomicU8, Ordering},
+};
+
+use critical_section::Mutex;
+use esp32c3_hal::{
+    init,
+    interrupt,
+    pac::{self, Peripherals, UART0},
+    prelude::*,
+    serial::Rx,
+    Delay,
+    Serial,
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+static RX: Mutex<RefCell<Option<Rx<UART0>>>> = Mutex::new(RefCell::new(None));
+static RECEIVED: AtomicU32 = AtomicU32::new(0);
+static LAST: AtomicU8 = AtomicU8::new(b' ');
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let serial = Serial::new_console(peripherals.UART0, &hal.clocks);
+    let (mut tx, mut rx) = serial.split();
+
+    rx.set_rx_fifo_full_threshold(1);
+    rx.listen_rx_fifo_full();
+    critical_section::with(|cs| RX.borrow_ref_mut(cs).replace(rx));
+
+    interrupt::enable(pac::Interrupt::UART0, interrupt::Priority::Priority1).unwrap();
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    let mut delay = Delay::new(&hal.clocks);
+
+    loop {
+        writeln!(
+            tx,
+            "{} bytes received, last {:?}",
+            RECEIVED.load(Ordering::Relaxed),
+            LAST.load(Ordering::Relaxed) as char
+        )
+        .unwrap();
+        tx.flush().unwrap();
+
+        delay.delay_ms(1000u32);
+    }
+}
+
+#[interrupt]
+fn UART0() {
+    critical_section::with(|cs| {
+        let mut rx = RX.borrow_ref_mut(cs);
+        let rx = rx.as_mut().unwrap();
+
+        while let Ok(byte) = rx.read_nb() {
+            RECEIVED.fetch_add(1, Ordering::Relaxed);
+            LAST.store(byte, Ordering::Relaxed);
+        }
+
+        rx.reset_rx_fifo_full_interrupt();
+    });
+}
@@ -0,0 +1,214 @@
+//! Renders to an ILI9341 display with and without DMA double buffering
+//!
+//! Folowing pins are used:
+//! SCLK    GPIO6
+//! MOSI    GPIO7
+//! DC      GPIO4
+//! RST     GPIO5
+//!
+//! Tie CS of the display low. A frame of 320x240 RGB565 pixels is rendered
+//! with embedded-graphics in six bands of 40 lines, each band is sent with
+//! DMA at 40 MHz. The example alternates between single buffering (render,
+//! send, wait) and double buffering (render the next band while the
+//! previous one is sent) and prints the frame rate of both, plus the swaps
+//! which found the bus already idle.
+
+#![no_std]
+#![no_main]
+
+use embedded_graphics::{
+    mono_font::{ascii::FONT_10X20, MonoTextStyle},
+    pixelcolor::{IntoStorage, Rgb565},
+    prelude::*,
+    primitives::{Circle, PrimitiveStyle},
+    text::Text,
+};
+use esp32c3_hal::{
+    dma::DmaPriority,
+    gdma::Gdma,
+    init,
+    pac::Peripherals,
+    prelude::*,
+    spi::{
+        dma::{DmaBuffer, DmaDoubleBuffer},
+        Spi,
+        SpiMode,
+    },
+    systimer::SystemTimer,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const WIDTH: usize = 320;
+const HEIGHT: usize = 240;
+const BAND_HEIGHT: usize = 40;
+const BAND_BYTES: usize = WIDTH * BAND_HEIGHT * 2;
+
+static mut BUFFERS: [DmaBuffer<BAND_BYTES>; 2] = [DmaBuffer::new(), DmaBuffer::new()];
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let dma = Gdma::new(peripherals.DMA, &mut hal.peripheral_clock_control);
+    let mut descriptors = [0u32; 8 * 3];
+    let mut rx_descriptors = [0u32; 3];
+
+    let mut spi = Spi::new_no_cs_no_miso(
+        peripherals.SPI2,
+        hal.io.pins.gpio6,
+        hal.io.pins.gpio7,
+        40u32.MHz(),
+        SpiMode::Mode0,
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    )
+    .with_dma(dma.channel0.configure(
+        false,
+        &mut descriptors,
+        &mut rx_descriptors,
+        DmaPriority::Priority0,
+    ));
+
+    let mut dc = hal.io.pins.gpio4.into_push_pull_output();
+    let mut rst = hal.io.pins.gpio5.into_push_pull_output();
+    let mut delay = Delay::new(&hal.clocks);
+
+    rst.set_low().unwrap();
+    delay.delay_ms(10u32);
+    rst.set_high().unwrap();
+    delay.delay_ms(120u32);
+
+    for (command, data) in [
+        (0x01, &[][..]), // software reset
+        (0x11, &[]),     // sleep out
+        (0x3a, &[0x55]), // 16 bit pixels
+        (0x36, &[0x28]), // landscape
+        (0x29, &[]),     // display on
+    ] {
+        dc.set_low().unwrap();
+        spi.write(&[command]).unwrap();
+        dc.set_high().unwrap();
+        if !data.is_empty() {
+            spi.write(data).unwrap();
+        }
+        delay.delay_ms(120u32);
+    }
+
+    let mut frames = DmaDoubleBuffer::new(spi, unsafe { &mut BUFFERS });
+    let mut position = 0;
+
+    loop {
+        for double_buffering in [false, true] {
+            let start = SystemTimer::now();
+            let missed = frames.missed_swaps();
+            let mut count = 0;
+
+            while SystemTimer::now() - start < 3 * SystemTimer::TICKS_PER_SECOND {
+                for top in (0..HEIGHT).step_by(BAND_HEIGHT) {
+                    render(frames.back_mut(), top, position);
+
+                    frames
+                        .swap_with(|spi| {
+                            if top == 0 {
+                                // the frame covers the whole display
+                                dc.set_low().unwrap();
+                                spi.write(&[0x2a]).unwrap();
+                                dc.set_high().unwrap();
+                                spi.write(&[0x00, 0x00, 0x01, 0x3f]).unwrap();
+                                dc.set_low().unwrap();
+                                spi.write(&[0x2b]).unwrap();
+                                dc.set_high().unwrap();
+                                spi.write(&[0x00, 0x00, 0x00, 0xef]).unwrap();
+                                dc.set_low().unwrap();
+                                spi.write(&[0x2c]).unwrap();
+                                dc.set_high().unwrap();
+                            }
+                            Ok(())
+                        })
+                        .unwrap();
+
+                    if !double_buffering {
+                        frames.flush();
+                    }
+                }
+
+                position = (position + 4) % WIDTH as i32;
+                count += 1;
+            }
+
+            println!(
+                "{} buffering: {} frames/s, {} swaps found the bus idle",
+                if double_buffering { "double" } else { "single" },
+                count / 3,
+                frames.missed_swaps() - missed
+            );
+        }
+    }
+}
+
+/// Render the band starting at line `top` of the scene
+fn render(buffer: &mut [u8], top: usize, position: i32) {
+    let mut band = Band {
+        buffer,
+        top: top as i32,
+    };
+
+    band.clear(Rgb565::BLACK).unwrap();
+    Circle::new(Point::new(position - 40, 80), 80)
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::YELLOW))
+        .draw(&mut band)
+        .unwrap();
+    Text::new(
+        "DMA double buffering",
+        Point::new(60, 200),
+        MonoTextStyle::new(&FONT_10X20, Rgb565::WHITE),
+    )
+    .draw(&mut band)
+    .unwrap();
+}
+
+/// A horizontal band of the display, drawing outside of it is clipped
+struct Band<'a> {
+    buffer: &'a mut [u8],
+    top: i32,
+}
+
+impl OriginDimensions for Band<'_> {
+    fn size(&self) -> Size {
+        Size::new(WIDTH as u32, HEIGHT as u32)
+    }
+}
+
+impl DrawTarget for Band<'_> {
+    type Color = Rgb565;
+    type Error = core::convert::Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            let row = point.y - self.top;
+            if (0..BAND_HEIGHT as i32).contains(&row) && (0..WIDTH as i32).contains(&point.x) {
+                let index = (row as usize * WIDTH + point.x as usize) * 2;
+                self.buffer[index..index + 2].copy_from_slice(&color.into_storage().to_be_bytes());
+            }
+        }
+
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Self::Color) -> Result<(), Self::Error> {
+        let [high, low] = color.into_storage().to_be_bytes();
+        for pixel in self.buffer.chunks_exact_mut(2) {
+            pixel[0] = high;
+            pixel[1] = low;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,275 @@
+//! A display and an SD card sharing SPI2
+//!
+//! The following pins are used:
+//! SCLK            GPIO6
+//! MISO            GPIO2
+//! MOSI            GPIO7
+//! Display CS      GPIO10 (CS line 1 of SPI2)
+//! Display DC      GPIO4
+//! SD card CS      GPIO5 (GPIO)
+//!
+//! The display is an ST7789 with 240x240 pixels, driven at 40 MHz in mode 3.
+//! The SD card is initialized at 400 kHz, afterwards it runs at 20 MHz. It
+//! is selected by a GPIO since it needs CS to stay low for a whole command,
+//! while the CS lines of the SPI host go high after each FIFO sized chunk.
+//!
+//! The example fills the display with changing colors and reads the first
+//! block of the SD card in between, checking the boot signature of the MBR.
+
+#![no_std]
+#![no_main]
+
+use embedded_hal_1::spi::{SpiBus, SpiBusFlush, SpiBusWrite, SpiDevice};
+use esp32c3_hal::{
+    ehal::digital::v2::OutputPin,
+    init,
+    pac::Peripherals,
+    prelude::*,
+    spi::{ChipSelect, Config, Spi, SpiBusController, SpiMode},
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const DISPLAY_SIZE: usize = 240;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+    let pins = hal.io.pins;
+    let mut delay = Delay::new(&hal.clocks);
+
+    let mut spi = Spi::new_no_cs(
+        peripherals.SPI2,
+        pins.gpio6,
+        pins.gpio7,
+        pins.gpio2,
+        400u32.kHz(),
+        SpiMode::Mode0,
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    );
+
+    // an SD card wants at least 74 clocks with CS high before the first
+    // command
+    let mut sd_cs = pins.gpio5.into_push_pull_output();
+    sd_cs.set_high().unwrap();
+    SpiBusWrite::write(&mut spi, &[0xff; 10]).unwrap();
+    SpiBusFlush::flush(&mut spi).unwrap();
+
+    let controller = SpiBusController::from_spi(spi);
+    let mut display = controller.add_device_with_config(
+        pins.gpio10,
+        ChipSelect::Cs1,
+        Config {
+            frequency: 40u32.MHz(),
+            mode: SpiMode::Mode3,
+        },
+    );
+    let mut sd_card = controller.add_device_with_config(
+        sd_cs,
+        ChipSelect::Software,
+        Config {
+            frequency: 400u32.kHz(),
+            mode: SpiMode::Mode0,
+        },
+    );
+    let mut dc = pins.gpio4.into_push_pull_output();
+
+    // software reset, sleep out, 16 bit colors, inversion on, display on
+    let init: [(u8, &[u8], u32); 5] = [
+        (0x01, &[], 150),
+        (0x11, &[], 10),
+        (0x3a, &[0x55], 0),
+        (0x21, &[], 0),
+        (0x29, &[], 0),
+    ];
+    for (command, data, wait_ms) in init {
+        display
+            .transaction(|bus| display_command(bus, &mut dc, command, data))
+            .unwrap();
+        delay.delay_ms(wait_ms);
+    }
+
+    let high_capacity = match init_sd_card(&mut sd_card, &mut delay) {
+        Some(high_capacity) => high_capacity,
+        None => panic!("No SD card"),
+    };
+    println!("SD card initialized, high capacity: {}", high_capacity);
+    sd_card.set_config(Config {
+        frequency: 20u32.MHz(),
+        mode: SpiMode::Mode0,
+    });
+
+    let mut color: u16 = 0;
+    let mut block = [0u8; 512];
+    loop {
+        fill_display(&mut display, &mut dc, color);
+        color = color.wrapping_add(0x0841);
+
+        sd_card
+            .transaction(|bus| read_block(bus, 0, &mut block))
+            .unwrap();
+        println!(
+            "Display filled with {:04x}, MBR signature {:02x?}",
+            color,
+            &block[510..]
+        );
+
+        delay.delay_ms(500u32);
+    }
+}
+
+/// Send `command` to the display, followed by `data`
+fn display_command<B, DC>(
+    bus: &mut B,
+    dc: &mut DC,
+    command: u8,
+    data: &[u8],
+) -> Result<(), B::Error>
+where
+    B: SpiBusWrite + SpiBusFlush,
+    DC: OutputPin,
+{
+    // the display samples DC with the last bit of every byte
+    dc.set_low().ok();
+    bus.write(&[command])?;
+    bus.flush()?;
+
+    dc.set_high().ok();
+    bus.write(data)?;
+    bus.flush()
+}
+
+fn fill_display<D, DC>(display: &mut D, dc: &mut DC, color: u16)
+where
+    D: SpiDevice,
+    D::Bus: SpiBusWrite + SpiBusFlush,
+    DC: OutputPin,
+{
+    let end = (DISPLAY_SIZE as u16 - 1).to_be_bytes();
+    let mut line = [0u8; 2 * DISPLAY_SIZE];
+    for pixel in line.chunks_mut(2) {
+        pixel.copy_from_slice(&color.to_be_bytes());
+    }
+
+    display
+        .transaction(|bus| {
+            // whole screen as window, then write the pixels
+            display_command(bus, dc, 0x2a, &[0, 0, end[0], end[1]])?;
+            display_command(bus, dc, 0x2b, &[0, 0, end[0], end[1]])?;
+            display_command(bus, dc, 0x2c, &[])?;
+            for _ in 0..DISPLAY_SIZE {
+                bus.write(&line)?;
+            }
+            bus.flush()
+        })
+        .unwrap();
+}
+
+/// Send a command to the SD card and return its R1 response
+fn sd_command<B>(bus: &mut B, command: u8, argument: u32) -> Result<u8, B::Error>
+where
+    B: SpiBus,
+{
+    let crc = match command {
+        0 => 0x95,
+        8 => 0x87,
+        _ => 0x01,
+    };
+    let argument = argument.to_be_bytes();
+    bus.write(&[
+        0x40 | command,
+        argument[0],
+        argument[1],
+        argument[2],
+        argument[3],
+        crc,
+    ])?;
+
+    // the response follows within 8 bytes, the card reads 0xff meanwhile
+    for _ in 0..8 {
+        let mut response = [0xff];
+        bus.transfer_in_place(&mut response)?;
+        if response[0] & 0x80 == 0 {
+            return Ok(response[0]);
+        }
+    }
+    Ok(0xff)
+}
+
+/// Bring the SD card into SPI mode, returns if it is a high capacity card
+fn init_sd_card<D>(sd_card: &mut D, delay: &mut Delay) -> Option<bool>
+where
+    D: SpiDevice,
+    D::Bus: SpiBus,
+{
+    // GO_IDLE_STATE
+    if sd_card.transaction(|bus| sd_command(bus, 0, 0)).ok()? != 0x01 {
+        return None;
+    }
+
+    // SEND_IF_COND with 3.3 V and the check pattern 0xaa, only version 2
+    // cards know it
+    let version2 = sd_card
+        .transaction(|bus| {
+            let r1 = sd_command(bus, 8, 0x1aa)?;
+            let mut r7 = [0xff; 4];
+            bus.transfer_in_place(&mut r7)?;
+            Ok(r1 == 0x01 && r7[3] == 0xaa)
+        })
+        .ok()?;
+
+    // APP_CMD and SD_SEND_OP_COND until the card left the idle state
+    let argument = if version2 { 1 << 30 } else { 0 };
+    for _ in 0..100 {
+        let r1 = sd_card
+            .transaction(|bus| {
+                sd_command(bus, 55, 0)?;
+                sd_command(bus, 41, argument)
+            })
+            .ok()?;
+        if r1 == 0 {
+            if !version2 {
+                return Some(false);
+            }
+
+            // READ_OCR, bit 30 is the card capacity status
+            let ocr = sd_card
+                .transaction(|bus| {
+                    sd_command(bus, 58, 0)?;
+                    let mut ocr = [0xff; 4];
+                    bus.transfer_in_place(&mut ocr)?;
+                    Ok(ocr)
+                })
+                .ok()?;
+            return Some(ocr[0] & 0x40 != 0);
+        }
+        delay.delay_ms(10u32);
+    }
+
+    None
+}
+
+/// Read the block at `address` (a block number for high capacity cards, a
+/// byte address otherwise) into `block`
+fn read_block<B>(bus: &mut B, address: u32, block: &mut [u8; 512]) -> Result<(), B::Error>
+where
+    B: SpiBus,
+{
+    // READ_SINGLE_BLOCK, then wait for the data token
+    sd_command(bus, 17, address)?;
+    let mut token = [0xff];
+    while token[0] != 0xfe {
+        token[0] = 0xff;
+        bus.transfer_in_place(&mut token)?;
+    }
+
+    block.fill(0xff);
+    bus.transfer_in_place(block)?;
+
+    let mut crc = [0xff; 2];
+    bus.transfer_in_place(&mut crc)
+}
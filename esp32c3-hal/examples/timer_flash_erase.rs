@@ -0,0 +1,110 @@
+//! Toggles GPIO5 from a timer interrupt while a flash sector is erased
+//!
+//! TIMG0 fires every 100 µs and its handler toggles GPIO5. The main loop
+//! erases the last 4 kB sector of a 4 MB flash every second, with the cache
+//! suspended. With the `place-isr-in-ram` feature the whole interrupt path
+//! runs from RAM, so GPIO5 keeps toggling during the erase. An interrupt
+//! path in flash would stall until the cache is resumed, which shows up on a
+//! logic analyzer as a gap as long as the erase.
+//!
+//! Nothing must be stored in the erased sector. Build with `--release`, at
+//! lower optimization levels the helpers of `RefCell` and `Option` used by
+//! the handler might not be inlined and stay in flash.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use esp32c3_hal::{
+    gpio::{Gpio5, Output, PushPull},
+    init,
+    interrupt,
+    macros::ram,
+    pac::{self, Peripherals, TIMG0},
+    prelude::*,
+    timer::{Timer, Timer0},
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+/// Last sector of a 4 MB flash
+const SECTOR: u32 = 0x3ff;
+
+// Functions from internal ROM
+const ESP_ROM_SPIFLASH_UNLOCK: usize = 0x4000_0140;
+const ESP_ROM_SPIFLASH_ERASE_SECTOR: usize = 0x4000_0128;
+const CACHE_SUSPEND_ICACHE: usize = 0x4000_0524;
+const CACHE_RESUME_ICACHE: usize = 0x4000_0528;
+
+static TIMER0: Mutex<RefCell<Option<Timer<Timer0<TIMG0>>>>> = Mutex::new(RefCell::new(None));
+static LED: Mutex<RefCell<Option<Gpio5<Output<PushPull>>>>> = Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let mut timer0 = hal.timer_group0.timer0;
+    let led = hal.io.pins.gpio5.into_push_pull_output();
+    let mut delay = Delay::new(&hal.clocks);
+
+    // the handler of an interrupt is looked up when the interrupt is enabled,
+    // with `place-isr-in-ram` a handler in flash is reported in debug builds
+    interrupt::enable(pac::Interrupt::TG0_T0_LEVEL, interrupt::Priority::Priority1).unwrap();
+    timer0.start(100u64.micros());
+    timer0.listen();
+
+    critical_section::with(|cs| {
+        TIMER0.borrow_ref_mut(cs).replace(timer0);
+        LED.borrow_ref_mut(cs).replace(led);
+    });
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    loop {
+        let result = unsafe { erase_sector(SECTOR) };
+        println!("Erased sector {:#x}: {}", SECTOR, result);
+        delay.delay_ms(1_000u32);
+    }
+}
+
+/// Erase `sector` with the ROM functions, returns 0 on success
+///
+/// The cache is suspended meanwhile, so this function and everything it
+/// calls has to be in RAM or ROM.
+#[ram]
+unsafe fn erase_sector(sector: u32) -> i32 {
+    let cache_suspend_icache: fn() -> u32 = core::mem::transmute(CACHE_SUSPEND_ICACHE);
+    let cache_resume_icache: fn(u32) = core::mem::transmute(CACHE_RESUME_ICACHE);
+    let unlock: fn() -> i32 = core::mem::transmute(ESP_ROM_SPIFLASH_UNLOCK);
+    let erase_sector: fn(u32) -> i32 = core::mem::transmute(ESP_ROM_SPIFLASH_ERASE_SECTOR);
+
+    let autoload = cache_suspend_icache();
+    let mut result = unlock();
+    if result == 0 {
+        result = erase_sector(sector);
+    }
+    cache_resume_icache(autoload);
+
+    result
+}
+
+#[ram]
+#[interrupt]
+fn TG0_T0_LEVEL() {
+    critical_section::with(|cs| {
+        let mut timer0 = TIMER0.borrow_ref_mut(cs);
+        let timer0 = timer0.as_mut().unwrap();
+        // clears the interrupt and arms the alarm for the next period
+        timer0.wait().ok();
+
+        let mut led = LED.borrow_ref_mut(cs);
+        led.as_mut().unwrap().toggle().ok();
+    });
+}
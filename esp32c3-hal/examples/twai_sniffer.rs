@@ -0,0 +1,75 @@
+//! Prints every frame on a CAN bus with a timestamp
+//!
+//! The controller runs in listen-only mode, it never acknowledges frames nor
+//! sends error flags, so the bus isn't disturbed. Bus errors and lost frames
+//! are printed as well.
+//!
+//! Connect the RX pin of a CAN transceiver to GPIO3. GPIO2 is not driven,
+//! pull the TX pin of the transceiver high (recessive).
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    init,
+    pac::Peripherals,
+    prelude::*,
+    systimer::SystemTimer,
+    twai::{AlertFlags, BaudRate, Error, Id, Twai, TwaiMode},
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let mut twai = Twai::new(
+        peripherals.TWAI,
+        hal.io.pins.gpio2,
+        hal.io.pins.gpio3,
+        BaudRate::B500K,
+        TwaiMode::ListenOnly,
+        &mut hal.peripheral_clock_control,
+        &hal.clocks,
+    )
+    .unwrap();
+
+    loop {
+        let alerts = twai.read_alerts();
+        if alerts.contains(AlertFlags::ERROR_WARNING) {
+            println!("bus errors (REC {})", twai.receive_error_count());
+        }
+        if alerts.contains(AlertFlags::RX_OVERRUN) {
+            println!("frames lost");
+        }
+
+        match twai.receive() {
+            Ok(frame) => {
+                let micros = SystemTimer::now() * 1_000_000 / SystemTimer::TICKS_PER_SECOND;
+                let (id, kind) = match frame.id() {
+                    Id::Standard(id) => (id as u32, "std"),
+                    Id::Extended(id) => (id, "ext"),
+                };
+
+                if frame.is_remote() {
+                    println!("{:>12} {} {:08x} RTR [{}]", micros, kind, id, frame.dlc());
+                } else {
+                    println!(
+                        "{:>12} {} {:08x} [{}] {:02x?}",
+                        micros,
+                        kind,
+                        id,
+                        frame.dlc(),
+                        frame.data()
+                    );
+                }
+            }
+            Err(nb::Error::Other(Error::Overrun)) => println!("frames lost"),
+            Err(nb::Error::Other(error)) => println!("error: {:?}", error),
+            Err(nb::Error::WouldBlock) => {}
+        }
+    }
+}
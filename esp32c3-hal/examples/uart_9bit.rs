@@ -0,0 +1,165 @@
+//! Exchanges addressed frames on a 9-bit multiprocessor bus
+//!
+//! Flash this onto two boards, connect GPIO4 (TX) of each to GPIO5 (RX) of
+//! the other and connect their GND. The board with GPIO6 tied to GND is the
+//! master (address 0x01), the other one the node at address 0x10.
+//!
+//! A frame is an address byte with the 9th bit set, a length byte and the
+//! payload. Twice a second the master sends a request to node 0x10, which
+//! answers with the payload reversed, a request to node 0x11, which doesn't
+//! exist, and a broadcast to address 0xff. The node's UART drops the frames
+//! for node 0x11 in software, it only prints what was addressed to it.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    init,
+    pac::{Peripherals, UART1},
+    prelude::*,
+    serial::{
+        config::{Config, NineBitMode},
+        Error,
+        TxRxPins,
+    },
+    time::Deadline,
+    Delay,
+    Serial,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+const MASTER: u8 = 0x01;
+const NODE: u8 = 0x10;
+const ABSENT_NODE: u8 = 0x11;
+const BROADCAST: u8 = 0xff;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+
+    let role = hal.io.pins.gpio6.into_pull_up_input();
+    let master = role.is_low().unwrap();
+    let address = if master { MASTER } else { NODE };
+
+    let pins = TxRxPins::new_tx_rx(
+        hal.io.pins.gpio4.into_push_pull_output(),
+        hal.io.pins.gpio5.into_floating_input(),
+    );
+    let mut serial = Serial::new_with_config(
+        peripherals.UART1,
+        Some(Config::default().baudrate(115_200)),
+        Some(pins),
+        &hal.clocks,
+    );
+    serial.set_nine_bit_mode(Some(
+        NineBitMode::default().address(address).broadcast(BROADCAST),
+    ));
+
+    let mut delay = Delay::new(&hal.clocks);
+    let mut buffer = [0u8; 16];
+
+    println!("Address {:02x}, master {}", address, master);
+
+    if master {
+        let mut sequence = 0u8;
+
+        loop {
+            for node in [NODE, ABSENT_NODE] {
+                let request = [sequence, b'p', b'i', b'n', b'g'];
+                send(&mut serial, node, &request);
+
+                let deadline = Deadline::after(20u64.millis());
+                match receive(&mut serial, &mut buffer, deadline) {
+                    Ok((_, len)) => println!("{:02x} answered {:02x?}", node, &buffer[..len]),
+                    Err(error) => println!("{:02x} didn't answer: {:?}", node, error),
+                }
+            }
+
+            send(&mut serial, BROADCAST, &[sequence]);
+
+            sequence = sequence.wrapping_add(1);
+            delay.delay_ms(500u32);
+        }
+    } else {
+        loop {
+            let deadline = Deadline::after(1u64.secs());
+            let (to, len) = match receive(&mut serial, &mut buffer, deadline) {
+                Ok(frame) => frame,
+                Err(error) => {
+                    println!("Nothing received: {:?}", error);
+                    continue;
+                }
+            };
+            println!("Frame to {:02x}: {:02x?}", to, &buffer[..len]);
+
+            // broadcasts aren't answered, they'd all collide
+            if to == NODE {
+                buffer[..len].reverse();
+                send(&mut serial, MASTER, &buffer[..len]);
+            }
+        }
+    }
+}
+
+/// Send a frame to `address`
+fn send<P>(serial: &mut Serial<UART1, P>, address: u8, payload: &[u8]) {
+    serial.write_9bit(0x100 | address as u16).unwrap();
+    serial.write_9bit(payload.len() as u16).unwrap();
+    for &byte in payload {
+        serial.write_9bit(byte as u16).unwrap();
+    }
+    nb::block!(serial.flush_nb()).unwrap();
+}
+
+/// Receive the next frame for this node into `buffer`, returns the address it
+/// was sent to and the length of its payload
+fn receive<P>(
+    serial: &mut Serial<UART1, P>,
+    buffer: &mut [u8],
+    deadline: Deadline,
+) -> Result<(u8, usize), Error> {
+    let mut to = None;
+    let mut len = None;
+    let mut received = 0;
+
+    loop {
+        let data = match serial.read_9bit() {
+            Ok(data) => data,
+            Err(nb::Error::WouldBlock) if deadline.is_expired() => {
+                return Err(Error::Timeout {
+                    transferred: received,
+                });
+            }
+            Err(nb::Error::WouldBlock) => continue,
+            Err(nb::Error::Other(error)) => return Err(error),
+        };
+
+        // an address byte starts a new frame, even if the last one was cut
+        // short
+        if data & 0x100 != 0 {
+            to = Some(data as u8);
+            len = None;
+            received = 0;
+            continue;
+        }
+
+        match (to, len) {
+            (Some(_), None) => len = Some((data as usize).min(buffer.len())),
+            (Some(_), Some(_)) => {
+                buffer[received] = data as u8;
+                received += 1;
+            }
+            // data of a frame whose start was missed
+            (None, _) => {}
+        }
+
+        if let (Some(to), Some(len)) = (to, len) {
+            if received == len {
+                return Ok((to, len));
+            }
+        }
+    }
+}
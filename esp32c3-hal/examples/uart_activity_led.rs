@@ -0,0 +1,110 @@
+//! Blinks an LED on UART TX activity
+//!
+//! The following pins are used:
+//! - UART1 TX => GPIO1
+//! - UART1 RX => GPIO2
+//! - TX activity copy => GPIO3, leave unconnected
+//! - LED => GPIO4
+//!
+//! A burst of a few bytes is sent every second, taking about 3 ms at 115200
+//! baud. The LED lights up for 50 ms after each burst. The activity state is
+//! checked 30 ms after the burst, when the LED has to be on, and 80 ms after
+//! the burst, when it has to be off again.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use esp32c3_hal::{
+    gpio::{
+        activity_led::{ActivityLed, ActivitySource},
+        edge_counter,
+        Gpio3,
+        Gpio4,
+        OutputSignal,
+        Unknown,
+    },
+    init,
+    interrupt,
+    pac::{self, Peripherals, TIMG0},
+    prelude::*,
+    serial::{config::Config, TxRxPins},
+    timer::Timer0,
+    Delay,
+    Serial,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+type TxLed = ActivityLed<Gpio3<Unknown>, Gpio4<Unknown>, Timer0<TIMG0>>;
+
+static TX_LED: Mutex<RefCell<Option<TxLed>>> = Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+    let mut delay = Delay::new(&hal.clocks);
+
+    let pins = TxRxPins::new_tx_rx(
+        hal.io.pins.gpio1.into_push_pull_output(),
+        hal.io.pins.gpio2.into_floating_input(),
+    );
+    let mut serial1 = Serial::new_with_config(
+        peripherals.UART1,
+        Some(Config::default()),
+        Some(pins),
+        &hal.clocks,
+    );
+
+    let tx_led = ActivityLed::new(
+        ActivitySource::signal(OutputSignal::U1TXD, hal.io.pins.gpio3),
+        hal.io.pins.gpio4,
+        hal.timer_group0.timer0,
+        50u64.millis(),
+    );
+    critical_section::with(|cs| TX_LED.borrow_ref_mut(cs).replace(tx_led));
+
+    interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority3).unwrap();
+    interrupt::enable(pac::Interrupt::TG0_T0_LEVEL, interrupt::Priority::Priority1).unwrap();
+
+    unsafe {
+        riscv::interrupt::enable();
+    }
+
+    loop {
+        serial1.write_bytes(b"activity!\r\n").unwrap();
+
+        delay.delay_ms(30u32);
+        let on = is_active();
+        delay.delay_ms(50u32);
+        let off = !is_active();
+
+        println!(
+            "LED on after 30 ms: {}, off after 80 ms: {}",
+            if on { "OK" } else { "FAIL" },
+            if off { "OK" } else { "FAIL" }
+        );
+
+        delay.delay_ms(920u32);
+    }
+}
+
+fn is_active() -> bool {
+    critical_section::with(|cs| TX_LED.borrow_ref(cs).as_ref().unwrap().is_active())
+}
+
+#[interrupt]
+fn GPIO() {
+    edge_counter::handle_interrupt();
+}
+
+#[interrupt]
+fn TG0_T0_LEVEL() {
+    critical_section::with(|cs| {
+        TX_LED.borrow_ref_mut(cs).as_mut().unwrap().on_interrupt();
+    });
+}
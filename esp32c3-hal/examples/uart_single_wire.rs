@@ -0,0 +1,94 @@
+//! Shares a single-wire UART bus between several nodes
+//!
+//! Flash this onto two (or more) boards and connect their GPIO4 and GND. Every
+//! node sends a two byte frame (its ID and a sequence number) twice a second
+//! and prints the frames of the other nodes. When two nodes send at the same
+//! time the collision is detected and both retry after a random backoff.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    efuse::Efuse,
+    gpio::{Gpio4, Unknown},
+    init,
+    pac::Peripherals,
+    prelude::*,
+    serial::{config::Config, TxRxPins},
+    Delay,
+    Rng,
+    Serial,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use nb::block;
+use riscv_rt::entry;
+
+const MAX_ATTEMPTS: u32 = 8;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let mut serial1 = Serial::new_with_config::<TxRxPins<Gpio4<Unknown>, Gpio4<Unknown>>>(
+        peripherals.UART1,
+        Some(Config::default().baudrate(9600)),
+        None,
+        &hal.clocks,
+    );
+    serial1.single_wire_mode(&mut hal.io.pins.gpio4);
+
+    let mut rng = Rng::new(peripherals.RNG);
+    let mut delay = Delay::new(&hal.clocks);
+    let id = Efuse::get_mac_address()[5];
+    let mut sequence = 0u8;
+
+    println!("Node {:02x}", id);
+
+    loop {
+        // print the frames of the other nodes
+        while let Ok(byte) = serial1.read_nb() {
+            println!("Received {:02x}", byte);
+        }
+
+        let frame = [id, sequence];
+        let mut attempt = 0;
+        loop {
+            serial1.reset_collision_detected();
+            serial1.write_bytes(&frame).unwrap();
+            block!(serial1.flush_nb()).unwrap();
+
+            // the receiver sees our own frame as well, parts of it may be lost
+            // in a collision
+            delay.delay_ms(1u32);
+            let mut echo = [0u8; 2];
+            let mut echoed = 0;
+            while echoed < echo.len() {
+                match serial1.read_nb() {
+                    Ok(byte) => echo[echoed] = byte,
+                    Err(_) => break,
+                }
+                echoed += 1;
+            }
+
+            if !serial1.collision_detected() && echoed == echo.len() && echo == frame {
+                break;
+            }
+
+            attempt += 1;
+            if attempt == MAX_ATTEMPTS {
+                println!("Giving up on frame {}", sequence);
+                break;
+            }
+
+            // binary exponential backoff, in frame times (~2 ms at 9600 baud)
+            let slots = rng.random() % (1 << attempt.min(5));
+            println!("Collision, retrying in {} slots", slots);
+            delay.delay_ms(slots * 3);
+        }
+
+        sequence = sequence.wrapping_add(1);
+        delay.delay_ms(500u32);
+    }
+}
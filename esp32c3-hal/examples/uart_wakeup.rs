@@ -0,0 +1,62 @@
+//! Sleeps until the host starts talking on the console UART
+//!
+//! The chip goes into light sleep, UART0 wakes it up after three edges on
+//! the RX line. Type "hello" into a serial terminal connected to the
+//! console: the first character wakes the chip up and is lost, the bytes
+//! typed within the next two seconds are received and echoed. Then the chip
+//! goes back to sleep.
+//!
+//! The chip sleeps with `SleepConfig::max_savings()`: the RTC peripherals,
+//! the RTC fast memory, the XTAL and the 8M oscillator are powered down. The
+//! default configuration keeps the RTC peripherals and the RTC fast memory
+//! powered.
+
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+
+use esp32c3_hal::{
+    init,
+    pac::Peripherals,
+    prelude::*,
+    rtc_cntl::{SleepConfig, WakeReason},
+    time::Deadline,
+    Serial,
+};
+use esp_backtrace as _;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let mut serial = Serial::new_console(peripherals.UART0, &hal.clocks);
+    serial.enable_wakeup(3);
+
+    loop {
+        writeln!(serial, "Going to sleep").unwrap();
+        nb::block!(serial.flush_nb()).unwrap();
+
+        match hal
+            .rtc
+            .sleep_light_with_config(&SleepConfig::max_savings())
+            .unwrap()
+        {
+            Some(WakeReason::Uart(n)) => writeln!(serial, "Woken up by UART{}", n).unwrap(),
+            reason => writeln!(serial, "Woken up: {:?}", reason).unwrap(),
+        }
+
+        let deadline = Deadline::after(2u64.secs());
+        let mut received = 0;
+        while !deadline.is_expired() {
+            if let Ok(byte) = serial.read_nb() {
+                nb::block!(serial.write_nb(byte)).unwrap();
+                received += 1;
+            }
+        }
+
+        writeln!(serial, "\r\n{} bytes received", received).unwrap();
+    }
+}
@@ -0,0 +1,53 @@
+//! The application half of a supervised boot, see
+//! `watchdog_handover_bootloader`
+//!
+//! The RTC watchdog armed by the bootloader is kept by `init`, so the
+//! application is supervised from its first instruction on. Once it's up, it
+//! takes the watchdog over with its own timeout and feeds it from its main
+//! loop.
+//!
+//! The ESP-IDF bootloader hands over the same way if
+//! `CONFIG_BOOTLOADER_WDT_ENABLE` is set (the default, with a timeout of nine
+//! seconds), so this also runs behind it.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    init::{self, WatchdogConfig, WatchdogsConfig},
+    pac::Peripherals,
+    prelude::*,
+    rtc_cntl::watchdogs_status,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(
+        peripherals,
+        init::Config::default().watchdogs(WatchdogsConfig {
+            rwdt: WatchdogConfig::Keep,
+            ..WatchdogsConfig::default()
+        })
+    );
+    let mut delay = Delay::new(&hal.clocks);
+
+    let inherited = watchdogs_status();
+    println!("Inherited from the bootloader: {:?}", inherited.rwdt);
+    if !inherited.rwdt.enabled {
+        println!("The bootloader didn't hand over the RTC watchdog");
+    }
+
+    // from here on the application supervises itself
+    hal.rtc.rwdt.start(2u64.secs());
+
+    loop {
+        hal.rtc.rwdt.feed();
+        println!("Fed the RTC watchdog");
+        delay.delay_ms(1000u32);
+    }
+}
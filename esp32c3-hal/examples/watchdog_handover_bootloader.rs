@@ -0,0 +1,59 @@
+//! The bootloader half of a supervised boot, see `watchdog_handover_app`
+//!
+//! Runs as a direct boot image, started by the ROM with the RTC watchdog and
+//! the watchdog of timer group 0 armed in flash boot protection mode. Both
+//! are left armed by `init`, the one of timer group 0 is fed while the
+//! bootloader works (simulated by a few delays here, e.g. verifying the
+//! application image).
+//!
+//! Before the application is started, `hand_over_watchdogs` arms the RTC
+//! watchdog with the time the application gets to take it over, without a
+//! moment in which no watchdog is armed.
+//!
+//! This example doesn't load an application, so nothing takes the RTC
+//! watchdog over and it resets the chip after five seconds, which starts the
+//! bootloader again.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{
+    init::{self, WatchdogConfig, WatchdogsConfig},
+    pac::Peripherals,
+    prelude::*,
+    rtc_cntl::{hand_over_watchdogs, watchdogs_status},
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(
+        peripherals,
+        init::Config::default().watchdogs(WatchdogsConfig {
+            rwdt: WatchdogConfig::Keep,
+            timg0: WatchdogConfig::Keep,
+            timg1: WatchdogConfig::Disabled,
+            swd: false,
+        })
+    );
+    let mut delay = Delay::new(&hal.clocks);
+
+    println!("Armed by the ROM: {:?}", watchdogs_status());
+
+    for step in 0..10 {
+        println!("Verifying the application, step {}", step);
+        delay.delay_ms(100u32);
+        hal.timer_group0.wdt.feed_flashboot();
+    }
+
+    hand_over_watchdogs(&mut hal.rtc.rwdt, &mut hal.timer_group0.wdt, 5u64.secs());
+    println!("Handed over: {:?}", watchdogs_status());
+
+    // a real bootloader jumps to the entry point of the application here
+    println!("No application, waiting for the RTC watchdog");
+    loop {}
+}
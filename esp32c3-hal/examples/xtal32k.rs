@@ -0,0 +1,37 @@
+//! Switches the RTC slow clock to an external 32.768 kHz crystal
+//!
+//! Needs a crystal (with load capacitors) between GPIO0 and GPIO1. The
+//! measured frequency tells how far the crystal is off, in parts per million.
+
+#![no_std]
+#![no_main]
+
+use esp32c3_hal::{init, pac::Peripherals, prelude::*, rtc_cntl::Xtal32kMode};
+use esp_backtrace as _;
+use esp_println::println;
+use riscv_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    match hal.rtc.enable_external_32k(
+        Xtal32kMode::Crystal,
+        hal.io.pins.gpio0,
+        hal.io.pins.gpio1,
+        2000u32.millis(),
+    ) {
+        Ok(frequency) => {
+            let ppm = (frequency.raw() as i32 - 32768) * 1_000_000 / 32768;
+            println!(
+                "32 kHz crystal running at {} Hz ({} ppm)",
+                frequency.raw(),
+                ppm
+            );
+        }
+        Err(error) => println!("32 kHz crystal failed: {:?}", error),
+    }
+
+    loop {}
+}
@@ -8,24 +8,36 @@ pub use embedded_hal as ehal;
 #[doc(inline)]
 pub use esp_hal_common::{
     analog::adc::implementation as adc,
+    board_pins,
+    chip,
     clock,
+    coex,
     dma,
     dma::gdma,
+    ds,
     efuse,
     gpio,
+    hmac,
     i2c,
     i2s,
+    init,
     interrupt,
     ledc,
     macros,
+    mode,
+    one_wire,
     pac,
     prelude,
+    profiling,
     pulse_control,
+    rtc_cntl,
     serial,
     spi,
     system,
     systimer,
+    time,
     timer,
+    twai,
     utils,
     Cpu,
     Delay,
@@ -38,8 +50,14 @@ pub use esp_hal_common::{
     sha
 };
 
+#[cfg(feature = "stack-guard")]
+pub use esp_hal_common::debug;
 #[cfg(feature = "embassy")]
 pub use esp_hal_common::embassy;
+#[cfg(feature = "logger")]
+pub use esp_hal_common::logger;
+#[cfg(feature = "sdmmc")]
+pub use esp_hal_common::sd_spi;
 
 #[cfg(feature = "direct-boot")]
 use riscv_rt::pre_init;
@@ -119,9 +137,24 @@ extern "C" {
     static mut _irtc_fast_data: u32;
 }
 
-global_asm!(
+// The trap entry is placed in RAM with the `place-isr-in-ram` feature, so that
+// interrupts are taken while the flash cache is disabled
+#[cfg(not(feature = "place-isr-in-ram"))]
+macro_rules! trap_section {
+    () => {
+        ".section .trap, \"ax\""
+    };
+}
+#[cfg(feature = "place-isr-in-ram")]
+macro_rules! trap_section {
+    () => {
+        ".section .rwtext, \"ax\""
+    };
+}
+
+global_asm!(concat!(
+    trap_section!(),
     r#"
-.section .trap, "ax"
 .balign 0x100
 .global _vector_table_hal
 .type _vector_table_hal, @function
@@ -134,16 +167,16 @@ _vector_table_hal:
     j _start_trap_hal
     .endr
 "#
-);
+));
 
-global_asm!(
+global_asm!(concat!(
+    trap_section!(),
     r#"
     /*
     Trap entry point (_start_trap_hal)
     Saves registers and calls _start_trap_rust_hal,
     restores registers and then returns.
 */
-.section .trap, "ax"
 .global _start_trap_hal
 .option norelax
 .align 6
@@ -238,7 +271,7 @@ _start_trap_hal:
     mret
 
 "#
-);
+));
 
 #[cfg(feature = "mcu-boot")]
 #[link_section = ".entry_addr"]
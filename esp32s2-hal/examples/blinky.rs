@@ -1,19 +1,13 @@
 //! Blinks an LED
 //!
 //! This assumes that a LED is connected to the pin assigned to `led`. (GPIO4)
+//!
+//! The clocks, the watchdogs and `IO` are set up by `init!`.
 
 #![no_std]
 #![no_main]
 
-use esp32s2_hal::{
-    clock::ClockControl,
-    gpio::IO,
-    pac::Peripherals,
-    prelude::*,
-    timer::TimerGroup,
-    Delay,
-    Rtc,
-};
+use esp32s2_hal::{init, pac::Peripherals, prelude::*, Delay};
 use esp_backtrace as _;
 use xtensa_atomic_emulation_trap as _;
 use xtensa_lx_rt::entry;
@@ -21,26 +15,18 @@ use xtensa_lx_rt::entry;
 #[entry]
 fn main() -> ! {
     let peripherals = Peripherals::take().unwrap();
-    let system = peripherals.SYSTEM.split();
-    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
-
-    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
-    let mut wdt = timer_group0.wdt;
-    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
 
-    // Disable MWDT and RWDT (Watchdog) flash boot protection
-    wdt.disable();
-    rtc.rwdt.disable();
+    // Keep the boot default clocks and disable all watchdogs
+    let hal = init!(peripherals, init::Config::default());
 
     // Set GPIO4 as an output, and set its state high initially.
-    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
-    let mut led = io.pins.gpio4.into_push_pull_output();
+    let mut led = hal.io.pins.gpio4.into_push_pull_output();
 
     led.set_high().unwrap();
 
     // Initialize the Delay peripheral, and use it to toggle the LED state in a
     // loop.
-    let mut delay = Delay::new(&clocks);
+    let mut delay = Delay::new(&hal.clocks);
 
     loop {
         led.toggle().unwrap();
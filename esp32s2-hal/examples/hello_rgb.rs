@@ -53,7 +53,7 @@ fn main() -> ! {
 
     // We use one of the RMT channels to instantiate a `SmartLedsAdapter` which can
     // be used directly with all `smart_led` implementations
-    let mut led = <smartLedAdapter!(1)>::new(pulse.channel0, io.pins.gpio18);
+    let mut led = <smartLedAdapter!(1)>::new(pulse.channel0, io.pins.gpio18, &clocks).unwrap();
 
     // Initialize the Delay peripheral, and use it to toggle the LED state in a
     // loop.
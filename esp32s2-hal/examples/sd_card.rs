@@ -0,0 +1,175 @@
+//! Appends a line to `LOG.TXT` on a FAT formatted SD card every second.
+//!
+//! The card is connected in SPI mode:
+//! - SCLK => GPIO36
+//! - MISO => GPIO37
+//! - MOSI => GPIO35
+//! - CS   => GPIO34
+//!
+//! The card detect switch of the socket is connected to GPIO33 and pulls
+//! it low while a card is inserted. The card is (re-)initialized whenever it
+//! gets inserted.
+
+#![no_std]
+#![no_main]
+
+use core::cell::{Cell, RefCell};
+
+use critical_section::Mutex;
+use embedded_sdmmc::{BlockDevice, Controller, Mode, TimeSource, Timestamp, VolumeIdx};
+use esp32s2_hal::{
+    clock::ClockControl,
+    gpio::{Event, Gpio33, Input, PullUp, IO},
+    interrupt,
+    macros::ram,
+    pac::{self, Peripherals},
+    prelude::*,
+    sd_spi::SdSpi,
+    spi::{Spi, SpiMode},
+    timer::TimerGroup,
+    Delay,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_atomic_emulation_trap as _;
+use xtensa_lx_rt::entry;
+
+static CARD_DETECT: Mutex<RefCell<Option<Gpio33<Input<PullUp>>>>> = Mutex::new(RefCell::new(None));
+// Start out as changed so an already inserted card gets initialized
+static CARD_CHANGED: Mutex<Cell<bool>> = Mutex::new(Cell::new(true));
+
+// There is no RTC time available, use a fixed timestamp for the files
+struct Clock;
+
+impl TimeSource for Clock {
+    fn get_timestamp(&self) -> Timestamp {
+        Timestamp {
+            year_since_1970: 52,
+            zero_indexed_month: 0,
+            zero_indexed_day: 0,
+            hours: 0,
+            minutes: 0,
+            seconds: 0,
+        }
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+    let sclk = io.pins.gpio36;
+    let miso = io.pins.gpio37;
+    let mosi = io.pins.gpio35;
+    let cs = io.pins.gpio34.into_push_pull_output();
+
+    let mut card_detect = io.pins.gpio33.into_pull_up_input();
+    card_detect.listen(Event::AnyEdge);
+    critical_section::with(|cs| CARD_DETECT.borrow_ref_mut(cs).replace(card_detect));
+
+    interrupt::enable(pac::Interrupt::GPIO, interrupt::Priority::Priority2).unwrap();
+
+    // The chip select is driven by the SD card driver, the frequency is
+    // changed during initialization
+    let spi = Spi::new_no_cs(
+        peripherals.SPI2,
+        sclk,
+        mosi,
+        miso,
+        400u32.kHz(),
+        SpiMode::Mode0,
+        &mut system.peripheral_clock_control,
+        &clocks,
+    );
+
+    let mut controller = Controller::new(SdSpi::new(spi, cs), Clock);
+    let mut delay = Delay::new(&clocks);
+    let mut mounted = false;
+
+    loop {
+        if critical_section::with(|cs| CARD_CHANGED.borrow(cs).replace(false)) {
+            let inserted = critical_section::with(|cs| {
+                CARD_DETECT
+                    .borrow_ref(cs)
+                    .as_ref()
+                    .unwrap()
+                    .is_low()
+                    .unwrap()
+            });
+
+            mounted = false;
+            if inserted {
+                // give the contacts time to settle and the card time to power up
+                delay.delay_ms(100u32);
+
+                match controller.device().init(20u32.MHz(), &clocks) {
+                    Ok(card_type) => {
+                        println!("Card inserted: {:?}", card_type);
+                        mounted = true;
+                    }
+                    Err(err) => println!("Initializing the card failed: {:?}", err),
+                }
+            } else {
+                println!("Card removed");
+            }
+        }
+
+        if mounted {
+            match append(&mut controller, b"Hello from esp-hal!\r\n") {
+                Ok(length) => println!("LOG.TXT is now {} bytes long", length),
+                Err(err) => {
+                    println!("Appending to LOG.TXT failed: {:?}", err);
+                    mounted = false;
+                }
+            }
+        }
+
+        delay.delay_ms(1000u32);
+    }
+}
+
+fn append<D: BlockDevice>(
+    controller: &mut Controller<D, Clock>,
+    data: &[u8],
+) -> Result<u32, embedded_sdmmc::Error<D::Error>> {
+    let mut volume = controller.get_volume(VolumeIdx(0))?;
+    let root = controller.open_root_dir(&volume)?;
+
+    let result = controller
+        .open_file_in_dir(&mut volume, &root, "LOG.TXT", Mode::ReadWriteCreateOrAppend)
+        .and_then(|mut file| {
+            let written = controller.write(&mut volume, &mut file, data);
+            let length = file.length();
+            controller.close_file(&volume, file)?;
+            written.map(|_| length)
+        });
+
+    controller.close_dir(&volume, root);
+
+    result
+}
+
+#[ram]
+#[interrupt]
+fn GPIO() {
+    critical_section::with(|cs| {
+        CARD_DETECT
+            .borrow_ref_mut(cs)
+            .as_mut()
+            .unwrap()
+            .clear_interrupt();
+        CARD_CHANGED.borrow(cs).set(true);
+    });
+}
@@ -0,0 +1,154 @@
+//! Two SPI buses running concurrently using DMA
+//!
+//! SPI2 runs at 1 MHz in mode 0, SPI3 at 4 MHz in mode 3. Both
+//! transfers are started before waiting for either of them, so the two buses
+//! are active at the same time.
+//!
+//! Folowing pins are used:
+//!
+//!         SPI2    SPI3
+//! SCLK    GPIO36  GPIO6
+//! MISO    GPIO37  GPIO5
+//! MOSI    GPIO35  GPIO4
+//! CS      GPIO34  GPIO3
+//!
+//! Depending on your target and the board you are using you have to change the
+//! pins.
+//!
+//! Connect MISO and MOSI of each bus to see the outgoing data is read as
+//! incoming data.
+
+#![no_std]
+#![no_main]
+
+use esp32s2_hal::{
+    clock::ClockControl,
+    dma::DmaPriority,
+    gpio::IO,
+    pac::Peripherals,
+    pdma::Dma,
+    prelude::*,
+    spi::{Spi, SpiMode},
+    timer::TimerGroup,
+    Delay,
+    Rtc,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_atomic_emulation_trap as _;
+use xtensa_lx_rt::entry;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::boot_defaults(system.clock_control).freeze();
+
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    let dma = Dma::new(system.dma, &mut system.peripheral_clock_control);
+    let dma_channel2 = dma.spi2channel;
+    let dma_channel3 = dma.spi3channel;
+
+    let mut descriptors2 = [0u32; 8 * 3];
+    let mut rx_descriptors2 = [0u32; 8 * 3];
+    let mut descriptors3 = [0u32; 8 * 3];
+    let mut rx_descriptors3 = [0u32; 8 * 3];
+
+    let mut spi2 = Spi::new(
+        peripherals.SPI2,
+        io.pins.gpio36,
+        io.pins.gpio35,
+        io.pins.gpio37,
+        io.pins.gpio34,
+        1u32.MHz(),
+        SpiMode::Mode0,
+        &mut system.peripheral_clock_control,
+        &clocks,
+    )
+    .with_dma(dma_channel2.configure(
+        false,
+        &mut descriptors2,
+        &mut rx_descriptors2,
+        DmaPriority::Priority0,
+    ));
+
+    let mut spi3 = Spi::new(
+        peripherals.SPI3,
+        io.pins.gpio6,
+        io.pins.gpio4,
+        io.pins.gpio5,
+        io.pins.gpio3,
+        4u32.MHz(),
+        SpiMode::Mode3,
+        &mut system.peripheral_clock_control,
+        &clocks,
+    )
+    .with_dma(dma_channel3.configure(
+        false,
+        &mut descriptors3,
+        &mut rx_descriptors3,
+        DmaPriority::Priority0,
+    ));
+
+    let mut delay = Delay::new(&clocks);
+
+    // DMA buffer require a static life-time
+    let mut send2 = buffer1();
+    let mut receive2 = buffer2();
+    let mut send3 = buffer3();
+    let mut receive3 = buffer4();
+    let mut i = 0;
+
+    for (i, v) in send2.iter_mut().enumerate() {
+        *v = (i % 255) as u8;
+    }
+    for (i, v) in send3.iter_mut().enumerate() {
+        *v = 255 - (i % 255) as u8;
+    }
+
+    loop {
+        send2[0] = i;
+        send3[0] = i;
+        i = i.wrapping_add(1);
+
+        // start both transfers, then wait for them to complete
+        let transfer2 = spi2.dma_transfer(send2, receive2).unwrap();
+        let transfer3 = spi3.dma_transfer(send3, receive3).unwrap();
+
+        (receive2, send2, spi2) = transfer2.wait();
+        (receive3, send3, spi3) = transfer3.wait();
+
+        println!("SPI2 {:x?}", &receive2[..10]);
+        println!("SPI3 {:x?}", &receive3[..10]);
+
+        delay.delay_ms(250u32);
+    }
+}
+
+fn buffer1() -> &'static mut [u8; 4096] {
+    static mut BUFFER: [u8; 4096] = [0u8; 4096];
+    unsafe { &mut BUFFER }
+}
+
+fn buffer2() -> &'static mut [u8; 4096] {
+    static mut BUFFER: [u8; 4096] = [0u8; 4096];
+    unsafe { &mut BUFFER }
+}
+
+fn buffer3() -> &'static mut [u8; 4096] {
+    static mut BUFFER: [u8; 4096] = [0u8; 4096];
+    unsafe { &mut BUFFER }
+}
+
+fn buffer4() -> &'static mut [u8; 4096] {
+    static mut BUFFER: [u8; 4096] = [0u8; 4096];
+    unsafe { &mut BUFFER }
+}
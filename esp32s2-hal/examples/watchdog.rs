@@ -5,7 +5,14 @@
 #![no_std]
 #![no_main]
 
-use esp32s2_hal::{clock::ClockControl, pac::Peripherals, prelude::*, timer::TimerGroup, Rtc};
+use esp32s2_hal::{
+    clock::ClockControl,
+    pac::Peripherals,
+    prelude::*,
+    rtc_cntl::watchdogs_status,
+    timer::TimerGroup,
+    Rtc,
+};
 use esp_backtrace as _;
 use esp_println::println;
 use xtensa_atomic_emulation_trap as _;
@@ -28,6 +35,9 @@ fn main() -> ! {
 
     timer0.start(1u64.secs());
 
+    // Show which watchdogs are armed and how long until they bite
+    println!("{:?}", watchdogs_status());
+
     loop {
         wdt.feed();
         println!("Hello world!");
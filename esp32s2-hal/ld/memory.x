@@ -13,9 +13,7 @@ RESERVE_CACHES = 0x2000;
 
 VECTORS_SIZE = 0x400;
 
-/* reserved at the start of the RTC memories for use by the ULP processor */
-RESERVE_RTC_FAST = 0;
-RESERVE_RTC_SLOW = 0;
+INCLUDE "memory_extras.x"
 
 /* define stack size for both cores */
 STACK_SIZE = 8k;
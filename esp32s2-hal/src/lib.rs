@@ -5,25 +5,35 @@ pub use embedded_hal as ehal;
 pub use esp_hal_common::{
     analog::adc::implementation as adc,
     analog::dac::implementation as dac,
+    board_pins,
+    chip,
     clock,
+    coex,
     dma,
     dma::pdma,
     efuse,
     gpio,
     i2s,
     i2c::{self, I2C},
+    init,
     interrupt,
     ledc,
     macros,
+    mode,
+    one_wire,
     otg_fs,
     pac,
     prelude,
+    profiling,
     pulse_control,
+    rtc_cntl,
     serial,
     spi,
     system,
     systimer,
+    time,
     timer,
+    ulp,
     utils,
     Cpu,
     Delay,
@@ -35,14 +45,25 @@ pub use esp_hal_common::{
     sha
 };
 
+#[cfg(feature = "stack-guard")]
+pub use esp_hal_common::debug;
 #[cfg(feature = "embassy")]
 pub use esp_hal_common::embassy;
+#[cfg(feature = "logger")]
+pub use esp_hal_common::logger;
+#[cfg(feature = "sdmmc")]
+pub use esp_hal_common::sd_spi;
 
 pub use self::gpio::IO;
 
 /// Common module for analog functions
 pub mod analog {
-    pub use esp_hal_common::analog::{AvailableAnalog, SensExt};
+    pub use esp_hal_common::analog::{
+        calibration::{self_calibrate, CalibrationCurve, CalibrationError},
+        touch,
+        AvailableAnalog,
+        SensExt,
+    };
 }
 
 #[no_mangle]
@@ -29,6 +29,12 @@ fn main() {
         .write_all(include_bytes!("ld/linkall.x"))
         .unwrap();
 
+    let memory_extras = generate_memory_extras();
+    File::create(out.join("memory_extras.x"))
+        .unwrap()
+        .write_all(&memory_extras)
+        .unwrap();
+
     println!("cargo:rustc-link-search={}", out.display());
 
     // Only re-run the build script when memory.x is changed,
@@ -65,9 +71,33 @@ fn main() {
         .write_all(include_bytes!("ld/linkall.x"))
         .unwrap();
 
+    let memory_extras = generate_memory_extras();
+    File::create(out.join("memory_extras.x"))
+        .unwrap()
+        .write_all(&memory_extras)
+        .unwrap();
+
     println!("cargo:rustc-link-search={}", out.display());
 
     // Only re-run the build script when memory.x is changed,
     // instead of when any part of the source code changes.
     println!("cargo:rerun-if-changed=ld/memory.x");
 }
+
+fn generate_memory_extras() -> Vec<u8> {
+    let reserve_rtc_slow = if cfg!(feature = "ulp") {
+        "0x1000"
+    } else {
+        "0x0"
+    };
+
+    format!(
+        "
+    /* reserved at the start of the RTC memories for use by the ULP processor */
+    RESERVE_RTC_FAST = 0;
+    RESERVE_RTC_SLOW = {reserve_rtc_slow};
+    _rtc_slow_reserved_end = 0x50000000 + RESERVE_RTC_SLOW;"
+    )
+    .as_bytes()
+    .to_vec()
+}
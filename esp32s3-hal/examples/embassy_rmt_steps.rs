@@ -0,0 +1,160 @@
+//! Sends stepper motor step pulses asynchronously and counts them
+//!
+//! RMT channel 0 drives the step signal on GPIO4: an acceleration ramp of 200
+//! steps, which doesn't fit into the channel RAM and is refilled from the
+//! threshold interrupt, followed by a burst of exactly 1000 steps sent with
+//! the loop count of the channel. Connect GPIO4 to GPIO5, PCNT unit 0 counts
+//! the rising edges on GPIO5 and has to arrive at 1200 after every run.
+//!
+//! There is no PCNT driver yet, the unit is set up with its registers.
+
+#![no_std]
+#![no_main]
+#![feature(type_alias_impl_trait)]
+
+use embassy_executor::Executor;
+use embassy_time::{Duration, Timer};
+use esp32s3_hal::{
+    embassy,
+    gpio::{InputPin, InputSignal},
+    init,
+    interrupt,
+    pac::{self, Peripherals},
+    prelude::*,
+    pulse_control::{
+        asynch::{self, TransmitAsync},
+        ClockSource,
+        ConfiguredChannel0,
+        OutputChannel,
+        PulseCode,
+        RepeatMode,
+    },
+    system::Peripheral,
+    PulseControl,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use static_cell::StaticCell;
+
+const RAMP_STEPS: usize = 200;
+const BURST_STEPS: u16 = 1000;
+
+const PCNT_U0_CONF0: usize = 0x00;
+const PCNT_U0_CNT: usize = 0x30;
+const PCNT_CTRL: usize = 0x60;
+
+/// Channel 0 increments on rising edges and ignores its control input
+const CH0_POS_MODE_INCREMENT: u32 = 1 << 18;
+const CNT_RST_U0: u32 = 1 << 0;
+const CLK_EN: u32 = 1 << 16;
+
+/// A step pulse of `period_us`, the lengths are in ticks of the channel
+/// clock which runs at 1 MHz
+fn step(period_us: u32) -> u32 {
+    PulseCode {
+        level1: true,
+        length1: 5u32.nanos(),
+        level2: false,
+        length2: (period_us - 5).nanos(),
+    }
+    .into()
+}
+
+#[embassy_executor::task]
+async fn steps(mut channel: ConfiguredChannel0) {
+    // from 1 kHz to 10 kHz, the last entry is the end marker
+    let mut ramp = [0u32; RAMP_STEPS + 1];
+    for (i, entry) in ramp[..RAMP_STEPS].iter_mut().enumerate() {
+        *entry = step(1000 - 900 * i as u32 / RAMP_STEPS as u32);
+    }
+    let burst = [step(100), 0];
+
+    loop {
+        write_pcnt(PCNT_CTRL, read_pcnt(PCNT_CTRL) | CNT_RST_U0);
+        write_pcnt(PCNT_CTRL, read_pcnt(PCNT_CTRL) & !CNT_RST_U0);
+
+        channel
+            .transmit_async(RepeatMode::SingleShot, &ramp)
+            .await
+            .unwrap();
+        channel
+            .transmit_async(RepeatMode::RepeatNtimes(BURST_STEPS), &burst)
+            .await
+            .unwrap();
+
+        let count = read_pcnt(PCNT_U0_CNT) & 0xffff;
+        let expected = RAMP_STEPS as u32 + BURST_STEPS as u32;
+        println!(
+            "{} steps: {}",
+            count,
+            if count == expected { "PASS" } else { "FAIL" }
+        );
+
+        Timer::after(Duration::from_millis(1_000)).await;
+    }
+}
+
+#[interrupt]
+fn RMT() {
+    asynch::handle_interrupt();
+}
+
+static EXECUTOR: StaticCell<Executor> = StaticCell::new();
+
+#[xtensa_lx_rt::entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    #[cfg(feature = "embassy-time-systick")]
+    embassy::init(
+        &hal.clocks,
+        esp32s3_hal::systimer::SystemTimer::new(peripherals.SYSTIMER),
+    );
+
+    #[cfg(feature = "embassy-time-timg0")]
+    embassy::init(&hal.clocks, hal.timer_group0.timer0);
+
+    let pulse = PulseControl::new(
+        peripherals.RMT,
+        &mut hal.peripheral_clock_control,
+        ClockSource::APB,
+        0,
+        0,
+        0,
+    )
+    .unwrap();
+
+    let mut channel = pulse.channel0;
+    channel
+        .set_idle_output_level(false)
+        .set_carrier_modulation(false)
+        .set_channel_divider(80)
+        .set_idle_output(true);
+    let channel = channel.assign_pin(hal.io.pins.gpio4);
+
+    hal.io
+        .pins
+        .gpio5
+        .set_to_input()
+        .connect_input_to_peripheral(InputSignal::PCNT_SIG_CH0_0);
+
+    hal.peripheral_clock_control.enable(Peripheral::Pcnt);
+    write_pcnt(PCNT_CTRL, read_pcnt(PCNT_CTRL) | CLK_EN);
+    write_pcnt(PCNT_U0_CONF0, CH0_POS_MODE_INCREMENT);
+
+    interrupt::enable(pac::Interrupt::RMT, interrupt::Priority::Priority1).unwrap();
+
+    let executor = EXECUTOR.init(Executor::new());
+    executor.run(|spawner| {
+        spawner.spawn(steps(channel)).ok();
+    });
+}
+
+fn read_pcnt(offset: usize) -> u32 {
+    unsafe { ((pac::PCNT::PTR as usize + offset) as *const u32).read_volatile() }
+}
+
+fn write_pcnt(offset: usize, value: u32) {
+    unsafe { ((pac::PCNT::PTR as usize + offset) as *mut u32).write_volatile(value) }
+}
@@ -0,0 +1,55 @@
+//! Initializes the PSRAM and tests a 1 MiB buffer in it
+//!
+//! Needs a module with quad PSRAM (e.g. ESP32-S3-WROOM-1 N8R2), run with
+//! `--features psram`.
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{init, pac::Peripherals, psram};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+const BUFFER_WORDS: usize = 1024 * 1024 / 4;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let _hal = init!(peripherals, init::Config::default());
+
+    let psram = psram::init().unwrap();
+    println!(
+        "{} KiB of PSRAM at {:#x}",
+        psram.size() / 1024,
+        psram.start()
+    );
+
+    let buffer =
+        unsafe { core::slice::from_raw_parts_mut(psram.as_mut_ptr() as *mut u32, BUFFER_WORDS) };
+
+    let mut errors = 0;
+    for pattern in [0x0000_0000, 0xffff_ffff, 0xaaaa_aaaa, 0x5555_5555] {
+        buffer.fill(pattern);
+        errors += buffer.iter().filter(|word| **word != pattern).count();
+    }
+
+    // every word holds its own address, catches address lines stuck or
+    // shorted together
+    for (i, word) in buffer.iter_mut().enumerate() {
+        *word = i as u32;
+    }
+    errors += buffer
+        .iter()
+        .enumerate()
+        .filter(|(i, word)| **word != *i as u32)
+        .count();
+
+    if errors == 0 {
+        println!("PASS");
+    } else {
+        println!("FAIL: {} bad words", errors);
+    }
+
+    loop {}
+}
@@ -5,6 +5,7 @@
 #![no_main]
 
 use esp32s3_hal::{
+    chip,
     clock::ClockControl,
     efuse::Efuse,
     pac::Peripherals,
@@ -29,6 +30,8 @@ fn main() -> ! {
     // Disable MWDT and RWDT (Watchdog) flash boot protection
     wdt.disable();
     rtc.rwdt.disable();
+    println!("Chip {}", chip::info());
+    println!("Features {:?}", chip::features());
     println!("MAC address {:02x?}", Efuse::get_mac_address());
     println!("Flash Encryption {:?}", Efuse::get_flash_encryption());
 
@@ -0,0 +1,118 @@
+//! Drives four WS2812 strips in parallel with synchronized RMT channels
+//!
+//! Every frame tick (30 per second) one frame is sent to all four strips, the
+//! channels are started together by an `RmtSyncGroup` so the rows of e.g. an
+//! LED matrix panel are updated at the same time. A dot runs along the
+//! strips, shifted by one LED per row.
+//!
+//! Connect the data inputs of the strips (8 LEDs each) to GPIO4, GPIO5, GPIO6
+//! and GPIO7. With a scope on two of the pins the first edges of a frame are
+//! in the same RMT clock cycle.
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{
+    init,
+    pac::Peripherals,
+    prelude::*,
+    pulse_control::{ClockSource, OutputChannel, RepeatMode, RmtSyncGroup},
+    systimer::SystemTimer,
+    PulseControl,
+};
+use esp_backtrace as _;
+use xtensa_lx_rt::entry;
+
+const LEDS: usize = 8;
+const FRAME_TICKS: u64 = SystemTimer::TICKS_PER_SECOND / 30;
+
+// One pulse code per bit and the end marker
+const SEQUENCE_LEN: usize = LEDS * 24 + 1;
+
+// WS2812 bit timings in 80 MHz RMT clock cycles
+const T0H: u32 = 32;
+const T0L: u32 = 68;
+const T1H: u32 = 64;
+const T1L: u32 = 36;
+
+fn pulse(high: u32, low: u32) -> u32 {
+    high | 1 << 15 | low << 16
+}
+
+/// Encode GRB colors into raw pulse codes
+fn encode(colors: &[[u8; 3]; LEDS], sequence: &mut [u32; SEQUENCE_LEN]) {
+    let mut index = 0;
+    for byte in colors.iter().flatten() {
+        for bit in (0..8).rev() {
+            sequence[index] = if byte & (1 << bit) != 0 {
+                pulse(T1H, T1L)
+            } else {
+                pulse(T0H, T0L)
+            };
+            index += 1;
+        }
+    }
+    sequence[index] = 0;
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    let pulse = PulseControl::new(
+        peripherals.RMT,
+        &mut hal.peripheral_clock_control,
+        ClockSource::APB,
+        0,
+        0,
+        0,
+    )
+    .unwrap();
+
+    let mut channel0 = pulse.channel0;
+    let mut channel1 = pulse.channel1;
+    let mut channel2 = pulse.channel2;
+    let mut channel3 = pulse.channel3;
+    setup(&mut channel0);
+    setup(&mut channel1);
+    setup(&mut channel2);
+    setup(&mut channel3);
+
+    let mut strip0 = channel0.assign_pin(hal.io.pins.gpio4);
+    let mut strip1 = channel1.assign_pin(hal.io.pins.gpio5);
+    let mut strip2 = channel2.assign_pin(hal.io.pins.gpio6);
+    let mut strip3 = channel3.assign_pin(hal.io.pins.gpio7);
+
+    let mut group = RmtSyncGroup::new([&mut strip0, &mut strip1, &mut strip2, &mut strip3]);
+
+    let mut sequences = [[0u32; SEQUENCE_LEN]; 4];
+    let mut position = 0;
+    let mut next_frame = SystemTimer::now();
+
+    loop {
+        for (row, sequence) in sequences.iter_mut().enumerate() {
+            let mut colors = [[0u8; 3]; LEDS];
+            colors[(position + row) % LEDS] = [0, 32, 8];
+            encode(&colors, sequence);
+        }
+        position = (position + 1) % LEDS;
+
+        // Wait for the shared frame tick
+        next_frame += FRAME_TICKS;
+        while SystemTimer::now() < next_frame {}
+
+        let [row0, row1, row2, row3] = &sequences;
+        group
+            .send_pulse_sequences_raw(RepeatMode::SingleShot, [row0, row1, row2, row3])
+            .unwrap();
+    }
+}
+
+fn setup<C>(channel: &mut impl OutputChannel<C>) {
+    channel
+        .set_idle_output_level(false)
+        .set_carrier_modulation(false)
+        .set_channel_divider(1)
+        .set_idle_output(true);
+}
@@ -0,0 +1,57 @@
+//! Catches a stack overflow with the stack guard
+//!
+//! The example recurses deeper and deeper, each level keeps 64 bytes on the
+//! stack. Without the guard the last round would overwrite the heap and the
+//! static data below the stack and crash somewhere, with it the panic handler
+//! prints
+//!
+//! ```text
+//! stack overflow detected at 0x42004f2c, sp 0x3fceb0a0 (guard 0x3fceb080)
+//! ```
+//!
+//! and a backtrace through `recurse`.
+//!
+//! Run with `cargo run --example stack_guard --features stack-guard`.
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{debug, init, pac::Peripherals, prelude::*, Delay};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+/// Stack left to the exception and the panic handler below the guard
+const MARGIN: usize = 2048;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let hal = init!(peripherals, init::Config::default());
+    let mut delay = Delay::new(&hal.clocks);
+
+    debug::enable_stack_guard(MARGIN);
+
+    for depth in [10, 100, 1_000_000] {
+        println!("recursing {} levels", depth);
+        println!("sum {}", recurse(depth));
+        delay.delay_ms(500u32);
+    }
+
+    unreachable!()
+}
+
+/// Keep 64 bytes on the stack for each of `depth` levels
+#[inline(never)]
+fn recurse(depth: u32) -> u32 {
+    let mut frame = [0u8; 64];
+    // volatile, so the frame is really written and isn't optimized away
+    unsafe { core::ptr::write_volatile(&mut frame, [depth as u8; 64]) };
+    let frame = unsafe { core::ptr::read_volatile(&frame) };
+
+    if depth == 0 {
+        0
+    } else {
+        recurse(depth - 1) + frame[63] as u32
+    }
+}
@@ -0,0 +1,134 @@
+//! Touch buttons behind the front panel of a sealed enclosure
+//!
+//! The following pads are used:
+//! - guard ring around the buttons => GPIO1 (TOUCH1)
+//! - power button => GPIO2 (TOUCH2)
+//! - up button => GPIO3 (TOUCH3)
+//! - down button => GPIO4 (TOUCH4)
+//! - shield electrode between the buttons and the ring => GPIO14 (TOUCH14)
+//!
+//! Water on the panel covers the guard ring, which locks the buttons until
+//! it's wiped off. After 30 seconds without a touch the chip goes into deep
+//! sleep, the power button wakes it up again.
+//!
+//! The thresholds depend on the electrodes and the panel, the example prints
+//! the benchmarks at start to pick them: roughly 2% of the benchmark of a
+//! button for a finger on 2 mm of plastic.
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{
+    analog::touch::{
+        Denoise,
+        DenoiseResolution,
+        DriftCompensation,
+        FilterMode,
+        GuardRing,
+        Touch,
+        TouchConfig,
+    },
+    init,
+    pac::Peripherals,
+    prelude::*,
+    rtc_cntl::SleepConfig,
+    Delay,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+const BUTTON_THRESHOLD: u32 = 1500;
+const POWER_THRESHOLD: u32 = 2000;
+const GUARD_THRESHOLD: u32 = 1000;
+
+/// 10 ms polls
+const IDLE_POLLS: u32 = 30 * 100;
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+    let mut delay = Delay::new(&hal.clocks);
+
+    println!(
+        "wake reason {:?}, {} deep sleeps",
+        hal.rtc.wake_reason(),
+        hal.rtc.sleep_count()
+    );
+
+    let config = TouchConfig {
+        denoise: Some(Denoise {
+            resolution: DenoiseResolution::Bit12,
+            capacitance: 4,
+        }),
+        waterproof: Some(GuardRing {
+            pad: 1,
+            shield_drive: 2,
+        }),
+        sleep_channel: Some((2, POWER_THRESHOLD)),
+        drift: Some(DriftCompensation {
+            filter: FilterMode::Iir16,
+            // once a minute
+            rebenchmark_interval: 60 * 100,
+        }),
+    };
+
+    let analog = peripherals.SENS.split();
+    let mut touch = Touch::new(analog.touch, config).unwrap();
+
+    let pins = hal.io.pins;
+    let guard = pins.gpio1.into_analog();
+    let power = pins.gpio2.into_analog();
+    let up = pins.gpio3.into_analog();
+    let down = pins.gpio4.into_analog();
+    // driven by the touch controller
+    let _shield = pins.gpio14.into_analog();
+
+    touch.enable_pad(&guard, GUARD_THRESHOLD).unwrap();
+    touch.enable_pad(&power, POWER_THRESHOLD).unwrap();
+    touch.enable_pad(&up, BUTTON_THRESHOLD).unwrap();
+    touch.enable_pad(&down, BUTTON_THRESHOLD).unwrap();
+    touch.enable_wakeup(true).unwrap();
+
+    // let the filter settle
+    delay.delay_ms(500u32);
+    println!(
+        "benchmarks: guard {} power {} up {} down {}, noise {}",
+        touch.benchmark(&guard),
+        touch.benchmark(&power),
+        touch.benchmark(&up),
+        touch.benchmark(&down),
+        touch.denoise_data()
+    );
+
+    let mut last = 0;
+    let mut idle = 0;
+    loop {
+        let active = touch.poll();
+
+        if active != last {
+            if touch.is_touched(&guard) {
+                println!("water on the panel, buttons locked");
+            } else {
+                println!(
+                    "power {} up {} down {}",
+                    touch.is_touched(&power),
+                    touch.is_touched(&up),
+                    touch.is_touched(&down)
+                );
+            }
+            last = active;
+        }
+
+        idle = if active == 0 { idle + 1 } else { 0 };
+        if idle >= IDLE_POLLS {
+            println!("going to sleep, touch the power button to wake up");
+            let error = hal.rtc.sleep_deep(&SleepConfig::default()).unwrap_err();
+            println!("can't sleep: {:?}", error);
+            idle = 0;
+        }
+
+        delay.delay_ms(10u32);
+    }
+}
@@ -0,0 +1,96 @@
+//! Wakes the main CPU from deep sleep with the ULP-FSM.
+//!
+//! The ULP runs every 100 ms and counts its runs in RTC slow memory. After 10
+//! runs it wakes up the main CPU, which prints the reason and the count,
+//! restarts the ULP and goes back to sleep, so the chip wakes about once a
+//! second.
+//!
+//! The chip sleeps with `SleepConfig::ulp_monitoring()`: only the RTC
+//! peripherals, the RTC slow memory and the 8M oscillator stay on.
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{
+    init,
+    pac::Peripherals,
+    prelude::*,
+    rtc_cntl::SleepConfig,
+    ulp::UlpSharedMemory,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+// Variables of the ULP program, in bytes from the start of RTC slow memory
+const COUNT: usize = 36;
+const THRESHOLD: usize = 40;
+
+// Prebuilt ULP-FSM binary (ESP-IDF format), assembled from:
+//
+//         .bss
+//     count:     .long 0
+//     threshold: .long 0
+//
+//         .text
+//     entry:
+//         move r3, count
+//         ld r1, r3, 0
+//         add r1, r1, 1
+//         st r1, r3, 0                      // count += 1
+//         ld r2, r3, 4
+//         sub r0, r1, r2                    // overflows while count <
+// threshold         jump done, ov
+//         wake
+//     done:
+//         halt
+//
+// These instructions are encoded the same way on the ESP32, ESP32-S2 and
+// ESP32-S3, the ESP32-S2/S3 store the whole register with `st`.
+#[rustfmt::skip]
+static ULP_PROGRAM: [u8; 48] = [
+    // magic, text offset, text size, data size, bss size
+    0x75, 0x6c, 0x70, 0x00, 0x0c, 0x00, 0x24, 0x00, 0x00, 0x00, 0x08, 0x00,
+    0x93, 0x00, 0x80, 0x72, // move r3, count
+    0x0d, 0x00, 0x00, 0xd0, // ld r1, r3, 0
+    0x15, 0x00, 0x00, 0x72, // add r1, r1, 1
+    0x0d, 0x00, 0x00, 0x68, // st r1, r3, 0
+    0x0e, 0x04, 0x00, 0xd0, // ld r2, r3, 4
+    0x24, 0x00, 0x20, 0x70, // sub r0, r1, r2
+    0x20, 0x00, 0x80, 0x80, // jump done, ov
+    0x01, 0x00, 0x00, 0x90, // wake
+    0x00, 0x00, 0x00, 0xb0, // halt
+];
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    // stop the ULP before reading the count, it kept running while the chip
+    // booted
+    hal.rtc.ulp.stop();
+
+    // After power-on the RTC memory contents are random
+    if let Some(reason) = hal.rtc.wake_reason() {
+        let count = UlpSharedMemory::<u32>::at(COUNT).unwrap();
+        println!("{:?} after {} ULP runs", reason, count.read() & 0xffff);
+    }
+
+    hal.rtc.ulp.load_binary(0, &ULP_PROGRAM).unwrap();
+    UlpSharedMemory::<u32>::at(THRESHOLD).unwrap().write(10);
+
+    hal.rtc
+        .ulp
+        .set_wakeup_period(0, 100_000u64.micros())
+        .unwrap();
+    hal.rtc.ulp.start_fsm(0).unwrap();
+
+    println!("Going to deep sleep");
+    let error = hal
+        .rtc
+        .ulp
+        .sleep_until_wakeup_with_config(&SleepConfig::ulp_monitoring())
+        .unwrap_err();
+    panic!("Invalid sleep configuration: {:?}", error);
+}
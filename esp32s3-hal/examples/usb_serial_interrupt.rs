@@ -0,0 +1,114 @@
+//! CDC-ACM serial port example driven by the USB interrupt.
+//!
+//! The bus is polled in the `USB` interrupt, the main loop is free for other
+//! work. Received data is echoed back in upper case.
+//!
+//! This example should be built in release mode.
+
+#![no_std]
+#![no_main]
+
+use core::cell::RefCell;
+
+use critical_section::Mutex;
+use esp32s3_hal::{
+    clock::{ClockControl, CpuClock},
+    gpio::{Gpio18, Gpio19, Gpio20, Unknown},
+    interrupt,
+    otg_fs::{UsbBus, USB},
+    pac::{self, Peripherals},
+    prelude::*,
+    timer::TimerGroup,
+    Rtc,
+    IO,
+};
+use esp_backtrace as _;
+use usb_device::{
+    class_prelude::UsbBusAllocator,
+    prelude::{UsbDevice, UsbDeviceBuilder, UsbVidPid},
+};
+use usbd_serial::SerialPort;
+use xtensa_lx_rt::entry;
+
+type Bus = UsbBus<USB<Gpio18<Unknown>, Gpio19<Unknown>, Gpio20<Unknown>>>;
+
+static mut EP_MEMORY: [u32; 1024] = [0; 1024];
+static mut USB_BUS: Option<UsbBusAllocator<Bus>> = None;
+
+static USB_DEVICE: Mutex<RefCell<Option<UsbDevice<Bus>>>> = Mutex::new(RefCell::new(None));
+static SERIAL: Mutex<RefCell<Option<SerialPort<Bus>>>> = Mutex::new(RefCell::new(None));
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut system = peripherals.SYSTEM.split();
+    let clocks = ClockControl::configure(system.clock_control, CpuClock::Clock240MHz).freeze();
+
+    let timer_group0 = TimerGroup::new(peripherals.TIMG0, &clocks);
+    let mut wdt = timer_group0.wdt;
+    let mut rtc = Rtc::new(peripherals.RTC_CNTL);
+
+    // Disable MWDT and RWDT (Watchdog) flash boot protection
+    wdt.disable();
+    rtc.rwdt.disable();
+
+    let io = IO::new(peripherals.GPIO, peripherals.IO_MUX);
+
+    let usb = USB::new(
+        peripherals.USB0,
+        io.pins.gpio18,
+        io.pins.gpio19,
+        io.pins.gpio20,
+        &mut system.peripheral_clock_control,
+    );
+
+    // The allocator has to outlive the device and the class, which are moved
+    // into statics for the interrupt handler
+    let usb_bus = unsafe {
+        USB_BUS = Some(UsbBus::new(usb, &mut EP_MEMORY));
+        USB_BUS.as_ref().unwrap()
+    };
+
+    let serial = SerialPort::new(usb_bus);
+    let usb_dev = UsbDeviceBuilder::new(usb_bus, UsbVidPid(0x16c0, 0x27dd))
+        .manufacturer("esp-hal")
+        .product("esp-hal")
+        .serial_number("12345678")
+        .device_class(usbd_serial::USB_CLASS_CDC)
+        .build();
+
+    critical_section::with(|cs| {
+        USB_DEVICE.borrow_ref_mut(cs).replace(usb_dev);
+        SERIAL.borrow_ref_mut(cs).replace(serial);
+    });
+
+    interrupt::enable(pac::Interrupt::USB, interrupt::Priority::Priority1).unwrap();
+
+    loop {}
+}
+
+#[interrupt]
+fn USB() {
+    critical_section::with(|cs| {
+        let mut usb_dev = USB_DEVICE.borrow_ref_mut(cs);
+        let mut serial = SERIAL.borrow_ref_mut(cs);
+        let usb_dev = usb_dev.as_mut().unwrap();
+        let serial = serial.as_mut().unwrap();
+
+        if !usb_dev.poll(&mut [serial]) {
+            return;
+        }
+
+        let mut buf = [0u8; 64];
+        if let Ok(count) = serial.read(&mut buf) {
+            // Echo back in upper case
+            for c in buf[0..count].iter_mut() {
+                c.make_ascii_uppercase();
+            }
+
+            // Data which doesn't fit into the endpoint buffer is dropped, a
+            // real application would queue it until the next interrupt
+            serial.write(&buf[0..count]).ok();
+        }
+    });
+}
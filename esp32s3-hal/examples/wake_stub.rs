@@ -0,0 +1,91 @@
+//! Blink an LED from a deep sleep wake stub
+//!
+//! The chip sleeps in 500 ms steps. On each wake the stub in RTC fast memory
+//! toggles GPIO4 and goes back to sleep right away, without booting the
+//! application; every tenth wake it boots and the application prints the
+//! number of wakes.
+//!
+//! GPIO4 is driven by the RTC IO, which keeps the pad configured through
+//! deep sleep. Watch it with a scope: the pulses of the supply current
+//! around each edge are the time the chip is awake for the stub.
+
+#![no_std]
+#![no_main]
+
+use esp32s3_hal::{
+    init,
+    macros::ram,
+    pac::{self, Peripherals},
+    prelude::*,
+    reset::{self, WakePeriod},
+    rtc_cntl::SleepConfig,
+};
+use esp_backtrace as _;
+use esp_println::println;
+use xtensa_lx_rt::entry;
+
+/// Wakes between two boots of the application
+const WAKES_PER_BOOT: u32 = 10;
+
+/// RTC GPIO4, the RTC IO output bits start at bit 10
+const LED: u32 = 1 << (10 + 4);
+
+/// Wakes handled by the stub since the last boot, kept through the boots
+#[ram(rtc_fast, uninitialized)]
+static mut WAKES: u32 = 0;
+
+#[ram(rtc_fast)]
+static mut PERIOD: Option<WakePeriod> = None;
+
+#[ram(rtc_fast)]
+fn stub() -> ! {
+    let rtcio = unsafe { &*pac::RTC_IO::PTR };
+
+    // NOTE(unsafe) nothing else runs while the stub does
+    let wakes = unsafe {
+        WAKES += 1;
+        WAKES
+    };
+
+    if wakes % 2 == 0 {
+        rtcio.rtc_gpio_out_w1tc.write(|w| unsafe { w.bits(LED) });
+    } else {
+        rtcio.rtc_gpio_out_w1ts.write(|w| unsafe { w.bits(LED) });
+    }
+
+    match unsafe { PERIOD } {
+        Some(period) if wakes % WAKES_PER_BOOT != 0 => reset::stub_sleep_for(period),
+        _ => reset::stub_continue_boot(),
+    }
+}
+
+#[entry]
+fn main() -> ! {
+    let peripherals = Peripherals::take().unwrap();
+    let mut hal = init!(peripherals, init::Config::default());
+
+    match hal.rtc.wake_reason() {
+        None => unsafe { WAKES = 0 },
+        reason => println!("{:?}, {} wakes", reason, unsafe { WAKES }),
+    }
+
+    // GPIO4 to the RTC IO as an output, the digital GPIO matrix is powered
+    // down in deep sleep
+    let rtcio = unsafe { &*pac::RTC_IO::PTR };
+    rtcio
+        .touch_pad4
+        .modify(|_, w| unsafe { w.mux_sel().set_bit().fun_sel().bits(0) });
+    rtcio.rtc_gpio_enable_w1ts.write(|w| unsafe { w.bits(LED) });
+
+    let period = WakePeriod::new(500u64.millis());
+    unsafe { PERIOD = Some(period) };
+    reset::set_wake_deep_sleep_stub(stub).unwrap();
+
+    let error = hal
+        .rtc
+        .sleep_deep_for(&SleepConfig::default(), period)
+        .unwrap_err();
+    println!("can't sleep: {:?}", error);
+
+    loop {}
+}
@@ -4,9 +4,7 @@ ENTRY(ESP32Reset)
 /* reserved for ICACHE */
 RESERVE_ICACHE = 0x8000;
 
-/* reserved at the start of the RTC memories for use by the ULP processor */
-RESERVE_RTC_FAST = 0;
-RESERVE_RTC_SLOW = 0;
+INCLUDE "memory_extras.x"
 
 /* define stack size for both cores */
 STACK_SIZE = 8k;
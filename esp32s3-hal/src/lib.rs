@@ -6,26 +6,40 @@ pub use embedded_hal as ehal;
 #[doc(inline)]
 pub use esp_hal_common::{
     analog::adc::implementation as adc,
+    board_pins,
+    chip,
     clock,
+    coex,
     cpu_control::CpuControl,
     dma::{self, gdma},
+    ds,
     efuse,
+    get_core,
     gpio,
+    hmac,
     i2c,
     i2s,
+    init,
     interrupt,
     ledc,
     macros,
     mcpwm,
+    mode,
+    one_wire,
     otg_fs,
     pac,
     prelude,
+    profiling,
     pulse_control,
+    reset,
+    rtc_cntl,
     serial,
     spi,
     system,
     systimer,
+    time,
     timer,
+    ulp,
     utils,
     Cpu,
     Delay,
@@ -38,14 +52,22 @@ pub use esp_hal_common::{
     sha
 };
 
+#[cfg(feature = "stack-guard")]
+pub use esp_hal_common::debug;
 #[cfg(feature = "embassy")]
 pub use esp_hal_common::embassy;
+#[cfg(feature = "psram")]
+pub use esp_hal_common::psram;
+#[cfg(feature = "logger")]
+pub use esp_hal_common::logger;
+#[cfg(feature = "sdmmc")]
+pub use esp_hal_common::sd_spi;
 
 pub use self::gpio::IO;
 
 /// Common module for analog functions
 pub mod analog {
-    pub use esp_hal_common::analog::{AvailableAnalog, SensExt};
+    pub use esp_hal_common::analog::{touch, AvailableAnalog, SensExt};
 }
 
 #[no_mangle]